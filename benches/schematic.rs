@@ -0,0 +1,176 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use mc_schem::bench_fixtures;
+use mc_schem::{
+    LitematicaLoadOption, LitematicaSaveOption, Schematic, VanillaStructureLoadOption,
+    VanillaStructureSaveOption, WorldEdit13LoadOption, WorldEdit13SaveOption,
+};
+
+const SIZE: i32 = 64;
+const PALETTE_SIZES: [usize; 2] = [16, 1024];
+
+fn fixtures() -> Vec<(usize, Schematic)> {
+    return PALETTE_SIZES
+        .iter()
+        .map(|p| (*p, bench_fixtures::cube(SIZE, *p)))
+        .collect();
+}
+
+fn bench_litematica(c: &mut Criterion) {
+    let mut group = c.benchmark_group("litematica");
+    for (palette_size, schem) in fixtures() {
+        let mut bytes = Vec::new();
+        schem
+            .save_litematica_writer(&mut bytes, &LitematicaSaveOption::default())
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("save", palette_size), &schem, |b, s| {
+            b.iter(|| {
+                let mut dest = Vec::with_capacity(bytes.len());
+                s.save_litematica_writer(&mut dest, &LitematicaSaveOption::default())
+                    .unwrap();
+                dest
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("load", palette_size),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    Schematic::from_litematica_bytes(
+                        black_box(bytes),
+                        &LitematicaLoadOption::default(),
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_world_edit_13(c: &mut Criterion) {
+    let mut group = c.benchmark_group("world_edit_13");
+    for (palette_size, schem) in fixtures() {
+        let mut bytes = Vec::new();
+        schem
+            .save_world_edit_13_writer(&mut bytes, &WorldEdit13SaveOption::default())
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("save", palette_size), &schem, |b, s| {
+            b.iter(|| {
+                let mut dest = Vec::with_capacity(bytes.len());
+                s.save_world_edit_13_writer(&mut dest, &WorldEdit13SaveOption::default())
+                    .unwrap();
+                dest
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("load", palette_size),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    Schematic::from_world_edit_13_bytes(
+                        black_box(bytes),
+                        &WorldEdit13LoadOption::default(),
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_vanilla_structure(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vanilla_structure");
+    group.sample_size(10);
+    for (palette_size, schem) in fixtures() {
+        let mut bytes = Vec::new();
+        schem
+            .save_vanilla_structure_writer(&mut bytes, &VanillaStructureSaveOption::default())
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new("save", palette_size), &schem, |b, s| {
+            b.iter(|| {
+                let mut dest = Vec::with_capacity(bytes.len());
+                s.save_vanilla_structure_writer(&mut dest, &VanillaStructureSaveOption::default())
+                    .unwrap();
+                dest
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("load", palette_size),
+            &bytes,
+            |b, bytes| {
+                b.iter(|| {
+                    Schematic::from_vanilla_structure_bytes(
+                        black_box(bytes),
+                        &VanillaStructureLoadOption::default(),
+                    )
+                    .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_transform(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform");
+    for palette_size in PALETTE_SIZES {
+        let schem = bench_fixtures::synthetic_schematic(&bench_fixtures::FixtureOption {
+            shape: [32, 64, 64],
+            palette_size,
+            region_count: 2,
+            ..Default::default()
+        });
+        group.bench_with_input(
+            BenchmarkId::new("full_palette", palette_size),
+            &schem,
+            |b, s| b.iter(|| s.full_palette().0.len()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("merge_regions", palette_size),
+            &schem,
+            |b, s| {
+                b.iter(|| {
+                    let mut s = s.clone();
+                    s.merge_regions(&mc_schem::Block::air());
+                    s
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("scale_2", palette_size), &schem, |b, s| {
+            b.iter(|| {
+                let mut s = s.clone();
+                s.scale(2).unwrap();
+                s
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_litematica,
+    bench_world_edit_13,
+    bench_vanilla_structure,
+    bench_transform
+);
+criterion_main!(benches);
@@ -437,6 +437,9 @@ MC_SCHEM_EXPORT void MC_SCHEM_swap_error(MC_SCHEM_error *a, MC_SCHEM_error *b);
 
 MC_SCHEM_EXPORT void MC_SCHEM_error_to_string(const MC_SCHEM_error *, char *dest, size_t capacity, size_t *length);
 
+// Stable numeric code of error, 0 is reserved for no error
+MC_SCHEM_EXPORT uint16_t MC_SCHEM_error_get_code(const MC_SCHEM_error *);
+
 MC_SCHEM_EXPORT MC_SCHEM_error_box MC_SCHEM_error_test_none();
 
 MC_SCHEM_EXPORT MC_SCHEM_error_box MC_SCHEM_error_test_some();
@@ -617,10 +620,37 @@ typedef struct {
 
 MC_SCHEM_writer MC_SCHEM_writer_wrap_stream(FILE *f);
 
+typedef enum : uint8_t {
+  MC_SCHEM_compress_method_gzip = 0,
+  MC_SCHEM_compress_method_zlib = 1,
+  MC_SCHEM_compress_method_uncompressed = 2,
+  MC_SCHEM_compress_method_zstd = 3,
+} MC_SCHEM_compress_method;
+
+typedef enum : uint8_t {
+  MC_SCHEM_air_policy_omit = 0,
+  MC_SCHEM_air_policy_include = 1,
+} MC_SCHEM_air_policy;
+
+typedef enum : uint8_t {
+  MC_SCHEM_palette_order_insertion = 0,
+  MC_SCHEM_palette_order_sorted = 1,
+} MC_SCHEM_palette_order;
+
 typedef struct {
   alignas(512) uint32_t compress_level;
   bool rename_duplicated_regions;
-  //uint8_t reserved[507];
+  // 0 means auto
+  uint8_t bits_per_block;
+  bool verify_block_states;
+  MC_SCHEM_compress_method compress_method;
+  // 0 means keeping data version of schematic
+  int32_t target_data_version;
+  // 0 means keeping litematica version of schematic
+  int32_t target_version;
+  // only decides whether TotalBlocks counts air
+  MC_SCHEM_air_policy air_policy;
+  MC_SCHEM_palette_order palette_order;
 } MC_SCHEM_save_option_litematica;
 static_assert(sizeof(MC_SCHEM_save_option_litematica) == 512, "sizeof(MC_SCHEM_save_option_litematica) should be 512");
 MC_SCHEM_EXPORT MC_SCHEM_save_option_litematica MC_SCHEM_save_option_litematica_default();
@@ -635,10 +665,22 @@ MC_SCHEM_schem_save_litematica_file(const MC_SCHEM_schematic *, MC_SCHEM_string_
 typedef struct {
   alignas(512) uint32_t compress_level;
   bool keep_air;
-  //uint8_t reserved[507];
+  MC_SCHEM_compress_method compress_method;
+  // all zero means no limit
+  int32_t max_size[3];
+  bool split_oversized;
+  // 0 means keeping data version of schematic
+  int32_t target_data_version;
+  bool keep_unmapped_metadata;
+  bool fill_block_entity_id;
+  // 0 means no limit
+  uint64_t max_file_size;
+  MC_SCHEM_palette_order palette_order;
 } MC_SCHEM_save_option_vanilla_structure;
 static_assert(sizeof(MC_SCHEM_save_option_vanilla_structure)==512,"sizeof(MC_SCHEM_save_option_vanilla_structure) should be 512");
 MC_SCHEM_EXPORT MC_SCHEM_save_option_vanilla_structure MC_SCHEM_save_option_vanilla_structure_default();
+// Preset for schematics of Create mod
+MC_SCHEM_EXPORT MC_SCHEM_save_option_vanilla_structure MC_SCHEM_save_option_vanilla_structure_create_mod();
 MC_SCHEM_EXPORT MC_SCHEM_error_box
 MC_SCHEM_schem_save_vanilla_structure(const MC_SCHEM_schematic *, MC_SCHEM_writer writer,
                                       const MC_SCHEM_save_option_vanilla_structure *option);
@@ -650,6 +692,13 @@ MC_SCHEM_schem_save_vanilla_structure_file(const MC_SCHEM_schematic *, MC_SCHEM_
 typedef struct {
   alignas(512) uint32_t compress_level;
   MC_SCHEM_common_block background_block;
+  // 0 means selecting by data version
+  int32_t schem_version;
+  MC_SCHEM_compress_method compress_method;
+  // 0 means keeping data version of schematic
+  int32_t target_data_version;
+  bool keep_unmapped_metadata;
+  MC_SCHEM_palette_order palette_order;
 } MC_SCHEM_save_option_world_edit_13;
 static_assert(sizeof(MC_SCHEM_save_option_world_edit_13) == 512,
               "sizeof(MC_SCHEM_save_option_world_edit_13) should be 512");
@@ -727,6 +776,11 @@ MC_SCHEM_EXPORT MC_SCHEM_array3_i32 MC_SCHEM_schem_get_shape(const MC_SCHEM_sche
 MC_SCHEM_EXPORT uint64_t MC_SCHEM_schem_get_volume(const MC_SCHEM_schematic*);
 MC_SCHEM_EXPORT uint64_t MC_SCHEM_schem_get_total_blocks(const MC_SCHEM_schematic*,bool include_air);
 
+typedef void (*MC_SCHEM_material_list_callback)(void *handle, MC_SCHEM_string_view id, uint64_t count);
+// Call fun for each kind of material, sorted by id
+MC_SCHEM_EXPORT void MC_SCHEM_schem_get_material_list(const MC_SCHEM_schematic *, bool full_block_state, bool include_air,
+                                                      MC_SCHEM_material_list_callback fun, void *handle);
+
 
 #ifdef __cplusplus
 }
@@ -27,6 +27,7 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 #include <format>
 #include <functional>
 #include <istream>
+#include <map>
 #include <memory>
 #include <optional>
 #include <span>
@@ -303,6 +304,10 @@ namespace mc_schem {
         this->to_string(result);
         return result;
       }
+
+      [[nodiscard]] uint16_t code() const noexcept {
+        return MC_SCHEM_error_get_code(this->handle);
+      }
     };
 
     using error_box = box<error, MC_SCHEM_error_box>;
@@ -330,6 +335,10 @@ namespace mc_schem {
       : error{detail::error_box{std::move(box)}} {
       assert(this->content->unwrap_handle() != nullptr);
     }
+
+    [[nodiscard]] uint16_t code() const noexcept {
+      return this->content->code();
+    }
   };
 
   class rust_string : public detail::wrapper<MC_SCHEM_string *> {
@@ -1443,6 +1452,23 @@ namespace mc_schem {
     structure_void = 1,
   };
 
+  enum class compress_method : uint8_t {
+    gzip = 0,
+    zlib = 1,
+    uncompressed = 2,
+    zstd = 3,
+  };
+
+  enum class air_policy : uint8_t {
+    omit = 0,
+    include = 1,
+  };
+
+  enum class palette_order : uint8_t {
+    insertion = 0,
+    sorted = 1,
+  };
+
   struct litematica_load_option {
     using c_type = MC_SCHEM_load_option_litematica;
     static_assert(sizeof(c_type) == 512);
@@ -1464,10 +1490,28 @@ namespace mc_schem {
 
     uint32_t compress_level;
     bool rename_duplicated_regions;
+    // 0 means auto
+    uint8_t bits_per_block;
+    bool verify_block_states;
+    compress_method method;
+    // 0 means keeping data version of schematic
+    int32_t target_data_version;
+    // 0 means keeping litematica version of schematic
+    int32_t target_version;
+    // only decides whether TotalBlocks counts air
+    air_policy air;
+    palette_order order;
 
     explicit litematica_save_option(const c_type &src)
       : compress_level{src.compress_level},
-        rename_duplicated_regions{src.rename_duplicated_regions} {}
+        rename_duplicated_regions{src.rename_duplicated_regions},
+        bits_per_block{src.bits_per_block},
+        verify_block_states{src.verify_block_states},
+        method{static_cast<compress_method>(src.compress_method)},
+        target_data_version{src.target_data_version},
+        target_version{src.target_version},
+        air{static_cast<air_policy>(src.air_policy)},
+        order{static_cast<palette_order>(src.palette_order)} {}
 
     litematica_save_option()
       : litematica_save_option{MC_SCHEM_save_option_litematica_default()} {}
@@ -1476,6 +1520,13 @@ namespace mc_schem {
       return c_type{
         this->compress_level,
         this->rename_duplicated_regions,
+        this->bits_per_block,
+        this->verify_block_states,
+        static_cast<MC_SCHEM_compress_method>(this->method),
+        this->target_data_version,
+        this->target_version,
+        static_cast<MC_SCHEM_air_policy>(this->air),
+        static_cast<MC_SCHEM_palette_order>(this->order),
       };
     }
   };
@@ -1504,18 +1555,51 @@ namespace mc_schem {
 
     uint32_t compress_level;
     bool keep_air;
+    compress_method method;
+    // all zero means no limit
+    std::array<int32_t, 3> max_size;
+    bool split_oversized;
+    // 0 means keeping data version of schematic
+    int32_t target_data_version;
+    bool keep_unmapped_metadata;
+    bool fill_block_entity_id;
+    // 0 means no limit
+    uint64_t max_file_size;
+    palette_order order;
 
     explicit vanilla_structure_save_option(const c_type &src)
-      : compress_level{src.compress_level}, keep_air{src.keep_air} {}
+      : compress_level{src.compress_level}, keep_air{src.keep_air},
+        method{static_cast<compress_method>(src.compress_method)},
+        max_size{src.max_size[0], src.max_size[1], src.max_size[2]},
+        split_oversized{src.split_oversized},
+        target_data_version{src.target_data_version},
+        keep_unmapped_metadata{src.keep_unmapped_metadata},
+        fill_block_entity_id{src.fill_block_entity_id},
+        max_file_size{src.max_file_size},
+        order{static_cast<palette_order>(src.palette_order)} {}
 
     vanilla_structure_save_option()
       : vanilla_structure_save_option{
           MC_SCHEM_save_option_vanilla_structure_default()} {}
 
+    // Preset for schematics of Create mod
+    [[nodiscard]] static vanilla_structure_save_option create_mod() noexcept {
+      return vanilla_structure_save_option{
+        MC_SCHEM_save_option_vanilla_structure_create_mod()};
+    }
+
     [[nodiscard]] c_type to_c_type() const noexcept {
       return c_type{
         this->compress_level,
         this->keep_air,
+        static_cast<MC_SCHEM_compress_method>(this->method),
+        {this->max_size[0], this->max_size[1], this->max_size[2]},
+        this->split_oversized,
+        this->target_data_version,
+        this->keep_unmapped_metadata,
+        this->fill_block_entity_id,
+        this->max_file_size,
+        static_cast<MC_SCHEM_palette_order>(this->order),
       };
     }
   };
@@ -1542,10 +1626,22 @@ namespace mc_schem {
 
     uint32_t compress_level;
     common_block background_block;
+    // 0 means selecting by data version
+    int32_t schem_version;
+    compress_method method;
+    // 0 means keeping data version of schematic
+    int32_t target_data_version;
+    bool keep_unmapped_metadata;
+    palette_order order;
 
     explicit world_edit_13_save_option(const c_type &src)
       : compress_level{src.compress_level},
-        background_block{static_cast<common_block>(src.background_block)} {}
+        background_block{static_cast<common_block>(src.background_block)},
+        schem_version{src.schem_version},
+        method{static_cast<compress_method>(src.compress_method)},
+        target_data_version{src.target_data_version},
+        keep_unmapped_metadata{src.keep_unmapped_metadata},
+        order{static_cast<palette_order>(src.palette_order)} {}
 
     world_edit_13_save_option()
       : world_edit_13_save_option{
@@ -1555,6 +1651,11 @@ namespace mc_schem {
       return c_type{
         this->compress_level,
         static_cast<MC_SCHEM_common_block>(this->background_block),
+        this->schem_version,
+        static_cast<MC_SCHEM_compress_method>(this->method),
+        this->target_data_version,
+        this->keep_unmapped_metadata,
+        static_cast<MC_SCHEM_palette_order>(this->order),
       };
     }
   };
@@ -2144,6 +2245,20 @@ namespace mc_schem {
     [[nodiscard]] uint64_t total_blocks(bool include_air) const noexcept {
       return MC_SCHEM_schem_get_total_blocks(this->handle, include_air);
     }
+
+    [[nodiscard]] std::map<std::string, uint64_t, std::less<>> material_list(
+      bool full_block_state, bool include_air) const noexcept {
+      std::map<std::string, uint64_t, std::less<>> result;
+      auto fun = [](void *handle, MC_SCHEM_string_view id, uint64_t count) {
+        auto &dest =
+          *reinterpret_cast<std::map<std::string, uint64_t, std::less<>> *>(
+            handle);
+        dest.emplace(detail::string_view_schem_to_std(id), count);
+      };
+      MC_SCHEM_schem_get_material_list(this->handle, full_block_state,
+                                       include_air, fun, &result);
+      return result;
+    }
   };
 
 }  // namespace mc_schem
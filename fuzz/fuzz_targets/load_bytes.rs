@@ -0,0 +1,19 @@
+//! Arbitrary bytes, compressed or not, fed into every `from_*_bytes` loader.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mc_schem::schem::VanillaStructureLoadOption;
+use mc_schem::{
+    LitematicaLoadOption, Schematic, WorldEdit12LoadOption, WorldEdit13LoadOption,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Schematic::from_bytes_detect_format(data, None);
+    let _ = Schematic::from_litematica_bytes(data, &LitematicaLoadOption::default());
+    let _ = Schematic::from_world_edit_13_bytes(data, &WorldEdit13LoadOption::default());
+    let _ = Schematic::from_world_edit_12_bytes(data, &WorldEdit12LoadOption::default());
+    let mut option = VanillaStructureLoadOption::default();
+    option.max_volume = 1 << 24;
+    let _ = Schematic::from_vanilla_structure_bytes(data, &option);
+    let _ = Schematic::from_axiom_bytes(data);
+});
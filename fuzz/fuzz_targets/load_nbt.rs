@@ -0,0 +1,23 @@
+//! Input is uncompressed nbt, so mutations of a seed corpus of decompressed schematic files
+//! change tags rather than breaking compression. Every valid compound is fed into every
+//! `from_*_nbt` loader.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mc_schem::schem::{common, VanillaStructureLoadOption};
+use mc_schem::{
+    LitematicaLoadOption, Schematic, WorldEdit12LoadOption, WorldEdit13LoadOption,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(nbt) = common::parse_nbt_compound(data) else {
+        return;
+    };
+    let _ = Schematic::from_litematica_nbt(nbt.clone(), &LitematicaLoadOption::default());
+    let _ = Schematic::from_world_edit_13_nbt(nbt.clone(), &WorldEdit13LoadOption::default());
+    let _ = Schematic::from_world_edit_12_nbt(nbt.clone(), &WorldEdit12LoadOption::default());
+    let mut option = VanillaStructureLoadOption::default();
+    option.max_volume = 1 << 24;
+    let _ = Schematic::from_vanilla_structure_nbt(nbt.clone(), &option);
+    let _ = Schematic::from_nbt_axiom(nbt);
+});
@@ -0,0 +1,14 @@
+//! Text parsers: block ids, block matchers and snbt schematics.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mc_schem::{Block, BlockMatcher, Schematic};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = Block::from_id(text);
+    let _ = BlockMatcher::from_id(text);
+    let _ = Schematic::from_snbt(text);
+});
@@ -0,0 +1,112 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Synthetic schematics to measure performance of loading, saving and transforming. The content
+//! only depends on the option, so results are comparable across releases.
+
+use crate::block::Block;
+use crate::region::Region;
+use crate::schem::pattern::SplitMix64;
+use crate::schem::Schematic;
+use ndarray::Array3;
+
+/// Option of generating a synthetic schematic
+#[derive(Debug, Clone)]
+pub struct FixtureOption {
+    /// Shape of every region in xyz
+    pub shape: [i32; 3],
+    /// Kinds of blocks in every region, including air. Must be in `[1, 65536]`
+    pub palette_size: usize,
+    /// Count of regions, placed side by side along the x axis
+    pub region_count: usize,
+    /// Probability that a block is air, in `[0, 1]`
+    pub air_ratio: f64,
+    /// Seed of the pseudo-random block distribution
+    pub seed: u64,
+}
+
+impl Default for FixtureOption {
+    fn default() -> Self {
+        return FixtureOption {
+            shape: [64, 64, 64],
+            palette_size: 16,
+            region_count: 1,
+            air_ratio: 0.25,
+            seed: 0,
+        };
+    }
+}
+
+/// Generate a schematic by `option`. Blocks other than air are `bench:block_<i>`, every 4th
+/// of which has a property, so palettes look like modded ones.
+///
+/// # Panics
+/// If `palette_size` is not in `[1, 65536]`, or any dimension of `shape` is not positive.
+pub fn synthetic_schematic(option: &FixtureOption) -> Schematic {
+    assert!(option.palette_size >= 1 && option.palette_size <= 65536);
+    assert!(option.shape.iter().all(|d| *d > 0));
+    let mut schem = Schematic::new();
+    for reg_idx in 0..option.region_count {
+        let mut region = synthetic_region(option, reg_idx as u64);
+        region.name = format!("Region{reg_idx}");
+        region.offset = [reg_idx as i32 * option.shape[0], 0, 0];
+        schem.regions.push(region);
+    }
+    return schem;
+}
+
+/// A single region cube with `size`³ blocks and `palette_size` kinds of blocks
+pub fn cube(size: i32, palette_size: usize) -> Schematic {
+    return synthetic_schematic(&FixtureOption {
+        shape: [size, size, size],
+        palette_size,
+        ..Default::default()
+    });
+}
+
+fn synthetic_region(option: &FixtureOption, salt: u64) -> Region {
+    let mut region = Region::with_shape(option.shape);
+    region.palette = Vec::with_capacity(option.palette_size);
+    region.palette.push(Block::air());
+    for idx in 1..option.palette_size {
+        let mut blk = Block::empty_block();
+        blk.namespace = "bench".to_string();
+        blk.id = format!("block_{idx}");
+        if idx % 4 == 0 {
+            blk.attributes
+                .insert("variant".to_string(), (idx % 7).to_string());
+        }
+        region.palette.push(blk);
+    }
+
+    let mut rng = SplitMix64(option.seed ^ salt.wrapping_mul(0x9E3779B97F4A7C15));
+    let air_threshold = (option.air_ratio.clamp(0.0, 1.0) * u32::MAX as f64) as u64;
+    let non_air = option.palette_size as u64 - 1;
+    let shape = option.shape;
+    region.array_yzx = Array3::from_shape_simple_fn(
+        (shape[1] as usize, shape[2] as usize, shape[0] as usize),
+        || {
+            let r = rng.next();
+            if non_air == 0 || (r & 0xFFFFFFFF) < air_threshold {
+                return 0;
+            }
+            return ((r >> 32) % non_air + 1) as u16;
+        },
+    );
+    return region;
+}
@@ -20,6 +20,9 @@ use chrono::DateTime;
 use clap::{command, Parser, Subcommand};
 use mc_schem::schem;
 use mc_schem::schem::{RawMetaData, Schematic};
+use mc_schem::{MaterialListOption, SchemFormat};
+use std::path::Path;
+use std::time::Duration;
 
 /// Read, write, convert minecraft schematic files via different versions
 #[derive(Parser, Debug)]
@@ -32,7 +35,8 @@ struct Args {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Get information about a schematic
-    See {
+    #[command(alias = "see")]
+    Info {
         /// Schematic file to load
         #[arg()]
         file: String,
@@ -58,82 +62,182 @@ enum Commands {
         #[arg(long, default_value_t = false)]
         savable_formats: bool,
     },
-    /// Convert schematic via different formats
+    /// Convert schematic via different formats. Input format is detected from content, and
+    /// output format is deduced from extension.
     Convert {
-        /// Input file
-        #[arg()]
-        input: String,
+        /// Input files
+        #[arg(required = true)]
+        inputs: Vec<String>,
 
-        /// Output file
+        /// Output file, used only if there is one input
         #[arg(short, long, default_value_t = String::from("out.litematic"))]
         output: String,
 
+        /// Convert every input to this format, the output is placed besides input with extension
+        /// replaced. For example, `--to .schem`
+        #[arg(long)]
+        to: Option<String>,
+
         /// Record time used
         #[arg(long, default_value_t = false)]
         benchmark: bool,
+
+        /// Replace structure void with air, so the whole area is cleared when pasted
+        #[arg(long, default_value_t = false, conflicts_with = "air_to_void")]
+        void_to_air: bool,
+
+        /// Replace air with structure void, so existing blocks are kept when pasted
+        #[arg(long, default_value_t = false)]
+        air_to_void: bool,
+    },
+    /// Count blocks in a schematic
+    MaterialList {
+        /// Schematic file to load
+        #[arg()]
+        file: String,
+        /// Distinguish blocks by full block state, not only id
+        #[arg(long, default_value_t = false)]
+        full_block_state: bool,
+        /// Count air
+        #[arg(long, default_value_t = false)]
+        include_air: bool,
+        /// Print counts as shulker boxes and stacks
+        #[arg(long, default_value_t = false)]
+        stacks: bool,
+    },
+    /// Compare two schematics. Exits with 0 if they are same, otherwise 1.
+    Diff {
+        /// The former schematic
+        #[arg()]
+        before: String,
+        /// The latter schematic
+        #[arg()]
+        after: String,
+        /// Print every changed block
+        #[arg(long, default_value_t = false)]
+        blocks: bool,
     },
 }
 
+fn load_or_exit(filename: &str) -> (Schematic, RawMetaData) {
+    return match Schematic::from_file_detect_format(filename) {
+        Ok((schem, raw, _)) => (schem, raw),
+        Err(e) => {
+            eprintln!("Failed to load {}: {e}", filename);
+            std::process::exit(1);
+        }
+    };
+}
+
+/// Returns time spent loading and saving, or `None` if failed
+fn convert_one(
+    input: &str,
+    output: &str,
+    option: &mc_schem::ConvertOption,
+) -> Option<(Duration, Duration)> {
+    return match mc_schem::convert_file_with_option(input, output, option) {
+        Ok(report) => {
+            println!(
+                "{input} -> {output}: {} to {}, {} blocks",
+                report.input_format, report.output_format, report.total_blocks
+            );
+            for w in &report.warnings {
+                eprintln!("Warning: {w}");
+            }
+            Some((report.load_time, report.save_time))
+        }
+        Err(e) => {
+            eprintln!("Failed to convert {input} to {output}: {e}");
+            None
+        }
+    };
+}
+
+fn block_to_string(blk: &Option<mc_schem::Block>) -> String {
+    return match blk {
+        Some(b) => b.full_id(),
+        None => String::from("(none)"),
+    };
+}
+
 fn main() {
     let args = Args::parse();
 
     match args.command {
         Commands::Convert {
-            input,
+            inputs,
             output,
+            to,
             benchmark,
+            void_to_air,
+            air_to_void,
         } => {
-            let begin_time = std::time::SystemTime::now();
-            let schem = match Schematic::from_file(&input) {
-                Ok(s) => s.0,
-                Err(e) => {
-                    eprintln!("Failed to load {}: {e}", input);
-                    std::process::exit(1);
-                }
+            let option = mc_schem::ConvertOption {
+                void_policy: if void_to_air {
+                    mc_schem::VoidPolicy::VoidToAir
+                } else if air_to_void {
+                    mc_schem::VoidPolicy::AirToVoid
+                } else {
+                    mc_schem::VoidPolicy::Keep
+                },
             };
+            let mut outputs = Vec::with_capacity(inputs.len());
+            match to {
+                Some(extension) => {
+                    let extension = extension.trim_start_matches('.');
+                    if SchemFormat::from_filename(&format!(".{extension}")).is_none() {
+                        eprintln!("Unknown format \"{extension}\"");
+                        std::process::exit(2);
+                    }
+                    for input in &inputs {
+                        let path = Path::new(input);
+                        let stem = path.file_stem().unwrap_or(path.as_os_str());
+                        let mut file_name = stem.to_os_string();
+                        file_name.push(format!(".{extension}"));
+                        outputs.push(path.with_file_name(file_name).to_string_lossy().to_string());
+                    }
+                }
+                None => {
+                    if inputs.len() > 1 {
+                        eprintln!("Multiple inputs requires --to to decide output format");
+                        std::process::exit(2);
+                    }
+                    outputs.push(output);
+                }
+            }
 
-            let parsed_time = std::time::SystemTime::now();
-
-            match schem.save_to_file(&output) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!("Failed to save {}: {e}", output);
-                    std::process::exit(2);
+            let mut failed = 0;
+            let mut load_time = Duration::ZERO;
+            let mut save_time = Duration::ZERO;
+            for (input, output) in inputs.iter().zip(&outputs) {
+                match convert_one(input, output, &option) {
+                    Some((load, save)) => {
+                        load_time += load;
+                        save_time += save;
+                    }
+                    None => failed += 1,
                 }
             }
 
-            let finish_time = std::time::SystemTime::now();
             if benchmark {
-                let load_time = parsed_time
-                    .duration_since(begin_time)
-                    .expect("Failed to compute time cost.");
-                let save_time = finish_time
-                    .duration_since(parsed_time)
-                    .expect("Failed to compute time cost.");
-                let total_time = finish_time
-                    .duration_since(begin_time)
-                    .expect("Failed to compute time cost.");
-                print!(
+                println!(
                     "Loading cost {} seconds, saving cost {} seconds, {} seconds in total.",
                     load_time.as_secs_f64(),
                     save_time.as_secs_f64(),
-                    total_time.as_secs_f64()
+                    (load_time + save_time).as_secs_f64()
                 );
             }
+            if failed > 0 {
+                std::process::exit(1);
+            }
         }
-        Commands::See {
+        Commands::Info {
             file,
             mut all,
             size,
             metadata,
         } => {
-            let (schematic, raw) = match Schematic::from_file(&file) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("Failed to load {}: {e}", file);
-                    std::process::exit(1);
-                }
-            };
+            let (schematic, raw) = load_or_exit(&file);
             if !all {
                 all = !(size || metadata);
             }
@@ -182,6 +286,9 @@ fn main() {
                             "\tEnclosingSize: {}",
                             schem::common::format_size(&raw.enclosing_size)
                         );
+                        if let Some(preview) = &raw.preview {
+                            println!("\tPreview: {}x{}", preview.width, preview.height);
+                        }
                     }
                     RawMetaData::VanillaStructure(raw) => {
                         println!("\tDataVersion: {}", raw.data_version);
@@ -221,6 +328,15 @@ fn main() {
                             println!("\tWEOrigin{}: {}", dim_letters[dim], raw.we_origin[dim]);
                         }
                     }
+                    RawMetaData::Axiom(raw) => {
+                        println!("\tDataVersion: {}", raw.data_version);
+                        println!("\tName: {}", raw.name);
+                        println!("\tAuthor: {}", raw.author);
+                        println!("\tTags: {}", raw.tags.join(", "));
+                        println!("\tBlockCount: {}", raw.block_count);
+                        println!("\tContainsAir: {}", raw.contains_air);
+                        println!("\tThumbnail: {} bytes", raw.thumbnail.len());
+                    }
                 }
             }
         }
@@ -248,5 +364,52 @@ fn main() {
                 }
             }
         }
+        Commands::MaterialList {
+            file,
+            full_block_state,
+            include_air,
+            stacks,
+        } => {
+            let (schematic, _) = load_or_exit(&file);
+            let option = MaterialListOption {
+                full_block_state,
+                include_air,
+                ..MaterialListOption::default()
+            };
+            if stacks {
+                for (id, count) in schematic.material_list_in_stacks(&option) {
+                    println!("{id}\t{count}");
+                }
+            } else {
+                for (id, count) in schematic.material_list(&option) {
+                    println!("{id}\t{count}");
+                }
+            }
+        }
+        Commands::Diff {
+            before,
+            after,
+            blocks,
+        } => {
+            let (schem_before, _) = load_or_exit(&before);
+            let (schem_after, _) = load_or_exit(&after);
+            let diff = schem_before.diff(&schem_after);
+            if diff.is_empty() {
+                println!("No difference");
+                return;
+            }
+            println!("{diff}");
+            if blocks {
+                for d in &diff.blocks {
+                    println!(
+                        "{}: {} -> {}",
+                        schem::common::format_size(&d.pos),
+                        block_to_string(&d.before),
+                        block_to_string(&d.after)
+                    );
+                }
+            }
+            std::process::exit(1);
+        }
     }
 }
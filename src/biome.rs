@@ -21,7 +21,7 @@ use strum::{Display, EnumIter, IntoEnumIterator};
 /// Biome in Minecraft
 #[repr(u8)]
 #[allow(non_camel_case_types)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumIter, Display)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter, Display)]
 pub enum Biome {
     the_void = 0,
     plains = 1,
@@ -90,6 +90,11 @@ pub enum Biome {
 }
 
 impl Biome {
+    /// Full id with namespace, like `minecraft:plains`
+    pub fn id(&self) -> String {
+        return format!("minecraft:{}", self);
+    }
+
     pub fn from_str(id: &str) -> Option<Self> {
         if id.starts_with("minecraft:") {
             return Self::from_str(&id[10..id.len()]);
@@ -17,13 +17,15 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use fastnbt::Value;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
-use strum::{Display, EnumString};
+use std::str::FromStr;
+use strum::{Display, EnumString, FromRepr};
 
 /// `Block` is a type of block with namespace and properties(aka attributes) in MC.
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct Block {
     /// Namespace of the block. All vanilla blocks have namespace `minecraft`
     pub namespace: String,
@@ -52,6 +54,8 @@ pub enum BlockIdParseError {
     InvalidCharacter = 12,
 }
 
+impl std::error::Error for BlockIdParseError {}
+
 fn check_blockid_characters(blkid: &str) -> Result<(), BlockIdParseError> {
     for ch in blkid.chars() {
         if ch.is_ascii_lowercase() {
@@ -388,6 +392,25 @@ impl Block {
         self.attributes.insert(key.to_string(), value.to_string());
     }
 
+    /// Returns the value of property `key` parsed as `T`. Returns `None` if the property doesn't
+    /// exist or can't be parsed as `T`.
+    pub fn get_property_as<T: FromStr>(&self, key: &str) -> Option<T> {
+        return match self.attributes.get(key) {
+            Some(val) => val.parse::<T>().ok(),
+            None => None,
+        };
+    }
+
+    /// Returns the value of property `waterlogged`, or false if it doesn't exist.
+    pub fn is_waterlogged(&self) -> bool {
+        return self.get_property_as::<bool>("waterlogged").unwrap_or(false);
+    }
+
+    /// Set property `waterlogged`
+    pub fn set_waterlogged(&mut self, waterlogged: bool) {
+        self.set_property("waterlogged", &waterlogged);
+    }
+
     /// Returns true if `self` can be made by adding 1 or more properties to `blk_less_attr`
     pub fn is_inherited_from(&self, blk_less_attr: &Block) -> bool {
         if self.attributes.len() < blk_less_attr.attributes.len() {
@@ -451,7 +474,7 @@ impl Display for Block {
 
 /// Enumerate common blocks
 #[repr(u16)]
-#[derive(Debug, Display, Clone, Copy)]
+#[derive(Debug, Display, Clone, Copy, FromRepr)]
 #[allow(dead_code)]
 pub enum CommonBlock {
     Air = 0,
@@ -467,3 +490,76 @@ impl CommonBlock {
         };
     }
 }
+
+/// Value of property `facing`
+#[derive(Debug, EnumString, Display, PartialEq, Eq, Hash, Copy, Clone)]
+#[strum(serialize_all = "snake_case")]
+pub enum Facing {
+    Down,
+    Up,
+    North,
+    South,
+    West,
+    East,
+}
+
+#[allow(dead_code)]
+impl Facing {
+    /// Returns the direction in the opposite
+    pub fn opposite(&self) -> Facing {
+        return match self {
+            Facing::Down => Facing::Up,
+            Facing::Up => Facing::Down,
+            Facing::North => Facing::South,
+            Facing::South => Facing::North,
+            Facing::West => Facing::East,
+            Facing::East => Facing::West,
+        };
+    }
+
+    /// Returns the axis that this direction is along
+    pub fn axis(&self) -> Axis {
+        return match self {
+            Facing::Down | Facing::Up => Axis::Y,
+            Facing::North | Facing::South => Axis::Z,
+            Facing::West | Facing::East => Axis::X,
+        };
+    }
+
+    /// Returns the unit vector in xyz
+    pub fn offset(&self) -> [i32; 3] {
+        return match self {
+            Facing::Down => [0, -1, 0],
+            Facing::Up => [0, 1, 0],
+            Facing::North => [0, 0, -1],
+            Facing::South => [0, 0, 1],
+            Facing::West => [-1, 0, 0],
+            Facing::East => [1, 0, 0],
+        };
+    }
+}
+
+/// Value of property `axis`, used by logs, pillars, chains, etc.
+#[derive(Debug, EnumString, Display, PartialEq, Eq, Hash, Copy, Clone)]
+#[strum(serialize_all = "snake_case")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Value of property `half` of stairs and trapdoors
+#[derive(Debug, EnumString, Display, PartialEq, Eq, Hash, Copy, Clone)]
+#[strum(serialize_all = "snake_case")]
+pub enum Half {
+    Top,
+    Bottom,
+}
+
+/// Value of property `half` of doors and tall plants
+#[derive(Debug, EnumString, Display, PartialEq, Eq, Hash, Copy, Clone)]
+#[strum(serialize_all = "snake_case")]
+pub enum DoubleBlockHalf {
+    Upper,
+    Lower,
+}
@@ -0,0 +1,352 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+
+use crate::block::Block;
+#[cfg(feature = "resource_pack")]
+use crate::error::Error;
+use crate::render::RgbaImage;
+
+/// Average colors of vanilla block textures as `(id, rgb, data version the block was added)`.
+/// Colors of tinted textures like grass and leaves are taken in plains.
+const VANILLA_BLOCK_COLORS: &[(&str, [u8; 3], i32)] = &[
+    // 1.13
+    ("stone", [125, 125, 125], 1519),
+    ("granite", [149, 103, 85], 1519),
+    ("polished_granite", [154, 106, 89], 1519),
+    ("diorite", [188, 188, 188], 1519),
+    ("polished_diorite", [192, 193, 194], 1519),
+    ("andesite", [136, 136, 136], 1519),
+    ("polished_andesite", [132, 134, 133], 1519),
+    ("grass_block", [95, 159, 53], 1519),
+    ("dirt", [134, 96, 67], 1519),
+    ("coarse_dirt", [119, 85, 59], 1519),
+    ("podzol", [91, 63, 24], 1519),
+    ("mycelium", [111, 98, 101], 1519),
+    ("farmland", [81, 44, 15], 1519),
+    ("cobblestone", [127, 127, 127], 1519),
+    ("mossy_cobblestone", [110, 118, 94], 1519),
+    ("stone_bricks", [122, 121, 122], 1519),
+    ("bricks", [150, 97, 83], 1519),
+    ("bedrock", [85, 85, 85], 1519),
+    ("obsidian", [15, 10, 24], 1519),
+    ("sand", [219, 207, 163], 1519),
+    ("red_sand", [190, 102, 33], 1519),
+    ("gravel", [131, 127, 126], 1519),
+    ("clay", [160, 166, 179], 1519),
+    ("sandstone", [216, 203, 155], 1519),
+    ("red_sandstone", [181, 97, 31], 1519),
+    ("coal_ore", [105, 105, 105], 1519),
+    ("iron_ore", [136, 129, 122], 1519),
+    ("gold_ore", [145, 133, 106], 1519),
+    ("lapis_ore", [99, 110, 132], 1519),
+    ("redstone_ore", [133, 107, 107], 1519),
+    ("diamond_ore", [121, 141, 140], 1519),
+    ("emerald_ore", [117, 136, 124], 1519),
+    ("coal_block", [16, 15, 15], 1519),
+    ("iron_block", [220, 220, 220], 1519),
+    ("gold_block", [246, 208, 61], 1519),
+    ("lapis_block", [30, 67, 140], 1519),
+    ("redstone_block", [175, 24, 5], 1519),
+    ("diamond_block", [98, 237, 228], 1519),
+    ("emerald_block", [42, 203, 87], 1519),
+    ("quartz_block", [235, 229, 222], 1519),
+    ("oak_planks", [162, 130, 78], 1519),
+    ("spruce_planks", [114, 84, 48], 1519),
+    ("birch_planks", [192, 175, 121], 1519),
+    ("jungle_planks", [160, 115, 80], 1519),
+    ("acacia_planks", [168, 90, 50], 1519),
+    ("dark_oak_planks", [66, 43, 20], 1519),
+    ("oak_log", [109, 85, 50], 1519),
+    ("spruce_log", [58, 37, 16], 1519),
+    ("birch_log", [216, 215, 210], 1519),
+    ("jungle_log", [85, 67, 25], 1519),
+    ("acacia_log", [103, 96, 86], 1519),
+    ("dark_oak_log", [60, 46, 26], 1519),
+    ("oak_leaves", [59, 122, 35], 1519),
+    ("spruce_leaves", [61, 99, 61], 1519),
+    ("birch_leaves", [81, 111, 51], 1519),
+    ("jungle_leaves", [48, 113, 20], 1519),
+    ("acacia_leaves", [56, 105, 23], 1519),
+    ("dark_oak_leaves", [59, 122, 35], 1519),
+    ("bookshelf", [117, 94, 59], 1519),
+    ("crafting_table", [119, 89, 55], 1519),
+    ("pumpkin", [198, 118, 24], 1519),
+    ("melon", [111, 145, 30], 1519),
+    ("hay_block", [166, 136, 38], 1519),
+    ("sponge", [195, 192, 74], 1519),
+    ("wet_sponge", [171, 181, 70], 1519),
+    ("tnt", [142, 62, 53], 1519),
+    ("glass", [175, 213, 219], 1519),
+    ("ice", [145, 183, 253], 1519),
+    ("packed_ice", [141, 180, 250], 1519),
+    ("blue_ice", [116, 167, 253], 1519),
+    ("snow_block", [249, 254, 254], 1519),
+    ("water", [63, 118, 228], 1519),
+    ("lava", [207, 92, 20], 1519),
+    ("netherrack", [97, 38, 38], 1519),
+    ("soul_sand", [81, 62, 50], 1519),
+    ("glowstone", [171, 131, 84], 1519),
+    ("nether_bricks", [44, 21, 26], 1519),
+    ("nether_wart_block", [114, 2, 2], 1519),
+    ("magma_block", [142, 63, 31], 1519),
+    ("end_stone", [219, 222, 158], 1519),
+    ("end_stone_bricks", [218, 224, 162], 1519),
+    ("purpur_block", [169, 125, 169], 1519),
+    ("prismarine", [99, 156, 151], 1519),
+    ("prismarine_bricks", [99, 171, 158], 1519),
+    ("dark_prismarine", [51, 91, 75], 1519),
+    ("sea_lantern", [172, 199, 190], 1519),
+    ("slime_block", [111, 192, 91], 1519),
+    ("bone_block", [229, 225, 207], 1519),
+    ("dried_kelp_block", [50, 58, 38], 1519),
+    ("terracotta", [152, 94, 67], 1519),
+    ("white_terracotta", [209, 178, 161], 1519),
+    ("orange_terracotta", [161, 83, 37], 1519),
+    ("magenta_terracotta", [149, 88, 108], 1519),
+    ("light_blue_terracotta", [113, 108, 137], 1519),
+    ("yellow_terracotta", [186, 133, 35], 1519),
+    ("lime_terracotta", [103, 117, 52], 1519),
+    ("pink_terracotta", [161, 78, 78], 1519),
+    ("gray_terracotta", [57, 42, 35], 1519),
+    ("light_gray_terracotta", [135, 106, 97], 1519),
+    ("cyan_terracotta", [86, 91, 91], 1519),
+    ("purple_terracotta", [118, 70, 86], 1519),
+    ("blue_terracotta", [74, 59, 91], 1519),
+    ("brown_terracotta", [77, 51, 35], 1519),
+    ("green_terracotta", [76, 83, 42], 1519),
+    ("red_terracotta", [143, 61, 46], 1519),
+    ("black_terracotta", [37, 22, 16], 1519),
+    ("white_wool", [233, 236, 236], 1519),
+    ("orange_wool", [240, 118, 19], 1519),
+    ("magenta_wool", [189, 68, 179], 1519),
+    ("light_blue_wool", [58, 175, 217], 1519),
+    ("yellow_wool", [248, 197, 39], 1519),
+    ("lime_wool", [112, 185, 25], 1519),
+    ("pink_wool", [237, 141, 172], 1519),
+    ("gray_wool", [62, 68, 71], 1519),
+    ("light_gray_wool", [142, 142, 134], 1519),
+    ("cyan_wool", [21, 137, 145], 1519),
+    ("purple_wool", [121, 42, 172], 1519),
+    ("blue_wool", [53, 57, 157], 1519),
+    ("brown_wool", [114, 71, 40], 1519),
+    ("green_wool", [84, 109, 27], 1519),
+    ("red_wool", [160, 39, 34], 1519),
+    ("black_wool", [20, 21, 25], 1519),
+    ("white_concrete", [207, 213, 214], 1519),
+    ("orange_concrete", [224, 97, 0], 1519),
+    ("magenta_concrete", [169, 48, 159], 1519),
+    ("light_blue_concrete", [35, 137, 198], 1519),
+    ("yellow_concrete", [240, 175, 21], 1519),
+    ("lime_concrete", [94, 168, 24], 1519),
+    ("pink_concrete", [213, 101, 142], 1519),
+    ("gray_concrete", [54, 57, 61], 1519),
+    ("light_gray_concrete", [125, 125, 115], 1519),
+    ("cyan_concrete", [21, 119, 136], 1519),
+    ("purple_concrete", [100, 31, 156], 1519),
+    ("blue_concrete", [44, 46, 143], 1519),
+    ("brown_concrete", [96, 59, 31], 1519),
+    ("green_concrete", [73, 91, 36], 1519),
+    ("red_concrete", [142, 32, 32], 1519),
+    ("black_concrete", [8, 10, 15], 1519),
+    // 1.14
+    ("smooth_stone", [158, 158, 158], 1952),
+    ("smooth_sandstone", [223, 214, 170], 1952),
+    // 1.15
+    ("honey_block", [251, 185, 52], 2225),
+    ("honeycomb_block", [229, 148, 29], 2225),
+    ("bee_nest", [202, 160, 74], 2225),
+    // 1.16
+    ("crimson_planks", [101, 48, 70], 2566),
+    ("warped_planks", [43, 104, 99], 2566),
+    ("crimson_nylium", [130, 31, 31], 2566),
+    ("warped_nylium", [43, 114, 101], 2566),
+    ("warped_wart_block", [22, 119, 121], 2566),
+    ("shroomlight", [240, 146, 70], 2566),
+    ("soul_soil", [75, 57, 46], 2566),
+    ("basalt", [80, 81, 86], 2566),
+    ("blackstone", [42, 36, 41], 2566),
+    ("crying_obsidian", [32, 10, 60], 2566),
+    ("ancient_debris", [94, 66, 58], 2566),
+    ("netherite_block", [66, 61, 63], 2566),
+    // 1.17
+    ("deepslate", [80, 80, 82], 2724),
+    ("cobbled_deepslate", [77, 77, 80], 2724),
+    ("tuff", [108, 109, 102], 2724),
+    ("calcite", [223, 224, 220], 2724),
+    ("amethyst_block", [133, 97, 191], 2724),
+    ("copper_block", [192, 107, 79], 2724),
+    ("raw_iron_block", [166, 135, 107], 2724),
+    ("raw_copper_block", [154, 105, 79], 2724),
+    ("raw_gold_block", [221, 169, 46], 2724),
+    ("moss_block", [89, 109, 45], 2724),
+    ("dripstone_block", [134, 107, 92], 2724),
+    ("smooth_basalt", [72, 72, 78], 2724),
+    // 1.19
+    ("mud", [60, 57, 60], 3105),
+    ("packed_mud", [142, 106, 79], 3105),
+    ("mud_bricks", [137, 103, 79], 3105),
+    ("mangrove_planks", [117, 54, 48], 3105),
+    ("sculk", [12, 29, 36], 3105),
+    // 1.20
+    ("cherry_planks", [226, 178, 172], 3463),
+    ("cherry_leaves", [229, 172, 194], 3463),
+    ("bamboo_planks", [193, 173, 80], 3463),
+    // 1.21
+    ("polished_tuff", [97, 104, 99], 3953),
+    ("tuff_bricks", [98, 103, 95], 3953),
+];
+
+/// Colors of blocks used by renderers, keyed by full id like `minecraft:stone`. Blocks not in
+/// the table fall back to map colors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockColorTable {
+    colors: HashMap<String, [u8; 3]>,
+}
+
+fn full_id(id: &str) -> String {
+    return if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    };
+}
+
+/// Alpha weighted average color of an image, `None` if the image is fully transparent. Only the
+/// top square is used if the image is taller than wide, which is the first frame of animated
+/// textures.
+pub fn average_color(image: &RgbaImage) -> Option<[u8; 3]> {
+    let rows = image.height.min(image.width.max(1));
+    let mut sum = [0u64; 3];
+    let mut weight = 0u64;
+    for y in 0..rows {
+        for x in 0..image.width {
+            let [r, g, b, a] = image.pixel(x, y)?;
+            sum[0] += r as u64 * a as u64;
+            sum[1] += g as u64 * a as u64;
+            sum[2] += b as u64 * a as u64;
+            weight += a as u64;
+        }
+    }
+    if weight == 0 {
+        return None;
+    }
+    return Some(sum.map(|s| ((s + weight / 2) / weight) as u8));
+}
+
+#[allow(dead_code)]
+impl BlockColorTable {
+    /// An empty table
+    pub fn new() -> BlockColorTable {
+        return BlockColorTable::default();
+    }
+
+    /// Built-in average colors of vanilla textures, only blocks existing in `data_version` are
+    /// included.
+    pub fn vanilla(data_version: i32) -> BlockColorTable {
+        let mut table = BlockColorTable::new();
+        for (id, rgb, added) in VANILLA_BLOCK_COLORS {
+            if *added <= data_version {
+                table.set(id, *rgb);
+            }
+        }
+        return table;
+    }
+
+    /// Count of blocks with color
+    pub fn len(&self) -> usize {
+        return self.colors.len();
+    }
+
+    /// Returns true if no block has color
+    pub fn is_empty(&self) -> bool {
+        return self.colors.is_empty();
+    }
+
+    /// Set the color of a block id, namespace defaults to `minecraft`
+    pub fn set(&mut self, id: &str, rgb: [u8; 3]) {
+        self.colors.insert(full_id(id), rgb);
+    }
+
+    /// Color of a block by namespace and id, properties are ignored
+    pub fn color_of(&self, block: &Block) -> Option<[u8; 3]> {
+        let namespace = if block.namespace.is_empty() {
+            "minecraft"
+        } else {
+            &block.namespace
+        };
+        return self
+            .colors
+            .get(&format!("{}:{}", namespace, block.id))
+            .copied();
+    }
+
+    /// Recompute colors from block textures of a resource pack zip, and overwrite existing ones.
+    /// A texture `assets/<namespace>/textures/block/<name>.png` gives the color of
+    /// `<namespace>:<name>`, and `<name>_top.png` takes precedence since renderers look from
+    /// above. Textures that can't be decoded are skipped, and tinted textures are kept gray. \
+    /// Returns the count of updated blocks.
+    #[cfg(feature = "resource_pack")]
+    pub fn apply_resource_pack(&mut self, zip_bytes: &[u8]) -> Result<usize, Error> {
+        use std::io::Read;
+
+        let zip_err = |e: zip::result::ZipError| Error::InvalidValue {
+            tag_path: "resource pack".to_string(),
+            error: e.to_string(),
+        };
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip_bytes)).map_err(zip_err)?;
+        // full id -> (is top texture, color)
+        let mut found: HashMap<String, (bool, [u8; 3])> = HashMap::new();
+        for idx in 0..archive.len() {
+            let mut file = archive.by_index(idx).map_err(zip_err)?;
+            let name = file.name().to_string();
+            let parts: Vec<&str> = name.split('/').collect();
+            let ["assets", namespace, "textures", "block" | "blocks", texture] = parts[..] else {
+                continue;
+            };
+            let Some(texture) = texture.strip_suffix(".png") else {
+                continue;
+            };
+            let (is_top, id) = match texture.strip_suffix("_top") {
+                Some(id) => (true, id),
+                None => (false, texture),
+            };
+            let key = format!("{namespace}:{id}");
+            if matches!(found.get(&key), Some((true, _))) {
+                continue;
+            }
+            let mut png = Vec::with_capacity(file.size() as usize);
+            if file.read_to_end(&mut png).is_err() {
+                continue;
+            }
+            let Some(rgb) = RgbaImage::from_png(&png)
+                .ok()
+                .and_then(|image| average_color(&image))
+            else {
+                continue;
+            };
+            found.insert(key, (is_top, rgb));
+        }
+        let count = found.len();
+        for (key, (_, rgb)) in found {
+            self.colors.insert(key, rgb);
+        }
+        return Ok(count);
+    }
+}
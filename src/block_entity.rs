@@ -0,0 +1,876 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::unwrap_opt_compound;
+use crate::item::{Inventory, Item};
+use crate::region::Region;
+use crate::schem::id_of_nbt_tag;
+use crate::transform::{Mirror, Rotation};
+use crate::{unwrap_tag, Block, BlockEntity, DataVersion, Error};
+use fastnbt::Value;
+use std::collections::HashMap;
+use strum::{Display, EnumString};
+
+/// Returns the string tag named `key`, or `None` if it doesn't exist.
+fn opt_string(
+    nbt: &HashMap<String, Value>,
+    key: &str,
+    tag_path: &str,
+) -> Result<Option<String>, Error> {
+    if let Some(value) = nbt.get(key) {
+        let s = unwrap_tag!(value, String, String::new(), format!("{tag_path}/{key}"));
+        return Ok(Some(s.clone()));
+    }
+    return Ok(None);
+}
+
+/// Returns the short tag named `key`, or 0 if it doesn't exist.
+fn opt_i16(nbt: &HashMap<String, Value>, key: &str, tag_path: &str) -> Result<i16, Error> {
+    if let Some(value) = nbt.get(key) {
+        return Ok(*unwrap_tag!(value, Short, 0, format!("{tag_path}/{key}")));
+    }
+    return Ok(0);
+}
+
+/// Returns the byte tag named `key` as bool, or false if it doesn't exist.
+fn opt_bool(nbt: &HashMap<String, Value>, key: &str, tag_path: &str) -> Result<bool, Error> {
+    if let Some(value) = nbt.get(key) {
+        return Ok(*unwrap_tag!(value, Byte, 0, format!("{tag_path}/{key}")) != 0);
+    }
+    return Ok(false);
+}
+
+fn set_opt_string(nbt: &mut HashMap<String, Value>, key: &str, value: &Option<String>) {
+    if let Some(v) = value {
+        nbt.insert(key.to_string(), Value::String(v.clone()));
+    } else {
+        nbt.remove(key);
+    }
+}
+
+fn parse_items(tags: &HashMap<String, Value>) -> Result<Inventory, Error> {
+    if let Some(items) = tags.get("Items") {
+        let items = unwrap_tag!(items, List, vec![], "/Items");
+        return Inventory::from_nbt(items, "/Items");
+    }
+    return Ok(Inventory::default());
+}
+
+/// Contents of a container with an `Items` list, like chests, barrels, shulker boxes, hoppers,
+/// dispensers and droppers.
+#[derive(Debug, Clone, Default)]
+pub struct ChestData {
+    /// Items indexed by slot
+    pub items: Inventory,
+    /// Custom name as raw json text component
+    pub custom_name: Option<String>,
+    /// Name of the item required to open the container
+    pub lock: Option<String>,
+    /// Loot table to generate contents when opened
+    pub loot_table: Option<String>,
+    /// Seed of the loot table
+    pub loot_table_seed: Option<i64>,
+}
+
+#[allow(dead_code)]
+impl ChestData {
+    /// Parse from tags of a block entity. Missing tags are treated as empty.
+    pub fn from_block_entity(be: &BlockEntity) -> Result<ChestData, Error> {
+        let tags = &be.tags;
+        let loot_table_seed = if let Some(seed) = tags.get("LootTableSeed") {
+            Some(*unwrap_tag!(seed, Long, 0, "/LootTableSeed"))
+        } else {
+            None
+        };
+        return Ok(ChestData {
+            items: parse_items(tags)?,
+            custom_name: opt_string(tags, "CustomName", "")?,
+            lock: opt_string(tags, "Lock", "")?,
+            loot_table: opt_string(tags, "LootTable", "")?,
+            loot_table_seed,
+        });
+    }
+
+    /// Write contents into a block entity. Tags unrelated to the container are kept.
+    pub fn write_to(&self, be: &mut BlockEntity) {
+        let tags = &mut be.tags;
+        tags.insert("Items".to_string(), Value::List(self.items.to_nbt()));
+        set_opt_string(tags, "CustomName", &self.custom_name);
+        set_opt_string(tags, "Lock", &self.lock);
+        set_opt_string(tags, "LootTable", &self.loot_table);
+        if let Some(seed) = self.loot_table_seed {
+            tags.insert("LootTableSeed".to_string(), Value::Long(seed));
+        } else {
+            tags.remove("LootTableSeed");
+        }
+    }
+}
+
+/// Contents of a furnace, blast furnace or smoker.
+#[derive(Debug, Clone, Default)]
+pub struct FurnaceData {
+    /// Item being smelted, slot 0
+    pub input: Option<Item>,
+    /// Fuel, slot 1
+    pub fuel: Option<Item>,
+    /// Output, slot 2
+    pub result: Option<Item>,
+    /// Ticks until the current fuel runs out
+    pub burn_time: i16,
+    /// Ticks the current item has been cooking
+    pub cook_time: i16,
+    /// Ticks required to finish the current item
+    pub cook_time_total: i16,
+    /// Custom name as raw json text component
+    pub custom_name: Option<String>,
+}
+
+#[allow(dead_code)]
+impl FurnaceData {
+    /// Parse from tags of a block entity. Both `BurnTime`/`CookTime`/`CookTimeTotal` and the
+    /// 1.21.2+ `lit_time_remaining`/`cooking_time_spent`/`cooking_total_time` are accepted.
+    pub fn from_block_entity(be: &BlockEntity) -> Result<FurnaceData, Error> {
+        let tags = &be.tags;
+        let mut items = parse_items(tags)?.0;
+        let new_format = tags.contains_key("cooking_time_spent");
+        let (burn, cook, total) = if new_format {
+            (
+                "lit_time_remaining",
+                "cooking_time_spent",
+                "cooking_total_time",
+            )
+        } else {
+            ("BurnTime", "CookTime", "CookTimeTotal")
+        };
+        return Ok(FurnaceData {
+            input: items.remove(&0),
+            fuel: items.remove(&1),
+            result: items.remove(&2),
+            burn_time: opt_i16(tags, burn, "")?,
+            cook_time: opt_i16(tags, cook, "")?,
+            cook_time_total: opt_i16(tags, total, "")?,
+            custom_name: opt_string(tags, "CustomName", "")?,
+        });
+    }
+
+    /// Write contents into a block entity, keeping the time tag names it already uses.
+    pub fn write_to(&self, be: &mut BlockEntity) {
+        let mut items = Inventory::default();
+        for (slot, item) in [&self.input, &self.fuel, &self.result].iter().enumerate() {
+            if let Some(item) = item {
+                items.0.insert(slot as i8, item.clone());
+            }
+        }
+        let tags = &mut be.tags;
+        let new_format = tags.contains_key("cooking_time_spent");
+        let (burn, cook, total) = if new_format {
+            (
+                "lit_time_remaining",
+                "cooking_time_spent",
+                "cooking_total_time",
+            )
+        } else {
+            ("BurnTime", "CookTime", "CookTimeTotal")
+        };
+        tags.insert("Items".to_string(), Value::List(items.to_nbt()));
+        tags.insert(burn.to_string(), Value::Short(self.burn_time));
+        tags.insert(cook.to_string(), Value::Short(self.cook_time));
+        tags.insert(total.to_string(), Value::Short(self.cook_time_total));
+        set_opt_string(tags, "CustomName", &self.custom_name);
+    }
+}
+
+/// Text on one side of a sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignSide {
+    /// 4 lines as raw json text components
+    pub messages: [String; 4],
+    /// Dye color, like `black`
+    pub color: String,
+    /// Whether the text is glowing
+    pub glowing: bool,
+}
+
+impl Default for SignSide {
+    fn default() -> Self {
+        return SignSide {
+            messages: std::array::from_fn(|_| "\"\"".to_string()),
+            color: "black".to_string(),
+            glowing: false,
+        };
+    }
+}
+
+#[allow(dead_code)]
+impl SignSide {
+    /// Lines as plain strings, with formatting of json text components dropped. Lines that are not
+    /// valid json are returned as they are.
+    pub fn plain_lines(&self) -> [String; 4] {
+        return std::array::from_fn(|idx| json_text_to_plain(&self.messages[idx]));
+    }
+
+    /// Set lines from plain strings, encoded as json strings.
+    pub fn set_plain_lines(&mut self, lines: [&str; 4]) {
+        for (idx, line) in lines.iter().enumerate() {
+            self.messages[idx] = plain_to_json_text(line);
+        }
+    }
+
+    fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<SignSide, Error> {
+        let mut result = SignSide::default();
+        if let Some(messages) = nbt.get("messages") {
+            let path = format!("{tag_path}/messages");
+            let messages = unwrap_tag!(messages, List, vec![], path);
+            for (idx, msg) in messages.iter().take(4).enumerate() {
+                let msg = unwrap_tag!(msg, String, String::new(), format!("{path}/[{idx}]"));
+                result.messages[idx] = msg.clone();
+            }
+        }
+        if let Some(color) = opt_string(nbt, "color", tag_path)? {
+            result.color = color;
+        }
+        result.glowing = opt_bool(nbt, "has_glowing_text", tag_path)?;
+        return Ok(result);
+    }
+
+    fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = HashMap::with_capacity(3);
+        let messages = self.messages.iter().map(|m| Value::String(m.clone()));
+        nbt.insert("messages".to_string(), Value::List(messages.collect()));
+        nbt.insert("color".to_string(), Value::String(self.color.clone()));
+        nbt.insert(
+            "has_glowing_text".to_string(),
+            Value::Byte(self.glowing as i8),
+        );
+        return nbt;
+    }
+}
+
+/// Text of a sign or hanging sign.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignText {
+    /// Front side
+    pub front: SignSide,
+    /// Back side, only exists since 1.20
+    pub back: SignSide,
+    /// Whether the sign is waxed and can't be edited
+    pub waxed: bool,
+}
+
+const OLD_SIGN_KEYS: [&str; 6] = ["Text1", "Text2", "Text3", "Text4", "Color", "GlowingText"];
+const NEW_SIGN_KEYS: [&str; 3] = ["front_text", "back_text", "is_waxed"];
+
+#[allow(dead_code)]
+impl SignText {
+    /// Parse from tags of a block entity, in either the 1.20+ format (`front_text`, `back_text`)
+    /// or the older one (`Text1`..`Text4`).
+    pub fn from_block_entity(be: &BlockEntity) -> Result<SignText, Error> {
+        let tags = &be.tags;
+        let mut result = SignText::default();
+        if tags.contains_key("front_text") || tags.contains_key("back_text") {
+            if tags.contains_key("front_text") {
+                let front = unwrap_opt_compound(tags, "front_text", "")?;
+                result.front = SignSide::from_nbt(front, "/front_text")?;
+            }
+            if tags.contains_key("back_text") {
+                let back = unwrap_opt_compound(tags, "back_text", "")?;
+                result.back = SignSide::from_nbt(back, "/back_text")?;
+            }
+            result.waxed = opt_bool(tags, "is_waxed", "")?;
+            return Ok(result);
+        }
+
+        for idx in 0..4 {
+            if let Some(text) = opt_string(tags, &format!("Text{}", idx + 1), "")? {
+                result.front.messages[idx] = text;
+            }
+        }
+        if let Some(color) = opt_string(tags, "Color", "")? {
+            result.front.color = color;
+        }
+        result.front.glowing = opt_bool(tags, "GlowingText", "")?;
+        return Ok(result);
+    }
+
+    /// Write text into a block entity, using the format of `data_version`. The back side and
+    /// wax are dropped before 1.20.
+    pub fn write_to(&self, be: &mut BlockEntity, data_version: i32) {
+        let tags = &mut be.tags;
+        if data_version >= DataVersion::Java_1_20 as i32 {
+            for key in OLD_SIGN_KEYS {
+                tags.remove(key);
+            }
+            tags.insert(
+                "front_text".to_string(),
+                Value::Compound(self.front.to_nbt()),
+            );
+            tags.insert("back_text".to_string(), Value::Compound(self.back.to_nbt()));
+            tags.insert("is_waxed".to_string(), Value::Byte(self.waxed as i8));
+        } else {
+            for key in NEW_SIGN_KEYS {
+                tags.remove(key);
+            }
+            for (idx, msg) in self.front.messages.iter().enumerate() {
+                tags.insert(format!("Text{}", idx + 1), Value::String(msg.clone()));
+            }
+            tags.insert("Color".to_string(), Value::String(self.front.color.clone()));
+            tags.insert(
+                "GlowingText".to_string(),
+                Value::Byte(self.front.glowing as i8),
+            );
+        }
+    }
+}
+
+/// Returns the int tag named `key`, or `None` if it doesn't exist.
+fn opt_i32(nbt: &HashMap<String, Value>, key: &str, tag_path: &str) -> Result<Option<i32>, Error> {
+    if let Some(value) = nbt.get(key) {
+        return Ok(Some(*unwrap_tag!(
+            value,
+            Int,
+            0,
+            format!("{tag_path}/{key}")
+        )));
+    }
+    return Ok(None);
+}
+
+/// Parse the string tag named `key` with `FromStr`, or `None` if it doesn't exist.
+fn opt_parsed<T: std::str::FromStr>(
+    nbt: &HashMap<String, Value>,
+    key: &str,
+    tag_path: &str,
+) -> Result<Option<T>, Error> {
+    let Some(text) = opt_string(nbt, key, tag_path)? else {
+        return Ok(None);
+    };
+    return match text.parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => Err(Error::InvalidValue {
+            tag_path: format!("{tag_path}/{key}"),
+            error: format!("Invalid value \"{text}\""),
+        }),
+    };
+}
+
+/// How a jigsaw piece may turn around the jigsaw it's attached to
+#[derive(Debug, Display, EnumString, Copy, Clone, Default, Eq, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum JigsawJoint {
+    /// The attached piece can be rotated freely
+    #[default]
+    Rollable,
+    /// The attached piece keeps the orientation of this jigsaw
+    Aligned,
+}
+
+/// Connection of a jigsaw block, which attaches pieces from template pools when a structure is
+/// generated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JigsawData {
+    /// Name of this jigsaw, matched by `target` of jigsaws in other pieces
+    pub name: String,
+    /// Name of the jigsaw to attach to
+    pub target: String,
+    /// Template pool of the piece to attach, like `minecraft:village/plains/houses`
+    pub pool: String,
+    /// Block that this jigsaw turns into after generation, like `minecraft:air`
+    pub final_state: String,
+    /// Only used by jigsaws facing up or down
+    pub joint: JigsawJoint,
+    /// Pieces attached to jigsaws with higher priority are generated first, since 1.20.3
+    pub placement_priority: i32,
+    /// Jigsaws with higher priority are attached first, since 1.20.3
+    pub selection_priority: i32,
+}
+
+impl Default for JigsawData {
+    fn default() -> Self {
+        return JigsawData {
+            name: "minecraft:empty".to_string(),
+            target: "minecraft:empty".to_string(),
+            pool: "minecraft:empty".to_string(),
+            final_state: "minecraft:air".to_string(),
+            joint: JigsawJoint::Rollable,
+            placement_priority: 0,
+            selection_priority: 0,
+        };
+    }
+}
+
+/// Valid values of `orientation` of jigsaw blocks, as `front_top`
+pub const JIGSAW_ORIENTATIONS: [&str; 12] = [
+    "down_east",
+    "down_north",
+    "down_south",
+    "down_west",
+    "up_east",
+    "up_north",
+    "up_south",
+    "up_west",
+    "west_up",
+    "east_up",
+    "north_up",
+    "south_up",
+];
+
+#[allow(dead_code)]
+impl JigsawData {
+    /// Parse from tags of a block entity. Missing tags are taken as default, and the 1.14
+    /// `attachement_type` and `target_pool` are accepted as `target` and `pool`.
+    pub fn from_block_entity(be: &BlockEntity) -> Result<JigsawData, Error> {
+        let tags = &be.tags;
+        let mut result = JigsawData::default();
+        if let Some(name) = opt_string(tags, "name", "")? {
+            result.name = name;
+        }
+        if let Some(target) = opt_string(tags, "target", "")? {
+            result.target = target;
+        } else if let Some(target) = opt_string(tags, "attachement_type", "")? {
+            result.target = target;
+        }
+        if let Some(pool) = opt_string(tags, "pool", "")? {
+            result.pool = pool;
+        } else if let Some(pool) = opt_string(tags, "target_pool", "")? {
+            result.pool = pool;
+        }
+        if let Some(final_state) = opt_string(tags, "final_state", "")? {
+            result.final_state = final_state;
+        }
+        if let Some(joint) = opt_parsed(tags, "joint", "")? {
+            result.joint = joint;
+        }
+        result.placement_priority = opt_i32(tags, "placement_priority", "")?.unwrap_or(0);
+        result.selection_priority = opt_i32(tags, "selection_priority", "")?.unwrap_or(0);
+        return Ok(result);
+    }
+
+    /// Write the connection into a block entity, in the 1.16+ format. Other tags are kept.
+    pub fn write_to(&self, be: &mut BlockEntity) {
+        let tags = &mut be.tags;
+        tags.remove("attachement_type");
+        tags.remove("target_pool");
+        tags.insert("name".to_string(), Value::String(self.name.clone()));
+        tags.insert("target".to_string(), Value::String(self.target.clone()));
+        tags.insert("pool".to_string(), Value::String(self.pool.clone()));
+        tags.insert(
+            "final_state".to_string(),
+            Value::String(self.final_state.clone()),
+        );
+        tags.insert("joint".to_string(), Value::String(self.joint.to_string()));
+        tags.insert(
+            "placement_priority".to_string(),
+            Value::Int(self.placement_priority),
+        );
+        tags.insert(
+            "selection_priority".to_string(),
+            Value::Int(self.selection_priority),
+        );
+    }
+
+    /// Jigsaw block with `orientation` like `north_up`, see [`JIGSAW_ORIENTATIONS`].
+    pub fn block(orientation: &str) -> Result<Block, Error> {
+        if !JIGSAW_ORIENTATIONS.contains(&orientation) {
+            return Err(Error::InvalidValue {
+                tag_path: "orientation".to_string(),
+                error: format!("\"{orientation}\" is not a valid orientation of jigsaw"),
+            });
+        }
+        let mut block = Block::from_id("minecraft:jigsaw").unwrap();
+        block.set_property("orientation", orientation);
+        return Ok(block);
+    }
+}
+
+/// Mode of a structure block
+#[derive(Debug, Display, EnumString, Copy, Clone, Default, Eq, PartialEq)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum StructureBlockMode {
+    /// Save blocks in the box as a structure
+    Save,
+    /// Load a structure into the box
+    Load,
+    /// Mark a corner to detect the box of the structure block in save mode
+    Corner,
+    /// Mark a position for structure generators with the `metadata` string
+    #[default]
+    Data,
+}
+
+/// Settings of a structure block
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureBlockData {
+    /// Name of the structure to save or load, like `minecraft:village/plains/houses/plains_small_house_1`
+    pub name: String,
+    /// Name of the player who saved the structure
+    pub author: String,
+    /// Custom data string in data mode, like `chest` for marking loot chests
+    pub metadata: String,
+    /// Offset of the structure relative to the structure block
+    pub pos: [i32; 3],
+    /// Size of the structure
+    pub size: [i32; 3],
+    pub rotation: Rotation,
+    pub mirror: Mirror,
+    pub mode: StructureBlockMode,
+    /// Chance of each block to be loaded, from 0 to 1
+    pub integrity: f32,
+    /// Seed to remove blocks when `integrity` is less than 1, 0 means random
+    pub seed: i64,
+    /// Don't save or load entities
+    pub ignore_entities: bool,
+    /// Highlight air blocks in the box
+    pub show_air: bool,
+    /// Show the outline of the box
+    pub show_bounding_box: bool,
+    /// Whether the structure block is powered by redstone
+    pub powered: bool,
+}
+
+impl Default for StructureBlockData {
+    fn default() -> Self {
+        return StructureBlockData {
+            name: String::new(),
+            author: String::new(),
+            metadata: String::new(),
+            pos: [0, 1, 0],
+            size: [0, 0, 0],
+            rotation: Rotation::None,
+            mirror: Mirror::None,
+            mode: StructureBlockMode::Data,
+            integrity: 1.0,
+            seed: 0,
+            ignore_entities: true,
+            show_air: false,
+            show_bounding_box: true,
+            powered: false,
+        };
+    }
+}
+
+const STRUCTURE_BLOCK_ROTATIONS: [(Rotation, &str); 4] = [
+    (Rotation::None, "NONE"),
+    (Rotation::Clockwise90, "CLOCKWISE_90"),
+    (Rotation::Clockwise180, "CLOCKWISE_180"),
+    (Rotation::CounterClockwise90, "COUNTERCLOCKWISE_90"),
+];
+const STRUCTURE_BLOCK_MIRRORS: [(Mirror, &str); 3] = [
+    (Mirror::None, "NONE"),
+    (Mirror::LeftRight, "LEFT_RIGHT"),
+    (Mirror::FrontBack, "FRONT_BACK"),
+];
+
+/// Find the enum value of `key` in `names`
+fn parse_named<T: Copy>(
+    nbt: &HashMap<String, Value>,
+    key: &str,
+    names: &[(T, &str)],
+) -> Result<Option<T>, Error> {
+    let Some(text) = opt_string(nbt, key, "")? else {
+        return Ok(None);
+    };
+    if let Some((value, _)) = names.iter().find(|(_, name)| *name == text) {
+        return Ok(Some(*value));
+    }
+    return Err(Error::InvalidValue {
+        tag_path: format!("/{key}"),
+        error: format!("Invalid value \"{text}\""),
+    });
+}
+
+#[allow(dead_code)]
+impl StructureBlockData {
+    /// Parse from tags of a block entity. Missing tags are taken as default.
+    pub fn from_block_entity(be: &BlockEntity) -> Result<StructureBlockData, Error> {
+        let tags = &be.tags;
+        let mut result = StructureBlockData::default();
+        if let Some(name) = opt_string(tags, "name", "")? {
+            result.name = name;
+        }
+        if let Some(author) = opt_string(tags, "author", "")? {
+            result.author = author;
+        }
+        if let Some(metadata) = opt_string(tags, "metadata", "")? {
+            result.metadata = metadata;
+        }
+        for (dim, axis) in ["X", "Y", "Z"].iter().enumerate() {
+            if let Some(pos) = opt_i32(tags, &format!("pos{axis}"), "")? {
+                result.pos[dim] = pos;
+            }
+            if let Some(size) = opt_i32(tags, &format!("size{axis}"), "")? {
+                result.size[dim] = size;
+            }
+        }
+        if let Some(rotation) = parse_named(tags, "rotation", &STRUCTURE_BLOCK_ROTATIONS)? {
+            result.rotation = rotation;
+        }
+        if let Some(mirror) = parse_named(tags, "mirror", &STRUCTURE_BLOCK_MIRRORS)? {
+            result.mirror = mirror;
+        }
+        if let Some(mode) = opt_parsed(tags, "mode", "")? {
+            result.mode = mode;
+        }
+        if let Some(integrity) = tags.get("integrity") {
+            result.integrity = *unwrap_tag!(integrity, Float, 0.0, "/integrity");
+        }
+        if let Some(seed) = tags.get("seed") {
+            result.seed = *unwrap_tag!(seed, Long, 0, "/seed");
+        }
+        if tags.contains_key("ignoreEntities") {
+            result.ignore_entities = opt_bool(tags, "ignoreEntities", "")?;
+        }
+        result.show_air = opt_bool(tags, "showair", "")?;
+        if tags.contains_key("showboundingbox") {
+            result.show_bounding_box = opt_bool(tags, "showboundingbox", "")?;
+        }
+        result.powered = opt_bool(tags, "powered", "")?;
+        return Ok(result);
+    }
+
+    /// Write settings into a block entity. Other tags are kept.
+    pub fn write_to(&self, be: &mut BlockEntity) {
+        let tags = &mut be.tags;
+        tags.insert("name".to_string(), Value::String(self.name.clone()));
+        tags.insert("author".to_string(), Value::String(self.author.clone()));
+        tags.insert("metadata".to_string(), Value::String(self.metadata.clone()));
+        for (dim, axis) in ["X", "Y", "Z"].iter().enumerate() {
+            tags.insert(format!("pos{axis}"), Value::Int(self.pos[dim]));
+            tags.insert(format!("size{axis}"), Value::Int(self.size[dim]));
+        }
+        let rotation = STRUCTURE_BLOCK_ROTATIONS
+            .iter()
+            .find(|(r, _)| *r == self.rotation)
+            .unwrap()
+            .1;
+        let mirror = STRUCTURE_BLOCK_MIRRORS
+            .iter()
+            .find(|(m, _)| *m == self.mirror)
+            .unwrap()
+            .1;
+        tags.insert("rotation".to_string(), Value::String(rotation.to_string()));
+        tags.insert("mirror".to_string(), Value::String(mirror.to_string()));
+        tags.insert("mode".to_string(), Value::String(self.mode.to_string()));
+        tags.insert("integrity".to_string(), Value::Float(self.integrity));
+        tags.insert("seed".to_string(), Value::Long(self.seed));
+        tags.insert(
+            "ignoreEntities".to_string(),
+            Value::Byte(self.ignore_entities as i8),
+        );
+        tags.insert("showair".to_string(), Value::Byte(self.show_air as i8));
+        tags.insert(
+            "showboundingbox".to_string(),
+            Value::Byte(self.show_bounding_box as i8),
+        );
+        tags.insert("powered".to_string(), Value::Byte(self.powered as i8));
+    }
+
+    /// Structure block whose `mode` property matches `self.mode`
+    pub fn block(&self) -> Block {
+        let mut block = Block::from_id("minecraft:structure_block").unwrap();
+        block.set_property("mode", &self.mode.to_string().to_lowercase());
+        return block;
+    }
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Place a jigsaw block with `orientation` at `r_pos`, and write `data` into its block
+    /// entity.
+    pub fn set_jigsaw(
+        &mut self,
+        r_pos: [i32; 3],
+        orientation: &str,
+        data: &JigsawData,
+    ) -> Result<(), Error> {
+        let block = JigsawData::block(orientation)?;
+        if self.set_block(r_pos, &block).is_err() {
+            return Err(Error::InvalidValue {
+                tag_path: "r_pos".to_string(),
+                error: format!("{r_pos:?} is out of the region"),
+            });
+        }
+        let mut be = BlockEntity::new();
+        data.write_to(&mut be);
+        self.block_entities.insert(r_pos, be);
+        return Ok(());
+    }
+
+    /// Relative positions and connections of all jigsaw blocks, ordered by y, z, x. Jigsaws
+    /// without block entity get the default connection.
+    pub fn jigsaws(&self) -> Result<Vec<([i32; 3], JigsawData)>, Error> {
+        let matched: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| {
+                blk.id == "jigsaw" && (blk.namespace.is_empty() || blk.namespace == "minecraft")
+            })
+            .collect();
+        let mut result = Vec::new();
+        if !matched.contains(&true) {
+            return Ok(result);
+        }
+        for ((y, z, x), idx) in self.array_yzx.indexed_iter() {
+            if !matched.get(*idx as usize).copied().unwrap_or(false) {
+                continue;
+            }
+            let pos = [x as i32, y as i32, z as i32];
+            let data = match self.block_entities.get(&pos) {
+                Some(be) => JigsawData::from_block_entity(be)?,
+                None => JigsawData::default(),
+            };
+            result.push((pos, data));
+        }
+        return Ok(result);
+    }
+}
+
+fn append_plain(json: &serde_json::Value, dest: &mut String) {
+    match json {
+        serde_json::Value::String(s) => dest.push_str(s),
+        serde_json::Value::Array(elements) => {
+            for e in elements {
+                append_plain(e, dest);
+            }
+        }
+        serde_json::Value::Object(members) => {
+            for key in ["text", "extra"] {
+                if let Some(value) = members.get(key) {
+                    append_plain(value, dest);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Convert a json text component to plain string. Returns `text` itself if it's not valid json.
+pub fn json_text_to_plain(text: &str) -> String {
+    return match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(json) => {
+            let mut result = String::new();
+            append_plain(&json, &mut result);
+            result
+        }
+        Err(_) => text.to_string(),
+    };
+}
+
+/// Encode a plain string as a json string component.
+pub fn plain_to_json_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    return result;
+}
+
+/// Block entity id(without namespace) that a vanilla block has, or `None` if the block has no
+/// block entity. Blocks out of `minecraft` namespace always return `None`, while an empty
+/// namespace is taken as `minecraft`.
+pub fn block_entity_id_of(block: &Block) -> Option<&'static str> {
+    if !block.namespace.is_empty() && block.namespace != "minecraft" {
+        return None;
+    }
+    let id = block.id.as_str();
+    match id {
+        "piston" | "piston_head" => return None,
+        "spawner" => return Some("mob_spawner"),
+        "moving_piston" => return Some("piston"),
+        "soul_campfire" => return Some("campfire"),
+        "bee_nest" => return Some("beehive"),
+        "suspicious_sand" | "suspicious_gravel" => return Some("brushable_block"),
+        "chain_command_block" | "repeating_command_block" => return Some("command_block"),
+        _ => {}
+    }
+    // most blocks share the id with their block entity
+    if let Some(be_id) = BLOCK_ENTITY_IDS.iter().find(|be_id| **be_id == id) {
+        return Some(be_id);
+    }
+    let suffixes = [
+        ("hanging_sign", "hanging_sign"),
+        ("_sign", "sign"),
+        ("_banner", "banner"),
+        ("_bed", "bed"),
+        ("shulker_box", "shulker_box"),
+        ("_head", "skull"),
+        ("_skull", "skull"),
+    ];
+    for (suffix, be_id) in suffixes {
+        if id.ends_with(suffix) {
+            return Some(be_id);
+        }
+    }
+    return None;
+}
+
+/// Ids of vanilla block entities
+pub const BLOCK_ENTITY_IDS: &[&str] = &[
+    "banner",
+    "barrel",
+    "beacon",
+    "bed",
+    "beehive",
+    "bell",
+    "blast_furnace",
+    "brewing_stand",
+    "brushable_block",
+    "calibrated_sculk_sensor",
+    "campfire",
+    "chest",
+    "chiseled_bookshelf",
+    "command_block",
+    "comparator",
+    "conduit",
+    "crafter",
+    "creaking_heart",
+    "daylight_detector",
+    "decorated_pot",
+    "dispenser",
+    "dropper",
+    "enchanting_table",
+    "end_gateway",
+    "end_portal",
+    "ender_chest",
+    "furnace",
+    "hanging_sign",
+    "hopper",
+    "jigsaw",
+    "jukebox",
+    "lectern",
+    "mob_spawner",
+    "piston",
+    "sculk_catalyst",
+    "sculk_sensor",
+    "sculk_shrieker",
+    "shulker_box",
+    "sign",
+    "skull",
+    "smoker",
+    "structure_block",
+    "trapped_chest",
+    "trial_spawner",
+    "vault",
+];
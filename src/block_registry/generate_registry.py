@@ -0,0 +1,61 @@
+#!/usr/bin/env python3
+"""
+Generate an embedded block registry (java_*.txt) from the blocks.json data report of vanilla
+Minecraft.
+
+Get blocks.json by running the data generator of the server jar of that version:
+    1.18+:   java -DbundlerMainClass=net.minecraft.data.Main -jar server.jar --reports
+    1.13-1.17: java -cp server.jar net.minecraft.data.Main --reports
+The report is written to generated/reports/blocks.json.
+
+Usage:
+    python3 generate_registry.py <blocks.json> <version name> <data version> <output txt>
+Example:
+    python3 generate_registry.py generated/reports/blocks.json 1.20.4 3700 java_1_20_4.txt
+"""
+
+import json
+import sys
+
+
+def default_properties(block):
+    for state in block.get("states", []):
+        if state.get("default", False):
+            return state.get("properties", {})
+    return {}
+
+
+def registry_lines(blocks):
+    lines = []
+    for block_id, block in blocks.items():
+        segments = [block_id]
+        defaults = default_properties(block)
+        for name, values in block.get("properties", {}).items():
+            default = defaults.get(name, values[0])
+            # the first value is the default value
+            ordered = [default] + [v for v in values if v != default]
+            segments.append("{}={}".format(name, "|".join(ordered)))
+        lines.append(" ".join(segments))
+    return lines
+
+
+def main():
+    if len(sys.argv) != 5:
+        print(__doc__)
+        sys.exit(1)
+    src, version, data_version, dst = sys.argv[1:]
+    with open(src, encoding="utf-8") as f:
+        blocks = json.load(f)
+    lines = registry_lines(blocks)
+    with open(dst, "w", encoding="utf-8", newline="\n") as f:
+        f.write("# Block states of Java {} (data version {}).\n".format(version, data_version))
+        f.write("# Generated by generate_registry.py from the blocks.json data report, do not edit.\n")
+        f.write("# Each line is a block id followed by its properties; "
+                "the first value of each property is the default.\n")
+        for line in lines:
+            f.write(line + "\n")
+    print("Wrote {} blocks to {}".format(len(lines), dst))
+
+
+if __name__ == "__main__":
+    main()
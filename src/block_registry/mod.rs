@@ -0,0 +1,298 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::schem::DataVersion;
+use crate::Schematic;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Properties and allowed values of a block in the registry
+#[derive(Debug, Clone)]
+pub struct BlockStateDef {
+    /// Full id without properties, for example `minecraft:oak_stairs`
+    pub id: String,
+    /// Property names and their allowed values. The first value is the default value
+    pub properties: Vec<(String, Vec<String>)>,
+}
+
+/// Block states of vanilla Minecraft of a specific version
+#[derive(Debug)]
+pub struct BlockRegistry {
+    /// Data version of this registry
+    pub data_version: i32,
+    blocks: HashMap<String, BlockStateDef>,
+    /// Whether every vanilla block is listed, so that unknown blocks of `minecraft` are invalid
+    complete: bool,
+}
+
+/// Embedded registries, sorted by data version. Generate them from the `blocks.json` data report
+/// of vanilla with `generate_registry.py` in this directory. The files below are written by hand
+/// and cover common blocks only, so they are not complete.
+const EMBEDDED_REGISTRIES: [(DataVersion, &str); 2] = [
+    (DataVersion::Java_1_16_5, include_str!("java_1_16_5.txt")),
+    (DataVersion::Java_1_20_4, include_str!("java_1_20_4.txt")),
+];
+
+static PARSED_REGISTRIES: OnceLock<Vec<BlockRegistry>> = OnceLock::new();
+
+/// Header comment of registries written by `generate_registry.py`
+const GENERATED_MARK: &str = "# Generated by generate_registry.py";
+
+impl BlockStateDef {
+    /// Returns allowed values of `property`
+    pub fn allowed_values(&self, property: &str) -> Option<&[String]> {
+        for (name, values) in &self.properties {
+            if name == property {
+                return Some(values);
+            }
+        }
+        return None;
+    }
+
+    /// Returns the block with all properties set to default values. Fails if the id is invalid or
+    /// a property has no values, which can only happen with a registry built by hand.
+    pub fn default_block(&self) -> Result<Block, Error> {
+        let mut blk = Block::from_id(&self.id).map_err(|reason| Error::InvalidBlockId {
+            id: self.id.clone(),
+            reason,
+        })?;
+        for (name, values) in &self.properties {
+            let Some(default) = values.first() else {
+                return Err(Error::InvalidValue {
+                    tag_path: format!("{}/{}", self.id, name),
+                    error: format!("Property {name} of {} has no values", self.id),
+                });
+            };
+            blk.attributes.insert(name.clone(), default.clone());
+        }
+        return Ok(blk);
+    }
+}
+
+#[allow(dead_code)]
+impl BlockRegistry {
+    /// Parse registry from text. Each non-empty line that doesn't start with `#` is a block id
+    /// followed by properties like `facing=north|south|west|east`, separated by spaces. The first
+    /// value of each property is the default value. \
+    /// Text written by `generate_registry.py` lists every block of the version, and it's marked
+    /// by the comment `# Generated by generate_registry.py`. Such registries are complete, see
+    /// [`BlockRegistry::is_complete`].
+    pub fn parse(data_version: i32, text: &str) -> Result<BlockRegistry, Error> {
+        let mut blocks = HashMap::new();
+        let mut complete = false;
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with(GENERATED_MARK) {
+                complete = true;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut segments = line.split_whitespace();
+            let id = segments.next().unwrap();
+            if let Err(reason) = Block::from_id(id) {
+                return Err(Error::InvalidBlockId {
+                    id: id.to_string(),
+                    reason,
+                });
+            }
+            let mut properties = Vec::new();
+            for seg in segments {
+                let (name, values) = match seg.split_once('=') {
+                    Some(nv) if !nv.1.is_empty() => nv,
+                    _ => {
+                        return Err(Error::InvalidValue {
+                            tag_path: format!("line {}", line_idx + 1),
+                            error: format!("Expected property=values, found \"{seg}\""),
+                        })
+                    }
+                };
+                let values: Vec<String> = values.split('|').map(|v| v.to_string()).collect();
+                properties.push((name.to_string(), values));
+            }
+            blocks.insert(
+                id.to_string(),
+                BlockStateDef {
+                    id: id.to_string(),
+                    properties,
+                },
+            );
+        }
+        return Ok(BlockRegistry {
+            data_version,
+            blocks,
+            complete,
+        });
+    }
+
+    /// Whether the registry lists every vanilla block. Complete registries reject blocks of
+    /// `minecraft` that they don't have, while others only check blocks they have.
+    pub fn is_complete(&self) -> bool {
+        return self.complete;
+    }
+
+    /// Returns the embedded registry that fits `data_version` best, i.e. the latest one whose
+    /// data version is not greater than `data_version`, or the earliest one if there isn't.
+    pub fn embedded(data_version: i32) -> &'static BlockRegistry {
+        let registries = PARSED_REGISTRIES.get_or_init(|| {
+            let mut result = Vec::with_capacity(EMBEDDED_REGISTRIES.len());
+            for (dv, text) in EMBEDDED_REGISTRIES {
+                result.push(Self::parse(dv as i32, text).expect("Embedded registry is broken"));
+            }
+            return result;
+        });
+        let mut selected = &registries[0];
+        for reg in registries {
+            if reg.data_version <= data_version {
+                selected = reg;
+            }
+        }
+        return selected;
+    }
+
+    /// Returns the latest embedded registry
+    pub fn latest() -> &'static BlockRegistry {
+        return Self::embedded(i32::MAX);
+    }
+
+    /// Data versions of all embedded registries, in ascending order
+    pub fn embedded_data_versions() -> Vec<i32> {
+        return EMBEDDED_REGISTRIES
+            .iter()
+            .map(|(dv, _)| *dv as i32)
+            .collect();
+    }
+
+    /// Validate every block of `palette`, errors are reported with `tag_path` and the index in
+    /// palette.
+    pub fn validate_palette(&self, palette: &[Block], tag_path: &str) -> Result<(), Error> {
+        for (idx, blk) in palette.iter().enumerate() {
+            if let Err(e) = self.validate(blk) {
+                return Err(Error::InvalidValue {
+                    tag_path: format!("{tag_path}[{idx}]"),
+                    error: e.to_string(),
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    /// Number of blocks in the registry
+    pub fn len(&self) -> usize {
+        return self.blocks.len();
+    }
+
+    /// Returns true if the registry has no blocks
+    pub fn is_empty(&self) -> bool {
+        return self.blocks.is_empty();
+    }
+
+    /// Get the definition of a block by id, properties are ignored. Blocks without namespace are
+    /// looked up in `minecraft`.
+    pub fn get(&self, blk: &Block) -> Option<&BlockStateDef> {
+        let namespace = if blk.namespace.is_empty() {
+            "minecraft"
+        } else {
+            &blk.namespace
+        };
+        return self.blocks.get(&format!("{}:{}", namespace, blk.id));
+    }
+
+    /// Check that every property of `blk` exists and has an allowed value. Blocks of other
+    /// namespaces are always accepted, and so are unknown `minecraft` blocks unless the registry
+    /// is complete.
+    pub fn validate(&self, blk: &Block) -> Result<(), Error> {
+        let def = match self.get(blk) {
+            Some(d) => d,
+            None => {
+                let is_vanilla = blk.namespace.is_empty() || blk.namespace == "minecraft";
+                if self.complete && is_vanilla {
+                    return Err(Error::UnknownBlock {
+                        block: blk.clone(),
+                        data_version: self.data_version,
+                    });
+                }
+                return Ok(());
+            }
+        };
+        for (key, val) in &blk.attributes {
+            let allowed = match def.allowed_values(key) {
+                Some(a) => a,
+                None => {
+                    return Err(Error::UnknownBlockProperty {
+                        block: blk.clone(),
+                        property: key.clone(),
+                    })
+                }
+            };
+            if !allowed.contains(val) {
+                return Err(Error::InvalidBlockPropertyValue {
+                    block: blk.clone(),
+                    property: key.clone(),
+                    value: val.clone(),
+                    allowed_values: allowed.to_vec(),
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    /// Validate `blk` and fill missing properties with default values
+    pub fn canonicalize(&self, blk: &mut Block) -> Result<(), Error> {
+        self.validate(blk)?;
+        if let Some(def) = self.get(blk) {
+            for (name, values) in &def.properties {
+                if !blk.attributes.contains_key(name) {
+                    blk.attributes.insert(name.clone(), values[0].clone());
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[allow(dead_code)]
+impl Block {
+    /// Check the block against the embedded vanilla registry of `data_version`. Properties that
+    /// don't exist or have impossible values are rejected, see [`BlockRegistry::validate`] for
+    /// unknown blocks.
+    pub fn validate(&self, data_version: i32) -> Result<(), Error> {
+        return BlockRegistry::embedded(data_version).validate(self);
+    }
+
+    /// Validate with the latest embedded registry, and fill in default values of missing properties.
+    pub fn canonicalize(&mut self) -> Result<(), Error> {
+        return BlockRegistry::latest().canonicalize(self);
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Validate blocks in all palettes with the data version of this schematic
+    pub fn validate_palettes(&self) -> Result<(), Error> {
+        let registry = BlockRegistry::embedded(self.metadata.mc_data_version);
+        for reg in &self.regions {
+            for blk in &reg.palette {
+                registry.validate(blk)?;
+            }
+        }
+        return Ok(());
+    }
+}
@@ -22,9 +22,10 @@ use crate::error::Error;
 #[allow(unused_imports)]
 use crate::region::{BlockEntity, Entity, PendingTick};
 use crate::schem::{
-    DataVersion, LitematicaLoadOption, LitematicaSaveOption, MetaDataIR, Schematic,
-    VanillaStructureLoadOption, VanillaStructureSaveOption, WorldEdit12LoadOption,
-    WorldEdit13LoadOption, WorldEdit13SaveOption,
+    AirPolicy, BitsPerBlock, CompressMethod, DataVersion, LitematicaLoadOption,
+    LitematicaSaveOption, MetaDataIR, PaletteOrder, Schematic, VanillaStructureLoadOption,
+    VanillaStructureSaveOption, WorldEdit12LoadOption, WorldEdit13LoadOption,
+    WorldEdit13SaveOption,
 };
 use fastnbt::Value;
 use flate2::Compression;
@@ -393,6 +394,11 @@ unsafe extern "C" fn MC_SCHEM_error_to_string(
     copy_nonoverlapping(s.as_ptr() as *const c_char, dest, s.as_bytes().len());
 }
 
+#[no_mangle]
+unsafe extern "C" fn MC_SCHEM_error_get_code(error: *const Error) -> u16 {
+    return (*error).code();
+}
+
 pub fn error_to_box(err: Option<Error>) -> Option<Box<Error>> {
     sa::const_assert!(size_of::<Option<Box<Error>>>() == size_of::<usize>());
     return if let Some(e) = err {
@@ -515,7 +521,7 @@ struct CLitematicaLoadOption {
 sa::const_assert!(size_of::<CLitematicaLoadOption>() == 512);
 impl CLitematicaLoadOption {
     pub fn to_option(&self) -> LitematicaLoadOption {
-        return LitematicaLoadOption {};
+        return LitematicaLoadOption::default();
     }
 
     pub fn from_option(_src: &LitematicaLoadOption) -> Self {
@@ -523,21 +529,39 @@ impl CLitematicaLoadOption {
     }
 }
 
+/// Convert an enum value passed from C, values out of range are rejected instead of being
+/// transmuted, which is UB.
+fn enum_from_c<T, V: Display>(value: V, parsed: Option<T>, field: &str) -> Result<T, Error> {
+    return match parsed {
+        Some(v) => Ok(v),
+        None => Err(Error::InvalidValue {
+            tag_path: field.to_string(),
+            error: format!("{value} is not a valid value of {field}"),
+        }),
+    };
+}
+
 #[repr(C, align(512))]
 struct CVanillaStructureLoadOption {
-    pub background_block: CommonBlock,
+    // MC_SCHEM_common_block
+    pub background_block: u16,
 }
 
 sa::const_assert!(size_of::<CVanillaStructureLoadOption>() == 512);
 impl CVanillaStructureLoadOption {
-    pub fn to_option(&self) -> VanillaStructureLoadOption {
-        return VanillaStructureLoadOption {
-            background_block: self.background_block,
-        };
+    pub fn to_option(&self) -> Result<VanillaStructureLoadOption, Error> {
+        return Ok(VanillaStructureLoadOption {
+            background_block: enum_from_c(
+                self.background_block,
+                CommonBlock::from_repr(self.background_block),
+                "background_block",
+            )?,
+            ..VanillaStructureLoadOption::default()
+        });
     }
     pub fn from_option(src: &VanillaStructureLoadOption) -> Self {
         return Self {
-            background_block: src.background_block,
+            background_block: src.background_block as u16,
         };
     }
 }
@@ -550,7 +574,7 @@ struct CWE13LoadOption {
 sa::const_assert!(size_of::<CWE13LoadOption>() == 512);
 impl CWE13LoadOption {
     pub fn to_option(&self) -> WorldEdit13LoadOption {
-        return WorldEdit13LoadOption {};
+        return WorldEdit13LoadOption::default();
     }
 
     pub fn from_option(_src: &WorldEdit13LoadOption) -> Self {
@@ -560,19 +584,24 @@ impl CWE13LoadOption {
 
 #[repr(C, align(512))]
 struct CWE12LoadOption {
-    pub data_version: DataVersion,
+    pub data_version: i32,
 }
 sa::const_assert!(size_of::<CWE12LoadOption>() == 512);
 
 impl CWE12LoadOption {
-    pub fn to_option(&self) -> WorldEdit12LoadOption {
-        return WorldEdit12LoadOption {
-            data_version: self.data_version,
-        };
+    pub fn to_option(&self) -> Result<WorldEdit12LoadOption, Error> {
+        return Ok(WorldEdit12LoadOption {
+            data_version: enum_from_c(
+                self.data_version,
+                DataVersion::from_repr(self.data_version),
+                "data_version",
+            )?,
+            ..WorldEdit12LoadOption::default()
+        });
     }
     pub fn from_option(src: &WorldEdit12LoadOption) -> Self {
         return Self {
-            data_version: src.data_version,
+            data_version: src.data_version as i32,
         };
     }
 }
@@ -639,25 +668,83 @@ impl Write for CWriter {
     }
 }
 
+fn compress_method_from_c(value: u8) -> Result<CompressMethod, Error> {
+    return enum_from_c(value, CompressMethod::from_repr(value), "compress_method");
+}
+
+fn palette_order_from_c(value: u8) -> Result<PaletteOrder, Error> {
+    return enum_from_c(value, PaletteOrder::from_repr(value), "palette_order");
+}
+
 #[repr(C, align(512))]
 struct CLitematicaSaveOption {
     compress_level: u32,
     rename_duplicated_regions: bool,
+    // 0 means auto
+    bits_per_block: u8,
+    verify_block_states: bool,
+    // MC_SCHEM_compress_method
+    compress_method: u8,
+    // 0 means keeping data version of schematic
+    target_data_version: i32,
+    // 0 means keeping litematica version of schematic
+    target_version: i32,
+    // MC_SCHEM_air_policy
+    air_policy: u8,
+    // MC_SCHEM_palette_order
+    palette_order: u8,
 }
 sa::const_assert!(size_of::<CLitematicaSaveOption>() == 512);
 
 impl CLitematicaSaveOption {
-    pub fn to_option(&self) -> LitematicaSaveOption {
-        return LitematicaSaveOption {
+    pub fn to_option(&self) -> Result<LitematicaSaveOption, Error> {
+        return Ok(LitematicaSaveOption {
             compress_level: Compression::new(min(self.compress_level, 9)),
             rename_duplicated_regions: self.rename_duplicated_regions,
-        };
+            bits_per_block: if self.bits_per_block == 0 {
+                BitsPerBlock::Auto
+            } else {
+                BitsPerBlock::Forced(self.bits_per_block)
+            },
+            verify_block_states: self.verify_block_states,
+            compress_method: compress_method_from_c(self.compress_method)?,
+            target_data_version: if self.target_data_version == 0 {
+                None
+            } else {
+                Some(self.target_data_version)
+            },
+            target_version: if self.target_version == 0 {
+                None
+            } else {
+                Some(self.target_version)
+            },
+            on_downgrade: None,
+            progress: None,
+            cancel: None,
+            preview: None,
+            air_policy: enum_from_c(
+                self.air_policy,
+                AirPolicy::from_repr(self.air_policy),
+                "air_policy",
+            )?,
+            palette_order: palette_order_from_c(self.palette_order)?,
+        });
     }
 
     pub fn from_option(src: &LitematicaSaveOption) -> Self {
         return CLitematicaSaveOption {
             compress_level: src.compress_level.level(),
             rename_duplicated_regions: src.rename_duplicated_regions,
+            bits_per_block: match src.bits_per_block {
+                BitsPerBlock::Auto => 0,
+                BitsPerBlock::Forced(b) => b,
+            },
+            verify_block_states: src.verify_block_states,
+            compress_method: src.compress_method as u8,
+            target_data_version: src.target_data_version.unwrap_or(0),
+            target_version: src.target_version.unwrap_or(0),
+            air_policy: src.air_policy as u8,
+            palette_order: src.palette_order as u8,
         };
     }
 }
@@ -666,20 +753,69 @@ impl CLitematicaSaveOption {
 struct CVanillaStructureSaveOption {
     compress_level: u32,
     keep_air: bool,
+    // MC_SCHEM_compress_method
+    compress_method: u8,
+    // all zero means no limit
+    max_size: [i32; 3],
+    split_oversized: bool,
+    // 0 means keeping data version of schematic
+    target_data_version: i32,
+    keep_unmapped_metadata: bool,
+    fill_block_entity_id: bool,
+    // 0 means no limit
+    max_file_size: u64,
+    // MC_SCHEM_palette_order
+    palette_order: u8,
 }
 sa::const_assert!(size_of::<CVanillaStructureSaveOption>() == 512);
 
 impl CVanillaStructureSaveOption {
-    pub fn to_option(&self) -> VanillaStructureSaveOption {
-        return VanillaStructureSaveOption {
+    pub fn to_option(&self) -> Result<VanillaStructureSaveOption, Error> {
+        return Ok(VanillaStructureSaveOption {
             compress_level: Compression::new(min(self.compress_level, 9)),
-            keep_air: self.keep_air,
-        };
+            air_policy: if self.keep_air {
+                AirPolicy::Include
+            } else {
+                AirPolicy::Omit
+            },
+            compress_method: compress_method_from_c(self.compress_method)?,
+            max_size: if self.max_size == [0, 0, 0] {
+                None
+            } else {
+                Some(self.max_size)
+            },
+            split_oversized: self.split_oversized,
+            target_data_version: if self.target_data_version == 0 {
+                None
+            } else {
+                Some(self.target_data_version)
+            },
+            on_downgrade: None,
+            progress: None,
+            cancel: None,
+            keep_unmapped_metadata: self.keep_unmapped_metadata,
+            fill_block_entity_id: self.fill_block_entity_id,
+            max_file_size: if self.max_file_size == 0 {
+                None
+            } else {
+                Some(self.max_file_size)
+            },
+            palette_order: palette_order_from_c(self.palette_order)?,
+            palette_variant: None,
+        });
     }
     pub fn from_option(src: &VanillaStructureSaveOption) -> Self {
         return CVanillaStructureSaveOption {
             compress_level: src.compress_level.level(),
-            keep_air: src.keep_air,
+            keep_air: src.air_policy == AirPolicy::Include,
+            compress_method: src.compress_method as u8,
+            max_size: src.max_size.unwrap_or([0, 0, 0]),
+            split_oversized: src.split_oversized,
+            target_data_version: src.target_data_version.unwrap_or(0),
+            keep_unmapped_metadata: src.keep_unmapped_metadata,
+            fill_block_entity_id: src.fill_block_entity_id,
+            max_file_size: src.max_file_size.unwrap_or(0),
+            palette_order: src.palette_order as u8,
         };
     }
 }
@@ -687,22 +823,57 @@ impl CVanillaStructureSaveOption {
 #[repr(C, align(512))]
 struct CWE13SaveOption {
     compress_level: u32,
-    background_block: CommonBlock,
+    // MC_SCHEM_common_block
+    background_block: u16,
+    // 0 means selecting by data version
+    schem_version: i32,
+    // MC_SCHEM_compress_method
+    compress_method: u8,
+    // 0 means keeping data version of schematic
+    target_data_version: i32,
+    keep_unmapped_metadata: bool,
+    // MC_SCHEM_palette_order
+    palette_order: u8,
 }
 sa::const_assert!(size_of::<CWE13SaveOption>() == 512);
 
 impl CWE13SaveOption {
-    pub fn to_option(&self) -> WorldEdit13SaveOption {
-        return WorldEdit13SaveOption {
+    pub fn to_option(&self) -> Result<WorldEdit13SaveOption, Error> {
+        return Ok(WorldEdit13SaveOption {
             compress_level: Compression::new(min(self.compress_level, 9)),
-            background_block: self.background_block,
-        };
+            background_block: enum_from_c(
+                self.background_block,
+                CommonBlock::from_repr(self.background_block),
+                "background_block",
+            )?,
+            schem_version: if self.schem_version == 0 {
+                None
+            } else {
+                Some(self.schem_version)
+            },
+            compress_method: compress_method_from_c(self.compress_method)?,
+            target_data_version: if self.target_data_version == 0 {
+                None
+            } else {
+                Some(self.target_data_version)
+            },
+            on_downgrade: None,
+            progress: None,
+            cancel: None,
+            keep_unmapped_metadata: self.keep_unmapped_metadata,
+            palette_order: palette_order_from_c(self.palette_order)?,
+        });
     }
 
     pub fn from_option(src: &WorldEdit13SaveOption) -> Self {
         return CWE13SaveOption {
             compress_level: src.compress_level.level(),
-            background_block: src.background_block,
+            background_block: src.background_block as u16,
+            schem_version: src.schem_version.unwrap_or(0),
+            compress_method: src.compress_method as u8,
+            target_data_version: src.target_data_version.unwrap_or(0),
+            keep_unmapped_metadata: src.keep_unmapped_metadata,
+            palette_order: src.palette_order as u8,
         };
     }
 }
@@ -831,6 +1002,7 @@ impl CMetadata {
 
             litematica_version: self.litematica_version,
             litematica_subversion: self.litematica_subversion.to_option(),
+            litematica_preview: None,
 
             schem_version: self.schem_version,
             schem_offset: self.schem_offset,
@@ -841,6 +1013,8 @@ impl CMetadata {
             schem_editing_platform: self.schem_editing_platform.to_option_string(),
             schem_origin: self.schem_origin.to_option(),
             schem_material: self.schem_material.to_string(),
+            axiom_tags: Vec::new(),
+            axiom_thumbnail: None,
         };
     }
 }
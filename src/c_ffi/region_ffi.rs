@@ -77,7 +77,7 @@ extern "C" fn MC_SCHEM_entity_set_block_pos(entity: *mut Entity, pos: CPosInt) {
 #[no_mangle]
 extern "C" fn MC_SCHEM_entity_set_pos(entity: *mut Entity, pos: CPosDouble) {
     unsafe {
-        (*entity).position = pos.pos;
+        (*entity).set_position(pos.pos);
     }
 }
 
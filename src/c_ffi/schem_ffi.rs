@@ -28,7 +28,8 @@ use crate::schem::{
     VanillaStructureSaveOption, WorldEdit12LoadOption, WorldEdit13LoadOption,
     WorldEdit13SaveOption,
 };
-use crate::{Region, Schematic};
+use crate::{MaterialListOption, Region, Schematic};
+use std::ffi::c_void;
 use std::ptr::{drop_in_place, null, slice_from_raw_parts};
 
 #[no_mangle]
@@ -74,6 +75,7 @@ unsafe extern "C" fn MC_SCHEM_schem_load_litematica(
     return CSchemLoadResult::new(Schematic::from_litematica_reader(&mut src, &option));
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_load_litematica_file(
     filename: CStringView,
@@ -99,16 +101,33 @@ unsafe extern "C" fn MC_SCHEM_schem_load_vanilla_structure(
     mut src: CReader,
     option: *const CVanillaStructureLoadOption,
 ) -> CSchemLoadResult {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::new(Schematic::from_vanilla_structure_reader(&mut src, &option));
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_load_vanilla_structure_file(
     filename: CStringView,
     option: *const CVanillaStructureLoadOption,
 ) -> CSchemLoadResult {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::new(Schematic::from_vanilla_structure_file(
         filename.to_str(),
         &option,
@@ -122,7 +141,15 @@ unsafe extern "C" fn MC_SCHEM_schem_load_vanilla_structure_bytes(
     option: *const CVanillaStructureLoadOption,
 ) -> CSchemLoadResult {
     let bytes: &mut &[u8] = &mut &*slice_from_raw_parts(bytes, length);
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::new(Schematic::from_vanilla_structure_reader(bytes, &option));
 }
 
@@ -135,6 +162,7 @@ unsafe extern "C" fn MC_SCHEM_schem_load_world_edit_13(
     return CSchemLoadResult::new(Schematic::from_world_edit_13_reader(&mut src, &option));
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_load_world_edit_13_file(
     filename: CStringView,
@@ -163,16 +191,33 @@ unsafe extern "C" fn MC_SCHEM_schem_load_world_edit_12(
     mut src: CReader,
     option: *const CWE12LoadOption,
 ) -> CSchemLoadResult {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::from(Schematic::from_world_edit_12_reader(&mut src, &option));
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_load_world_edit_12_file(
     filename: CStringView,
     option: *const CWE12LoadOption,
 ) -> CSchemLoadResult {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::from(Schematic::from_world_edit_12_file(
         filename.to_str(),
         &option,
@@ -186,7 +231,15 @@ unsafe extern "C" fn MC_SCHEM_schem_load_world_edit_12_bytes(
     option: *const CWE12LoadOption,
 ) -> CSchemLoadResult {
     let bytes: &mut &[u8] = &mut &*slice_from_raw_parts(bytes, length);
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => {
+            return CSchemLoadResult {
+                schematic: None,
+                error: Some(Box::new(e)),
+            }
+        }
+    };
     return CSchemLoadResult::from(Schematic::from_world_edit_12_reader(bytes, &option));
 }
 
@@ -200,20 +253,27 @@ unsafe extern "C" fn MC_SCHEM_schem_save_litematica(
     mut dst: CWriter,
     option: *const CLitematicaSaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_litematica_writer(&mut dst, &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
     };
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_save_litematica_file(
     schem: *const Schematic,
     filename: CStringView,
     option: *const CLitematicaSaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_litematica_file(filename.to_str(), &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
@@ -225,25 +285,36 @@ extern "C" fn MC_SCHEM_save_option_vanilla_structure_default() -> CVanillaStruct
     return CVanillaStructureSaveOption::from_option(&VanillaStructureSaveOption::default());
 }
 #[no_mangle]
+extern "C" fn MC_SCHEM_save_option_vanilla_structure_create_mod() -> CVanillaStructureSaveOption {
+    return CVanillaStructureSaveOption::from_option(&VanillaStructureSaveOption::create_mod());
+}
+#[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_save_vanilla_structure(
     schem: *const Schematic,
     mut dst: CWriter,
     option: *const CVanillaStructureSaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_vanilla_structure_writer(&mut dst, &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
     };
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_save_vanilla_structure_file(
     schem: *const Schematic,
     filename: CStringView,
     option: *const CVanillaStructureSaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_vanilla_structure_file(filename.to_str(), &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
@@ -260,20 +331,27 @@ unsafe extern "C" fn MC_SCHEM_schem_save_world_edit_13(
     mut dst: CWriter,
     option: *const CWE13SaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_world_edit_13_writer(&mut dst, &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
     };
 }
 
+#[cfg(feature = "fs")]
 #[no_mangle]
 unsafe extern "C" fn MC_SCHEM_schem_save_world_edit_13_file(
     schem: *const Schematic,
     filename: CStringView,
     option: *const CWE13SaveOption,
 ) -> Option<Box<Error>> {
-    let option = (*option).to_option();
+    let option = match (*option).to_option() {
+        Ok(o) => o,
+        Err(e) => return Some(Box::new(e)),
+    };
     return match (*schem).save_world_edit_13_file(filename.to_str(), &option) {
         Ok(_) => None,
         Err(e) => Some(Box::new(e)),
@@ -401,3 +479,23 @@ unsafe extern "C" fn MC_SCHEM_schem_get_total_blocks(
 ) -> u64 {
     return (*schem).total_blocks(include_air);
 }
+
+type MaterialListCallback = extern "C" fn(handle: *mut c_void, id: CStringView, count: u64);
+
+#[no_mangle]
+unsafe extern "C" fn MC_SCHEM_schem_get_material_list(
+    schem: *const Schematic,
+    full_block_state: bool,
+    include_air: bool,
+    fun: MaterialListCallback,
+    handle: *mut c_void,
+) {
+    let option = MaterialListOption {
+        full_block_state,
+        include_air,
+        ..MaterialListOption::default()
+    };
+    for (id, count) in (*schem).material_list(&option) {
+        fun(handle, CStringView::from(&id), count);
+    }
+}
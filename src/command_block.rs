@@ -0,0 +1,281 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{BlockEntity, WorldSlice};
+use crate::schem::Schematic;
+use crate::transform::{EntityTransformer, Mirror, Rotation};
+use fastnbt::Value;
+
+/// A command block found by [`Schematic::command_blocks`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandBlockInfo {
+    /// Index of region
+    pub region: usize,
+    /// Relative position in region
+    pub pos: [i32; 3],
+    pub block: Block,
+    /// The `Command` tag, empty if it's missing
+    pub command: String,
+}
+
+/// Returns true for impulse, chain and repeating command blocks
+pub fn is_command_block(block: &Block) -> bool {
+    if !block.namespace.is_empty() && block.namespace != "minecraft" {
+        return false;
+    }
+    return matches!(
+        block.id.as_str(),
+        "command_block" | "chain_command_block" | "repeating_command_block"
+    );
+}
+
+/// Byte ranges of words that are separated by spaces and outside of any bracket or quote, so
+/// coordinates in selectors, nbt and json text are never touched.
+fn top_level_words(command: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    // start of current word, and whether it has brackets or quotes
+    let mut word: Option<(usize, bool)> = None;
+    for (idx, ch) in command.char_indices() {
+        if quote.is_none() && depth == 0 && ch == ' ' {
+            if let Some((start, false)) = word.take() {
+                words.push((start, idx));
+            }
+            word = None;
+            continue;
+        }
+        let (start, _) = *word.get_or_insert((idx, false));
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == q {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => quote = Some(ch),
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => depth = (depth - 1).max(0),
+            _ => continue,
+        }
+        word = Some((start, true));
+    }
+    if let Some((start, false)) = word {
+        words.push((start, command.len()));
+    }
+    return words;
+}
+
+/// A single coordinate like `12`, `-0.5`, `~` or `~3`
+#[derive(Debug, Clone, Copy)]
+enum Coordinate {
+    Absolute(f64),
+    Relative(f64),
+}
+
+impl Coordinate {
+    fn parse(word: &str) -> Option<Coordinate> {
+        if let Some(rest) = word.strip_prefix('~') {
+            if rest.is_empty() {
+                return Some(Coordinate::Relative(0.0));
+            }
+            return Self::parse_number(rest).map(Coordinate::Relative);
+        }
+        return Self::parse_number(word).map(Coordinate::Absolute);
+    }
+
+    fn parse_number(word: &str) -> Option<f64> {
+        let digits = word.strip_prefix('-').unwrap_or(word);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+        return word.parse().ok();
+    }
+
+    fn format_number(value: f64) -> String {
+        if value.fract() == 0.0 {
+            return format!("{}", value as i64);
+        }
+        return format!("{value}");
+    }
+
+    fn to_text(self) -> String {
+        return match self {
+            Coordinate::Absolute(v) => Self::format_number(v),
+            Coordinate::Relative(v) => {
+                if v == 0.0 {
+                    "~".to_string()
+                } else {
+                    format!("~{}", Self::format_number(v))
+                }
+            }
+        };
+    }
+}
+
+/// Find 3 consecutive top-level words that are all coordinates, and replace them with the result
+/// of `fix`, which returns `None` to keep them unchanged.
+fn rewrite_coordinates<F>(command: &str, mut fix: F) -> String
+where
+    F: FnMut([Coordinate; 3]) -> Option<[Coordinate; 3]>,
+{
+    let words = top_level_words(command);
+    let mut result = String::with_capacity(command.len());
+    let mut copied = 0;
+    let mut idx = 0;
+    while idx + 3 <= words.len() {
+        let triple = [words[idx], words[idx + 1], words[idx + 2]];
+        let parsed: Vec<Coordinate> = triple
+            .iter()
+            .filter_map(|(s, e)| Coordinate::parse(&command[*s..*e]))
+            .collect();
+        if parsed.len() != 3 {
+            idx += 1;
+            continue;
+        }
+        if let Some(fixed) = fix([parsed[0], parsed[1], parsed[2]]) {
+            for ((s, e), coord) in triple.iter().zip(fixed) {
+                result.push_str(&command[copied..*s]);
+                result.push_str(&coord.to_text());
+                copied = *e;
+            }
+        }
+        idx += 3;
+    }
+    result.push_str(&command[copied..]);
+    return result;
+}
+
+/// Rotate and mirror relative coordinates (`~x ~y ~z`) in a command, so that they point to the
+/// same block after the command block is rotated and mirrored with the schematic. Only triples
+/// whose x and z are both relative are changed, local coordinates (`^`) are kept.
+pub fn transform_command(command: &str, rotation: Rotation, mirror: Mirror) -> String {
+    let transformer = EntityTransformer::new(rotation, mirror, [1, 1, 1]);
+    return rewrite_coordinates(command, |coords| {
+        let (Coordinate::Relative(x), y, Coordinate::Relative(z)) =
+            (coords[0], coords[1], coords[2])
+        else {
+            return None;
+        };
+        let [x, _, z] = transformer.transform_vector([x, 0.0, z]);
+        // avoid -0
+        let (x, z) = (x + 0.0, z + 0.0);
+        return Some([Coordinate::Relative(x), y, Coordinate::Relative(z)]);
+    });
+}
+
+/// Add `offset` to absolute coordinates in a command, so that they follow the schematic when
+/// it's moved. Relative coordinates are kept because they move with the command block. Note that
+/// any 3 consecutive numbers are taken as a position.
+pub fn translate_command(command: &str, offset: [i32; 3]) -> String {
+    return rewrite_coordinates(command, |coords| {
+        let mut changed = false;
+        let mut result = coords;
+        for (coord, delta) in result.iter_mut().zip(offset) {
+            if let Coordinate::Absolute(v) = coord {
+                *v += delta as f64;
+                changed = true;
+            }
+        }
+        return if changed { Some(result) } else { None };
+    });
+}
+
+impl Schematic {
+    /// All command blocks with their commands, ordered by region, then y, z, x
+    pub fn command_blocks(&self) -> Vec<CommandBlockInfo> {
+        let mut result = Vec::new();
+        for (reg_idx, reg) in self.regions.iter().enumerate() {
+            let matched: Vec<bool> = reg.palette.iter().map(is_command_block).collect();
+            if !matched.contains(&true) {
+                continue;
+            }
+            for ((y, z, x), idx) in reg.array_yzx.indexed_iter() {
+                if !matched.get(*idx as usize).copied().unwrap_or(false) {
+                    continue;
+                }
+                let pos = [x as i32, y as i32, z as i32];
+                let command = match reg
+                    .block_entity_at(pos)
+                    .and_then(|be| be.tags.get("Command"))
+                {
+                    Some(Value::String(cmd)) => cmd.clone(),
+                    _ => String::new(),
+                };
+                result.push(CommandBlockInfo {
+                    region: reg_idx,
+                    pos,
+                    block: reg.palette[*idx as usize].clone(),
+                    command,
+                });
+            }
+        }
+        return result;
+    }
+
+    /// Call `rewrite` with every command block, and replace the command if it returns `Some`.
+    /// Command blocks without block entity get one. Returns the number of changed commands.
+    pub fn rewrite_commands<F>(&mut self, mut rewrite: F) -> usize
+    where
+        F: FnMut(&CommandBlockInfo) -> Option<String>,
+    {
+        let mut changed = 0;
+        for info in self.command_blocks() {
+            let Some(new_command) = rewrite(&info) else {
+                continue;
+            };
+            if new_command == info.command {
+                continue;
+            }
+            let reg = &mut self.regions[info.region];
+            let be = reg
+                .block_entities
+                .entry(info.pos)
+                .or_insert_with(BlockEntity::new);
+            if !be.tags.contains_key("id") {
+                be.tags.insert(
+                    "id".to_string(),
+                    Value::String("minecraft:command_block".to_string()),
+                );
+            }
+            be.tags
+                .insert("Command".to_string(), Value::String(new_command));
+            changed += 1;
+        }
+        return changed;
+    }
+
+    /// Apply [`transform_command`] to all command blocks. Call it when the schematic is rotated or
+    /// mirrored. Returns the number of changed commands.
+    pub fn transform_commands(&mut self, rotation: Rotation, mirror: Mirror) -> usize {
+        return self
+            .rewrite_commands(|info| Some(transform_command(&info.command, rotation, mirror)));
+    }
+
+    /// Apply [`translate_command`] to all command blocks. Call it when the schematic is placed
+    /// `offset` away from where it was built. Returns the number of changed commands.
+    pub fn translate_commands(&mut self, offset: [i32; 3]) -> usize {
+        return self.rewrite_commands(|info| Some(translate_command(&info.command, offset)));
+    }
+}
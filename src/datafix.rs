@@ -0,0 +1,609 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::block_entity::SignText;
+use crate::region::{BlockEntity, Region};
+use crate::schem::{DataVersion, Schematic};
+use std::cmp::max;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// Result of downgrading a block by a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DowngradeResult {
+    /// The block is not affected
+    Unchanged,
+    /// The block is changed to its old form
+    Changed,
+    /// The block doesn't exist in old versions, it's replaced by a similar block
+    Substituted,
+}
+
+/// A change of blocks in minecraft, applied when data version goes across `data_version`.
+pub struct BlockFixRule {
+    /// The first data version that uses the new form
+    pub data_version: i32,
+    /// What is changed
+    pub description: &'static str,
+    /// Changes block from the old form to the new form. Returns `false` if the block is not
+    /// affected.
+    pub upgrade: fn(&mut Block) -> bool,
+    /// Changes block from the new form to the old form
+    pub downgrade: fn(&mut Block) -> DowngradeResult,
+}
+
+/// Blocks substituted when downgrading
+#[derive(Debug, Clone, Default)]
+pub struct DowngradeReport {
+    /// Number of blocks in palettes that are changed to their old form
+    pub changed: usize,
+    /// Blocks that don't exist in the target version, and their substitutes
+    pub substituted: Vec<(Block, Block)>,
+}
+
+/// Callback to receive the [`DowngradeReport`] when a schematic is saved with an older
+/// `target_data_version`
+#[derive(Clone)]
+pub struct DowngradeCallback(Arc<dyn Fn(&DowngradeReport) + Send + Sync>);
+
+impl DowngradeCallback {
+    pub fn new(callback: impl Fn(&DowngradeReport) + Send + Sync + 'static) -> DowngradeCallback {
+        return DowngradeCallback(Arc::new(callback));
+    }
+}
+
+impl Debug for DowngradeCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "DowngradeCallback");
+    }
+}
+
+fn rename(block: &mut Block, from: &str, to: &str) -> bool {
+    if block.namespace != "minecraft" || block.id != from {
+        return false;
+    }
+    block.id = to.to_string();
+    return true;
+}
+
+fn unchanged(_block: &mut Block) -> bool {
+    return false;
+}
+
+fn renamed(changed: bool) -> DowngradeResult {
+    return if changed {
+        DowngradeResult::Changed
+    } else {
+        DowngradeResult::Unchanged
+    };
+}
+
+/// Replaces blocks of wood type in `woods` with oak
+fn substitute_wood(block: &mut Block, woods: &[&str]) -> DowngradeResult {
+    const SUFFIXES: [&str; 11] = [
+        "_planks",
+        "_stairs",
+        "_slab",
+        "_fence",
+        "_fence_gate",
+        "_door",
+        "_trapdoor",
+        "_button",
+        "_pressure_plate",
+        "_sign",
+        "_wall_sign",
+    ];
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    for wood in woods {
+        let rest = match block.id.strip_prefix(wood) {
+            Some(r) => r,
+            None => continue,
+        };
+        if SUFFIXES.contains(&rest) {
+            block.id = format!("oak{rest}");
+            return DowngradeResult::Substituted;
+        }
+    }
+    return DowngradeResult::Unchanged;
+}
+
+fn substitute(block: &mut Block, pairs: &[(&str, &str)]) -> DowngradeResult {
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    for (new_id, old_id) in pairs {
+        if block.id == *new_id {
+            block.id = old_id.to_string();
+            return DowngradeResult::Substituted;
+        }
+    }
+    return DowngradeResult::Unchanged;
+}
+
+fn upgrade_sign(block: &mut Block) -> bool {
+    return rename(block, "sign", "oak_sign") || rename(block, "wall_sign", "oak_wall_sign");
+}
+
+fn downgrade_sign(block: &mut Block) -> DowngradeResult {
+    if rename(block, "oak_sign", "sign") || rename(block, "oak_wall_sign", "wall_sign") {
+        return DowngradeResult::Changed;
+    }
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    // signs of other wood types are added in 1.14, and they become the old oak sign directly
+    for wood in ["spruce", "birch", "jungle", "acacia", "dark_oak"] {
+        let old_id = match block.id.strip_prefix(wood) {
+            Some("_sign") => "sign",
+            Some("_wall_sign") => "wall_sign",
+            _ => continue,
+        };
+        block.id = old_id.to_string();
+        return DowngradeResult::Substituted;
+    }
+    return DowngradeResult::Unchanged;
+}
+
+fn upgrade_stone_slab(block: &mut Block) -> bool {
+    return rename(block, "stone_slab", "smooth_stone_slab");
+}
+
+fn downgrade_stone_slab(block: &mut Block) -> DowngradeResult {
+    if block.namespace == "minecraft" && block.id == "stone_slab" {
+        // stone_slab of 1.14 is a new block, the old stone_slab is the smooth one
+        return DowngradeResult::Substituted;
+    }
+    return renamed(rename(block, "smooth_stone_slab", "stone_slab"));
+}
+
+fn upgrade_wall(block: &mut Block) -> bool {
+    if block.namespace != "minecraft" || !block.id.ends_with("_wall") {
+        return false;
+    }
+    let mut changed = false;
+    for dir in ["east", "north", "south", "west"] {
+        let new_value = match block.attributes.get(dir).map(|v| v.as_str()) {
+            Some("true") => "low",
+            Some("false") => "none",
+            _ => continue,
+        };
+        block.set_property(dir, new_value);
+        changed = true;
+    }
+    return changed;
+}
+
+fn downgrade_wall(block: &mut Block) -> DowngradeResult {
+    if block.namespace != "minecraft" || !block.id.ends_with("_wall") {
+        return DowngradeResult::Unchanged;
+    }
+    let mut changed = false;
+    for dir in ["east", "north", "south", "west"] {
+        let old_value = match block.attributes.get(dir).map(|v| v.as_str()) {
+            Some("low") | Some("tall") => "true",
+            Some("none") => "false",
+            _ => continue,
+        };
+        block.set_property(dir, old_value);
+        changed = true;
+    }
+    return renamed(changed);
+}
+
+fn downgrade_nether_update(block: &mut Block) -> DowngradeResult {
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    let result = substitute_wood(block, &["crimson", "warped"]);
+    if result != DowngradeResult::Unchanged {
+        return result;
+    }
+    for wood in ["crimson", "warped"] {
+        for (new_suffix, old_suffix) in [("_stem", "_log"), ("_hyphae", "_wood")] {
+            for prefix in ["", "stripped_"] {
+                if block.id == format!("{prefix}{wood}{new_suffix}") {
+                    block.id = format!("{prefix}oak{old_suffix}");
+                    return DowngradeResult::Substituted;
+                }
+            }
+        }
+    }
+    return substitute(
+        block,
+        &[
+            ("netherite_block", "diamond_block"),
+            ("ancient_debris", "netherrack"),
+            ("crying_obsidian", "obsidian"),
+            ("blackstone", "cobblestone"),
+            ("polished_blackstone", "stone"),
+            ("polished_blackstone_bricks", "stone_bricks"),
+            ("crimson_nylium", "netherrack"),
+            ("warped_nylium", "netherrack"),
+            ("warped_wart_block", "nether_wart_block"),
+            ("soul_soil", "soul_sand"),
+            ("lodestone", "stone"),
+        ],
+    );
+}
+
+fn upgrade_grass_path(block: &mut Block) -> bool {
+    return rename(block, "grass_path", "dirt_path");
+}
+
+fn upgrade_cauldron(block: &mut Block) -> bool {
+    if block.namespace != "minecraft" || block.id != "cauldron" {
+        return false;
+    }
+    let level = match block.attributes.remove("level") {
+        Some(l) => l,
+        None => return false,
+    };
+    if level != "0" {
+        block.id = "water_cauldron".to_string();
+        block.set_property("level", &level);
+    }
+    return true;
+}
+
+fn downgrade_cauldron(block: &mut Block) -> DowngradeResult {
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    match block.id.as_str() {
+        "cauldron" => {
+            block.set_property("level", "0");
+            return DowngradeResult::Changed;
+        }
+        "water_cauldron" => {
+            block.id = "cauldron".to_string();
+            return DowngradeResult::Changed;
+        }
+        "lava_cauldron" | "powder_snow_cauldron" => {
+            block.id = "cauldron".to_string();
+            block.attributes.clear();
+            block.set_property("level", "0");
+            return DowngradeResult::Substituted;
+        }
+        _ => return DowngradeResult::Unchanged,
+    }
+}
+
+fn downgrade_caves_and_cliffs(block: &mut Block) -> DowngradeResult {
+    if block.namespace != "minecraft" {
+        return DowngradeResult::Unchanged;
+    }
+    if let Some(ore) = block.id.strip_prefix("deepslate_") {
+        if ore.ends_with("_ore") && ore != "copper_ore" {
+            block.id = ore.to_string();
+            return DowngradeResult::Substituted;
+        }
+    }
+    if rename(block, "dirt_path", "grass_path") {
+        return DowngradeResult::Changed;
+    }
+    return substitute(
+        block,
+        &[
+            ("cobbled_deepslate", "cobblestone"),
+            ("polished_deepslate", "stone"),
+            ("deepslate_bricks", "stone_bricks"),
+            ("deepslate_tiles", "stone_bricks"),
+            ("tuff", "andesite"),
+            ("calcite", "diorite"),
+            ("copper_ore", "iron_ore"),
+            ("deepslate_copper_ore", "iron_ore"),
+            ("copper_block", "iron_block"),
+            ("raw_copper_block", "iron_block"),
+            ("raw_iron_block", "iron_block"),
+            ("raw_gold_block", "gold_block"),
+            ("amethyst_block", "purpur_block"),
+            ("moss_block", "grass_block"),
+            ("rooted_dirt", "coarse_dirt"),
+            ("smooth_basalt", "stone"),
+        ],
+    );
+}
+
+fn downgrade_wild_update(block: &mut Block) -> DowngradeResult {
+    let result = substitute_wood(block, &["mangrove"]);
+    if result != DowngradeResult::Unchanged {
+        return result;
+    }
+    return substitute(
+        block,
+        &[
+            ("mud", "dirt"),
+            ("packed_mud", "dirt"),
+            ("mud_bricks", "bricks"),
+            ("sculk", "black_wool"),
+            ("reinforced_deepslate", "bedrock"),
+        ],
+    );
+}
+
+fn downgrade_trails_and_tales(block: &mut Block) -> DowngradeResult {
+    let result = substitute_wood(block, &["cherry", "bamboo"]);
+    if result != DowngradeResult::Unchanged {
+        return result;
+    }
+    return substitute(block, &[("bamboo_mosaic", "oak_planks")]);
+}
+
+fn upgrade_grass(block: &mut Block) -> bool {
+    return rename(block, "grass", "short_grass");
+}
+
+fn downgrade_grass(block: &mut Block) -> DowngradeResult {
+    return renamed(rename(block, "short_grass", "grass"));
+}
+
+static RULES: [BlockFixRule; 9] = [
+    BlockFixRule {
+        data_version: DataVersion::Java_1_14 as i32,
+        description: "sign and wall_sign are renamed to oak_sign and oak_wall_sign",
+        upgrade: upgrade_sign,
+        downgrade: downgrade_sign,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_14 as i32,
+        description: "stone_slab is renamed to smooth_stone_slab",
+        upgrade: upgrade_stone_slab,
+        downgrade: downgrade_stone_slab,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_16 as i32,
+        description: "Sides of walls are changed from true/false to low/none",
+        upgrade: upgrade_wall,
+        downgrade: downgrade_wall,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_16 as i32,
+        description: "Nether blocks are added",
+        upgrade: unchanged,
+        downgrade: downgrade_nether_update,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_17 as i32,
+        description: "grass_path is renamed to dirt_path, deepslate and copper blocks are added",
+        upgrade: upgrade_grass_path,
+        downgrade: downgrade_caves_and_cliffs,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_17 as i32,
+        description: "cauldron with water is split into water_cauldron",
+        upgrade: upgrade_cauldron,
+        downgrade: downgrade_cauldron,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_19 as i32,
+        description: "Mangrove wood and mud blocks are added",
+        upgrade: unchanged,
+        downgrade: downgrade_wild_update,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_20 as i32,
+        description: "Cherry and bamboo wood are added",
+        upgrade: unchanged,
+        downgrade: downgrade_trails_and_tales,
+    },
+    BlockFixRule {
+        data_version: DataVersion::Java_1_20_3 as i32,
+        description: "grass is renamed to short_grass",
+        upgrade: upgrade_grass,
+        downgrade: downgrade_grass,
+    },
+];
+
+/// All rules, sorted by data version
+pub fn rules() -> &'static [BlockFixRule] {
+    return &RULES;
+}
+
+/// Upgrade a block from data version `from` to `to`. Returns `None` if the block is not changed.
+pub fn upgrade_block(block: &Block, from: i32, to: i32) -> Option<Block> {
+    let mut result = block.clone();
+    let mut changed = false;
+    for rule in rules() {
+        if from < rule.data_version && rule.data_version <= to {
+            changed |= (rule.upgrade)(&mut result);
+        }
+    }
+    return if changed { Some(result) } else { None };
+}
+
+/// Downgrade a block from data version `from` to `to`. Rules are applied from the newest to the
+/// oldest, and the result is the most lossy one of all rules.
+pub fn downgrade_block(block: &Block, from: i32, to: i32) -> (Block, DowngradeResult) {
+    let mut result = block.clone();
+    let mut res = DowngradeResult::Unchanged;
+    for rule in rules().iter().rev() {
+        if to < rule.data_version && rule.data_version <= from {
+            res = max(res, (rule.downgrade)(&mut result));
+        }
+    }
+    return (result, res);
+}
+
+/// Convert a block entity from data version `from` to `to`. Sign text is converted between the
+/// `Text1`..`Text4` format and the 1.20 `front_text`/`back_text` format, and the back side is
+/// dropped when downgrading. Returns whether the block entity is changed.
+pub fn fix_block_entity(be: &mut BlockEntity, from: i32, to: i32) -> bool {
+    let v1_20 = DataVersion::Java_1_20 as i32;
+    if (from >= v1_20) == (to >= v1_20) {
+        return false;
+    }
+    let is_sign = ["Text1", "front_text", "back_text"]
+        .iter()
+        .any(|key| be.tags.contains_key(*key));
+    if !is_sign {
+        return false;
+    }
+    if let Ok(text) = SignText::from_block_entity(be) {
+        text.write_to(be, to);
+        return true;
+    }
+    return false;
+}
+
+impl Region {
+    /// Upgrade blocks in palette from data version `from` to `to`, blocks that become the same
+    /// are merged. Block entities are converted by [`fix_block_entity`]. Returns the number of
+    /// changed blocks in palette.
+    pub fn upgrade_blocks(&mut self, from: i32, to: i32) -> usize {
+        let mut changed = 0;
+        let variants = self.alternate_palettes.iter_mut().flatten();
+        for blk in self.palette.iter_mut().chain(variants) {
+            if let Some(new_blk) = upgrade_block(blk, from, to) {
+                *blk = new_blk;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.invalidate_palette_lookup();
+            self.merge_duplicated_palette();
+        }
+        self.fix_block_entities(from, to);
+        return changed;
+    }
+
+    /// Downgrade blocks in palette from data version `from` to `to`, blocks that become the same
+    /// are merged. Block entities are converted by [`fix_block_entity`]. Substituted blocks are
+    /// appended to `report`.
+    pub fn downgrade_blocks(&mut self, from: i32, to: i32, report: &mut DowngradeReport) {
+        let mut changed = false;
+        let variants = self.alternate_palettes.iter_mut().flatten();
+        for blk in self.palette.iter_mut().chain(variants) {
+            let (new_blk, res) = downgrade_block(blk, from, to);
+            match res {
+                DowngradeResult::Unchanged => continue,
+                DowngradeResult::Changed => report.changed += 1,
+                DowngradeResult::Substituted => {
+                    report.substituted.push((blk.clone(), new_blk.clone()))
+                }
+            }
+            *blk = new_blk;
+            changed = true;
+        }
+        if changed {
+            self.invalidate_palette_lookup();
+            self.merge_duplicated_palette();
+        }
+        self.fix_block_entities(from, to);
+    }
+
+    fn fix_block_entities(&mut self, from: i32, to: i32) {
+        for be in self.block_entities.values_mut() {
+            fix_block_entity(be, from, to);
+        }
+    }
+
+    /// Entries are merged only if they are the same in all palette variants
+    fn merge_duplicated_palette(&mut self) {
+        let mut first_index: HashMap<Vec<&Block>, u16> = HashMap::with_capacity(self.palette.len());
+        let mut lut: Vec<u16> = Vec::with_capacity(self.palette.len());
+        let mut kept: Vec<usize> = Vec::with_capacity(self.palette.len());
+        for (old_idx, blk) in self.palette.iter().enumerate() {
+            let mut key = vec![blk];
+            key.extend(
+                self.alternate_palettes
+                    .iter()
+                    .filter_map(|v| v.get(old_idx)),
+            );
+            let idx = *first_index.entry(key).or_insert_with(|| {
+                kept.push(old_idx);
+                (kept.len() - 1) as u16
+            });
+            lut.push(idx);
+        }
+        if kept.len() == self.palette.len() {
+            return;
+        }
+        for blk_id in &mut self.array_yzx {
+            *blk_id = lut[*blk_id as usize];
+        }
+        self.palette = kept.iter().map(|idx| self.palette[*idx].clone()).collect();
+        for variant in &mut self.alternate_palettes {
+            *variant = kept
+                .iter()
+                .filter_map(|idx| variant.get(*idx).cloned())
+                .collect();
+        }
+    }
+}
+
+impl Schematic {
+    /// Upgrade blocks of all regions to `data_version`, and set the data version in metadata.
+    /// Nothing happens if `data_version` is not newer than the current one. Returns the number of
+    /// changed blocks in palettes.
+    pub fn upgrade_to(&mut self, data_version: i32) -> usize {
+        let from = self.metadata.mc_data_version;
+        if data_version <= from {
+            return 0;
+        }
+        let mut changed = 0;
+        for reg in &mut self.regions {
+            changed += reg.upgrade_blocks(from, data_version);
+        }
+        self.metadata.mc_data_version = data_version;
+        return changed;
+    }
+
+    /// Downgrade blocks of all regions to `data_version`, and set the data version in metadata.
+    /// Nothing happens if `data_version` is not older than the current one. Blocks that don't
+    /// exist in `data_version` are substituted by similar ones, and listed in the report.
+    pub fn downgrade_to(&mut self, data_version: i32) -> DowngradeReport {
+        let mut report = DowngradeReport::default();
+        let from = self.metadata.mc_data_version;
+        if data_version >= from {
+            return report;
+        }
+        for reg in &mut self.regions {
+            reg.downgrade_blocks(from, data_version, &mut report);
+        }
+        self.metadata.mc_data_version = data_version;
+        return report;
+    }
+
+    /// Returns a copy upgraded or downgraded to `target`, or `None` if no conversion is needed.
+    /// Used by writers with a target data version in save options, the report of downgrading is
+    /// passed to `on_downgrade`.
+    pub(crate) fn converted_to(
+        &self,
+        target: Option<i32>,
+        on_downgrade: Option<&DowngradeCallback>,
+    ) -> Option<Schematic> {
+        let target = target?;
+        if target == self.metadata.mc_data_version {
+            return None;
+        }
+        let mut result = self.clone();
+        if target > self.metadata.mc_data_version {
+            result.upgrade_to(target);
+        } else {
+            let report = result.downgrade_to(target);
+            if let Some(cb) = on_downgrade {
+                (cb.0)(&report);
+            }
+        }
+        return Some(result);
+    }
+}
@@ -0,0 +1,264 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{Region, WorldSlice};
+
+/// Call `fun` on every block in the cuboid between corners `a` and `b`, inclusive. Only positions
+/// inside `[0, shape)` are visited, so the loop never runs beyond the region.
+fn for_each_in_cuboid(a: [i32; 3], b: [i32; 3], shape: [i32; 3], mut fun: impl FnMut([i32; 3])) {
+    let min: [i32; 3] = std::array::from_fn(|dim| a[dim].min(b[dim]).max(0));
+    let max: [i32; 3] = std::array::from_fn(|dim| a[dim].max(b[dim]).min(shape[dim] - 1));
+    for x in min[0]..=max[0] {
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                fun([x, y, z]);
+            }
+        }
+    }
+}
+
+/// Call `fun` on every block of the 6 faces of the cuboid between corners `a` and `b`.
+fn for_each_on_cuboid_faces(
+    a: [i32; 3],
+    b: [i32; 3],
+    shape: [i32; 3],
+    mut fun: impl FnMut([i32; 3]),
+) {
+    let min = [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])];
+    let max = [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])];
+    for_each_in_cuboid(min, max, shape, |pos| {
+        let on_face = (0..3).any(|dim| pos[dim] == min[dim] || pos[dim] == max[dim]);
+        if on_face {
+            fun(pos);
+        }
+    });
+}
+
+/// Call `fun` on every block of a line from `a` to `b`, both ends included. The line is clipped
+/// to `[0, shape)` first, so that the loop doesn't run over the part far out of the region.
+fn for_each_on_line(a: [i32; 3], b: [i32; 3], shape: [i32; 3], mut fun: impl FnMut([i32; 3])) {
+    let delta: [i64; 3] = std::array::from_fn(|dim| b[dim] as i64 - a[dim] as i64);
+    let steps = delta.iter().map(|d| d.abs()).max().unwrap();
+    if steps == 0 {
+        fun(a);
+        return;
+    }
+    // step s is at a + delta * s / steps, keep the steps that may round into the region
+    let mut first = 0;
+    let mut last = steps;
+    for dim in 0..3 {
+        let start = a[dim] as f64;
+        let (lower, upper) = (-0.5, shape[dim] as f64 - 0.5);
+        if delta[dim] == 0 {
+            if start < lower || start > upper {
+                return;
+            }
+            continue;
+        }
+        let scale = steps as f64 / delta[dim] as f64;
+        let s0 = (lower - start) * scale;
+        let s1 = (upper - start) * scale;
+        first = first.max(s0.min(s1).floor() as i64);
+        last = last.min(s0.max(s1).ceil() as i64);
+    }
+    for step in first..=last {
+        let t = step as f64 / steps as f64;
+        let pos: [i32; 3] = std::array::from_fn(|dim| {
+            (a[dim] as i64 + (delta[dim] as f64 * t).round() as i64) as i32
+        });
+        fun(pos);
+    }
+}
+
+/// Call `fun` on every block whose center is within `radius` of the center of block `center`.
+fn for_each_in_sphere(
+    center: [i32; 3],
+    radius: f64,
+    shape: [i32; 3],
+    mut fun: impl FnMut([i32; 3]),
+) {
+    let r = radius.max(0.0).floor() as i32;
+    for_each_in_cuboid(
+        center.map(|c| c.saturating_sub(r)),
+        center.map(|c| c.saturating_add(r)),
+        shape,
+        |pos| {
+            if in_sphere(pos, center, radius) {
+                fun(pos);
+            }
+        },
+    );
+}
+
+/// Call `fun` on every block of a vertical cylinder, whose bottom center is `base_center`.
+/// Negative `height` extends downwards.
+fn for_each_in_cylinder(
+    base_center: [i32; 3],
+    radius: f64,
+    height: i32,
+    shape: [i32; 3],
+    mut fun: impl FnMut([i32; 3]),
+) {
+    if height == 0 {
+        return;
+    }
+    let r = radius.max(0.0).floor() as i32;
+    let top = base_center[1].saturating_add(height - height.signum());
+    for_each_in_cuboid(
+        [
+            base_center[0].saturating_sub(r),
+            base_center[1],
+            base_center[2].saturating_sub(r),
+        ],
+        [
+            base_center[0].saturating_add(r),
+            top,
+            base_center[2].saturating_add(r),
+        ],
+        shape,
+        |pos| {
+            if in_disc(pos, base_center, radius) {
+                fun(pos);
+            }
+        },
+    );
+}
+
+fn in_sphere(pos: [i32; 3], center: [i32; 3], radius: f64) -> bool {
+    let d2: f64 = (0..3)
+        .map(|dim| (pos[dim] as f64 - center[dim] as f64).powi(2))
+        .sum();
+    return d2 <= radius * radius;
+}
+
+fn in_disc(pos: [i32; 3], center: [i32; 3], radius: f64) -> bool {
+    let dx = pos[0] as f64 - center[0] as f64;
+    let dz = pos[2] as f64 - center[2] as f64;
+    return dx * dx + dz * dz <= radius * radius;
+}
+
+impl Region {
+    /// Set `block` at every position produced by `for_each` that is inside the region, and remove
+    /// the block entities and pending ticks of overwritten blocks. `for_each` receives the shape
+    /// of region to clip its loop. Returns the number of blocks set.
+    fn draw_with(
+        &mut self,
+        block: &Block,
+        for_each: impl FnOnce([i32; 3], &mut dyn FnMut([i32; 3])),
+    ) -> usize {
+        let shape = self.shape();
+        // the block is added to palette on the first write, so drawing out of the region doesn't
+        // leave an unused palette entry
+        let mut blk_id = None;
+        let mut counter = 0;
+        for_each(shape, &mut |pos| {
+            if (0..3).all(|dim| pos[dim] >= 0 && pos[dim] < shape[dim]) {
+                let id = *blk_id.get_or_insert_with(|| self.find_or_append_to_palette(block));
+                self.array_yzx[[pos[1] as usize, pos[2] as usize, pos[0] as usize]] = id;
+                self.block_entities.remove(&pos);
+                self.pending_ticks.remove(&pos);
+                counter += 1;
+            }
+        });
+        return counter;
+    }
+
+    /// Draw a line from `a` to `b` (relative positions, both ends included). Blocks outside the
+    /// region are skipped. Returns the number of blocks set.
+    pub fn draw_line(&mut self, a: [i32; 3], b: [i32; 3], block: &Block) -> usize {
+        return self.draw_with(block, |shape, fun| for_each_on_line(a, b, shape, fun));
+    }
+
+    /// Draw the cuboid between corners `a` and `b` (relative positions, inclusive). If `hollow`,
+    /// only the 6 faces are drawn and the inside is not changed. Blocks outside the region are
+    /// skipped. Returns the number of blocks set.
+    pub fn draw_cuboid(&mut self, a: [i32; 3], b: [i32; 3], block: &Block, hollow: bool) -> usize {
+        return if hollow {
+            self.draw_with(block, |shape, fun| {
+                for_each_on_cuboid_faces(a, b, shape, fun)
+            })
+        } else {
+            self.draw_with(block, |shape, fun| for_each_in_cuboid(a, b, shape, fun))
+        };
+    }
+
+    /// Draw a sphere containing blocks whose centers are within `radius` of the center of block
+    /// `center`. If `hollow`, only blocks with a neighbor outside the sphere are drawn. Blocks
+    /// outside the region are skipped. Returns the number of blocks set.
+    pub fn draw_sphere(
+        &mut self,
+        center: [i32; 3],
+        radius: f64,
+        block: &Block,
+        hollow: bool,
+    ) -> usize {
+        return self.draw_with(block, |shape, fun| {
+            for_each_in_sphere(center, radius, shape, |pos| {
+                if hollow {
+                    let on_surface = NEIGHBORS.iter().any(|d| {
+                        !in_sphere(
+                            [pos[0] + d[0], pos[1] + d[1], pos[2] + d[2]],
+                            center,
+                            radius,
+                        )
+                    });
+                    if !on_surface {
+                        return;
+                    }
+                }
+                fun(pos);
+            })
+        });
+    }
+
+    /// Draw a vertical cylinder whose bottom center is `base_center`, negative `height` extends
+    /// downwards. If `hollow`, only the side wall is drawn, without top and bottom. Blocks
+    /// outside the region are skipped. Returns the number of blocks set.
+    pub fn draw_cylinder(
+        &mut self,
+        base_center: [i32; 3],
+        radius: f64,
+        height: i32,
+        block: &Block,
+        hollow: bool,
+    ) -> usize {
+        return self.draw_with(block, |shape, fun| {
+            for_each_in_cylinder(base_center, radius, height, shape, |pos| {
+                if hollow {
+                    let on_wall = NEIGHBORS.iter().filter(|d| d[1] == 0).any(|d| {
+                        !in_disc([pos[0] + d[0], pos[1], pos[2] + d[2]], base_center, radius)
+                    });
+                    if !on_wall {
+                        return;
+                    }
+                }
+                fun(pos);
+            })
+        });
+    }
+}
+
+const NEIGHBORS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
@@ -19,8 +19,9 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 use fastnbt::{ByteArray, IntArray, LongArray, Value};
 use serde::de::StdError;
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::ops::Range;
+use std::sync::Mutex;
 // use serde::Deserializer;
 use crate::block::{Block, BlockIdParseError};
 use crate::item::Item;
@@ -28,6 +29,8 @@ use crate::old_block::OldBlockParseError;
 use crate::region::Region;
 use crate::schem::common::{format_range, format_size};
 use crate::schem::id_of_nbt_tag;
+use crate::schem::CompressMethod;
+use crate::SchemFormat;
 use strum::Display;
 
 /// Errors when loading and saving schematic
@@ -95,6 +98,9 @@ pub enum Error {
     UnrecognisedExtension {
         extension: String,
     },
+    UnsupportedSaveFormat {
+        format: SchemFormat,
+    },
     //write error
     NBTWriteError(fastnbt::error::Error),
     NegativeSize {
@@ -107,6 +113,17 @@ pub enum Error {
         max_index: u16,
     },
     FileCreateError(std::io::Error),
+    InvalidBitsPerBlock {
+        region_name: String,
+        bits: u8,
+        palette_size: usize,
+    },
+    BlockStatesVerifyFailed {
+        region_name: String,
+        index: usize,
+        expected: u16,
+        found: u64,
+    },
     DuplicatedRegionName {
         name: String,
     },
@@ -135,6 +152,7 @@ pub enum Error {
         compress_label: u8,
     },
     IOReadError(std::io::Error),
+    #[cfg(feature = "fs")]
     SevenZipDecompressError(sevenz_rust::Error),
     NoSuchFile {
         filename: String,
@@ -174,6 +192,89 @@ pub enum Error {
         former: (Item, String),
         latter: (Item, String),
     },
+    UnknownBlockProperty {
+        block: Block,
+        property: String,
+    },
+    InvalidBlockPropertyValue {
+        block: Block,
+        property: String,
+        value: String,
+        allowed_values: Vec<String>,
+    },
+    SNBTParseError {
+        /// Byte offset in text
+        position: usize,
+        detail: String,
+    },
+    ValueTooLargeForBits {
+        index: usize,
+        value: u64,
+        bits: u8,
+    },
+    NoOldBlockEquivalent {
+        block: Block,
+        pos: [i32; 3],
+    },
+    /// The cancellation flag in options was set
+    Cancelled,
+    /// Region `first` and `second` share blocks from `lower` to `upper`(global coordinates)
+    RegionsOverlap {
+        first: usize,
+        second: usize,
+        lower: [i32; 3],
+        upper: [i32; 3],
+    },
+    UnsupportedLitematicaVersion {
+        version: i32,
+        supported_versions: Vec<i32>,
+    },
+    /// The saved file takes `size` bytes, more than `max_size` allowed by options
+    FileTooLarge {
+        size: u64,
+        max_size: u64,
+    },
+    /// The compression is not compiled in, enable the feature of the same name
+    CompressMethodDisabled {
+        method: CompressMethod,
+    },
+    /// Same as `NBTReadError`, but located in uncompressed nbt
+    NBTReadErrorAt {
+        error: fastnbt::error::Error,
+        /// Byte offset in uncompressed nbt where parsing stopped
+        position: usize,
+        /// Path of the innermost tag that starts before `position`, `None` if the root tag is
+        /// broken
+        tag_path: Option<String>,
+    },
+    /// A value to write doesn't fit in the tag type of the format, like `TotalVolume` of litematica
+    /// which is an int
+    MetadataOverflow {
+        tag_path: String,
+        value: u64,
+        max: u64,
+    },
+    /// Blocks placed from y `y_range[0]` to `y_range[1]` don't fit in the build height of the
+    /// world, from `world_y_range[0]` to `world_y_range[1]`
+    OutOfWorldHeight {
+        y_range: [i32; 2],
+        world_y_range: [i32; 2],
+    },
+    /// The schematic spans `size`, and it has more than `max_volume` blocks
+    VolumeTooLarge {
+        size: [u64; 3],
+        max_volume: u64,
+    },
+    /// `block` is in the `minecraft` namespace, but it's not in the complete block registry of
+    /// `data_version`
+    UnknownBlock {
+        block: Block,
+        data_version: i32,
+    },
+    /// An option requires cargo feature `feature`, which is disabled
+    FeatureDisabled {
+        feature: &'static str,
+    },
     CustomError(String),
 }
 
@@ -211,6 +312,8 @@ impl Display for Error {
             => write!(f, "Invalid number id at {tag_path}, detail: {detail}"),
             Error::UnrecognisedExtension { extension }
             => write!(f, "Unrecognised extension {extension}, can not deduce schematic format from filename extension, try loading with explicit format."),
+            Error::UnsupportedSaveFormat { format }
+            => write!(f, "Saving as {format}({}) is not supported", format.extension()),
 
             Error::NBTWriteError(err) => write!(f, "Failed to write nbt, detail: {}", err),
             Error::NegativeSize { size, region_name }
@@ -220,6 +323,10 @@ impl Display for Error {
                       format_size(r_pos), block_index, max_index),
             Error::FileCreateError(err)
             => write!(f, "Failed to create file, detail: {}", err),
+            Error::InvalidBitsPerBlock { region_name, bits, palette_size }
+            => write!(f, "Region \"{region_name}\" can not be saved with {bits} bits per block, the palette has {palette_size} blocks. Bits per block should be in range [1, 16], hold all block indices, and can be detected from length of BlockStates"),
+            Error::BlockStatesVerifyFailed { region_name, index, expected, found }
+            => write!(f, "Verifying encoded blocks of region \"{region_name}\" failed, block index at {index} should be {expected}, but decoded {found}"),
             Error::DuplicatedRegionName { name }
             => write!(f, "More than one region used name \"{}\"", name),
             Error::SizeTooLarge { size, max_size }
@@ -238,6 +345,7 @@ impl Display for Error {
             => write!(f, "Invalid compress type {compress_label}, valid values: [1, 2, 3, 128, 129, 130]"),
             Error::IOReadError(e)
             => write!(f, "IOReadError, detail: {e}"),
+            #[cfg(feature = "fs")]
             Error::SevenZipDecompressError(e7z)
             => write!(f, "7z decompress failed, detail: {e7z}"),
             Error::NoSuchFile { filename, expected_to_exist_in }
@@ -256,13 +364,166 @@ impl Display for Error {
             => write!(f, "The y range of dimension {dimension_id} is {}, but y range of chunk ({exception_chunk_x}, {exception_chunk_z}) is {}", format_range(dimension_y_range), format_range(exception_value)),
             Error::MultipleItemsInOneSlot { slot, former, latter }
             => write!(f, "Found multiple items in slot {slot} when parsing inventory, the former is {:?}, defined at {}, the latter is {:?}, defined at {}", former.0, former.1, latter.0, latter.1),
+            Error::UnknownBlockProperty { block, property }
+            => write!(f, "Block {block} has unknown property \"{property}\""),
+            Error::InvalidBlockPropertyValue { block, property, value, allowed_values }
+            => write!(f, "Block {block} has invalid value \"{value}\" for property \"{property}\", allowed values: {:?}", allowed_values),
+            Error::SNBTParseError { position, detail }
+            => write!(f, "Failed to parse SNBT at byte {position}: {detail}"),
+            Error::ValueTooLargeForBits { index, value, bits }
+            => write!(f, "Value {value} at index {index} can not be stored in {bits} bits"),
+            Error::NoOldBlockEquivalent { block, pos }
+            => write!(f, "Block {block} at {pos:?} has no equivalent number id in 1.12"),
+            Error::Cancelled => write!(f, "Operation cancelled"),
+            Error::RegionsOverlap { first, second, lower, upper }
+            => write!(f, "Region {first} and region {second} overlap from {lower:?} to {upper:?}"),
+            Error::UnsupportedLitematicaVersion { version, supported_versions }
+            => write!(f, "Litematica format version(not minecraft version) {version} is not supported, supported versions: {supported_versions:?}"),
+            Error::FileTooLarge { size, max_size }
+            => write!(f, "File size {size} bytes exceeds the limit of {max_size} bytes"),
+            Error::CompressMethodDisabled { method }
+            => write!(f, "Compress method {method:?} is disabled, enable the corresponding feature of mc_schem to use it"),
+            Error::NBTReadErrorAt { error, position, tag_path: Some(tag_path) }
+            => write!(f, "NBT format broken near byte {position}, in or after tag {tag_path}. Detail: {error}"),
+            Error::NBTReadErrorAt { error, position, tag_path: None }
+            => write!(f, "NBT format broken near byte {position}. Detail: {error}"),
+            Error::MetadataOverflow { tag_path, value, max }
+            => write!(f, "Value {value} of tag {tag_path} exceeds the max value {max} of its tag type"),
+            Error::OutOfWorldHeight { y_range, world_y_range }
+            => write!(f, "Blocks from y={} to y={} are out of the world height from y={} to y={}", y_range[0], y_range[1], world_y_range[0], world_y_range[1]),
+            Error::VolumeTooLarge { size, max_volume }
+            => write!(f, "Schematic size {} exceeds the limit of {max_volume} blocks", format_size(size)),
+            Error::UnknownBlock { block, data_version }
+            => write!(f, "Block {block} doesn't exist in data version {data_version}"),
+            Error::FeatureDisabled { feature }
+            => write!(f, "Feature {feature} is disabled, enable it in mc_schem to use this option"),
             Error::CustomError(s)
             => write!(f, "Custom error : \"{s}\"")
         };
     }
 }
 
-impl StdError for Error {}
+impl Error {
+    /// Stable numeric code of the error, 0 is reserved for no error. Codes of existing variants
+    /// never change, new variants get new codes.
+    pub fn code(&self) -> u16 {
+        return match self {
+            Error::NBTReadError(..) => 1,
+            Error::TagMissing(..) => 2,
+            Error::TagTypeMismatch { .. } => 3,
+            Error::InvalidValue { .. } => 4,
+            Error::InvalidBlockId { .. } => 5,
+            Error::InvalidBlockProperty { .. } => 6,
+            Error::PaletteIsEmpty { .. } => 7,
+            Error::PaletteTooLong(..) => 8,
+            Error::BlockIndexOutOfRange { .. } => 9,
+            Error::BlockPosOutOfRange { .. } => 10,
+            Error::FileOpenError(..) => 11,
+            Error::MultipleBlockEntityInOnePos { .. } => 12,
+            Error::MultiplePendingTickInOnePos { .. } => 13,
+            Error::ConflictingIndexInPalette { .. } => 14,
+            Error::BlockDataIncomplete { .. } => 15,
+            Error::InvalidBlockNumberId { .. } => 16,
+            Error::UnrecognisedExtension { .. } => 17,
+            Error::UnsupportedSaveFormat { .. } => 18,
+            Error::NBTWriteError(..) => 19,
+            Error::NegativeSize { .. } => 20,
+            Error::BlockIndexOutOfRangeWriting { .. } => 21,
+            Error::FileCreateError(..) => 22,
+            Error::InvalidBitsPerBlock { .. } => 23,
+            Error::BlockStatesVerifyFailed { .. } => 24,
+            Error::DuplicatedRegionName { .. } => 25,
+            Error::SizeTooLarge { .. } => 26,
+            Error::UnsupportedVersion { .. } => 27,
+            Error::UnsupportedWorldEdit13Version { .. } => 28,
+            Error::IncompleteSegmentInMCA { .. } => 29,
+            Error::InvalidSegmentRangeInMCA { .. } => 30,
+            Error::InvalidMCACompressType { .. } => 31,
+            Error::IOReadError(..) => 32,
+            #[cfg(feature = "fs")]
+            Error::SevenZipDecompressError(..) => 33,
+            Error::NoSuchFile { .. } => 34,
+            Error::InvalidBiome { .. } => 35,
+            Error::InvalidChunkStatus { .. } => 36,
+            Error::MissingSubChunk { .. } => 37,
+            Error::MissingMCCFile { .. } => 38,
+            Error::DifferentYRangeInOneDimension { .. } => 39,
+            Error::IncorrectYRangeInChunk { .. } => 40,
+            Error::MultipleItemsInOneSlot { .. } => 41,
+            Error::UnknownBlockProperty { .. } => 42,
+            Error::InvalidBlockPropertyValue { .. } => 43,
+            Error::CustomError(..) => 44,
+            Error::SNBTParseError { .. } => 45,
+            Error::ValueTooLargeForBits { .. } => 46,
+            Error::NoOldBlockEquivalent { .. } => 47,
+            Error::Cancelled => 48,
+            Error::RegionsOverlap { .. } => 49,
+            Error::UnsupportedLitematicaVersion { .. } => 50,
+            Error::FileTooLarge { .. } => 51,
+            Error::CompressMethodDisabled { .. } => 52,
+            Error::NBTReadErrorAt { .. } => 53,
+            Error::MetadataOverflow { .. } => 54,
+            Error::OutOfWorldHeight { .. } => 55,
+            Error::VolumeTooLarge { .. } => 56,
+            Error::UnknownBlock { .. } => 57,
+            Error::FeatureDisabled { .. } => 58,
+        };
+    }
+
+    /// Full nbt path of the tag where the error happens, like `/Regions/main/BlockStates`.
+    /// For errors about 2 tags, it's the latter one.
+    pub fn tag_path(&self) -> Option<&str> {
+        return match self {
+            Error::TagMissing(tag_path)
+            | Error::TagTypeMismatch { tag_path, .. }
+            | Error::InvalidValue { tag_path, .. }
+            | Error::InvalidBlockProperty { tag_path, .. }
+            | Error::PaletteIsEmpty { tag_path }
+            | Error::BlockIndexOutOfRange { tag_path, .. }
+            | Error::BlockPosOutOfRange { tag_path, .. }
+            | Error::BlockDataIncomplete { tag_path, .. }
+            | Error::InvalidBlockNumberId { tag_path, .. }
+            | Error::InvalidBiome { tag_path, .. }
+            | Error::InvalidChunkStatus { tag_path, .. }
+            | Error::MissingSubChunk { tag_path, .. }
+            | Error::MetadataOverflow { tag_path, .. } => Some(tag_path),
+            Error::MultipleBlockEntityInOnePos {
+                latter_tag_path, ..
+            }
+            | Error::MultiplePendingTickInOnePos {
+                latter_tag_path, ..
+            } => Some(latter_tag_path),
+            Error::NBTReadErrorAt { tag_path, .. } => tag_path.as_deref(),
+            _ => None,
+        };
+    }
+
+    /// Byte offset in the source text or data where the error happens, if known.
+    pub fn byte_offset(&self) -> Option<usize> {
+        return match self {
+            Error::SNBTParseError { position, .. } | Error::NBTReadErrorAt { position, .. } => {
+                Some(*position)
+            }
+            _ => None,
+        };
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        return match self {
+            Error::NBTReadError(e)
+            | Error::NBTWriteError(e)
+            | Error::NBTReadErrorAt { error: e, .. } => Some(e),
+            Error::FileOpenError(e) | Error::FileCreateError(e) | Error::IOReadError(e) => Some(e),
+            #[cfg(feature = "fs")]
+            Error::SevenZipDecompressError(e) => Some(e),
+            Error::InvalidBlockId { reason, .. } => Some(reason),
+            Error::InvalidBlockNumberId { detail, .. } => Some(detail),
+            _ => None,
+        };
+    }
+}
 
 impl serde::de::Error for Error {
     fn custom<T>(msg: T) -> Self
@@ -823,6 +1084,41 @@ impl<T> ErrorHandleResult<T> {
             ErrorHandleResult::HandledWithoutWarning(val) => Some(val),
         };
     }
+
+    /// Returns the fixed value and records a warning if required, or `error` if not handled.
+    pub(crate) fn resolve(
+        self,
+        error: Error,
+        fix: impl FnOnce(&T) -> String,
+        warnings: &mut Vec<LoadWarning>,
+    ) -> Result<T, Error> {
+        return match self {
+            ErrorHandleResult::NotHandled => Err(error),
+            ErrorHandleResult::HandledWithoutWarning(val) => Ok(val),
+            ErrorHandleResult::HandledWithWarning(val) => {
+                warnings.push(LoadWarning {
+                    fix: fix(&val),
+                    error,
+                });
+                Ok(val)
+            }
+        };
+    }
+}
+
+/// A recoverable error fixed by an `ErrorHandler` when loading
+#[derive(Debug)]
+pub struct LoadWarning {
+    /// The fixed error
+    pub error: Error,
+    /// How it's fixed
+    pub fix: String,
+}
+
+impl Display for LoadWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}, fixed by: {}", self.error, self.fix);
+    }
 }
 
 /// Not used now.
@@ -832,16 +1128,132 @@ pub enum BlockPosOutOfRangeFixMethod {
     FixPos([i32; 3]),
 }
 
-/// Not used now.
+/// How to fix `MultipleBlockEntityInOnePos`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultipleBlockEntityFixMethod {
+    KeepFormer,
+    KeepLatter,
+}
+
+/// Fix returned by an [`ErrorHandlerFn`], the variant must match the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fix {
+    /// Block index to use instead, for `BlockIndexOutOfRange`
+    BlockIndex(u16),
+    /// For `MultipleBlockEntityInOnePos`
+    MultipleBlockEntity(MultipleBlockEntityFixMethod),
+}
+
+type ErrorHandlerClosure = dyn FnMut(&Error, &mut Vec<Block>) -> ErrorHandleResult<Fix> + Send;
+
+/// Closure to fix recoverable errors when loading, used by load options. It receives the error
+/// and palette of the region being loaded, and is asked before the `ErrorHandler` type. A fix
+/// that doesn't match the error is treated as `NotHandled`.
+pub struct ErrorHandlerFn(Mutex<Box<ErrorHandlerClosure>>);
+
+impl ErrorHandlerFn {
+    pub fn new(
+        handler: impl FnMut(&Error, &mut Vec<Block>) -> ErrorHandleResult<Fix> + Send + 'static,
+    ) -> ErrorHandlerFn {
+        return ErrorHandlerFn(Mutex::new(Box::new(handler)));
+    }
+
+    fn call(&self, error: &Error, palette: &mut Vec<Block>) -> ErrorHandleResult<Fix> {
+        let mut handler = match self.0.lock() {
+            Ok(h) => h,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        return handler(error, palette);
+    }
+}
+
+impl Debug for ErrorHandlerFn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "ErrorHandlerFn");
+    }
+}
+
+fn map_fix<T>(
+    res: ErrorHandleResult<Fix>,
+    extract: impl Fn(Fix) -> Option<T>,
+) -> ErrorHandleResult<T> {
+    let (fix, warn) = match res {
+        ErrorHandleResult::NotHandled => return ErrorHandleResult::NotHandled,
+        ErrorHandleResult::HandledWithWarning(fix) => (fix, true),
+        ErrorHandleResult::HandledWithoutWarning(fix) => (fix, false),
+    };
+    return match (extract(fix), warn) {
+        (None, _) => ErrorHandleResult::NotHandled,
+        (Some(val), true) => ErrorHandleResult::HandledWithWarning(val),
+        (Some(val), false) => ErrorHandleResult::HandledWithoutWarning(val),
+    };
+}
+
+/// Asks the closure first, then `H`. An index out of the palette is not a fix, so the original
+/// error is kept.
+pub(crate) fn fix_block_index_out_of_range<H: ErrorHandler>(
+    handler: Option<&ErrorHandlerFn>,
+    palette: &mut Vec<Block>,
+    error: &Error,
+) -> ErrorHandleResult<u16> {
+    if let Some(handler) = handler {
+        let res = map_fix(handler.call(error, palette), |fix| match fix {
+            Fix::BlockIndex(idx) if (idx as usize) < palette.len() => Some(idx),
+            _ => None,
+        });
+        if res.has_value() {
+            return res;
+        }
+    }
+    return match H::fix_block_index_out_of_range(palette, error) {
+        ErrorHandleResult::HandledWithWarning(idx)
+        | ErrorHandleResult::HandledWithoutWarning(idx)
+            if (idx as usize) >= palette.len() =>
+        {
+            ErrorHandleResult::NotHandled
+        }
+        res => res,
+    };
+}
+
+/// Asks the closure first, then `H`
+pub(crate) fn fix_multiple_block_entity_in_one_pos<H: ErrorHandler>(
+    handler: Option<&ErrorHandlerFn>,
+    palette: &mut Vec<Block>,
+    error: &Error,
+) -> ErrorHandleResult<MultipleBlockEntityFixMethod> {
+    if let Some(handler) = handler {
+        let res = map_fix(handler.call(error, palette), |fix| match fix {
+            Fix::MultipleBlockEntity(method) => Some(method),
+            _ => None,
+        });
+        if res.has_value() {
+            return res;
+        }
+    }
+    return H::fix_multiple_block_entity_in_one_pos(error);
+}
+
+/// Decides how to fix recoverable errors. Block index and block entity errors are fixed by the
+/// litematica and vanilla structure loaders, and `fix_no_old_block_equivalent` by the
+/// `.schematic` writer.
 pub trait ErrorHandler {
-    // returns the fixed block index
+    /// Returns the block index to use instead, it may be appended to `palette`.
+    /// `error` is `BlockIndexOutOfRange`.
     fn fix_block_index_out_of_range(
-        _region: &mut Region,
+        _palette: &mut Vec<Block>,
         _error: &Error,
     ) -> ErrorHandleResult<u16> {
         return ErrorHandleResult::NotHandled;
     }
 
+    /// Decides which block entity is kept. `error` is `MultipleBlockEntityInOnePos`.
+    fn fix_multiple_block_entity_in_one_pos(
+        _error: &Error,
+    ) -> ErrorHandleResult<MultipleBlockEntityFixMethod> {
+        return ErrorHandleResult::NotHandled;
+    }
+
     fn fix_block_pos_out_of_range(
         _region: &mut Region,
         _error: &Error,
@@ -852,21 +1264,48 @@ pub trait ErrorHandler {
     fn fix_invalid_block_id(_region: &mut Region, _error: &Error) -> ErrorHandleResult<Block> {
         return ErrorHandleResult::NotHandled;
     }
+
+    /// Returns the number id and damage to save instead, when saving `.schematic`.
+    /// `error` is `NoOldBlockEquivalent`.
+    fn fix_no_old_block_equivalent(_error: &Error) -> ErrorHandleResult<(u8, u8)> {
+        return ErrorHandleResult::NotHandled;
+    }
 }
 
-/// Not used now.
+/// Fixes nothing, every error is returned.
 pub struct StrictErrorHandler {}
 
 impl ErrorHandler for StrictErrorHandler {}
 
-/// Not used now.
+/// Fixes errors when possible, with warnings.
 pub struct DefaultErrorHandler {}
 
 impl ErrorHandler for DefaultErrorHandler {
-    fn fix_block_index_out_of_range(region: &mut Region, error: &Error) -> ErrorHandleResult<u16> {
+    /// Invalid block indices are replaced by air
+    fn fix_block_index_out_of_range(
+        palette: &mut Vec<Block>,
+        error: &Error,
+    ) -> ErrorHandleResult<u16> {
         if let Error::BlockIndexOutOfRange { .. } = error {
-            let air_id = region.find_or_append_to_palette(&Block::air());
-            return ErrorHandleResult::HandledWithWarning(air_id);
+            let air = Block::air();
+            let air_id = match palette.iter().position(|b| b == &air) {
+                Some(idx) => idx,
+                None => {
+                    palette.push(air);
+                    palette.len() - 1
+                }
+            };
+            return ErrorHandleResult::HandledWithWarning(air_id as u16);
+        }
+        return ErrorHandleResult::NotHandled;
+    }
+
+    /// The latter block entity overwrites the former
+    fn fix_multiple_block_entity_in_one_pos(
+        error: &Error,
+    ) -> ErrorHandleResult<MultipleBlockEntityFixMethod> {
+        if let Error::MultipleBlockEntityInOnePos { .. } = error {
+            return ErrorHandleResult::HandledWithWarning(MultipleBlockEntityFixMethod::KeepLatter);
         }
         return ErrorHandleResult::NotHandled;
     }
@@ -881,4 +1320,9 @@ impl ErrorHandler for DefaultErrorHandler {
     // fn fix_invalid_block_id(_region: &mut Region, _error: &LoadError) -> ErrorHandleResult<Block> {
     //     return ErrorHandleResult::NotHandled;
     // }
+
+    /// Blocks without number id are saved as air
+    fn fix_no_old_block_equivalent(_error: &Error) -> ErrorHandleResult<(u8, u8)> {
+        return ErrorHandleResult::HandledWithWarning((0, 0));
+    }
 }
@@ -0,0 +1,274 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use ndarray::Array2;
+use strum::{Display, EnumString};
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{Region, WorldSlice};
+use crate::render::RgbaImage;
+
+/// Kinds of heightmaps, with the same names as in chunk nbt. Without block registry, blocks
+/// without collision are guessed by id, so the result is close to the game but not exact.
+#[derive(Debug, Display, EnumString, Copy, Clone, Eq, PartialEq, Hash)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+pub enum HeightmapKind {
+    /// Highest block that is not air
+    WorldSurface,
+    /// Highest block that blocks motion or contains fluid
+    MotionBlocking,
+    /// Same as `MotionBlocking`, but leaves are skipped
+    MotionBlockingNoLeaves,
+    /// Highest block that blocks motion, fluids are skipped
+    OceanFloor,
+}
+
+fn is_empty(block: &Block) -> bool {
+    return block.is_air()
+        || block.is_structure_void()
+        || (block.namespace == "minecraft" && (block.id == "cave_air" || block.id == "void_air"));
+}
+
+fn has_fluid(block: &Block) -> bool {
+    return block.id == "water"
+        || block.id == "lava"
+        || block.id == "bubble_column"
+        || block.id == "kelp"
+        || block.id == "kelp_plant"
+        || block.id == "seagrass"
+        || block.id == "tall_seagrass"
+        || block.is_waterlogged();
+}
+
+/// Guess if a block has collision by its id
+fn blocks_motion(block: &Block) -> bool {
+    let id = block.id.as_str();
+    const PASSABLE: [&str; 25] = [
+        "water",
+        "lava",
+        "bubble_column",
+        "kelp",
+        "kelp_plant",
+        "seagrass",
+        "tall_seagrass",
+        "short_grass",
+        "grass",
+        "tall_grass",
+        "fern",
+        "large_fern",
+        "dead_bush",
+        "vine",
+        "glow_lichen",
+        "redstone_wire",
+        "tripwire",
+        "tripwire_hook",
+        "lever",
+        "ladder",
+        "fire",
+        "soul_fire",
+        "cobweb",
+        "snow",
+        "light",
+    ];
+    if is_empty(block) || PASSABLE.contains(&id) {
+        return false;
+    }
+    const PASSABLE_SUFFIXES: [&str; 10] = [
+        "torch",
+        "rail",
+        "_button",
+        "_pressure_plate",
+        "sign",
+        "_sapling",
+        "_banner",
+        "_flower",
+        "_tulip",
+        "_mushroom",
+    ];
+    if PASSABLE_SUFFIXES.iter().any(|s| id.ends_with(s)) && !id.ends_with("_block") {
+        return false;
+    }
+    const FLOWERS: [&str; 12] = [
+        "dandelion",
+        "poppy",
+        "blue_orchid",
+        "allium",
+        "azure_bluet",
+        "oxeye_daisy",
+        "cornflower",
+        "lily_of_the_valley",
+        "wither_rose",
+        "sunflower",
+        "lilac",
+        "peony",
+    ];
+    return !FLOWERS.contains(&id) && !id.ends_with("rose_bush");
+}
+
+impl HeightmapKind {
+    /// If a block counts in this kind of heightmap
+    pub fn counts(&self, block: &Block) -> bool {
+        return match self {
+            HeightmapKind::WorldSurface => !is_empty(block),
+            HeightmapKind::MotionBlocking => blocks_motion(block) || has_fluid(block),
+            HeightmapKind::MotionBlockingNoLeaves => {
+                (blocks_motion(block) || has_fluid(block)) && !block.id.ends_with("leaves")
+            }
+            HeightmapKind::OceanFloor => blocks_motion(block),
+        };
+    }
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Compute a heightmap of the region, indexed by `[z, x]`. Like the game, every value is the
+    /// relative y of the highest counted block plus 1, and 0 if the column has no such block.
+    pub fn heightmap(&self, kind: HeightmapKind) -> Array2<i32> {
+        let counted: Vec<bool> = self.palette.iter().map(|blk| kind.counts(blk)).collect();
+        let [shape_x, shape_y, shape_z] = self.shape().map(|s| s as usize);
+        return Array2::from_shape_fn((shape_z, shape_x), |(z, x)| {
+            for y in (0..shape_y).rev() {
+                let blk_id = self.array_yzx[[y, z, x]];
+                if counted.get(blk_id as usize).copied().unwrap_or(false) {
+                    return y as i32 + 1;
+                }
+            }
+            return 0;
+        });
+    }
+
+    /// Heightmap as a grayscale image, see [`heightmap_to_image`]. The region height is taken as
+    /// white.
+    pub fn heightmap_image(&self, kind: HeightmapKind) -> RgbaImage {
+        return heightmap_to_image(&self.heightmap(kind), self.shape()[1]);
+    }
+}
+
+/// Convert a heightmap indexed by `[z, x]` to a grayscale image, with x to the right and z
+/// downward like top-down renders. Heights are scaled so that 0 is black and `max_height` is
+/// white, and values out of the range are clamped. Save it with [`RgbaImage::write_png`].
+pub fn heightmap_to_image(heights: &Array2<i32>, max_height: i32) -> RgbaImage {
+    let (size_z, size_x) = heights.dim();
+    let mut image = RgbaImage::new(size_x as u32, size_z as u32, [0, 0, 0, 255]);
+    for ((z, x), h) in heights.indexed_iter() {
+        let gray = if max_height <= 0 {
+            0
+        } else {
+            (*h.clamp(&0, &max_height) as i64 * 255 / max_height as i64) as u8
+        };
+        image.set_pixel(x as u32, z as u32, [gray, gray, gray, 255]);
+    }
+    return image;
+}
+
+/// Options of [`terrain_from_heights`] and [`terrain_from_image`]
+#[derive(Debug, Clone)]
+pub struct TerrainOption {
+    /// The top block of every column
+    pub surface: Block,
+    /// Blocks under the surface
+    pub filler: Block,
+    /// Blocks under the filler, down to y = 0
+    pub bottom: Block,
+    /// Thickness of the filler layer
+    pub filler_depth: i32,
+    /// Blocks per unit of height. For images, a unit is one level of gray in 0..=255
+    pub vertical_scale: f64,
+    /// Blocks added to every column, so that the lowest height still has a surface
+    pub base_height: i32,
+}
+
+impl Default for TerrainOption {
+    fn default() -> Self {
+        return TerrainOption {
+            surface: Block::from_id("grass_block").unwrap(),
+            filler: Block::from_id("dirt").unwrap(),
+            bottom: Block::from_id("stone").unwrap(),
+            filler_depth: 3,
+            vertical_scale: 1.0,
+            base_height: 1,
+        };
+    }
+}
+
+/// Highest column a terrain may have, the same as the build height of the game
+const MAX_TERRAIN_HEIGHT: i64 = 4064;
+
+/// Build a terrain region from heights indexed by `[z, x]`. Every column is
+/// `base_height + round(height * vertical_scale)` blocks high, made of the bottom, filler and
+/// surface blocks from y = 0 upward, and columns of height 0 are air. \
+/// Returns `InvalidValue` if the scale or a height is not finite, or a column exceeds 4064 blocks.
+pub fn terrain_from_heights(
+    heights: &Array2<f64>,
+    option: &TerrainOption,
+) -> Result<Region, Error> {
+    if !option.vertical_scale.is_finite() || option.vertical_scale < 0.0 {
+        return Err(Error::InvalidValue {
+            tag_path: "vertical_scale".to_string(),
+            error: format!(
+                "vertical_scale should be finite and non-negative, but found {}",
+                option.vertical_scale
+            ),
+        });
+    }
+    let mut columns = Array2::<i32>::zeros(heights.dim());
+    for ((z, x), h) in heights.indexed_iter() {
+        let column = (h * option.vertical_scale).round() + option.base_height as f64;
+        if !column.is_finite() || column > MAX_TERRAIN_HEIGHT as f64 {
+            return Err(Error::InvalidValue {
+                tag_path: format!("heights[{z}, {x}]"),
+                error: format!(
+                    "column height should be finite and no more than {MAX_TERRAIN_HEIGHT}, but found {column}"
+                ),
+            });
+        }
+        columns[[z, x]] = column.max(0.0) as i32;
+    }
+    let (size_z, size_x) = columns.dim();
+    let size_y = columns.iter().copied().max().unwrap_or(0).max(1);
+
+    let mut region = Region::with_shape([size_x as i32, size_y, size_z as i32]);
+    let surface = region.find_or_append_to_palette(&option.surface);
+    let filler = region.find_or_append_to_palette(&option.filler);
+    let bottom = region.find_or_append_to_palette(&option.bottom);
+    for ((z, x), top) in columns.indexed_iter() {
+        for y in 0..*top {
+            let blk_id = if y == top - 1 {
+                surface
+            } else if y >= top - 1 - option.filler_depth {
+                filler
+            } else {
+                bottom
+            };
+            region.array_yzx[[y as usize, z, x]] = blk_id;
+        }
+    }
+    return Ok(region);
+}
+
+/// Build a terrain region from a grayscale image, such as a digital elevation map. The image is
+/// read like [`heightmap_to_image`] writes: x to the right, z downward, and the luminance of
+/// every pixel is the height. See [`terrain_from_heights`] for the rest.
+pub fn terrain_from_image(image: &RgbaImage, option: &TerrainOption) -> Result<Region, Error> {
+    let heights = Array2::from_shape_fn((image.height as usize, image.width as usize), |(z, x)| {
+        let [r, g, b, _] = image.pixel(x as u32, z as u32).unwrap_or([0; 4]);
+        return 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    });
+    return terrain_from_heights(&heights, option);
+}
@@ -0,0 +1,91 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Handle of a block in a [`BlockInterner`]. Comparing or hashing handles only touches the
+/// index, handles from different interners must not be mixed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct InternedBlock(u32);
+
+impl InternedBlock {
+    /// Index in the interner
+    pub fn index(&self) -> u32 {
+        return self.0;
+    }
+}
+
+/// Palette of distinct blocks owned by the caller. Every distinct block is stored only once, so
+/// equal blocks (even from different regions or schematics) share one allocation. Blocks are
+/// freed when the interner is dropped.
+#[derive(Debug, Default, Clone)]
+pub struct BlockInterner {
+    blocks: Vec<Arc<Block>>,
+    index_of_block: HashMap<Arc<Block>, u32>,
+}
+
+impl BlockInterner {
+    pub fn new() -> BlockInterner {
+        return BlockInterner::default();
+    }
+
+    /// Intern `block`, returns the existing handle if an equal block is interned before.
+    /// Returns `PaletteTooLong` if there are already `u32::MAX` blocks.
+    pub fn intern(&mut self, block: &Block) -> Result<InternedBlock, Error> {
+        if let Some(idx) = self.index_of_block.get(block) {
+            return Ok(InternedBlock(*idx));
+        }
+        let Ok(idx) = u32::try_from(self.blocks.len()) else {
+            return Err(Error::PaletteTooLong(self.blocks.len()));
+        };
+        let shared = Arc::new(block.clone());
+        self.blocks.push(shared.clone());
+        self.index_of_block.insert(shared, idx);
+        return Ok(InternedBlock(idx));
+    }
+
+    /// Intern all blocks of a palette, the result has the same order as `blocks`.
+    pub fn intern_all(&mut self, blocks: &[Block]) -> Result<Vec<InternedBlock>, Error> {
+        return blocks.iter().map(|blk| self.intern(blk)).collect();
+    }
+
+    /// Handle of `block` if it's interned
+    pub fn find(&self, block: &Block) -> Option<InternedBlock> {
+        return self
+            .index_of_block
+            .get(block)
+            .map(|idx| InternedBlock(*idx));
+    }
+
+    /// The interned block, `None` if `handle` doesn't come from this interner.
+    pub fn block(&self, handle: InternedBlock) -> Option<&Arc<Block>> {
+        return self.blocks.get(handle.0 as usize);
+    }
+
+    /// Count of blocks interned
+    pub fn len(&self) -> usize {
+        return self.blocks.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.blocks.is_empty();
+    }
+}
@@ -1,42 +1,113 @@
 use crate::error::{unwrap_opt_i8, unwrap_opt_string};
 use crate::schem::id_of_nbt_tag;
-use crate::{unwrap_tag, Error};
+use crate::{unwrap_tag, Error, Region, Schematic};
 use fastnbt::Value;
 use serde::Deserialize;
 use std::collections::{BTreeMap, HashMap};
 //use crate::error::NBTWithPath;
 
-#[derive(Debug, Clone, Default, Deserialize)]
+/// An item stack, as stored in inventories of block entities and entities. Both the pre-1.20.5
+/// format (`Count` byte and `tag` compound) and the 1.20.5+ format (`count` int and `components`
+/// compound) are supported.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct Item {
     #[serde(rename = "Count")]
     pub count: i8,
+    /// Namespaced id, like `minecraft:diamond`
     pub id: String,
-    #[serde(rename = "tag")]
+    /// Extra nbt of pre-1.20.5 items
+    #[serde(rename = "tag", default)]
     pub tags: HashMap<String, Value>,
+    /// Data components of 1.20.5+ items. If this is `Some`, the item is encoded in the new format.
+    #[serde(skip)]
+    pub components: Option<HashMap<String, Value>>,
 }
 
 impl Item {
+    /// Create an item stack without tag or components, encoded in the pre-1.20.5 format
+    pub fn new(id: &str, count: i8) -> Item {
+        return Item {
+            count,
+            id: id.to_string(),
+            tags: HashMap::new(),
+            components: None,
+        };
+    }
+
+    /// Parse from an item compound, detecting the format by the case of the count tag.
     pub fn from_nbt(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<Item, Error> {
-        let count = unwrap_opt_i8(nbt, "Count", tag_path)?;
         let id = unwrap_opt_string(nbt, "id", tag_path)?.clone();
+        if nbt.contains_key("count") || nbt.contains_key("components") {
+            let count = if let Some(c) = nbt.get("count") {
+                *unwrap_tag!(c, Int, 0, format!("{tag_path}/count"))
+            } else {
+                1
+            };
+            let components = if let Some(c) = nbt.get("components") {
+                unwrap_tag!(
+                    c,
+                    Compound,
+                    HashMap::new(),
+                    format!("{tag_path}/components")
+                )
+                .clone()
+            } else {
+                HashMap::new()
+            };
+            return Ok(Item {
+                // stacks never exceed 99 items
+                count: count.clamp(i8::MIN as i32, i8::MAX as i32) as i8,
+                id,
+                tags: HashMap::new(),
+                components: Some(components),
+            });
+        }
+
+        let count = unwrap_opt_i8(nbt, "Count", tag_path)?;
         let tags = if let Some(t) = nbt.get("tag") {
             unwrap_tag!(t, Compound, HashMap::new(), format!("{tag_path}/tag")).clone()
         } else {
             HashMap::new()
         };
+        return Ok(Item {
+            count,
+            id,
+            tags,
+            components: None,
+        });
+    }
 
-        Ok(Item { count, id, tags })
+    /// Write id, count and tag/components into an item compound, keeping other tags like `Slot`.
+    pub fn write_to(&self, nbt: &mut HashMap<String, Value>) {
+        for key in ["Count", "count", "tag", "components"] {
+            nbt.remove(key);
+        }
+        nbt.insert("id".to_string(), Value::String(self.id.clone()));
+        if let Some(components) = &self.components {
+            nbt.insert("count".to_string(), Value::Int(self.count as i32));
+            if !components.is_empty() {
+                nbt.insert(
+                    "components".to_string(),
+                    Value::Compound(components.clone()),
+                );
+            }
+        } else {
+            nbt.insert("Count".to_string(), Value::Byte(self.count));
+            if !self.tags.is_empty() {
+                nbt.insert("tag".to_string(), Value::Compound(self.tags.clone()));
+            }
+        }
+    }
 
-        // let nbt = Value::Compound(nbt);
-        // let deserializer = NBTWithPath {
-        //     nbt: &nbt,
-        //     path: tag_path.to_string(),
-        // };
-        //
-        // return Item::deserialize(deserializer);
+    /// Encode the item into nbt, without slot index
+    pub fn to_nbt(&self) -> HashMap<String, Value> {
+        let mut nbt = HashMap::with_capacity(3);
+        self.write_to(&mut nbt);
+        return nbt;
     }
 }
 
+/// Items indexed by slot
 #[derive(Debug, Clone, Default)]
 pub struct Inventory(pub BTreeMap<i8, Item>);
 
@@ -62,4 +133,234 @@ impl Inventory {
         }
         return Ok(Self(result));
     }
+
+    /// Encode the inventory into a list of item compounds with `Slot` tags
+    pub fn to_nbt(&self) -> Vec<Value> {
+        let mut result = Vec::with_capacity(self.0.len());
+        for (slot, item) in &self.0 {
+            let mut nbt = item.to_nbt();
+            nbt.insert("Slot".to_string(), Value::Byte(*slot));
+            result.push(Value::Compound(nbt));
+        }
+        return result;
+    }
+}
+
+/// Tags of block entities and entities that hold a list of items
+const ITEM_LIST_KEYS: [&str; 5] = [
+    "Items",
+    "Inventory",
+    "HandItems",
+    "ArmorItems",
+    "EnderItems",
+];
+/// Tags of block entities and entities that hold a single item
+const ITEM_KEYS: [&str; 6] = [
+    "Item",
+    "RecordItem",
+    "Book",
+    "SaddleItem",
+    "ArmorItem",
+    "DecorItem",
+];
+
+/// Callback on an item compound and its nbt path
+type ItemVisitor<'a> = dyn FnMut(&mut HashMap<String, Value>, &str) + 'a;
+
+/// Visit every item compound held by `tags`, including items in nested containers like shulker
+/// boxes. Children are visited before the item that contains them.
+fn visit_items_mut(tags: &mut HashMap<String, Value>, tag_path: &str, fun: &mut ItemVisitor) {
+    for key in ITEM_LIST_KEYS {
+        if let Some(Value::List(list)) = tags.get_mut(key) {
+            for (idx, item) in list.iter_mut().enumerate() {
+                if let Value::Compound(item) = item {
+                    visit_item_mut(item, &format!("{tag_path}/{key}/[{idx}]"), fun);
+                }
+            }
+        }
+    }
+    for key in ITEM_KEYS {
+        if let Some(Value::Compound(item)) = tags.get_mut(key) {
+            visit_item_mut(item, &format!("{tag_path}/{key}"), fun);
+        }
+    }
+}
+
+fn visit_item_mut(item: &mut HashMap<String, Value>, tag_path: &str, fun: &mut ItemVisitor) {
+    if !item.contains_key("id") {
+        return;
+    }
+    if let Some(Value::Compound(tag)) = item.get_mut("tag") {
+        if let Some(Value::Compound(be)) = tag.get_mut("BlockEntityTag") {
+            visit_items_mut(be, &format!("{tag_path}/tag/BlockEntityTag"), fun);
+        }
+    }
+    if let Some(Value::Compound(components)) = item.get_mut("components") {
+        if let Some(Value::List(list)) = components.get_mut("minecraft:container") {
+            for (idx, slot) in list.iter_mut().enumerate() {
+                if let Value::Compound(slot) = slot {
+                    if let Some(Value::Compound(nested)) = slot.get_mut("item") {
+                        let path =
+                            format!("{tag_path}/components/minecraft:container/[{idx}]/item");
+                        visit_item_mut(nested, &path, fun);
+                    }
+                }
+            }
+        }
+    }
+    fun(item, tag_path);
+}
+
+/// Where an item is stored in a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemHolder {
+    /// In the block entity at this position
+    BlockEntity([i32; 3]),
+    /// In the entity at this index of `Region::entities`
+    Entity(usize),
+}
+
+/// An item found by [`Region::find_items`]
+#[derive(Debug, Clone)]
+pub struct FoundItem {
+    /// Block entity or entity that holds the item
+    pub holder: ItemHolder,
+    /// Nbt path of the item compound inside the holder
+    pub tag_path: String,
+    /// Parsed item
+    pub item: Item,
+}
+
+impl Region {
+    /// Tags of all block entities and entities, which may hold items
+    fn item_holders(&self) -> impl Iterator<Item = (ItemHolder, &HashMap<String, Value>)> {
+        let block_entities = self
+            .block_entities
+            .iter()
+            .map(|(pos, be)| (ItemHolder::BlockEntity(*pos), &be.tags));
+        let entities = self
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(idx, e)| (ItemHolder::Entity(idx), &e.tags));
+        return block_entities.chain(entities);
+    }
+
+    /// Call `fun` for every item in block entities and entities. `fun` returns whether the item is
+    /// modified, and modified items are written back. Returns number of modified items. If any
+    /// item fails to parse, nothing is changed.
+    pub fn modify_items(
+        &mut self,
+        mut fun: impl FnMut(ItemHolder, &mut Item) -> bool,
+    ) -> Result<usize, Error> {
+        let mut counter = 0;
+        let mut error = None;
+        // edited tags of every changed holder, applied after all items are parsed
+        let mut edited: Vec<(ItemHolder, HashMap<String, Value>)> = Vec::new();
+        for (holder, tags) in self.item_holders() {
+            let mut tags = tags.clone();
+            let mut modified = 0;
+            visit_items_mut(&mut tags, "", &mut |nbt, tag_path| {
+                if error.is_some() {
+                    return;
+                }
+                match Item::from_nbt(nbt, tag_path) {
+                    Ok(mut item) => {
+                        if fun(holder, &mut item) {
+                            item.write_to(nbt);
+                            modified += 1;
+                        }
+                    }
+                    Err(e) => error = Some(e),
+                }
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+            if modified > 0 {
+                counter += modified;
+                edited.push((holder, tags));
+            }
+        }
+        for (holder, tags) in edited {
+            match holder {
+                ItemHolder::BlockEntity(pos) => {
+                    if let Some(be) = self.block_entities.get_mut(&pos) {
+                        be.tags = tags;
+                    }
+                }
+                ItemHolder::Entity(idx) => self.entities[idx].tags = tags,
+            }
+        }
+        return Ok(counter);
+    }
+
+    /// Find all items in block entities and entities that satisfy `pred`.
+    pub fn find_items(&self, pred: impl Fn(&Item) -> bool) -> Result<Vec<FoundItem>, Error> {
+        let mut result = Vec::new();
+        let mut error = None;
+        for (holder, tags) in self.item_holders() {
+            // the visitor only exists in mutable form, so walk a copy
+            let mut tags = tags.clone();
+            visit_items_mut(&mut tags, "", &mut |nbt, tag_path| {
+                if error.is_some() {
+                    return;
+                }
+                match Item::from_nbt(nbt, tag_path) {
+                    Ok(item) => {
+                        if pred(&item) {
+                            result.push(FoundItem {
+                                holder,
+                                tag_path: tag_path.to_string(),
+                                item,
+                            });
+                        }
+                    }
+                    Err(e) => error = Some(e),
+                }
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+        return Ok(result);
+    }
+
+    /// Change id of all items with id `from` to `to`, keeping counts and tags. Returns number of
+    /// replaced item stacks.
+    pub fn replace_items(&mut self, from: &str, to: &str) -> Result<usize, Error> {
+        return self.modify_items(|_, item| {
+            if item.id != from {
+                return false;
+            }
+            item.id = to.to_string();
+            return true;
+        });
+    }
+}
+
+impl Schematic {
+    /// Find items satisfying `pred` in all regions, with the index of region.
+    pub fn find_items(
+        &self,
+        pred: impl Fn(&Item) -> bool,
+    ) -> Result<Vec<(usize, FoundItem)>, Error> {
+        let mut result = Vec::new();
+        for (idx, reg) in self.regions.iter().enumerate() {
+            for found in reg.find_items(&pred)? {
+                result.push((idx, found));
+            }
+        }
+        return Ok(result);
+    }
+
+    /// Change id of all items with id `from` to `to` in all regions. Returns number of replaced
+    /// item stacks.
+    pub fn replace_items(&mut self, from: &str, to: &str) -> Result<usize, Error> {
+        let mut counter = 0;
+        for reg in &mut self.regions {
+            counter += reg.replace_items(from, to)?;
+        }
+        return Ok(counter);
+    }
 }
@@ -0,0 +1,143 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! JavaScript bindings, enabled by feature `js`. Build for `wasm32-unknown-unknown` with
+//! `--no-default-features --features js`, then generate the glue code with `wasm-bindgen`.
+
+use crate::error::Error;
+use crate::region::WorldSlice;
+use crate::{Block, MaterialListOption, SchemFormat, Schematic};
+use wasm_bindgen::prelude::*;
+
+fn to_js_err(err: Error) -> JsError {
+    return JsError::new(&err.to_string());
+}
+
+/// Schematic with one or more regions
+#[wasm_bindgen(js_name = Schematic)]
+pub struct JsSchematic {
+    inner: Schematic,
+}
+
+#[wasm_bindgen(js_class = Schematic)]
+impl JsSchematic {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsSchematic {
+        return JsSchematic {
+            inner: Schematic::new(),
+        };
+    }
+
+    /// Load schematic from bytes of a file, the format is detected from content. `filename` is
+    /// used only if the content is ambiguous.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8], filename: Option<String>) -> Result<JsSchematic, JsError> {
+        let (schem, _, _) =
+            Schematic::from_bytes_detect_format(bytes, filename.as_deref()).map_err(to_js_err)?;
+        return Ok(JsSchematic { inner: schem });
+    }
+
+    /// Save schematic to bytes, the format is deduced from `filename`, like `a.litematic`
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self, filename: &str) -> Result<Vec<u8>, JsError> {
+        let format = match SchemFormat::from_filename(filename) {
+            Some(f) => f,
+            None => {
+                return Err(to_js_err(Error::UnrecognisedExtension {
+                    extension: filename.rsplit('.').next().unwrap_or("").to_string(),
+                }))
+            }
+        };
+        return self.inner.to_bytes(&format).map_err(to_js_err);
+    }
+
+    #[wasm_bindgen(getter, js_name = dataVersion)]
+    pub fn data_version(&self) -> i32 {
+        return self.inner.metadata.mc_data_version;
+    }
+
+    /// Enclosing shape of all regions, in x, y, z
+    #[wasm_bindgen(getter)]
+    pub fn shape(&self) -> Vec<i32> {
+        return self.inner.shape().to_vec();
+    }
+
+    pub fn volume(&self) -> u64 {
+        return self.inner.volume();
+    }
+
+    #[wasm_bindgen(js_name = totalBlocks)]
+    pub fn total_blocks(&self, include_air: bool) -> u64 {
+        return self.inner.total_blocks(include_air);
+    }
+
+    #[wasm_bindgen(getter, js_name = regionCount)]
+    pub fn region_count(&self) -> usize {
+        return self.inner.regions.len();
+    }
+
+    /// Full id of block at `[x, y, z]` in schematic coordinates, `undefined` if no region
+    /// contains it
+    #[wasm_bindgen(js_name = blockAt)]
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Option<String> {
+        return self
+            .inner
+            .first_block_at([x, y, z])
+            .map(|blk| blk.full_id());
+    }
+
+    /// Set block at `[x, y, z]` in schematic coordinates, `full_id` is like
+    /// `minecraft:oak_log[axis=y]`
+    #[wasm_bindgen(js_name = setBlock)]
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, full_id: &str) -> Result<(), JsError> {
+        let block = Block::from_id(full_id).map_err(|reason| {
+            to_js_err(Error::InvalidBlockId {
+                id: full_id.to_string(),
+                reason,
+            })
+        })?;
+        for reg in &mut self.inner.regions {
+            let r_pos: [i32; 3] = std::array::from_fn(|dim| [x, y, z][dim] - reg.offset[dim]);
+            if reg.contains_coord(r_pos) {
+                return reg
+                    .set_block(r_pos, &block)
+                    .map_err(|_| to_js_err(Error::PaletteTooLong(reg.palette.len())));
+            }
+        }
+        let bbox = self.inner.bounding_box();
+        return Err(to_js_err(Error::BlockPosOutOfRange {
+            tag_path: String::new(),
+            pos: [x, y, z],
+            lower_bound: bbox.map_or([0; 3], |b| b.lower),
+            upper_bound: bbox.map_or([0; 3], |b| b.upper),
+        }));
+    }
+
+    /// Material list as a JSON object, mapping block ids to counts
+    #[wasm_bindgen(js_name = materialList)]
+    pub fn material_list(&self, full_block_state: bool, include_air: bool) -> String {
+        let option = MaterialListOption {
+            full_block_state,
+            include_air,
+            ..MaterialListOption::default()
+        };
+        let list = self.inner.material_list(&option);
+        // keys are strings and values are integers, so it never fails
+        return serde_json::to_string(&list).unwrap();
+    }
+}
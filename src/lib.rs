@@ -33,70 +33,227 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 //! 3. mc_schem C++ wrapper
 //!
 //!    A header-only c++ wrapper based on C ffi of mc_schem
-//! 4. `schemtool` (executable)
+//! 4. `mc_schem` (executable)
 //!
 //!    An executable to do various manipulations on schematics
 //!
+//! ## Features
+//! - `fs` (default): load/save by filename, and the `world` module. Without it, the crate builds
+//!   for `wasm32-unknown-unknown`; use `Schematic::from_*_bytes` and `Schematic::to_*_bytes`.
+//! - `js`: JavaScript bindings through `wasm-bindgen`, exported as class `Schematic`.
+//!
+
+// Functions end with an explicit `return`, and every fallible function returns the shared `Error`.
+#![allow(clippy::needless_return)]
+#![allow(clippy::result_large_err)]
 
 use strum::Display;
 
+/// Synthetic schematics for benchmarks
+pub mod bench_fixtures;
 /// Implement minecraft block and string id parsing
 pub mod block;
+/// Average colors of blocks, optionally computed from resource packs
+pub mod block_color;
+/// Typed views of common block entities, like chests, furnaces and signs
+pub mod block_entity;
+/// Vanilla block state registry, used to validate and canonicalize blocks
+#[cfg(feature = "block_registry")]
+pub mod block_registry;
+/// List and edit commands of command blocks
+pub mod command_block;
+/// Upgrade and downgrade blocks and block entities between data versions
+pub mod datafix;
 /// Errors in loading, saving and manipulating
 pub mod error;
+/// Heightmaps of regions and terrain
+pub mod heightmap;
+/// Interning of blocks
+pub mod interner;
+/// Items and inventories
+pub mod item;
 /// Number id parsing
 pub mod old_block;
 /// Implement region, entity, block entity and pending ticks
 pub mod region;
+/// Render preview images
+pub mod render;
 /// Implement metadata, schematics and loading/saving
 pub mod schem;
-
+/// Read and write stringified nbt(SNBT)
+pub mod snbt;
+/// Rotate and mirror
+pub mod transform;
+/// Varint codec of block data in sponge schematics
+pub mod varint;
+/// Load minecraft saves, requires file system access
+#[cfg(feature = "fs")]
 pub mod world;
 
 mod biome;
+// the C API is meaningless in browsers, and its layout asserts assume 64-bit pointers
+#[cfg(not(target_arch = "wasm32"))]
 mod c_ffi;
-mod item;
+mod draw;
+#[cfg(feature = "js")]
+mod js;
+mod nbt_serde;
 mod player;
+#[cfg(feature = "pyo3")]
+mod python;
+#[cfg(feature = "fs")]
 mod raid;
 
 /// `Block` is a type of block with namespace and properties(aka attributes) in MC.
 pub type Block = block::Block;
 /// Enumerate common blocks
 pub type CommonBlock = block::CommonBlock;
+/// Handle of a block in a `BlockInterner`
+pub type InternedBlock = interner::InternedBlock;
+/// Caller-owned palette that stores every distinct block once
+pub type BlockInterner = interner::BlockInterner;
 /// An entity in MC, like zombie, minecart, etc.
 pub type Entity = region::Entity;
 /// Block entity(also known as tile entity) in MC, like chest, furnace, etc.
 pub type BlockEntity = region::BlockEntity;
 /// A tick waiting to be processed
 pub type PendingTick = region::PendingTick;
+/// Biome in Minecraft
+pub type Biome = biome::Biome;
+/// Base colors of vanilla maps, used to render previews
+pub type MapColor = render::MapColor;
+/// An RGBA image, made by renderers
+pub type RgbaImage = render::RgbaImage;
+/// Options to render a top-down preview
+pub type TopDownRenderOption = render::TopDownRenderOption;
+/// Options to render an isometric preview
+pub type IsometricRenderOption = render::IsometricRenderOption;
+/// Colors of blocks used by renderers
+pub type BlockColorTable = block_color::BlockColorTable;
+/// Kinds of heightmaps
+pub type HeightmapKind = heightmap::HeightmapKind;
+/// Options to build terrain from heightmaps
+pub type TerrainOption = heightmap::TerrainOption;
 /// Region is a 3d area in Minecraft, containing blocks and entities.
 //pub trait WorldSlice = region::WorldSlice;
 pub type Region = region::Region;
+/// Order to visit blocks of a 3d array, like `YZX`
+pub type IterationOrder = schem::order::IterationOrder;
+/// A cuboid of identical blocks in a region
+pub type Cuboid = schem::cuboids::Cuboid;
+/// Blocks selected in a schematic, like selections of WorldEdit
+pub type Selection = schem::clipboard::Selection;
+/// Blocks copied from a schematic, relative to an origin
+pub type Clipboard = schem::clipboard::Clipboard;
+/// Options of pasting a clipboard
+pub type PasteOption = schem::clipboard::PasteOption;
 /// Schematic is part of a Minecraft world, like `.litematic` of litematica mod, `.schem` and
 /// `.schematic` of world edit, `.nbt` of vanilla structure.
 pub type Schematic = schem::Schematic;
+/// A read-only schematic shared between threads
+pub type SharedSchematic = schem::parallel::SharedSchematic;
+/// Build a single-region schematic with chained calls
+pub type SchematicBuilder = schem::builder::SchematicBuilder;
+/// Estimated memory used by a schematic, per region
+pub type MemoryReport = schem::memory::MemoryReport;
+/// A region stored in 16x16x16 sections with per-section palettes
+pub type ChunkedRegion = schem::chunked::ChunkedRegion;
+/// A read-only region that keeps blocks bit-packed
+pub type PackedRegion = schem::packed::PackedRegion;
+/// A schematic with bit-packed regions
+pub type PackedSchematic = schem::packed::PackedSchematic;
 /// A 3d slice of schematic
 pub type SchemSlice<'a> = schem::schem_slice::SchemSlice<'a>;
+/// Read-only view of a schematic with precomputed indices
+pub type SchematicView<'a> = schem::view::SchematicView<'a>;
 /// Intermediate representation via different metadata formats
 pub type MetaDataIR = schem::MetaDataIR;
 /// Options to load litematica
 pub type LitematicaLoadOption = schem::LitematicaLoadOption;
 /// Options to save litematica
 pub type LitematicaSaveOption = schem::LitematicaSaveOption;
+/// Selects regions to load by name
+pub type RegionFilter = schem::RegionFilter;
 /// Options to load vanilla structure
 pub type VanillaStructureLoadOption = schem::VanillaStructureLoadOption;
 /// Options to save vanilla structure
 pub type VanillaStructureSaveOption = schem::VanillaStructureSaveOption;
+/// A piece of schematic split for structure blocks
+pub type StructurePiece = schem::vanilla_structure::StructurePiece;
 /// Options to load litematica
 pub type WorldEdit13LoadOption = schem::WorldEdit13LoadOption;
 /// Options to save world edit 1.13+
 pub type WorldEdit13SaveOption = schem::WorldEdit13SaveOption;
 /// Options to load litematica
 pub type WorldEdit12LoadOption = schem::WorldEdit12LoadOption;
+/// Options to save world edit 1.12-
+pub type WorldEdit12SaveOption = schem::WorldEdit12SaveOption;
+/// Options to save axiom blueprint
+pub type AxiomSaveOption = schem::AxiomSaveOption;
+/// Options to make material list
+pub type MaterialListOption = schem::material_list::MaterialListOption;
+/// Options to export schematic as a BO3 object
+pub type Bo3ExportOption = schem::bo3::Bo3ExportOption;
+/// A block entity file referenced by a BO3 object
+pub type Bo3NbtFile = schem::bo3::Bo3NbtFile;
+/// Options to export schematic as mcfunction
+pub type McFunctionExportOption = schem::mcfunction::McFunctionExportOption;
+/// File formats of exported meshes
+pub type MeshFormat = schem::mesh::MeshFormat;
+/// How faces of exported meshes are colored
+pub type MeshColoring = schem::mesh::MeshColoring;
+/// Options to export meshes
+pub type MeshExportOption = schem::mesh::MeshExportOption;
+/// Progress of loading or saving
+pub type Progress = schem::progress::Progress;
+/// Callback to receive progress, used by load and save options
+pub type ProgressCallback = schem::progress::ProgressCallback;
+/// Bit-packed vector of unsigned integers, used by litematica `BlockStates`
+pub type MultiBitSet = schem::litematica::MultiBitSet;
 /// Minecraft data versions.
 pub type DataVersion = schem::DataVersion;
 /// Errors when loading and saving schematic
 pub type Error = error::Error;
+/// A recoverable error fixed when loading
+pub type LoadWarning = error::LoadWarning;
+/// How to handle overlapping regions in `Schematic::resolve_overlaps`
+pub type OverlapStrategy = schem::overlap::OverlapStrategy;
+/// Condition on blocks used by `Schematic::find_blocks`
+pub type BlockMatcher = schem::search::BlockMatcher;
+/// Error of parsing a WorldEdit mask into `BlockMatcher`
+pub type MaskParseError = schem::search::MaskParseError;
+/// Weighted blocks used by fill and replace, like patterns of WorldEdit
+pub type Pattern = schem::pattern::Pattern;
+/// Error of parsing a WorldEdit pattern
+pub type PatternParseError = schem::pattern::PatternParseError;
+/// How blocks are merged by `Schematic::downscale`
+pub type DownscaleStrategy = schem::scale::DownscaleStrategy;
+/// A piece of schematic made by `Schematic::split`
+pub type SchematicPiece = schem::split::SchematicPiece;
+/// Statistics of a region, made by `Region::stats`
+pub type RegionStats = schem::stats::RegionStats;
+/// Problems found by `Schematic::validate`
+pub type ValidationReport = schem::validate::ValidationReport;
+/// What to do with block entities on wrong blocks, used by `Region::check_block_entity_bindings`
+pub type BlockEntityBindingFix = schem::validate::BlockEntityBindingFix;
+/// Summary of a conversion made by `convert_file`
+pub type ConversionReport = schem::convert::ConversionReport;
+/// Options to convert files
+pub type ConvertOption = schem::convert::ConvertOption;
+/// Whether air is written when saving
+pub type AirPolicy = schem::AirPolicy;
+/// Order of blocks in palette of saved files
+pub type PaletteOrder = schem::PaletteOrder;
+/// How structure void and air are treated in conversions
+pub type VoidPolicy = schem::VoidPolicy;
+/// Build height of a world
+pub type WorldHeight = schem::WorldHeight;
+/// Convert a schematic file to another format with default options
+#[cfg(feature = "fs")]
+pub use schem::convert::convert_file;
+/// Convert a schematic file to another format, converting structure void and air by option
+#[cfg(feature = "fs")]
+pub use schem::convert::convert_file_with_option;
 
 /// Format of known schematics
 #[repr(u8)]
@@ -106,6 +263,7 @@ pub enum SchemFormat {
     VanillaStructure = 1,
     WorldEdit13 = 2,
     WorldEdit12 = 3,
+    Axiom = 4,
 }
 
 impl SchemFormat {
@@ -116,6 +274,7 @@ impl SchemFormat {
             SchemFormat::VanillaStructure => ".nbt",
             SchemFormat::WorldEdit13 => ".schem",
             SchemFormat::WorldEdit12 => ".schematic",
+            SchemFormat::Axiom => ".bp",
         };
     }
 
@@ -126,12 +285,24 @@ impl SchemFormat {
     /// Return all loadable formats
     pub fn loadable_formats() -> &'static [SchemFormat] {
         use SchemFormat::*;
-        return &[Litematica, VanillaStructure, WorldEdit13, WorldEdit12];
+        return &[
+            Litematica,
+            VanillaStructure,
+            WorldEdit13,
+            WorldEdit12,
+            Axiom,
+        ];
     }
     /// Return all savable formats
     pub fn savable_formats() -> &'static [SchemFormat] {
         use SchemFormat::*;
-        return &[Litematica, VanillaStructure, WorldEdit13];
+        return &[
+            Litematica,
+            VanillaStructure,
+            WorldEdit13,
+            WorldEdit12,
+            Axiom,
+        ];
     }
     /// Return if the format can be loaded
     pub fn loadable(&self) -> bool {
@@ -0,0 +1,117 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! (De)serialize nbt compounds with serde, keeping the type of every tag. `fastnbt::Value`
+//! itself can't be deserialized from formats like json, since `3` may be a byte, short, int or
+//! long tag. So each tag is written as a single-key map like `{"Int": 3}`.
+
+use fastnbt::{ByteArray, IntArray, LongArray, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+struct TaggedRef<'a>(&'a Value);
+
+impl<'a> Serialize for TaggedRef<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        const NAME: &str = "Tag";
+        return match self.0 {
+            Value::Byte(v) => serializer.serialize_newtype_variant(NAME, 0, "Byte", v),
+            Value::Short(v) => serializer.serialize_newtype_variant(NAME, 1, "Short", v),
+            Value::Int(v) => serializer.serialize_newtype_variant(NAME, 2, "Int", v),
+            Value::Long(v) => serializer.serialize_newtype_variant(NAME, 3, "Long", v),
+            Value::Float(v) => serializer.serialize_newtype_variant(NAME, 4, "Float", v),
+            Value::Double(v) => serializer.serialize_newtype_variant(NAME, 5, "Double", v),
+            Value::String(v) => serializer.serialize_newtype_variant(NAME, 6, "String", v),
+            Value::ByteArray(v) => serializer.serialize_newtype_variant(NAME, 7, "ByteArray", &**v),
+            Value::IntArray(v) => serializer.serialize_newtype_variant(NAME, 8, "IntArray", &**v),
+            Value::LongArray(v) => serializer.serialize_newtype_variant(NAME, 9, "LongArray", &**v),
+            Value::List(v) => {
+                let list: Vec<TaggedRef<'_>> = v.iter().map(TaggedRef).collect();
+                serializer.serialize_newtype_variant(NAME, 10, "List", &list)
+            }
+            Value::Compound(v) => {
+                serializer.serialize_newtype_variant(NAME, 11, "Compound", &sorted(v))
+            }
+        };
+    }
+}
+
+/// Owned mirror of `TaggedRef`, variants must be in the same order.
+#[derive(Deserialize)]
+#[serde(rename = "Tag")]
+enum Tagged {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    ByteArray(Vec<i8>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+    List(Vec<Tagged>),
+    Compound(HashMap<String, Tagged>),
+}
+
+impl From<Tagged> for Value {
+    fn from(tag: Tagged) -> Value {
+        return match tag {
+            Tagged::Byte(v) => Value::Byte(v),
+            Tagged::Short(v) => Value::Short(v),
+            Tagged::Int(v) => Value::Int(v),
+            Tagged::Long(v) => Value::Long(v),
+            Tagged::Float(v) => Value::Float(v),
+            Tagged::Double(v) => Value::Double(v),
+            Tagged::String(v) => Value::String(v),
+            Tagged::ByteArray(v) => Value::ByteArray(ByteArray::new(v)),
+            Tagged::IntArray(v) => Value::IntArray(IntArray::new(v)),
+            Tagged::LongArray(v) => Value::LongArray(LongArray::new(v)),
+            Tagged::List(v) => Value::List(v.into_iter().map(Value::from).collect()),
+            Tagged::Compound(v) => {
+                Value::Compound(v.into_iter().map(|(k, v)| (k, Value::from(v))).collect())
+            }
+        };
+    }
+}
+
+/// Sort keys so that the output is stable
+fn sorted(compound: &HashMap<String, Value>) -> BTreeMap<&String, TaggedRef<'_>> {
+    return compound.iter().map(|(k, v)| (k, TaggedRef(v))).collect();
+}
+
+pub fn serialize<S>(compound: &HashMap<String, Value>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    return sorted(compound).serialize(serializer);
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<String, Value>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let compound: HashMap<String, Tagged> = HashMap::deserialize(deserializer)?;
+    return Ok(compound
+        .into_iter()
+        .map(|(k, v)| (k, Value::from(v)))
+        .collect());
+}
@@ -19,6 +19,8 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 use crate::block::Block;
 use crate::region::BlockEntity;
 use crate::schem::DataVersion;
+use std::collections::HashMap;
+use std::sync::OnceLock;
 use strum::Display;
 
 /// A look-up table from number id to string
@@ -570,8 +572,13 @@ pub enum OldBlockParseError {
         damage: u8,
         version: DataVersion,
     },
+    NoOldEquivalent {
+        block: String,
+    },
 }
 
+impl std::error::Error for OldBlockParseError {}
+
 /// Returns if the number id is valid
 pub fn is_number_id_valid(id: u8) -> Result<(), OldBlockParseError> {
     if id == 253 || id == 254 {
@@ -1861,4 +1868,59 @@ impl Block {
 
         return Ok(None);
     }
+
+    /// Convert `Block` to number id and damage of 1.12. If no number id matches all properties,
+    /// the one that matches most properties is selected, for example, stairs of any `shape` are
+    /// converted to straight ones.
+    pub fn to_old(&self) -> Result<(u8, u8), OldBlockParseError> {
+        let candidates = match reverse_lut().get(&format!("{}:{}", self.namespace, self.id)) {
+            Some(c) => c,
+            None => {
+                return Err(OldBlockParseError::NoOldEquivalent {
+                    block: self.full_id(),
+                })
+            }
+        };
+        debug_assert!(!candidates.is_empty());
+
+        let mut best: Option<(usize, u8, u8)> = None;
+        for (blk, id, damage) in candidates {
+            if blk == self {
+                return Ok((*id, *damage));
+            }
+            let matched = blk
+                .attributes
+                .iter()
+                .filter(|(key, value)| self.attributes.get(*key) == Some(*value))
+                .count();
+            if best.map_or(true, |(best_matched, _, _)| matched > best_matched) {
+                best = Some((matched, *id, *damage));
+            }
+        }
+        let (_, id, damage) = best.unwrap();
+        return Ok((id, damage));
+    }
+}
+
+/// Look-up table from `namespace:id` to all blocks of 1.12 with this id, with their number id and
+/// damage
+type ReverseLUT = HashMap<String, Vec<(Block, u8, u8)>>;
+
+fn reverse_lut() -> &'static ReverseLUT {
+    static LUT: OnceLock<ReverseLUT> = OnceLock::new();
+    return LUT.get_or_init(|| {
+        let mut lut = ReverseLUT::new();
+        let mut damages = Vec::new();
+        for id in 0..=255u8 {
+            get_valid_damage_values(id, &mut damages);
+            for damage in &damages {
+                if let Ok(blk) = Block::from_old(id, *damage, DataVersion::Java_1_12_2) {
+                    lut.entry(format!("{}:{}", blk.namespace, blk.id))
+                        .or_default()
+                        .push((blk, id, *damage));
+                }
+            }
+        }
+        return lut;
+    });
 }
@@ -0,0 +1,356 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Python bindings, enabled by feature `pyo3`. Build the cdylib and rename it to `mc_schem.so`
+//! (`mc_schem.pyd` on Windows), then `import mc_schem` in python.
+
+use crate::error::{DefaultErrorHandler, Error};
+use crate::region::WorldSlice;
+use crate::schem::{
+    AxiomSaveOption, LitematicaSaveOption, VanillaStructureSaveOption, WorldEdit12SaveOption,
+    WorldEdit13SaveOption,
+};
+use crate::{Block, MaterialListOption, Region, SchemFormat, Schematic};
+use numpy::{PyArray3, PyArrayMethods, PyReadonlyArray3};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+
+fn to_py_err(err: Error) -> PyErr {
+    return PyValueError::new_err(err.to_string());
+}
+
+/// Block with namespace, id and properties
+#[pyclass(name = "Block", module = "mc_schem")]
+#[derive(Clone)]
+pub struct PyBlock {
+    pub inner: Block,
+}
+
+#[pymethods]
+impl PyBlock {
+    /// Parse block from full id like `minecraft:oak_log[axis=y]`
+    #[new]
+    fn new(full_id: &str) -> PyResult<PyBlock> {
+        return match Block::from_id(full_id) {
+            Ok(blk) => Ok(PyBlock { inner: blk }),
+            Err(reason) => Err(to_py_err(Error::InvalidBlockId {
+                id: full_id.to_string(),
+                reason,
+            })),
+        };
+    }
+
+    #[getter]
+    fn namespace(&self) -> String {
+        return self.inner.namespace.clone();
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        return self.inner.id.clone();
+    }
+
+    #[getter]
+    fn properties(&self) -> BTreeMap<String, String> {
+        return self.inner.attributes.clone();
+    }
+
+    fn full_id(&self) -> String {
+        return self.inner.full_id();
+    }
+
+    fn __str__(&self) -> String {
+        return self.inner.full_id();
+    }
+
+    fn __repr__(&self) -> String {
+        return format!("Block(\"{}\")", self.inner.full_id());
+    }
+
+    fn __eq__(&self, other: &PyBlock) -> bool {
+        return self.inner == other.inner;
+    }
+}
+
+/// A 3d area of blocks, block entities and entities
+#[pyclass(name = "Region", module = "mc_schem")]
+pub struct PyRegion {
+    pub inner: Region,
+}
+
+#[pymethods]
+impl PyRegion {
+    /// Create a region of `shape` (x, y, z) filled with air
+    #[new]
+    fn new(shape: [i32; 3]) -> PyResult<PyRegion> {
+        if shape.iter().any(|s| *s < 0) {
+            return Err(PyValueError::new_err("Shape should be non-negative"));
+        }
+        return Ok(PyRegion {
+            inner: Region::with_shape(shape),
+        });
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        return self.inner.name.clone();
+    }
+
+    #[setter]
+    fn set_name(&mut self, name: String) {
+        self.inner.name = name;
+    }
+
+    #[getter]
+    fn offset(&self) -> [i32; 3] {
+        return self.inner.offset;
+    }
+
+    #[setter]
+    fn set_offset(&mut self, offset: [i32; 3]) {
+        self.inner.offset = offset;
+    }
+
+    /// Shape in x, y, z
+    #[getter]
+    fn shape(&self) -> [i32; 3] {
+        return self.inner.shape();
+    }
+
+    #[getter]
+    fn palette(&self) -> Vec<PyBlock> {
+        return self
+            .inner
+            .palette
+            .iter()
+            .map(|blk| PyBlock { inner: blk.clone() })
+            .collect();
+    }
+
+    /// Block index array in numpy, indexed by [y, z, x]. The array is a read-only view without
+    /// copying, it keeps the region alive and sees later changes of the region. Assign to
+    /// `block_indices` to write indices, so that they are checked.
+    #[getter]
+    fn block_indices<'py>(slf: Bound<'py, Self>) -> Bound<'py, PyArray3<u16>> {
+        let region = slf.borrow();
+        // SAFETY: the array is never reallocated, since the shape of region can't be changed from
+        // python, and the region object is kept alive by the numpy array.
+        let array =
+            unsafe { PyArray3::borrow_from_array(&region.inner.array_yzx, slf.clone().into_any()) };
+        // the array doesn't own its data, so python can't make it writeable again
+        array.readwrite().make_nonwriteable();
+        return array;
+    }
+
+    /// Copy `indices` into the block index array. The shape should be the same, and every index
+    /// should be less than the length of palette.
+    #[setter]
+    fn set_block_indices(&mut self, indices: PyReadonlyArray3<u16>) -> PyResult<()> {
+        let indices = indices.as_array();
+        if indices.shape() != self.inner.array_yzx.shape() {
+            return Err(PyValueError::new_err(format!(
+                "Shape of block indices should be {:?}, but found {:?}",
+                self.inner.array_yzx.shape(),
+                indices.shape()
+            )));
+        }
+        let palette_len = self.inner.palette.len();
+        if let Some(idx) = indices.iter().find(|idx| **idx as usize >= palette_len) {
+            return Err(PyValueError::new_err(format!(
+                "Block index {} is out of palette with {} blocks",
+                idx, palette_len
+            )));
+        }
+        self.inner.array_yzx.assign(&indices);
+        return Ok(());
+    }
+
+    fn block_at(&self, pos: [i32; 3]) -> PyResult<PyBlock> {
+        return match self.inner.block_at(pos) {
+            Some(blk) => Ok(PyBlock { inner: blk.clone() }),
+            None => Err(PyIndexError::new_err(format!(
+                "Position {:?} is out of region",
+                pos
+            ))),
+        };
+    }
+
+    fn set_block(&mut self, pos: [i32; 3], block: &PyBlock) -> PyResult<()> {
+        return match self.inner.set_block(pos, &block.inner) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(PyIndexError::new_err(format!(
+                "Failed to set block at {:?}",
+                pos
+            ))),
+        };
+    }
+
+    fn volume(&self) -> u64 {
+        return self.inner.volume();
+    }
+
+    #[pyo3(signature = (include_air = false))]
+    fn total_blocks(&self, include_air: bool) -> u64 {
+        return self.inner.total_blocks(include_air);
+    }
+}
+
+/// Schematic with one or more regions
+#[pyclass(name = "Schematic", module = "mc_schem")]
+pub struct PySchematic {
+    /// Everything except regions, which are in `regions`
+    pub inner: Schematic,
+    /// Region objects shared with python, so that changes of them are visible to the schematic
+    pub regions: Vec<Py<PyRegion>>,
+}
+
+impl PySchematic {
+    fn from_schematic(py: Python<'_>, mut schem: Schematic) -> PyResult<PySchematic> {
+        let mut regions = Vec::with_capacity(schem.regions.len());
+        for reg in schem.regions.drain(..) {
+            regions.push(Py::new(py, PyRegion { inner: reg })?);
+        }
+        return Ok(PySchematic {
+            inner: schem,
+            regions,
+        });
+    }
+
+    /// Move regions into the schematic while calling `f`, and move them back. Moving a region
+    /// doesn't reallocate its block index array, so numpy views of it stay valid.
+    fn with_schematic<R>(&mut self, py: Python<'_>, f: impl FnOnce(&Schematic) -> R) -> R {
+        for reg in &self.regions {
+            let mut reg = reg.borrow_mut(py);
+            self.inner
+                .regions
+                .push(std::mem::replace(&mut reg.inner, Region::new()));
+        }
+        let result = f(&self.inner);
+        for (reg, owned) in self.regions.iter().zip(self.inner.regions.drain(..)) {
+            reg.borrow_mut(py).inner = owned;
+        }
+        return result;
+    }
+}
+
+#[pymethods]
+impl PySchematic {
+    #[new]
+    fn new() -> PySchematic {
+        return PySchematic {
+            inner: Schematic::new(),
+            regions: Vec::new(),
+        };
+    }
+
+    /// Load schematic from file, the format is detected from content
+    #[staticmethod]
+    fn load(py: Python<'_>, filename: &str) -> PyResult<PySchematic> {
+        let (schem, _, _) = Schematic::from_file_detect_format(filename).map_err(to_py_err)?;
+        return PySchematic::from_schematic(py, schem);
+    }
+
+    /// Save schematic to file, the format is deduced from extension
+    fn save(&mut self, py: Python<'_>, filename: &str) -> PyResult<()> {
+        let format = match SchemFormat::from_filename(filename) {
+            Some(f) => f,
+            None => {
+                return Err(to_py_err(Error::UnrecognisedExtension {
+                    extension: filename.rsplit('.').next().unwrap_or("").to_string(),
+                }))
+            }
+        };
+        let res = self.with_schematic(py, |schem| match format {
+            SchemFormat::Litematica => {
+                schem.save_litematica_file(filename, &LitematicaSaveOption::default())
+            }
+            SchemFormat::VanillaStructure => {
+                schem.save_vanilla_structure_file(filename, &VanillaStructureSaveOption::default())
+            }
+            SchemFormat::WorldEdit13 => {
+                schem.save_world_edit_13_file(filename, &WorldEdit13SaveOption::default())
+            }
+            SchemFormat::WorldEdit12 => schem.save_world_edit_12_file::<DefaultErrorHandler>(
+                filename,
+                &WorldEdit12SaveOption::default(),
+            ),
+            SchemFormat::Axiom => schem.save_axiom_file(filename, &AxiomSaveOption::default()),
+        });
+        return res.map_err(to_py_err);
+    }
+
+    #[getter]
+    fn data_version(&self) -> i32 {
+        return self.inner.metadata.mc_data_version;
+    }
+
+    /// All regions, they are shared with the schematic rather than copied
+    #[getter]
+    fn regions(&self, py: Python<'_>) -> Vec<Py<PyRegion>> {
+        return self.regions.iter().map(|reg| reg.clone_ref(py)).collect();
+    }
+
+    /// Add `region` to the schematic without copying, so later changes of it are visible to the
+    /// schematic. A region can't be added to the same schematic twice.
+    fn add_region(&mut self, region: Py<PyRegion>) -> PyResult<()> {
+        if self.regions.iter().any(|reg| reg.is(&region)) {
+            return Err(PyValueError::new_err("Region is already in the schematic"));
+        }
+        self.regions.push(region);
+        return Ok(());
+    }
+
+    /// Enclosing shape of all regions, in x, y, z
+    #[getter]
+    fn shape(&mut self, py: Python<'_>) -> [i32; 3] {
+        return self.with_schematic(py, |schem| schem.shape());
+    }
+
+    fn volume(&mut self, py: Python<'_>) -> u64 {
+        return self.with_schematic(py, |schem| schem.volume());
+    }
+
+    #[pyo3(signature = (include_air = false))]
+    fn total_blocks(&mut self, py: Python<'_>, include_air: bool) -> u64 {
+        return self.with_schematic(py, |schem| schem.total_blocks(include_air));
+    }
+
+    #[pyo3(signature = (full_block_state = false, include_air = false))]
+    fn material_list(
+        &mut self,
+        py: Python<'_>,
+        full_block_state: bool,
+        include_air: bool,
+    ) -> BTreeMap<String, u64> {
+        let option = MaterialListOption {
+            full_block_state,
+            include_air,
+            ..MaterialListOption::default()
+        };
+        return self.with_schematic(py, |schem| schem.material_list(&option));
+    }
+}
+
+#[pymodule]
+fn mc_schem(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBlock>()?;
+    m.add_class::<PyRegion>()?;
+    m.add_class::<PySchematic>()?;
+    return Ok(());
+}
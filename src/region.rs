@@ -16,9 +16,13 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::biome::Biome;
 use crate::block::Block;
 use crate::error::Error;
-use ndarray::Array3;
+use crate::schem::VoidPolicy;
+use fastnbt::Value;
+use ndarray::{concatenate, Array3, ArrayView3, ArrayViewMut3, Axis};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Sky light and block light
@@ -26,24 +30,36 @@ use std::collections::HashMap;
 pub struct Light(u8);
 
 /// An entity in MC, like zombie, minecart, etc.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Entity {
     /// nbt tags of entity
+    #[serde(with = "crate::nbt_serde")]
     pub tags: HashMap<String, fastnbt::Value>,
     /// Position in double precision float
     pub position: [f64; 3],
-    /// Position in integer
+    /// The block that contains this entity, which is `position` rounded down (so `-0.5` is in block
+    /// `-1`). Use [`Entity::set_position`] to change `position` and keep them consistent.
+    ///
+    /// It stays public as it always was, so code that reads it for block queries or builds
+    /// `Entity` with a struct literal keeps compiling. If it's written directly or gets out of
+    /// sync, [`Entity::update_block_pos`] derives it from `position` again.
     pub block_pos: [i32; 3],
+    /// Entities riding this one, parsed from the `Passengers` tag when loaded, and written back
+    /// to it when saved. Their positions are in the same coordinates as `position`, so they move
+    /// together with the vehicle when the schematic is shifted or transformed.
+    #[serde(default)]
+    pub passengers: Vec<Entity>,
 }
 
 /// Block entity(also known as tile entity) in MC, like chest, furnace, etc.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockEntity {
     /// nbt tags of block entity
+    #[serde(with = "crate::nbt_serde")]
     pub tags: HashMap<String, fastnbt::Value>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum PendingTickInfo {
     Fluid { id: String },
@@ -51,7 +67,7 @@ pub enum PendingTickInfo {
 }
 
 /// A tick waiting to be processed
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct PendingTick {
     pub priority: i32,
@@ -143,6 +159,25 @@ pub trait HasOffset {
     fn offset(&self) -> [i32; 3];
 }
 
+/// Axis-aligned box of blocks, both `lower` and `upper` are inclusive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BlockBox {
+    pub lower: [i32; 3],
+    pub upper: [i32; 3],
+}
+
+/// Grid index over entities and block entities of a region. Entities are bucketed into cubic cells
+/// by their `block_pos`, and block entities by their position, so that box queries only visit cells
+/// that intersect the box. \
+/// The index borrows the region, so it can never be out of date; build a new one after modifying the region.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex<'r> {
+    region: &'r Region,
+    cell_size: i32,
+    entity_cells: HashMap<[i32; 3], Vec<usize>>,
+    block_entity_cells: HashMap<[i32; 3], Vec<[i32; 3]>>,
+}
+
 // /// Returns detailed block infos at `r_pos`, including block index, block, block entity(mutable) and pending tick(mutable).
 // /// Returns `None` if the block is outside the region
 // fn block_info_at_mut(&mut self, r_pos: [i32; 3]) -> Option<(u16, &Block, Option<&mut BlockEntity>, Option<&mut PendingTick>)>;
@@ -161,7 +196,8 @@ pub trait HasOffset {
 /// kinds of blocks in this region, so each block can be represented by an index(u16). Indices of
 /// every block are stored in 3d array, indexed by y, z, x. YZX is applied because all schematic
 /// formats store blocks in this order, by following this custom, our library can have better performance in loading and saving.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RegionData")]
 pub struct Region {
     /// Name of this region, only useful in litematica
     pub name: String,
@@ -169,17 +205,200 @@ pub struct Region {
     pub array_yzx: Array3<u16>,
     /// All kinds of blocks
     pub palette: Vec<Block>,
+    /// Other palettes of vanilla structures with random variants, like shipwrecks. Each one has
+    /// the same length as `palette`, and a block index refers to the block at the same index of
+    /// the variant in use. Empty for regions without variants.
+    #[serde(default)]
+    pub alternate_palettes: Vec<Vec<Block>>,
     /// All block entities. The key is position (xyz)
+    #[serde(with = "pos_map")]
     pub block_entities: HashMap<[i32; 3], BlockEntity>,
     /// All pending ticks. The key is position (xyz)
+    #[serde(with = "pos_map")]
     pub pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
     /// All entities
     pub entities: Vec<Entity>,
     /// Offset of this region
     pub offset: [i32; 3],
+    /// Axes on which litematica stores this region with negative `Size`, where `Position` is the
+    /// upper corner instead of the lower one. Only useful in litematica, it's kept to save the
+    /// region in its original orientation.
+    #[serde(default)]
+    pub negative_size: [bool; 3],
+    /// Array of biome indices, stored in y,z,x. `None` if this region doesn't record biomes
+    pub biomes: Option<Array3<u16>>,
+    /// All kinds of biomes in full id like `minecraft:plains`, indexed by `biomes`. Biomes of
+    /// datapacks and mods are kept as they are.
+    pub biome_palette: Vec<String>,
+    /// Index of blocks in `palette`, used to append blocks without scanning the palette
+    #[serde(skip)]
+    palette_lookup: PaletteLookup,
     //pub array_number_id_damage: Option<Array3<(u8, u8)>>
 }
 
+/// Deserialized fields of [`Region`], checked before they become a region
+#[derive(Deserialize)]
+struct RegionData {
+    name: String,
+    array_yzx: Array3<u16>,
+    palette: Vec<Block>,
+    #[serde(default)]
+    alternate_palettes: Vec<Vec<Block>>,
+    #[serde(with = "pos_map")]
+    block_entities: HashMap<[i32; 3], BlockEntity>,
+    #[serde(with = "pos_map")]
+    pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
+    entities: Vec<Entity>,
+    offset: [i32; 3],
+    #[serde(default)]
+    negative_size: [bool; 3],
+    biomes: Option<Array3<u16>>,
+    biome_palette: Vec<String>,
+}
+
+impl TryFrom<RegionData> for Region {
+    type Error = String;
+
+    fn try_from(data: RegionData) -> Result<Self, Self::Error> {
+        if let Some(idx) = data
+            .array_yzx
+            .iter()
+            .find(|idx| **idx as usize >= data.palette.len())
+        {
+            return Err(format!(
+                "block index {idx} is out of palette of {} blocks",
+                data.palette.len()
+            ));
+        }
+        for (idx, pal) in data.alternate_palettes.iter().enumerate() {
+            if pal.len() != data.palette.len() {
+                return Err(format!(
+                    "alternate palette {idx} has {} blocks, but palette has {}",
+                    pal.len(),
+                    data.palette.len()
+                ));
+            }
+        }
+        let shape_yzx = data.array_yzx.shape();
+        if let Some(biomes) = &data.biomes {
+            if biomes.shape() != shape_yzx {
+                return Err(format!(
+                    "shape of biomes {:?} differs from shape of blocks {:?}",
+                    biomes.shape(),
+                    shape_yzx
+                ));
+            }
+            if let Some(idx) = biomes
+                .iter()
+                .find(|idx| **idx as usize >= data.biome_palette.len())
+            {
+                return Err(format!(
+                    "biome index {idx} is out of biome palette of {} biomes",
+                    data.biome_palette.len()
+                ));
+            }
+        }
+        let shape_xyz = [shape_yzx[2], shape_yzx[0], shape_yzx[1]];
+        let inside = |pos: &[i32; 3]| {
+            (0..3).all(|dim| pos[dim] >= 0 && (pos[dim] as usize) < shape_xyz[dim])
+        };
+        if let Some(pos) = data.block_entities.keys().find(|pos| !inside(pos)) {
+            return Err(format!("block entity at {pos:?} is out of region"));
+        }
+        if let Some(pos) = data.pending_ticks.keys().find(|pos| !inside(pos)) {
+            return Err(format!("pending tick at {pos:?} is out of region"));
+        }
+
+        return Ok(Region {
+            name: data.name,
+            array_yzx: data.array_yzx,
+            palette: data.palette,
+            alternate_palettes: data.alternate_palettes,
+            block_entities: data.block_entities,
+            pending_ticks: data.pending_ticks,
+            entities: data.entities,
+            offset: data.offset,
+            negative_size: data.negative_size,
+            biomes: data.biomes,
+            biome_palette: data.biome_palette,
+            palette_lookup: PaletteLookup::default(),
+        });
+    }
+}
+
+/// Block -> index of its first occurrence in palette. The palette is a public field and can be
+/// changed without `Region`, so the lookup remembers the palette length it's built for and is
+/// rebuilt once the length differs, or a found index points to another block. If a block is
+/// replaced in place, a lookup for the new block may miss and append it again, which leaves a
+/// duplicated but still valid palette entry.
+#[derive(Debug, Clone, Default)]
+struct PaletteLookup {
+    index_of_block: HashMap<Block, usize>,
+    palette_len: usize,
+}
+
+impl PaletteLookup {
+    fn rebuild(&mut self, palette: &[Block]) {
+        self.index_of_block.clear();
+        self.index_of_block.reserve(palette.len());
+        for (idx, blk) in palette.iter().enumerate() {
+            self.index_of_block.entry(blk.clone()).or_insert(idx);
+        }
+        self.palette_len = palette.len();
+    }
+
+    /// Index of `block` in `palette`, or append it to `palette`
+    fn find_or_append(&mut self, palette: &mut Vec<Block>, block: &Block) -> usize {
+        if self.palette_len != palette.len() {
+            self.rebuild(palette);
+        }
+        if let Some(idx) = self.index_of_block.get(block) {
+            if palette.get(*idx) == Some(block) {
+                return *idx;
+            }
+            // the palette is changed in place
+            self.rebuild(palette);
+            if let Some(idx) = self.index_of_block.get(block) {
+                return *idx;
+            }
+        }
+        palette.push(block.clone());
+        self.index_of_block.insert(block.clone(), palette.len() - 1);
+        self.palette_len = palette.len();
+        return palette.len() - 1;
+    }
+
+    fn invalidate(&mut self) {
+        self.palette_len = usize::MAX;
+    }
+}
+
+/// (De)serialize maps keyed by position as a list of `(pos, value)` pairs sorted by position,
+/// because formats like json only accept string keys.
+mod pos_map {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S, T>(map: &HashMap<[i32; 3], T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        let mut pairs: Vec<(&[i32; 3], &T)> = map.iter().collect();
+        pairs.sort_by_key(|(pos, _)| **pos);
+        return serializer.collect_seq(pairs);
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashMap<[i32; 3], T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        let pairs: Vec<([i32; 3], T)> = Vec::deserialize(deserializer)?;
+        return Ok(pairs.into_iter().collect());
+    }
+}
+
 impl Default for Light {
     fn default() -> Self {
         return Self(0xFF);
@@ -205,15 +424,98 @@ impl Entity {
             tags: HashMap::new(),
             position: [0.0, 0.0, 0.0],
             block_pos: [0, 0, 0],
+            passengers: Vec::new(),
         };
     }
 
-    /// Add adder to position and block_pos
+    /// Create an entity at `position`, `block_pos` is computed from it
+    pub fn with_position(position: [f64; 3]) -> Entity {
+        let mut entity = Entity::new();
+        entity.set_position(position);
+        return entity;
+    }
+
+    /// The block that contains `position`, every coordinate is rounded down. Values outside the
+    /// range of i32 are clamped, and NaN becomes 0.
+    pub fn block_pos_of(position: [f64; 3]) -> [i32; 3] {
+        return [
+            position[0].floor() as i32,
+            position[1].floor() as i32,
+            position[2].floor() as i32,
+        ];
+    }
+
+    /// Set `position` and recompute `block_pos`
+    pub fn set_position(&mut self, position: [f64; 3]) {
+        self.position = position;
+        self.block_pos = Self::block_pos_of(position);
+    }
+
+    /// Recompute `block_pos` from `position`, useful after modifying `position` directly
+    pub fn update_block_pos(&mut self) {
+        self.block_pos = Self::block_pos_of(self.position);
+    }
+
+    /// Add adder to position and block_pos, including all passengers
     pub fn pos_shift(&mut self, adder: [i32; 3]) {
         for dim in 0..3 {
             self.block_pos[dim] += adder[dim];
             self.position[dim] += adder[dim] as f64;
         }
+        for passenger in &mut self.passengers {
+            passenger.pos_shift(adder);
+        }
+    }
+
+    /// Move the `Passengers` tag into `passengers`, recursively. A passenger without valid `Pos`
+    /// is placed at its vehicle, and elements that are not compounds are dropped.
+    pub fn extract_passengers(&mut self) {
+        let Some(Value::List(list)) = self.tags.remove("Passengers") else {
+            return;
+        };
+        for tag in list {
+            let Value::Compound(tags) = tag else {
+                continue;
+            };
+            let mut passenger = Entity::new();
+            passenger.set_position(self.position);
+            if let Some(Value::List(pos)) = tags.get("Pos") {
+                if let [Value::Double(x), Value::Double(y), Value::Double(z)] = pos.as_slice() {
+                    passenger.set_position([*x, *y, *z]);
+                }
+            }
+            passenger.tags = tags;
+            passenger.extract_passengers();
+            self.passengers.push(passenger);
+        }
+    }
+
+    /// Tags with `passengers` written to `Passengers`, and `Pos` of every passenger set to its
+    /// position plus `shift`, which converts positions to the coordinates of the saved file.
+    /// Same as `tags` if there is no passenger.
+    pub fn tags_with_passengers(&self, shift: [f64; 3]) -> HashMap<String, Value> {
+        let mut tags = self.tags.clone();
+        if self.passengers.is_empty() {
+            return tags;
+        }
+        let mut list = Vec::with_capacity(self.passengers.len());
+        for passenger in &self.passengers {
+            let mut p_tags = passenger.tags_with_passengers(shift);
+            let pos = (0..3).map(|dim| Value::Double(passenger.position[dim] + shift[dim]));
+            p_tags.insert("Pos".to_string(), Value::List(pos.collect()));
+            list.push(Value::Compound(p_tags));
+        }
+        tags.insert("Passengers".to_string(), Value::List(list));
+        return tags;
+    }
+
+    /// Count of passengers, including passengers of passengers
+    pub fn passenger_count(&self) -> usize {
+        return self
+            .passengers
+            .iter()
+            .map(|p| 1 + p.passenger_count())
+            .sum();
     }
 }
 
@@ -231,6 +533,168 @@ impl PendingTickInfo {
     }
 }
 
+impl BlockBox {
+    /// Create a box from two corners in any order
+    pub fn new(corner_a: [i32; 3], corner_b: [i32; 3]) -> BlockBox {
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        for dim in 0..3 {
+            lower[dim] = corner_a[dim].min(corner_b[dim]);
+            upper[dim] = corner_a[dim].max(corner_b[dim]);
+        }
+        return BlockBox { lower, upper };
+    }
+
+    /// Create a box from position of the lower corner and shape. The upper corner saturates at
+    /// the bounds of `i32` instead of overflowing.
+    pub fn from_pos_shape(pos: [i32; 3], shape: [i32; 3]) -> BlockBox {
+        return BlockBox {
+            lower: pos,
+            upper: std::array::from_fn(|dim| pos[dim].saturating_add(shape[dim].saturating_sub(1))),
+        };
+    }
+
+    /// If `pos` is inside the box
+    pub fn contains(&self, pos: [i32; 3]) -> bool {
+        return (0..3).all(|dim| pos[dim] >= self.lower[dim] && pos[dim] <= self.upper[dim]);
+    }
+
+    /// Returns true if the two boxes share at least one block
+    pub fn intersects(&self, other: &BlockBox) -> bool {
+        for dim in 0..3 {
+            if self.upper[dim] < other.lower[dim] || other.upper[dim] < self.lower[dim] {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /// Blocks shared by the two boxes, `None` if they don't intersect
+    pub fn intersection(&self, other: &BlockBox) -> Option<BlockBox> {
+        if !self.intersects(other) {
+            return None;
+        }
+        return Some(BlockBox {
+            lower: std::array::from_fn(|dim| self.lower[dim].max(other.lower[dim])),
+            upper: std::array::from_fn(|dim| self.upper[dim].min(other.upper[dim])),
+        });
+    }
+}
+
+impl<'r> SpatialIndex<'r> {
+    /// Index all entities and block entities of `region`. `cell_size` is the edge length of each
+    /// cell in blocks, values less than 1 are treated as 1.
+    pub fn new(region: &'r Region, cell_size: i32) -> SpatialIndex<'r> {
+        let cell_size = cell_size.max(1);
+        let mut entity_cells: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+        for (idx, entity) in region.entities.iter().enumerate() {
+            let cell = Self::cell_of(cell_size, entity.block_pos);
+            entity_cells.entry(cell).or_default().push(idx);
+        }
+        let mut block_entity_cells: HashMap<[i32; 3], Vec<[i32; 3]>> = HashMap::new();
+        for pos in region.block_entities.keys() {
+            let cell = Self::cell_of(cell_size, *pos);
+            block_entity_cells.entry(cell).or_default().push(*pos);
+        }
+        return SpatialIndex {
+            region,
+            cell_size,
+            entity_cells,
+            block_entity_cells,
+        };
+    }
+
+    fn cell_of(cell_size: i32, pos: [i32; 3]) -> [i32; 3] {
+        return [
+            pos[0].div_euclid(cell_size),
+            pos[1].div_euclid(cell_size),
+            pos[2].div_euclid(cell_size),
+        ];
+    }
+
+    /// Cells that intersect with `aabb`. If the box covers more cells than occupied, only occupied
+    /// cells are checked.
+    fn cells_in<T>(&self, cells: &HashMap<[i32; 3], T>, aabb: &BlockBox) -> Vec<[i32; 3]> {
+        let lower = Self::cell_of(self.cell_size, aabb.lower);
+        let upper = Self::cell_of(self.cell_size, aabb.upper);
+        let mut num_cells: u64 = 1;
+        for dim in 0..3 {
+            let count = upper[dim] as i64 - lower[dim] as i64 + 1;
+            num_cells = num_cells.saturating_mul(count.max(0) as u64);
+        }
+        let cell_box = BlockBox { lower, upper };
+        if num_cells > cells.len() as u64 {
+            let mut result: Vec<[i32; 3]> = cells
+                .keys()
+                .filter(|c| cell_box.contains(**c))
+                .copied()
+                .collect();
+            result.sort();
+            return result;
+        }
+
+        let mut result = Vec::with_capacity(num_cells as usize);
+        for x in lower[0]..=upper[0] {
+            for y in lower[1]..=upper[1] {
+                for z in lower[2]..=upper[2] {
+                    if cells.contains_key(&[x, y, z]) {
+                        result.push([x, y, z]);
+                    }
+                }
+            }
+        }
+        return result;
+    }
+
+    /// Edge length of cells in blocks
+    pub fn cell_size(&self) -> i32 {
+        return self.cell_size;
+    }
+
+    /// The indexed region
+    pub fn region(&self) -> &'r Region {
+        return self.region;
+    }
+
+    /// Indices of entities whose `block_pos` is inside `aabb`, in ascending order
+    pub fn entity_indices_in(&self, aabb: &BlockBox) -> Vec<usize> {
+        let mut result = Vec::new();
+        for cell in self.cells_in(&self.entity_cells, aabb) {
+            for idx in &self.entity_cells[&cell] {
+                if aabb.contains(self.region.entities[*idx].block_pos) {
+                    result.push(*idx);
+                }
+            }
+        }
+        result.sort();
+        return result;
+    }
+
+    /// Entities whose `block_pos` is inside `aabb`, in the same order as `Region::entities`
+    pub fn entities_in(&self, aabb: &BlockBox) -> Vec<&'r Entity> {
+        let region = self.region;
+        return self
+            .entity_indices_in(aabb)
+            .into_iter()
+            .map(|idx| &region.entities[idx])
+            .collect();
+    }
+
+    /// Block entities inside `aabb` with their positions, sorted by position
+    pub fn block_entities_in(&self, aabb: &BlockBox) -> Vec<([i32; 3], &'r BlockEntity)> {
+        let mut result = Vec::new();
+        for cell in self.cells_in(&self.block_entity_cells, aabb) {
+            for pos in &self.block_entity_cells[&cell] {
+                if aabb.contains(*pos) {
+                    result.push((*pos, &self.region.block_entities[pos]));
+                }
+            }
+        }
+        result.sort_by_key(|(pos, _)| *pos);
+        return result;
+    }
+}
+
 impl HasPalette for Region {
     fn palette(&self) -> &[Block] {
         return &self.palette;
@@ -368,10 +832,15 @@ impl Region {
             name: String::from("NewRegion"),
             array_yzx: Array3::zeros(shape_yzx),
             palette: Vec::new(),
+            alternate_palettes: Vec::new(),
             block_entities: HashMap::new(),
             pending_ticks: HashMap::new(),
             entities: Vec::new(),
             offset: [0, 0, 0],
+            negative_size: [false; 3],
+            biomes: None,
+            biome_palette: Vec::new(),
+            palette_lookup: PaletteLookup::default(),
         };
         result.find_or_append_to_palette(&Block::air());
         return result;
@@ -417,16 +886,7 @@ impl Region {
         if !self.contains_coord(r_pos) {
             return Err(());
         }
-        let mut blkid = self.palette.len();
-        for (idx, blk) in self.palette.iter().enumerate() {
-            if blk == block {
-                blkid = idx;
-                break;
-            }
-        }
-        if blkid >= self.palette.len() {
-            self.palette.push(block.clone());
-        }
+        let blkid = self.palette_lookup.find_or_append(&mut self.palette, block);
         if blkid >= 65536 {
             return Err(());
         }
@@ -466,12 +926,49 @@ impl Region {
         }
         let shape_yzx = Self::pos_xyz_to_yzx(&usz);
         self.array_yzx = Array3::zeros(shape_yzx);
+        self.biomes = None;
+        self.biome_palette.clear();
         //let shape_zx = [shape_xyz[2], shape_xyz[1]];
         // self.sky_block_light = Array3::default(shape_yzx);
         // self.sky_block_light.fill(Light::default());
         // self.biome = Array2::default(shape_zx);
     }
 
+    /// Block indices in palette, indexed by `[y, z, x]` in relative coordinates, so x changes
+    /// fastest when iterating (`IterationOrder::YZX`). Use it for bulk operations like masks and
+    /// histograms.
+    pub fn indices(&self) -> ArrayView3<'_, u16> {
+        return self.array_yzx.view();
+    }
+
+    /// Mutable block indices, see [`Region::indices`]. The shape can't be changed through the
+    /// view, and every index written must be less than the length of palette.
+    pub fn indices_mut(&mut self) -> ArrayViewMut3<'_, u16> {
+        return self.array_yzx.view_mut();
+    }
+
+    /// Block indices as a flat slice in memory order, with the shape and strides(in elements) of
+    /// axes y, z, x. Index of relative pos `[x, y, z]` in the slice is
+    /// `y * strides[0] + z * strides[1] + x * strides[2]`. Arrays made by this library are always
+    /// contiguous with positive strides, otherwise `None` is returned.
+    pub fn indices_slice(&self) -> Option<(&[u16], [usize; 3], [usize; 3])> {
+        let strides = self.array_yzx.strides();
+        if strides.iter().any(|s| *s < 0) {
+            return None;
+        }
+        let slice = self.array_yzx.as_slice_memory_order()?;
+        let shape = self.array_yzx.shape();
+        return Some((
+            slice,
+            [shape[0], shape[1], shape[2]],
+            [
+                strides[0] as usize,
+                strides[1] as usize,
+                strides[2] as usize,
+            ],
+        ));
+    }
+
     /// Shape in y, z, x
     pub fn shape_yzx(&self) -> [i32; 3] {
         let shape = self.array_yzx.shape();
@@ -533,11 +1030,21 @@ impl Region {
                 id_map[id] = counter;
                 counter += 1;
             }
+            let palette_len = self.palette.len();
             for id in (0..block_counter.len()).rev() {
                 if block_counter[id] == 0 {
                     self.palette.remove(id);
                 }
             }
+            for variant in &mut self.alternate_palettes {
+                if variant.len() == palette_len {
+                    let mut id = 0;
+                    variant.retain(|_| {
+                        id += 1;
+                        return block_counter[id - 1] != 0;
+                    });
+                }
+            }
         }
         for blkid in &mut self.array_yzx {
             let new_id = id_map[*blkid as usize];
@@ -548,6 +1055,43 @@ impl Region {
         Ok(())
     }
 
+    /// Number of palette variants, 1 for regions without `alternate_palettes`
+    pub fn palette_variant_count(&self) -> usize {
+        return self.alternate_palettes.len() + 1;
+    }
+
+    /// Use palette variant `variant` as `palette`, where 0 is the current palette and `i` is
+    /// `alternate_palettes[i - 1]`. The two palettes are swapped, so selecting the same variant
+    /// again restores the original palette.
+    pub fn select_palette_variant(&mut self, variant: usize) -> Result<(), Error> {
+        if variant >= self.palette_variant_count() {
+            return Err(Error::InvalidValue {
+                tag_path: "Region.alternate_palettes".to_string(),
+                error: format!(
+                    "Palette variant {variant} is out of range, the region has {} variants",
+                    self.palette_variant_count()
+                ),
+            });
+        }
+        if variant == 0 {
+            return Ok(());
+        }
+        let selected = &mut self.alternate_palettes[variant - 1];
+        if selected.len() != self.palette.len() {
+            return Err(Error::InvalidValue {
+                tag_path: format!("Region.alternate_palettes[{}]", variant - 1),
+                error: format!(
+                    "Palette variant {variant} has {} blocks, but the palette has {}",
+                    selected.len(),
+                    self.palette.len()
+                ),
+            });
+        }
+        std::mem::swap(&mut self.palette, selected);
+        self.invalidate_palette_lookup();
+        return Ok(());
+    }
+
     /// Find the block index of a block in palette
     pub fn find_in_palette(&self, block: &Block) -> Option<u16> {
         for (idx, blk) in self.palette.iter().enumerate() {
@@ -558,15 +1102,16 @@ impl Region {
         return None;
     }
 
-    /// Find the block in palette, if not exist, append it to the palette.
+    /// Find the block in palette, if not exist, append it to the palette. Blocks are looked up by
+    /// hash, so appending many blocks doesn't scan the palette every time.
     pub fn find_or_append_to_palette(&mut self, block: &Block) -> u16 {
-        return match self.find_in_palette(block) {
-            Some(idx) => idx,
-            None => {
-                self.palette.push(block.clone());
-                (self.palette.len() - 1) as u16
-            }
-        };
+        return self.palette_lookup.find_or_append(&mut self.palette, block) as u16;
+    }
+
+    /// Drop the palette lookup after changing blocks of `palette` in place, otherwise these
+    /// blocks may be appended to the palette again.
+    pub(crate) fn invalidate_palette_lookup(&mut self) {
+        self.palette_lookup.invalidate();
     }
     /// Fill the region with block
     pub fn fill_with(&mut self, block: &Block) {
@@ -574,6 +1119,52 @@ impl Region {
         self.array_yzx.fill(blk_id);
     }
 
+    /// Replace structure void with air or air with structure void according to `policy`. Returns
+    /// the number of replaced blocks.
+    pub fn apply_void_policy(&mut self, policy: VoidPolicy) -> u64 {
+        let (is_source, target): (fn(&Block) -> bool, Block) = match policy {
+            VoidPolicy::Keep => return 0,
+            VoidPolicy::VoidToAir => (Block::is_structure_void, Block::air()),
+            VoidPolicy::AirToVoid => (Block::is_air, Block::structure_void()),
+        };
+        if !self.palette.iter().any(is_source) {
+            return 0;
+        }
+        self.find_or_append_to_palette(&target);
+        // remove source blocks from palette, and map them to target
+        let mut lut: Vec<u16> = Vec::with_capacity(self.palette.len());
+        let mut new_palette: Vec<Block> = Vec::with_capacity(self.palette.len());
+        for blk in &self.palette {
+            if is_source(blk) {
+                // assigned after target is found
+                lut.push(0);
+            } else {
+                lut.push(new_palette.len() as u16);
+                new_palette.push(blk.clone());
+            }
+        }
+        let target_id = new_palette.iter().position(|b| b == &target).unwrap() as u16;
+        for (blk, new_id) in self.palette.iter().zip(lut.iter_mut()) {
+            if is_source(blk) {
+                *new_id = target_id;
+            }
+        }
+
+        let mut replaced = 0;
+        for blk_id in &mut self.array_yzx {
+            let Some(new_id) = lut.get(*blk_id as usize) else {
+                continue;
+            };
+            if is_source(&self.palette[*blk_id as usize]) {
+                replaced += 1;
+            }
+            *blk_id = *new_id;
+        }
+        self.palette = new_palette;
+        self.invalidate_palette_lookup();
+        return replaced;
+    }
+
     /// Set block entity at `r_pos`
     pub fn set_block_entity_at(&mut self, r_pos: [i32; 3], be: BlockEntity) -> Option<BlockEntity> {
         return self.block_entities.insert(r_pos, be);
@@ -608,6 +1199,228 @@ impl Region {
             None
         };
     }
+    /// If `other` touches this region face to face and they together form a cuboid, returns the axis
+    /// (0 for x, 1 for y, 2 for z) and whether `other` is on the positive side.
+    pub fn adjacent_axis(&self, other: &Region) -> Option<(usize, bool)> {
+        let shape_s = self.shape();
+        let shape_o = other.shape();
+        for axis in 0..3 {
+            let mut aligned = true;
+            for dim in 0..3 {
+                if dim == axis {
+                    continue;
+                }
+                if self.offset[dim] != other.offset[dim] || shape_s[dim] != shape_o[dim] {
+                    aligned = false;
+                    break;
+                }
+            }
+            if !aligned {
+                continue;
+            }
+            if self.offset[axis] + shape_s[axis] == other.offset[axis] {
+                return Some((axis, true));
+            }
+            if other.offset[axis] + shape_o[axis] == self.offset[axis] {
+                return Some((axis, false));
+            }
+        }
+        return None;
+    }
+
+    /// Merge two regions that are adjacent (see [`Region::adjacent_axis`]) and have the same kinds
+    /// of blocks in palette (order doesn't matter). Returns `None` if they can't be merged. \
+    /// The result takes the name and palette of the region with lower offset. Biomes should be
+    /// recorded in both regions or neither. Alternate palettes must agree block by block, so that
+    /// every variant is the same in both regions.
+    pub fn merge_adjacent(&self, other: &Region) -> Option<Region> {
+        let (axis, other_is_upper) = self.adjacent_axis(other)?;
+        let (lower, upper) = if other_is_upper {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if lower.palette.len() != upper.palette.len() {
+            return None;
+        }
+        // block index in upper -> block index in lower
+        let mut lut: Vec<u16> = Vec::with_capacity(upper.palette.len());
+        for blk in &upper.palette {
+            lut.push(lower.find_in_palette(blk)?);
+        }
+        for idx in upper.array_yzx.iter() {
+            if *idx as usize >= lut.len() {
+                return None;
+            }
+        }
+        if lower.alternate_palettes.len() != upper.alternate_palettes.len() {
+            return None;
+        }
+        for (lower_alt, upper_alt) in lower
+            .alternate_palettes
+            .iter()
+            .zip(&upper.alternate_palettes)
+        {
+            if upper_alt.len() != lut.len() {
+                return None;
+            }
+            for (upper_idx, blk) in upper_alt.iter().enumerate() {
+                if lower_alt.get(lut[upper_idx] as usize) != Some(blk) {
+                    return None;
+                }
+            }
+        }
+
+        let upper_array = upper.array_yzx.mapv(|idx| lut[idx as usize]);
+        // x, y, z are the 2nd, 0th and 1st axis of array_yzx
+        let axis_yzx = [2, 0, 1][axis];
+        let array_yzx = concatenate(
+            Axis(axis_yzx),
+            &[lower.array_yzx.view(), upper_array.view()],
+        )
+        .ok()?;
+
+        let mut shift = [0; 3];
+        shift[axis] = lower.shape()[axis];
+
+        let mut result = lower.clone();
+        result.array_yzx = array_yzx;
+        match (&lower.biomes, &upper.biomes) {
+            (None, None) => {}
+            (Some(lower_biomes), Some(upper_biomes)) => {
+                let biome_lut: Vec<u16> = upper
+                    .biome_palette
+                    .iter()
+                    .map(|b| result.find_or_append_biome(b))
+                    .collect();
+                let upper_biomes = upper_biomes.mapv(|idx| biome_lut[idx as usize]);
+                result.biomes = Some(
+                    concatenate(Axis(axis_yzx), &[lower_biomes.view(), upper_biomes.view()])
+                        .ok()?,
+                );
+            }
+            _ => return None,
+        }
+        result.block_entities.reserve(upper.block_entities.len());
+        for (pos, be) in &upper.block_entities {
+            let pos = [pos[0] + shift[0], pos[1] + shift[1], pos[2] + shift[2]];
+            result.block_entities.insert(pos, be.clone());
+        }
+        for (pos, ticks) in &upper.pending_ticks {
+            let pos = [pos[0] + shift[0], pos[1] + shift[1], pos[2] + shift[2]];
+            result.pending_ticks.insert(pos, ticks.clone());
+        }
+        result.entities.reserve(upper.entities.len());
+        for entity in &upper.entities {
+            let mut e = entity.clone();
+            e.pos_shift(shift);
+            result.entities.push(e);
+        }
+        return Some(result);
+    }
+
+    /// Returns id of biome at `r_pos`, or `None` if the position is out of range or biomes are
+    /// not recorded
+    pub fn biome_id_at(&self, r_pos: [i32; 3]) -> Option<&str> {
+        if !self.contains_coord(r_pos) {
+            return None;
+        }
+        let biomes = self.biomes.as_ref()?;
+        let pos_usize = Self::i32_to_usize(&r_pos);
+        let idx = biomes[Self::pos_xyz_to_yzx(&pos_usize)];
+        return self.biome_palette.get(idx as usize).map(|id| id.as_str());
+    }
+
+    /// Returns biome at `r_pos`. Returns `None` if the position is out of range, biomes are not
+    /// recorded, or the biome is not vanilla, use [`Region::biome_id_at`] for such biomes.
+    pub fn biome_at(&self, r_pos: [i32; 3]) -> Option<Biome> {
+        return Biome::from_str(self.biome_id_at(r_pos)?);
+    }
+
+    /// Find `biome_id` in biome palette, append it if not found
+    pub(crate) fn find_or_append_biome(&mut self, biome_id: &str) -> u16 {
+        if let Some(idx) = self.biome_palette.iter().position(|b| b == biome_id) {
+            return idx as u16;
+        }
+        self.biome_palette.push(biome_id.to_string());
+        return (self.biome_palette.len() - 1) as u16;
+    }
+
+    /// Set biome at `r_pos` by id, for example `minecraft:plains` or `terralith:yellowstone`. If
+    /// biomes are not recorded, other positions are filled with the default biome. Returns
+    /// `BlockPosOutOfRange` if the position is out of range.
+    pub fn set_biome_id(&mut self, r_pos: [i32; 3], biome_id: &str) -> Result<(), Error> {
+        if !self.contains_coord(r_pos) {
+            let shape = self.shape();
+            return Err(Error::BlockPosOutOfRange {
+                tag_path: String::new(),
+                pos: r_pos,
+                lower_bound: [0, 0, 0],
+                upper_bound: shape.map(|s| s - 1),
+            });
+        }
+        if self.biomes.is_none() {
+            self.fill_biome(Biome::default());
+        }
+        let idx = self.find_or_append_biome(biome_id);
+        let pos_usize = Self::i32_to_usize(&r_pos);
+        if let Some(biomes) = &mut self.biomes {
+            biomes[Self::pos_xyz_to_yzx(&pos_usize)] = idx;
+        }
+        return Ok(());
+    }
+
+    /// Set biome at `r_pos`, see [`Region::set_biome_id`]
+    pub fn set_biome(&mut self, r_pos: [i32; 3], biome: Biome) -> Result<(), Error> {
+        return self.set_biome_id(r_pos, &biome.id());
+    }
+
+    /// Fill the whole region with biome `biome_id`
+    pub fn fill_biome_id(&mut self, biome_id: &str) {
+        self.biome_palette = vec![biome_id.to_string()];
+        self.biomes = Some(Array3::zeros(self.array_yzx.raw_dim()));
+    }
+
+    /// Fill the whole region with `biome`
+    pub fn fill_biome(&mut self, biome: Biome) {
+        self.fill_biome_id(&biome.id());
+    }
+
+    /// Remove all biomes
+    pub fn clear_biomes(&mut self) {
+        self.biomes = None;
+        self.biome_palette.clear();
+    }
+
+    /// Build a grid index over entities and block entities, see [`SpatialIndex`]. Use it when
+    /// querying many boxes on a region with lots of entities.
+    pub fn spatial_index(&self, cell_size: i32) -> SpatialIndex<'_> {
+        return SpatialIndex::new(self, cell_size);
+    }
+
+    /// Entities whose `block_pos` is inside `aabb`. This function scans all entities, build a
+    /// [`SpatialIndex`] for repeated queries.
+    pub fn entities_in(&self, aabb: &BlockBox) -> Vec<&Entity> {
+        return self
+            .entities
+            .iter()
+            .filter(|e| aabb.contains(e.block_pos))
+            .collect();
+    }
+
+    /// Block entities inside `aabb` with their positions, sorted by position. This function scans
+    /// all block entities, build a [`SpatialIndex`] for repeated queries.
+    pub fn block_entities_in(&self, aabb: &BlockBox) -> Vec<([i32; 3], &BlockEntity)> {
+        let mut result: Vec<([i32; 3], &BlockEntity)> = self
+            .block_entities
+            .iter()
+            .filter(|(pos, _)| aabb.contains(**pos))
+            .map(|(pos, be)| (*pos, be))
+            .collect();
+        result.sort_by_key(|(pos, _)| *pos);
+        return result;
+    }
+
     /// Get mutable block entity at `r_pos`
     pub fn block_entity_at_mut(&mut self, r_pos: [i32; 3]) -> Option<&mut BlockEntity> {
         return self.block_entities.get_mut(&r_pos);
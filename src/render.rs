@@ -0,0 +1,787 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::block_color::BlockColorTable;
+use crate::error::Error;
+use crate::region::{Region, WorldSlice};
+use crate::schem::Schematic;
+use crate::transform::{EntityTransformer, Mirror, Rotation};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// Base colors of vanilla maps, used to render previews without textures.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapColor {
+    /// Invisible on maps, like air, glass and torches
+    Transparent = 0,
+    Grass,
+    Sand,
+    Wool,
+    Fire,
+    Ice,
+    Metal,
+    Plant,
+    Snow,
+    Clay,
+    Dirt,
+    Stone,
+    Water,
+    Wood,
+    Quartz,
+    ColorOrange,
+    ColorMagenta,
+    ColorLightBlue,
+    ColorYellow,
+    ColorLightGreen,
+    ColorPink,
+    ColorGray,
+    ColorLightGray,
+    ColorCyan,
+    ColorPurple,
+    ColorBlue,
+    ColorBrown,
+    ColorGreen,
+    ColorRed,
+    ColorBlack,
+    Gold,
+    Diamond,
+    Lapis,
+    Emerald,
+    Podzol,
+    Nether,
+    TerracottaWhite,
+    TerracottaOrange,
+    TerracottaMagenta,
+    TerracottaLightBlue,
+    TerracottaYellow,
+    TerracottaLightGreen,
+    TerracottaPink,
+    TerracottaGray,
+    TerracottaLightGray,
+    TerracottaCyan,
+    TerracottaPurple,
+    TerracottaBlue,
+    TerracottaBrown,
+    TerracottaGreen,
+    TerracottaRed,
+    TerracottaBlack,
+    CrimsonNylium,
+    CrimsonStem,
+    CrimsonHyphae,
+    WarpedNylium,
+    WarpedStem,
+    WarpedHyphae,
+    WarpedWartBlock,
+    Deepslate,
+    RawIron,
+    GlowLichen,
+}
+
+const MAP_COLOR_RGB: [u32; 62] = [
+    0x000000, 0x7FB238, 0xF7E9A3, 0xC7C7C7, 0xFF0000, 0xA0A0FF, 0xA7A7A7, 0x007C00, 0xFFFFFF,
+    0xA4A8B8, 0x976D4D, 0x707070, 0x4040FF, 0x8F7748, 0xFFFCF5, 0xD87F33, 0xB24CD8, 0x6699D8,
+    0xE5E533, 0x7FCC19, 0xF27FA5, 0x4C4C4C, 0x999999, 0x4C7F99, 0x7F3FB2, 0x334CB2, 0x664C33,
+    0x667F33, 0x993333, 0x191919, 0xFAEE4D, 0x5CDBD5, 0x4A80FF, 0x00D93A, 0x815631, 0x700200,
+    0xD1B1A1, 0x9F5224, 0x95576C, 0x706C8A, 0xBA8524, 0x677535, 0xA04D4E, 0x392923, 0x876B62,
+    0x575C5C, 0x7A4958, 0x4C3E5C, 0x4C3223, 0x4C522A, 0x8E3C2E, 0x251610, 0xBD3031, 0x943F61,
+    0x5C191D, 0x167E86, 0x3A8E8C, 0x56214E, 0x14B485, 0x646464, 0xD8AF93, 0x7FA796,
+];
+
+/// Dye colors in block ids, longer names first so that `light_gray` is not taken as `gray`.
+/// Each color maps to (plain color, terracotta color).
+const DYE_COLORS: [(&str, MapColor, MapColor); 16] = [
+    (
+        "light_gray",
+        MapColor::ColorLightGray,
+        MapColor::TerracottaLightGray,
+    ),
+    (
+        "light_blue",
+        MapColor::ColorLightBlue,
+        MapColor::TerracottaLightBlue,
+    ),
+    ("white", MapColor::Snow, MapColor::TerracottaWhite),
+    ("orange", MapColor::ColorOrange, MapColor::TerracottaOrange),
+    (
+        "magenta",
+        MapColor::ColorMagenta,
+        MapColor::TerracottaMagenta,
+    ),
+    ("yellow", MapColor::ColorYellow, MapColor::TerracottaYellow),
+    (
+        "lime",
+        MapColor::ColorLightGreen,
+        MapColor::TerracottaLightGreen,
+    ),
+    ("pink", MapColor::ColorPink, MapColor::TerracottaPink),
+    ("gray", MapColor::ColorGray, MapColor::TerracottaGray),
+    ("cyan", MapColor::ColorCyan, MapColor::TerracottaCyan),
+    ("purple", MapColor::ColorPurple, MapColor::TerracottaPurple),
+    ("blue", MapColor::ColorBlue, MapColor::TerracottaBlue),
+    ("brown", MapColor::ColorBrown, MapColor::TerracottaBrown),
+    ("green", MapColor::ColorGreen, MapColor::TerracottaGreen),
+    ("red", MapColor::ColorRed, MapColor::TerracottaRed),
+    ("black", MapColor::ColorBlack, MapColor::TerracottaBlack),
+];
+
+#[allow(dead_code)]
+impl MapColor {
+    /// RGB of this color on maps, without height shading
+    pub fn rgb(&self) -> [u8; 3] {
+        let c = MAP_COLOR_RGB[*self as usize];
+        return [(c >> 16) as u8, (c >> 8) as u8, c as u8];
+    }
+
+    /// Guess the map color of a block by its id. Vanilla blocks are matched by name patterns,
+    /// so this is close to the game but not exact. Unknown blocks are taken as stone.
+    pub fn of_block(block: &Block) -> MapColor {
+        let id = block.id.as_str();
+        const TRANSPARENT: [&str; 16] = [
+            "air",
+            "cave_air",
+            "void_air",
+            "structure_void",
+            "barrier",
+            "light",
+            "glass",
+            "glass_pane",
+            "redstone_wire",
+            "tripwire",
+            "tripwire_hook",
+            "lever",
+            "ladder",
+            "repeater",
+            "comparator",
+            "moving_piston",
+        ];
+        if TRANSPARENT.contains(&id)
+            || id.contains("torch")
+            || id.ends_with("rail")
+            || id.ends_with("_button")
+        {
+            return MapColor::Transparent;
+        }
+
+        for (name, color, terracotta) in DYE_COLORS {
+            if let Some(rest) = id.strip_prefix(name).and_then(|r| r.strip_prefix('_')) {
+                if rest == "terracotta" {
+                    return terracotta;
+                }
+                return color;
+            }
+        }
+
+        let exact = match id {
+            "grass_block" | "slime_block" => Some(MapColor::Grass),
+            "clay" => Some(MapColor::Clay),
+            "podzol" => Some(MapColor::Podzol),
+            "mycelium" => Some(MapColor::ColorPurple),
+            "obsidian" | "crying_obsidian" | "coal_block" => Some(MapColor::ColorBlack),
+            "terracotta" | "pumpkin" | "carved_pumpkin" | "jack_o_lantern" => {
+                Some(MapColor::ColorOrange)
+            }
+            "melon" => Some(MapColor::ColorLightGreen),
+            "hay_block" | "sponge" | "wet_sponge" => Some(MapColor::ColorYellow),
+            "gold_block" => Some(MapColor::Gold),
+            "diamond_block" | "prismarine_bricks" | "dark_prismarine" => Some(MapColor::Diamond),
+            "lapis_block" => Some(MapColor::Lapis),
+            "emerald_block" => Some(MapColor::Emerald),
+            "raw_iron_block" => Some(MapColor::RawIron),
+            "glow_lichen" => Some(MapColor::GlowLichen),
+            "crimson_nylium" => Some(MapColor::CrimsonNylium),
+            "warped_nylium" => Some(MapColor::WarpedNylium),
+            "warped_wart_block" => Some(MapColor::WarpedWartBlock),
+            "soul_sand" | "soul_soil" => Some(MapColor::ColorBrown),
+            "redstone_block" | "tnt" | "fire" | "lava" => Some(MapColor::Fire),
+            "tinted_glass" => Some(MapColor::ColorGray),
+            _ => None,
+        };
+        if let Some(color) = exact {
+            return color;
+        }
+
+        // ordered patterns, the first one contained in id wins
+        const PATTERNS: [(&str, MapColor); 38] = [
+            ("crimson_hyphae", MapColor::CrimsonHyphae),
+            ("warped_hyphae", MapColor::WarpedHyphae),
+            ("crimson", MapColor::CrimsonStem),
+            ("warped", MapColor::WarpedStem),
+            ("water", MapColor::Water),
+            ("bubble_column", MapColor::Water),
+            ("kelp", MapColor::Water),
+            ("seagrass", MapColor::Water),
+            ("ice", MapColor::Ice),
+            ("snow", MapColor::Snow),
+            ("leaves", MapColor::Plant),
+            ("sapling", MapColor::Plant),
+            ("vine", MapColor::Plant),
+            ("grass", MapColor::Plant),
+            ("fern", MapColor::Plant),
+            ("cactus", MapColor::Plant),
+            ("sugar_cane", MapColor::Plant),
+            ("wheat", MapColor::Plant),
+            ("lily_pad", MapColor::Plant),
+            ("deepslate", MapColor::Deepslate),
+            ("quartz", MapColor::Quartz),
+            ("diorite", MapColor::Quartz),
+            ("sea_lantern", MapColor::Quartz),
+            ("iron", MapColor::Metal),
+            ("anvil", MapColor::Metal),
+            ("copper", MapColor::ColorOrange),
+            ("prismarine", MapColor::ColorCyan),
+            ("purpur", MapColor::ColorMagenta),
+            ("nether", MapColor::Nether),
+            ("magma", MapColor::Nether),
+            ("sand", MapColor::Sand),
+            ("end_stone", MapColor::Sand),
+            ("glowstone", MapColor::Sand),
+            ("bone_block", MapColor::Sand),
+            ("dirt", MapColor::Dirt),
+            ("farmland", MapColor::Dirt),
+            ("granite", MapColor::Dirt),
+            ("wool", MapColor::Wool),
+        ];
+        for (pattern, color) in PATTERNS {
+            if id.contains(pattern) {
+                return color;
+            }
+        }
+
+        const WOODS: [(&str, MapColor); 10] = [
+            ("spruce", MapColor::Podzol),
+            ("birch", MapColor::Sand),
+            ("jungle", MapColor::Dirt),
+            ("acacia", MapColor::ColorOrange),
+            ("dark_oak", MapColor::ColorBrown),
+            ("mangrove", MapColor::ColorRed),
+            ("cherry", MapColor::TerracottaWhite),
+            ("bamboo", MapColor::ColorYellow),
+            ("oak", MapColor::Wood),
+            ("mushroom_stem", MapColor::Wool),
+        ];
+        for (wood, color) in WOODS {
+            if id.contains(wood) {
+                return color;
+            }
+        }
+        if id.ends_with("_log")
+            || id.ends_with("_planks")
+            || id.ends_with("_wood")
+            || id.contains("chest")
+            || id.contains("crafting_table")
+            || id.contains("bookshelf")
+        {
+            return MapColor::Wood;
+        }
+        return MapColor::Stone;
+    }
+}
+
+/// An RGBA image with 8 bits per channel, pixels are stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// `width * height * 4` bytes
+    pub pixels: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl RgbaImage {
+    /// Create an image filled with `color`
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        let pixels = color
+            .iter()
+            .copied()
+            .cycle()
+            .take(width as usize * height as usize * 4)
+            .collect();
+        return RgbaImage {
+            width,
+            height,
+            pixels,
+        };
+    }
+
+    fn pixel_index(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        return Some((y as usize * self.width as usize + x as usize) * 4);
+    }
+
+    /// Returns the pixel at `(x, y)`, or `None` if it's out of range
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let idx = self.pixel_index(x, y)?;
+        return Some(std::array::from_fn(|c| self.pixels[idx + c]));
+    }
+
+    /// Set the pixel at `(x, y)`, does nothing if it's out of range
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if let Some(idx) = self.pixel_index(x, y) {
+            self.pixels[idx..(idx + 4)].copy_from_slice(&color);
+        }
+    }
+
+    /// Encode the image as png into `dest`
+    pub fn write_png(&self, dest: &mut dyn Write) -> Result<(), Error> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&self.width.to_be_bytes());
+        ihdr.extend_from_slice(&self.height.to_be_bytes());
+        // 8 bits per channel, RGBA, default compression, filter and no interlace
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        let row_bytes = self.width as usize * 4;
+        let result: std::io::Result<Vec<u8>> = (|| {
+            for row in self.pixels.chunks(row_bytes.max(1)) {
+                // filter type 0: none
+                encoder.write_all(&[0])?;
+                encoder.write_all(row)?;
+            }
+            return encoder.finish();
+        })();
+        let idat = result.map_err(|e| Error::NBTWriteError(e.into()))?;
+
+        let write_chunk = |dest: &mut dyn Write, kind: &[u8; 4], data: &[u8]| {
+            let mut crc = flate2::Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            dest.write_all(&(data.len() as u32).to_be_bytes())?;
+            dest.write_all(kind)?;
+            dest.write_all(data)?;
+            return dest.write_all(&crc.sum().to_be_bytes());
+        };
+        let result: std::io::Result<()> = (|| {
+            dest.write_all(b"\x89PNG\r\n\x1a\n")?;
+            write_chunk(dest, b"IHDR", &ihdr)?;
+            write_chunk(dest, b"IDAT", &idat)?;
+            return write_chunk(dest, b"IEND", &[]);
+        })();
+        return result.map_err(|e| Error::NBTWriteError(e.into()));
+    }
+
+    /// Decode a png image. Non-interlaced images of all color types are supported, including
+    /// palette images with transparency. 16-bit channels keep the high byte.
+    pub fn from_png(src: &[u8]) -> Result<RgbaImage, Error> {
+        let invalid = |error: &str| Error::InvalidValue {
+            tag_path: "png".to_string(),
+            error: error.to_string(),
+        };
+        let Some(mut rest) = src.strip_prefix(b"\x89PNG\r\n\x1a\n") else {
+            return Err(invalid("missing png signature"));
+        };
+        let mut ihdr: Option<&[u8]> = None;
+        let mut plte: &[u8] = &[];
+        let mut trns: &[u8] = &[];
+        let mut idat = Vec::new();
+        while rest.len() >= 12 {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            if len > rest.len() - 12 {
+                return Err(invalid("chunk exceeds the end of file"));
+            }
+            let kind = &rest[4..8];
+            let data = &rest[8..(8 + len)];
+            match kind {
+                b"IHDR" => ihdr = Some(data),
+                b"PLTE" => plte = data,
+                b"tRNS" => trns = data,
+                b"IDAT" => idat.extend_from_slice(data),
+                b"IEND" => break,
+                _ => {}
+            }
+            rest = &rest[(12 + len)..];
+        }
+        let Some(ihdr) = ihdr.filter(|h| h.len() == 13) else {
+            return Err(invalid("missing or invalid IHDR"));
+        };
+        let width = u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]);
+        let height = u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]);
+        let (bit_depth, color_type, interlace) = (ihdr[8], ihdr[9], ihdr[12]);
+        let (channels, valid_depths): (usize, &[u8]) = match color_type {
+            0 => (1, &[1, 2, 4, 8, 16]),
+            2 => (3, &[8, 16]),
+            3 => (1, &[1, 2, 4, 8]),
+            4 => (2, &[8, 16]),
+            6 => (4, &[8, 16]),
+            _ => return Err(invalid(&format!("unsupported color type {color_type}"))),
+        };
+        if !valid_depths.contains(&bit_depth) {
+            return Err(invalid(&format!(
+                "bit depth {bit_depth} is invalid for color type {color_type}"
+            )));
+        }
+        if color_type == 3 && plte.is_empty() {
+            return Err(invalid("missing PLTE of palette image"));
+        }
+        if interlace != 0 {
+            return Err(invalid("interlaced png is not supported"));
+        }
+        let bits_per_pixel = channels * bit_depth as usize;
+        // bytes per complete pixel used by filters, at least 1
+        let bpp = bits_per_pixel.div_ceil(8);
+        let row_bytes = (width as usize * bits_per_pixel).div_ceil(8);
+        let expected = (row_bytes + 1)
+            .checked_mul(height as usize)
+            .ok_or_else(|| invalid("image is too large"))?;
+
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(idat.as_slice())
+            .take(expected as u64)
+            .read_to_end(&mut raw)
+            .map_err(|e| invalid(&format!("failed to decompress IDAT: {e}")))?;
+        if raw.len() != expected {
+            return Err(invalid("image data is incomplete"));
+        }
+
+        let mut image = RgbaImage::new(width, height, [0, 0, 0, 255]);
+        let mut prev = vec![0u8; row_bytes];
+        let mut cur = vec![0u8; row_bytes];
+        for (y, line) in raw.chunks(row_bytes + 1).enumerate() {
+            let filter = line[0];
+            cur.copy_from_slice(&line[1..]);
+            for i in 0..row_bytes {
+                let a = if i >= bpp { cur[i - bpp] as i16 } else { 0 };
+                let b = prev[i] as i16;
+                let c = if i >= bpp { prev[i - bpp] as i16 } else { 0 };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => (a + b) / 2,
+                    4 => {
+                        let p = a + b - c;
+                        let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                        if pa <= pb && pa <= pc {
+                            a
+                        } else if pb <= pc {
+                            b
+                        } else {
+                            c
+                        }
+                    }
+                    _ => return Err(invalid(&format!("invalid filter type {filter}"))),
+                };
+                cur[i] = cur[i].wrapping_add(predictor as u8);
+            }
+            for x in 0..width as usize {
+                // channel c of this pixel, samples under 8 bits are returned unscaled, and 16-bit
+                // samples keep the high byte
+                let sample = |c: usize| -> u8 {
+                    let bit = (x * channels + c) * bit_depth as usize;
+                    return match bit_depth {
+                        16 | 8 => cur[bit / 8],
+                        _ => {
+                            let shift = 8 - bit_depth as usize - bit % 8;
+                            (cur[bit / 8] >> shift) & ((1u8 << bit_depth) - 1)
+                        }
+                    };
+                };
+                let rgba = match color_type {
+                    0 => {
+                        let v = sample(0);
+                        let gray = match bit_depth {
+                            1 | 2 | 4 => (v as u32 * 255 / ((1u32 << bit_depth) - 1)) as u8,
+                            _ => v,
+                        };
+                        [gray, gray, gray, 255]
+                    }
+                    3 => {
+                        let idx = sample(0) as usize;
+                        if idx * 3 + 2 >= plte.len() {
+                            return Err(invalid(&format!("palette index {idx} out of range")));
+                        }
+                        let alpha = trns.get(idx).copied().unwrap_or(255);
+                        [plte[idx * 3], plte[idx * 3 + 1], plte[idx * 3 + 2], alpha]
+                    }
+                    4 => [sample(0), sample(0), sample(0), sample(1)],
+                    2 => [sample(0), sample(1), sample(2), 255],
+                    _ => [sample(0), sample(1), sample(2), sample(3)],
+                };
+                image.set_pixel(x as u32, y as u32, rgba);
+            }
+            std::mem::swap(&mut prev, &mut cur);
+        }
+        return Ok(image);
+    }
+}
+
+/// Options of [`Schematic::render_top_down`]
+#[derive(Debug, Clone)]
+pub struct TopDownRenderOption {
+    /// Every block becomes a square of `pixels_per_block`, 0 is taken as 1
+    pub pixels_per_block: u32,
+    /// Make a block brighter if it's higher than the block to its north, and darker if lower,
+    /// like vanilla maps
+    pub shade_by_height: bool,
+    /// Color of columns without visible blocks
+    pub background: [u8; 4],
+    /// Colors of blocks instead of map colors. Blocks not in the table and invisible blocks
+    /// still use map colors.
+    pub block_colors: Option<Arc<BlockColorTable>>,
+}
+
+impl Default for TopDownRenderOption {
+    fn default() -> Self {
+        return TopDownRenderOption {
+            pixels_per_block: 1,
+            shade_by_height: true,
+            background: [0, 0, 0, 0],
+            block_colors: None,
+        };
+    }
+}
+
+/// Color of every block in palette, `None` for invisible blocks
+fn palette_colors(region: &Region, table: Option<&BlockColorTable>) -> Vec<Option<[u8; 3]>> {
+    return region
+        .palette
+        .iter()
+        .map(|blk| {
+            let map_color = MapColor::of_block(blk);
+            if map_color == MapColor::Transparent {
+                return None;
+            }
+            return Some(
+                table
+                    .and_then(|t| t.color_of(blk))
+                    .unwrap_or(map_color.rgb()),
+            );
+        })
+        .collect();
+}
+
+/// Options of [`Schematic::render_isometric`]
+#[derive(Debug, Clone)]
+pub struct IsometricRenderOption {
+    /// Width of the top face of a block in pixels, rounded down to a multiple of 4, at least 4.
+    /// The top face is half as high, and a whole block takes `tile_width` pixels vertically.
+    pub tile_width: u32,
+    /// View angle. With `Rotation::None` the camera is at the south-east above, so the top, south
+    /// and east faces are visible. Other values rotate the schematic clockwise before rendering.
+    pub rotation: Rotation,
+    /// Brightness of top, left and right faces, 255 means the original map color
+    pub face_brightness: [u8; 3],
+    /// Color of pixels without blocks
+    pub background: [u8; 4],
+    /// Colors of blocks instead of map colors, see [`TopDownRenderOption::block_colors`]
+    pub block_colors: Option<Arc<BlockColorTable>>,
+}
+
+impl Default for IsometricRenderOption {
+    fn default() -> Self {
+        return IsometricRenderOption {
+            tile_width: 16,
+            rotation: Rotation::None,
+            face_brightness: [255, 204, 153],
+            background: [0, 0, 0, 0],
+            block_colors: None,
+        };
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Lower and upper corner of all regions, or `None` if there is no region
+    fn render_bounds(&self) -> Option<([i32; 3], [i32; 3])> {
+        let first = self.regions.first()?;
+        let mut lower = first.offset;
+        let mut upper = first.global_box().upper;
+        for reg in &self.regions {
+            let g_box = reg.global_box();
+            for dim in 0..3 {
+                lower[dim] = lower[dim].min(g_box.lower[dim]);
+                upper[dim] = upper[dim].max(g_box.upper[dim]);
+            }
+        }
+        return Some((lower, upper));
+    }
+
+    /// Render a top-down view of all regions with vanilla map colors, so that thumbnails can be
+    /// made without a 3D renderer. Every column shows its highest visible block, north is up and
+    /// the top-left pixel is the lowest x and z of all regions.
+    pub fn render_top_down(&self, option: &TopDownRenderOption) -> RgbaImage {
+        let scale = option.pixels_per_block.max(1);
+        let Some((lower, upper)) = self.render_bounds() else {
+            return RgbaImage::new(0, 0, option.background);
+        };
+        let size_x = (upper[0] - lower[0] + 1) as usize;
+        let size_z = (upper[2] - lower[2] + 1) as usize;
+
+        // (global y, color) of the highest visible block in every column, x-major
+        let mut top: Vec<Option<(i32, [u8; 3])>> = vec![None; size_x * size_z];
+        for reg in &self.regions {
+            let colors = palette_colors(reg, option.block_colors.as_deref());
+            let [shape_x, shape_y, shape_z] = reg.shape();
+            for x in 0..shape_x {
+                for z in 0..shape_z {
+                    let column = (x + reg.offset[0] - lower[0]) as usize * size_z
+                        + (z + reg.offset[2] - lower[2]) as usize;
+                    for y in (0..shape_y).rev() {
+                        let idx = reg.array_yzx[[y as usize, z as usize, x as usize]];
+                        let Some(color) = colors.get(idx as usize).copied().flatten() else {
+                            continue;
+                        };
+                        let g_y = y + reg.offset[1];
+                        if top[column].map_or(true, |(cur_y, _)| g_y > cur_y) {
+                            top[column] = Some((g_y, color));
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut image = RgbaImage::new(
+            size_x as u32 * scale,
+            size_z as u32 * scale,
+            option.background,
+        );
+        for x in 0..size_x {
+            for z in 0..size_z {
+                let Some((y, color)) = top[x * size_z + z] else {
+                    continue;
+                };
+                let mut brightness: u32 = 220;
+                if option.shade_by_height && z > 0 {
+                    if let Some((north_y, _)) = top[x * size_z + z - 1] {
+                        if y > north_y {
+                            brightness = 255;
+                        } else if y < north_y {
+                            brightness = 180;
+                        }
+                    }
+                }
+                let rgb = color.map(|c| (c as u32 * brightness / 255) as u8);
+                let rgba = [rgb[0], rgb[1], rgb[2], 255];
+                for dx in 0..scale {
+                    for dz in 0..scale {
+                        image.set_pixel(x as u32 * scale + dx, z as u32 * scale + dz, rgba);
+                    }
+                }
+            }
+        }
+        return image;
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Render an isometric view of all regions with vanilla map colors, showing three faces of
+    /// every block with different brightness. It's a cheap replacement of a 3D renderer for
+    /// thumbnails, blocks are drawn as flat colored cubes.
+    pub fn render_isometric(&self, option: &IsometricRenderOption) -> RgbaImage {
+        let Some((lower, upper)) = self.render_bounds() else {
+            return RgbaImage::new(0, 0, option.background);
+        };
+        let tile_width = (option.tile_width / 4).max(1) as i64 * 4;
+        let (half, quarter) = (tile_width / 2, tile_width / 4);
+        let bound_shape: [i32; 3] = std::array::from_fn(|dim| upper[dim] - lower[dim] + 1);
+        let transformer = EntityTransformer::new(option.rotation, Mirror::None, bound_shape);
+        let shape = transformer.transformed_shape();
+        let [size_x, size_y, size_z] = shape.map(|v| v as usize);
+        let grid_index = |pos: [i32; 3]| -> usize {
+            return (pos[1] as usize * size_z + pos[2] as usize) * size_x + pos[0] as usize;
+        };
+
+        // color of every position in the rotated bounding box, later regions cover earlier ones
+        let mut grid: Vec<Option<[u8; 3]>> = vec![None; size_x * size_y * size_z];
+        for reg in &self.regions {
+            let colors = palette_colors(reg, option.block_colors.as_deref());
+            let [shape_x, shape_y, shape_z] = reg.shape();
+            for y in 0..shape_y {
+                for z in 0..shape_z {
+                    for x in 0..shape_x {
+                        let idx = reg.array_yzx[[y as usize, z as usize, x as usize]];
+                        let Some(color) = colors.get(idx as usize).copied().flatten() else {
+                            continue;
+                        };
+                        let pos = [x, y, z];
+                        let local: [i32; 3] =
+                            std::array::from_fn(|dim| pos[dim] + reg.offset[dim] - lower[dim]);
+                        grid[grid_index(transformer.transform_block_pos(local))] = Some(color);
+                    }
+                }
+            }
+        }
+
+        let is_opaque = |pos: [i32; 3]| -> bool {
+            if (0..3).any(|dim| pos[dim] >= shape[dim]) {
+                return false;
+            }
+            return grid[grid_index(pos)].is_some();
+        };
+        // blocks are drawn from back to front. Cubes are projected along (1, 1, 1), so a block
+        // can only be covered by blocks with larger x + y + z.
+        let mut blocks: Vec<([i32; 3], [u8; 3])> = Vec::new();
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let Some(color) = grid[grid_index([x, y, z])] else {
+                        continue;
+                    };
+                    let hidden = is_opaque([x + 1, y, z])
+                        && is_opaque([x, y + 1, z])
+                        && is_opaque([x, y, z + 1]);
+                    if !hidden {
+                        blocks.push(([x, y, z], color));
+                    }
+                }
+            }
+        }
+        blocks.sort_by_key(|(pos, _)| pos[0] + pos[1] + pos[2]);
+
+        let width = (shape[0] + shape[2]) as i64 * half;
+        let height = (shape[0] + shape[2]) as i64 * quarter + shape[1] as i64 * half;
+        let mut image = RgbaImage::new(width as u32, height as u32, option.background);
+        let face_colors = |rgb: [u8; 3]| -> [[u8; 4]; 3] {
+            return option.face_brightness.map(|b| {
+                let c = rgb.map(|c| (c as u32 * b as u32 / 255) as u8);
+                [c[0], c[1], c[2], 255]
+            });
+        };
+        for ([x, y, z], color) in blocks {
+            let [top, left, right] = face_colors(color);
+            // the top vertex of the hexagon, which is the projection of corner (x, y+1, z)
+            let top_x = (x - z + shape[2]) as i64 * half;
+            let top_y = (x + z) as i64 * quarter + (shape[1] - y - 1) as i64 * half;
+            for py in 0..tile_width {
+                for px in 0..tile_width {
+                    // pixel center relative to the top vertex, doubled to stay integral
+                    let u = (2 * px + 1 - tile_width).abs();
+                    let v = 2 * py + 1;
+                    if 2 * v < u || 2 * v > 4 * tile_width - u {
+                        continue;
+                    }
+                    let face = if 2 * v <= 2 * tile_width - u {
+                        top
+                    } else if 2 * px + 1 < tile_width {
+                        left
+                    } else {
+                        right
+                    };
+                    image.set_pixel((top_x - half + px) as u32, (top_y + py) as u32, face);
+                }
+            }
+        }
+        return image;
+    }
+}
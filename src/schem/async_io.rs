@@ -0,0 +1,79 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Async loading and saving for the `tokio` runtime. Files are read and written with async io,
+//! while NBT decoding and encoding run in the blocking thread pool, so the runtime is never
+//! blocked by a large schematic.
+
+use crate::error::Error;
+use crate::schem::{LitematicaLoadOption, LitematicaMetaData, LitematicaSaveOption, Schematic};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::task::JoinHandle;
+
+/// Wait for a blocking task, panics in the task are resumed in the caller.
+async fn join_blocking<T>(handle: JoinHandle<Result<T, Error>>) -> Result<T, Error> {
+    return match handle.await {
+        Ok(res) => res,
+        Err(e) => {
+            if e.is_panic() {
+                std::panic::resume_unwind(e.into_panic());
+            }
+            // the runtime is shutting down
+            Err(Error::Cancelled)
+        }
+    };
+}
+
+impl Schematic {
+    /// Load `.litematic` from async reader. The whole file is read into memory, then decoded in
+    /// the blocking thread pool, so it must be called inside a tokio runtime.
+    pub async fn from_litematica_async<R: AsyncRead + Unpin>(
+        src: &mut R,
+        option: LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let mut bytes = Vec::new();
+        src.read_to_end(&mut bytes)
+            .await
+            .map_err(Error::IOReadError)?;
+        let handle = tokio::task::spawn_blocking(move || {
+            return Schematic::from_litematica_bytes(&bytes, &option);
+        });
+        return join_blocking(handle).await;
+    }
+
+    /// Save `.litematic` to async writer. The schematic is cloned and encoded in the blocking
+    /// thread pool, so it must be called inside a tokio runtime.
+    pub async fn save_litematica_async<W: AsyncWrite + Unpin>(
+        &self,
+        dest: &mut W,
+        option: LitematicaSaveOption,
+    ) -> Result<(), Error> {
+        let schem = self.clone();
+        let handle = tokio::task::spawn_blocking(move || {
+            return schem.to_litematica_bytes(&option);
+        });
+        let bytes = join_blocking(handle).await?;
+        dest.write_all(&bytes)
+            .await
+            .map_err(|e| Error::NBTWriteError(e.into()))?;
+        dest.flush()
+            .await
+            .map_err(|e| Error::NBTWriteError(e.into()))?;
+        return Ok(());
+    }
+}
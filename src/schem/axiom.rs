@@ -0,0 +1,629 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{Region, WorldSlice};
+use crate::render::IsometricRenderOption;
+use crate::schem::{
+    common, id_of_nbt_tag, AxiomMetaData, AxiomSaveOption, CompressMethod, MetaDataIR, Schematic,
+};
+use crate::{unwrap_opt_tag, unwrap_tag};
+use fastnbt::{LongArray, Value};
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// First 4 bytes of a `.bp` file, in big endian
+pub const AXIOM_BLUEPRINT_MAGIC: u32 = 0x0AE5BB36;
+
+/// Blocks are stored in sections of 16x16x16, like chunk sections of a world
+const SECTION_VOLUME: usize = 4096;
+
+/// Max blocks of the region that sections are copied into, sections far away from each other
+/// would need a huge region
+const MAX_VOLUME: u64 = i32::MAX as u64;
+
+#[allow(dead_code)]
+impl MetaDataIR {
+    pub fn from_axiom(src: &AxiomMetaData) -> MetaDataIR {
+        let mut result = MetaDataIR::default();
+        result.mc_data_version = src.data_version;
+        result.name = src.name.clone();
+        result.author = src.author.clone();
+        result.axiom_tags = src.tags.clone();
+        if !src.thumbnail.is_empty() {
+            result.axiom_thumbnail = Some(src.thumbnail.clone());
+        }
+        return result;
+    }
+
+    pub fn to_axiom(&self) -> AxiomMetaData {
+        return AxiomMetaData {
+            data_version: self.mc_data_version,
+            name: self.name.clone(),
+            author: self.author.clone(),
+            tags: self.axiom_tags.clone(),
+            thumbnail: self.axiom_thumbnail.clone().unwrap_or_default(),
+            ..AxiomMetaData::default()
+        };
+    }
+}
+
+/// Bits per block in packed data, the same as chunk sections of vanilla.
+fn bits_per_block(palette_len: usize) -> u32 {
+    let ceil_log2 = usize::BITS - palette_len.saturating_sub(1).leading_zeros();
+    return ceil_log2.max(4);
+}
+
+/// Pack indices into longs, indices never span across 2 longs.
+fn pack_section(indices: &[u16], bits: u32) -> Vec<i64> {
+    let per_long = (64 / bits) as usize;
+    let mut data = vec![0u64; indices.len().div_ceil(per_long)];
+    for (idx, value) in indices.iter().enumerate() {
+        data[idx / per_long] |= (*value as u64) << ((idx % per_long) as u32 * bits);
+    }
+    return data.into_iter().map(|v| v as i64).collect();
+}
+
+fn unpack_section(data: &[i64], bits: u32) -> Vec<u16> {
+    let per_long = (64 / bits) as usize;
+    let mask = (1u64 << bits) - 1;
+    return (0..SECTION_VOLUME)
+        .map(|idx| {
+            let long = data[idx / per_long] as u64;
+            return ((long >> ((idx % per_long) as u32 * bits)) & mask) as u16;
+        })
+        .collect();
+}
+
+/// Index of block in section, `local` is in x, y, z
+fn section_index(local: [usize; 3]) -> usize {
+    return (local[1] * 16 + local[2]) * 16 + local[0];
+}
+
+struct Section {
+    /// Position in sections
+    pos: [i32; 3],
+    palette: Vec<Block>,
+    /// Indices in `palette`, ordered by `section_index`
+    indices: Vec<u16>,
+}
+
+fn parse_section(nbt: &HashMap<String, Value>, tag_path: &str) -> Result<Section, Error> {
+    let mut pos = [0; 3];
+    for (dim, key) in ["X", "Y", "Z"].iter().enumerate() {
+        pos[dim] = *unwrap_opt_tag!(nbt.get(*key), Int, 0, format!("{tag_path}/{key}"));
+    }
+
+    let states_path = format!("{tag_path}/BlockStates");
+    let states = unwrap_opt_tag!(
+        nbt.get("BlockStates"),
+        Compound,
+        HashMap::new(),
+        &states_path
+    );
+    let palette_tag = unwrap_opt_tag!(
+        states.get("palette"),
+        List,
+        vec![],
+        format!("{states_path}/palette")
+    );
+    if palette_tag.is_empty() {
+        return Err(Error::InvalidValue {
+            tag_path: format!("{states_path}/palette"),
+            error: "Palette should not be empty".to_string(),
+        });
+    }
+    let mut palette = Vec::with_capacity(palette_tag.len());
+    for (idx, blk) in palette_tag.iter().enumerate() {
+        let blk_path = format!("{states_path}/palette[{idx}]");
+        let blk_comp = unwrap_tag!(blk, Compound, HashMap::new(), &blk_path);
+        palette.push(common::parse_block(blk_comp, &blk_path)?);
+    }
+
+    // a section of only one block has no data
+    if palette.len() == 1 {
+        return Ok(Section {
+            pos,
+            palette,
+            indices: vec![0; SECTION_VOLUME],
+        });
+    }
+    let data_path = format!("{states_path}/data");
+    let data = unwrap_opt_tag!(
+        states.get("data"),
+        LongArray,
+        LongArray::new(vec![]),
+        &data_path
+    );
+    let bits = bits_per_block(palette.len());
+    let expected_len = SECTION_VOLUME.div_ceil((64 / bits) as usize);
+    if data.len() != expected_len {
+        return Err(Error::InvalidValue {
+            tag_path: data_path,
+            error: format!(
+                "Expected {expected_len} longs for {} blocks in palette, but found {}",
+                palette.len(),
+                data.len()
+            ),
+        });
+    }
+    let indices = unpack_section(data, bits);
+    for (idx, block_index) in indices.iter().enumerate() {
+        if *block_index as usize >= palette.len() {
+            return Err(Error::BlockIndexOutOfRange {
+                tag_path: format!("{data_path}[{idx}]"),
+                index: *block_index as i32,
+                range: [0, palette.len() as i32],
+            });
+        }
+    }
+    return Ok(Section {
+        pos,
+        palette,
+        indices,
+    });
+}
+
+fn read_u32(src: &mut dyn Read) -> Result<u32, Error> {
+    let mut bytes = [0u8; 4];
+    src.read_exact(&mut bytes).map_err(Error::IOReadError)?;
+    return Ok(u32::from_be_bytes(bytes));
+}
+
+/// Read a part prefixed by its length
+fn read_part(src: &mut dyn Read) -> Result<Vec<u8>, Error> {
+    let len = read_u32(src)? as u64;
+    let mut part = Vec::new();
+    src.take(len)
+        .read_to_end(&mut part)
+        .map_err(Error::IOReadError)?;
+    if part.len() as u64 != len {
+        return Err(Error::IOReadError(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!(
+                "Expected {len} bytes, but the file ends after {}",
+                part.len()
+            ),
+        )));
+    }
+    return Ok(part);
+}
+
+fn parse_header(nbt: &HashMap<String, Value>) -> Result<AxiomMetaData, Error> {
+    let mut md = AxiomMetaData {
+        name: unwrap_opt_tag!(nbt.get("Name"), String, String::new(), "/Name").clone(),
+        author: unwrap_opt_tag!(nbt.get("Author"), String, String::new(), "/Author").clone(),
+        ..AxiomMetaData::default()
+    };
+    if let Some(tags) = nbt.get("Tags") {
+        for (idx, tag) in unwrap_tag!(tags, List, vec![], "/Tags").iter().enumerate() {
+            let tag = unwrap_tag!(tag, String, String::new(), format!("/Tags[{idx}]"));
+            md.tags.push(tag.clone());
+        }
+    }
+    if let Some(yaw) = nbt.get("ThumbnailYaw") {
+        md.thumbnail_yaw = *unwrap_tag!(yaw, Float, 0.0, "/ThumbnailYaw");
+    }
+    if let Some(pitch) = nbt.get("ThumbnailPitch") {
+        md.thumbnail_pitch = *unwrap_tag!(pitch, Float, 0.0, "/ThumbnailPitch");
+    }
+    if let Some(locked) = nbt.get("LockedThumbnail") {
+        md.locked_thumbnail = *unwrap_tag!(locked, Byte, 0, "/LockedThumbnail") != 0;
+    }
+    if let Some(count) = nbt.get("BlockCount") {
+        md.block_count = *unwrap_tag!(count, Int, 0, "/BlockCount");
+    }
+    if let Some(contains_air) = nbt.get("ContainsAir") {
+        md.contains_air = *unwrap_tag!(contains_air, Byte, 0, "/ContainsAir") != 0;
+    }
+    return Ok(md);
+}
+
+fn header_to_nbt(md: &AxiomMetaData) -> HashMap<String, Value> {
+    let mut nbt = HashMap::new();
+    nbt.insert("Version".to_string(), Value::Int(1));
+    nbt.insert("Name".to_string(), Value::String(md.name.clone()));
+    nbt.insert("Author".to_string(), Value::String(md.author.clone()));
+    nbt.insert(
+        "Tags".to_string(),
+        Value::List(md.tags.iter().map(|t| Value::String(t.clone())).collect()),
+    );
+    nbt.insert("ThumbnailYaw".to_string(), Value::Float(md.thumbnail_yaw));
+    nbt.insert(
+        "ThumbnailPitch".to_string(),
+        Value::Float(md.thumbnail_pitch),
+    );
+    nbt.insert(
+        "LockedThumbnail".to_string(),
+        Value::Byte(md.locked_thumbnail as i8),
+    );
+    nbt.insert("BlockCount".to_string(), Value::Int(md.block_count));
+    nbt.insert(
+        "ContainsAir".to_string(),
+        Value::Byte(md.contains_air as i8),
+    );
+    return nbt;
+}
+
+/// Axiom blueprint (`.bp`) is made of a magic number and 3 parts, each prefixed by its length as
+/// big endian u32: an uncompressed nbt header, a png thumbnail, and gzip-compressed nbt of blocks.
+/// Axiom may compress block entities with zstd, such block entities are not supported.
+#[allow(dead_code)]
+impl Schematic {
+    /// Load axiom blueprint from file
+    #[cfg(feature = "fs")]
+    pub fn from_axiom_file(filename: &str) -> Result<(Schematic, AxiomMetaData), Error> {
+        let mut file = match File::open(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileOpenError(e)),
+        };
+        return Self::from_axiom_reader(&mut file);
+    }
+
+    /// Load axiom blueprint from bytes, the content of a `.bp` file
+    pub fn from_axiom_bytes(bytes: &[u8]) -> Result<(Schematic, AxiomMetaData), Error> {
+        let mut src = bytes;
+        return Self::from_axiom_reader(&mut src);
+    }
+
+    /// Load axiom blueprint from reader
+    pub fn from_axiom_reader(src: &mut dyn Read) -> Result<(Schematic, AxiomMetaData), Error> {
+        let magic = read_u32(src)?;
+        if magic != AXIOM_BLUEPRINT_MAGIC {
+            return Err(Error::InvalidValue {
+                tag_path: "".to_string(),
+                error: format!(
+                    "Expected magic number {AXIOM_BLUEPRINT_MAGIC:#010x} of axiom blueprint, but found {magic:#010x}"
+                ),
+            });
+        }
+        let header_bytes = read_part(src)?;
+        let header = common::parse_nbt_compound(&header_bytes)?;
+        let thumbnail = read_part(src)?;
+        let block_bytes = read_part(src)?;
+        let blocks =
+            common::read_nbt_compound(&mut common::decompress_reader(block_bytes.as_slice())?)?;
+
+        let (mut schem, block_md) = Self::from_nbt_axiom(blocks)?;
+        let mut md = parse_header(&header)?;
+        md.data_version = block_md.data_version;
+        md.thumbnail = thumbnail;
+        schem.metadata = MetaDataIR::from_axiom(&md);
+        for reg in &mut schem.regions {
+            reg.name = md.name.clone();
+        }
+        return Ok((schem, md));
+    }
+
+    /// Load blocks of axiom blueprint from the nbt after the thumbnail. The header is not
+    /// included, so the returned metadata contains only data version.
+    pub fn from_nbt_axiom(
+        nbt: HashMap<String, Value>,
+    ) -> Result<(Schematic, AxiomMetaData), Error> {
+        let md = AxiomMetaData {
+            data_version: *unwrap_opt_tag!(nbt.get("DataVersion"), Int, 0, "/DataVersion"),
+            ..AxiomMetaData::default()
+        };
+
+        let section_tags = unwrap_opt_tag!(nbt.get("BlockRegion"), List, vec![], "/BlockRegion");
+        let mut sections = Vec::with_capacity(section_tags.len());
+        for (idx, tag) in section_tags.iter().enumerate() {
+            let tag_path = format!("/BlockRegion[{idx}]");
+            let comp = unwrap_tag!(tag, Compound, HashMap::new(), &tag_path);
+            sections.push(parse_section(comp, &tag_path)?);
+        }
+
+        let mut schem = Schematic::new();
+        schem.metadata = MetaDataIR::from_axiom(&md);
+        if sections.is_empty() {
+            return Ok((schem, md));
+        }
+        let mut lower = [i32::MAX; 3];
+        let mut upper = [i32::MIN; 3];
+        for section in &sections {
+            for dim in 0..3 {
+                lower[dim] = lower[dim].min(section.pos[dim]);
+                upper[dim] = upper[dim].max(section.pos[dim]);
+            }
+        }
+        let mut size = [0u64; 3];
+        for dim in 0..3 {
+            size[dim] = (upper[dim] as i64)
+                .checked_sub(lower[dim] as i64)
+                .and_then(|len| len.checked_add(1))
+                .and_then(|len| len.checked_mul(16))
+                .and_then(|len| u64::try_from(len).ok())
+                .ok_or(Error::SizeTooLarge {
+                    size: [u64::MAX; 3],
+                    max_size: [i32::MAX as u64; 3],
+                })?;
+        }
+        if size.iter().any(|len| *len > i32::MAX as u64) {
+            return Err(Error::SizeTooLarge {
+                size,
+                max_size: [i32::MAX as u64; 3],
+            });
+        }
+        let volume = size
+            .iter()
+            .try_fold(1u64, |vol, len| vol.checked_mul(*len))
+            .unwrap_or(u64::MAX);
+        if volume > MAX_VOLUME {
+            return Err(Error::VolumeTooLarge {
+                size,
+                max_volume: MAX_VOLUME,
+            });
+        }
+        let shape: [i32; 3] = size.map(|len| len as i32);
+
+        // positions that are not in any section are structure void
+        let mut region = Region::with_shape(shape);
+        region.palette = vec![Block::structure_void()];
+        for Section {
+            pos,
+            palette,
+            indices,
+        } in &sections
+        {
+            let lut: Vec<u16> = palette
+                .iter()
+                .map(|blk| region.find_or_append_to_palette(blk))
+                .collect();
+            let origin: [usize; 3] =
+                std::array::from_fn(|dim| ((pos[dim] as i64 - lower[dim] as i64) * 16) as usize);
+            for y in 0..16 {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let index = indices[section_index([x, y, z])];
+                        region.array_yzx[[origin[1] + y, origin[2] + z, origin[0] + x]] =
+                            lut[index as usize];
+                    }
+                }
+            }
+        }
+
+        if let Some(be_tags) = nbt.get("BlockEntities") {
+            let be_tags = unwrap_tag!(be_tags, List, vec![], "/BlockEntities");
+            for (idx, tag) in be_tags.iter().enumerate() {
+                let tag_path = format!("/BlockEntities[{idx}]");
+                let comp = unwrap_tag!(tag, Compound, HashMap::new(), &tag_path);
+                let (pos, be) = common::parse_block_entity_nocheck(comp.clone(), &tag_path, true)?;
+                let r_pos: [i64; 3] =
+                    std::array::from_fn(|dim| pos[dim] as i64 - lower[dim] as i64 * 16);
+                if (0..3).any(|dim| r_pos[dim] < 0 || r_pos[dim] >= shape[dim] as i64) {
+                    return Err(Error::BlockPosOutOfRange {
+                        tag_path,
+                        pos,
+                        lower_bound: lower.map(|v| v.saturating_mul(16)),
+                        upper_bound: upper.map(|v| v.saturating_mul(16).saturating_add(15)),
+                    });
+                }
+                let r_pos = r_pos.map(|v| v as i32);
+                region.block_entities.insert(r_pos, be);
+            }
+        }
+
+        // shrink to the blocks that are present
+        let mut bbox: Option<([i32; 3], [i32; 3])> = None;
+        for ((y, z, x), idx) in region.array_yzx.indexed_iter() {
+            if *idx == 0 {
+                continue;
+            }
+            let pos = [x as i32, y as i32, z as i32];
+            let (lo, up) = bbox.get_or_insert((pos, pos));
+            for dim in 0..3 {
+                lo[dim] = lo[dim].min(pos[dim]);
+                up[dim] = up[dim].max(pos[dim]);
+            }
+        }
+        let Some((lo, up)) = bbox else {
+            return Ok((schem, md));
+        };
+        let mut region = region
+            .cropped(&crate::region::BlockBox {
+                lower: lo,
+                upper: up,
+            })
+            .unwrap();
+        region.offset = [0, 0, 0];
+        schem.regions.push(region);
+        return Ok((schem, md));
+    }
+
+    /// Save blocks to nbt as the last part of axiom blueprint. Structure void and the space out of
+    /// regions are not saved.
+    pub fn to_nbt_axiom(&self, option: &AxiomSaveOption) -> Result<HashMap<String, Value>, Error> {
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.to_nbt_axiom(option);
+        }
+        let region = self.to_single_region(&Block::structure_void());
+        let is_void: Vec<bool> = region
+            .palette
+            .iter()
+            .map(|blk| blk.is_structure_void())
+            .collect();
+        let shape = region.shape();
+        let section_counts: [i32; 3] = std::array::from_fn(|dim| (shape[dim] + 15) / 16);
+
+        let mut sections = Vec::new();
+        // index in region palette -> index in section palette
+        let mut lut: Vec<Option<u16>> = vec![None; region.palette.len()];
+        for sy in 0..section_counts[1] {
+            for sz in 0..section_counts[2] {
+                for sx in 0..section_counts[0] {
+                    let origin = [sx * 16, sy * 16, sz * 16];
+                    lut.fill(None);
+                    let mut palette: Vec<u16> = Vec::new();
+                    let mut indices = vec![0u16; SECTION_VOLUME];
+                    let mut void_index: Option<u16> = None;
+                    let mut has_block = false;
+                    for y in 0..16 {
+                        for z in 0..16 {
+                            for x in 0..16 {
+                                let r_pos = [origin[0] + x, origin[1] + y, origin[2] + z];
+                                let region_index = region.block_index_at(r_pos);
+                                let local_index = match region_index {
+                                    Some(ri) if !is_void[ri as usize] => {
+                                        has_block = true;
+                                        *lut[ri as usize].get_or_insert_with(|| {
+                                            palette.push(ri);
+                                            return palette.len() as u16 - 1;
+                                        })
+                                    }
+                                    // the section palette refers to structure void by u16::MAX
+                                    _ => *void_index.get_or_insert_with(|| {
+                                        palette.push(u16::MAX);
+                                        return palette.len() as u16 - 1;
+                                    }),
+                                };
+                                indices[section_index([x as usize, y as usize, z as usize])] =
+                                    local_index;
+                            }
+                        }
+                    }
+                    if !has_block {
+                        continue;
+                    }
+
+                    let palette_nbt: Vec<Value> = palette
+                        .iter()
+                        .map(|ri| match region.palette.get(*ri as usize) {
+                            Some(blk) => Value::Compound(blk.to_nbt()),
+                            None => Value::Compound(Block::structure_void().to_nbt()),
+                        })
+                        .collect();
+                    let mut states = HashMap::new();
+                    if palette.len() > 1 {
+                        let bits = bits_per_block(palette.len());
+                        states.insert(
+                            "data".to_string(),
+                            Value::LongArray(LongArray::new(pack_section(&indices, bits))),
+                        );
+                    }
+                    states.insert("palette".to_string(), Value::List(palette_nbt));
+
+                    let mut section = HashMap::new();
+                    section.insert("X".to_string(), Value::Int(sx));
+                    section.insert("Y".to_string(), Value::Int(sy));
+                    section.insert("Z".to_string(), Value::Int(sz));
+                    section.insert("BlockStates".to_string(), Value::Compound(states));
+                    sections.push(Value::Compound(section));
+                }
+            }
+        }
+
+        let mut block_entities = Vec::with_capacity(region.block_entities.len());
+        for (pos, be) in &region.block_entities {
+            let mut tags = be.tags.clone();
+            tags.insert("x".to_string(), Value::Int(pos[0]));
+            tags.insert("y".to_string(), Value::Int(pos[1]));
+            tags.insert("z".to_string(), Value::Int(pos[2]));
+            block_entities.push(Value::Compound(tags));
+        }
+
+        let mut nbt = HashMap::new();
+        nbt.insert(
+            "DataVersion".to_string(),
+            Value::Int(self.metadata.mc_data_version),
+        );
+        nbt.insert("BlockRegion".to_string(), Value::List(sections));
+        nbt.insert("BlockEntities".to_string(), Value::List(block_entities));
+        return Ok(nbt);
+    }
+
+    /// Header of axiom blueprint of this schematic, block count and thumbnail are computed.
+    fn axiom_metadata(&self, option: &AxiomSaveOption) -> Result<AxiomMetaData, Error> {
+        let mut md = self.metadata.to_axiom();
+        let mut block_count: u64 = 0;
+        for reg in &self.regions {
+            let counts = reg.palette_counts();
+            for (blk, count) in reg.palette.iter().zip(counts) {
+                if blk.is_air() {
+                    md.contains_air |= count > 0;
+                } else if !blk.is_structure_void() {
+                    block_count += count;
+                }
+            }
+        }
+        md.block_count = block_count.min(i32::MAX as u64) as i32;
+
+        if md.thumbnail.is_empty() && option.render_thumbnail {
+            let shape = self.shape();
+            let tile_width = (1024 / (shape[0] + shape[2]).max(1)).clamp(4, 16) as u32;
+            let image = self.render_isometric(&IsometricRenderOption {
+                tile_width,
+                ..IsometricRenderOption::default()
+            });
+            if image.width > 0 && image.height > 0 {
+                image.write_png(&mut md.thumbnail)?;
+            }
+        }
+        return Ok(md);
+    }
+
+    /// Save schematic to writer as axiom blueprint
+    pub fn save_axiom_writer(
+        &self,
+        dest: &mut dyn Write,
+        option: &AxiomSaveOption,
+    ) -> Result<(), Error> {
+        let md = self.axiom_metadata(option)?;
+        let header = fastnbt::to_bytes(&header_to_nbt(&md)).map_err(Error::NBTWriteError)?;
+        let blocks = self.to_nbt_axiom(option)?;
+        let mut block_bytes = Vec::new();
+        common::write_compressed_nbt(
+            &mut block_bytes,
+            &blocks,
+            CompressMethod::Gzip,
+            option.compress_level,
+            None,
+        )?;
+
+        let write = |dest: &mut dyn Write| -> std::io::Result<()> {
+            dest.write_all(&AXIOM_BLUEPRINT_MAGIC.to_be_bytes())?;
+            for part in [&header, &md.thumbnail, &block_bytes] {
+                dest.write_all(&(part.len() as u32).to_be_bytes())?;
+                dest.write_all(part)?;
+            }
+            return Ok(());
+        };
+        return write(dest).map_err(|e| Error::NBTWriteError(e.into()));
+    }
+
+    /// Save schematic to bytes as axiom blueprint
+    pub fn to_axiom_bytes(&self, option: &AxiomSaveOption) -> Result<Vec<u8>, Error> {
+        let mut dest = Vec::new();
+        self.save_axiom_writer(&mut dest, option)?;
+        return Ok(dest);
+    }
+
+    /// Save schematic to file as axiom blueprint
+    #[cfg(feature = "fs")]
+    pub fn save_axiom_file(&self, filename: &str, option: &AxiomSaveOption) -> Result<(), Error> {
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        return self.save_axiom_writer(&mut file, option);
+    }
+}
@@ -0,0 +1,103 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use crate::region::{BlockBox, Region, WorldSlice};
+use ndarray::s;
+
+#[allow(dead_code)]
+impl Region {
+    /// Copy blocks in `src_box`(relative coordinates of `src`) to this region, so that the lower
+    /// corner of `src_box` lands at `dst_pos`(relative coordinates of this region). Parts outside
+    /// either region are clipped. Blocks are copied row by row through a palette lookup table, and
+    /// block entities and pending ticks of copied blocks replace existing ones. Entities and biomes
+    /// are not copied. \
+    /// Returns the count of copied blocks, or `PaletteTooLong` if the merged palette exceeds 65536.
+    pub fn blit_from(
+        &mut self,
+        src: &Region,
+        src_box: &BlockBox,
+        dst_pos: [i32; 3],
+    ) -> Result<u64, Error> {
+        let src_shape = src.shape();
+        let dst_shape = self.shape();
+        // destination pos = source pos + shift
+        let shift: [i32; 3] = std::array::from_fn(|dim| dst_pos[dim] - src_box.lower[dim]);
+        // clip to both regions, in source coordinates
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        for dim in 0..3 {
+            lower[dim] = src_box.lower[dim].max(0).max(-shift[dim]);
+            upper[dim] = src_box.upper[dim]
+                .min(src_shape[dim] - 1)
+                .min(dst_shape[dim] - 1 - shift[dim]);
+            if lower[dim] > upper[dim] {
+                return Ok(0);
+            }
+        }
+        let [x0, y0, z0] = lower.map(|v| v as usize);
+        let [x1, y1, z1] = upper.map(|v| v as usize + 1);
+        let [dx, dy, dz] = shift.map(|v| v as isize);
+        let src_view = src.array_yzx.slice(s![y0..y1, z0..z1, x0..x1]);
+
+        // map only the blocks that are copied, so unused blocks don't pollute the palette
+        let mut used = vec![false; src.palette.len()];
+        for idx in src_view.iter() {
+            used[*idx as usize] = true;
+        }
+        let mut lut = vec![0u16; src.palette.len()];
+        for (idx, blk) in src.palette.iter().enumerate() {
+            if used[idx] {
+                lut[idx] = self.find_or_append_to_palette(blk);
+            }
+        }
+        if self.palette.len() > 65536 {
+            return Err(Error::PaletteTooLong(self.palette.len()));
+        }
+
+        let mut dst_view = self.array_yzx.slice_mut(s![
+            (y0 as isize + dy)..(y1 as isize + dy),
+            (z0 as isize + dz)..(z1 as isize + dz),
+            (x0 as isize + dx)..(x1 as isize + dx)
+        ]);
+        dst_view.zip_mut_with(&src_view, |dst, src| *dst = lut[*src as usize]);
+
+        let src_r_box = BlockBox { lower, upper };
+        let dst_r_box = BlockBox {
+            lower: std::array::from_fn(|dim| lower[dim] + shift[dim]),
+            upper: std::array::from_fn(|dim| upper[dim] + shift[dim]),
+        };
+        let to_dst =
+            |pos: &[i32; 3]| -> [i32; 3] { std::array::from_fn(|dim| pos[dim] + shift[dim]) };
+        self.block_entities
+            .retain(|pos, _| !dst_r_box.contains(*pos));
+        self.pending_ticks
+            .retain(|pos, _| !dst_r_box.contains(*pos));
+        for (pos, be) in &src.block_entities {
+            if src_r_box.contains(*pos) {
+                self.block_entities.insert(to_dst(pos), be.clone());
+            }
+        }
+        for (pos, ticks) in &src.pending_ticks {
+            if src_r_box.contains(*pos) {
+                self.pending_ticks.insert(to_dst(pos), ticks.clone());
+            }
+        }
+        return Ok(src_view.len() as u64);
+    }
+}
@@ -0,0 +1,179 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use crate::schem::{common, AirPolicy, CompressMethod, Schematic};
+use flate2::Compression;
+use std::io::Write;
+
+/// Options to export schematic as a BO3 object of OpenTerrainGenerator
+#[derive(Debug, Clone)]
+pub struct Bo3ExportOption {
+    /// Author of the object, `None` takes the author in metadata
+    pub author: Option<String>,
+    /// Description of the object, `None` takes the description in metadata
+    pub description: Option<String>,
+    /// Global position that becomes `(0, 0, 0)` of the object. If `None`, it's the center of the
+    /// bottom layer of non-air blocks, so the object is centered where it spawns.
+    pub center: Option<[i32; 3]>,
+    /// Whether to write air, so that terrain in the object is cleared. Structure void is never
+    /// written.
+    pub air_policy: AirPolicy,
+    /// Write block entities to nbt files, and reference them in block lines
+    pub include_block_entities: bool,
+}
+
+impl Default for Bo3ExportOption {
+    fn default() -> Self {
+        return Bo3ExportOption {
+            author: None,
+            description: None,
+            center: None,
+            air_policy: AirPolicy::Omit,
+            include_block_entities: true,
+        };
+    }
+}
+
+/// A block entity file referenced by a BO3 object. It should be saved at `filename` relative to
+/// the folder of the `.bo3` file.
+#[derive(Debug, Clone)]
+pub struct Bo3NbtFile {
+    pub filename: String,
+    /// Gzip compressed nbt
+    pub content: Vec<u8>,
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Export blocks as a BO3 object, whose coordinates are relative to the object center (see
+    /// [`Bo3ExportOption::center`]). Block entities are referenced as
+    /// `{object_name}/{n}_{block id}.nbt`, and their content is returned so that the caller can
+    /// save them. Regions are exported in order, so latter regions overwrite former ones where
+    /// they overlap.
+    pub fn export_bo3(
+        &self,
+        dest: &mut dyn Write,
+        object_name: &str,
+        option: &Bo3ExportOption,
+    ) -> Result<Vec<Bo3NbtFile>, Error> {
+        let center = match (option.center, self.non_air_bounding_box()) {
+            (Some(center), _) => center,
+            (None, Some(g_box)) => [
+                (g_box.lower[0] + g_box.upper[0]).div_euclid(2),
+                g_box.lower[1],
+                (g_box.lower[2] + g_box.upper[2]).div_euclid(2),
+            ],
+            (None, None) => [0, 0, 0],
+        };
+        let author = option.author.as_ref().unwrap_or(&self.metadata.author);
+        let description = option
+            .description
+            .as_ref()
+            .unwrap_or(&self.metadata.description);
+        let single_line = |s: &str| s.replace(['\r', '\n'], " ");
+        let header = format!(
+            "# BO3 object exported by mc_schem\n\
+            Author: {}\n\
+            Description: {}\n\
+            Version: 3\n\
+            SettingsMode: WriteDisable\n\
+            RotateRandomly: false\n\n",
+            single_line(author),
+            single_line(description)
+        );
+        dest.write_all(header.as_bytes())
+            .map_err(|e| Error::NBTWriteError(e.into()))?;
+
+        let mut nbt_files = Vec::new();
+        for reg in &self.regions {
+            let skip: Vec<bool> = reg
+                .palette
+                .iter()
+                .map(|blk| {
+                    blk.is_structure_void()
+                        || (option.air_policy == AirPolicy::Omit && blk.is_air())
+                })
+                .collect();
+            for ((y, z, x), idx) in reg.array_yzx.indexed_iter() {
+                if skip.get(*idx as usize).copied().unwrap_or(true) {
+                    continue;
+                }
+                let block = &reg.palette[*idx as usize];
+                let r_pos = [x as i32, y as i32, z as i32];
+                let pos: [i32; 3] =
+                    std::array::from_fn(|dim| r_pos[dim] + reg.offset[dim] - center[dim]);
+                let mut line = format!("Block({},{},{},{}", pos[0], pos[1], pos[2], block);
+                if let Some(be) = reg.block_entities.get(&r_pos) {
+                    if option.include_block_entities {
+                        let mut tags = be.tags.clone();
+                        for key in ["x", "y", "z"] {
+                            tags.remove(key);
+                        }
+                        let filename =
+                            format!("{object_name}/{}_{}.nbt", nbt_files.len(), block.id);
+                        let mut content = Vec::new();
+                        common::write_compressed_nbt(
+                            &mut content,
+                            &tags,
+                            CompressMethod::Gzip,
+                            Compression::default(),
+                            None,
+                        )?;
+                        line.push(',');
+                        line.push_str(&filename);
+                        nbt_files.push(Bo3NbtFile { filename, content });
+                    }
+                }
+                line.push_str(")\n");
+                dest.write_all(line.as_bytes())
+                    .map_err(|e| Error::NBTWriteError(e.into()))?;
+            }
+        }
+        return Ok(nbt_files);
+    }
+
+    /// Save as a BO3 object to `filename`, and save block entities into a folder with the same
+    /// name beside it. The file stem is the object name.
+    #[cfg(feature = "fs")]
+    pub fn save_bo3_file(&self, filename: &str, option: &Bo3ExportOption) -> Result<(), Error> {
+        let path = std::path::Path::new(filename);
+        let stem = match path.file_stem() {
+            Some(s) => s.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::FileCreateError(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid filename \"{filename}\""),
+                )))
+            }
+        };
+        let dir = path.parent().unwrap_or(std::path::Path::new(""));
+
+        let mut text = Vec::new();
+        let nbt_files = self.export_bo3(&mut text, &stem, option)?;
+        std::fs::write(path, text).map_err(Error::FileCreateError)?;
+        for file in nbt_files {
+            let nbt_path = dir.join(&file.filename);
+            if let Some(parent) = nbt_path.parent() {
+                std::fs::create_dir_all(parent).map_err(Error::FileCreateError)?;
+            }
+            std::fs::write(nbt_path, file.content).map_err(Error::FileCreateError)?;
+        }
+        return Ok(());
+    }
+}
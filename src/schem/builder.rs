@@ -0,0 +1,143 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::Region;
+use crate::schem::{MetaDataIR, Schematic};
+
+/// Build a single-region schematic with chained calls. Positions are relative to the region, and
+/// blocks outside the region are ignored.
+///
+/// ```
+/// use mc_schem::{Block, SchematicBuilder};
+/// let stone = Block::from_id("minecraft:stone").unwrap();
+/// let schem = SchematicBuilder::new()
+///     .with_size([9, 9, 9])
+///     .sphere([4, 4, 4], 4.0, &stone)
+///     .line([0, 0, 0], [8, 8, 8], &Block::from_id("minecraft:glass").unwrap())
+///     .build();
+/// assert_eq!(schem.regions.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchematicBuilder {
+    metadata: MetaDataIR,
+    region: Region,
+}
+
+impl Default for SchematicBuilder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[allow(dead_code)]
+impl SchematicBuilder {
+    /// Create a builder with a 1x1x1 region filled with air
+    pub fn new() -> SchematicBuilder {
+        return SchematicBuilder {
+            metadata: MetaDataIR::default(),
+            region: Region::new(),
+        };
+    }
+
+    /// Resize the region, all blocks are reset to air.
+    pub fn with_size(mut self, size: [i32; 3]) -> Self {
+        self.region.reshape(&size);
+        self.region.palette.clear();
+        self.region.find_or_append_to_palette(&Block::air());
+        self.region.block_entities.clear();
+        self.region.pending_ticks.clear();
+        self.region.entities.clear();
+        return self;
+    }
+
+    /// Set name of the schematic and the region
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.metadata.name = name.to_string();
+        self.region.name = name.to_string();
+        return self;
+    }
+
+    /// Set data version of the schematic
+    pub fn with_data_version(mut self, data_version: i32) -> Self {
+        self.metadata.mc_data_version = data_version;
+        return self;
+    }
+
+    /// Set offset of the region
+    pub fn with_offset(mut self, offset: [i32; 3]) -> Self {
+        self.region.offset = offset;
+        return self;
+    }
+
+    /// Set block at `pos`
+    pub fn set(mut self, pos: [i32; 3], block: &Block) -> Self {
+        let _ = self.region.set_block(pos, block);
+        return self;
+    }
+
+    /// Fill the cuboid between corners `a` and `b` (inclusive)
+    pub fn fill(mut self, a: [i32; 3], b: [i32; 3], block: &Block) -> Self {
+        self.region.draw_cuboid(a, b, block, false);
+        return self;
+    }
+
+    /// Draw a line from `a` to `b`
+    pub fn line(mut self, a: [i32; 3], b: [i32; 3], block: &Block) -> Self {
+        self.region.draw_line(a, b, block);
+        return self;
+    }
+
+    /// Draw the 6 faces of the cuboid between corners `a` and `b`, the inside is not changed.
+    pub fn box_outline(mut self, a: [i32; 3], b: [i32; 3], block: &Block) -> Self {
+        self.region.draw_cuboid(a, b, block, true);
+        return self;
+    }
+
+    /// Draw a solid vertical cylinder from `base_center` upwards
+    pub fn cylinder(
+        mut self,
+        base_center: [i32; 3],
+        radius: f64,
+        height: i32,
+        block: &Block,
+    ) -> Self {
+        self.region
+            .draw_cylinder(base_center, radius, height, block, false);
+        return self;
+    }
+
+    /// Draw a solid sphere
+    pub fn sphere(mut self, center: [i32; 3], radius: f64, block: &Block) -> Self {
+        self.region.draw_sphere(center, radius, block, false);
+        return self;
+    }
+
+    /// Access the region being built, for operations not covered by the builder
+    pub fn region_mut(&mut self) -> &mut Region {
+        return &mut self.region;
+    }
+
+    /// Finish building
+    pub fn build(self) -> Schematic {
+        let mut schem = Schematic::new();
+        schem.metadata = self.metadata;
+        schem.regions.push(self.region);
+        return schem;
+    }
+}
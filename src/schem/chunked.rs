@@ -0,0 +1,398 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{BlockEntity, Entity, HasOffset, HasPalette, PendingTick, Region, WorldSlice};
+use crate::schem::litematica::{block_required_bits, MultiBitSet};
+use crate::schem::memory;
+use crate::schem::memory::RegionMemoryReport;
+use ndarray::Array3;
+use std::cmp::max;
+use std::collections::HashMap;
+
+/// Size of a section in every dimension
+pub const SECTION_SIZE: i32 = 16;
+const SECTION_VOLUME: usize = (SECTION_SIZE * SECTION_SIZE * SECTION_SIZE) as usize;
+
+/// A 16x16x16 cube of blocks in a [`ChunkedRegion`]
+#[derive(Debug, Clone)]
+pub enum Section {
+    /// All blocks are the same, so nothing is stored per block
+    Uniform(u16),
+    /// Blocks are stored as indices into `palette`, which holds block indices of the region.
+    /// `indices` is in y,z,x order.
+    Paletted {
+        palette: Vec<u16>,
+        indices: MultiBitSet,
+    },
+}
+
+fn local_index(local: [i32; 3]) -> usize {
+    return ((local[1] * SECTION_SIZE + local[2]) * SECTION_SIZE + local[0]) as usize;
+}
+
+fn element_bits(palette_size: usize) -> u8 {
+    return max(block_required_bits(palette_size), 1) as u8;
+}
+
+fn set_local_id(indices: &mut MultiBitSet, index: usize, value: u64) -> Result<(), Error> {
+    let bits = indices.element_bits();
+    return indices
+        .set(index, value)
+        .map_err(|_| Error::ValueTooLargeForBits { index, value, bits });
+}
+
+#[allow(dead_code)]
+impl Section {
+    /// Block index in the region palette at `local`, which is a position inside this section
+    pub fn block_index_at(&self, local: [i32; 3]) -> u16 {
+        return match self {
+            Section::Uniform(id) => *id,
+            Section::Paletted { palette, indices } => {
+                palette[indices.get(local_index(local)) as usize]
+            }
+        };
+    }
+
+    /// Returns true if all blocks in this section are the same
+    pub fn is_uniform(&self) -> bool {
+        return matches!(self, Section::Uniform(_));
+    }
+
+    fn set_block_index(&mut self, local: [i32; 3], blk_id: u16) -> Result<(), Error> {
+        if let Section::Uniform(id) = self {
+            if *id == blk_id {
+                return Ok(());
+            }
+            let mut indices = MultiBitSet::new();
+            indices.reset(1, SECTION_VOLUME);
+            *self = Section::Paletted {
+                palette: vec![*id],
+                indices,
+            };
+        }
+        let Section::Paletted { palette, indices } = self else {
+            unreachable!()
+        };
+        let local_id = match palette.iter().position(|id| *id == blk_id) {
+            Some(idx) => idx,
+            None => {
+                palette.push(blk_id);
+                let bits = element_bits(palette.len());
+                if bits > indices.element_bits() {
+                    indices.resize_element_bits(bits)?;
+                }
+                palette.len() - 1
+            }
+        };
+        return set_local_id(indices, local_index(local), local_id as u64);
+    }
+
+    /// Remove unused entries in the section palette, and turn the section into
+    /// [`Section::Uniform`] if only one block is used.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        let Section::Paletted { palette, indices } = self else {
+            return Ok(());
+        };
+        let mut used = vec![false; palette.len()];
+        for id in indices.iter() {
+            used[id as usize] = true;
+        }
+        let used_count = used.iter().filter(|u| **u).count();
+        if let (1, Some(idx)) = (used_count, used.iter().position(|u| *u)) {
+            *self = Section::Uniform(palette[idx]);
+            return Ok(());
+        }
+        if used_count == palette.len() {
+            return Ok(());
+        }
+        let mut lut = vec![0u64; palette.len()];
+        let mut new_palette = Vec::with_capacity(used_count);
+        for (idx, blk_id) in palette.iter().enumerate() {
+            if used[idx] {
+                lut[idx] = new_palette.len() as u64;
+                new_palette.push(*blk_id);
+            }
+        }
+        let mut new_indices = MultiBitSet::new();
+        new_indices.reset(element_bits(new_palette.len()), SECTION_VOLUME);
+        for (idx, id) in indices.iter().enumerate() {
+            set_local_id(&mut new_indices, idx, lut[id as usize])?;
+        }
+        *palette = new_palette;
+        *indices = new_indices;
+        return Ok(());
+    }
+}
+
+/// A region that stores blocks in 16x16x16 sections with per-section palettes, like chunk
+/// sections of minecraft saves. Sections with only one kind of block take almost no memory,
+/// which suits large builds that are mostly air. Convert from and to [`Region`] with
+/// [`ChunkedRegion::from_region`] and [`ChunkedRegion::to_region`].
+#[derive(Debug, Clone)]
+pub struct ChunkedRegion {
+    /// Name of this region
+    pub name: String,
+    shape: [i32; 3],
+    /// Number of sections in x, y, z
+    section_shape: [i32; 3],
+    /// Sections indexed by section position in y,z,x order
+    sections: Vec<Section>,
+    /// All kinds of blocks
+    pub palette: Vec<Block>,
+    /// Other palettes of random variants, same as [`Region::alternate_palettes`]
+    pub alternate_palettes: Vec<Vec<Block>>,
+    /// All block entities. The key is position (xyz)
+    pub block_entities: HashMap<[i32; 3], BlockEntity>,
+    /// All pending ticks. The key is position (xyz)
+    pub pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
+    /// All entities
+    pub entities: Vec<Entity>,
+    /// Offset of this region
+    pub offset: [i32; 3],
+    /// Same as [`Region::negative_size`]
+    pub negative_size: [bool; 3],
+    /// Array of biome indices, stored densely in y,z,x like [`Region::biomes`]. `None` if this
+    /// region doesn't record biomes
+    pub biomes: Option<Array3<u16>>,
+    /// All kinds of biomes, indexed by `biomes`
+    pub biome_palette: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl ChunkedRegion {
+    /// Create a region with shape `shape_xyz`, filled with air
+    pub fn with_shape(shape_xyz: [i32; 3]) -> ChunkedRegion {
+        let section_shape = shape_xyz.map(|s| (max(s, 0) + SECTION_SIZE - 1) / SECTION_SIZE);
+        let count = section_shape.iter().map(|s| *s as usize).product();
+        return ChunkedRegion {
+            name: String::from("NewRegion"),
+            shape: shape_xyz,
+            section_shape,
+            sections: vec![Section::Uniform(0); count],
+            palette: vec![Block::air()],
+            alternate_palettes: Vec::new(),
+            block_entities: HashMap::new(),
+            pending_ticks: HashMap::new(),
+            entities: Vec::new(),
+            offset: [0, 0, 0],
+            negative_size: [false; 3],
+            biomes: None,
+            biome_palette: Vec::new(),
+        };
+    }
+
+    /// Copy blocks and everything else from a [`Region`]. Uniform sections are detected.
+    pub fn from_region(region: &Region) -> Result<ChunkedRegion, Error> {
+        let mut result = Self::with_shape(region.shape());
+        result.name = region.name.clone();
+        result.palette = region.palette.clone();
+        result.block_entities = region.block_entities.clone();
+        result.pending_ticks = region.pending_ticks.clone();
+        result.entities = region.entities.clone();
+        result.offset = region.offset;
+        result.alternate_palettes = region.alternate_palettes.clone();
+        result.negative_size = region.negative_size;
+        result.biomes = region.biomes.clone();
+        result.biome_palette = region.biome_palette.clone();
+        result.sections.fill(Section::Uniform(
+            region.array_yzx.first().copied().unwrap_or(0),
+        ));
+        for ((y, z, x), blk_id) in region.array_yzx.indexed_iter() {
+            let pos = [x as i32, y as i32, z as i32];
+            let (sec, local) = result.section_and_local(pos);
+            result.sections[sec].set_block_index(local, *blk_id)?;
+        }
+        result.compact()?;
+        return Ok(result);
+    }
+
+    /// Unpack into a dense [`Region`]
+    pub fn to_region(&self) -> Region {
+        let mut region = Region::with_shape(self.shape);
+        for ((y, z, x), dest) in region.array_yzx.indexed_iter_mut() {
+            *dest = self.block_index_at([x as i32, y as i32, z as i32]).unwrap();
+        }
+        region.name = self.name.clone();
+        region.palette = self.palette.clone();
+        region.block_entities = self.block_entities.clone();
+        region.pending_ticks = self.pending_ticks.clone();
+        region.entities = self.entities.clone();
+        region.offset = self.offset;
+        region.alternate_palettes = self.alternate_palettes.clone();
+        region.negative_size = self.negative_size;
+        region.biomes = self.biomes.clone();
+        region.biome_palette = self.biome_palette.clone();
+        return region;
+    }
+
+    fn section_and_local(&self, r_pos: [i32; 3]) -> (usize, [i32; 3]) {
+        let [sx, sy, sz] = r_pos.map(|v| v / SECTION_SIZE);
+        let [nx, _, nz] = self.section_shape;
+        let sec = ((sy * nz + sz) * nx + sx) as usize;
+        return (sec, r_pos.map(|v| v % SECTION_SIZE));
+    }
+
+    /// Number of sections in x, y, z
+    pub fn section_shape(&self) -> [i32; 3] {
+        return self.section_shape;
+    }
+
+    /// Section at section position `section_pos`, which is the relative position divided by 16
+    pub fn section_at(&self, section_pos: [i32; 3]) -> Option<&Section> {
+        let inside = section_pos
+            .iter()
+            .zip(self.section_shape)
+            .all(|(pos, len)| *pos >= 0 && *pos < len);
+        if !inside {
+            return None;
+        }
+        let (sec, _) = self.section_and_local(section_pos.map(|v| v * SECTION_SIZE));
+        return Some(&self.sections[sec]);
+    }
+
+    /// All sections in y,z,x order
+    pub fn sections(&self) -> &[Section] {
+        return &self.sections;
+    }
+
+    /// Set block at `r_pos`. Fails if `r_pos` is outside the region, or palette exceeds 65535.
+    pub fn set_block(&mut self, r_pos: [i32; 3], block: &Block) -> Result<(), Error> {
+        let blk_id = match self.find_in_palette(block) {
+            Some(id) => id,
+            None => {
+                if self.palette.len() >= 65536 {
+                    return Err(Error::PaletteTooLong(self.palette.len() + 1));
+                }
+                self.palette.push(block.clone());
+                (self.palette.len() - 1) as u16
+            }
+        };
+        return self.set_block_id(r_pos, blk_id);
+    }
+
+    /// Set block index at `r_pos`. Fails if `r_pos` is outside the region, `block_id` >= length
+    /// of palette, or the section can't store it.
+    pub fn set_block_id(&mut self, r_pos: [i32; 3], block_id: u16) -> Result<(), Error> {
+        if !self.contains_coord(r_pos) {
+            return Err(Error::BlockPosOutOfRange {
+                tag_path: String::new(),
+                pos: r_pos,
+                lower_bound: [0, 0, 0],
+                upper_bound: self.shape.map(|s| s - 1),
+            });
+        }
+        if block_id as usize >= self.palette.len() {
+            return Err(Error::BlockIndexOutOfRange {
+                tag_path: String::new(),
+                index: block_id as i32,
+                range: [0, self.palette.len() as i32],
+            });
+        }
+        let (sec, local) = self.section_and_local(r_pos);
+        return self.sections[sec].set_block_index(local, block_id);
+    }
+
+    /// Estimate heap memory used by this region
+    pub fn memory_usage(&self) -> RegionMemoryReport {
+        let mut block_array = self.sections.capacity() * std::mem::size_of::<Section>();
+        for sec in &self.sections {
+            if let Section::Paletted { palette, indices } = sec {
+                block_array += palette.capacity() * std::mem::size_of::<u16>();
+                block_array += std::mem::size_of_val(indices.as_u64_slice());
+            }
+        }
+        return RegionMemoryReport {
+            name: self.name.clone(),
+            block_array,
+            palette: memory::palette_bytes(&self.palette),
+            block_entities: memory::block_entities_bytes(&self.block_entities),
+            pending_ticks: memory::pending_ticks_bytes(&self.pending_ticks),
+            entities: memory::entities_bytes(&self.entities),
+            biomes: memory::biomes_bytes(&self.biome_palette, &self.biomes),
+        };
+    }
+
+    /// Compact all sections, see [`Section::compact`]
+    pub fn compact(&mut self) -> Result<(), Error> {
+        for sec in &mut self.sections {
+            sec.compact()?;
+        }
+        return Ok(());
+    }
+}
+
+impl HasPalette for ChunkedRegion {
+    fn palette(&self) -> &[Block] {
+        return &self.palette;
+    }
+}
+
+impl HasOffset for ChunkedRegion {
+    fn offset(&self) -> [i32; 3] {
+        return self.offset;
+    }
+}
+
+impl WorldSlice for ChunkedRegion {
+    fn shape(&self) -> [i32; 3] {
+        return self.shape;
+    }
+
+    fn total_blocks(&self, include_air: bool) -> u64 {
+        let air = self.block_index_of_air();
+        let structure_void = self.block_index_of_structure_void();
+        let mut counter = 0;
+        for x in 0..self.shape[0] {
+            for y in 0..self.shape[1] {
+                for z in 0..self.shape[2] {
+                    let blk_id = self.block_index_at([x, y, z]);
+                    if blk_id == structure_void || (!include_air && blk_id == air) {
+                        continue;
+                    }
+                    counter += 1;
+                }
+            }
+        }
+        return counter;
+    }
+
+    fn block_index_at(&self, r_pos: [i32; 3]) -> Option<u16> {
+        if !self.contains_coord(r_pos) {
+            return None;
+        }
+        let (sec, local) = self.section_and_local(r_pos);
+        return Some(self.sections[sec].block_index_at(local));
+    }
+
+    fn block_at(&self, r_pos: [i32; 3]) -> Option<&Block> {
+        return Some(&self.palette[self.block_index_at(r_pos)? as usize]);
+    }
+
+    fn block_entity_at(&self, r_pos: [i32; 3]) -> Option<&BlockEntity> {
+        return self.block_entities.get(&r_pos);
+    }
+
+    fn pending_tick_at(&self, r_pos: [i32; 3]) -> &[PendingTick] {
+        if let Some(pts) = self.pending_ticks.get(&r_pos) {
+            return pts;
+        }
+        return &[];
+    }
+}
@@ -0,0 +1,441 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{BlockBox, Region, WorldSlice};
+use crate::schem::entity_uuid::{regenerate_entity_uuids, unique_seed};
+use crate::schem::Schematic;
+
+/// Blocks selected in a schematic, like selections of WorldEdit. Coordinates are global when
+/// passed to [`Schematic::copy`], and relative to the origin when stored in a [`Clipboard`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    /// All blocks in the box
+    Cuboid(BlockBox),
+    /// Blocks whose distance to `center` is no more than `radius + 0.5`, the same as `//sphere`
+    Sphere { center: [i32; 3], radius: f64 },
+    /// A polygon on the xz plane extruded from `min_y` to `max_y`(inclusive). `points` are x and z
+    /// of the vertices, at least 3 points are required. Blocks on edges are selected.
+    Polygon {
+        points: Vec<[i32; 2]>,
+        min_y: i32,
+        max_y: i32,
+    },
+}
+
+fn on_segment(a: [i32; 2], b: [i32; 2], p: [i32; 2]) -> bool {
+    let [ax, az] = a.map(|v| v as i64);
+    let [bx, bz] = b.map(|v| v as i64);
+    let [px, pz] = p.map(|v| v as i64);
+    if (bx - ax) * (pz - az) != (bz - az) * (px - ax) {
+        return false;
+    }
+    return px >= ax.min(bx) && px <= ax.max(bx) && pz >= az.min(bz) && pz <= az.max(bz);
+}
+
+fn polygon_contains(points: &[[i32; 2]], p: [i32; 2]) -> bool {
+    if points.len() < 3 {
+        return false;
+    }
+    let mut inside = false;
+    for (idx, a) in points.iter().enumerate() {
+        let b = points[(idx + 1) % points.len()];
+        if on_segment(*a, b, p) {
+            return true;
+        }
+        // even-odd rule, casting a ray towards +x
+        if (a[1] > p[1]) != (b[1] > p[1]) {
+            let cross_x =
+                a[0] as f64 + (p[1] - a[1]) as f64 * (b[0] - a[0]) as f64 / (b[1] - a[1]) as f64;
+            if (p[0] as f64) < cross_x {
+                inside = !inside;
+            }
+        }
+    }
+    return inside;
+}
+
+impl Selection {
+    /// The smallest box containing the selection, `None` if the selection is empty or too large
+    /// to be represented in i32.
+    pub fn bounding_box(&self) -> Option<BlockBox> {
+        return match self {
+            Selection::Cuboid(b) => Some(*b),
+            Selection::Sphere { center, radius } => {
+                let extent = (radius + 0.5).floor();
+                if !extent.is_finite() || extent < 0.0 || extent > i32::MAX as f64 {
+                    return None;
+                }
+                let extent = extent as i32;
+                let mut lower = [0; 3];
+                let mut upper = [0; 3];
+                for dim in 0..3 {
+                    lower[dim] = center[dim].checked_sub(extent)?;
+                    upper[dim] = center[dim].checked_add(extent)?;
+                }
+                Some(BlockBox { lower, upper })
+            }
+            Selection::Polygon {
+                points,
+                min_y,
+                max_y,
+            } => {
+                if points.len() < 3 || min_y > max_y {
+                    return None;
+                }
+                let min_x = points.iter().map(|p| p[0]).min()?;
+                let max_x = points.iter().map(|p| p[0]).max()?;
+                let min_z = points.iter().map(|p| p[1]).min()?;
+                let max_z = points.iter().map(|p| p[1]).max()?;
+                Some(BlockBox {
+                    lower: [min_x, *min_y, min_z],
+                    upper: [max_x, *max_y, max_z],
+                })
+            }
+        };
+    }
+
+    /// If `pos` is selected
+    pub fn contains(&self, pos: [i32; 3]) -> bool {
+        return match self {
+            Selection::Cuboid(b) => b.contains(pos),
+            Selection::Sphere { center, radius } => {
+                let dist_sq: f64 = (0..3)
+                    .map(|dim| {
+                        let d = pos[dim] as f64 - center[dim] as f64;
+                        d * d
+                    })
+                    .sum();
+                dist_sq <= (radius + 0.5) * (radius + 0.5)
+            }
+            Selection::Polygon {
+                points,
+                min_y,
+                max_y,
+            } => pos[1] >= *min_y && pos[1] <= *max_y && polygon_contains(points, [pos[0], pos[2]]),
+        };
+    }
+
+    /// Iterate selected positions in x, y, z order(z changes fastest)
+    pub fn positions(&self) -> impl Iterator<Item = [i32; 3]> + '_ {
+        let bbox = self.bounding_box();
+        return bbox
+            .into_iter()
+            .flat_map(|b| {
+                (b.lower[0]..=b.upper[0]).flat_map(move |x| {
+                    (b.lower[1]..=b.upper[1])
+                        .flat_map(move |y| (b.lower[2]..=b.upper[2]).map(move |z| [x, y, z]))
+                })
+            })
+            .filter(|pos| self.contains(*pos));
+    }
+
+    /// Count of selected blocks
+    pub fn volume(&self) -> u64 {
+        return self.positions().count() as u64;
+    }
+
+    /// The same selection moved by `delta`
+    pub fn shifted(&self, delta: [i32; 3]) -> Selection {
+        return match self {
+            Selection::Cuboid(b) => Selection::Cuboid(BlockBox {
+                lower: std::array::from_fn(|dim| b.lower[dim] + delta[dim]),
+                upper: std::array::from_fn(|dim| b.upper[dim] + delta[dim]),
+            }),
+            Selection::Sphere { center, radius } => Selection::Sphere {
+                center: std::array::from_fn(|dim| center[dim] + delta[dim]),
+                radius: *radius,
+            },
+            Selection::Polygon {
+                points,
+                min_y,
+                max_y,
+            } => Selection::Polygon {
+                points: points
+                    .iter()
+                    .map(|p| [p[0] + delta[0], p[1] + delta[2]])
+                    .collect(),
+                min_y: min_y + delta[1],
+                max_y: max_y + delta[1],
+            },
+        };
+    }
+}
+
+/// Blocks copied from a schematic. Like the clipboard of WorldEdit, positions are relative to the
+/// origin, which is usually where the player stands when copying.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    /// Bounding box of the selection, with offset relative to the origin. Blocks outside the
+    /// selection or outside all regions of the source are structure void.
+    pub region: Region,
+    /// The selection relative to the origin
+    pub selection: Selection,
+    /// Global position of the origin when copied
+    pub origin: [i32; 3],
+}
+
+/// Options of [`Schematic::paste`]
+#[derive(Debug, Clone)]
+pub struct PasteOption {
+    /// Don't replace blocks with air in clipboard, like `//paste -a`
+    pub ignore_air: bool,
+    /// Paste entities into the region containing them
+    pub paste_entities: bool,
+    /// Give pasted entities new UUIDs, so that pasting the same clipboard twice doesn't
+    /// duplicate them. Leashes, passengers and other references among pasted entities are
+    /// updated.
+    pub regenerate_uuids: bool,
+}
+
+impl Default for PasteOption {
+    fn default() -> Self {
+        return PasteOption {
+            ignore_air: false,
+            paste_entities: true,
+            regenerate_uuids: false,
+        };
+    }
+}
+
+impl Clipboard {
+    /// Position in `region` of a position relative to the origin
+    fn region_pos_of(&self, pos: [i32; 3]) -> [i32; 3] {
+        return std::array::from_fn(|dim| pos[dim] - self.region.offset[dim]);
+    }
+
+    /// A copy of the clipboard region placed with origin at `target`, which can be added to a
+    /// schematic as a new region.
+    pub fn region_at(&self, target: [i32; 3]) -> Region {
+        let mut region = self.region.clone();
+        region.offset = std::array::from_fn(|dim| self.region.offset[dim] + target[dim]);
+        return region;
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Copy blocks, block entities, pending ticks and entities in `selection` to a clipboard.
+    /// Positions are stored relative to `origin`. Where regions overlap, the latter one wins.
+    /// Returns `InvalidValue` if the selection is empty.
+    pub fn copy(&self, selection: &Selection, origin: [i32; 3]) -> Result<Clipboard, Error> {
+        let Some(bbox) = selection.bounding_box() else {
+            return Err(Error::InvalidValue {
+                tag_path: "selection".to_string(),
+                error: format!("selection {:?} is empty", selection),
+            });
+        };
+        let shape: [i32; 3] = std::array::from_fn(|dim| bbox.upper[dim] - bbox.lower[dim] + 1);
+        let mut region = Region::with_shape(shape);
+        region.fill_with(&Block::structure_void());
+
+        for reg in &self.regions {
+            let Some(part) = reg.cropped(&bbox) else {
+                continue;
+            };
+            let dst_pos: [i32; 3] = std::array::from_fn(|dim| part.offset[dim] - bbox.lower[dim]);
+            let src_box = BlockBox::from_pos_shape([0; 3], part.shape());
+            region.blit_from(&part, &src_box, dst_pos)?;
+            for entity in &part.entities {
+                let g_pos: [i32; 3] =
+                    std::array::from_fn(|dim| entity.block_pos[dim] + part.offset[dim]);
+                if selection.contains(g_pos) {
+                    let mut entity = entity.clone();
+                    entity.pos_shift(dst_pos);
+                    region.entities.push(entity);
+                }
+            }
+        }
+
+        // blocks out of the selection are not copied
+        let void = region.find_or_append_to_palette(&Block::structure_void());
+        for x in 0..shape[0] {
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    let g_pos = [x + bbox.lower[0], y + bbox.lower[1], z + bbox.lower[2]];
+                    if !selection.contains(g_pos) {
+                        region.array_yzx[[y as usize, z as usize, x as usize]] = void;
+                        region.block_entities.remove(&[x, y, z]);
+                        region.pending_ticks.remove(&[x, y, z]);
+                    }
+                }
+            }
+        }
+        // indices are either copied or void, so they never exceed the palette
+        let _ = region.shrink_palette();
+        region.offset = std::array::from_fn(|dim| bbox.lower[dim] - origin[dim]);
+
+        return Ok(Clipboard {
+            region,
+            selection: selection.shifted(origin.map(|v| -v)),
+            origin,
+        });
+    }
+
+    /// Copy `selection` to a clipboard like [`Schematic::copy`], then replace selected blocks
+    /// with air and remove block entities, pending ticks and entities there.
+    pub fn cut(&mut self, selection: &Selection, origin: [i32; 3]) -> Result<Clipboard, Error> {
+        let clipboard = self.copy(selection, origin)?;
+        let air = Block::air();
+        for reg in &mut self.regions {
+            let Some(bbox) = selection.bounding_box() else {
+                break;
+            };
+            if !reg.global_box().intersects(&bbox) {
+                continue;
+            }
+            let air_id = reg.find_or_append_to_palette(&air);
+            let shape = reg.shape();
+            for x in 0..shape[0] {
+                for y in 0..shape[1] {
+                    for z in 0..shape[2] {
+                        if !selection.contains(reg.relative_pos_to_global_pos([x, y, z])) {
+                            continue;
+                        }
+                        reg.array_yzx[[y as usize, z as usize, x as usize]] = air_id;
+                        reg.block_entities.remove(&[x, y, z]);
+                        reg.pending_ticks.remove(&[x, y, z]);
+                    }
+                }
+            }
+            let offset = reg.offset;
+            reg.entities.retain(|e| {
+                let g_pos: [i32; 3] = std::array::from_fn(|dim| e.block_pos[dim] + offset[dim]);
+                return !selection.contains(g_pos);
+            });
+        }
+        return Ok(clipboard);
+    }
+
+    /// Paste the clipboard with its origin at `target`(global coordinates). Blocks are only
+    /// pasted into existing regions, parts outside all regions are clipped. Structure void in the
+    /// clipboard is never pasted. Block entities and pending ticks of pasted blocks replace
+    /// existing ones. \
+    /// Returns the count of pasted blocks, or `PaletteTooLong` if a palette exceeds 65536.
+    pub fn paste(
+        &mut self,
+        clipboard: &Clipboard,
+        target: [i32; 3],
+        option: &PasteOption,
+    ) -> Result<u64, Error> {
+        let clip_box = clipboard.region_at(target).global_box();
+        let clip_index = clipboard.region.spatial_index(16);
+        let mut counter = 0u64;
+        for reg in &mut self.regions {
+            let reg_box = reg.global_box();
+            if !reg_box.intersects(&clip_box) {
+                continue;
+            }
+            // clipboard block index -> region block index
+            let mut lut: Vec<Option<u16>> = vec![None; clipboard.region.palette.len()];
+            for gx in
+                reg_box.lower[0].max(clip_box.lower[0])..=reg_box.upper[0].min(clip_box.upper[0])
+            {
+                for gy in reg_box.lower[1].max(clip_box.lower[1])
+                    ..=reg_box.upper[1].min(clip_box.upper[1])
+                {
+                    for gz in reg_box.lower[2].max(clip_box.lower[2])
+                        ..=reg_box.upper[2].min(clip_box.upper[2])
+                    {
+                        let pos = [gx - target[0], gy - target[1], gz - target[2]];
+                        if !clipboard.selection.contains(pos) {
+                            continue;
+                        }
+                        let c_pos = clipboard.region_pos_of(pos);
+                        let Some(c_id) = clipboard.region.block_index_at(c_pos) else {
+                            continue;
+                        };
+                        let blk = &clipboard.region.palette[c_id as usize];
+                        if blk.is_structure_void() || (option.ignore_air && blk.is_air()) {
+                            continue;
+                        }
+                        let id = match lut[c_id as usize] {
+                            Some(id) => id,
+                            None => {
+                                let id = reg.find_or_append_to_palette(blk);
+                                lut[c_id as usize] = Some(id);
+                                id
+                            }
+                        };
+                        let r_pos = reg.global_pos_to_relative_pos([gx, gy, gz]);
+                        let _ = reg.set_block_id(r_pos, id);
+                        reg.block_entities.remove(&r_pos);
+                        reg.pending_ticks.remove(&r_pos);
+                        if let Some(ticks) = clipboard.region.pending_ticks.get(&c_pos) {
+                            reg.pending_ticks.insert(r_pos, ticks.clone());
+                        }
+                        counter += 1;
+                    }
+                }
+            }
+            if reg.palette.len() > 65536 {
+                return Err(Error::PaletteTooLong(reg.palette.len()));
+            }
+
+            // block entities are sparse, so only those in the pasted part are looked up
+            let Some(g_box) = reg_box.intersection(&clip_box) else {
+                continue;
+            };
+            let c_box = BlockBox {
+                lower: clipboard
+                    .region_pos_of(std::array::from_fn(|dim| g_box.lower[dim] - target[dim])),
+                upper: clipboard
+                    .region_pos_of(std::array::from_fn(|dim| g_box.upper[dim] - target[dim])),
+            };
+            for (c_pos, be) in clip_index.block_entities_in(&c_box) {
+                let pos: [i32; 3] =
+                    std::array::from_fn(|dim| c_pos[dim] + clipboard.region.offset[dim]);
+                if !clipboard.selection.contains(pos) {
+                    continue;
+                }
+                let Some(blk) = clipboard.region.block_at(c_pos) else {
+                    continue;
+                };
+                if blk.is_structure_void() || (option.ignore_air && blk.is_air()) {
+                    continue;
+                }
+                let r_pos = reg
+                    .global_pos_to_relative_pos(std::array::from_fn(|dim| pos[dim] + target[dim]));
+                reg.block_entities.insert(r_pos, be.clone());
+            }
+        }
+
+        if option.paste_entities {
+            let mut entities = clipboard.region.entities.clone();
+            if option.regenerate_uuids {
+                regenerate_entity_uuids(&mut entities, unique_seed());
+            }
+            let shift: [i32; 3] =
+                std::array::from_fn(|dim| clipboard.region.offset[dim] + target[dim]);
+            for mut entity in entities {
+                let g_pos: [i32; 3] = std::array::from_fn(|dim| entity.block_pos[dim] + shift[dim]);
+                let Some(reg) = self
+                    .regions
+                    .iter_mut()
+                    .find(|r| r.global_box().contains(g_pos))
+                else {
+                    continue;
+                };
+                entity.pos_shift(std::array::from_fn(|dim| shift[dim] - reg.offset[dim]));
+                reg.entities.push(entity);
+            }
+        }
+        return Ok(counter);
+    }
+}
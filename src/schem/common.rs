@@ -19,14 +19,447 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 use crate::block::Block;
 use crate::error::Error;
 use crate::region::{BlockEntity, Entity};
+use crate::schem::CompressMethod;
 use crate::{schem::id_of_nbt_tag, unwrap_opt_tag, unwrap_tag};
 use fastnbt::Value;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::{Compression, GzBuilder};
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::ops::{Add, Range};
 use std::time;
 use std::time::SystemTime;
 
+/// First bytes of a zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Detect compression of nbt from its first bytes: gzip, zlib, zstd or uncompressed.
+pub fn detect_compress_method(head: &[u8]) -> CompressMethod {
+    if head.starts_with(&[0x1f, 0x8b]) {
+        return CompressMethod::Gzip;
+    }
+    if head.first() == Some(&0x78) {
+        return CompressMethod::Zlib;
+    }
+    if head.starts_with(&ZSTD_MAGIC) {
+        return CompressMethod::Zstd;
+    }
+    return CompressMethod::Uncompressed;
+}
+
+/// Wrap `src` with a decoder according to its first bytes, see [`detect_compress_method`].
+pub fn decompress_reader<'a, R: Read + 'a>(src: R) -> Result<Box<dyn Read + 'a>, Error> {
+    let mut src = BufReader::new(src);
+    let head = src.fill_buf().map_err(Error::IOReadError)?;
+    return match detect_compress_method(head) {
+        CompressMethod::Gzip => Ok(Box::new(GzDecoder::new(src))),
+        CompressMethod::Zlib => Ok(Box::new(ZlibDecoder::new(src))),
+        #[cfg(feature = "zstd")]
+        CompressMethod::Zstd => {
+            let decoder =
+                zstd::stream::read::Decoder::with_buffer(src).map_err(Error::IOReadError)?;
+            Ok(Box::new(decoder))
+        }
+        #[cfg(not(feature = "zstd"))]
+        CompressMethod::Zstd => Err(Error::CompressMethodDisabled {
+            method: CompressMethod::Zstd,
+        }),
+        CompressMethod::Uncompressed => Ok(Box::new(src)),
+    };
+}
+
+/// Read uncompressed nbt from `src`, see [`parse_nbt_compound`].
+pub fn read_nbt_compound(src: &mut dyn Read) -> Result<HashMap<String, Value>, Error> {
+    let mut bytes = Vec::new();
+    src.read_to_end(&mut bytes).map_err(Error::IOReadError)?;
+    return parse_nbt_compound(&bytes);
+}
+
+/// Parse uncompressed nbt. If it's broken, the error tells where parsing stopped and the path of
+/// the tag there, as `Error::NBTReadErrorAt`.
+pub fn parse_nbt_compound(bytes: &[u8]) -> Result<HashMap<String, Value>, Error> {
+    if let Err((position, reason)) = check_nbt_bounds(bytes) {
+        return Err(Error::NBTReadErrorAt {
+            error: serde::de::Error::custom(reason),
+            position,
+            tag_path: tag_path_before(bytes, position),
+        });
+    }
+    let mut remaining = bytes;
+    return fastnbt::from_reader(&mut remaining).map_err(|error| {
+        let position = bytes.len() - remaining.len();
+        return Error::NBTReadErrorAt {
+            error,
+            position,
+            tag_path: tag_path_before(bytes, position),
+        };
+    });
+}
+
+/// Max nesting depth of compounds and lists, same as minecraft
+const MAX_NBT_DEPTH: usize = 512;
+
+/// Walks raw nbt to find the innermost tag starting before `position`
+struct TagLocator<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    position: usize,
+    found: Option<String>,
+    depth: usize,
+    /// Only check the structure without building paths of tags
+    check_only: bool,
+    /// Set when a tag needs more bytes than remaining
+    truncated: bool,
+}
+
+impl TagLocator<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            self.truncated = true;
+            return None;
+        };
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        return Some(taken);
+    }
+
+    fn take_len(&mut self, bytes: usize) -> Option<usize> {
+        let taken = self.take(bytes)?;
+        let mut len: i64 = 0;
+        for b in taken {
+            len = (len << 8) | *b as i64;
+        }
+        if bytes == 4 {
+            len = len as u32 as i32 as i64;
+        }
+        return usize::try_from(len).ok();
+    }
+
+    /// Record `path` of a tag starting at `start`. Returns `None` to stop walking if the tag
+    /// starts at or after `position`.
+    fn enter<F: FnOnce() -> String>(&mut self, start: usize, path: F) -> Option<String> {
+        if start >= self.position {
+            return None;
+        }
+        if self.check_only {
+            return Some(String::new());
+        }
+        let path = path();
+        self.found = Some(path.clone());
+        return Some(path);
+    }
+
+    /// Skip payload of a tag. Returns `None` if walking stops.
+    fn skip_payload(&mut self, tag_type: u8, path: &str) -> Option<()> {
+        let array_elem_size = match tag_type {
+            7 => 1,
+            11 => 4,
+            12 => 8,
+            _ => 0,
+        };
+        match tag_type {
+            1..=6 => {
+                self.take([1, 2, 4, 8, 4, 8][tag_type as usize - 1])?;
+            }
+            7 | 11 | 12 => {
+                let len = self.take_len(4)?;
+                self.take(len.checked_mul(array_elem_size)?)?;
+            }
+            8 => {
+                let len = self.take_len(2)?;
+                self.take(len)?;
+            }
+            9 => {
+                let elem_type = self.take(1)?[0];
+                let len = self.take_len(4)?;
+                if elem_type == 0 && len > 0 {
+                    return None;
+                }
+                self.depth += 1;
+                if self.depth > MAX_NBT_DEPTH {
+                    return None;
+                }
+                for idx in 0..len {
+                    let child = self.enter(self.pos, || format!("{path}[{idx}]"))?;
+                    self.skip_payload(elem_type, &child)?;
+                }
+                self.depth -= 1;
+            }
+            10 => {
+                self.depth += 1;
+                if self.depth > MAX_NBT_DEPTH {
+                    return None;
+                }
+                loop {
+                    let start = self.pos;
+                    let child_type = self.take(1)?[0];
+                    if child_type == 0 {
+                        break;
+                    }
+                    let name_len = self.take_len(2)?;
+                    let check_only = self.check_only;
+                    let name = self.take(name_len)?;
+                    let name = if check_only {
+                        String::new()
+                    } else {
+                        String::from_utf8_lossy(name).to_string()
+                    };
+                    let child = self.enter(start, || format!("{path}/{name}"))?;
+                    self.skip_payload(child_type, &child)?;
+                }
+                self.depth -= 1;
+            }
+            _ => return None,
+        }
+        return Some(());
+    }
+}
+
+/// Path of the innermost tag of uncompressed nbt `bytes` that starts before `position`. `None` if
+/// no tag in root compound starts before it.
+fn tag_path_before(bytes: &[u8], position: usize) -> Option<String> {
+    let mut locator = TagLocator {
+        bytes,
+        pos: 0,
+        position,
+        found: None,
+        depth: 0,
+        check_only: false,
+        truncated: false,
+    };
+    // root is a compound with a name
+    if locator.take(1)? != [10] {
+        return None;
+    }
+    let name_len = locator.take_len(2)?;
+    locator.take(name_len)?;
+    locator.skip_payload(10, "");
+    return locator.found;
+}
+
+/// Check that every array, list and string of uncompressed nbt `bytes` fits in the remaining
+/// bytes, and tags are not nested deeper than minecraft allows, so a forged length or depth can't
+/// make the parser allocate huge memory or overflow the stack. Returns the position where
+/// checking fails and the reason. Like a truncated file, a tag longer than the remaining bytes
+/// fails at the end of data.
+fn check_nbt_bounds(bytes: &[u8]) -> Result<(), (usize, &'static str)> {
+    let mut locator = TagLocator {
+        bytes,
+        pos: 0,
+        position: usize::MAX,
+        found: None,
+        depth: 0,
+        check_only: true,
+        truncated: false,
+    };
+    let mut check = || -> Option<()> {
+        if locator.take(1)? != [10] {
+            return None;
+        }
+        let name_len = locator.take_len(2)?;
+        locator.take(name_len)?;
+        return locator.skip_payload(10, "");
+    };
+    if check().is_some() {
+        return Ok(());
+    }
+    if locator.depth > MAX_NBT_DEPTH {
+        return Err((locator.pos, "tags are nested too deep"));
+    }
+    if locator.truncated {
+        return Err((bytes.len(), "data ends before the tag is complete"));
+    }
+    return Err((
+        locator.pos,
+        "invalid tag type, or a list of end tags is not empty",
+    ));
+}
+
+/// Compress `nbt` with `method` and write it to `dest`. `filename` is recorded in gzip header.
+/// For zstd, `level` is used as zstd level directly, and 0 means the default level of zstd.
+pub fn write_compressed_nbt(
+    dest: &mut dyn Write,
+    nbt: &HashMap<String, Value>,
+    method: CompressMethod,
+    level: Compression,
+    filename: Option<&str>,
+) -> Result<(), Error> {
+    let mut encoder = CompressedWriter::new(dest, method, level, filename)?;
+    fastnbt::to_writer(&mut encoder, nbt).map_err(Error::NBTWriteError)?;
+    return encoder.finish();
+}
+
+/// Encoder of any `CompressMethod`, so that nbt can be streamed into it piece by piece. Call
+/// `finish` after writing, otherwise errors of the last block are lost.
+pub enum CompressedWriter<'a> {
+    Gzip(GzEncoder<&'a mut dyn Write>),
+    Zlib(ZlibEncoder<&'a mut dyn Write>),
+    Uncompressed(&'a mut dyn Write),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, &'a mut dyn Write>),
+}
+
+impl<'a> CompressedWriter<'a> {
+    /// Arguments are the same as `write_compressed_nbt`
+    pub fn new(
+        dest: &'a mut dyn Write,
+        method: CompressMethod,
+        level: Compression,
+        filename: Option<&str>,
+    ) -> Result<CompressedWriter<'a>, Error> {
+        return match method {
+            CompressMethod::Gzip => {
+                let mut builder = GzBuilder::new().comment("Generated by mc_schem");
+                if let Some(filename) = filename {
+                    builder = builder.filename(filename);
+                }
+                Ok(CompressedWriter::Gzip(builder.write(dest, level)))
+            }
+            CompressMethod::Zlib => Ok(CompressedWriter::Zlib(ZlibEncoder::new(dest, level))),
+            CompressMethod::Uncompressed => Ok(CompressedWriter::Uncompressed(dest)),
+            #[cfg(feature = "zstd")]
+            CompressMethod::Zstd => {
+                match zstd::stream::write::Encoder::new(dest, level.level() as i32) {
+                    Ok(encoder) => Ok(CompressedWriter::Zstd(encoder)),
+                    Err(e) => Err(Error::NBTWriteError(e.into())),
+                }
+            }
+            #[cfg(not(feature = "zstd"))]
+            CompressMethod::Zstd => Err(Error::CompressMethodDisabled { method }),
+        };
+    }
+
+    /// Write the remaining compressed data
+    pub fn finish(self) -> Result<(), Error> {
+        let result = match self {
+            CompressedWriter::Gzip(encoder) => encoder.finish().map(|_| ()),
+            CompressedWriter::Zlib(encoder) => encoder.finish().map(|_| ()),
+            CompressedWriter::Uncompressed(dest) => dest.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.finish().map(|_| ()),
+        };
+        return result.map_err(|e| Error::NBTWriteError(e.into()));
+    }
+}
+
+impl Write for CompressedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return match self {
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zlib(encoder) => encoder.write(buf),
+            CompressedWriter::Uncompressed(dest) => dest.write(buf),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        };
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return match self {
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zlib(encoder) => encoder.flush(),
+            CompressedWriter::Uncompressed(dest) => dest.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        };
+    }
+}
+
+/// Forwards the output of `fastnbt::to_writer` for a single-entry compound, without the root
+/// compound header(tag id and empty name) and the trailing end tag. What remains is the named
+/// entry, which can be written inside a compound that is being streamed.
+struct NbtEntryWriter<'w> {
+    dest: &'w mut dyn Write,
+    header_left: usize,
+    pending: Option<u8>,
+}
+
+impl Write for NbtEntryWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let skip = self.header_left.min(buf.len());
+        self.header_left -= skip;
+        let rest = &buf[skip..];
+        if let Some((last, body)) = rest.split_last() {
+            // the last byte is held back, since it may be the end tag of root
+            if let Some(pending) = self.pending {
+                self.dest.write_all(&[pending])?;
+            }
+            self.dest.write_all(body)?;
+            self.pending = Some(*last);
+        }
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.dest.flush();
+    }
+}
+
+/// Write a named tag as an entry of a compound. Used to stream a compound without holding all of
+/// its entries in memory: write `[10, name length(u16 BE), name]`, then every entry with this
+/// function, then an end tag(`0`).
+pub fn write_nbt_entry(dest: &mut dyn Write, name: &str, value: &Value) -> Result<(), Error> {
+    let mut entry_writer = NbtEntryWriter {
+        dest,
+        header_left: 3,
+        pending: None,
+    };
+    // serialize a compound of one entry
+    let wrapper = HashMap::from([(name, value)]);
+    fastnbt::to_writer(&mut entry_writer, &wrapper).map_err(Error::NBTWriteError)?;
+    debug_assert_eq!(entry_writer.header_left, 0);
+    debug_assert_eq!(entry_writer.pending, Some(0));
+    return Ok(());
+}
+
+/// Write raw bytes of a streamed nbt
+pub fn write_raw_bytes(dest: &mut dyn Write, bytes: &[u8]) -> Result<(), Error> {
+    return dest
+        .write_all(bytes)
+        .map_err(|e| Error::NBTWriteError(e.into()));
+}
+
+/// Write the tag id and name of a named tag, so that its payload can be streamed afterwards. `name`
+/// must be ascii, since nbt names are modified utf-8.
+pub fn write_tag_header(dest: &mut dyn Write, tag_id: u8, name: &str) -> Result<(), Error> {
+    debug_assert!(name.is_ascii());
+    write_raw_bytes(dest, &[tag_id])?;
+    write_raw_bytes(dest, &(name.len() as u16).to_be_bytes())?;
+    return write_raw_bytes(dest, name.as_bytes());
+}
+
+/// Write every entry of `compound` and an end tag, which is the payload of a compound tag
+pub fn write_compound_payload(
+    dest: &mut dyn Write,
+    compound: &HashMap<String, Value>,
+) -> Result<(), Error> {
+    for (key, value) in compound {
+        write_nbt_entry(dest, key, value)?;
+    }
+    return write_raw_bytes(dest, &[0]);
+}
+
+/// Convert a count to an int tag, returns `MetadataOverflow` instead of wrapping around if it
+/// exceeds `i32::MAX`.
+pub fn checked_int_tag<T>(value: T, tag_path: &str) -> Result<Value, Error>
+where
+    T: TryInto<i32> + Into<u64> + Copy,
+{
+    return match value.try_into() {
+        Ok(v) => Ok(Value::Int(v)),
+        Err(_) => Err(Error::MetadataOverflow {
+            tag_path: tag_path.to_string(),
+            value: value.into(),
+            max: i32::MAX as u64,
+        }),
+    };
+}
+
 pub fn size_to_compound<T>(size: &[T; 3]) -> HashMap<String, Value>
 where
     T: Copy,
@@ -173,6 +606,59 @@ pub fn i64_ms_timestamp_to_system_time(timestamp: i64) -> SystemTime {
     return time;
 }
 
+/// Current unix time stamp in millisecond. `SystemTime::now` panics on wasm32, so the time
+/// comes from JavaScript with the `js` feature, otherwise it's 0.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn current_ms_timestamp() -> i64 {
+    return SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+}
+
+/// Current unix time stamp in millisecond. `SystemTime::now` panics on wasm32, so the time
+/// comes from JavaScript with the `js` feature, otherwise it's 0.
+#[cfg(target_arch = "wasm32")]
+pub fn current_ms_timestamp() -> i64 {
+    #[cfg(feature = "js")]
+    return js_sys::Date::now() as i64;
+    #[cfg(not(feature = "js"))]
+    return 0;
+}
+
+/// Validate `palette` with the embedded block registry of `data_version` if `validate` is set,
+/// errors are reported with `tag_path`.
+#[cfg(feature = "block_registry")]
+pub fn validate_palette_if(
+    validate: bool,
+    data_version: i32,
+    palette: &[Block],
+    tag_path: &str,
+) -> Result<(), Error> {
+    if !validate {
+        return Ok(());
+    }
+    return crate::block_registry::BlockRegistry::embedded(data_version)
+        .validate_palette(palette, tag_path);
+}
+
+/// Validating blocks requires the embedded registry, so it fails without feature
+/// `block_registry` if `validate` is set.
+#[cfg(not(feature = "block_registry"))]
+pub fn validate_palette_if(
+    validate: bool,
+    _data_version: i32,
+    _palette: &[Block],
+    _tag_path: &str,
+) -> Result<(), Error> {
+    if !validate {
+        return Ok(());
+    }
+    return Err(Error::FeatureDisabled {
+        feature: "block_registry",
+    });
+}
+
 pub fn parse_entity_litematica(
     nbt: HashMap<String, Value>,
     tag_path: &str,
@@ -195,13 +681,13 @@ pub fn parse_entity_litematica(
         for dim in 0..3 {
             let cur_tag_path = format!("{}/Pos[{}]", tag_path, dim);
             pos_d[dim] = unwrap_tag!(pos[dim], Double, 0.0, cur_tag_path);
-            entity.block_pos[dim] = pos_d[dim] as i32;
         }
 
-        entity.position = pos_d;
+        entity.set_position(pos_d);
     }
 
     entity.tags = nbt;
+    entity.extract_passengers();
     return Ok(entity);
 }
 
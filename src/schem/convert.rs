@@ -0,0 +1,413 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::{DefaultErrorHandler, Error};
+use crate::schem::axiom::AXIOM_BLUEPRINT_MAGIC;
+use crate::schem::common;
+use crate::schem::{
+    AxiomSaveOption, LitematicaLoadOption, LitematicaSaveOption, RawMetaData, Schematic,
+    VanillaStructureLoadOption, VanillaStructureSaveOption, VoidPolicy, WorldEdit12LoadOption,
+    WorldEdit12SaveOption, WorldEdit13LoadOption, WorldEdit13SaveOption,
+};
+use crate::{snbt, SchemFormat};
+use fastnbt::Value;
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+#[cfg(feature = "fs")]
+use std::time::Instant;
+
+/// Summary of a conversion made by [`convert_file`]
+#[derive(Debug, Clone)]
+pub struct ConversionReport {
+    /// Format of input file, detected by content
+    pub input_format: SchemFormat,
+    /// Format of output file, deduced from extension
+    pub output_format: SchemFormat,
+    /// Minecraft data version of the schematic
+    pub data_version: i32,
+    /// Count of regions in input file
+    pub input_regions: usize,
+    /// Count of regions written to output file
+    pub output_regions: usize,
+    /// Enclosing shape in x, y, z
+    pub shape: [i32; 3],
+    /// Count of blocks, air and structure void are not counted
+    pub total_blocks: u64,
+    pub block_entities: usize,
+    pub entities: usize,
+    /// Possible information loss during conversion
+    pub warnings: Vec<String>,
+    /// Time spent loading the input file
+    pub load_time: Duration,
+    /// Time spent saving the output file
+    pub save_time: Duration,
+}
+
+/// Options of [`convert_file_with_option`]
+#[derive(Debug, Clone)]
+pub struct ConvertOption {
+    /// How structure void and air are converted. Use `VoidToAir` to strip structure voids of a
+    /// vanilla structure before saving as litematica, or `AirToVoid` to make air of a litematica
+    /// not overwrite existing blocks when saved as vanilla structure.
+    pub void_policy: VoidPolicy,
+}
+
+impl Default for ConvertOption {
+    fn default() -> Self {
+        return ConvertOption {
+            void_policy: VoidPolicy::Keep,
+        };
+    }
+}
+
+impl SchemFormat {
+    /// Deduce format from extension of `filename`
+    pub fn from_filename(filename: &str) -> Option<SchemFormat> {
+        for format in Self::supported_formats() {
+            if filename.ends_with(format.extension()) {
+                return Some(format.clone());
+            }
+        }
+        return None;
+    }
+
+    /// Deduce format from tags in root compound
+    pub fn from_nbt(root: &HashMap<String, Value>) -> Option<SchemFormat> {
+        if root.contains_key("Regions") {
+            return Some(SchemFormat::Litematica);
+        }
+        if root.contains_key("Schematic")
+            || (root.contains_key("Palette") && root.contains_key("BlockData"))
+        {
+            return Some(SchemFormat::WorldEdit13);
+        }
+        if root.contains_key("Blocks") && root.contains_key("Data") {
+            return Some(SchemFormat::WorldEdit12);
+        }
+        if root.contains_key("blocks") && root.contains_key("size") {
+            return Some(SchemFormat::VanillaStructure);
+        }
+        if root.contains_key("BlockRegion") {
+            return Some(SchemFormat::Axiom);
+        }
+        return None;
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Load schematic from file, the format is detected from content, and extension is used only if
+    /// the content is ambiguous. Default load options are used.
+    #[cfg(feature = "fs")]
+    pub fn from_file_detect_format(
+        filename: &str,
+    ) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        let file = File::open(filename).map_err(Error::FileOpenError)?;
+        let mut file = BufReader::new(file);
+        let head = file.fill_buf().map_err(Error::IOReadError)?;
+        if head.starts_with(&AXIOM_BLUEPRINT_MAGIC.to_be_bytes()) {
+            let (schem, raw) = Self::from_axiom_reader(&mut file)?;
+            return Ok((schem, RawMetaData::Axiom(raw), SchemFormat::Axiom));
+        }
+        let nbt = common::read_nbt_compound(&mut common::decompress_reader(file)?)?;
+        return Self::from_nbt_detect_format(nbt, Some(filename));
+    }
+
+    /// Load schematic from bytes, compression is detected. The format is detected from content, and
+    /// `filename` is used only if the content is ambiguous. Default load options are used.
+    pub fn from_bytes_detect_format(
+        bytes: &[u8],
+        filename: Option<&str>,
+    ) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        if bytes.starts_with(&AXIOM_BLUEPRINT_MAGIC.to_be_bytes()) {
+            let (schem, raw) = Self::from_axiom_bytes(bytes)?;
+            return Ok((schem, RawMetaData::Axiom(raw), SchemFormat::Axiom));
+        }
+        let nbt = common::read_nbt_compound(&mut common::decompress_reader(bytes)?)?;
+        return Self::from_nbt_detect_format(nbt, filename);
+    }
+
+    fn from_nbt_detect_format(
+        nbt: HashMap<String, Value>,
+        filename: Option<&str>,
+    ) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        let filename = filename.unwrap_or("");
+        let format = match SchemFormat::from_nbt(&nbt) {
+            Some(f) => f,
+            None => match SchemFormat::from_filename(filename) {
+                Some(f) => f,
+                None => {
+                    return Err(Error::UnrecognisedExtension {
+                        extension: filename.rsplit('.').next().unwrap_or("").to_string(),
+                    })
+                }
+            },
+        };
+
+        let (schem, raw) = match format {
+            SchemFormat::Litematica => {
+                let (schem, raw) =
+                    Self::from_litematica_nbt(nbt, &LitematicaLoadOption::default())?;
+                (schem, RawMetaData::Litematica(raw))
+            }
+            SchemFormat::VanillaStructure => {
+                let (schem, raw) =
+                    Self::from_vanilla_structure_nbt(nbt, &VanillaStructureLoadOption::default())?;
+                (schem, RawMetaData::VanillaStructure(raw))
+            }
+            SchemFormat::WorldEdit13 => {
+                let (schem, raw) =
+                    Self::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption::default())?;
+                (schem, RawMetaData::WE13(raw))
+            }
+            SchemFormat::WorldEdit12 => {
+                let (schem, raw, ..) =
+                    Self::from_world_edit_12_nbt(nbt, &WorldEdit12LoadOption::default())?;
+                (schem, RawMetaData::WE12(raw))
+            }
+            SchemFormat::Axiom => {
+                let (schem, raw) = Self::from_nbt_axiom(nbt)?;
+                (schem, RawMetaData::Axiom(raw))
+            }
+        };
+        return Ok((schem, raw, format));
+    }
+
+    /// Load schematic from SNBT of any format, the format is detected from content. Default load
+    /// options are used.
+    pub fn from_snbt(text: &str) -> Result<(Schematic, RawMetaData, SchemFormat), Error> {
+        let nbt = snbt::parse_compound(text)?;
+        return Self::from_nbt_detect_format(nbt, None);
+    }
+
+    /// Convert schematic to nbt of `format`, with default save options.
+    pub fn to_nbt(&self, format: &SchemFormat) -> Result<HashMap<String, Value>, Error> {
+        return match format {
+            SchemFormat::Litematica => self.to_nbt_litematica(&LitematicaSaveOption::default()),
+            SchemFormat::VanillaStructure => {
+                self.to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+            }
+            SchemFormat::WorldEdit13 => {
+                self.to_nbt_world_edit_13(&WorldEdit13SaveOption::default())
+            }
+            SchemFormat::WorldEdit12 => {
+                self.to_nbt_world_edit_12::<DefaultErrorHandler>(&WorldEdit12SaveOption::default())
+            }
+            SchemFormat::Axiom => self.to_nbt_axiom(&AxiomSaveOption::default()),
+        };
+    }
+
+    /// Convert schematic to indented SNBT of `format`, with default save options. Keys are sorted,
+    /// so the text is stable and suits diffs.
+    pub fn to_snbt(&self, format: &SchemFormat) -> Result<String, Error> {
+        let nbt = self.to_nbt(format)?;
+        return Ok(snbt::compound_to_snbt(&nbt, true));
+    }
+
+    /// Save schematic to gzip-compressed bytes in `format`, with default save options.
+    pub fn to_bytes(&self, format: &SchemFormat) -> Result<Vec<u8>, Error> {
+        return match format {
+            SchemFormat::Litematica => self.to_litematica_bytes(&LitematicaSaveOption::default()),
+            SchemFormat::VanillaStructure => {
+                self.to_vanilla_structure_bytes(&VanillaStructureSaveOption::default())
+            }
+            SchemFormat::WorldEdit13 => {
+                self.to_world_edit_13_bytes(&WorldEdit13SaveOption::default())
+            }
+            SchemFormat::WorldEdit12 => self
+                .to_world_edit_12_bytes::<DefaultErrorHandler>(&WorldEdit12SaveOption::default()),
+            SchemFormat::Axiom => self.to_axiom_bytes(&AxiomSaveOption::default()),
+        };
+    }
+}
+
+/// Convert `in_path` to `out_path`. The input format is detected from content, and the output
+/// format is deduced from extension of `out_path`. Default load and save options are used.
+#[cfg(feature = "fs")]
+pub fn convert_file(in_path: &str, out_path: &str) -> Result<ConversionReport, Error> {
+    return convert_file_with_option(in_path, out_path, &ConvertOption::default());
+}
+
+/// Same as [`convert_file`], but converts structure void and air as `option` says.
+#[cfg(feature = "fs")]
+pub fn convert_file_with_option(
+    in_path: &str,
+    out_path: &str,
+    option: &ConvertOption,
+) -> Result<ConversionReport, Error> {
+    let output_format = match SchemFormat::from_filename(out_path) {
+        Some(f) => f,
+        None => {
+            return Err(Error::UnrecognisedExtension {
+                extension: out_path.rsplit('.').next().unwrap_or("").to_string(),
+            })
+        }
+    };
+    if !output_format.savable() {
+        return Err(Error::UnsupportedSaveFormat {
+            format: output_format,
+        });
+    }
+
+    let load_begin = Instant::now();
+    let (mut schem, raw, input_format) = Schematic::from_file_detect_format(in_path)?;
+    let load_time = load_begin.elapsed();
+
+    let mut warnings = Vec::new();
+    // formats without a name fall back to the file name
+    let has_name = match &raw {
+        RawMetaData::Litematica(_) => true,
+        RawMetaData::WE13(md) => md.name.is_some(),
+        RawMetaData::VanillaStructure(md) => md.name.is_some(),
+        RawMetaData::WE12(_) => false,
+        RawMetaData::Axiom(_) => true,
+    };
+    if !has_name {
+        let file_name = in_path.rsplit(['/', '\\']).next().unwrap_or(in_path);
+        let stem = match file_name.rsplit_once('.') {
+            Some((stem, _)) if !stem.is_empty() => stem,
+            _ => file_name,
+        };
+        schem.set_name(stem);
+    }
+    let has_description = match &raw {
+        RawMetaData::Litematica(md) => !md.description.is_empty(),
+        RawMetaData::WE13(md) => md.description.as_ref().is_some_and(|d| !d.is_empty()),
+        RawMetaData::VanillaStructure(md) => md.description.as_ref().is_some_and(|d| !d.is_empty()),
+        RawMetaData::WE12(_) | RawMetaData::Axiom(_) => false,
+    };
+    if has_description && output_format != SchemFormat::Litematica {
+        warnings.push(format!(
+            "Description is not saved, because {} has no standard tag for it",
+            output_format.extension()
+        ));
+    }
+    if input_format == SchemFormat::WorldEdit12 {
+        warnings.push(format!(
+            "{} doesn't store data version, assumed to be {}",
+            SchemFormat::WorldEdit12.extension(),
+            WorldEdit12LoadOption::default().data_version
+        ));
+    }
+
+    let replaced = schem.apply_void_policy(option.void_policy);
+    if replaced > 0 {
+        let (from, to) = match option.void_policy {
+            VoidPolicy::VoidToAir => ("structure void", "air"),
+            _ => ("air", "structure void"),
+        };
+        warnings.push(format!(
+            "{replaced} blocks of {from} are replaced with {to}"
+        ));
+    }
+
+    let mut output_regions = schem.regions.len();
+    let save_begin = Instant::now();
+    match output_format {
+        SchemFormat::Litematica => {
+            schem.save_litematica_file(out_path, &LitematicaSaveOption::default())?;
+        }
+        SchemFormat::VanillaStructure => {
+            schem.save_vanilla_structure_file(out_path, &VanillaStructureSaveOption::default())?;
+        }
+        SchemFormat::WorldEdit13 => {
+            schem.save_world_edit_13_file(out_path, &WorldEdit13SaveOption::default())?;
+        }
+        SchemFormat::WorldEdit12 => {
+            schem.save_world_edit_12_file::<DefaultErrorHandler>(
+                out_path,
+                &WorldEdit12SaveOption::default(),
+            )?;
+        }
+        SchemFormat::Axiom => {
+            schem.save_axiom_file(out_path, &AxiomSaveOption::default())?;
+        }
+    }
+    let save_time = save_begin.elapsed();
+    if output_format == SchemFormat::WorldEdit12 {
+        let (palette, _) = schem.full_palette();
+        let unmappable: Vec<String> = palette
+            .iter()
+            .filter(|(blk, _)| !blk.is_structure_void() && blk.to_old().is_err())
+            .map(|(blk, _)| blk.full_id())
+            .collect();
+        if !unmappable.is_empty() {
+            warnings.push(format!(
+                "{} blocks have no number id in 1.12 and are saved as air: {}",
+                unmappable.len(),
+                unmappable.join(", ")
+            ));
+        }
+    }
+    if output_format != SchemFormat::Litematica && schem.regions.len() > 1 {
+        output_regions = 1;
+        warnings.push(format!(
+            "{} regions are merged into one, because {} supports only one region",
+            schem.regions.len(),
+            output_format.extension()
+        ));
+    }
+
+    let mut block_entities = 0;
+    let mut entities = 0;
+    for reg in &schem.regions {
+        block_entities += reg.block_entities.len();
+        entities += reg.entities.len();
+    }
+    let pending_ticks: usize = schem
+        .regions
+        .iter()
+        .map(|reg| reg.pending_ticks.values().map(|t| t.len()).sum::<usize>())
+        .sum();
+    let keeps_ticks = match output_format {
+        SchemFormat::Litematica => true,
+        SchemFormat::WorldEdit13 => schem.metadata_world_edit_13()?.version >= 3,
+        _ => false,
+    };
+    if pending_ticks > 0 && !keeps_ticks {
+        warnings.push(format!(
+            "{pending_ticks} pending ticks are not saved, because {} has no tag for them",
+            output_format.extension()
+        ));
+    }
+    if output_format == SchemFormat::Axiom && entities > 0 {
+        warnings.push(format!(
+            "{entities} entities are not saved, because {} stores only blocks",
+            SchemFormat::Axiom.extension()
+        ));
+    }
+
+    return Ok(ConversionReport {
+        input_format,
+        output_format,
+        data_version: schem.metadata.mc_data_version,
+        input_regions: schem.regions.len(),
+        output_regions,
+        shape: schem.shape(),
+        total_blocks: schem.total_blocks(false),
+        block_entities,
+        entities,
+        warnings,
+        load_time,
+        save_time,
+    });
+}
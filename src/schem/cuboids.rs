@@ -0,0 +1,115 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::region::{BlockBox, Region};
+use ndarray::Array3;
+
+/// A cuboid of identical blocks in a region
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cuboid {
+    /// The cuboid in relative coordinates of the region
+    pub r_box: BlockBox,
+    /// Index of the block in palette of the region
+    pub block_index: u16,
+}
+
+/// Grow a cuboid from `start` along x, then z, then y, as long as every block in it is `idx` and
+/// not consumed yet. Returns the upper corner(inclusive).
+fn grow_cuboid(
+    region: &Region,
+    consumed: &Array3<bool>,
+    start: [usize; 3],
+    idx: u16,
+    max_volume: u64,
+) -> [usize; 3] {
+    let [y0, z0, x0] = start;
+    let [sy, sz, sx] = region.shape_yzx().map(|s| s as usize);
+    let usable = |y: usize, z: usize, x: usize| {
+        return region.array_yzx[[y, z, x]] == idx && !consumed[[y, z, x]];
+    };
+    let volume = |x1: usize, z1: usize, y1: usize| {
+        return ((x1 - x0 + 1) * (z1 - z0 + 1) * (y1 - y0 + 1)) as u64;
+    };
+
+    let mut x1 = x0;
+    while x1 + 1 < sx && usable(y0, z0, x1 + 1) && volume(x1 + 1, z0, y0) <= max_volume {
+        x1 += 1;
+    }
+    let mut z1 = z0;
+    while z1 + 1 < sz
+        && (x0..=x1).all(|x| usable(y0, z1 + 1, x))
+        && volume(x1, z1 + 1, y0) <= max_volume
+    {
+        z1 += 1;
+    }
+    let mut y1 = y0;
+    while y1 + 1 < sy
+        && (z0..=z1).all(|z| (x0..=x1).all(|x| usable(y1 + 1, z, x)))
+        && volume(x1, z1, y1 + 1) <= max_volume
+    {
+        y1 += 1;
+    }
+    return [y1, z1, x1];
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Decompose the whole region into cuboids of identical blocks by greedy box merging. Every
+    /// block is covered by exactly one cuboid, and cuboids are ordered by their lower corner in
+    /// y-z-x order. The result is not guaranteed to be minimal, but is usually close for
+    /// schematics made of large flat areas.
+    pub fn to_cuboids(&self) -> Vec<Cuboid> {
+        return self.to_cuboids_filtered(u64::MAX, |_, _| true);
+    }
+
+    /// Like `to_cuboids`, but only blocks for which `include(r_pos, block_index)` returns true are
+    /// covered, and no cuboid contains more than `max_volume` blocks. Excluded blocks split
+    /// cuboids, so they can be used to keep certain positions(like block entities) as single
+    /// blocks. A `max_volume` of 1 or less gives one cuboid per included block.
+    pub fn to_cuboids_filtered<F>(&self, max_volume: u64, include: F) -> Vec<Cuboid>
+    where
+        F: Fn([i32; 3], u16) -> bool,
+    {
+        let max_volume = max_volume.max(1);
+        let mut consumed = Array3::from_elem(self.array_yzx.dim(), false);
+        for ((y, z, x), idx) in self.array_yzx.indexed_iter() {
+            if !include([x as i32, y as i32, z as i32], *idx) {
+                consumed[[y, z, x]] = true;
+            }
+        }
+
+        let mut result = Vec::new();
+        for ((y, z, x), idx) in self.array_yzx.indexed_iter() {
+            if consumed[[y, z, x]] {
+                continue;
+            }
+            let [y1, z1, x1] = grow_cuboid(self, &consumed, [y, z, x], *idx, max_volume);
+            consumed
+                .slice_mut(ndarray::s![y..=y1, z..=z1, x..=x1])
+                .fill(true);
+            result.push(Cuboid {
+                r_box: BlockBox {
+                    lower: [x as i32, y as i32, z as i32],
+                    upper: [x1 as i32, y1 as i32, z1 as i32],
+                },
+                block_index: *idx,
+            });
+        }
+        return result;
+    }
+}
@@ -0,0 +1,265 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{BlockBox, BlockEntity, Entity, SpatialIndex};
+use crate::schem::Schematic;
+use std::cmp::max;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Difference of block at one position. `None` means the position is not covered by any region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDiff {
+    /// Global position
+    pub pos: [i32; 3],
+    pub before: Option<Block>,
+    pub after: Option<Block>,
+}
+
+/// Difference of block entity at one position
+#[derive(Debug, Clone)]
+pub struct BlockEntityDiff {
+    /// Global position
+    pub pos: [i32; 3],
+    pub before: Option<BlockEntity>,
+    pub after: Option<BlockEntity>,
+}
+
+/// Differences between two schematics, made by [`Schematic::diff`]. All positions are global.
+#[derive(Debug, Clone, Default)]
+pub struct SchemDiff {
+    /// Shape of the two schematics
+    pub shape_before: [i32; 3],
+    pub shape_after: [i32; 3],
+    /// Positions where the first block differs, sorted in y, z, x order
+    pub blocks: Vec<BlockDiff>,
+    /// Blocks that only exist in palettes of the former schematic
+    pub palette_removed: Vec<Block>,
+    /// Blocks that only exist in palettes of the latter schematic
+    pub palette_added: Vec<Block>,
+    /// Block entities that are added, removed or changed, sorted by position
+    pub block_entities: Vec<BlockEntityDiff>,
+    /// Entities that only exist in the former schematic, positions are global
+    pub entities_removed: Vec<Entity>,
+    /// Entities that only exist in the latter schematic, positions are global
+    pub entities_added: Vec<Entity>,
+}
+
+impl SchemDiff {
+    /// Returns true if the two schematics have same shape, blocks, palettes, block entities and entities
+    pub fn is_empty(&self) -> bool {
+        return self.shape_before == self.shape_after
+            && self.blocks.is_empty()
+            && self.palette_removed.is_empty()
+            && self.palette_added.is_empty()
+            && self.block_entities.is_empty()
+            && self.entities_removed.is_empty()
+            && self.entities_added.is_empty();
+    }
+}
+
+impl Display for SchemDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.shape_before != self.shape_after {
+            writeln!(
+                f,
+                "shape: [{}, {}, {}] -> [{}, {}, {}]",
+                self.shape_before[0],
+                self.shape_before[1],
+                self.shape_before[2],
+                self.shape_after[0],
+                self.shape_after[1],
+                self.shape_after[2]
+            )?;
+        }
+        writeln!(f, "{} blocks changed", self.blocks.len())?;
+        writeln!(
+            f,
+            "palette: {} removed, {} added",
+            self.palette_removed.len(),
+            self.palette_added.len()
+        )?;
+        writeln!(f, "{} block entities changed", self.block_entities.len())?;
+        return write!(
+            f,
+            "entities: {} removed, {} added",
+            self.entities_removed.len(),
+            self.entities_added.len()
+        );
+    }
+}
+
+fn entity_eq(a: &Entity, b: &Entity) -> bool {
+    return a.position == b.position
+        && a.tags == b.tags
+        && a.passengers.len() == b.passengers.len()
+        && a.passengers
+            .iter()
+            .zip(&b.passengers)
+            .all(|(pa, pb)| entity_eq(pa, pb));
+}
+
+fn global_entities(schem: &Schematic) -> Vec<Entity> {
+    let mut result = Vec::new();
+    for reg in &schem.regions {
+        for entity in &reg.entities {
+            let mut e = entity.clone();
+            e.pos_shift(reg.offset);
+            result.push(e);
+        }
+    }
+    return result;
+}
+
+fn palette_set(schem: &Schematic) -> BTreeMap<String, &Block> {
+    let mut result = BTreeMap::new();
+    for reg in &schem.regions {
+        for blk in &reg.palette {
+            result.insert(blk.full_id(), blk);
+        }
+    }
+    return result;
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Compare with `other`, `self` is treated as the former one. Blocks are compared at every
+    /// position of the union of two shapes, and only the first block at each position is used if
+    /// regions overlap.
+    pub fn diff(&self, other: &Schematic) -> SchemDiff {
+        let mut result = SchemDiff {
+            shape_before: self.shape(),
+            shape_after: other.shape(),
+            ..SchemDiff::default()
+        };
+
+        // blocks
+        let shape = [
+            max(result.shape_before[0], result.shape_after[0]),
+            max(result.shape_before[1], result.shape_after[1]),
+            max(result.shape_before[2], result.shape_after[2]),
+        ];
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let pos = [x, y, z];
+                    let before = self.first_block_at(pos);
+                    let after = other.first_block_at(pos);
+                    if before == after {
+                        continue;
+                    }
+                    result.blocks.push(BlockDiff {
+                        pos,
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    });
+                }
+            }
+        }
+
+        // palette
+        {
+            let before = palette_set(self);
+            let after = palette_set(other);
+            for (id, blk) in &before {
+                if !after.contains_key(id) {
+                    result.palette_removed.push((*blk).clone());
+                }
+            }
+            for (id, blk) in &after {
+                if !before.contains_key(id) {
+                    result.palette_added.push((*blk).clone());
+                }
+            }
+        }
+
+        // block entities
+        {
+            let mut positions: BTreeSet<[i32; 3]> = BTreeSet::new();
+            for schem in [self, other] {
+                for reg in &schem.regions {
+                    for pos in reg.block_entities.keys() {
+                        positions.insert(reg.relative_pos_to_global_pos(*pos));
+                    }
+                }
+            }
+            for pos in positions {
+                let before = self.first_block_entity_at(pos);
+                let after = other.first_block_entity_at(pos);
+                let same = match (before, after) {
+                    (Some(b), Some(a)) => b.tags == a.tags,
+                    (None, None) => true,
+                    _ => false,
+                };
+                if same {
+                    continue;
+                }
+                result.block_entities.push(BlockEntityDiff {
+                    pos,
+                    before: before.cloned(),
+                    after: after.cloned(),
+                });
+            }
+        }
+
+        // entities, candidates are looked up by block position so that large entity lists are not
+        // compared pairwise
+        {
+            let indices: Vec<SpatialIndex<'_>> =
+                other.regions.iter().map(|r| r.spatial_index(16)).collect();
+            let mut matched: HashSet<(usize, usize)> = HashSet::new();
+            for entity in global_entities(self) {
+                let g_pos = entity.block_pos;
+                let found = indices.iter().enumerate().find_map(|(r_idx, index)| {
+                    let reg = index.region();
+                    let r_pos = reg.global_pos_to_relative_pos(g_pos);
+                    return index
+                        .entity_indices_in(&BlockBox::new(r_pos, r_pos))
+                        .into_iter()
+                        .find(|e_idx| {
+                            if matched.contains(&(r_idx, *e_idx)) {
+                                return false;
+                            }
+                            let mut e = reg.entities[*e_idx].clone();
+                            e.pos_shift(reg.offset);
+                            return entity_eq(&e, &entity);
+                        })
+                        .map(|e_idx| (r_idx, e_idx));
+                });
+                match found {
+                    Some(key) => {
+                        matched.insert(key);
+                    }
+                    None => result.entities_removed.push(entity),
+                }
+            }
+            for (r_idx, reg) in other.regions.iter().enumerate() {
+                for (e_idx, entity) in reg.entities.iter().enumerate() {
+                    if !matched.contains(&(r_idx, e_idx)) {
+                        let mut e = entity.clone();
+                        e.pos_shift(reg.offset);
+                        result.entities_added.push(e);
+                    }
+                }
+            }
+        }
+
+        return result;
+    }
+}
@@ -0,0 +1,234 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use fastnbt::{IntArray, Value};
+
+use crate::region::{Entity, Region};
+use crate::schem::pattern::SplitMix64;
+use crate::schem::Schematic;
+
+/// UUID of an entity, from the 1.16+ `UUID` int array, or `UUIDMost` and `UUIDLeast` of older
+/// versions. `None` if the entity has no UUID.
+pub fn entity_uuid(tags: &HashMap<String, Value>) -> Option<u128> {
+    if let Some(Value::IntArray(arr)) = tags.get("UUID") {
+        return ints_to_uuid(arr);
+    }
+    if let (Some(Value::Long(most)), Some(Value::Long(least))) =
+        (tags.get("UUIDMost"), tags.get("UUIDLeast"))
+    {
+        return Some(((*most as u64 as u128) << 64) | (*least as u64 as u128));
+    }
+    return None;
+}
+
+fn ints_to_uuid(arr: &IntArray) -> Option<u128> {
+    if arr.len() != 4 {
+        return None;
+    }
+    return Some(
+        arr.iter()
+            .fold(0u128, |uuid, v| (uuid << 32) | (*v as u32 as u128)),
+    );
+}
+
+fn uuid_to_ints(uuid: u128) -> IntArray {
+    let ints = (0..4).map(|idx| (uuid >> (96 - 32 * idx)) as u32 as i32);
+    return IntArray::new(ints.collect());
+}
+
+fn parse_uuid_string(text: &str) -> Option<u128> {
+    let groups: Vec<&str> = text.split('-').collect();
+    let lengths = groups.iter().map(|g| g.len()).collect::<Vec<_>>();
+    if lengths != [8, 4, 4, 4, 12] {
+        return None;
+    }
+    return u128::from_str_radix(&groups.concat(), 16).ok();
+}
+
+fn uuid_to_string(uuid: u128) -> String {
+    let hex = format!("{uuid:032x}");
+    return format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    );
+}
+
+/// Random version 4 UUID
+fn random_uuid(rng: &mut SplitMix64) -> u128 {
+    let uuid = ((rng.next() as u128) << 64) | rng.next() as u128;
+    let uuid = (uuid & !(0xF << 76)) | (0x4 << 76);
+    return (uuid & !(0x3 << 62)) | (0x2 << 62);
+}
+
+/// Assign a new UUID to the entity and all passengers, recording old -> new in `map`
+fn collect_entity_uuids(entity: &Entity, rng: &mut SplitMix64, map: &mut HashMap<u128, u128>) {
+    collect_uuids(&entity.tags, rng, map);
+    for passenger in &entity.passengers {
+        collect_entity_uuids(passenger, rng, map);
+    }
+}
+
+fn replace_entity_uuids(entity: &mut Entity, map: &HashMap<u128, u128>) {
+    replace_uuids_in_compound(&mut entity.tags, map);
+    for passenger in &mut entity.passengers {
+        replace_entity_uuids(passenger, map);
+    }
+}
+
+/// Assign a new UUID to the entity and passengers in its `Passengers` tag, recording old -> new
+/// in `map`
+fn collect_uuids(
+    tags: &HashMap<String, Value>,
+    rng: &mut SplitMix64,
+    map: &mut HashMap<u128, u128>,
+) {
+    if let Some(uuid) = entity_uuid(tags) {
+        map.entry(uuid).or_insert_with(|| random_uuid(rng));
+    }
+    if let Some(Value::List(passengers)) = tags.get("Passengers") {
+        for passenger in passengers {
+            if let Value::Compound(passenger) = passenger {
+                collect_uuids(passenger, rng, map);
+            }
+        }
+    }
+}
+
+/// Replace every UUID in `map` found in `value`, as int array, hyphenated string, or a pair of
+/// `*Most` and `*Least` longs. Returns the number of replaced UUIDs.
+fn replace_uuids(value: &mut Value, map: &HashMap<u128, u128>) -> usize {
+    match value {
+        Value::IntArray(arr) => {
+            if let Some(new) = ints_to_uuid(arr).and_then(|uuid| map.get(&uuid)) {
+                *arr = uuid_to_ints(*new);
+                return 1;
+            }
+        }
+        Value::String(text) => {
+            if let Some(new) = parse_uuid_string(text).and_then(|uuid| map.get(&uuid)) {
+                *text = uuid_to_string(*new);
+                return 1;
+            }
+        }
+        Value::List(list) => {
+            return list.iter_mut().map(|v| replace_uuids(v, map)).sum();
+        }
+        Value::Compound(comp) => return replace_uuids_in_compound(comp, map),
+        _ => {}
+    }
+    return 0;
+}
+
+fn replace_uuids_in_compound(
+    comp: &mut HashMap<String, Value>,
+    map: &HashMap<u128, u128>,
+) -> usize {
+    let mut counter = 0;
+    let prefixes: Vec<String> = comp
+        .keys()
+        .filter_map(|key| key.strip_suffix("Most"))
+        .filter(|prefix| comp.contains_key(&format!("{prefix}Least")))
+        .map(|prefix| prefix.to_string())
+        .collect();
+    for prefix in prefixes {
+        let most_key = format!("{prefix}Most");
+        let least_key = format!("{prefix}Least");
+        let (Some(Value::Long(most)), Some(Value::Long(least))) =
+            (comp.get(&most_key), comp.get(&least_key))
+        else {
+            continue;
+        };
+        let uuid = ((*most as u64 as u128) << 64) | (*least as u64 as u128);
+        if let Some(new) = map.get(&uuid) {
+            comp.insert(most_key, Value::Long((*new >> 64) as u64 as i64));
+            comp.insert(least_key, Value::Long(*new as u64 as i64));
+            counter += 1;
+        }
+    }
+    for value in comp.values_mut() {
+        counter += replace_uuids(value, map);
+    }
+    return counter;
+}
+
+/// Give entities and their passengers new UUIDs generated from `seed`, and update references
+/// among them, like leashes, owners and angry targets. References to entities out of
+/// `entities` are kept. Returns the number of regenerated UUIDs.
+pub fn regenerate_entity_uuids<'a, I>(entities: I, seed: u64) -> usize
+where
+    I: IntoIterator<Item = &'a mut Entity>,
+{
+    let entities: Vec<&mut Entity> = entities.into_iter().collect();
+    let mut rng = SplitMix64(seed);
+    let mut map = HashMap::new();
+    for entity in &entities {
+        collect_entity_uuids(entity, &mut rng, &mut map);
+    }
+    if map.is_empty() {
+        return 0;
+    }
+    for entity in entities {
+        replace_entity_uuids(entity, &map);
+    }
+    return map.len();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn current_ns_timestamp() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+}
+
+/// `SystemTime::now` panics on wasm32
+#[cfg(target_arch = "wasm32")]
+fn current_ns_timestamp() -> u64 {
+    return crate::schem::common::current_ms_timestamp() as u64 * 1_000_000;
+}
+
+/// Seed that differs in each call, for regenerating UUIDs without a given seed
+pub(crate) fn unique_seed() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = current_ns_timestamp();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    return SplitMix64(nanos ^ count.rotate_left(32)).next();
+}
+
+impl Region {
+    /// Give all entities new UUIDs, see [`regenerate_entity_uuids`]
+    pub fn regenerate_entity_uuids(&mut self, seed: u64) -> usize {
+        return regenerate_entity_uuids(&mut self.entities, seed);
+    }
+}
+
+impl Schematic {
+    /// Give entities of all regions new UUIDs, references across regions are updated too. See
+    /// [`regenerate_entity_uuids`].
+    pub fn regenerate_entity_uuids(&mut self, seed: u64) -> usize {
+        let entities = self.regions.iter_mut().flat_map(|r| r.entities.iter_mut());
+        return regenerate_entity_uuids(entities, seed);
+    }
+}
@@ -0,0 +1,175 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::biome::Biome;
+use crate::error::Error;
+use crate::region::{BlockBox, Region, WorldSlice};
+use ndarray::{concatenate, s, Array3, Axis};
+use std::ops::Range;
+
+#[allow(dead_code)]
+impl Region {
+    /// Copy the horizontal slab `y_range`(relative coordinates) into a new region with the same
+    /// name. The part outside the region is ignored, and the offset of the result is moved to the
+    /// lowest layer of the slab, so it stays in place when pasted. Returns `None` if the slab has
+    /// no layers inside this region.
+    pub fn extract_slab(&self, y_range: Range<i32>) -> Option<Region> {
+        let shape = self.shape();
+        let y0 = y_range.start.max(0);
+        let y1 = y_range.end.min(shape[1]);
+        if y0 >= y1 {
+            return None;
+        }
+        let g_box = BlockBox::from_pos_shape(
+            [self.offset[0], self.offset[1] + y0, self.offset[2]],
+            [shape[0], y1 - y0, shape[2]],
+        );
+        return self.cropped(&g_box);
+    }
+
+    /// Copy a single layer at relative height `y`, see [`Region::extract_slab`]
+    pub fn extract_layer(&self, y: i32) -> Option<Region> {
+        return self.extract_slab(y..(y + 1));
+    }
+
+    /// Insert all layers of `slab` at relative height `y`, layers at and above `y` are moved up by
+    /// the height of `slab`. This is the inverse of [`Region::extract_slab`], the offset and name
+    /// of `slab` are ignored. \
+    /// The x and z size of `slab` must be equal to this region, and `y` should be in
+    /// `0..=shape[1]`, otherwise `InvalidValue` is returned. If only one of them records biomes,
+    /// the other part is filled with the default biome.
+    pub fn insert_layer(&mut self, y: i32, slab: &Region) -> Result<(), Error> {
+        let shape = self.shape();
+        let slab_shape = slab.shape();
+        if shape[0] != slab_shape[0] || shape[2] != slab_shape[2] {
+            return Err(Error::InvalidValue {
+                tag_path: "slab".to_string(),
+                error: format!(
+                    "x and z size of slab should be {}x{}, but found {}x{}",
+                    shape[0], shape[2], slab_shape[0], slab_shape[2]
+                ),
+            });
+        }
+        if y < 0 || y > shape[1] {
+            return Err(Error::InvalidValue {
+                tag_path: "y".to_string(),
+                error: format!("y should be in range [0, {}], but found {y}", shape[1]),
+            });
+        }
+        let height = slab_shape[1];
+        let split = y as usize;
+
+        if let Some(idx) = slab
+            .array_yzx
+            .iter()
+            .find(|idx| **idx as usize >= slab.palette.len())
+        {
+            return Err(Error::BlockIndexOutOfRange {
+                tag_path: "slab".to_string(),
+                index: *idx as i32,
+                range: [0, slab.palette.len() as i32],
+            });
+        }
+        // block index in slab -> block index in self
+        let lut: Vec<u16> = slab
+            .palette
+            .iter()
+            .map(|blk| self.find_or_append_to_palette(blk))
+            .collect();
+        let slab_array = slab.array_yzx.mapv(|idx| lut[idx as usize]);
+        self.array_yzx = concatenate(
+            Axis(0),
+            &[
+                self.array_yzx.slice(s![..split, .., ..]),
+                slab_array.view(),
+                self.array_yzx.slice(s![split.., .., ..]),
+            ],
+        )
+        .expect("x and z size are checked");
+
+        if self.biomes.is_some() || slab.biomes.is_some() {
+            if self.biomes.is_none() {
+                self.fill_biome(Biome::default());
+            }
+            let slab_biomes = match &slab.biomes {
+                Some(biomes) => {
+                    let biome_lut: Vec<u16> = slab
+                        .biome_palette
+                        .iter()
+                        .map(|b| self.find_or_append_biome(b))
+                        .collect();
+                    biomes.mapv(|idx| biome_lut[idx as usize])
+                }
+                None => {
+                    let idx = self.find_or_append_biome(&Biome::default().id());
+                    Array3::from_elem(slab.array_yzx.raw_dim(), idx)
+                }
+            };
+            if let Some(biomes) = &mut self.biomes {
+                *biomes = concatenate(
+                    Axis(0),
+                    &[
+                        biomes.slice(s![..split, .., ..]),
+                        slab_biomes.view(),
+                        biomes.slice(s![split.., .., ..]),
+                    ],
+                )
+                .expect("x and z size are checked");
+            }
+        }
+
+        let move_up = |pos: [i32; 3]| -> [i32; 3] {
+            if pos[1] >= y {
+                [pos[0], pos[1] + height, pos[2]]
+            } else {
+                pos
+            }
+        };
+        self.block_entities = self
+            .block_entities
+            .drain()
+            .map(|(pos, be)| (move_up(pos), be))
+            .collect();
+        self.pending_ticks = self
+            .pending_ticks
+            .drain()
+            .map(|(pos, ticks)| (move_up(pos), ticks))
+            .collect();
+        for entity in &mut self.entities {
+            if entity.block_pos[1] >= y {
+                entity.pos_shift([0, height, 0]);
+            }
+        }
+
+        for (pos, be) in &slab.block_entities {
+            self.block_entities
+                .insert([pos[0], pos[1] + y, pos[2]], be.clone());
+        }
+        for (pos, ticks) in &slab.pending_ticks {
+            self.pending_ticks
+                .insert([pos[0], pos[1] + y, pos[2]], ticks.clone());
+        }
+        self.entities.reserve(slab.entities.len());
+        for entity in &slab.entities {
+            let mut entity = entity.clone();
+            entity.pos_shift([0, y, 0]);
+            self.entities.push(entity);
+        }
+        return Ok(());
+    }
+}
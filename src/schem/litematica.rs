@@ -16,22 +16,34 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::error::Error;
+use crate::block::Block;
+use crate::error::{
+    fix_block_index_out_of_range, fix_multiple_block_entity_in_one_pos, Error, ErrorHandler,
+    ErrorHandlerFn, LoadWarning, MultipleBlockEntityFixMethod, StrictErrorHandler,
+};
 use crate::region::{PendingTick, PendingTickInfo, WorldSlice};
 use crate::schem::common;
 use crate::schem::common::size_i32_abs;
+use crate::schem::packed::{PackedRegion, PackedSchematic};
+use crate::schem::progress::ProgressReporter;
 use crate::schem::{
-    id_of_nbt_tag, BlockEntity, LitematicaLoadOption, LitematicaMetaData, LitematicaSaveOption,
-    MetaDataIR, Region, Schematic,
+    id_of_nbt_tag, AirPolicy, BitsPerBlock, BlockEntity, LitematicaLoadOption, LitematicaMetaData,
+    LitematicaSaveOption, MetaDataIR, PreviewImage, Region, Schematic,
 };
 use crate::{unwrap_opt_tag, unwrap_tag};
-use fastnbt::{LongArray, Value};
-use flate2::read::GzDecoder;
-use flate2::GzBuilder;
+use fastnbt::{IntArray, LongArray, Value};
 use std::cmp::max;
 use std::collections::HashMap;
 use std::convert::From;
+#[cfg(feature = "fs")]
 use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::Write;
+
+/// Litematica format versions(`Version` tag) that can be saved
+pub const LITEMATICA_SAVE_VERSIONS: [i32; 4] = [4, 5, 6, 7];
+/// Latest litematica format version
+pub const LITEMATICA_LATEST_VERSION: i32 = 7;
 
 impl MetaDataIR {
     pub fn from_litematica(src: &LitematicaMetaData) -> Self {
@@ -46,6 +58,7 @@ impl MetaDataIR {
 
         result.litematica_version = src.version;
         result.litematica_subversion = src.sub_version;
+        result.litematica_preview = src.preview.clone();
         //result.date = Some(src.time_modified);
         return result;
     }
@@ -53,10 +66,84 @@ impl MetaDataIR {
 
 impl Schematic {
     /// Load litematica from file.
+    #[cfg(feature = "fs")]
     pub fn from_litematica_file(
         filename: &str,
         option: &LitematicaLoadOption,
     ) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let (packed, md) = PackedSchematic::from_litematica_file(filename, option)?;
+        return Ok((packed.into_schematic(), md));
+    }
+    /// Load litematica from bytes, the content of a `.litematic` file. Compression is detected.
+    pub fn from_litematica_bytes(
+        bytes: &[u8],
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let (packed, md) = PackedSchematic::from_litematica_bytes(bytes, option)?;
+        return Ok((packed.into_schematic(), md));
+    }
+    /// Load litematica from a reader
+    pub fn from_litematica_reader(
+        src: &mut dyn std::io::Read,
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let (packed, md) = PackedSchematic::from_litematica_reader(src, option)?;
+        return Ok((packed.into_schematic(), md));
+    }
+
+    pub fn from_litematica_nbt(
+        nbt: HashMap<String, Value>,
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData), Error> {
+        let (packed, md) = PackedSchematic::from_litematica_nbt(nbt, option)?;
+        return Ok((packed.into_schematic(), md));
+    }
+
+    /// Load litematica from file, recoverable errors are fixed by `H` and returned as warnings.
+    #[cfg(feature = "fs")]
+    pub fn from_litematica_file_with_warnings<H: ErrorHandler>(
+        filename: &str,
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData, Vec<LoadWarning>), Error> {
+        let mut file = File::open(filename).map_err(Error::FileOpenError)?;
+        let mut decoder = common::decompress_reader(&mut file)?;
+        return Self::from_litematica_reader_with_warnings::<H>(&mut decoder, option);
+    }
+    /// Load litematica from bytes, recoverable errors are fixed by `H` and returned as warnings.
+    pub fn from_litematica_bytes_with_warnings<H: ErrorHandler>(
+        bytes: &[u8],
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData, Vec<LoadWarning>), Error> {
+        let mut decoder = common::decompress_reader(bytes)?;
+        return Self::from_litematica_reader_with_warnings::<H>(&mut decoder, option);
+    }
+    /// Load litematica from a reader, recoverable errors are fixed by `H` and returned as
+    /// warnings.
+    pub fn from_litematica_reader_with_warnings<H: ErrorHandler>(
+        src: &mut dyn std::io::Read,
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData, Vec<LoadWarning>), Error> {
+        let nbt = common::read_nbt_compound(src)?;
+        return Self::from_litematica_nbt_with_warnings::<H>(nbt, option);
+    }
+    /// Load litematica from nbt, recoverable errors are fixed by `H` and returned as warnings.
+    pub fn from_litematica_nbt_with_warnings<H: ErrorHandler>(
+        nbt: HashMap<String, Value>,
+        option: &LitematicaLoadOption,
+    ) -> Result<(Schematic, LitematicaMetaData, Vec<LoadWarning>), Error> {
+        let mut warnings = Vec::new();
+        let (packed, md) = PackedSchematic::parse_litematica_nbt::<H>(nbt, option, &mut warnings)?;
+        return Ok((packed.into_schematic(), md, warnings));
+    }
+}
+
+impl PackedSchematic {
+    /// Load litematica from file, keeping blocks packed.
+    #[cfg(feature = "fs")]
+    pub fn from_litematica_file(
+        filename: &str,
+        option: &LitematicaLoadOption,
+    ) -> Result<(PackedSchematic, LitematicaMetaData), Error> {
         let file_res = File::open(filename);
         let mut file;
         match file_res {
@@ -64,31 +151,45 @@ impl Schematic {
             Err(e) => return Err(Error::FileOpenError(e)),
         }
 
-        let mut decoder = GzDecoder::new(&mut file);
+        let mut decoder = common::decompress_reader(&mut file)?;
         return Self::from_litematica_reader(&mut decoder, option);
     }
-    /// Load litematica from a reader
+    /// Load litematica from bytes, keeping blocks packed. Compression is detected.
+    pub fn from_litematica_bytes(
+        bytes: &[u8],
+        option: &LitematicaLoadOption,
+    ) -> Result<(PackedSchematic, LitematicaMetaData), Error> {
+        let mut decoder = common::decompress_reader(bytes)?;
+        return Self::from_litematica_reader(&mut decoder, option);
+    }
+    /// Load litematica from a reader, keeping blocks packed.
     pub fn from_litematica_reader(
         src: &mut dyn std::io::Read,
         option: &LitematicaLoadOption,
-    ) -> Result<(Schematic, LitematicaMetaData), Error> {
-        let parse_res: Result<HashMap<String, Value>, fastnbt::error::Error> =
-            fastnbt::from_reader(src);
-        let parsed;
-        match parse_res {
-            Ok(nbt) => parsed = nbt,
-            Err(e) => return Err(Error::NBTReadError(e)),
-        }
+    ) -> Result<(PackedSchematic, LitematicaMetaData), Error> {
+        let parsed = common::read_nbt_compound(src)?;
         return Self::from_litematica_nbt(parsed, option);
     }
 
+    /// Load litematica from nbt, keeping blocks packed.
     pub fn from_litematica_nbt(
+        nbt: HashMap<String, Value>,
+        option: &LitematicaLoadOption,
+    ) -> Result<(PackedSchematic, LitematicaMetaData), Error> {
+        return Self::parse_litematica_nbt::<StrictErrorHandler>(nbt, option, &mut Vec::new());
+    }
+
+    pub(crate) fn parse_litematica_nbt<H: ErrorHandler>(
         mut nbt: HashMap<String, Value>,
-        _option: &LitematicaLoadOption,
-    ) -> Result<(Schematic, LitematicaMetaData), Error> {
-        let mut schem = Schematic::new();
+        option: &LitematicaLoadOption,
+        warnings: &mut Vec<LoadWarning>,
+    ) -> Result<(PackedSchematic, LitematicaMetaData), Error> {
+        let mut schem = PackedSchematic {
+            metadata: MetaDataIR::default(),
+            regions: Vec::new(),
+        };
         let raw_metadata;
-        match parse_metadata(&nbt) {
+        match parse_metadata(&nbt, warnings) {
             Ok(md) => {
                 schem.metadata = MetaDataIR::from_litematica(&md);
                 raw_metadata = md;
@@ -102,12 +203,56 @@ impl Schematic {
             HashMap::new(),
             "/Regions".to_string()
         );
+        if let Some(filter) = &option.region_filter {
+            regions.retain(|name, _| filter.accepts(name));
+        }
         schem.regions.reserve(regions.len());
+        // sizes are parsed again with error checking when parsing each region
+        let blocks_total: u64 = regions
+            .values()
+            .filter_map(|reg| match reg {
+                Value::Compound(reg) => match reg.get("Size") {
+                    Some(Value::Compound(size)) => common::parse_size_compound(size, "", true).ok(),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .map(|size| {
+                size.iter()
+                    .fold(1u64, |v, s| v.saturating_mul(s.unsigned_abs() as u64))
+            })
+            .fold(0u64, |sum, v| sum.saturating_add(v));
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            regions.len(),
+            blocks_total,
+        );
         for (key, val) in regions {
             let reg = unwrap_tag!(val, Compound, HashMap::new(), format!("/Regions/{}", key));
-            match Region::from_nbt_litematica(reg, &*format!("/Regions/{}", key)) {
+            if option.skip_entities {
+                reg.insert("Entities".to_string(), Value::List(vec![]));
+            }
+            if option.skip_block_entities {
+                reg.insert("TileEntities".to_string(), Value::List(vec![]));
+            }
+            match PackedRegion::parse_litematica::<H>(
+                reg,
+                &format!("/Regions/{}", key),
+                raw_metadata.version,
+                &mut reporter,
+                option.error_handler.as_ref(),
+                warnings,
+            ) {
                 Ok(mut reg) => {
                     reg.name = key.clone();
+                    common::validate_palette_if(
+                        option.validate_blocks,
+                        raw_metadata.data_version,
+                        &reg.palette,
+                        &format!("/Regions/{key}/BlockStatePalette"),
+                    )?;
+                    reporter.finish_region(reg.volume())?;
                     schem.regions.push(reg);
                 }
                 Err(e) => return Err(e),
@@ -118,7 +263,11 @@ impl Schematic {
     }
 }
 
-fn parse_metadata(root: &HashMap<String, Value>) -> Result<LitematicaMetaData, Error> {
+/// A broken preview image is dropped with a warning, since it doesn't affect the blocks
+fn parse_metadata(
+    root: &HashMap<String, Value>,
+    warnings: &mut Vec<LoadWarning>,
+) -> Result<LitematicaMetaData, Error> {
     let mut result = LitematicaMetaData::default();
 
     result.data_version = *unwrap_opt_tag!(
@@ -136,18 +285,18 @@ fn parse_metadata(root: &HashMap<String, Value>) -> Result<LitematicaMetaData, E
         "/Metadata".to_string()
     );
 
-    result.time_created = *unwrap_opt_tag!(
-        md.get("TimeCreated"),
-        Long,
-        0,
-        "/Metadata/TimeCreated".to_string()
-    );
-    result.time_modified = *unwrap_opt_tag!(
-        md.get("TimeModified"),
-        Long,
-        0,
-        "/Metadata/TimeModified".to_string()
-    );
+    // files written by other tools may miss time stamps, fill them with each other or now
+    let time_created = match md.get("TimeCreated") {
+        Some(value) => Some(*unwrap_tag!(value, Long, 0, "/Metadata/TimeCreated")),
+        None => None,
+    };
+    let time_modified = match md.get("TimeModified") {
+        Some(value) => Some(*unwrap_tag!(value, Long, 0, "/Metadata/TimeModified")),
+        None => None,
+    };
+    let now = common::current_ms_timestamp();
+    result.time_created = time_created.or(time_modified).unwrap_or(now);
+    result.time_modified = time_modified.or(time_created).unwrap_or(now);
     {
         let enclosing_size = unwrap_opt_tag!(
             md.get("EnclosingSize"),
@@ -238,6 +387,21 @@ fn parse_metadata(root: &HashMap<String, Value>) -> Result<LitematicaMetaData, E
         result.sub_version = Some(*unwrap_tag!(value, Int, 0, "/SubVersion"));
     }
 
+    if let Some(value) = md.get("PreviewImageData") {
+        let tag_path = "/Metadata/PreviewImageData";
+        let data = unwrap_tag!(value, IntArray, IntArray::new(vec![]), tag_path);
+        match PreviewImage::from_argb_ints(data) {
+            Some(img) => result.preview = Some(img),
+            None => warnings.push(LoadWarning {
+                error: Error::InvalidValue {
+                    tag_path: tag_path.to_string(),
+                    error: format!("{} pixels can not form a square image", data.len()),
+                },
+                fix: "preview image is dropped".to_string(),
+            }),
+        }
+    }
+
     return Ok(result);
 }
 
@@ -250,21 +414,126 @@ pub fn block_required_bits(palette_size: usize) -> usize {
     return bits;
 }
 
+/// Bits per block used to save a region with `palette_size` blocks and `total_blocks` blocks.
+/// Forced values are rejected if the loader would take another value for the same length of
+/// `BlockStates`, since the width is not stored in the file.
+pub fn litematica_bits_per_block(
+    palette_size: usize,
+    total_blocks: usize,
+    bits: BitsPerBlock,
+    region_name: &str,
+) -> Result<u8, Error> {
+    let required = block_required_bits(palette_size);
+    return match bits {
+        BitsPerBlock::Auto => Ok(max(required, 2) as u8),
+        BitsPerBlock::Forced(b) => {
+            let len = block_states_len(total_blocks, b);
+            let detected = bits_per_block_candidates(palette_size)
+                .find(|c| block_states_len(total_blocks, *c) == len);
+            if b == 0 || b > 16 || (b as usize) < required || detected != Some(b) {
+                return Err(Error::InvalidBitsPerBlock {
+                    region_name: region_name.to_string(),
+                    bits: b,
+                    palette_size,
+                });
+            }
+            Ok(b)
+        }
+    };
+}
+
+/// Bits per block tried when loading, in order. Litematica uses max(2, required bits), but files
+/// saved by older mc_schem may use fewer or more bits.
+fn bits_per_block_candidates(palette_size: usize) -> impl Iterator<Item = u8> {
+    let required = max(block_required_bits(palette_size), 1) as u8;
+    let vanilla = max(required, 2);
+    return std::iter::once(vanilla).chain((required..=64).filter(move |b| *b != vanilla));
+}
+
+/// Count of longs to store `total_blocks` elements of `bits` bits
+fn block_states_len(total_blocks: usize, bits: u8) -> usize {
+    return common::ceil_up_to((total_blocks * bits as usize) as isize, 64) as usize / 64;
+}
+
+/// Find bits per block of `BlockStates` by its length. If several widths give the same length,
+/// which happens for small regions, the first one that decodes to indices all inside the palette
+/// with zeroed padding bits is taken.
+fn detect_bits_per_block(data: &[u64], palette_size: usize, total_blocks: usize) -> Option<u8> {
+    let matched: Vec<u8> = bits_per_block_candidates(palette_size)
+        .filter(|bits| block_states_len(total_blocks, *bits) == data.len())
+        .collect();
+    if matched.len() > 1 {
+        for bits in &matched {
+            let used_bits = total_blocks * *bits as usize;
+            if used_bits % 64 != 0 && data.last().is_some_and(|w| w >> (used_bits % 64) != 0) {
+                continue;
+            }
+            let Some(mbs) = MultiBitSet::from_data_vec(data.to_vec(), total_blocks, *bits) else {
+                continue;
+            };
+            if mbs.iter().all(|idx| idx < palette_size as u64) {
+                return Some(*bits);
+            }
+        }
+    }
+    return matched.first().copied();
+}
+
 impl Region {
     /// Load a region from nbt
     pub fn from_nbt_litematica(
         nbt: &mut HashMap<String, Value>,
         tag_path: &str,
     ) -> Result<Region, Error> {
-        let mut region = Region::new();
+        return Ok(PackedRegion::from_nbt_litematica(nbt, tag_path)?.into_region());
+    }
+}
+
+impl PackedRegion {
+    /// Load a region from nbt without unpacking `BlockStates`. The long array is moved out of
+    /// `nbt` instead of being copied.
+    pub fn from_nbt_litematica(
+        nbt: &mut HashMap<String, Value>,
+        tag_path: &str,
+    ) -> Result<PackedRegion, Error> {
+        return Self::parse_litematica::<StrictErrorHandler>(
+            nbt,
+            tag_path,
+            LITEMATICA_LATEST_VERSION,
+            &mut ProgressReporter::new(None, None, 1, 0),
+            None,
+            &mut Vec::new(),
+        );
+    }
 
-        // parse position(offset)
+    fn parse_litematica<H: ErrorHandler>(
+        nbt: &mut HashMap<String, Value>,
+        tag_path: &str,
+        version: i32,
+        reporter: &mut ProgressReporter,
+        handler: Option<&ErrorHandlerFn>,
+        warnings: &mut Vec<LoadWarning>,
+    ) -> Result<PackedRegion, Error> {
+        let mut region = PackedRegion {
+            name: String::from("NewRegion"),
+            shape: [0, 0, 0],
+            blocks: MultiBitSet::new(),
+            palette: Vec::new(),
+            block_entities: HashMap::new(),
+            pending_ticks: HashMap::new(),
+            entities: Vec::new(),
+            offset: [0, 0, 0],
+            negative_size: [false; 3],
+        };
+
+        // parse position, which is a corner of the region, not necessarily the lower one
+        let position;
         {
             let cur_tag_path = format!("{}/Position", tag_path);
-            let position =
+            let pos_nbt =
                 unwrap_opt_tag!(nbt.get("Position"), Compound, HashMap::new(), cur_tag_path);
-            match common::parse_size_compound(position, &cur_tag_path, true) {
-                Ok(pos) => region.offset = size_i32_abs(pos),
+            match common::parse_size_compound(pos_nbt, &cur_tag_path, true) {
+                Ok(pos) => position = pos,
                 Err(e) => return Err(e),
             }
         }
@@ -297,8 +566,30 @@ impl Region {
             let size = unwrap_opt_tag!(nbt.get("Size"), Compound, HashMap::new(), cur_tag_path);
             match common::parse_size_compound(size, &cur_tag_path, true) {
                 Ok(size) => {
+                    // negative size means that the region extends from `Position` towards
+                    // negative direction, normalize it into lower corner and positive shape
+                    for dim in 0..3 {
+                        region.negative_size[dim] = size[dim] < 0;
+                        let lower = if size[dim] < 0 {
+                            // size + 1 never overflows when size is negative
+                            position[dim].checked_add(size[dim] + 1)
+                        } else {
+                            Some(position[dim])
+                        };
+                        match lower {
+                            Some(lower) if size[dim] != i32::MIN => region.offset[dim] = lower,
+                            _ => {
+                                return Err(Error::InvalidValue {
+                                    tag_path: cur_tag_path,
+                                    error: format!(
+                                        "Region at {position:?} with size {size:?} exceeds range of i32"
+                                    ),
+                                })
+                            }
+                        }
+                    }
                     let size = size_i32_abs(size);
-                    region.reshape(&size);
+                    region.shape = size;
                     region_size = size;
                 }
                 Err(e) => return Err(e),
@@ -312,39 +603,83 @@ impl Region {
         {
             let palette_len = region.palette.len();
             let array = unwrap_opt_tag!(
-                nbt.get("BlockStates"),
+                nbt.remove("BlockStates"),
                 LongArray,
                 LongArray::new(vec![]),
                 format!("{}/BlockStates", tag_path)
             );
-            let mut array_u8_be: Vec<u64> = Vec::with_capacity(array.len());
-            for val in array.iter() {
-                array_u8_be.push(u64::from_ne_bytes(val.to_le_bytes()));
-            }
-            let mbs = MultiBitSet::from_data_vec(
-                array_u8_be,
-                total_blocks as usize,
-                block_required_bits(palette_len) as u8,
-            );
-            assert!(mbs.is_some());
-            let mbs = mbs.unwrap();
-            let mut idx = 0;
-            for y in 0..region.shape()[1] {
-                for z in 0..region.shape()[2] {
-                    for x in 0..region.shape()[0] {
-                        let blk_id = mbs.get(idx);
-                        if blk_id >= palette_len as u64 {
-                            return Err(Error::BlockIndexOutOfRange {
-                                tag_path: format!("{}/BlockStates", tag_path),
-                                index: blk_id as i32,
-                                range: [0, palette_len as i32],
-                            });
-                        }
-                        idx += 1;
-                        region.array_yzx[[y as usize, z as usize, x as usize]] = blk_id as u16;
+            let array_len = array.len();
+            // reuses the allocation of the long array
+            let array_u8_be: Vec<u64> = array
+                .into_inner()
+                .into_iter()
+                .map(|val| u64::from_ne_bytes(val.to_le_bytes()))
+                .collect();
+            let bits = match detect_bits_per_block(&array_u8_be, palette_len, total_blocks as usize)
+            {
+                Some(b) => b,
+                None => {
+                    return Err(Error::BlockDataIncomplete {
+                        tag_path: format!("{}/BlockStates", tag_path),
+                        index: 0,
+                        detail: format!(
+                            "{} blocks with palette size {} can not be stored in {} longs",
+                            total_blocks, palette_len, array_len
+                        ),
+                    })
+                }
+            };
+            let mut mbs = match MultiBitSet::from_data_vec(array_u8_be, total_blocks as usize, bits)
+            {
+                Some(mbs) => mbs,
+                None => {
+                    return Err(Error::BlockDataIncomplete {
+                        tag_path: format!("{}/BlockStates", tag_path),
+                        index: 0,
+                        detail: format!("invalid bits per block: {bits}"),
+                    })
+                }
+            };
+            // invalid indices are fixed once per distinct value
+            let mut fixes: HashMap<u64, u16> = HashMap::new();
+            let mut invalid_positions: Vec<usize> = Vec::new();
+            for (idx, blk_id) in mbs.iter().enumerate() {
+                reporter.advance(1)?;
+                if blk_id >= palette_len as u64 {
+                    invalid_positions.push(idx);
+                    if fixes.contains_key(&blk_id) {
+                        continue;
                     }
+                    let err = Error::BlockIndexOutOfRange {
+                        tag_path: format!("{}/BlockStates", tag_path),
+                        index: blk_id as i32,
+                        range: [0, palette_len as i32],
+                    };
+                    let fixed =
+                        fix_block_index_out_of_range::<H>(handler, &mut region.palette, &err)
+                            .resolve(err, |f| format!("replaced by block index {f}"), warnings)?;
+                    fixes.insert(blk_id, fixed);
+                }
+            }
+            // a fix handler may append to the palette, so the fixed index can exceed the bits
+            // of the file
+            if let Some(max_fixed) = fixes.values().copied().max() {
+                if max_fixed as u64 > mbs.element_max_value() {
+                    let required_bits = (u16::BITS - max_fixed.leading_zeros()) as u8;
+                    mbs.resize_element_bits(required_bits)?;
+                }
+            }
+            for idx in invalid_positions {
+                let fixed = fixes[&mbs.get(idx)];
+                if mbs.set(idx, fixed as u64).is_err() {
+                    return Err(Error::BlockDataIncomplete {
+                        tag_path: format!("{}/BlockStates", tag_path),
+                        index: idx,
+                        detail: format!("failed to replace invalid block index by {fixed}"),
+                    });
                 }
             }
+            region.blocks = mbs;
         }
 
         //parse entities
@@ -353,13 +688,20 @@ impl Region {
             let mut entities_list =
                 unwrap_opt_tag!(nbt.remove("Entities"), List, vec![], cur_tag_path);
             for (idx, entity_comp) in entities_list.iter_mut().enumerate() {
-                let cur_tag_path = format!("{}/[{}]", cur_tag_path, idx);
+                let cur_tag_path = format!("{}[{}]", cur_tag_path, idx);
                 let entity_comp = unwrap_tag!(entity_comp, Compound, HashMap::new(), cur_tag_path);
                 let mut temp = HashMap::new();
                 std::mem::swap(&mut temp, entity_comp);
+                if version <= 1 {
+                    temp = unwrap_v1_entity(temp, &cur_tag_path)?;
+                }
                 let parse_res = common::parse_entity_litematica(temp, &cur_tag_path);
                 match parse_res {
-                    Ok(entity) => region.entities.push(entity),
+                    Ok(mut entity) => {
+                        // entity positions are relative to `Position` rather than the lower corner
+                        entity.pos_shift(negative_size_shift(region.negative_size, region_size));
+                        region.entities.push(entity);
+                    }
                     Err(e) => return Err(e),
                 }
             }
@@ -370,11 +712,14 @@ impl Region {
             let cur_tag_path = format!("{}/TileEntities", tag_path);
             let te_list = unwrap_opt_tag!(nbt.get_mut("TileEntities"), List, vec![], cur_tag_path);
             for (idx, te_comp) in te_list.iter_mut().enumerate() {
-                let cur_tag_path = format!("{}[{}]", tag_path, idx);
+                let cur_tag_path = format!("{}[{}]", cur_tag_path, idx);
                 let te_comp = unwrap_tag!(te_comp, Compound, HashMap::new(), cur_tag_path);
                 let mut temp = HashMap::new();
                 std::mem::swap(&mut temp, te_comp);
-                let te_res = parse_tile_entity(temp, tag_path, &region_size);
+                if version <= 1 {
+                    temp = unwrap_v1_tile_entity(temp, &cur_tag_path)?;
+                }
+                let te_res = parse_tile_entity(temp, &cur_tag_path, &region_size);
 
                 let pos;
                 let te;
@@ -387,10 +732,19 @@ impl Region {
                 }
 
                 if region.block_entities.contains_key(&pos) {
-                    return Err(Error::MultipleBlockEntityInOnePos {
+                    let err = Error::MultipleBlockEntityInOnePos {
                         pos,
                         latter_tag_path: cur_tag_path,
-                    });
+                    };
+                    let method = fix_multiple_block_entity_in_one_pos::<H>(
+                        handler,
+                        &mut region.palette,
+                        &err,
+                    )
+                    .resolve(err, |m| format!("{m:?}"), warnings)?;
+                    if method == MultipleBlockEntityFixMethod::KeepFormer {
+                        continue;
+                    }
                 }
                 region.block_entities.insert(pos, te);
             }
@@ -447,8 +801,9 @@ impl Region {
     }
 }
 
-/// Bit-packed vector of unsigned integers. Used to encode and decode litematica
-#[derive(Debug)]
+/// Bit-packed vector of unsigned integers. Used to encode and decode litematica `BlockStates`.
+/// Every element takes `element_bits` bits, and an element may cross the boundary of two `u64`s.
+#[derive(Debug, Clone)]
 pub struct MultiBitSet {
     arr: Vec<u64>,
     length: usize,
@@ -457,6 +812,7 @@ pub struct MultiBitSet {
 
 #[allow(dead_code)]
 impl MultiBitSet {
+    /// Create an empty set with 1 bit per element
     pub fn new() -> MultiBitSet {
         return MultiBitSet {
             arr: Vec::new(),
@@ -465,6 +821,8 @@ impl MultiBitSet {
         };
     }
 
+    /// Copy packed data. Returns `None` if `ele_bits` is not in `[1, 64]`, or `data` is too short to
+    /// hold `length` elements.
     pub fn from_data(data: &[u64], length: usize, ele_bits: u8) -> Option<MultiBitSet> {
         if ele_bits <= 0 || ele_bits > 64 {
             return None;
@@ -482,6 +840,7 @@ impl MultiBitSet {
         return Some(result);
     }
 
+    /// Same as `from_data`, but takes ownership of `data`
     pub fn from_data_vec(data: Vec<u64>, length: usize, ele_bits: u8) -> Option<MultiBitSet> {
         if ele_bits <= 0 || ele_bits > 64 {
             return None;
@@ -497,16 +856,22 @@ impl MultiBitSet {
         });
     }
 
+    /// Packed data, can be written to nbt directly
     pub fn as_u64_slice(&self) -> &[u64] {
         return &self.arr;
     }
 
+    /// Bits of each element
     pub fn element_bits(&self) -> u8 {
         return self.element_bits;
     }
+    /// Count of elements
     pub fn len(&self) -> usize {
         return self.length;
     }
+    pub fn is_empty(&self) -> bool {
+        return self.length == 0;
+    }
     pub fn total_bits(&self) -> usize {
         return self.length * (self.element_bits as usize);
     }
@@ -517,6 +882,8 @@ impl MultiBitSet {
         }
         return total_bits / 64 + 1;
     }
+    /// Change bits per element and length. Existing data is not repacked, use
+    /// `resize_element_bits` to keep values.
     pub fn reset(&mut self, element_bits: u8, len: usize) {
         assert!(element_bits > 0);
         assert!(element_bits <= 64);
@@ -580,6 +947,7 @@ impl MultiBitSet {
         return self.basic_mask();
     }
 
+    /// Get element at `ele_index`, panics if index is out of range
     pub fn get(&self, ele_index: usize) -> u64 {
         assert!(ele_index < self.length);
 
@@ -621,6 +989,7 @@ impl MultiBitSet {
         };
     }
 
+    /// Set element at `ele_index`. Fails if index is out of range, or `value` needs more bits.
     pub fn set(&mut self, ele_index: usize, value: u64) -> Result<(), ()> {
         if value > self.element_max_value() {
             return Err(());
@@ -673,6 +1042,173 @@ impl MultiBitSet {
 
         return Ok(());
     }
+
+    /// Iterate over all elements. The packed data is walked word by word, which is much faster than
+    /// calling `get` for each index.
+    pub fn iter(&self) -> MultiBitSetIter<'_> {
+        return MultiBitSetIter {
+            words: &self.arr,
+            word_index: 0,
+            bit_offset: 0,
+            element_bits: self.element_bits as u32,
+            mask: self.basic_mask(),
+            remaining: self.length,
+        };
+    }
+
+    /// Unpack all elements into `dest`, values are truncated to `u16`. Only
+    /// `min(dest.len(), self.len())` elements are written. Returns the number of written elements.
+    pub fn unpack_into_u16(&self, dest: &mut [u16]) -> usize {
+        let count = dest.len().min(self.length);
+        for (d, value) in dest[..count].iter_mut().zip(self.iter()) {
+            *d = value as u16;
+        }
+        return count;
+    }
+
+    /// Repack all elements with `new_bits` bits per element, values are kept. Fails without changing
+    /// anything if `new_bits` is not in `[1, 64]`, or a value can't be stored in `new_bits` bits.
+    pub fn resize_element_bits(&mut self, new_bits: u8) -> Result<(), Error> {
+        if new_bits == 0 || new_bits > 64 {
+            return Err(Error::InvalidValue {
+                tag_path: "MultiBitSet.element_bits".to_string(),
+                error: format!("Bits per element should be in [1, 64], but found {new_bits}"),
+            });
+        }
+        if new_bits == self.element_bits {
+            return Ok(());
+        }
+        let max_value = Self::mask_by_bits(new_bits);
+        if new_bits < self.element_bits {
+            for (index, value) in self.iter().enumerate() {
+                if value > max_value {
+                    return Err(Error::ValueTooLargeForBits {
+                        index,
+                        value,
+                        bits: new_bits,
+                    });
+                }
+            }
+        }
+
+        let mut result = MultiBitSet::new();
+        result.reset(new_bits, self.length);
+        for (index, value) in self.iter().enumerate() {
+            if result.set(index, value).is_err() {
+                return Err(Error::ValueTooLargeForBits {
+                    index,
+                    value,
+                    bits: new_bits,
+                });
+            }
+        }
+        *self = result;
+        return Ok(());
+    }
+}
+
+/// Iterator over elements of [`MultiBitSet`], decoding the packed words in order
+#[derive(Debug, Clone)]
+pub struct MultiBitSetIter<'a> {
+    words: &'a [u64],
+    word_index: usize,
+    /// Index of the lowest bit of next element in current word
+    bit_offset: u32,
+    element_bits: u32,
+    mask: u64,
+    remaining: usize,
+}
+
+impl Iterator for MultiBitSetIter<'_> {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let mut value = self.words[self.word_index] >> self.bit_offset;
+        let end = self.bit_offset + self.element_bits;
+        if end < 64 {
+            self.bit_offset = end;
+        } else {
+            self.word_index += 1;
+            if end > 64 {
+                // the element crosses the boundary, higher bits are in the next word
+                value |= self.words[self.word_index] << (64 - self.bit_offset);
+            }
+            self.bit_offset = end - 64;
+        }
+        return Some(value & self.mask);
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        return (self.remaining, Some(self.remaining));
+    }
+}
+
+impl ExactSizeIterator for MultiBitSetIter<'_> {}
+
+/// Litematica version 1 wraps tile entities as `{Pos: {x, y, z}, TileNBT: {..}}`, convert it to
+/// the flat form of later versions
+fn unwrap_v1_tile_entity(
+    mut nbt: HashMap<String, Value>,
+    tag_path: &str,
+) -> Result<HashMap<String, Value>, Error> {
+    let pos = common::parse_size_compound(
+        unwrap_opt_tag!(
+            nbt.get("Pos"),
+            Compound,
+            HashMap::new(),
+            format!("{tag_path}/Pos")
+        ),
+        &format!("{tag_path}/Pos"),
+        true,
+    )?;
+    let mut result = unwrap_opt_tag!(
+        nbt.remove("TileNBT"),
+        Compound,
+        HashMap::new(),
+        format!("{tag_path}/TileNBT")
+    );
+    for (key, val) in ["x", "y", "z"].iter().zip(pos) {
+        result.insert(key.to_string(), Value::Int(val));
+    }
+    return Ok(result);
+}
+
+/// Litematica version 1 wraps entities as `{Pos: {dx, dy, dz}, EntityData: {..}}`, convert it to
+/// the flat form of later versions
+fn unwrap_v1_entity(
+    mut nbt: HashMap<String, Value>,
+    tag_path: &str,
+) -> Result<HashMap<String, Value>, Error> {
+    let mut result = unwrap_opt_tag!(
+        nbt.remove("EntityData"),
+        Compound,
+        HashMap::new(),
+        format!("{tag_path}/EntityData")
+    );
+    if let Some(Value::Compound(pos)) = nbt.get("Pos") {
+        let mut pos_list = Vec::with_capacity(3);
+        for key in ["dx", "dy", "dz"] {
+            let val = *unwrap_opt_tag!(pos.get(key), Double, 0.0, format!("{tag_path}/Pos/{key}"));
+            pos_list.push(Value::Double(val));
+        }
+        result.insert("Pos".to_string(), Value::List(pos_list));
+    }
+    return Ok(result);
+}
+
+fn check_litematica_save_version(version: i32) -> Result<(), Error> {
+    if !LITEMATICA_SAVE_VERSIONS.contains(&version) {
+        return Err(Error::UnsupportedLitematicaVersion {
+            version,
+            supported_versions: LITEMATICA_SAVE_VERSIONS.to_vec(),
+        });
+    }
+    return Ok(());
 }
 
 fn parse_tile_entity(
@@ -684,7 +1220,7 @@ fn parse_tile_entity(
 
     let tag_names = ['x', 'y', 'z'];
     for (dim, p) in pos.iter().enumerate() {
-        if *p < 0 || *p > region_size[dim] {
+        if *p < 0 || *p >= region_size[dim] {
             return Err(Error::BlockPosOutOfRange {
                 tag_path: format!("{}/{}", tag_path, tag_names[dim]),
                 pos,
@@ -768,56 +1304,109 @@ impl Schematic {
         md.description = self.metadata.description.clone();
         md.version = self.metadata.litematica_version;
         md.sub_version = self.metadata.litematica_subversion;
+        // non-positive time stamps are treated as absent, and `md` keeps current time for them
+        if self.metadata.time_created > 0 {
+            md.time_created = self.metadata.time_created;
+        }
+        if self.metadata.time_modified > 0 {
+            md.time_modified = self.metadata.time_modified;
+        }
         md.total_volume = self.volume() as i32;
         md.region_count = self.regions.len() as i32;
         md.total_blocks = self.total_blocks(false) as i32;
         md.enclosing_size = self.shape();
+        md.preview = self.metadata.litematica_preview.clone();
 
         return Ok(md);
     }
 
+    /// Litematica format version to save as, `target` is checked if it's assigned.
+    fn litematica_save_version(&self, target: Option<i32>) -> Result<i32, Error> {
+        if let Some(version) = target {
+            check_litematica_save_version(version)?;
+            return Ok(version);
+        }
+        let version = self.metadata.litematica_version;
+        if LITEMATICA_SAVE_VERSIONS.contains(&version) {
+            return Ok(version);
+        }
+        let guessed =
+            LitematicaMetaData::data_version_to_lite_version(self.metadata.mc_data_version);
+        return Ok(guessed.unwrap_or(LITEMATICA_LATEST_VERSION));
+    }
+
     fn find_non_duplicate_name<T>(saved_regions: &HashMap<String, T>, old_name: &str) -> String {
-        let idx = 1u64;
+        let mut idx = 1u64;
         loop {
             let cur_name = format!("{}({})", old_name, idx);
             if saved_regions.contains_key(&cur_name) {
+                idx += 1;
                 continue;
             }
             return cur_name;
         }
     }
+    /// Names of regions in the saved file, duplicated names are renamed or rejected according to
+    /// `option`.
+    fn litematica_region_names(&self, option: &LitematicaSaveOption) -> Result<Vec<String>, Error> {
+        let mut used: HashMap<String, ()> = HashMap::with_capacity(self.regions.len());
+        let mut names = Vec::with_capacity(self.regions.len());
+        for reg in &self.regions {
+            let mut name = reg.name.clone();
+            if used.contains_key(&name) {
+                if !option.rename_duplicated_regions {
+                    return Err(Error::DuplicatedRegionName { name });
+                }
+                name = Self::find_non_duplicate_name(&used, &reg.name);
+            }
+            used.insert(name.clone(), ());
+            names.push(name);
+        }
+        return Ok(names);
+    }
+
     /// Save into nbt format
     pub fn to_nbt_litematica(
         &self,
         option: &LitematicaSaveOption,
     ) -> Result<HashMap<String, Value>, Error> {
-        let mut nbt: HashMap<String, Value> = HashMap::new();
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.to_nbt_litematica(option);
+        }
+        let version = self.litematica_save_version(option.target_version)?;
+        let names = self.litematica_region_names(option)?;
 
         //Regions
+        let mut regions: HashMap<String, Value> = HashMap::with_capacity(self.regions.len());
         {
-            let mut regions: HashMap<String, Value> = HashMap::with_capacity(self.regions.len());
-            for reg in &self.regions {
-                let nbt_region;
-                match reg.to_nbt_litematica() {
-                    Ok(nbt) => nbt_region = nbt,
-                    Err(e) => return Err(e),
-                }
-
-                if regions.contains_key(&reg.name) {
-                    if option.rename_duplicated_regions {
-                        let new_name = Self::find_non_duplicate_name(&regions, &reg.name);
-                        regions.insert(new_name, Value::Compound(nbt_region));
-                        continue;
-                    }
-                    return Err(Error::DuplicatedRegionName {
-                        name: reg.name.clone(),
-                    });
-                }
-                regions.insert(reg.name.clone(), Value::Compound(nbt_region));
+            let blocks_total = self.regions.iter().map(|r| r.volume()).sum();
+            let mut reporter = ProgressReporter::new(
+                option.progress.as_ref(),
+                option.cancel.as_ref(),
+                self.regions.len(),
+                blocks_total,
+            );
+            for (reg, name) in self.regions.iter().zip(names) {
+                let nbt_region = reg.to_nbt_litematica_versioned(option, version)?;
+                reporter.finish_region(reg.volume())?;
+                regions.insert(name, Value::Compound(nbt_region));
             }
-            nbt.insert("Regions".to_string(), Value::Compound(regions));
         }
 
+        let mut nbt = self.litematica_root_nbt(option, version)?;
+        nbt.insert("Regions".to_string(), Value::Compound(regions));
+        return Ok(nbt);
+    }
+
+    /// Tags of root compound except `Regions`
+    fn litematica_root_nbt(
+        &self,
+        option: &LitematicaSaveOption,
+        version: i32,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let mut nbt: HashMap<String, Value> = HashMap::new();
         // meta data
         {
             let md = match self.metadata_litematica() {
@@ -828,8 +1417,10 @@ impl Schematic {
                 "MinecraftDataVersion".to_string(),
                 Value::Int(md.data_version),
             );
-            nbt.insert("Version".to_string(), Value::Int(md.version));
-            if let Some(sv) = md.sub_version {
+            nbt.insert("Version".to_string(), Value::Int(version));
+            // SubVersion appears since version 6
+            if version >= 6 {
+                let sv = md.sub_version.unwrap_or(1);
                 nbt.insert("SubVersion".to_string(), Value::Int(sv));
             }
             {
@@ -839,19 +1430,52 @@ impl Schematic {
                 md_nbt.insert("Description".to_string(), Value::String(md.description));
                 md_nbt.insert("TimeCreated".to_string(), Value::Long(md.time_created));
                 md_nbt.insert("TimeModified".to_string(), Value::Long(md.time_modified));
-                md_nbt.insert("TotalVolume".to_string(), Value::Int(self.volume() as i32));
+                // litematica reads these as int, so there is no long variant to fall back to
+                md_nbt.insert(
+                    "TotalVolume".to_string(),
+                    common::checked_int_tag(self.volume(), "/Metadata/TotalVolume")?,
+                );
                 md_nbt.insert(
                     "TotalBlocks".to_string(),
-                    Value::Int(self.total_blocks(false) as i32),
+                    common::checked_int_tag(
+                        self.total_blocks(option.air_policy == AirPolicy::Include),
+                        "/Metadata/TotalBlocks",
+                    )?,
                 );
                 md_nbt.insert(
                     "RegionCount".to_string(),
-                    Value::Int(self.regions.len() as i32),
+                    common::checked_int_tag(self.regions.len() as u64, "/Metadata/RegionCount")?,
                 );
                 md_nbt.insert(
                     "EnclosingSize".to_string(),
                     Value::Compound(common::size_to_compound(&self.shape())),
                 );
+                if let Some(preview) = option.preview.as_ref().or(md.preview.as_ref()) {
+                    if preview.width != preview.height
+                        || preview.argb.len() != (preview.width * preview.height) as usize
+                    {
+                        return Err(Error::InvalidValue {
+                            tag_path: "/Metadata/PreviewImageData".to_string(),
+                            error: format!(
+                                "Litematica preview should be square, but found {}x{} with {} pixels",
+                                preview.width,
+                                preview.height,
+                                preview.argb.len()
+                            ),
+                        });
+                    }
+                    md_nbt.insert(
+                        "PreviewImageData".to_string(),
+                        Value::IntArray(IntArray::new(preview.to_argb_ints())),
+                    );
+                }
+                let required_mods = self.required_mods();
+                if !required_mods.is_empty() {
+                    md_nbt.insert(
+                        "RequiredMods".to_string(),
+                        Value::List(required_mods.into_iter().map(Value::String).collect()),
+                    );
+                }
 
                 nbt.insert("Metadata".to_string(), Value::Compound(md_nbt));
             }
@@ -859,112 +1483,207 @@ impl Schematic {
         return Ok(nbt);
     }
 
-    /// Save to writer
-    pub fn save_litematica_writer(
+    /// Encode regions one by one and stream them into the compressor, so that the nbt of the whole
+    /// schematic is never held in memory.
+    fn save_litematica_stream(
         &self,
         dest: &mut dyn std::io::Write,
         option: &LitematicaSaveOption,
+        filename: Option<&str>,
     ) -> Result<(), Error> {
-        let nbt = match self.to_nbt_litematica(option) {
-            Ok(nbt_) => nbt_,
-            Err(e) => return Err(e),
-        };
-        let mut encoder = GzBuilder::new()
-            .comment("Generated by mc_schem")
-            .write(dest, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.save_litematica_stream(dest, option, filename);
         }
-        if let Err(e) = encoder.finish() {
-            return Err(Error::NBTWriteError(e.into()));
+        let version = self.litematica_save_version(option.target_version)?;
+        let names = self.litematica_region_names(option)?;
+        let root = self.litematica_root_nbt(option, version)?;
+
+        let mut encoder = common::CompressedWriter::new(
+            dest,
+            option.compress_method,
+            option.compress_level,
+            filename,
+        )?;
+        // root compound with empty name
+        common::write_tag_header(&mut encoder, 10, "")?;
+        for (key, value) in &root {
+            common::write_nbt_entry(&mut encoder, key, value)?;
         }
+        // Regions
+        {
+            common::write_tag_header(&mut encoder, 10, "Regions")?;
+
+            let blocks_total = self.regions.iter().map(|r| r.volume()).sum();
+            let mut reporter = ProgressReporter::new(
+                option.progress.as_ref(),
+                option.cancel.as_ref(),
+                self.regions.len(),
+                blocks_total,
+            );
+            for (reg, name) in self.regions.iter().zip(names) {
+                let nbt_region = reg.to_nbt_litematica_versioned(option, version)?;
+                common::write_nbt_entry(&mut encoder, &name, &Value::Compound(nbt_region))?;
+                reporter.finish_region(reg.volume())?;
+            }
+            common::write_raw_bytes(&mut encoder, &[0])?;
+        }
+        common::write_raw_bytes(&mut encoder, &[0])?;
+        return encoder.finish();
+    }
 
-        return Ok(());
+    /// Save to writer. Regions are encoded and compressed one at a time, so peak memory is about
+    /// the largest region rather than the whole file.
+    pub fn save_litematica_writer(
+        &self,
+        dest: &mut dyn std::io::Write,
+        option: &LitematicaSaveOption,
+    ) -> Result<(), Error> {
+        return self.save_litematica_stream(dest, option, None);
     }
 
-    /// Save to file
+    /// Save to bytes, the content of a `.litematic` file.
+    pub fn to_litematica_bytes(&self, option: &LitematicaSaveOption) -> Result<Vec<u8>, Error> {
+        let mut dest = Vec::new();
+        self.save_litematica_writer(&mut dest, option)?;
+        return Ok(dest);
+    }
+
+    /// Save to file, regions are streamed like `save_litematica_writer`.
+    #[cfg(feature = "fs")]
     pub fn save_litematica_file(
         &self,
         filename: &str,
         option: &LitematicaSaveOption,
     ) -> Result<(), Error> {
-        let nbt = match self.to_nbt_litematica(option) {
-            Ok(nbt_) => nbt_,
-            Err(e) => return Err(e),
-        };
-
-        let file = match File::create(filename) {
+        let mut file = match File::create(filename) {
             Ok(f) => f,
             Err(e) => return Err(Error::FileCreateError(e)),
         };
+        let mut writer = std::io::BufWriter::new(&mut file);
+        self.save_litematica_stream(&mut writer, option, Some(filename))?;
+        return writer.flush().map_err(|e| Error::NBTWriteError(e.into()));
+    }
+}
 
-        let mut encoder = GzBuilder::new()
-            .filename(filename)
-            .comment("Generated by mc_schem")
-            .write(file, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
-        }
-        if let Err(e) = encoder.finish() {
-            return Err(Error::NBTWriteError(e.into()));
+/// Distance from the lower corner of a region to its litematica `Position`, which is nonzero on
+/// axes with negative size.
+fn negative_size_shift(negative_size: [bool; 3], shape: [i32; 3]) -> [i32; 3] {
+    let mut shift = [0; 3];
+    for dim in 0..3 {
+        if negative_size[dim] && shape[dim] > 0 {
+            shift[dim] = shape[dim] - 1;
         }
-
-        return Ok(());
     }
+    return shift;
 }
 
 impl Region {
     /// Save region to nbt
     pub fn to_nbt_litematica(&self) -> Result<HashMap<String, Value>, Error> {
+        return self.to_nbt_litematica_with_option(&LitematicaSaveOption::default());
+    }
+
+    /// Save region to nbt with bits per block and verification assigned by `option`. The latest
+    /// litematica version is used if `option.target_version` is `None`.
+    pub fn to_nbt_litematica_with_option(
+        &self,
+        option: &LitematicaSaveOption,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let version = option.target_version.unwrap_or(LITEMATICA_LATEST_VERSION);
+        check_litematica_save_version(version)?;
+        return self.to_nbt_litematica_versioned(option, version);
+    }
+
+    fn to_nbt_litematica_versioned(
+        &self,
+        option: &LitematicaSaveOption,
+        version: i32,
+    ) -> Result<HashMap<String, Value>, Error> {
         let mut nbt = HashMap::new();
+        // re-emit negative sizes, then `Position` is the upper corner on those axes
+        let shift = negative_size_shift(self.negative_size, self.shape());
         //Size
-        nbt.insert(
-            "Size".to_string(),
-            Value::Compound(common::size_to_compound(&self.shape())),
-        );
+        {
+            let mut size = self.shape();
+            for dim in 0..3 {
+                if shift[dim] > 0 {
+                    size[dim] = -size[dim];
+                }
+            }
+            nbt.insert(
+                "Size".to_string(),
+                Value::Compound(common::size_to_compound(&size)),
+            );
+        }
         //Position
-        nbt.insert(
-            "Position".to_string(),
-            Value::Compound(common::size_to_compound(&self.offset)),
-        );
+        {
+            let mut position = self.offset;
+            for dim in 0..3 {
+                position[dim] += shift[dim];
+            }
+            nbt.insert(
+                "Position".to_string(),
+                Value::Compound(common::size_to_compound(&position)),
+            );
+        }
         // BlockStatePalette
+        let new_indices: Vec<usize> = option
+            .palette_order
+            .new_indices(&self.palette.iter().collect::<Vec<&Block>>());
         {
-            let mut palette_vec = Vec::with_capacity(self.palette.len());
-            for blk in &self.palette {
-                palette_vec.push(Value::Compound(blk.to_nbt()));
+            let mut arranged: Vec<&Block> = self.palette.iter().collect();
+            for (old_idx, blk) in self.palette.iter().enumerate() {
+                arranged[new_indices[old_idx]] = blk;
             }
+            let palette_vec: Vec<Value> = arranged
+                .iter()
+                .map(|blk| Value::Compound(blk.to_nbt()))
+                .collect();
             nbt.insert("BlockStatePalette".to_string(), Value::List(palette_vec));
         }
         //Entities
         {
             let mut entities = Vec::with_capacity(self.entities.len());
+            let pos_adder = shift.map(|v| -v as f64);
             for entity in &self.entities {
-                let mut e_nbt = entity.tags.clone();
-                e_nbt.insert(
-                    "Pos".to_string(),
-                    Value::List(common::size_to_list(&entity.position)),
-                );
+                let mut e_nbt = entity.tags_with_passengers(pos_adder);
+                let mut pos = entity.position;
+                for dim in 0..3 {
+                    pos[dim] += pos_adder[dim];
+                }
+                e_nbt.insert("Pos".to_string(), Value::List(common::size_to_list(&pos)));
                 entities.push(Value::Compound(e_nbt));
             }
             nbt.insert("Entities".to_string(), Value::List(entities));
         }
         // BlockStates
         {
-            let mut mbs = MultiBitSet::new();
-            mbs.reset(
-                block_required_bits(self.palette.len()) as u8,
+            let bits = litematica_bits_per_block(
+                self.palette.len(),
                 self.volume() as usize,
-            );
+                option.bits_per_block,
+                &self.name,
+            )?;
+            let mut mbs = MultiBitSet::new();
+            mbs.reset(bits, self.volume() as usize);
             let mut idx = 0usize;
             for y in 0..self.shape()[1] as usize {
                 for z in 0..self.shape()[2] as usize {
                     for x in 0..self.shape()[0] as usize {
-                        let res = mbs.set(idx, self.array_yzx[[y, z, x]] as u64);
-                        assert!(res.is_ok());
+                        let blk_id = self.array_yzx[[y, z, x]];
+                        let new_id = new_indices
+                            .get(blk_id as usize)
+                            .copied()
+                            .unwrap_or(blk_id as usize);
+                        if mbs.set(idx, new_id as u64).is_err() {
+                            return Err(Error::BlockIndexOutOfRangeWriting {
+                                r_pos: [x as i32, y as i32, z as i32],
+                                block_index: blk_id,
+                                max_index: self.palette.len() as u16 - 1,
+                            });
+                        }
                         idx += 1;
                     }
                 }
@@ -975,6 +1694,10 @@ impl Region {
             for u_val in u64_slice {
                 i64_rep.push(i64::from_le_bytes(u_val.to_ne_bytes()));
             }
+
+            if option.verify_block_states {
+                self.verify_block_states_litematica(&i64_rep, bits, &new_indices)?;
+            }
             nbt.insert(
                 "BlockStates".to_string(),
                 Value::LongArray(LongArray::new(i64_rep)),
@@ -996,7 +1719,11 @@ impl Region {
         {
             let mut pft = Vec::with_capacity(self.pending_ticks.len());
             let mut pbt = Vec::with_capacity(self.pending_ticks.len());
-            for (pos, pt_list) in &self.pending_ticks {
+            // sort by position so that the output doesn't depend on hash order
+            let mut positions: Vec<&[i32; 3]> = self.pending_ticks.keys().collect();
+            positions.sort_by_key(|p| Region::pos_xyz_to_yzx(p));
+            for pos in positions {
+                let pt_list = &self.pending_ticks[pos];
                 for pt in pt_list {
                     let nbt = pt.to_nbt(pos);
                     if let PendingTickInfo::Fluid { .. } = pt.info {
@@ -1006,7 +1733,10 @@ impl Region {
                     }
                 }
             }
-            nbt.insert("PendingFluidTicks".to_string(), Value::List(pft));
+            // fluid ticks are separated from block ticks since version 5(minecraft 1.13)
+            if version >= 5 {
+                nbt.insert("PendingFluidTicks".to_string(), Value::List(pft));
+            }
             nbt.insert("PendingBlockTicks".to_string(), Value::List(pbt));
         }
 
@@ -1014,6 +1744,53 @@ impl Region {
     }
 }
 
+impl Region {
+    /// Decode `BlockStates` in the same way as loading, and compare with `array_yzx`
+    fn verify_block_states_litematica(
+        &self,
+        block_states: &[i64],
+        bits: u8,
+        new_indices: &[usize],
+    ) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(block_states.len());
+        for val in block_states {
+            data.push(u64::from_ne_bytes(val.to_le_bytes()));
+        }
+        let volume = self.volume() as usize;
+        let detected = detect_bits_per_block(&data, self.palette.len(), volume);
+        let mbs = MultiBitSet::from_data_vec(data, volume, bits);
+        let mbs = match (mbs, detected) {
+            (Some(mbs), Some(detected)) if detected == bits => mbs,
+            _ => {
+                return Err(Error::InvalidBitsPerBlock {
+                    region_name: self.name.clone(),
+                    bits,
+                    palette_size: self.palette.len(),
+                })
+            }
+        };
+        let mut idx = 0usize;
+        for y in 0..self.shape()[1] as usize {
+            for z in 0..self.shape()[2] as usize {
+                for x in 0..self.shape()[0] as usize {
+                    let expected = new_indices[self.array_yzx[[y, z, x]] as usize] as u16;
+                    let found = mbs.get(idx);
+                    if found != expected as u64 {
+                        return Err(Error::BlockStatesVerifyFailed {
+                            region_name: self.name.clone(),
+                            index: idx,
+                            expected,
+                            found,
+                        });
+                    }
+                    idx += 1;
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
 impl PendingTick {
     /// Save a pending tick to nbt, in litematica format
     pub fn to_nbt(&self, pos: &[i32; 3]) -> HashMap<String, Value> {
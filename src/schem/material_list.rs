@@ -0,0 +1,134 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::schem::Schematic;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Options to make material list
+#[derive(Debug, Clone)]
+pub struct MaterialListOption {
+    /// If true, blocks are distinguished by full block state(with properties), otherwise only by
+    /// namespace and id, like the material list of litematica.
+    pub full_block_state: bool,
+    /// Whether to count air. Structure void is never counted.
+    pub include_air: bool,
+    /// Max count of items in one stack, used when translating counts to item stacks.
+    pub stack_size: u64,
+}
+
+impl Default for MaterialListOption {
+    fn default() -> Self {
+        return MaterialListOption {
+            full_block_state: false,
+            include_air: false,
+            stack_size: 64,
+        };
+    }
+}
+
+/// Count of items split into shulker boxes, stacks and remaining items.
+/// `total = (shulker_boxes * 27 + stacks) * stack_size + items`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ItemStacks {
+    pub total: u64,
+    pub shulker_boxes: u64,
+    pub stacks: u64,
+    pub items: u64,
+}
+
+impl ItemStacks {
+    /// Number of slots in a shulker box
+    pub const SLOTS_OF_SHULKER_BOX: u64 = 27;
+
+    /// Split `total` items into shulker boxes, stacks and items. `stack_size` less than 1 is treated as 1.
+    pub fn from_count(total: u64, stack_size: u64) -> ItemStacks {
+        let stack_size = stack_size.max(1);
+        let full_stacks = total / stack_size;
+        return ItemStacks {
+            total,
+            shulker_boxes: full_stacks / Self::SLOTS_OF_SHULKER_BOX,
+            stacks: full_stacks % Self::SLOTS_OF_SHULKER_BOX,
+            items: total % stack_size,
+        };
+    }
+}
+
+impl Display for ItemStacks {
+    /// Formats like `1 SB + 3 stacks + 5`, zero parts are omitted.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = Vec::with_capacity(3);
+        if self.shulker_boxes > 0 {
+            parts.push(format!("{} SB", self.shulker_boxes));
+        }
+        if self.stacks > 0 {
+            parts.push(format!("{} stacks", self.stacks));
+        }
+        if self.items > 0 || parts.is_empty() {
+            parts.push(format!("{}", self.items));
+        }
+        return write!(f, "{}", parts.join(" + "));
+    }
+}
+
+/// Key of a block in material list
+pub fn material_key(block: &Block, full_block_state: bool) -> String {
+    if full_block_state {
+        return block.full_id();
+    }
+    if block.namespace.is_empty() {
+        return block.id.clone();
+    }
+    return format!("{}:{}", block.namespace, block.id);
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Count blocks of all regions, the key is block id (or full block state, see
+    /// `MaterialListOption::full_block_state`). Overlapping regions are counted separately.
+    pub fn material_list(&self, option: &MaterialListOption) -> BTreeMap<String, u64> {
+        let mut result: BTreeMap<String, u64> = BTreeMap::new();
+        for reg in &self.regions {
+            for (blk, count) in reg.palette.iter().zip(reg.palette_counts()) {
+                if count == 0 || blk.is_structure_void() {
+                    continue;
+                }
+                if !option.include_air && blk.is_air() {
+                    continue;
+                }
+                *result
+                    .entry(material_key(blk, option.full_block_state))
+                    .or_insert(0) += count;
+            }
+        }
+        return result;
+    }
+
+    /// Same as `material_list`, but counts are translated into shulker boxes and stacks.
+    pub fn material_list_in_stacks(
+        &self,
+        option: &MaterialListOption,
+    ) -> BTreeMap<String, ItemStacks> {
+        return self
+            .material_list(option)
+            .into_iter()
+            .map(|(id, count)| (id, ItemStacks::from_count(count, option.stack_size)))
+            .collect();
+    }
+}
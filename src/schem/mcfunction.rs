@@ -0,0 +1,213 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::WorldSlice;
+use crate::schem::{AirPolicy, Schematic, WorldHeight};
+use crate::snbt;
+use fastnbt::Value;
+use std::io::Write;
+
+/// Options to export schematic as mcfunction
+#[derive(Debug, Clone)]
+pub struct McFunctionExportOption {
+    /// Write absolute coordinates with the schematic placed at this position. If `None`,
+    /// coordinates are relative(`~x ~y ~z`) to where the function is executed.
+    pub origin: Option<[i32; 3]>,
+    /// Whether to place air, so that existing blocks in the area are cleared. Omit by default.
+    /// Structure void is never placed.
+    pub air_policy: AirPolicy,
+    /// Merge identical blocks into `fill` cuboids. If false, every block gets a `setblock`.
+    pub use_fill: bool,
+    /// Max volume of a `fill` command, vanilla allows 32768 blocks by default.
+    pub max_fill_volume: u64,
+    /// Whether to summon entities
+    pub include_entities: bool,
+    /// If set together with `origin`, fail with `OutOfWorldHeight` before writing anything if the
+    /// schematic doesn't fit in the build height, instead of writing commands that fail in game.
+    pub world_height: Option<WorldHeight>,
+}
+
+impl Default for McFunctionExportOption {
+    fn default() -> Self {
+        return McFunctionExportOption {
+            origin: None,
+            air_policy: AirPolicy::Omit,
+            use_fill: true,
+            max_fill_volume: 32768,
+            include_entities: true,
+            world_height: None,
+        };
+    }
+}
+
+impl McFunctionExportOption {
+    fn format_pos(&self, g_pos: [f64; 3]) -> String {
+        let fmt = |v: f64| {
+            if v.fract() == 0.0 {
+                format!("{}", v as i64)
+            } else {
+                format!("{v}")
+            }
+        };
+        return match self.origin {
+            Some(origin) => format!(
+                "{} {} {}",
+                fmt(origin[0] as f64 + g_pos[0]),
+                fmt(origin[1] as f64 + g_pos[1]),
+                fmt(origin[2] as f64 + g_pos[2])
+            ),
+            None => {
+                let rel = |v: f64| {
+                    if v == 0.0 {
+                        "~".to_string()
+                    } else {
+                        format!("~{}", fmt(v))
+                    }
+                };
+                format!("{} {} {}", rel(g_pos[0]), rel(g_pos[1]), rel(g_pos[2]))
+            }
+        };
+    }
+
+    fn format_block_pos(&self, g_pos: [i32; 3]) -> String {
+        return self.format_pos([g_pos[0] as f64, g_pos[1] as f64, g_pos[2] as f64]);
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Export blocks and entities as commands of a mcfunction file, one command per line. Blocks
+    /// with block entities are placed with `setblock` after other blocks, carrying their nbt.
+    /// Regions are exported in order, so latter regions overwrite former ones where they overlap.
+    /// Returns the number of commands written.
+    ///
+    /// Blocks are placed without block updates being considered, so blocks that need support(like
+    /// torches and sand) may break or fall if their support is placed later.
+    pub fn export_mcfunction(
+        &self,
+        dest: &mut dyn Write,
+        option: &McFunctionExportOption,
+    ) -> Result<usize, Error> {
+        if let (Some(origin), Some(height)) = (option.origin, &option.world_height) {
+            self.check_world_height(origin[1], height)?;
+        }
+        let mut lines: usize = 0;
+        let mut write_line = |line: String| {
+            lines += 1;
+            return writeln!(dest, "{line}");
+        };
+        let max_volume = option.max_fill_volume.max(1);
+
+        for reg in &self.regions {
+            let skip: Vec<bool> = reg
+                .palette
+                .iter()
+                .map(|blk| {
+                    blk.is_structure_void()
+                        || (option.air_policy == AirPolicy::Omit && blk.is_air())
+                })
+                .collect();
+            let max_volume = if option.use_fill { max_volume } else { 1 };
+            let cuboids = reg.to_cuboids_filtered(max_volume, |pos, idx| {
+                return !reg.block_entities.contains_key(&pos)
+                    && !skip.get(idx as usize).copied().unwrap_or(true);
+            });
+            for cuboid in &cuboids {
+                let block: &Block = &reg.palette[cuboid.block_index as usize];
+                let g_lower = [
+                    cuboid.r_box.lower[0] + reg.offset[0],
+                    cuboid.r_box.lower[1] + reg.offset[1],
+                    cuboid.r_box.lower[2] + reg.offset[2],
+                ];
+                if cuboid.r_box.lower == cuboid.r_box.upper {
+                    write_line(format!(
+                        "setblock {} {}",
+                        option.format_block_pos(g_lower),
+                        block.full_id()
+                    ))
+                    .map_err(|e| Error::NBTWriteError(e.into()))?;
+                } else {
+                    let g_upper = [
+                        cuboid.r_box.upper[0] + reg.offset[0],
+                        cuboid.r_box.upper[1] + reg.offset[1],
+                        cuboid.r_box.upper[2] + reg.offset[2],
+                    ];
+                    write_line(format!(
+                        "fill {} {} {}",
+                        option.format_block_pos(g_lower),
+                        option.format_block_pos(g_upper),
+                        block.full_id()
+                    ))
+                    .map_err(|e| Error::NBTWriteError(e.into()))?;
+                }
+            }
+
+            // block entities, sorted to make output stable
+            let mut be_positions: Vec<&[i32; 3]> = reg.block_entities.keys().collect();
+            be_positions.sort_by_key(|pos| [pos[1], pos[2], pos[0]]);
+            for pos in be_positions {
+                let Some(block) = reg.block_at(*pos) else {
+                    continue;
+                };
+                let mut tags = reg.block_entities[pos].tags.clone();
+                for key in ["id", "x", "y", "z", "keepPacked"] {
+                    tags.remove(key);
+                }
+                let g_pos = [
+                    pos[0] + reg.offset[0],
+                    pos[1] + reg.offset[1],
+                    pos[2] + reg.offset[2],
+                ];
+                write_line(format!(
+                    "setblock {} {}{}",
+                    option.format_block_pos(g_pos),
+                    block.full_id(),
+                    snbt::compound_to_snbt(&tags, false)
+                ))
+                .map_err(|e| Error::NBTWriteError(e.into()))?;
+            }
+
+            if option.include_entities {
+                for entity in &reg.entities {
+                    let Some(Value::String(id)) = entity.tags.get("id") else {
+                        continue;
+                    };
+                    let mut tags = entity.tags_with_passengers(reg.offset.map(|v| v as f64));
+                    for key in ["id", "Pos", "UUID", "UUIDMost", "UUIDLeast"] {
+                        tags.remove(key);
+                    }
+                    let g_pos = [
+                        entity.position[0] + reg.offset[0] as f64,
+                        entity.position[1] + reg.offset[1] as f64,
+                        entity.position[2] + reg.offset[2] as f64,
+                    ];
+                    write_line(format!(
+                        "summon {} {} {}",
+                        id,
+                        option.format_pos(g_pos),
+                        snbt::compound_to_snbt(&tags, false)
+                    ))
+                    .map_err(|e| Error::NBTWriteError(e.into()))?;
+                }
+            }
+        }
+        return Ok(lines);
+    }
+}
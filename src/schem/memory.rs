@@ -0,0 +1,170 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{BlockEntity, Entity, PendingTick, PendingTickInfo, Region};
+use crate::schem::Schematic;
+use fastnbt::Value;
+use ndarray::Array3;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Estimated heap memory used by one region, in bytes. Sizes of containers are counted by
+/// capacity, and allocator overhead is not included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionMemoryReport {
+    /// Name of the region
+    pub name: String,
+    /// Block index storage, like `Region::array_yzx`
+    pub block_array: usize,
+    /// Block palette
+    pub palette: usize,
+    /// Block entities and their nbt
+    pub block_entities: usize,
+    /// Pending ticks
+    pub pending_ticks: usize,
+    /// Entities and their nbt
+    pub entities: usize,
+    /// Biome array and palette
+    pub biomes: usize,
+}
+
+impl RegionMemoryReport {
+    /// Sum of all parts
+    pub fn total(&self) -> usize {
+        return self.block_array
+            + self.palette
+            + self.block_entities
+            + self.pending_ticks
+            + self.entities
+            + self.biomes;
+    }
+}
+
+/// Estimated heap memory used by a schematic, see [`Schematic::memory_usage`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// One report per region, in the same order as `Schematic::regions`
+    pub regions: Vec<RegionMemoryReport>,
+}
+
+impl MemoryReport {
+    /// Sum of all regions
+    pub fn total(&self) -> usize {
+        return self.regions.iter().map(|r| r.total()).sum();
+    }
+}
+
+fn block_bytes(block: &Block) -> usize {
+    let mut bytes = block.namespace.capacity() + block.id.capacity();
+    for (key, value) in &block.attributes {
+        // a btree node is amortized into every entry
+        bytes += size_of::<(String, String)>() + key.capacity() + value.capacity();
+    }
+    return bytes;
+}
+
+pub(crate) fn palette_bytes(palette: &Vec<Block>) -> usize {
+    return palette.capacity() * size_of::<Block>()
+        + palette.iter().map(block_bytes).sum::<usize>();
+}
+
+fn value_bytes(value: &Value) -> usize {
+    return match value {
+        Value::String(s) => s.capacity(),
+        Value::ByteArray(a) => a.len(),
+        Value::IntArray(a) => a.len() * size_of::<i32>(),
+        Value::LongArray(a) => a.len() * size_of::<i64>(),
+        Value::List(list) => {
+            list.capacity() * size_of::<Value>() + list.iter().map(value_bytes).sum::<usize>()
+        }
+        Value::Compound(c) => compound_bytes(c),
+        _ => 0,
+    };
+}
+
+pub(crate) fn compound_bytes(compound: &HashMap<String, Value>) -> usize {
+    let mut bytes = compound.capacity() * (size_of::<(String, Value)>() + 1);
+    for (key, value) in compound {
+        bytes += key.capacity() + value_bytes(value);
+    }
+    return bytes;
+}
+
+pub(crate) fn block_entities_bytes(block_entities: &HashMap<[i32; 3], BlockEntity>) -> usize {
+    let mut bytes = block_entities.capacity() * (size_of::<([i32; 3], BlockEntity)>() + 1);
+    for be in block_entities.values() {
+        bytes += compound_bytes(&be.tags);
+    }
+    return bytes;
+}
+
+pub(crate) fn pending_ticks_bytes(pending_ticks: &HashMap<[i32; 3], Vec<PendingTick>>) -> usize {
+    let mut bytes = pending_ticks.capacity() * (size_of::<([i32; 3], Vec<PendingTick>)>() + 1);
+    for ticks in pending_ticks.values() {
+        bytes += ticks.capacity() * size_of::<PendingTick>();
+        for tick in ticks {
+            bytes += match &tick.info {
+                PendingTickInfo::Fluid { id } | PendingTickInfo::Block { id } => id.capacity(),
+            };
+        }
+    }
+    return bytes;
+}
+
+pub(crate) fn entities_bytes(entities: &Vec<Entity>) -> usize {
+    return entities.capacity() * size_of::<Entity>()
+        + entities
+            .iter()
+            .map(|e| compound_bytes(&e.tags))
+            .sum::<usize>();
+}
+
+pub(crate) fn biomes_bytes(biome_palette: &Vec<String>, biomes: &Option<Array3<u16>>) -> usize {
+    let mut result = biome_palette.capacity() * size_of::<String>();
+    result += biome_palette.iter().map(|b| b.capacity()).sum::<usize>();
+    if let Some(arr) = biomes {
+        result += arr.len() * size_of::<u16>();
+    }
+    return result;
+}
+
+impl Region {
+    /// Estimate heap memory used by this region
+    pub fn memory_usage(&self) -> RegionMemoryReport {
+        return RegionMemoryReport {
+            name: self.name.clone(),
+            block_array: self.array_yzx.len() * size_of::<u16>(),
+            palette: palette_bytes(&self.palette),
+            block_entities: block_entities_bytes(&self.block_entities),
+            pending_ticks: pending_ticks_bytes(&self.pending_ticks),
+            entities: entities_bytes(&self.entities),
+            biomes: biomes_bytes(&self.biome_palette, &self.biomes),
+        };
+    }
+}
+
+impl Schematic {
+    /// Estimate heap memory used by every region. Use it to decide whether large schematics
+    /// should be loaded as [`crate::PackedRegion`] or converted to [`crate::ChunkedRegion`].
+    pub fn memory_usage(&self) -> MemoryReport {
+        return MemoryReport {
+            regions: self.regions.iter().map(|r| r.memory_usage()).collect(),
+        };
+    }
+}
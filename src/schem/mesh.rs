@@ -0,0 +1,466 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{Region, WorldSlice};
+use crate::render::MapColor;
+use crate::schem::Schematic;
+use std::io::Write;
+
+/// File formats of [`Schematic::export_mesh`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    /// Wavefront obj, faces are grouped by `usemtl <block>` without a mtl file
+    Obj,
+    /// glTF 2.0 json with the binary buffer embedded as a data uri, one primitive and material
+    /// per block
+    Gltf,
+}
+
+/// How faces of a mesh are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshColoring {
+    /// Only geometry and material names
+    None,
+    /// Vanilla map color of every block, see [`MapColor::of_block`]. It's written as vertex colors
+    /// in obj and base colors of materials in glTF.
+    MapColor,
+    /// Uv coordinates in a texture atlas, which has `columns` tiles per row. The n-th block in
+    /// [`Schematic::mesh_materials`] takes the n-th tile, counted row by row from the top left.
+    /// Tiles can't repeat inside an atlas, so faces are never merged.
+    AtlasUv { columns: u32 },
+}
+
+/// Options of [`Schematic::export_mesh`]
+#[derive(Debug, Clone)]
+pub struct MeshExportOption {
+    /// Merge adjacent coplanar faces of the same block into larger rectangles
+    pub greedy: bool,
+    pub coloring: MeshColoring,
+}
+
+impl Default for MeshExportOption {
+    fn default() -> Self {
+        return MeshExportOption {
+            greedy: true,
+            coloring: MeshColoring::None,
+        };
+    }
+}
+
+/// A rectangle face of the mesh
+#[derive(Debug, Clone)]
+struct Quad {
+    /// Index in materials
+    material: usize,
+    /// 0..6, `axis * 2 + (positive as usize)`
+    normal: usize,
+    /// Counter-clockwise viewed from outside
+    corners: [[f32; 3]; 4],
+}
+
+const NORMALS: [[f32; 3]; 6] = [
+    [-1.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, -1.0],
+    [0.0, 0.0, 1.0],
+];
+
+/// Whether a block takes no space in the mesh
+fn is_empty_block(block: &Block) -> bool {
+    if block.is_air() || block.is_structure_void() {
+        return true;
+    }
+    return block.namespace == "minecraft" && (block.id == "cave_air" || block.id == "void_air");
+}
+
+/// Collect exposed faces of `region`. Faces touching other regions are treated as exposed.
+fn region_quads(region: &Region, materials: &[usize], greedy: bool, quads: &mut Vec<Quad>) {
+    let shape = region.shape();
+    let material_at = |pos: [i32; 3]| -> Option<usize> {
+        if (0..3).any(|dim| pos[dim] < 0 || pos[dim] >= shape[dim]) {
+            return None;
+        }
+        let idx = region.array_yzx[[pos[1] as usize, pos[2] as usize, pos[0] as usize]];
+        return materials
+            .get(idx as usize)
+            .copied()
+            .filter(|m| *m != usize::MAX);
+    };
+
+    for axis in 0..3 {
+        // u and v are the other 2 axes, so that u x v = axis
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (size_u, size_v) = (shape[u_axis] as usize, shape[v_axis] as usize);
+        for positive in [false, true] {
+            let step = if positive { 1 } else { -1 };
+            let mut mask: Vec<Option<usize>> = vec![None; size_u * size_v];
+            for layer in 0..shape[axis] {
+                for v in 0..size_v {
+                    for u in 0..size_u {
+                        let mut pos = [0; 3];
+                        pos[axis] = layer;
+                        pos[u_axis] = u as i32;
+                        pos[v_axis] = v as i32;
+                        let mut neighbor = pos;
+                        neighbor[axis] += step;
+                        mask[v * size_u + u] = match material_at(pos) {
+                            Some(m) if material_at(neighbor).is_none() => Some(m),
+                            _ => None,
+                        };
+                    }
+                }
+
+                let plane = (layer + positive as i32) as f32;
+                for v0 in 0..size_v {
+                    let mut u0 = 0;
+                    while u0 < size_u {
+                        let Some(material) = mask[v0 * size_u + u0] else {
+                            u0 += 1;
+                            continue;
+                        };
+                        let mut u1 = u0 + 1;
+                        let mut v1 = v0 + 1;
+                        if greedy {
+                            while u1 < size_u && mask[v0 * size_u + u1] == Some(material) {
+                                u1 += 1;
+                            }
+                            while v1 < size_v
+                                && (u0..u1).all(|u| mask[v1 * size_u + u] == Some(material))
+                            {
+                                v1 += 1;
+                            }
+                        }
+                        for v in v0..v1 {
+                            mask[v * size_u + u0..v * size_u + u1].fill(None);
+                        }
+
+                        let corner = |u: usize, v: usize| -> [f32; 3] {
+                            let mut c = [0.0; 3];
+                            c[axis] = plane;
+                            c[u_axis] = u as f32;
+                            c[v_axis] = v as f32;
+                            return std::array::from_fn(|dim| c[dim] + region.offset[dim] as f32);
+                        };
+                        let mut corners = [
+                            corner(u0, v0),
+                            corner(u1, v0),
+                            corner(u1, v1),
+                            corner(u0, v1),
+                        ];
+                        if !positive {
+                            corners.reverse();
+                        }
+                        quads.push(Quad {
+                            material,
+                            normal: axis * 2 + positive as usize,
+                            corners,
+                        });
+                        u0 = u1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Uv of the 4 corners of a quad in the atlas tile of `material`, with origin at top left
+fn atlas_uv(material: usize, columns: u32, rows: u32) -> [[f32; 2]; 4] {
+    let columns = columns.max(1);
+    let col = (material as u32 % columns) as f32;
+    let row = (material as u32 / columns) as f32;
+    let (w, h) = (1.0 / columns as f32, 1.0 / rows.max(1) as f32);
+    let (u0, v0) = (col * w, row * h);
+    return [[u0, v0 + h], [u0 + w, v0 + h], [u0 + w, v0], [u0, v0]];
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    return result;
+}
+
+fn json_escape(s: &str) -> String {
+    return s.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// All blocks that appear in the mesh, in order of their first appearance. The index of a
+    /// block here is its material index and atlas tile in [`Schematic::export_mesh`].
+    pub fn mesh_materials(&self) -> Vec<Block> {
+        let mut materials: Vec<Block> = Vec::new();
+        for reg in &self.regions {
+            let mut used = vec![false; reg.palette.len()];
+            for idx in reg.array_yzx.iter() {
+                if let Some(flag) = used.get_mut(*idx as usize) {
+                    *flag = true;
+                }
+            }
+            for (blk, used) in reg.palette.iter().zip(used) {
+                if used && !is_empty_block(blk) && !materials.contains(blk) {
+                    materials.push(blk.clone());
+                }
+            }
+        }
+        return materials;
+    }
+
+    /// Export a blocky mesh of all regions for 3D softwares like blender. Every non-air block is
+    /// a unit cube in global coordinates, and only faces not covered by other blocks in the
+    /// same region are exported. Returns the number of rectangle faces written.
+    pub fn export_mesh(
+        &self,
+        dest: &mut dyn Write,
+        format: MeshFormat,
+        option: &MeshExportOption,
+    ) -> Result<usize, Error> {
+        let materials = self.mesh_materials();
+        let greedy = option.greedy && !matches!(option.coloring, MeshColoring::AtlasUv { .. });
+        let mut quads: Vec<Quad> = Vec::new();
+        for reg in &self.regions {
+            let lut: Vec<usize> = reg
+                .palette
+                .iter()
+                .map(|blk| {
+                    materials
+                        .iter()
+                        .position(|m| m == blk)
+                        .unwrap_or(usize::MAX)
+                })
+                .collect();
+            region_quads(reg, &lut, greedy, &mut quads);
+        }
+        quads.sort_by_key(|q| q.material);
+
+        let result = match format {
+            MeshFormat::Obj => write_obj(dest, &materials, &quads, option.coloring),
+            MeshFormat::Gltf => write_gltf(dest, &materials, &quads, option.coloring),
+        };
+        result.map_err(|e| Error::NBTWriteError(e.into()))?;
+        return Ok(quads.len());
+    }
+}
+
+fn atlas_rows(materials: &[Block], coloring: MeshColoring) -> Option<(u32, u32)> {
+    if let MeshColoring::AtlasUv { columns } = coloring {
+        let columns = columns.max(1);
+        return Some((columns, (materials.len() as u32).div_ceil(columns)));
+    }
+    return None;
+}
+
+fn write_obj(
+    dest: &mut dyn Write,
+    materials: &[Block],
+    quads: &[Quad],
+    coloring: MeshColoring,
+) -> std::io::Result<()> {
+    writeln!(dest, "# exported by mc_schem")?;
+    for n in NORMALS {
+        writeln!(dest, "vn {} {} {}", n[0], n[1], n[2])?;
+    }
+    let atlas = atlas_rows(materials, coloring);
+    let mut current_material = usize::MAX;
+    for (idx, quad) in quads.iter().enumerate() {
+        if quad.material != current_material {
+            current_material = quad.material;
+            writeln!(dest, "usemtl {}", materials[quad.material])?;
+        }
+        let color = if coloring == MeshColoring::MapColor {
+            let rgb = MapColor::of_block(&materials[quad.material]).rgb();
+            format!(
+                " {} {} {}",
+                rgb[0] as f32 / 255.0,
+                rgb[1] as f32 / 255.0,
+                rgb[2] as f32 / 255.0
+            )
+        } else {
+            String::new()
+        };
+        for c in quad.corners {
+            writeln!(dest, "v {} {} {}{color}", c[0], c[1], c[2])?;
+        }
+        // obj indices start from 1
+        let first = idx * 4 + 1;
+        let normal = quad.normal + 1;
+        if let Some((columns, rows)) = atlas {
+            // obj puts the origin of uv at bottom left
+            for [u, v] in atlas_uv(quad.material, columns, rows) {
+                writeln!(dest, "vt {} {}", u, 1.0 - v)?;
+            }
+            writeln!(
+                dest,
+                "f {0}/{0}/{4} {1}/{1}/{4} {2}/{2}/{4} {3}/{3}/{4}",
+                first,
+                first + 1,
+                first + 2,
+                first + 3,
+                normal
+            )?;
+        } else {
+            writeln!(
+                dest,
+                "f {0}//{4} {1}//{4} {2}//{4} {3}//{4}",
+                first,
+                first + 1,
+                first + 2,
+                first + 3,
+                normal
+            )?;
+        }
+    }
+    return Ok(());
+}
+
+fn write_gltf(
+    dest: &mut dyn Write,
+    materials: &[Block],
+    quads: &[Quad],
+    coloring: MeshColoring,
+) -> std::io::Result<()> {
+    let atlas = atlas_rows(materials, coloring);
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut buffer_views: Vec<String> = Vec::new();
+    let mut accessors: Vec<String> = Vec::new();
+    let mut primitives: Vec<String> = Vec::new();
+    let mut add_view = |buffer: &mut Vec<u8>, data: Vec<u8>, target: u32| -> usize {
+        buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{target}}}"#,
+            buffer.len(),
+            data.len()
+        ));
+        buffer.extend_from_slice(&data);
+        return buffer_views.len() - 1;
+    };
+
+    // quads are sorted by material, `slice::chunk_by` requires rust 1.77
+    let mut begin = 0;
+    while begin < quads.len() {
+        let material = quads[begin].material;
+        let end = quads[begin..]
+            .iter()
+            .position(|q| q.material != material)
+            .map_or(quads.len(), |len| begin + len);
+        let group = &quads[begin..end];
+        begin = end;
+        let vertex_count = group.len() * 4;
+        let mut positions: Vec<u8> = Vec::with_capacity(vertex_count * 12);
+        let mut normals: Vec<u8> = Vec::with_capacity(vertex_count * 12);
+        let mut uvs: Vec<u8> = Vec::new();
+        let mut indices: Vec<u8> = Vec::with_capacity(group.len() * 24);
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for (idx, quad) in group.iter().enumerate() {
+            for c in quad.corners {
+                for dim in 0..3 {
+                    min[dim] = min[dim].min(c[dim]);
+                    max[dim] = max[dim].max(c[dim]);
+                    positions.extend_from_slice(&c[dim].to_le_bytes());
+                    normals.extend_from_slice(&NORMALS[quad.normal][dim].to_le_bytes());
+                }
+            }
+            if let Some((columns, rows)) = atlas {
+                for uv in atlas_uv(quad.material, columns, rows) {
+                    uvs.extend_from_slice(&uv[0].to_le_bytes());
+                    uvs.extend_from_slice(&uv[1].to_le_bytes());
+                }
+            }
+            let first = (idx * 4) as u32;
+            for i in [0, 1, 2, 0, 2, 3] {
+                indices.extend_from_slice(&(first + i).to_le_bytes());
+            }
+        }
+
+        // 34962: ARRAY_BUFFER, 34963: ELEMENT_ARRAY_BUFFER, 5126: FLOAT, 5125: UNSIGNED_INT
+        let view = add_view(&mut buffer, positions, 34962);
+        accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":5126,"count":{vertex_count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+            min[0], min[1], min[2], max[0], max[1], max[2]
+        ));
+        let position_accessor = accessors.len() - 1;
+        let view = add_view(&mut buffer, normals, 34962);
+        accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":5126,"count":{vertex_count},"type":"VEC3"}}"#
+        ));
+        let normal_accessor = accessors.len() - 1;
+        let mut attributes =
+            format!(r#""POSITION":{position_accessor},"NORMAL":{normal_accessor}"#);
+        if !uvs.is_empty() {
+            let view = add_view(&mut buffer, uvs, 34962);
+            accessors.push(format!(
+                r#"{{"bufferView":{view},"componentType":5126,"count":{vertex_count},"type":"VEC2"}}"#
+            ));
+            attributes.push_str(&format!(r#","TEXCOORD_0":{}"#, accessors.len() - 1));
+        }
+        let view = add_view(&mut buffer, indices, 34963);
+        accessors.push(format!(
+            r#"{{"bufferView":{view},"componentType":5125,"count":{},"type":"SCALAR"}}"#,
+            group.len() * 6
+        ));
+        primitives.push(format!(
+            r#"{{"attributes":{{{attributes}}},"indices":{},"material":{material}}}"#,
+            accessors.len() - 1
+        ));
+    }
+
+    let gltf_materials: Vec<String> = materials
+        .iter()
+        .map(|blk| {
+            let name = json_escape(&blk.to_string());
+            if coloring == MeshColoring::MapColor {
+                let rgb = MapColor::of_block(blk).rgb().map(|c| c as f32 / 255.0);
+                return format!(
+                    r#"{{"name":"{name}","pbrMetallicRoughness":{{"baseColorFactor":[{},{},{},1],"metallicFactor":0}}}}"#,
+                    rgb[0], rgb[1], rgb[2]
+                );
+            }
+            return format!(r#"{{"name":"{name}"}}"#);
+        })
+        .collect();
+
+    write!(
+        dest,
+        r#"{{"asset":{{"version":"2.0","generator":"mc_schem"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{}]}}],"materials":[{}],"accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{},"uri":"data:application/octet-stream;base64,{}"}}]}}"#,
+        primitives.join(","),
+        gltf_materials.join(","),
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len(),
+        base64_encode(&buffer)
+    )?;
+    return Ok(());
+}
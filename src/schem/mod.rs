@@ -20,28 +20,63 @@ pub mod litematica;
 pub mod world_edit12;
 pub mod world_edit13;
 
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod axiom;
+pub mod blit;
+pub mod bo3;
+pub mod builder;
+pub mod chunked;
+pub mod clipboard;
 pub mod common;
+pub mod convert;
+pub mod cuboids;
+pub mod diff;
+pub mod entity_uuid;
+pub mod layer;
+pub mod material_list;
 pub mod mc_version;
+pub mod mcfunction;
+pub mod memory;
+pub mod mesh;
+pub mod order;
+pub mod overlap;
+pub mod packed;
+pub mod parallel;
+pub mod pattern;
+pub mod progress;
+pub mod scale;
 pub mod schem_slice;
+pub mod search;
+pub mod split;
+pub mod stats;
+pub mod validate;
 pub mod vanilla_structure;
+pub mod view;
 
 use crate::block::{Block, CommonBlock};
-use crate::error::Error;
+use crate::datafix::DowngradeCallback;
+use crate::error::{Error, ErrorHandlerFn};
 use fastnbt;
 use flate2::Compression;
+use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use strum::FromRepr;
 //use schem::mc_version;
-use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::region::{BlockBox, BlockEntity, Region, WorldSlice};
+use crate::schem::progress::ProgressCallback;
 use crate::{schem, PendingTick};
 
 /// Minecraft data versions.
 pub type DataVersion = mc_version::DataVersion;
 
 /// Metadata of litematica
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LitematicaMetaData {
     pub data_version: i32,
 
@@ -56,6 +91,64 @@ pub struct LitematicaMetaData {
     pub region_count: i32,
     pub total_blocks: i32,
     pub enclosing_size: [i32; 3],
+    /// Thumbnail stored as `PreviewImageData`, `None` if the file has no preview
+    pub preview: Option<PreviewImage>,
+}
+
+/// Preview image of litematica, stored as an ARGB int array. Litematica only reads square images.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewImage {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels in ARGB, row by row from the top left corner
+    pub argb: Vec<u32>,
+}
+
+impl PreviewImage {
+    /// Create from a RGBA image buffer. Returns `None` if the length of `rgba` is not
+    /// `4 * width * height`.
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Option<PreviewImage> {
+        if rgba.len() != 4 * width as usize * height as usize {
+            return None;
+        }
+        let argb = rgba
+            .chunks_exact(4)
+            .map(|px| u32::from_be_bytes([px[3], px[0], px[1], px[2]]))
+            .collect();
+        return Some(PreviewImage {
+            width,
+            height,
+            argb,
+        });
+    }
+
+    /// Convert to a RGBA image buffer, 4 bytes per pixel
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.argb.len() * 4);
+        for px in &self.argb {
+            let [a, r, g, b] = px.to_be_bytes();
+            result.extend_from_slice(&[r, g, b, a]);
+        }
+        return result;
+    }
+
+    /// Decode `PreviewImageData` of litematica. Returns `None` if the image is not square.
+    pub fn from_argb_ints(data: &[i32]) -> Option<PreviewImage> {
+        let size = (data.len() as f64).sqrt().round() as u32;
+        if (size as usize) * (size as usize) != data.len() {
+            return None;
+        }
+        return Some(PreviewImage {
+            width: size,
+            height: size,
+            argb: data.iter().map(|px| *px as u32).collect(),
+        });
+    }
+
+    /// Encode as `PreviewImageData` of litematica
+    pub fn to_argb_ints(&self) -> Vec<i32> {
+        return self.argb.iter().map(|px| *px as i32).collect();
+    }
 }
 
 #[allow(dead_code)]
@@ -92,16 +185,12 @@ impl LitematicaMetaData {
 
     /// Get default metadata from data version in `i32`
     pub fn from_data_version_i32(data_version: i32) -> Result<LitematicaMetaData, Error> {
-        use std::time::{SystemTime, UNIX_EPOCH};
         if data_version < DataVersion::Java_1_12 as i32 {
             return Err(Error::UnsupportedVersion {
                 data_version_i32: data_version,
             });
         }
-        let time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
+        let time = common::current_ms_timestamp();
         let result = LitematicaMetaData {
             data_version,
             version: Self::data_version_to_lite_version(data_version).unwrap_or(-1),
@@ -115,6 +204,7 @@ impl LitematicaMetaData {
             region_count: 0,
             total_blocks: 0,
             enclosing_size: [0; 3],
+            preview: None,
         };
         return Ok(result);
     }
@@ -126,7 +216,7 @@ impl LitematicaMetaData {
 }
 
 /// Metadata of World Edit 1.12-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct WE12MetaData {
     pub materials: String,
@@ -137,6 +227,10 @@ pub struct WE12MetaData {
     pub length: i16,
 }
 
+/// Schematic, raw metadata and the number ids `(id, damage)` of every block loaded from a World
+/// Edit 1.12- schematic
+pub type WE12LoadResult = Result<(Schematic, WE12MetaData, ndarray::Array3<(u8, u8)>), Error>;
+
 #[allow(dead_code)]
 impl WE12MetaData {
     pub fn default() -> WE12MetaData {
@@ -152,7 +246,7 @@ impl WE12MetaData {
 }
 
 /// Metadata of World Edit 1.13+
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct WE13MetaData {
     pub data_version: i32,
@@ -162,13 +256,19 @@ pub struct WE13MetaData {
     //time stamp in milliseconds
     pub date: Option<i64>,
     pub v3_extra: Option<WE13MetaDataV3Extra>,
+    /// `Name` in `Metadata`
+    pub name: Option<String>,
+    /// `Author` in `Metadata`
+    pub author: Option<String>,
+    /// `Description` in `Metadata`, not a standard tag and only written by mc_schem
+    pub description: Option<String>,
     pub width: i16,
     pub height: i16,
     pub length: i16,
 }
 
 /// Extra metadata of World Edit 1.13+, introduced in 1.20, version 3.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct WE13MetaDataV3Extra {
     pub world_edit_version: String,
@@ -178,11 +278,7 @@ pub struct WE13MetaDataV3Extra {
 
 impl Default for WE13MetaData {
     fn default() -> WE13MetaData {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
+        let time = common::current_ms_timestamp();
         return WE13MetaData {
             data_version: DataVersion::new() as i32,
             version: 5,
@@ -190,6 +286,9 @@ impl Default for WE13MetaData {
             offset: [0, 0, 0],
             date: Some(time),
             v3_extra: None,
+            name: None,
+            author: None,
+            description: None,
             width: 0,
             height: 0,
             length: 0,
@@ -237,10 +336,16 @@ impl WE13MetaData {
 }
 
 /// Metadata of vanilla structure
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct VanillaStructureMetaData {
     pub data_version: i32,
+    /// `author` tag, written by structure blocks before 1.13
+    pub author: Option<String>,
+    /// `Name` in `Metadata`, not a standard tag and only written by mc_schem
+    pub name: Option<String>,
+    /// `Description` in `Metadata`, not a standard tag and only written by mc_schem
+    pub description: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -248,6 +353,9 @@ impl VanillaStructureMetaData {
     pub fn default() -> VanillaStructureMetaData {
         return VanillaStructureMetaData {
             data_version: DataVersion::new() as i32,
+            author: None,
+            name: None,
+            description: None,
         };
     }
 
@@ -256,21 +364,61 @@ impl VanillaStructureMetaData {
     }
 
     pub fn from_data_version_i32(dv: i32) -> Result<VanillaStructureMetaData, Error> {
-        return Ok(VanillaStructureMetaData { data_version: dv });
+        return Ok(VanillaStructureMetaData {
+            data_version: dv,
+            author: None,
+            name: None,
+            description: None,
+        });
+    }
+}
+
+/// Metadata of axiom blueprint, stored in the header before block data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AxiomMetaData {
+    pub data_version: i32,
+    pub name: String,
+    pub author: String,
+    pub tags: Vec<String>,
+    /// Camera angle of the thumbnail, in degrees
+    pub thumbnail_yaw: f32,
+    pub thumbnail_pitch: f32,
+    pub locked_thumbnail: bool,
+    pub block_count: i32,
+    pub contains_air: bool,
+    /// Png image shown in the blueprint browser, empty if there is no thumbnail
+    pub thumbnail: Vec<u8>,
+}
+
+impl Default for AxiomMetaData {
+    fn default() -> AxiomMetaData {
+        return AxiomMetaData {
+            data_version: DataVersion::new() as i32,
+            name: String::new(),
+            author: String::new(),
+            tags: Vec::new(),
+            thumbnail_yaw: 135.0,
+            thumbnail_pitch: 30.0,
+            locked_thumbnail: false,
+            block_count: 0,
+            contains_air: false,
+            thumbnail: Vec::new(),
+        };
     }
 }
 
 /// Raw metadata of different formats
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum RawMetaData {
     Litematica(LitematicaMetaData),
     WE12(WE12MetaData),
     WE13(WE13MetaData),
     VanillaStructure(VanillaStructureMetaData),
+    Axiom(AxiomMetaData),
 }
 
 /// Intermediate representation via different metadata formats
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct MetaDataIR {
     /// Data version of minecraft
@@ -286,6 +434,7 @@ pub struct MetaDataIR {
 
     pub litematica_version: i32,
     pub litematica_subversion: Option<i32>,
+    pub litematica_preview: Option<PreviewImage>,
 
     pub schem_version: i32,
     pub schem_offset: [i32; 3],
@@ -297,6 +446,10 @@ pub struct MetaDataIR {
     pub schem_origin: Option<[i32; 3]>,
     /// `Alpha` or `Classic`
     pub schem_material: String,
+
+    pub axiom_tags: Vec<String>,
+    /// Png thumbnail of axiom blueprint
+    pub axiom_thumbnail: Option<Vec<u8>>,
     //pub raw_metadata: Option<MetaData>,
 }
 
@@ -311,11 +464,7 @@ impl MetaDataIR {
     }
 
     pub fn from_data_version_i32(version: i32) -> Result<MetaDataIR, Error> {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
+        let time = common::current_ms_timestamp();
 
         let result = MetaDataIR {
             mc_data_version: version,
@@ -326,6 +475,7 @@ impl MetaDataIR {
             description: String::from("Default metadata generated by mc_schem"),
             litematica_version: LitematicaMetaData::default().version,
             litematica_subversion: LitematicaMetaData::default().sub_version,
+            litematica_preview: None,
             schem_version: WE13MetaData::default().version,
             schem_offset: [0, 0, 0],
             schem_we_offset: None,
@@ -334,6 +484,8 @@ impl MetaDataIR {
             schem_editing_platform: None,
             schem_origin: Some([0, 0, 0]),
             schem_material: "Alpha".to_string(),
+            axiom_tags: Vec::new(),
+            axiom_thumbnail: None,
         };
         return Ok(result);
     }
@@ -341,7 +493,7 @@ impl MetaDataIR {
 
 /// Schematic is part of a Minecraft world, like `.litematic` of litematica mod, `.schem` and
 /// `.schematic` of world edit, `.nbt` of vanilla structure.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Schematic {
     pub metadata: MetaDataIR,
     /// A list of regions. A schematic can have multiple regions.
@@ -366,6 +518,83 @@ impl Schematic {
         };
     }
 
+    /// Set author in metadata
+    pub fn set_author(&mut self, author: &str) {
+        self.metadata.author = author.to_string();
+    }
+
+    /// Set name in metadata
+    pub fn set_name(&mut self, name: &str) {
+        self.metadata.name = name.to_string();
+    }
+
+    /// Set description in metadata
+    pub fn set_description(&mut self, description: &str) {
+        self.metadata.description = description.to_string();
+    }
+
+    /// Set modified time in metadata to now. Created time is also set if it's absent (non-positive).
+    pub fn touch_modified_time(&mut self) {
+        let now = common::current_ms_timestamp();
+        self.metadata.time_modified = now;
+        if self.metadata.time_created <= 0 {
+            self.metadata.time_created = now;
+        }
+    }
+
+    /// Copy fields of metadata into `raw` if the raw format has them, so that the raw metadata
+    /// returned by loading functions reflects changes made by setters like
+    /// [`Schematic::set_author`]. Formats without a field are left as they are.
+    pub fn sync_raw_metadata(&self, raw: &mut RawMetaData) {
+        let md = &self.metadata;
+        match raw {
+            RawMetaData::Litematica(lite) => {
+                lite.data_version = md.mc_data_version;
+                lite.author = md.author.clone();
+                lite.name = md.name.clone();
+                lite.description = md.description.clone();
+                if md.time_created > 0 {
+                    lite.time_created = md.time_created;
+                }
+                if md.time_modified > 0 {
+                    lite.time_modified = md.time_modified;
+                }
+                lite.preview = md.litematica_preview.clone();
+            }
+            RawMetaData::WE13(we13) => {
+                we13.data_version = md.mc_data_version;
+                we13.name = Some(md.name.clone());
+                we13.author = Some(md.author.clone());
+                if we13.description.is_some() {
+                    we13.description = Some(md.description.clone());
+                }
+                if md.time_modified > 0 {
+                    we13.date = Some(md.time_modified);
+                }
+            }
+            RawMetaData::VanillaStructure(vs) => {
+                vs.data_version = md.mc_data_version;
+                vs.author = Some(md.author.clone());
+                if vs.name.is_some() {
+                    vs.name = Some(md.name.clone());
+                }
+                if vs.description.is_some() {
+                    vs.description = Some(md.description.clone());
+                }
+            }
+            RawMetaData::Axiom(axiom) => {
+                axiom.data_version = md.mc_data_version;
+                axiom.name = md.name.clone();
+                axiom.author = md.author.clone();
+                axiom.tags = md.axiom_tags.clone();
+                if let Some(thumbnail) = &md.axiom_thumbnail {
+                    axiom.thumbnail = thumbnail.clone();
+                }
+            }
+            RawMetaData::WE12(_) => {}
+        }
+    }
+
     // pub fn metadata(&self) -> &MetaDataIR {
     //     return &self.metadata;
     // }
@@ -491,7 +720,41 @@ impl Schematic {
         return None;
     }
 
-    /// The enclosing shape(xyz) of schematic
+    /// Box enclosing all regions in global coordinates, which may be negative if a region has a
+    /// negative offset. `None` if there is no region, or all regions are empty.
+    pub fn bounding_box(&self) -> Option<BlockBox> {
+        let mut result: Option<BlockBox> = None;
+        for reg in &self.regions {
+            if reg.shape().contains(&0) {
+                continue;
+            }
+            let b = reg.global_box();
+            result = Some(match result {
+                None => b,
+                Some(r) => BlockBox::new(
+                    std::array::from_fn(|dim| r.lower[dim].min(b.lower[dim])),
+                    std::array::from_fn(|dim| r.upper[dim].max(b.upper[dim])),
+                ),
+            });
+        }
+        return result;
+    }
+
+    /// Returns `OutOfWorldHeight` if the schematic doesn't fit in `height` when placed with its
+    /// origin at y=`origin_y`. Regions below the origin, like with a negative offset, are
+    /// counted too.
+    pub fn check_world_height(&self, origin_y: i32, height: &WorldHeight) -> Result<(), Error> {
+        let Some(b) = self.bounding_box() else {
+            return Ok(());
+        };
+        return height.check_y_range(
+            origin_y.saturating_add(b.lower[1]),
+            origin_y.saturating_add(b.upper[1]),
+        );
+    }
+
+    /// The enclosing shape(xyz) of schematic, from the origin to the upper corner of all regions.
+    /// Parts of regions at negative coordinates are not counted, see [`Schematic::bounding_box`].
     pub fn shape(&self) -> [i32; 3] {
         let mut result = [0, 0, 0];
         for reg in &self.regions {
@@ -520,6 +783,33 @@ impl Schematic {
         return counter;
     }
 
+    /// Like `full_palette`, but `extra` is appended if it's not in palette, and the palette is
+    /// arranged in `order`.
+    pub(crate) fn full_palette_ordered<'a>(
+        &'a self,
+        order: PaletteOrder,
+        extra: Option<&'a Block>,
+    ) -> (Vec<(&'a Block, u64)>, Vec<Vec<usize>>) {
+        let (mut palette, mut lut_lut) = self.full_palette();
+        if let Some(extra) = extra {
+            if !palette.iter().any(|(blk, _)| *blk == extra) {
+                palette.push((extra, 0));
+            }
+        }
+        let blocks: Vec<&Block> = palette.iter().map(|(blk, _)| *blk).collect();
+        let new_indices = order.new_indices(&blocks);
+        let mut arranged = palette.clone();
+        for (old_idx, entry) in palette.into_iter().enumerate() {
+            arranged[new_indices[old_idx]] = entry;
+        }
+        for lut in &mut lut_lut {
+            for idx in lut.iter_mut() {
+                *idx = new_indices[*idx];
+            }
+        }
+        return (arranged, lut_lut);
+    }
+
     /// Returns `(Vec<(block, hash)>, Vec<LUT-cur-block-index-to-global-block-index>)`, this will
     /// be useful when merging multiple regions
     pub fn full_palette(&self) -> (Vec<(&Block, u64)>, Vec<Vec<usize>>) {
@@ -534,30 +824,25 @@ impl Schematic {
 
         let mut palette: Vec<(&Block, u64)> = Vec::with_capacity(possible_max_palette_size);
         let mut lut_lut: Vec<Vec<usize>> = Vec::with_capacity(self.regions.len());
+        // hash -> indices in full palette, so that each block is hashed only once
+        let mut indices_of_hash: HashMap<u64, Vec<usize>> =
+            HashMap::with_capacity(possible_max_palette_size);
         for reg in &self.regions {
             let mut lut: Vec<usize> = Vec::with_capacity(reg.palette.len());
-
             for cur_blk in &reg.palette {
                 let mut hasher = DefaultHasher::new();
                 cur_blk.hash(&mut hasher);
-                let cur_hash = hasher.finish();
-
-                let mut cur_block_index_in_full_palette = palette.len();
-                for (idx, (blk, hash)) in palette.iter().enumerate() {
-                    if *hash != cur_hash {
-                        continue;
+                let hash = hasher.finish();
+                let candidates = indices_of_hash.entry(hash).or_default();
+                let idx = match candidates.iter().find(|idx| palette[**idx].0 == cur_blk) {
+                    Some(idx) => *idx,
+                    None => {
+                        palette.push((cur_blk, hash));
+                        candidates.push(palette.len() - 1);
+                        palette.len() - 1
                     }
-                    if *blk != cur_blk {
-                        continue;
-                    }
-                    cur_block_index_in_full_palette = idx;
-                    break;
-                }
-
-                if cur_block_index_in_full_palette >= palette.len() {
-                    palette.push((cur_blk, cur_hash));
-                }
-                lut.push(cur_block_index_in_full_palette);
+                };
+                lut.push(idx);
             }
             lut_lut.push(lut);
         }
@@ -565,6 +850,7 @@ impl Schematic {
     }
 
     /// Load schematic from file.
+    #[cfg(feature = "fs")]
     pub fn from_file(filename: &str) -> Result<(Schematic, RawMetaData), Error> {
         if filename.ends_with(".litematic") {
             let (schem, raw) =
@@ -588,6 +874,10 @@ impl Schematic {
                 Self::from_world_edit_12_file(filename, &WorldEdit12LoadOption::default())?;
             return Ok((schem, RawMetaData::WE12(raw)));
         }
+        if filename.ends_with(".bp") {
+            let (schem, raw) = Self::from_axiom_file(filename)?;
+            return Ok((schem, RawMetaData::Axiom(raw)));
+        }
 
         let split = filename.split(".");
         let extension = split.last().unwrap_or("");
@@ -598,6 +888,7 @@ impl Schematic {
     }
 
     /// Save schematic to file.
+    #[cfg(feature = "fs")]
     pub fn save_to_file(&self, filename: &str) -> Result<(), Error> {
         if filename.ends_with(".litematic") {
             return self.save_litematica_file(filename, &LitematicaSaveOption::default());
@@ -609,6 +900,15 @@ impl Schematic {
         if filename.ends_with(".schem") {
             return self.save_world_edit_13_file(filename, &WorldEdit13SaveOption::default());
         }
+        if filename.ends_with(".schematic") {
+            return self.save_world_edit_12_file::<crate::error::DefaultErrorHandler>(
+                filename,
+                &WorldEdit12SaveOption::default(),
+            );
+        }
+        if filename.ends_with(".bp") {
+            return self.save_axiom_file(filename, &AxiomSaveOption::default());
+        }
 
         let split = filename.split(".");
         let extension = split.last().unwrap_or("");
@@ -734,6 +1034,117 @@ impl Schematic {
         let new_reg = self.to_single_region(background_block);
         self.regions = vec![new_reg];
     }
+
+    /// Merge regions which are adjacent, have same kinds of blocks and together form a cuboid, until
+    /// no such pair is left. Some exporters write one region per chunk, merging them makes files
+    /// smaller and faster to load in litematica. Returns the number of merges.
+    pub fn merge_adjacent_regions(&mut self) -> usize {
+        let mut merged = 0;
+        loop {
+            // merging along one axis may make regions mergeable along another
+            let merged_this_round: usize = (0..3)
+                .map(|axis| self.merge_adjacent_regions_along(axis))
+                .sum();
+            if merged_this_round == 0 {
+                break;
+            }
+            merged += merged_this_round;
+        }
+        return merged;
+    }
+
+    /// Merge every region with the regions that follow it on `axis` in one pass. Regions are
+    /// looked up by their lower corner and cross-section, so this is linear in region count.
+    fn merge_adjacent_regions_along(&mut self, axis: usize) -> usize {
+        let cross_section = |reg: &Region| {
+            let mut shape = reg.shape();
+            shape[axis] = 0;
+            return shape;
+        };
+        // (lower corner, cross-section) -> index of region
+        let mut region_at: HashMap<([i32; 3], [i32; 3]), usize> =
+            HashMap::with_capacity(self.regions.len());
+        for (idx, reg) in self.regions.iter().enumerate() {
+            region_at
+                .entry((reg.offset, cross_section(reg)))
+                .or_insert(idx);
+        }
+        // lower regions absorb the whole chain above them
+        let mut order: Vec<usize> = (0..self.regions.len()).collect();
+        order.sort_by_key(|idx| self.regions[*idx].offset[axis]);
+
+        let mut removed = vec![false; self.regions.len()];
+        let mut merged = 0;
+        for idx in order {
+            if removed[idx] {
+                continue;
+            }
+            loop {
+                let reg = &self.regions[idx];
+                let mut upper_corner = reg.offset;
+                upper_corner[axis] += reg.shape()[axis];
+                let Some(&upper) = region_at.get(&(upper_corner, cross_section(reg))) else {
+                    break;
+                };
+                if upper == idx || removed[upper] {
+                    break;
+                }
+                let Some(result) = reg.merge_adjacent(&self.regions[upper]) else {
+                    break;
+                };
+                self.regions[idx] = result;
+                removed[upper] = true;
+                merged += 1;
+            }
+        }
+        if merged > 0 {
+            let mut removed = removed.into_iter();
+            self.regions.retain(|_| !removed.next().unwrap());
+        }
+        return merged;
+    }
+
+    /// Replace structure void with air or air with structure void in all regions according to
+    /// `policy`. Returns the number of replaced blocks.
+    pub fn apply_void_policy(&mut self, policy: VoidPolicy) -> u64 {
+        return self
+            .regions
+            .iter_mut()
+            .map(|reg| reg.apply_void_policy(policy))
+            .sum();
+    }
+
+    /// Namespaces other than `minecraft` used by blocks, block entities, entities and biomes,
+    /// which are usually mods (or datapacks) that the schematic depends on.
+    pub fn required_mods(&self) -> BTreeSet<String> {
+        let mut result = BTreeSet::new();
+        let mut add = |id: &str| {
+            if let Some((namespace, _)) = id.split_once(':') {
+                if !namespace.is_empty() && namespace != "minecraft" {
+                    result.insert(namespace.to_string());
+                }
+            }
+        };
+        for reg in &self.regions {
+            for blk in &reg.palette {
+                add(&blk.full_id());
+            }
+            for be in reg.block_entities.values() {
+                if let Some(fastnbt::Value::String(id)) = be.tags.get("id") {
+                    add(id);
+                }
+            }
+            for entity in &reg.entities {
+                if let Some(fastnbt::Value::String(id)) = entity.tags.get("id") {
+                    add(id);
+                }
+            }
+            for biome in &reg.biome_palette {
+                add(biome);
+            }
+        }
+        return result;
+    }
 }
 
 /// Convert nbt tag type to number id
@@ -759,12 +1170,46 @@ pub fn id_of_nbt_tag(tag: &fastnbt::Value) -> u8 {
 pub struct VanillaStructureLoadOption {
     /// Background block of the schematic. vanilla structure will not store structure void.
     pub background_block: CommonBlock,
+    /// Called with progress when the structure is loaded.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Fixes invalid block states and duplicated block entities, before the `ErrorHandler`
+    /// type of `from_vanilla_structure_*_with_warnings`.
+    pub error_handler: Option<ErrorHandlerFn>,
+    /// Don't load entities, their nbt is dropped without being copied.
+    pub skip_entities: bool,
+    /// Don't load block entities, their nbt is dropped without being copied. Blocks are loaded
+    /// as usual.
+    pub skip_block_entities: bool,
+    /// Structures with more blocks in volume fail before any memory is allocated for blocks.
+    /// Unlike other formats, the size of a structure is not bounded by the length of its data,
+    /// so lower it when loading untrusted files.
+    pub max_volume: u64,
+    /// For structures with random variants in `palettes`, the variant used as `palette` of the
+    /// region. The other variants are kept in `Region::alternate_palettes`, in the same order as
+    /// `Region::select_palette_variant` leaves them.
+    pub palette_variant: usize,
+    /// Check blocks of palettes against the embedded vanilla registry of the data version, and
+    /// fail if a property doesn't exist or has an impossible value. See
+    /// `BlockRegistry::validate` for unknown blocks. Requires feature `block_registry`, otherwise
+    /// loading fails with `FeatureDisabled` when it's set.
+    pub validate_blocks: bool,
 }
 
 impl VanillaStructureLoadOption {
     pub fn default() -> VanillaStructureLoadOption {
         return VanillaStructureLoadOption {
             background_block: CommonBlock::StructureVoid,
+            progress: None,
+            cancel: None,
+            error_handler: None,
+            skip_entities: false,
+            skip_block_entities: false,
+            max_volume: i32::MAX as u64,
+            palette_variant: 0,
+            validate_blocks: false,
         };
     }
 }
@@ -772,39 +1217,346 @@ impl VanillaStructureLoadOption {
 /// Options to save vanilla structure
 #[derive(Debug)]
 pub struct VanillaStructureSaveOption {
-    /// Level of gzip compression, 0<= level <=9.
+    /// Level of compression, 0<= level <=9. Lower levels are much faster on large files.
     pub compress_level: Compression,
-    /// Whether to store air. If false, air will be not be treated, just like structure void.
-    pub keep_air: bool,
+    /// Compression method, gzip by default.
+    pub compress_method: CompressMethod,
+    /// Whether to store air. Omitted air is treated like structure void when loaded. Include by
+    /// default, same as structure blocks.
+    pub air_policy: AirPolicy,
+    /// Max size that a structure block can load, like `STRUCTURE_BLOCK_MAX_SIZE`. Saving a larger
+    /// schematic fails with `SizeTooLarge`, unless `split_oversized` is true. `None` means no
+    /// limit.
+    pub max_size: Option<[i32; 3]>,
+    /// When saving to file, split a schematic larger than `max_size` into a grid of `.nbt` files
+    /// named like `name_1_0_2.nbt`, and write a `name.json` manifest recording their offsets.
+    pub split_oversized: bool,
+    /// Data version to save as. Blocks are upgraded or downgraded with the `datafix` module, and
+    /// blocks that don't exist in old versions are substituted. `None` keeps the data version of
+    /// schematic. Substituted blocks are reported to `on_downgrade`.
+    pub target_data_version: Option<i32>,
+    /// Called with the blocks changed or substituted when downgrading to `target_data_version`
+    pub on_downgrade: Option<DowngradeCallback>,
+    /// Called with progress every `PROGRESS_BLOCK_INTERVAL` blocks and when the structure, or
+    /// every piece when splitting, is saved.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Save name and description in a custom `Metadata` compound, because vanilla structures have
+    /// no tags for them. Minecraft ignores it, but mc_schem reads it back.
+    pub keep_unmapped_metadata: bool,
+    /// Add `id` to block entities without one, according to the block they belong to. Loaders
+    /// that create block entities from nbt alone, like Create, drop block entities without id.
+    pub fill_block_entity_id: bool,
+    /// Max size of the saved file in bytes, after compression. Saving a larger file fails with
+    /// `FileTooLarge` and writes nothing. `None` means no limit.
+    pub max_file_size: Option<u64>,
+    /// Order of blocks in palette, insertion order by default. Use `Sorted` to get stable diffs
+    /// between exported files.
+    pub palette_order: PaletteOrder,
+    /// Save only this palette variant as `palette`. `None` saves all variants in `palettes` if
+    /// the schematic has a single region with `alternate_palettes`. Split pieces never have
+    /// variants.
+    pub palette_variant: Option<usize>,
+}
+
+impl VanillaStructureSaveOption {
+    /// Max size of structure blocks since 1.16
+    pub const STRUCTURE_BLOCK_MAX_SIZE: [i32; 3] = [48, 48, 48];
+    /// Max size of structure blocks before 1.16
+    pub const LEGACY_STRUCTURE_BLOCK_MAX_SIZE: [i32; 3] = [32, 32, 32];
+    /// Default `maxTotalSchematicSize` of Create, the largest schematic that clients can upload
+    /// to a server
+    pub const CREATE_MAX_FILE_SIZE: u64 = 256 * 1024;
+
+    /// Preset for schematics of Create mod, which are loaded by schematicannons and schematic
+    /// tables. Create reads the file as a single gzip compressed structure, so pieces are never
+    /// split, every block entity gets an `id`, and files over `CREATE_MAX_FILE_SIZE` are rejected
+    /// instead of failing to upload.
+    pub fn create_mod() -> VanillaStructureSaveOption {
+        return VanillaStructureSaveOption {
+            compress_method: CompressMethod::Gzip,
+            split_oversized: false,
+            max_size: None,
+            fill_block_entity_id: true,
+            max_file_size: Some(Self::CREATE_MAX_FILE_SIZE),
+            ..Default::default()
+        };
+    }
 }
 
 impl Default for VanillaStructureSaveOption {
     fn default() -> VanillaStructureSaveOption {
         return VanillaStructureSaveOption {
-            keep_air: true,
+            air_policy: AirPolicy::Include,
+            max_size: None,
+            split_oversized: false,
+            target_data_version: None,
+            on_downgrade: None,
+            progress: None,
+            cancel: None,
+            keep_unmapped_metadata: false,
+            fill_block_entity_id: false,
+            max_file_size: None,
+            palette_order: PaletteOrder::Insertion,
+            palette_variant: None,
             compress_level: Compression::best(),
+            compress_method: CompressMethod::Gzip,
         };
     }
 }
 
-//#[derive(Debug)]
 /// Options to load litematica
-pub struct LitematicaLoadOption {}
+#[derive(Debug)]
+pub struct LitematicaLoadOption {
+    /// Called with progress after every region and every `PROGRESS_BLOCK_INTERVAL` blocks.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Fixes invalid block indices and duplicated tile entities, before the `ErrorHandler`
+    /// type of `from_litematica_*_with_warnings`.
+    pub error_handler: Option<ErrorHandlerFn>,
+    /// Load only regions accepted by the filter, other regions are skipped without decoding.
+    /// `None` loads all regions.
+    pub region_filter: Option<RegionFilter>,
+    /// Don't load entities, their nbt is dropped without being copied.
+    pub skip_entities: bool,
+    /// Don't load block entities, their nbt is dropped without being copied. Blocks are loaded
+    /// as usual.
+    pub skip_block_entities: bool,
+    /// Check blocks of palettes against the embedded vanilla registry of the data version, and
+    /// fail if a property doesn't exist or has an impossible value. See
+    /// `BlockRegistry::validate` for unknown blocks. Requires feature `block_registry`, otherwise
+    /// loading fails with `FeatureDisabled` when it's set.
+    pub validate_blocks: bool,
+}
 
 impl LitematicaLoadOption {
     pub fn default() -> LitematicaLoadOption {
-        return LitematicaLoadOption {};
+        return LitematicaLoadOption {
+            progress: None,
+            cancel: None,
+            error_handler: None,
+            region_filter: None,
+            skip_entities: false,
+            skip_block_entities: false,
+            validate_blocks: false,
+        };
     }
 }
 
+/// Selects regions by name when loading, see `LitematicaLoadOption::region_filter`
+pub enum RegionFilter {
+    /// Regions with any of these names
+    Names(Vec<String>),
+    /// Regions whose name makes the closure return true
+    Predicate(Box<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl RegionFilter {
+    pub fn names<S: ToString>(names: &[S]) -> RegionFilter {
+        return RegionFilter::Names(names.iter().map(|n| n.to_string()).collect());
+    }
+
+    pub fn predicate(predicate: impl Fn(&str) -> bool + Send + Sync + 'static) -> RegionFilter {
+        return RegionFilter::Predicate(Box::new(predicate));
+    }
+
+    /// Whether the region named `name` should be loaded
+    pub fn accepts(&self, name: &str) -> bool {
+        return match self {
+            RegionFilter::Names(names) => names.iter().any(|n| n == name),
+            RegionFilter::Predicate(predicate) => predicate(name),
+        };
+    }
+}
+
+impl std::fmt::Debug for RegionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            RegionFilter::Names(names) => f.debug_tuple("Names").field(names).finish(),
+            RegionFilter::Predicate(_) => write!(f, "Predicate"),
+        };
+    }
+}
+
+/// Bits used to store each block index in litematica `BlockStates`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BitsPerBlock {
+    /// Minimum bits to hold all block indices, but not less than 2, same as litematica.
+    Auto,
+    /// Use the assigned value. It must be able to hold all block indices, and no more than 16.
+    Forced(u8),
+}
+
+/// How the nbt is compressed when saving. Loading detects it from content, so files saved with
+/// any method can be loaded back.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromRepr)]
+pub enum CompressMethod {
+    /// Used by minecraft, litematica and world edit
+    Gzip = 0,
+    Zlib = 1,
+    /// Fastest, but most tools can only read gzip.
+    Uncompressed = 2,
+    /// Smaller and faster than gzip, but only a few tools can read it. Requires feature `zstd`,
+    /// otherwise saving and loading returns `Error::CompressMethodDisabled`.
+    Zstd = 3,
+}
+
+/// Whether air is written when saving. Vanilla structures list blocks one by one, so air can be
+/// omitted and the omitted positions keep existing blocks when loaded, like structure void.
+/// Litematica and world edit store every position in an array, so air is always stored, but
+/// litematica counts air into `TotalBlocks` of metadata only with `Include`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromRepr)]
+pub enum AirPolicy {
+    /// Don't store air where the format allows
+    Omit = 0,
+    /// Store air as a normal block
+    Include = 1,
+}
+
+/// Order of blocks in palette of saved files. Block indices are remapped accordingly, so the saved
+/// blocks are the same in either order.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromRepr)]
+pub enum PaletteOrder {
+    /// Keep the order that blocks are added to palette
+    Insertion = 0,
+    /// Air first, then other blocks sorted by full id. Files with the same blocks get the same
+    /// palette, so they can be diffed, and index 0 is always air if air exists.
+    Sorted = 1,
+}
+
+impl PaletteOrder {
+    /// Returns the new index of every block in `palette`
+    pub(crate) fn new_indices(&self, palette: &[&Block]) -> Vec<usize> {
+        if *self == PaletteOrder::Insertion {
+            return (0..palette.len()).collect();
+        }
+        let mut keys: Vec<(bool, String, usize)> = palette
+            .iter()
+            .enumerate()
+            .map(|(idx, blk)| (!blk.is_air(), blk.full_id(), idx))
+            .collect();
+        keys.sort();
+        let mut result = vec![0; palette.len()];
+        for (new_idx, (_, _, old_idx)) in keys.iter().enumerate() {
+            result[*old_idx] = new_idx;
+        }
+        return result;
+    }
+}
+
+/// Build height of a world, blocks can be placed from `min_y` to `min_y + height - 1`. Schematics
+/// themselves have no height limit, it's only checked when they are placed at absolute
+/// coordinates.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WorldHeight {
+    /// Lowest y where blocks can be placed
+    pub min_y: i32,
+    /// Count of block layers
+    pub height: i32,
+}
+
+impl WorldHeight {
+    /// Overworld since 1.18, from y=-64 to y=319
+    pub const OVERWORLD: WorldHeight = WorldHeight {
+        min_y: -64,
+        height: 384,
+    };
+    /// All dimensions before 1.18, and the nether and the end since then, from y=0 to y=255
+    pub const LEGACY: WorldHeight = WorldHeight {
+        min_y: 0,
+        height: 256,
+    };
+
+    /// Height of the overworld in `data_version`
+    pub fn overworld_of(data_version: i32) -> WorldHeight {
+        if data_version >= mc_version::DataVersion::Java_1_18 as i32 {
+            return Self::OVERWORLD;
+        }
+        return Self::LEGACY;
+    }
+
+    /// Highest y where blocks can be placed
+    pub fn max_y(&self) -> i32 {
+        return self.min_y + self.height - 1;
+    }
+
+    /// If blocks can be placed at `y`
+    pub fn contains_y(&self, y: i32) -> bool {
+        return y >= self.min_y && y <= self.max_y();
+    }
+
+    /// Returns `OutOfWorldHeight` unless all layers from `lower_y` to `upper_y`(inclusive) are
+    /// in the world
+    pub fn check_y_range(&self, lower_y: i32, upper_y: i32) -> Result<(), Error> {
+        if self.contains_y(lower_y) && self.contains_y(upper_y) {
+            return Ok(());
+        }
+        return Err(Error::OutOfWorldHeight {
+            y_range: [lower_y, upper_y],
+            world_y_range: [self.min_y, self.max_y()],
+        });
+    }
+}
+
+/// How structure void and air are treated in conversions. Every position is a block, air or
+/// structure void. When pasted, air clears the existing block, while structure void keeps it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VoidPolicy {
+    /// Keep structure void and air as they are
+    Keep,
+    /// Replace structure void with air, so the whole area is cleared when pasted
+    VoidToAir,
+    /// Replace air with structure void, so only non-air blocks are placed
+    AirToVoid,
+}
+
 /// Options to save litematica
 #[derive(Debug)]
 pub struct LitematicaSaveOption {
-    /// Level of gzip compression, 0<= level <=9.
+    /// Level of compression, 0<= level <=9. Lower levels are much faster on large files.
     pub compress_level: Compression,
+    /// Compression method, gzip by default.
+    pub compress_method: CompressMethod,
     /// Whether to rename a region if multiple regions have same name. If `false`, returns error when
     /// name conflicts happen.
     pub rename_duplicated_regions: bool,
+    /// Bits per block of `BlockStates`. Litematica computes it from palette size, so only use
+    /// forced value when the file is consumed by tools that read it from array length.
+    pub bits_per_block: BitsPerBlock,
+    /// Decode `BlockStates` of every region after encoding and compare with the original indices,
+    /// returns error if they differ. Slower, but catches packing bugs before the file is written.
+    pub verify_block_states: bool,
+    /// Data version to save as. Blocks are upgraded or downgraded with the `datafix` module, and
+    /// blocks that don't exist in old versions are substituted. `None` keeps the data version of
+    /// schematic. Substituted blocks are reported to `on_downgrade`.
+    pub target_data_version: Option<i32>,
+    /// Called with the blocks changed or substituted when downgrading to `target_data_version`
+    pub on_downgrade: Option<DowngradeCallback>,
+    /// Litematica format version(`Version` tag, not minecraft version) to save as, 4 to 7.
+    /// Version 4 has no `PendingFluidTicks`, and `SubVersion` is written since version 6. `None`
+    /// keeps the version in metadata of schematic, or guesses it from data version if that
+    /// version can't be written.
+    pub target_version: Option<i32>,
+    /// Called with progress after every region is encoded.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Preview image to save instead of `litematica_preview` in metadata of schematic.
+    pub preview: Option<PreviewImage>,
+    /// Air is always stored in `BlockStates`, this only decides whether `TotalBlocks` counts air.
+    /// Omit by default, same as litematica.
+    pub air_policy: AirPolicy,
+    /// Order of blocks in palette of every region, insertion order by default. `Sorted` keeps air
+    /// at index 0, which litematica expects, and gives stable diffs between exported files.
+    pub palette_order: PaletteOrder,
 }
 
 impl Default for LitematicaSaveOption {
@@ -812,30 +1564,84 @@ impl Default for LitematicaSaveOption {
         return LitematicaSaveOption {
             rename_duplicated_regions: true,
             compress_level: Compression::best(),
+            compress_method: CompressMethod::Gzip,
+            bits_per_block: BitsPerBlock::Auto,
+            verify_block_states: false,
+            target_data_version: None,
+            on_downgrade: None,
+            target_version: None,
+            progress: None,
+            cancel: None,
+            preview: None,
+            air_policy: AirPolicy::Omit,
+            palette_order: PaletteOrder::Insertion,
         };
     }
 }
 
 /// Options to load litematica
 #[derive(Debug)]
-pub struct WorldEdit13LoadOption {}
+pub struct WorldEdit13LoadOption {
+    /// Called with progress every `PROGRESS_BLOCK_INTERVAL` blocks and when finished.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Don't load block entities, their nbt is dropped without being copied. Blocks are loaded
+    /// as usual.
+    pub skip_block_entities: bool,
+    /// Check blocks of palettes against the embedded vanilla registry of the data version, and
+    /// fail if a property doesn't exist or has an impossible value. See
+    /// `BlockRegistry::validate` for unknown blocks. Requires feature `block_registry`, otherwise
+    /// loading fails with `FeatureDisabled` when it's set.
+    pub validate_blocks: bool,
+}
 
 #[allow(dead_code)]
 impl WorldEdit13LoadOption {
     pub fn default() -> WorldEdit13LoadOption {
-        return WorldEdit13LoadOption {};
+        return WorldEdit13LoadOption {
+            progress: None,
+            cancel: None,
+            skip_block_entities: false,
+            validate_blocks: false,
+        };
     }
 }
 
 /// Options to save world edit 1.13+
 #[derive(Debug)]
 pub struct WorldEdit13SaveOption {
-    /// Level of gzip compression, 0<= level <=9.
+    /// Level of compression, 0<= level <=9. Lower levels are much faster on large files.
     pub compress_level: Compression,
+    /// Compression method, gzip by default.
+    pub compress_method: CompressMethod,
     /// If the schematic contains multiple regions, some positions may not be covered by any region,
     /// but `.schem` can have only one region, so we must define a block for these positions.
     /// Air by default.
     pub background_block: CommonBlock,
+    /// Version of `.schem` to write. `None` means selecting by data version (2 before 1.20, 3 since
+    /// 1.20). Set to `Some(2)` to keep `Offset`, `Metadata.WEOffsetX/Y/Z` and `PaletteMax` readable
+    /// by WorldEdit versions that don't support v3. Pending ticks are saved only in v3.
+    pub schem_version: Option<i32>,
+    /// Data version to save as. Blocks are upgraded or downgraded with the `datafix` module, and
+    /// blocks that don't exist in old versions are substituted. `None` keeps the data version of
+    /// schematic. Substituted blocks are reported to `on_downgrade`.
+    pub target_data_version: Option<i32>,
+    /// Called with the blocks changed or substituted when downgrading to `target_data_version`
+    pub on_downgrade: Option<DowngradeCallback>,
+    /// Called with progress every `PROGRESS_BLOCK_INTERVAL` blocks and when the schematic is
+    /// encoded.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Save description as `Description` in `Metadata`, which is not a standard tag of sponge
+    /// schematic. Other tools ignore it, but mc_schem reads it back.
+    pub keep_unmapped_metadata: bool,
+    /// Order of blocks in palette, insertion order by default. The background block takes part in
+    /// sorting. Use `Sorted` to get stable diffs between exported files.
+    pub palette_order: PaletteOrder,
 }
 
 #[allow(dead_code)]
@@ -844,6 +1650,14 @@ impl Default for WorldEdit13SaveOption {
         return WorldEdit13SaveOption {
             background_block: CommonBlock::Air,
             compress_level: Compression::best(),
+            compress_method: CompressMethod::Gzip,
+            schem_version: None,
+            target_data_version: None,
+            on_downgrade: None,
+            progress: None,
+            cancel: None,
+            keep_unmapped_metadata: false,
+            palette_order: PaletteOrder::Insertion,
         };
     }
 }
@@ -853,12 +1667,76 @@ impl Default for WorldEdit13SaveOption {
 pub struct WorldEdit12LoadOption {
     /// Data version of this schematic. Data version is not stored in `.schematic`, so we should assign it.
     pub data_version: DataVersion,
+    /// Called with progress when the schematic is loaded.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Don't load block entities, their nbt is dropped without being copied. Blocks are loaded
+    /// as usual.
+    pub skip_block_entities: bool,
 }
 
 impl Default for WorldEdit12LoadOption {
     fn default() -> Self {
         return WorldEdit12LoadOption {
             data_version: DataVersion::Java_1_12_2,
+            progress: None,
+            cancel: None,
+            skip_block_entities: false,
+        };
+    }
+}
+
+/// Options to save world edit 1.12-
+#[derive(Debug)]
+pub struct WorldEdit12SaveOption {
+    /// Level of compression, 0<= level <=9. Lower levels are much faster on large files.
+    pub compress_level: Compression,
+    /// Compression method, gzip by default.
+    pub compress_method: CompressMethod,
+    /// Block for positions that are not covered by any region. Air by default.
+    pub background_block: CommonBlock,
+    /// Called with progress every `PROGRESS_BLOCK_INTERVAL` blocks of regions and when the
+    /// schematic is encoded.
+    pub progress: Option<ProgressCallback>,
+    /// Once set to true, possibly from another thread, the operation stops and returns
+    /// `Error::Cancelled`.
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for WorldEdit12SaveOption {
+    fn default() -> Self {
+        return WorldEdit12SaveOption {
+            compress_level: Compression::best(),
+            compress_method: CompressMethod::Gzip,
+            background_block: CommonBlock::Air,
+            progress: None,
+            cancel: None,
+        };
+    }
+}
+
+/// Options to save axiom blueprint
+#[derive(Debug, Clone)]
+pub struct AxiomSaveOption {
+    /// Level of compression of block data, 0<= level <=9
+    pub compress_level: Compression,
+    /// Render an isometric thumbnail if metadata has none, otherwise the thumbnail is left empty
+    pub render_thumbnail: bool,
+    /// Data version to save as, see `VanillaStructureSaveOption::target_data_version`
+    pub target_data_version: Option<i32>,
+    /// See `VanillaStructureSaveOption::on_downgrade`
+    pub on_downgrade: Option<DowngradeCallback>,
+}
+
+impl Default for AxiomSaveOption {
+    fn default() -> Self {
+        return AxiomSaveOption {
+            compress_level: Compression::default(),
+            render_thumbnail: true,
+            target_data_version: None,
+            on_downgrade: None,
         };
     }
 }
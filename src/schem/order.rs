@@ -0,0 +1,148 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use crate::region::{Region, WorldSlice};
+use ndarray::{Array3, ArrayView3};
+
+/// Order to visit blocks of a 3d array, named from the slowest axis to the fastest. For example,
+/// `YZX` visits every block of the lowest layer row by row before going up, and x changes fastest.
+///
+/// Litematica packs `BlockStates` in `YZX`, sponge schematic (`.schem`) and world edit 1.12
+/// (`.schematic`) store `BlockData` in `YZX` too, and `Region::array_yzx` is indexed in `YZX`.
+/// Vanilla structure lists blocks one by one with positions, so any order works.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IterationOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+}
+
+impl IterationOrder {
+    /// All orders
+    pub fn all() -> &'static [IterationOrder] {
+        use IterationOrder::*;
+        return &[XYZ, XZY, YXZ, YZX, ZXY, ZYX];
+    }
+
+    /// Dimensions from the slowest to the fastest, 0 for x, 1 for y and 2 for z
+    pub fn dims(&self) -> [usize; 3] {
+        return match self {
+            IterationOrder::XYZ => [0, 1, 2],
+            IterationOrder::XZY => [0, 2, 1],
+            IterationOrder::YXZ => [1, 0, 2],
+            IterationOrder::YZX => [1, 2, 0],
+            IterationOrder::ZXY => [2, 0, 1],
+            IterationOrder::ZYX => [2, 1, 0],
+        };
+    }
+
+    /// Permute `pos` in x, y, z to this order
+    pub fn xyz_to_order<T: Copy>(&self, pos: [T; 3]) -> [T; 3] {
+        let dims = self.dims();
+        return dims.map(|d| pos[d]);
+    }
+
+    /// Permute `pos` in this order back to x, y, z
+    pub fn order_to_xyz<T: Copy>(&self, pos: [T; 3]) -> [T; 3] {
+        let dims = self.dims();
+        let mut result = pos;
+        for (idx, d) in dims.into_iter().enumerate() {
+            result[d] = pos[idx];
+        }
+        return result;
+    }
+
+    /// Index of `pos` in a flat array of `shape` visited in this order, both are in x, y, z.
+    pub fn linear_index(&self, pos: [i32; 3], shape: [i32; 3]) -> usize {
+        let [a, b, c] = self.xyz_to_order(pos).map(|v| v as usize);
+        let [_, size_b, size_c] = self.xyz_to_order(shape).map(|v| v as usize);
+        return (a * size_b + b) * size_c + c;
+    }
+
+    /// Position in x, y, z of the `index`-th block in a flat array of `shape`, the inverse of
+    /// [`IterationOrder::linear_index`].
+    pub fn pos_of_index(&self, index: usize, shape: [i32; 3]) -> [i32; 3] {
+        let [_, size_b, size_c] = self.xyz_to_order(shape).map(|v| v as usize);
+        let c = index % size_c;
+        let b = (index / size_c) % size_b;
+        let a = index / size_c / size_b;
+        return self.order_to_xyz([a, b, c].map(|v| v as i32));
+    }
+
+    /// Axes of `Region::array_yzx` in this order
+    fn array_axes(&self) -> [usize; 3] {
+        // axes of array_yzx are y, z, x
+        let axis_of_dim = [2, 0, 1];
+        return self.dims().map(|d| axis_of_dim[d]);
+    }
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// View of block indices indexed in `order`, no data is copied. Iterating the view visits
+    /// blocks in `order`.
+    pub fn as_order(&self, order: IterationOrder) -> ArrayView3<'_, u16> {
+        return self.array_yzx.view().permuted_axes(order.array_axes());
+    }
+
+    /// Copy block indices to an array indexed in `order`, which is contiguous in memory, so
+    /// `into_raw_vec` gives the flat array visited in `order`.
+    pub fn reorder(&self, order: IterationOrder) -> Array3<u16> {
+        return self.as_order(order).as_standard_layout().into_owned();
+    }
+
+    /// Replace all block indices with `data`, a flat array visited in `order`. `InvalidValue` is
+    /// returned if the length doesn't match the volume, and `BlockIndexOutOfRange` if any index
+    /// exceeds the palette.
+    pub fn assign_from_order(&mut self, order: IterationOrder, data: &[u16]) -> Result<(), Error> {
+        let shape = self.shape();
+        if data.len() as u64 != self.volume() {
+            return Err(Error::InvalidValue {
+                tag_path: String::new(),
+                error: format!(
+                    "Expected {} block indices for region of shape {shape:?}, but found {}",
+                    self.volume(),
+                    data.len()
+                ),
+            });
+        }
+        if let Some((idx, value)) = data
+            .iter()
+            .enumerate()
+            .find(|(_, v)| **v as usize >= self.palette.len())
+        {
+            return Err(Error::BlockIndexOutOfRange {
+                tag_path: format!("[{idx}]"),
+                index: *value as i32,
+                range: [0, self.palette.len() as i32],
+            });
+        }
+        let shape_in_order = order.xyz_to_order(shape).map(|v| v as usize);
+        let view = ArrayView3::from_shape(shape_in_order, data).unwrap();
+        self.as_order_mut(order).assign(&view);
+        return Ok(());
+    }
+
+    fn as_order_mut(&mut self, order: IterationOrder) -> ndarray::ArrayViewMut3<'_, u16> {
+        return self.array_yzx.view_mut().permuted_axes(order.array_axes());
+    }
+}
@@ -0,0 +1,198 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{BlockBox, Region, WorldSlice};
+use crate::schem::Schematic;
+
+/// Two regions of a schematic that share some blocks, found by [`Schematic::detect_overlaps`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RegionOverlap {
+    /// Index of the former region, always less than `second`
+    pub first: usize,
+    /// Index of the latter region
+    pub second: usize,
+    /// The shared blocks in global coordinates
+    pub bounds: BlockBox,
+}
+
+/// How [`Schematic::resolve_overlaps`] handles overlapping regions
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum OverlapStrategy {
+    /// The former region wins. Overlapped blocks of the latter region are replaced with structure
+    /// void, and its block entities and pending ticks there are removed. Regions keep their
+    /// shapes, so [`Schematic::detect_overlaps`] still reports them.
+    PriorityOrder,
+    /// Return `Error::RegionsOverlap` for the first overlap
+    Error,
+    /// Each group of overlapping regions is merged into one region that covers all of them, in
+    /// which former regions win. Blocks covered by no region are filled with structure void.
+    /// The merged region takes the place and name of the first region in the group, biomes are
+    /// dropped.
+    Merge,
+}
+
+impl Region {
+    /// Blocks occupied by this region in global coordinates
+    pub fn global_box(&self) -> BlockBox {
+        return BlockBox::from_pos_shape(self.offset, self.shape());
+    }
+
+    /// Replace blocks inside `g_box`(global coordinates) with structure void, and remove block
+    /// entities and pending ticks there.
+    fn clear_box(&mut self, g_box: &BlockBox) {
+        let void = self.find_or_append_to_palette(&Block::structure_void());
+        for x in g_box.lower[0]..=g_box.upper[0] {
+            for y in g_box.lower[1]..=g_box.upper[1] {
+                for z in g_box.lower[2]..=g_box.upper[2] {
+                    let r_pos = self.global_pos_to_relative_pos([x, y, z]);
+                    if self.set_block_id(r_pos, void).is_ok() {
+                        self.block_entities.remove(&r_pos);
+                        self.pending_ticks.remove(&r_pos);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Schematic {
+    /// Find all pairs of regions that share at least one block. Regions with no blocks never overlap.
+    pub fn detect_overlaps(&self) -> Vec<RegionOverlap> {
+        let mut result = Vec::new();
+        for first in 0..self.regions.len() {
+            if self.regions[first].volume() == 0 {
+                continue;
+            }
+            let box_a = self.regions[first].global_box();
+            for second in (first + 1)..self.regions.len() {
+                if self.regions[second].volume() == 0 {
+                    continue;
+                }
+                let box_b = self.regions[second].global_box();
+                if !box_a.intersects(&box_b) {
+                    continue;
+                }
+                let lower = std::array::from_fn(|dim| box_a.lower[dim].max(box_b.lower[dim]));
+                let upper = std::array::from_fn(|dim| box_a.upper[dim].min(box_b.upper[dim]));
+                result.push(RegionOverlap {
+                    first,
+                    second,
+                    bounds: BlockBox { lower, upper },
+                });
+            }
+        }
+        return result;
+    }
+
+    /// Make sure that no 2 regions share a block, so that the schematic can be converted to
+    /// single-region formats deterministically. Returns the number of overlaps resolved.
+    pub fn resolve_overlaps(&mut self, strategy: OverlapStrategy) -> Result<usize, Error> {
+        let overlaps = self.detect_overlaps();
+        if overlaps.is_empty() {
+            return Ok(0);
+        }
+        match strategy {
+            OverlapStrategy::Error => {
+                let ov = &overlaps[0];
+                return Err(Error::RegionsOverlap {
+                    first: ov.first,
+                    second: ov.second,
+                    lower: ov.bounds.lower,
+                    upper: ov.bounds.upper,
+                });
+            }
+            OverlapStrategy::PriorityOrder => {
+                for ov in &overlaps {
+                    self.regions[ov.second].clear_box(&ov.bounds);
+                }
+            }
+            OverlapStrategy::Merge => {
+                // a merged region is larger than its parts, so it may overlap more regions
+                let mut resolved = 0;
+                let mut overlaps = overlaps;
+                while !overlaps.is_empty() {
+                    resolved += overlaps.len();
+                    self.merge_overlapping_groups(&overlaps);
+                    overlaps = self.detect_overlaps();
+                }
+                return Ok(resolved);
+            }
+        }
+        return Ok(overlaps.len());
+    }
+
+    fn merge_overlapping_groups(&mut self, overlaps: &[RegionOverlap]) {
+        // union-find, the root of each group is its smallest index
+        let mut parent: Vec<usize> = (0..self.regions.len()).collect();
+        fn root(parent: &mut [usize], mut idx: usize) -> usize {
+            while parent[idx] != idx {
+                parent[idx] = parent[parent[idx]];
+                idx = parent[idx];
+            }
+            return idx;
+        }
+        for ov in overlaps {
+            let a = root(&mut parent, ov.first);
+            let b = root(&mut parent, ov.second);
+            if a != b {
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+
+        let old_regions = std::mem::take(&mut self.regions);
+        let mut groups: Vec<Vec<Region>> = Vec::new();
+        let mut group_of_root: Vec<Option<usize>> = vec![None; old_regions.len()];
+        for (idx, reg) in old_regions.into_iter().enumerate() {
+            let r = root(&mut parent, idx);
+            match group_of_root[r] {
+                Some(g) => groups[g].push(reg),
+                None => {
+                    group_of_root[r] = Some(groups.len());
+                    groups.push(vec![reg]);
+                }
+            }
+        }
+
+        for mut group in groups {
+            if group.len() == 1 {
+                self.regions.push(group.pop().unwrap());
+                continue;
+            }
+            let mut lower = group[0].offset;
+            for reg in &group {
+                for (l, offset) in lower.iter_mut().zip(reg.offset) {
+                    *l = (*l).min(offset);
+                }
+            }
+            let name = group[0].name.clone();
+            let mut temp = Schematic::new();
+            for mut reg in group {
+                for (offset, l) in reg.offset.iter_mut().zip(lower) {
+                    *offset -= l;
+                }
+                temp.regions.push(reg);
+            }
+            let mut merged = temp.to_single_region(&Block::structure_void());
+            merged.offset = lower;
+            merged.name = name;
+            self.regions.push(merged);
+        }
+    }
+}
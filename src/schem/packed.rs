@@ -0,0 +1,171 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{BlockEntity, Entity, HasOffset, HasPalette, PendingTick, Region, WorldSlice};
+use crate::schem::litematica::MultiBitSet;
+use crate::schem::memory;
+use crate::schem::memory::RegionMemoryReport;
+use crate::schem::{MetaDataIR, Schematic};
+use std::collections::HashMap;
+
+/// A read-only region that keeps blocks bit-packed as they are stored in litematica
+/// `BlockStates`, and resolves block indices on access. It takes much less memory than
+/// [`Region`] for large regions with small palettes, at the cost of slower access.
+#[derive(Debug, Clone)]
+pub struct PackedRegion {
+    /// Name of this region
+    pub name: String,
+    pub(crate) shape: [i32; 3],
+    /// Block indices in y,z,x order
+    pub(crate) blocks: MultiBitSet,
+    /// All kinds of blocks
+    pub palette: Vec<Block>,
+    /// All block entities. The key is position (xyz)
+    pub block_entities: HashMap<[i32; 3], BlockEntity>,
+    /// All pending ticks. The key is position (xyz)
+    pub pending_ticks: HashMap<[i32; 3], Vec<PendingTick>>,
+    /// All entities
+    pub entities: Vec<Entity>,
+    /// Offset of this region
+    pub offset: [i32; 3],
+    /// Axes on which litematica stores this region with negative size
+    pub negative_size: [bool; 3],
+}
+
+#[allow(dead_code)]
+impl PackedRegion {
+    /// Packed block indices in y,z,x order
+    pub fn packed_blocks(&self) -> &MultiBitSet {
+        return &self.blocks;
+    }
+
+    fn index_of(&self, r_pos: [i32; 3]) -> usize {
+        let [x, y, z] = r_pos.map(|v| v as usize);
+        let [sx, _, sz] = self.shape.map(|v| v as usize);
+        return (y * sz + z) * sx + x;
+    }
+
+    /// Estimate heap memory used by this region
+    pub fn memory_usage(&self) -> RegionMemoryReport {
+        return RegionMemoryReport {
+            name: self.name.clone(),
+            block_array: std::mem::size_of_val(self.blocks.as_u64_slice()),
+            palette: memory::palette_bytes(&self.palette),
+            block_entities: memory::block_entities_bytes(&self.block_entities),
+            pending_ticks: memory::pending_ticks_bytes(&self.pending_ticks),
+            entities: memory::entities_bytes(&self.entities),
+            biomes: 0,
+        };
+    }
+
+    /// Unpack into a [`Region`]
+    pub fn into_region(self) -> Region {
+        let mut region = Region::with_shape(self.shape);
+        match region.array_yzx.as_slice_mut() {
+            Some(dest) => {
+                self.blocks.unpack_into_u16(dest);
+            }
+            None => {
+                for (dest, src) in region.array_yzx.iter_mut().zip(self.blocks.iter()) {
+                    *dest = src as u16;
+                }
+            }
+        }
+        region.name = self.name;
+        region.palette = self.palette;
+        region.block_entities = self.block_entities;
+        region.pending_ticks = self.pending_ticks;
+        region.entities = self.entities;
+        region.offset = self.offset;
+        region.negative_size = self.negative_size;
+        return region;
+    }
+}
+
+impl HasPalette for PackedRegion {
+    fn palette(&self) -> &[Block] {
+        return &self.palette;
+    }
+}
+
+impl HasOffset for PackedRegion {
+    fn offset(&self) -> [i32; 3] {
+        return self.offset;
+    }
+}
+
+impl WorldSlice for PackedRegion {
+    fn shape(&self) -> [i32; 3] {
+        return self.shape;
+    }
+
+    fn total_blocks(&self, include_air: bool) -> u64 {
+        let air = self.block_index_of_air().map(|i| i as u64);
+        let structure_void = self.block_index_of_structure_void().map(|i| i as u64);
+        let mut counter = 0;
+        for blk_id in self.blocks.iter() {
+            if Some(blk_id) == structure_void || (!include_air && Some(blk_id) == air) {
+                continue;
+            }
+            counter += 1;
+        }
+        return counter;
+    }
+
+    fn block_index_at(&self, r_pos: [i32; 3]) -> Option<u16> {
+        if !self.contains_coord(r_pos) {
+            return None;
+        }
+        return Some(self.blocks.get(self.index_of(r_pos)) as u16);
+    }
+
+    fn block_at(&self, r_pos: [i32; 3]) -> Option<&Block> {
+        return Some(&self.palette[self.block_index_at(r_pos)? as usize]);
+    }
+
+    fn block_entity_at(&self, r_pos: [i32; 3]) -> Option<&BlockEntity> {
+        return self.block_entities.get(&r_pos);
+    }
+
+    fn pending_tick_at(&self, r_pos: [i32; 3]) -> &[PendingTick] {
+        if let Some(pts) = self.pending_ticks.get(&r_pos) {
+            return pts;
+        }
+        return &[];
+    }
+}
+
+/// A schematic whose regions are kept bit-packed, see [`PackedRegion`].
+#[derive(Debug, Clone)]
+pub struct PackedSchematic {
+    pub metadata: MetaDataIR,
+    /// A list of regions
+    pub regions: Vec<PackedRegion>,
+}
+
+#[allow(dead_code)]
+impl PackedSchematic {
+    /// Unpack all regions into a [`Schematic`]
+    pub fn into_schematic(self) -> Schematic {
+        let mut schem = Schematic::new();
+        schem.metadata = self.metadata;
+        schem.regions = self.regions.into_iter().map(|r| r.into_region()).collect();
+        return schem;
+    }
+}
@@ -0,0 +1,106 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use static_assertions as sa;
+
+use crate::block::Block;
+use crate::region::{BlockEntity, Entity, PendingTick, Region, WorldSlice};
+use crate::schem::{MetaDataIR, Schematic};
+
+// Nothing in a schematic uses interior mutability, so it can be read by many threads at once.
+sa::assert_impl_all!(Schematic: Send, Sync);
+sa::assert_impl_all!(Region: Send, Sync);
+sa::assert_impl_all!(Block: Send, Sync);
+sa::assert_impl_all!(Entity: Send, Sync);
+sa::assert_impl_all!(BlockEntity: Send, Sync);
+sa::assert_impl_all!(PendingTick: Send, Sync);
+sa::assert_impl_all!(MetaDataIR: Send, Sync);
+
+/// A read-only schematic shared between threads
+pub type SharedSchematic = Arc<Schematic>;
+
+/// Blocks counted by one task of [`Region::par_palette_counts`]
+const COUNT_CHUNK_BLOCKS: usize = 1 << 16;
+
+impl Region {
+    /// Iterate all blocks in parallel. Items are `(relative position, block)`, and they are in
+    /// y, z, x order if collected.
+    pub fn par_iter_blocks(&self) -> impl IndexedParallelIterator<Item = ([i32; 3], &Block)> + '_ {
+        let [sx, _, sz] = self.shape().map(|s| s as usize);
+        return (0..self.volume() as usize).into_par_iter().map(move |i| {
+            let x = i % sx;
+            let z = (i / sx) % sz;
+            let y = i / (sx * sz);
+            let blk_id = self.array_yzx[[y, z, x]];
+            return (
+                [x as i32, y as i32, z as i32],
+                &self.palette[blk_id as usize],
+            );
+        });
+    }
+
+    /// Same as [`Region::palette_counts`], but counts in parallel
+    pub fn par_palette_counts(&self) -> Vec<u64> {
+        let Some(indices) = self.array_yzx.as_slice() else {
+            return self.palette_counts();
+        };
+        let palette_len = self.palette.len();
+        return indices
+            .par_chunks(COUNT_CHUNK_BLOCKS)
+            .map(|chunk| {
+                let mut counts = vec![0u64; palette_len];
+                for idx in chunk {
+                    if let Some(c) = counts.get_mut(*idx as usize) {
+                        *c += 1;
+                    }
+                }
+                return counts;
+            })
+            .reduce(
+                || vec![0u64; palette_len],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    return a;
+                },
+            );
+    }
+}
+
+impl Schematic {
+    /// Wrap the schematic to share it between threads
+    pub fn into_shared(self) -> SharedSchematic {
+        return Arc::new(self);
+    }
+
+    /// Iterate blocks of all regions in parallel. Items are `(region index, global position,
+    /// block)`, overlapped regions yield the same position more than once.
+    pub fn par_iter_blocks(&self) -> impl ParallelIterator<Item = (usize, [i32; 3], &Block)> + '_ {
+        return self
+            .regions
+            .par_iter()
+            .enumerate()
+            .flat_map(|(reg_idx, reg)| {
+                reg.par_iter_blocks().map(move |(r_pos, blk)| {
+                    return (reg_idx, reg.relative_pos_to_global_pos(r_pos), blk);
+                })
+            });
+    }
+}
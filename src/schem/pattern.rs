@@ -0,0 +1,347 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::block::{Block, BlockIdParseError};
+use crate::error::Error;
+use crate::region::Region;
+use crate::schem::search::{split_outside_brackets, BlockMatcher};
+use crate::schem::Schematic;
+
+/// A block of a pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternItem {
+    /// Always this block
+    Block(Block),
+    /// Replace namespace and id, but keep properties of the replaced block, written as `*id` or
+    /// `^id`. Properties of this block overwrite the kept ones.
+    KeepProperties(Block),
+}
+
+impl PatternItem {
+    /// The block that replaces `existing`
+    pub fn apply(&self, existing: &Block) -> Block {
+        return match self {
+            PatternItem::Block(blk) => blk.clone(),
+            PatternItem::KeepProperties(blk) => {
+                let mut result = blk.clone();
+                result.attributes = existing.attributes.clone();
+                for (key, value) in &blk.attributes {
+                    result.attributes.insert(key.clone(), value.clone());
+                }
+                result
+            }
+        };
+    }
+}
+
+/// Blocks used by fill and replace, like patterns of WorldEdit. Every block is chosen from
+/// `items` at random, with probability in proportion to the weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    /// Weight and block, weights are positive
+    pub items: Vec<(f64, PatternItem)>,
+}
+
+/// Error of [`Pattern::parse`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternParseError {
+    /// The pattern contains nothing but spaces
+    EmptyPattern,
+    /// Nothing between two commas
+    EmptyItem { pattern: String },
+    /// The weight before `%` is not a positive number
+    InvalidWeight { item: String },
+    /// Failed to parse a block id
+    InvalidBlock {
+        block: String,
+        error: BlockIdParseError,
+    },
+}
+
+impl Display for PatternParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            PatternParseError::EmptyPattern => write!(f, "pattern is empty"),
+            PatternParseError::EmptyItem { pattern } => {
+                write!(f, "pattern \"{pattern}\" contains an empty item")
+            }
+            PatternParseError::InvalidWeight { item } => {
+                write!(f, "weight of \"{item}\" should be a positive number")
+            }
+            PatternParseError::InvalidBlock { block, error } => {
+                write!(f, "invalid block \"{block}\" in pattern, detail: {error}")
+            }
+        };
+    }
+}
+
+impl std::error::Error for PatternParseError {}
+
+impl FromStr for Pattern {
+    type Err = PatternParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Pattern::parse(s);
+    }
+}
+
+impl Pattern {
+    /// A pattern of only one block
+    pub fn from_block(block: Block) -> Pattern {
+        return Pattern {
+            items: vec![(1.0, PatternItem::Block(block))],
+        };
+    }
+
+    /// Parse a pattern string of WorldEdit, like `50%stone,50%cobblestone` or `*oak_planks`.
+    /// Items are separated by commas, and each one may start with a weight followed by `%`.
+    /// Items without weight have weight 1.
+    pub fn parse(pattern: &str) -> Result<Pattern, PatternParseError> {
+        let trimmed = pattern.trim();
+        if trimmed.is_empty() {
+            return Err(PatternParseError::EmptyPattern);
+        }
+        let mut items = Vec::new();
+        for item in split_outside_brackets(trimmed, |ch| ch == ',') {
+            let item = item.trim();
+            if item.is_empty() {
+                return Err(PatternParseError::EmptyItem {
+                    pattern: pattern.to_string(),
+                });
+            }
+            let (weight, block) = match item.split_once('%') {
+                Some((weight, block)) if !weight.contains('[') => {
+                    let weight = match weight.trim().parse::<f64>() {
+                        Ok(w) if w.is_finite() && w > 0.0 => w,
+                        _ => {
+                            return Err(PatternParseError::InvalidWeight {
+                                item: item.to_string(),
+                            })
+                        }
+                    };
+                    (weight, block.trim())
+                }
+                _ => (1.0, item),
+            };
+            let (keep_properties, id) = match block.strip_prefix(['*', '^']) {
+                Some(id) => (true, id),
+                None => (false, block),
+            };
+            let blk = Block::from_id(id).map_err(|error| PatternParseError::InvalidBlock {
+                block: block.to_string(),
+                error,
+            })?;
+            items.push((
+                weight,
+                if keep_properties {
+                    PatternItem::KeepProperties(blk)
+                } else {
+                    PatternItem::Block(blk)
+                },
+            ));
+        }
+        return Ok(Pattern { items });
+    }
+
+    /// Choose an item with `rng`, returns its index in `items`
+    fn choose(&self, rng: &mut SplitMix64) -> usize {
+        if self.items.len() <= 1 {
+            return 0;
+        }
+        let total: f64 = self.items.iter().map(|(w, _)| *w).sum();
+        let mut r = rng.next_f64() * total;
+        for (idx, (weight, _)) in self.items.iter().enumerate() {
+            if r < *weight {
+                return idx;
+            }
+            r -= weight;
+        }
+        return self.items.len() - 1;
+    }
+}
+
+/// Small deterministic generator, so that results only depend on the seed
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        return z ^ (z >> 31);
+    }
+
+    /// Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        return (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+}
+
+impl Region {
+    /// Replace blocks matched by `matcher` with blocks of `pattern`, or all blocks if `matcher` is
+    /// `None`. Blocks are chosen by a generator seeded with `seed`, so the same seed gives the
+    /// same result. Block entities and pending ticks of replaced blocks are removed. \
+    /// Returns the count of replaced blocks, or `PaletteTooLong` if the palette exceeds 65536.
+    pub fn replace_with_pattern(
+        &mut self,
+        matcher: Option<&BlockMatcher>,
+        pattern: &Pattern,
+        seed: u64,
+    ) -> Result<u64, Error> {
+        if pattern.items.is_empty() {
+            return Ok(0);
+        }
+        let matched: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| matcher.map_or(true, |m| m.matches(blk)))
+            .collect();
+        let mut rng = SplitMix64(seed);
+        // (item index, existing block index) -> new block index
+        let mut lut: HashMap<(usize, u16), u16> = HashMap::new();
+        let mut replaced = Vec::new();
+        let mut counter = 0u64;
+        for ((y, z, x), blk_id) in self.array_yzx.indexed_iter() {
+            if !matched.get(*blk_id as usize).copied().unwrap_or(false) {
+                continue;
+            }
+            let item_idx = pattern.choose(&mut rng);
+            replaced.push(([y, z, x], item_idx, *blk_id));
+        }
+        for ([y, z, x], item_idx, old_id) in replaced {
+            let key = match pattern.items[item_idx].1 {
+                PatternItem::Block(_) => (item_idx, 0),
+                PatternItem::KeepProperties(_) => (item_idx, old_id),
+            };
+            let new_id = match lut.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let blk = pattern.items[item_idx]
+                        .1
+                        .apply(&self.palette[old_id as usize]);
+                    let id = self.find_or_append_to_palette(&blk);
+                    if self.palette.len() > 65536 {
+                        return Err(Error::PaletteTooLong(self.palette.len()));
+                    }
+                    lut.insert(key, id);
+                    id
+                }
+            };
+            self.array_yzx[[y, z, x]] = new_id;
+            let r_pos = [x as i32, y as i32, z as i32];
+            self.block_entities.remove(&r_pos);
+            self.pending_ticks.remove(&r_pos);
+            counter += 1;
+        }
+        return Ok(counter);
+    }
+
+    /// Fill the whole region with blocks of `pattern`, see [`Region::replace_with_pattern`]
+    pub fn fill_with_pattern(&mut self, pattern: &Pattern, seed: u64) -> Result<u64, Error> {
+        return self.replace_with_pattern(None, pattern, seed);
+    }
+
+    /// Place blocks of `pattern` on top of blocks matched by `surface`, like flowers on grass.
+    /// Only air directly above a surface block is replaced, and each one is chosen with
+    /// probability `density`. The result only depends on `seed`. \
+    /// Returns the count of placed blocks, `InvalidValue` if `density` is not in [0, 1], or
+    /// `PaletteTooLong` if the palette exceeds 65536.
+    pub fn scatter(
+        &mut self,
+        surface: &BlockMatcher,
+        pattern: &Pattern,
+        density: f64,
+        seed: u64,
+    ) -> Result<u64, Error> {
+        if !(0.0..=1.0).contains(&density) {
+            return Err(Error::InvalidValue {
+                tag_path: "density".to_string(),
+                error: format!("density should be in [0, 1], but found {density}"),
+            });
+        }
+        if pattern.items.is_empty() {
+            return Ok(0);
+        }
+        let is_surface: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| surface.matches(blk))
+            .collect();
+        let is_air: Vec<bool> = self.palette.iter().map(|blk| blk.is_air()).collect();
+        let check = |flags: &[bool], idx: u16| flags.get(idx as usize).copied().unwrap_or(false);
+
+        let mut rng = SplitMix64(seed);
+        let mut placed = Vec::new();
+        let sy = self.array_yzx.shape()[0];
+        for ((y, z, x), blk_id) in self.array_yzx.indexed_iter() {
+            if y + 1 >= sy || !check(&is_surface, *blk_id) {
+                continue;
+            }
+            let above = self.array_yzx[[y + 1, z, x]];
+            if !check(&is_air, above) {
+                continue;
+            }
+            if rng.next_f64() < density {
+                placed.push(([y + 1, z, x], pattern.choose(&mut rng), above));
+            }
+        }
+
+        // item index -> new block index, the replaced block is always air
+        let mut lut: Vec<Option<u16>> = vec![None; pattern.items.len()];
+        for (yzx, item_idx, old_id) in &placed {
+            let new_id = match lut[*item_idx] {
+                Some(id) => id,
+                None => {
+                    let blk = pattern.items[*item_idx]
+                        .1
+                        .apply(&self.palette[*old_id as usize]);
+                    let id = self.find_or_append_to_palette(&blk);
+                    if self.palette.len() > 65536 {
+                        return Err(Error::PaletteTooLong(self.palette.len()));
+                    }
+                    lut[*item_idx] = Some(id);
+                    id
+                }
+            };
+            self.array_yzx[*yzx] = new_id;
+        }
+        return Ok(placed.len() as u64);
+    }
+}
+
+impl Schematic {
+    /// Replace blocks matched by `matcher` in all regions with blocks of `pattern`, see
+    /// [`Region::replace_with_pattern`]. Each region uses a different seed derived from `seed`.
+    pub fn replace_with_pattern(
+        &mut self,
+        matcher: Option<&BlockMatcher>,
+        pattern: &Pattern,
+        seed: u64,
+    ) -> Result<u64, Error> {
+        let mut counter = 0;
+        for (idx, reg) in self.regions.iter_mut().enumerate() {
+            let reg_seed = SplitMix64(seed ^ (idx as u64).wrapping_mul(0x9E3779B97F4A7C15)).next();
+            counter += reg.replace_with_pattern(matcher, pattern, reg_seed)?;
+        }
+        return Ok(counter);
+    }
+}
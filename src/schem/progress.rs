@@ -0,0 +1,146 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// While loading or saving a region, the progress callback is invoked every time this number of
+/// blocks is processed.
+pub const PROGRESS_BLOCK_INTERVAL: u64 = 1 << 16;
+
+/// Progress of loading or saving a schematic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Index of the region being processed. Equals to `region_count` when finished.
+    pub region_index: usize,
+    /// Number of regions
+    pub region_count: usize,
+    /// Number of blocks processed, including all finished regions
+    pub blocks_done: u64,
+    /// Number of blocks in all regions
+    pub blocks_total: u64,
+}
+
+impl Progress {
+    /// Processed blocks in range \[0, 1\]
+    pub fn fraction(&self) -> f64 {
+        if self.blocks_total == 0 {
+            return if self.region_index >= self.region_count {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        return self.blocks_done as f64 / self.blocks_total as f64;
+    }
+}
+
+/// Callback to receive [`Progress`], used by load and save options. It's invoked at least once
+/// when the operation finishes; options document how often it's invoked in between.
+pub struct ProgressCallback(Box<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> ProgressCallback {
+        return ProgressCallback(Box::new(callback));
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "ProgressCallback");
+    }
+}
+
+/// Returns `Error::Cancelled` if the cancellation flag is set
+pub(crate) fn check_cancelled(cancel: Option<&Arc<AtomicBool>>) -> Result<(), Error> {
+    if let Some(flag) = cancel {
+        if flag.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+    }
+    return Ok(());
+}
+
+/// Counts processed blocks, invokes the callback and checks the cancellation flag
+pub(crate) struct ProgressReporter<'a> {
+    callback: Option<&'a ProgressCallback>,
+    cancel: Option<&'a Arc<AtomicBool>>,
+    progress: Progress,
+    last_reported: u64,
+    region_start: u64,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(
+        callback: Option<&'a ProgressCallback>,
+        cancel: Option<&'a Arc<AtomicBool>>,
+        region_count: usize,
+        blocks_total: u64,
+    ) -> ProgressReporter<'a> {
+        return ProgressReporter {
+            callback,
+            cancel,
+            progress: Progress {
+                region_index: 0,
+                region_count,
+                blocks_done: 0,
+                blocks_total,
+            },
+            last_reported: 0,
+            region_start: 0,
+        };
+    }
+
+    fn report(&mut self) -> Result<(), Error> {
+        if let Some(cb) = self.callback {
+            (cb.0)(self.progress);
+        }
+        self.last_reported = self.progress.blocks_done;
+        return check_cancelled(self.cancel);
+    }
+
+    /// Mark `blocks` more blocks as processed. The cancellation flag is checked every
+    /// `PROGRESS_BLOCK_INTERVAL` blocks.
+    pub fn advance(&mut self, blocks: u64) -> Result<(), Error> {
+        self.progress.blocks_done += blocks;
+        if self.progress.blocks_done - self.last_reported >= PROGRESS_BLOCK_INTERVAL {
+            return self.report();
+        }
+        return Ok(());
+    }
+
+    /// Mark current region with `region_blocks` blocks as finished, whether or not its blocks
+    /// are reported by `advance`.
+    pub fn finish_region(&mut self, region_blocks: u64) -> Result<(), Error> {
+        self.progress.blocks_done = self.region_start + region_blocks;
+        self.region_start = self.progress.blocks_done;
+        self.progress.region_index += 1;
+        return self.report();
+    }
+}
+
+/// Report a finished schematic with only one region of `blocks` blocks
+pub(crate) fn report_single_region(
+    callback: Option<&ProgressCallback>,
+    cancel: Option<&Arc<AtomicBool>>,
+    blocks: u64,
+) -> Result<(), Error> {
+    return ProgressReporter::new(callback, cancel, 1, blocks).finish_region(blocks);
+}
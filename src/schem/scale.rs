@@ -0,0 +1,281 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::error::Error;
+use crate::region::{BlockEntity, Entity, PendingTick, Region, WorldSlice};
+use crate::schem::Schematic;
+use fastnbt::Value;
+use ndarray::Array3;
+use std::collections::HashMap;
+
+/// Scale position and tags of an entity and its passengers
+fn scale_entity(entity: &mut Entity, factor: f64) {
+    entity.set_position(entity.position.map(|p| p * factor));
+    scale_entity_tags(&mut entity.tags, factor);
+    for passenger in &mut entity.passengers {
+        scale_entity(passenger, factor);
+    }
+}
+
+/// Scale tags of an entity, including `Pos` if it exists
+fn scale_entity_tags(tags: &mut HashMap<String, Value>, factor: f64) {
+    if let Some(Value::List(pos)) = tags.get_mut("Pos") {
+        if let [Value::Double(x), Value::Double(y), Value::Double(z)] = pos.as_mut_slice() {
+            [*x, *y, *z] = [*x * factor, *y * factor, *z * factor];
+        }
+    }
+    // hanging entities stay at the center block of the scaled cube
+    if let (Some(Value::Int(x)), Some(Value::Int(y)), Some(Value::Int(z))) =
+        (tags.get("TileX"), tags.get("TileY"), tags.get("TileZ"))
+    {
+        let center = [*x, *y, *z].map(|v| (v as f64 + 0.5) * factor);
+        let [x, y, z] = Entity::block_pos_of(center);
+        tags.insert("TileX".to_string(), Value::Int(x));
+        tags.insert("TileY".to_string(), Value::Int(y));
+        tags.insert("TileZ".to_string(), Value::Int(z));
+    }
+    if let Some(Value::List(passengers)) = tags.get_mut("Passengers") {
+        for passenger in passengers {
+            if let Value::Compound(p) = passenger {
+                scale_entity_tags(p, factor);
+            }
+        }
+    }
+}
+
+/// How [`Region::downscale`] chooses one block for a cell of blocks
+#[derive(Debug, Clone)]
+pub enum DownscaleStrategy {
+    /// The most common block of the cell. Air and structure void are ignored unless the cell has
+    /// nothing else. Ties are broken by the order in palette.
+    Majority,
+    /// The most common block of the cell, air is counted like other blocks. Structure void is
+    /// still ignored unless the cell has nothing else.
+    MajorityWithAir,
+    /// The first block of the list that exists in the cell. Cells without any of them use
+    /// `Majority`.
+    Priority(Vec<Block>),
+}
+
+fn scaled_shape(shape: [i32; 3], factor: u32) -> Result<[i32; 3], Error> {
+    let scaled = shape.map(|s| s as u64 * factor as u64);
+    if scaled.iter().any(|s| *s > i32::MAX as u64) {
+        return Err(Error::SizeTooLarge {
+            size: scaled,
+            max_size: [i32::MAX as u64; 3],
+        });
+    }
+    return Ok(scaled.map(|s| s as i32));
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Replicate every block into a `factor`×`factor`×`factor` cube. Biomes, block entities and
+    /// pending ticks are copied to every block of the cube, entity positions are multiplied by `factor`.
+    /// `offset` is not changed. Returns `SizeTooLarge` if the scaled shape doesn't fit in `i32`.
+    ///
+    /// # Panics
+    /// If `factor` is 0.
+    pub fn scale(&mut self, factor: u32) -> Result<(), Error> {
+        assert!(factor > 0, "Scale factor must be positive");
+        if factor == 1 {
+            return Ok(());
+        }
+        let new_shape = scaled_shape(self.shape(), factor)?;
+        let f = factor as usize;
+        let old = &self.array_yzx;
+        self.array_yzx = Array3::from_shape_fn(
+            (
+                new_shape[1] as usize,
+                new_shape[2] as usize,
+                new_shape[0] as usize,
+            ),
+            |(y, z, x)| old[[y / f, z / f, x / f]],
+        );
+        if let Some(biomes) = &self.biomes {
+            self.biomes = Some(Array3::from_shape_fn(
+                self.array_yzx.raw_dim(),
+                |(y, z, x)| biomes[[y / f, z / f, x / f]],
+            ));
+        }
+
+        let cube_offsets: Vec<[i32; 3]> = (0..factor as i32)
+            .flat_map(|y| {
+                (0..factor as i32).flat_map(move |z| (0..factor as i32).map(move |x| [x, y, z]))
+            })
+            .collect();
+        let scale_pos = |pos: &[i32; 3], d: &[i32; 3]| -> [i32; 3] {
+            [0, 1, 2].map(|dim| pos[dim] * factor as i32 + d[dim])
+        };
+
+        let mut block_entities: HashMap<[i32; 3], BlockEntity> =
+            HashMap::with_capacity(self.block_entities.len() * cube_offsets.len());
+        for (pos, be) in &self.block_entities {
+            for d in &cube_offsets {
+                block_entities.insert(scale_pos(pos, d), be.clone());
+            }
+        }
+        self.block_entities = block_entities;
+
+        let mut pending_ticks: HashMap<[i32; 3], Vec<PendingTick>> =
+            HashMap::with_capacity(self.pending_ticks.len() * cube_offsets.len());
+        for (pos, ticks) in &self.pending_ticks {
+            for d in &cube_offsets {
+                pending_ticks.insert(scale_pos(pos, d), ticks.clone());
+            }
+        }
+        self.pending_ticks = pending_ticks;
+
+        for entity in &mut self.entities {
+            scale_entity(entity, factor as f64);
+        }
+        return Ok(());
+    }
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Merge every `factor`×`factor`×`factor` cell into one block chosen by `strategy`. The shape
+    /// is divided by `factor` and rounded up, so cells at the upper edges may be smaller. Entity
+    /// positions are divided by `factor`, while block entities and pending ticks are removed,
+    /// because merged blocks rarely keep their data. `offset` is not changed.
+    ///
+    /// # Panics
+    /// If `factor` is 0.
+    pub fn downscale(&mut self, factor: u32, strategy: &DownscaleStrategy) {
+        assert!(factor > 0, "Downscale factor must be positive");
+        if factor == 1 {
+            return;
+        }
+        let f = factor as usize;
+        let shape = self.shape().map(|s| s as usize);
+        let new_shape = shape.map(|s| s.div_ceil(f));
+
+        // 0 means highest priority, blocks not in the priority list get `usize::MAX`
+        let priorities: Vec<usize> = self
+            .palette
+            .iter()
+            .map(|blk| match strategy {
+                DownscaleStrategy::Priority(list) => {
+                    list.iter().position(|b| b == blk).unwrap_or(usize::MAX)
+                }
+                _ => usize::MAX,
+            })
+            .collect();
+        let ignored: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| match strategy {
+                DownscaleStrategy::MajorityWithAir => blk.is_structure_void(),
+                _ => blk.is_air() || blk.is_structure_void(),
+            })
+            .collect();
+
+        let mut counts: Vec<u64> = vec![0; self.palette.len()];
+        let mut touched: Vec<u16> = Vec::new();
+        let old = &self.array_yzx;
+        let new_array = Array3::from_shape_fn(
+            (new_shape[1], new_shape[2], new_shape[0]),
+            |(cy, cz, cx)| {
+                touched.clear();
+                for y in cy * f..shape[1].min((cy + 1) * f) {
+                    for z in cz * f..shape[2].min((cz + 1) * f) {
+                        for x in cx * f..shape[0].min((cx + 1) * f) {
+                            let idx = old[[y, z, x]];
+                            if let Some(c) = counts.get_mut(idx as usize) {
+                                if *c == 0 {
+                                    touched.push(idx);
+                                }
+                                *c += 1;
+                            }
+                        }
+                    }
+                }
+                // (higher priority, not ignored, more blocks, lower palette index)
+                let best = touched
+                    .iter()
+                    .copied()
+                    .max_by_key(|idx| {
+                        let i = *idx as usize;
+                        (
+                            std::cmp::Reverse(priorities[i]),
+                            !ignored[i],
+                            counts[i],
+                            std::cmp::Reverse(i),
+                        )
+                    })
+                    .unwrap_or(old[[cy * f, cz * f, cx * f]]);
+                for idx in &touched {
+                    counts[*idx as usize] = 0;
+                }
+                best
+            },
+        );
+        self.array_yzx = new_array;
+        // biome of a cell is the one at its lower corner
+        if let Some(biomes) = &self.biomes {
+            self.biomes = Some(Array3::from_shape_fn(
+                self.array_yzx.raw_dim(),
+                |(y, z, x)| biomes[[y * f, z * f, x * f]],
+            ));
+        }
+
+        self.block_entities.clear();
+        self.pending_ticks.clear();
+        let factor = factor as f64;
+        for entity in &mut self.entities {
+            scale_entity(entity, 1.0 / factor);
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Scale all regions with [`Region::scale`], region offsets are multiplied by `factor` so that
+    /// regions keep their layout. Builders use it to make giant versions of statues.
+    ///
+    /// # Panics
+    /// If `factor` is 0.
+    pub fn scale(&mut self, factor: u32) -> Result<(), Error> {
+        for reg in &self.regions {
+            scaled_shape(reg.shape(), factor)?;
+            // the upper corner must fit too
+            let upper: [i32; 3] =
+                std::array::from_fn(|dim| reg.offset[dim].abs() + reg.shape()[dim]);
+            scaled_shape(upper, factor)?;
+        }
+        for reg in &mut self.regions {
+            reg.scale(factor)?;
+            reg.offset = reg.offset.map(|o| o * factor as i32);
+        }
+        return Ok(());
+    }
+
+    /// Downscale all regions with [`Region::downscale`], region offsets are divided by `factor`
+    /// and rounded down. Useful to make low-detail previews or map art sources of large builds.
+    ///
+    /// # Panics
+    /// If `factor` is 0.
+    pub fn downscale(&mut self, factor: u32, strategy: &DownscaleStrategy) {
+        for reg in &mut self.regions {
+            reg.downscale(factor, strategy);
+            reg.offset = reg.offset.map(|o| o.div_euclid(factor as i32));
+        }
+    }
+}
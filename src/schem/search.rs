@@ -0,0 +1,244 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::block::{Block, BlockIdParseError};
+use crate::region::Region;
+use crate::schem::Schematic;
+
+/// Condition on blocks used by [`Schematic::find_blocks`]. It's tested once for each block in
+/// palette instead of each block in region.
+#[derive(Debug, Clone)]
+pub enum BlockMatcher {
+    /// Same namespace and id, and every property of the pattern has the same value, so `chest`
+    /// matches chests in any direction, while `chest[facing=north]` only matches those facing north.
+    /// Empty namespace is treated as `minecraft`.
+    Block(Block),
+    /// Exactly the same block, including all properties
+    Exact(Block),
+    /// Matches if any of the matchers matches
+    AnyOf(Vec<BlockMatcher>),
+    /// Matches if all of the matchers match
+    AllOf(Vec<BlockMatcher>),
+    /// Matches if the inner matcher doesn't match
+    Not(Box<BlockMatcher>),
+    /// Any block other than air, cave air, void air and structure void, like `#existing` of
+    /// WorldEdit
+    Existing,
+    /// Custom condition
+    Predicate(fn(&Block) -> bool),
+}
+
+fn same_namespace(a: &str, b: &str) -> bool {
+    fn normalize(ns: &str) -> &str {
+        return if ns.is_empty() { "minecraft" } else { ns };
+    }
+    return normalize(a) == normalize(b);
+}
+
+impl BlockMatcher {
+    /// Parse a pattern like `chest` or `minecraft:chest[facing=north]` as [`BlockMatcher::Block`]
+    pub fn from_id(pattern: &str) -> Result<BlockMatcher, BlockIdParseError> {
+        return Ok(BlockMatcher::Block(Block::from_id(pattern)?));
+    }
+
+    /// Parse a mask string of WorldEdit, like `#existing,!minecraft:air stone[axis=y]`. Masks
+    /// separated by spaces must all match, and masks separated by commas match if any of them
+    /// matches. A mask is a block id, `#existing`, or another mask prefixed by `!` to negate it.
+    pub fn parse_mask(mask: &str) -> Result<BlockMatcher, MaskParseError> {
+        let all: Vec<&str> = split_outside_brackets(mask, |ch| ch.is_whitespace())
+            .into_iter()
+            .filter(|term| !term.is_empty())
+            .collect();
+        if all.is_empty() {
+            return Err(MaskParseError::EmptyMask);
+        }
+        let mut all_of = Vec::with_capacity(all.len());
+        for term in all {
+            let mut any_of = Vec::new();
+            for item in split_outside_brackets(term, |ch| ch == ',') {
+                any_of.push(parse_single_mask(item, term)?);
+            }
+            all_of.push(if any_of.len() == 1 {
+                any_of.pop().unwrap()
+            } else {
+                BlockMatcher::AnyOf(any_of)
+            });
+        }
+        return Ok(if all_of.len() == 1 {
+            all_of.pop().unwrap()
+        } else {
+            BlockMatcher::AllOf(all_of)
+        });
+    }
+
+    /// Whether `block` satisfies this matcher
+    pub fn matches(&self, block: &Block) -> bool {
+        return match self {
+            BlockMatcher::Block(pattern) => {
+                same_namespace(&pattern.namespace, &block.namespace)
+                    && pattern.id == block.id
+                    && pattern
+                        .attributes
+                        .iter()
+                        .all(|(key, value)| block.attributes.get(key) == Some(value))
+            }
+            BlockMatcher::Exact(pattern) => {
+                same_namespace(&pattern.namespace, &block.namespace)
+                    && pattern.id == block.id
+                    && pattern.attributes == block.attributes
+            }
+            BlockMatcher::AnyOf(matchers) => matchers.iter().any(|m| m.matches(block)),
+            BlockMatcher::AllOf(matchers) => matchers.iter().all(|m| m.matches(block)),
+            BlockMatcher::Not(matcher) => !matcher.matches(block),
+            BlockMatcher::Existing => {
+                !(same_namespace(&block.namespace, "minecraft")
+                    && ["air", "cave_air", "void_air", "structure_void"]
+                        .contains(&block.id.as_str()))
+            }
+            BlockMatcher::Predicate(pred) => pred(block),
+        };
+    }
+}
+
+/// Error of [`BlockMatcher::parse_mask`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskParseError {
+    /// The mask contains nothing but spaces
+    EmptyMask,
+    /// Nothing between two commas, or after `!`
+    EmptyItem { mask: String },
+    /// A tag like `#solid` that is not supported
+    UnknownTag { tag: String },
+    /// Failed to parse a block id
+    InvalidBlock {
+        block: String,
+        error: BlockIdParseError,
+    },
+}
+
+impl Display for MaskParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            MaskParseError::EmptyMask => write!(f, "mask is empty"),
+            MaskParseError::EmptyItem { mask } => {
+                write!(f, "mask \"{mask}\" contains an empty item")
+            }
+            MaskParseError::UnknownTag { tag } => {
+                write!(f, "unknown tag \"{tag}\", supported tags: #existing")
+            }
+            MaskParseError::InvalidBlock { block, error } => {
+                write!(f, "invalid block \"{block}\" in mask, detail: {error}")
+            }
+        };
+    }
+}
+
+impl std::error::Error for MaskParseError {}
+
+impl FromStr for BlockMatcher {
+    type Err = MaskParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return BlockMatcher::parse_mask(s);
+    }
+}
+
+/// Split at characters accepted by `is_separator`, except those inside brackets of block states
+pub(crate) fn split_outside_brackets(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut begin = 0;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ if depth <= 0 && is_separator(ch) => {
+                result.push(&text[begin..idx]);
+                begin = idx + ch.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    result.push(&text[begin..]);
+    return result;
+}
+
+/// Parse an item between commas, `term` is only used in errors
+fn parse_single_mask(item: &str, term: &str) -> Result<BlockMatcher, MaskParseError> {
+    if item.is_empty() {
+        return Err(MaskParseError::EmptyItem {
+            mask: term.to_string(),
+        });
+    }
+    if let Some(inner) = item.strip_prefix('!') {
+        if inner.is_empty() {
+            return Err(MaskParseError::EmptyItem {
+                mask: term.to_string(),
+            });
+        }
+        return Ok(BlockMatcher::Not(Box::new(parse_single_mask(inner, term)?)));
+    }
+    if let Some(tag) = item.strip_prefix('#') {
+        return match tag {
+            "existing" => Ok(BlockMatcher::Existing),
+            _ => Err(MaskParseError::UnknownTag {
+                tag: item.to_string(),
+            }),
+        };
+    }
+    return BlockMatcher::from_id(item).map_err(|error| MaskParseError::InvalidBlock {
+        block: item.to_string(),
+        error,
+    });
+}
+
+impl Region {
+    /// Relative positions of all blocks matched by `matcher`, in y, z, x order
+    pub fn find_blocks<'a>(
+        &'a self,
+        matcher: &BlockMatcher,
+    ) -> impl Iterator<Item = [i32; 3]> + 'a {
+        let matched: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| matcher.matches(blk))
+            .collect();
+        return self
+            .array_yzx
+            .indexed_iter()
+            .filter(move |(_, idx)| matched.get(**idx as usize).copied().unwrap_or(false))
+            .map(|((y, z, x), _)| [x as i32, y as i32, z as i32]);
+    }
+}
+
+impl Schematic {
+    /// Find all blocks matched by `matcher`. Items are `(region index, relative position)`, add
+    /// offset of the region to get the global position.
+    pub fn find_blocks<'a>(
+        &'a self,
+        matcher: &'a BlockMatcher,
+    ) -> impl Iterator<Item = (usize, [i32; 3])> + 'a {
+        return self
+            .regions
+            .iter()
+            .enumerate()
+            .flat_map(move |(idx, reg)| reg.find_blocks(matcher).map(move |pos| (idx, pos)));
+    }
+}
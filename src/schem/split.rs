@@ -0,0 +1,161 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use crate::region::{BlockBox, Region, SpatialIndex};
+use crate::schem::Schematic;
+use ndarray::s;
+
+/// A piece of schematic made by [`Schematic::split`]
+#[derive(Debug, Clone)]
+pub struct SchematicPiece {
+    /// Index of this piece in the grid, xyz
+    pub index: [i32; 3],
+    /// Offset of this piece in the whole schematic
+    pub offset: [i32; 3],
+    /// Parts of regions inside this piece, with offsets relative to `offset`
+    pub schematic: Schematic,
+}
+
+#[allow(dead_code)]
+impl Region {
+    /// Copy the part of region inside `g_box`(global coordinates) into a new region with the same
+    /// name. Entities are kept if their block position is inside. Returns `None` if the region
+    /// doesn't intersect with `g_box`.
+    pub fn cropped(&self, g_box: &BlockBox) -> Option<Region> {
+        if !self.global_box().intersects(g_box) {
+            return None;
+        }
+        return self.cropped_indexed(&self.spatial_index(16), g_box);
+    }
+
+    /// Like [`Region::cropped`], but looks up entities and block entities in `index`, so that
+    /// cropping many boxes out of one region doesn't scan all of them every time.
+    pub(crate) fn cropped_indexed(
+        &self,
+        index: &SpatialIndex<'_>,
+        g_box: &BlockBox,
+    ) -> Option<Region> {
+        let own_box = self.global_box();
+        if !own_box.intersects(g_box) {
+            return None;
+        }
+        let lower: [i32; 3] =
+            std::array::from_fn(|dim| own_box.lower[dim].max(g_box.lower[dim]) - self.offset[dim]);
+        let upper: [i32; 3] =
+            std::array::from_fn(|dim| own_box.upper[dim].min(g_box.upper[dim]) - self.offset[dim]);
+        let r_box = BlockBox { lower, upper };
+        let [x0, y0, z0] = lower.map(|v| v as usize);
+        let [x1, y1, z1] = upper.map(|v| v as usize + 1);
+
+        let mut result = Region::new();
+        result.name = self.name.clone();
+        result.offset = std::array::from_fn(|dim| self.offset[dim] + lower[dim]);
+        result.negative_size = self.negative_size;
+        result.palette = self.palette.clone();
+        result.array_yzx = self.array_yzx.slice(s![y0..y1, z0..z1, x0..x1]).to_owned();
+        if let Some(biomes) = &self.biomes {
+            result.biomes = Some(biomes.slice(s![y0..y1, z0..z1, x0..x1]).to_owned());
+            result.biome_palette = self.biome_palette.clone();
+        }
+        let shift =
+            |pos: &[i32; 3]| -> [i32; 3] { std::array::from_fn(|dim| pos[dim] - lower[dim]) };
+        for (pos, be) in index.block_entities_in(&r_box) {
+            result.block_entities.insert(shift(&pos), be.clone());
+        }
+        for (pos, ticks) in &self.pending_ticks {
+            if r_box.contains(*pos) {
+                result.pending_ticks.insert(shift(pos), ticks.clone());
+            }
+        }
+        for entity in index.entities_in(&r_box) {
+            let mut entity = entity.clone();
+            entity.pos_shift(lower.map(|v| -v));
+            result.entities.push(entity);
+        }
+        // indices are copied from self, so they never exceed the palette
+        let _ = result.shrink_palette();
+        return Some(result);
+    }
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Chop the schematic into a grid of boxes no larger than `grid_size`, starting from the lower
+    /// corner of all regions. Every piece keeps the parts of all regions inside its box, so it can be loaded by
+    /// litematica or pasted under size limits of servers. Empty boxes are skipped, and pieces are
+    /// ordered x-major, then y, then z. Names of pieces get the grid index appended, like
+    /// `name_1_0_2`. Returns `InvalidValue` if `grid_size` is not positive.
+    pub fn split(&self, grid_size: [i32; 3]) -> Result<Vec<SchematicPiece>, Error> {
+        if grid_size.iter().any(|s| *s <= 0) {
+            return Err(Error::InvalidValue {
+                tag_path: "grid_size".to_string(),
+                error: format!("grid_size should be positive, but found {:?}", grid_size),
+            });
+        }
+        let Some(first) = self.regions.first() else {
+            return Ok(Vec::new());
+        };
+        let mut lower = first.offset;
+        let mut upper = first.global_box().upper;
+        for reg in &self.regions {
+            let g_box = reg.global_box();
+            for dim in 0..3 {
+                lower[dim] = lower[dim].min(g_box.lower[dim]);
+                upper[dim] = upper[dim].max(g_box.upper[dim]);
+            }
+        }
+        let counts: [i32; 3] =
+            std::array::from_fn(|dim| (upper[dim] - lower[dim] + grid_size[dim]) / grid_size[dim]);
+
+        let indices: Vec<SpatialIndex<'_>> = self
+            .regions
+            .iter()
+            .map(|reg| reg.spatial_index(16))
+            .collect();
+        let mut pieces = Vec::new();
+        for ix in 0..counts[0] {
+            for iy in 0..counts[1] {
+                for iz in 0..counts[2] {
+                    let index = [ix, iy, iz];
+                    let offset: [i32; 3] =
+                        std::array::from_fn(|dim| lower[dim] + index[dim] * grid_size[dim]);
+                    let cell = BlockBox::from_pos_shape(offset, grid_size);
+                    let mut piece = Schematic::new();
+                    for (reg, index) in self.regions.iter().zip(&indices) {
+                        if let Some(mut part) = reg.cropped_indexed(index, &cell) {
+                            part.offset = std::array::from_fn(|dim| part.offset[dim] - offset[dim]);
+                            piece.regions.push(part);
+                        }
+                    }
+                    if piece.regions.is_empty() {
+                        continue;
+                    }
+                    piece.metadata = self.metadata.clone();
+                    piece.metadata.name = format!("{}_{ix}_{iy}_{iz}", self.metadata.name);
+                    pieces.push(SchematicPiece {
+                        index,
+                        offset,
+                        schematic: piece,
+                    });
+                }
+            }
+        }
+        return Ok(pieces);
+    }
+}
@@ -0,0 +1,145 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::region::{BlockBox, Region, WorldSlice};
+use crate::schem::Schematic;
+
+/// Statistics of a region, made by [`Region::stats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionStats {
+    pub name: String,
+    pub shape: [i32; 3],
+    pub volume: u64,
+    /// Count of blocks, air is not counted
+    pub total_blocks: u64,
+    pub block_entities: usize,
+    pub entities: usize,
+    pub pending_ticks: usize,
+    /// Count of each kind of block in full id, sorted by count in descending order. Blocks that
+    /// are in palette but not used are skipped.
+    pub block_counts: Vec<(String, u64)>,
+    /// See [`Region::non_air_bounding_box`]
+    pub non_air_bounding_box: Option<BlockBox>,
+}
+
+impl Region {
+    /// Count of each block in palette, indexed by block index. Indices out of palette are not
+    /// counted.
+    pub fn palette_counts(&self) -> Vec<u64> {
+        let mut counts = vec![0; self.palette.len()];
+        for idx in self.array_yzx.iter() {
+            if let Some(c) = counts.get_mut(*idx as usize) {
+                *c += 1;
+            }
+        }
+        return counts;
+    }
+
+    /// Count of each kind of block, including air and structure void. Blocks that are in palette
+    /// but not used are skipped.
+    pub fn block_counts(&self) -> Vec<(&Block, u64)> {
+        return self
+            .palette
+            .iter()
+            .zip(self.palette_counts())
+            .filter(|(_, count)| *count > 0)
+            .collect();
+    }
+
+    /// The smallest box in relative coordinates containing all blocks other than air and structure
+    /// void. Returns `None` if there is no such block.
+    pub fn non_air_bounding_box(&self) -> Option<BlockBox> {
+        let is_empty: Vec<bool> = self
+            .palette
+            .iter()
+            .map(|blk| blk.is_air() || blk.is_structure_void())
+            .collect();
+        let mut result: Option<BlockBox> = None;
+        for ((y, z, x), idx) in self.array_yzx.indexed_iter() {
+            if is_empty.get(*idx as usize).copied().unwrap_or(true) {
+                continue;
+            }
+            let pos = [x as i32, y as i32, z as i32];
+            match &mut result {
+                Some(bbox) => {
+                    for (dim, p) in pos.into_iter().enumerate() {
+                        bbox.lower[dim] = bbox.lower[dim].min(p);
+                        bbox.upper[dim] = bbox.upper[dim].max(p);
+                    }
+                }
+                None => {
+                    result = Some(BlockBox {
+                        lower: pos,
+                        upper: pos,
+                    })
+                }
+            }
+        }
+        return result;
+    }
+
+    /// Compute statistics of this region
+    pub fn stats(&self) -> RegionStats {
+        let mut block_counts: Vec<(String, u64)> = self
+            .block_counts()
+            .into_iter()
+            .map(|(blk, count)| (blk.full_id(), count))
+            .collect();
+        block_counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        return RegionStats {
+            name: self.name.clone(),
+            shape: self.shape(),
+            volume: self.volume(),
+            total_blocks: self.total_blocks(false),
+            block_entities: self.block_entities.len(),
+            entities: self.entities.len(),
+            pending_ticks: self.pending_ticks.values().map(|ticks| ticks.len()).sum(),
+            block_counts,
+            non_air_bounding_box: self.non_air_bounding_box(),
+        };
+    }
+}
+
+impl Schematic {
+    /// Statistics of every region
+    pub fn region_stats(&self) -> Vec<RegionStats> {
+        return self.regions.iter().map(|reg| reg.stats()).collect();
+    }
+
+    /// The smallest box in global coordinates containing all blocks other than air and structure
+    /// void of all regions. Returns `None` if there is no such block.
+    pub fn non_air_bounding_box(&self) -> Option<BlockBox> {
+        let mut result: Option<BlockBox> = None;
+        for reg in &self.regions {
+            let Some(bbox) = reg.non_air_bounding_box() else {
+                continue;
+            };
+            let lower = std::array::from_fn(|dim| bbox.lower[dim] + reg.offset[dim]);
+            let upper = std::array::from_fn(|dim| bbox.upper[dim] + reg.offset[dim]);
+            result = Some(match result {
+                Some(r) => BlockBox::new(
+                    std::array::from_fn(|dim| r.lower[dim].min(lower[dim])),
+                    std::array::from_fn(|dim| r.upper[dim].max(upper[dim])),
+                ),
+                None => BlockBox { lower, upper },
+            });
+        }
+        return result;
+    }
+}
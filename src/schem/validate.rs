@@ -0,0 +1,309 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::block::Block;
+use crate::block_entity::block_entity_id_of;
+use crate::region::{Region, WorldSlice};
+use crate::schem::{DataVersion, Schematic};
+use fastnbt::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A problem found by [`Schematic::validate`]. `region` is the index of region.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// A block index in `array_yzx` is not less than the palette length
+    BlockIndexOutOfRange {
+        region: usize,
+        pos: [i32; 3],
+        index: u16,
+        palette_len: usize,
+    },
+    /// A block entity is outside of the region
+    BlockEntityOutOfRegion { region: usize, pos: [i32; 3] },
+    /// A block entity is attached to a block that has no block entity, or to a block with
+    /// another kind of block entity. `expected_id` is `None` if the block has no block entity.
+    BlockEntityOnWrongBlock {
+        region: usize,
+        pos: [i32; 3],
+        block: Block,
+        block_entity_id: Option<String>,
+        expected_id: Option<&'static str>,
+    },
+    /// An entity is outside of the region
+    EntityOutOfRegion {
+        region: usize,
+        entity_index: usize,
+        position: [f64; 3],
+    },
+    /// A pending tick is outside of the region
+    PendingTickOutOfRegion { region: usize, pos: [i32; 3] },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ValidationIssue::BlockIndexOutOfRange { region, pos, index, palette_len }
+            => write!(f, "Region {region}: block index {index} at {pos:?} is out of range, the palette has {palette_len} blocks"),
+            ValidationIssue::BlockEntityOutOfRegion { region, pos }
+            => write!(f, "Region {region}: block entity at {pos:?} is outside of the region"),
+            ValidationIssue::BlockEntityOnWrongBlock { region, pos, block, block_entity_id, expected_id }
+            => write!(f, "Region {region}: block entity {block_entity_id:?} at {pos:?} is attached to {block}, which expects {expected_id:?}"),
+            ValidationIssue::EntityOutOfRegion { region, entity_index, position }
+            => write!(f, "Region {region}: entity {entity_index} at {position:?} is outside of the region"),
+            ValidationIssue::PendingTickOutOfRegion { region, pos }
+            => write!(f, "Region {region}: pending tick at {pos:?} is outside of the region"),
+        };
+    }
+}
+
+/// Result of [`Schematic::validate`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true if no issue is found
+    pub fn is_valid(&self) -> bool {
+        return self.issues.is_empty();
+    }
+}
+
+/// Block entity ids that were renamed, as (current id, old id)
+const RENAMED_BLOCK_ENTITIES: &[(&str, &str)] = &[("brushable_block", "suspicious_sand")];
+
+/// Blocks that had block entities before 1.13
+const LEGACY_BLOCK_ENTITY_BLOCKS: &[&str] = &["flower_pot", "note_block"];
+
+fn inside(pos: [i32; 3], shape: [i32; 3]) -> bool {
+    return (0..3).all(|dim| pos[dim] >= 0 && pos[dim] < shape[dim]);
+}
+
+/// Id of block entity without namespace. Litematica and vanilla structure use `id`, while
+/// `.schem` uses `Id`.
+fn block_entity_id(tags: &HashMap<String, Value>) -> Option<String> {
+    for key in ["id", "Id"] {
+        if let Some(Value::String(id)) = tags.get(key) {
+            return Some(id.strip_prefix("minecraft:").unwrap_or(id).to_string());
+        }
+    }
+    return None;
+}
+
+/// A block entity that doesn't fit the block at its position, found by
+/// [`Region::check_block_entity_bindings`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEntityMismatch {
+    pub pos: [i32; 3],
+    pub block: Block,
+    /// Id of block entity without namespace, `None` if it has no id
+    pub block_entity_id: Option<String>,
+    /// Id of block entity that the block expects, `None` if the block can't have block entity
+    pub expected_id: Option<&'static str>,
+}
+
+/// What [`Region::check_block_entity_bindings`] does with mismatched block entities
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlockEntityBindingFix {
+    /// Only report them
+    Keep,
+    /// Remove them
+    Prune,
+    /// Change their id to what the block expects, other tags are kept. Block entities on blocks
+    /// that can't have one are removed.
+    Retype,
+}
+
+fn block_entity_mismatch(
+    pos: [i32; 3],
+    block: &Block,
+    tags: &HashMap<String, Value>,
+    data_version: i32,
+) -> Option<BlockEntityMismatch> {
+    if !block.namespace.is_empty() && block.namespace != "minecraft" {
+        return None;
+    }
+    // block entity ids before 1.13 are not comparable with block ids, and some blocks lost
+    // their block entities in 1.13
+    let check_id = data_version >= DataVersion::Java_1_13 as i32;
+    let expected_id = block_entity_id_of(block);
+    let be_id = block_entity_id(tags);
+    let matches = match (expected_id, &be_id) {
+        (None, _) => !check_id && LEGACY_BLOCK_ENTITY_BLOCKS.contains(&block.id.as_str()),
+        (Some(expected), Some(found)) => {
+            !check_id
+                || expected == found
+                || RENAMED_BLOCK_ENTITIES.contains(&(expected, found.as_str()))
+        }
+        (Some(_), None) => true,
+    };
+    if matches {
+        return None;
+    }
+    return Some(BlockEntityMismatch {
+        pos,
+        block: block.clone(),
+        block_entity_id: be_id,
+        expected_id,
+    });
+}
+
+impl Region {
+    /// Find block entities sitting on blocks that can't have them, or on blocks with another kind
+    /// of block entity, like chest nbt on stone after replacing blocks. They are pruned or
+    /// re-typed according to `fix`, and returned sorted by position. Block entities outside of
+    /// the region are not checked, see [`Schematic::validate`].
+    pub fn check_block_entity_bindings(
+        &mut self,
+        data_version: i32,
+        fix: BlockEntityBindingFix,
+    ) -> Vec<BlockEntityMismatch> {
+        let mut mismatches = Vec::new();
+        for (pos, be) in &self.block_entities {
+            let Some(block) = self.block_at(*pos) else {
+                continue;
+            };
+            if let Some(mismatch) = block_entity_mismatch(*pos, block, &be.tags, data_version) {
+                mismatches.push(mismatch);
+            }
+        }
+        mismatches.sort_by_key(|m| [m.pos[1], m.pos[2], m.pos[0]]);
+
+        for mismatch in &mismatches {
+            match (fix, mismatch.expected_id) {
+                (BlockEntityBindingFix::Keep, _) => {}
+                (BlockEntityBindingFix::Retype, Some(expected)) => {
+                    let be = self.block_entities.get_mut(&mismatch.pos).unwrap();
+                    let key = if be.tags.contains_key("Id") {
+                        "Id"
+                    } else {
+                        "id"
+                    };
+                    be.tags.insert(
+                        key.to_string(),
+                        Value::String(format!("minecraft:{expected}")),
+                    );
+                }
+                _ => {
+                    self.block_entities.remove(&mismatch.pos);
+                }
+            }
+        }
+        return mismatches;
+    }
+
+    /// Check this region as the `region_index`-th region, see [`Schematic::validate`].
+    pub fn validate(&self, region_index: usize, data_version: i32, report: &mut ValidationReport) {
+        let shape = self.shape();
+        let palette_len = self.palette.len();
+        for ((y, z, x), index) in self.array_yzx.indexed_iter() {
+            if *index as usize >= palette_len {
+                report.issues.push(ValidationIssue::BlockIndexOutOfRange {
+                    region: region_index,
+                    pos: [x as i32, y as i32, z as i32],
+                    index: *index,
+                    palette_len,
+                });
+            }
+        }
+
+        let mut block_entities: Vec<_> = self.block_entities.iter().collect();
+        block_entities.sort_by_key(|(pos, _)| [pos[1], pos[2], pos[0]]);
+        for (pos, be) in block_entities {
+            let pos = *pos;
+            if !inside(pos, shape) {
+                report.issues.push(ValidationIssue::BlockEntityOutOfRegion {
+                    region: region_index,
+                    pos,
+                });
+                continue;
+            }
+            let index = self.array_yzx[[pos[1] as usize, pos[2] as usize, pos[0] as usize]];
+            let block = match self.palette.get(index as usize) {
+                Some(b) => b,
+                // reported as BlockIndexOutOfRange
+                None => continue,
+            };
+            if let Some(mismatch) = block_entity_mismatch(pos, block, &be.tags, data_version) {
+                report
+                    .issues
+                    .push(ValidationIssue::BlockEntityOnWrongBlock {
+                        region: region_index,
+                        pos,
+                        block: mismatch.block,
+                        block_entity_id: mismatch.block_entity_id,
+                        expected_id: mismatch.expected_id,
+                    });
+            }
+        }
+
+        for (entity_index, entity) in self.entities.iter().enumerate() {
+            let p = entity.position;
+            let is_inside = (0..3).all(|dim| p[dim] >= 0.0 && p[dim] < shape[dim] as f64);
+            if !is_inside {
+                report.issues.push(ValidationIssue::EntityOutOfRegion {
+                    region: region_index,
+                    entity_index,
+                    position: p,
+                });
+            }
+        }
+
+        let mut tick_positions: Vec<[i32; 3]> = self.pending_ticks.keys().copied().collect();
+        tick_positions.sort_by_key(|pos| [pos[1], pos[2], pos[0]]);
+        for pos in tick_positions {
+            if !inside(pos, shape) {
+                report.issues.push(ValidationIssue::PendingTickOutOfRegion {
+                    region: region_index,
+                    pos,
+                });
+            }
+        }
+    }
+}
+
+impl Schematic {
+    /// Check the schematic in memory for problems that strict loaders reject or that
+    /// minecraft can't handle: block indices out of palette, block entities and pending ticks
+    /// outside of regions, block entities on wrong blocks and entities outside of regions.
+    /// Block entity ids are only compared with blocks since `data_version` 1.13.
+    pub fn validate(&self, data_version: i32) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for (idx, region) in self.regions.iter().enumerate() {
+            region.validate(idx, data_version, &mut report);
+        }
+        return report;
+    }
+
+    /// Check block entities of all regions with [`Region::check_block_entity_bindings`], using
+    /// data version of the schematic. Returns region indices with the mismatches.
+    pub fn check_block_entity_bindings(
+        &mut self,
+        fix: BlockEntityBindingFix,
+    ) -> Vec<(usize, BlockEntityMismatch)> {
+        let data_version = self.metadata.mc_data_version;
+        let mut result = Vec::new();
+        for (idx, region) in self.regions.iter_mut().enumerate() {
+            for mismatch in region.check_block_entity_bindings(data_version, fix) {
+                result.push((idx, mismatch));
+            }
+        }
+        return result;
+    }
+}
@@ -16,37 +16,68 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::schem::progress::{report_single_region, ProgressReporter};
 use crate::schem::{
-    common, id_of_nbt_tag, MetaDataIR, VanillaStructureLoadOption, VanillaStructureSaveOption,
+    common, id_of_nbt_tag, AirPolicy, MetaDataIR, VanillaStructureLoadOption,
+    VanillaStructureSaveOption,
 };
+use std::cmp::{max, min};
 use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::fs::File;
 //use compress::zlib;
-use crate::error::Error;
+use crate::block::Block;
+use crate::block_entity::block_entity_id_of;
+#[cfg(feature = "fs")]
 use crate::error::Error::FileOpenError;
-use crate::region::{Entity, Region, WorldSlice};
+use crate::error::{
+    fix_block_index_out_of_range, fix_multiple_block_entity_in_one_pos, Error, ErrorHandleResult,
+    ErrorHandler, LoadWarning, MultipleBlockEntityFixMethod,
+};
+use crate::region::{BlockBox, Entity, Region, WorldSlice};
 use crate::schem::schem::{BlockEntity, Schematic, VanillaStructureMetaData};
 use crate::{unwrap_opt_tag, unwrap_tag};
 use fastnbt;
 use fastnbt::Value;
-use flate2::read::GzDecoder;
-use flate2::GzBuilder;
 
 #[allow(dead_code)]
 impl MetaDataIR {
     pub fn from_vanilla_structure(src: &VanillaStructureMetaData) -> MetaDataIR {
         let mut result = MetaDataIR::default();
         result.mc_data_version = src.data_version;
+        if let Some(author) = &src.author {
+            result.author = author.clone();
+        }
+        if let Some(name) = &src.name {
+            result.name = name.clone();
+        }
+        if let Some(description) = &src.description {
+            result.description = description.clone();
+        }
         return result;
     }
 
     pub fn to_vanilla_structure(&self) -> Result<VanillaStructureMetaData, Error> {
         let mut result = VanillaStructureMetaData::from_data_version_i32(self.mc_data_version)?;
         result.data_version = self.mc_data_version;
+        result.author = Some(self.author.clone());
+        result.name = Some(self.name.clone());
+        result.description = Some(self.description.clone());
         return Ok(result);
     }
 }
 
+/// Parse a list of blocks in `palette` or one of `palettes`
+fn parse_palette(palette_list: &[Value], tag_path: &str) -> Result<Vec<Block>, Error> {
+    let mut palette = Vec::with_capacity(palette_list.len());
+    for (idx, blk_tag) in palette_list.iter().enumerate() {
+        let tag_path = format!("{}[{}]", tag_path, idx);
+        let blk_comp = unwrap_tag!(blk_tag, Compound, HashMap::new(), &tag_path);
+        palette.push(common::parse_block(blk_comp, &tag_path)?);
+    }
+    return Ok(palette);
+}
+
 fn parse_size_tag(nbt: &HashMap<String, Value>) -> Result<[i32; 3], Error> {
     let size_list = unwrap_opt_tag!(nbt.get("size"), List, vec![], "/size");
 
@@ -70,23 +101,17 @@ fn parse_size_tag(nbt: &HashMap<String, Value>) -> Result<[i32; 3], Error> {
     return Ok(size);
 }
 
+/// The state is not checked with palette. `nbt` is ignored if `skip_block_entity` is true.
 fn parse_array_item(
     item: &Value,
     tag_path: &str,
-    palette_size: i32,
     region_size: [i32; 3],
+    skip_block_entity: bool,
 ) -> Result<(i32, [i32; 3], Option<BlockEntity>), Error> {
     let map = unwrap_tag!(item, Compound, HashMap::new(), tag_path);
 
     // parse state
     let state: i32 = *unwrap_opt_tag!(map.get("state"), Int, 0, &*format!("{}/state", tag_path));
-    if state < 0 || state >= palette_size {
-        return Err(Error::BlockIndexOutOfRange {
-            tag_path: format!("{}/state", tag_path),
-            index: state,
-            range: [0, palette_size],
-        });
-    }
 
     let pos_list = unwrap_opt_tag!(map.get("pos"), List, vec![], &*format!("{}/pos", tag_path));
 
@@ -122,6 +147,7 @@ fn parse_array_item(
 
     let nbt_comp;
     match map.get("nbt") {
+        Some(_) if skip_block_entity => return Ok((state, pos, None)),
         Some(nbt_comp_tmp) => nbt_comp = nbt_comp_tmp,
         None => return Ok((state, pos, None)),
     }
@@ -205,11 +231,25 @@ fn parse_entity(tag: &mut Value, tag_path: &str) -> Result<Entity, Error> {
         );
         entity.tags = nbt;
     }
+    entity.extract_passengers();
     return Ok(entity);
 }
 
+/// Used when the caller doesn't choose an [`ErrorHandler`]. Duplicated block entities are
+/// overwritten by the latter one silently, as vanilla does, other errors are not fixed.
+struct KeepLatterBlockEntityHandler {}
+
+impl ErrorHandler for KeepLatterBlockEntityHandler {
+    fn fix_multiple_block_entity_in_one_pos(
+        _error: &Error,
+    ) -> ErrorHandleResult<MultipleBlockEntityFixMethod> {
+        return ErrorHandleResult::HandledWithoutWarning(MultipleBlockEntityFixMethod::KeepLatter);
+    }
+}
+
 impl Schematic {
     /// Load vanilla structure from file
+    #[cfg(feature = "fs")]
     pub fn from_vanilla_structure_file(
         filename: &str,
         option: &VanillaStructureLoadOption,
@@ -221,7 +261,15 @@ impl Schematic {
             Err(e) => return Err(FileOpenError(e)),
         }
 
-        let mut decoder = GzDecoder::new(&mut file);
+        let mut decoder = common::decompress_reader(&mut file)?;
+        return Self::from_vanilla_structure_reader(&mut decoder, option);
+    }
+    /// Load vanilla structure from bytes, the content of a `.nbt` file. Compression is detected.
+    pub fn from_vanilla_structure_bytes(
+        bytes: &[u8],
+        option: &VanillaStructureLoadOption,
+    ) -> Result<(Schematic, VanillaStructureMetaData), Error> {
+        let mut decoder = common::decompress_reader(bytes)?;
         return Self::from_vanilla_structure_reader(&mut decoder, option);
     }
     /// Load vanilla structure from reader
@@ -229,26 +277,79 @@ impl Schematic {
         src: &mut dyn std::io::Read,
         option: &VanillaStructureLoadOption,
     ) -> Result<(Schematic, VanillaStructureMetaData), Error> {
-        let loaded_opt: Result<HashMap<String, Value>, fastnbt::error::Error> =
-            fastnbt::from_reader(src);
-        let nbt;
-        match loaded_opt {
-            Ok(loaded_nbt) => nbt = loaded_nbt,
-            Err(err) => return Err(Error::NBTReadError(err)),
-        }
+        let nbt = common::read_nbt_compound(src)?;
         return Self::from_vanilla_structure_nbt(nbt, option);
     }
 
     /// Load vanilla structure from nbt.
     pub fn from_vanilla_structure_nbt(
-        mut nbt: HashMap<String, Value>,
+        nbt: HashMap<String, Value>,
         option: &VanillaStructureLoadOption,
     ) -> Result<(Schematic, VanillaStructureMetaData), Error> {
+        let (schem, md, _) = Self::from_vanilla_structure_nbt_with_warnings::<
+            KeepLatterBlockEntityHandler,
+        >(nbt, option)?;
+        return Ok((schem, md));
+    }
+
+    /// Load vanilla structure from file, recoverable errors are fixed by `H` and returned as
+    /// warnings.
+    #[cfg(feature = "fs")]
+    pub fn from_vanilla_structure_file_with_warnings<H: ErrorHandler>(
+        filename: &str,
+        option: &VanillaStructureLoadOption,
+    ) -> Result<(Schematic, VanillaStructureMetaData, Vec<LoadWarning>), Error> {
+        let mut file = File::open(filename).map_err(FileOpenError)?;
+        let mut decoder = common::decompress_reader(&mut file)?;
+        return Self::from_vanilla_structure_reader_with_warnings::<H>(&mut decoder, option);
+    }
+    /// Load vanilla structure from bytes, recoverable errors are fixed by `H` and returned as
+    /// warnings.
+    pub fn from_vanilla_structure_bytes_with_warnings<H: ErrorHandler>(
+        bytes: &[u8],
+        option: &VanillaStructureLoadOption,
+    ) -> Result<(Schematic, VanillaStructureMetaData, Vec<LoadWarning>), Error> {
+        let mut decoder = common::decompress_reader(bytes)?;
+        return Self::from_vanilla_structure_reader_with_warnings::<H>(&mut decoder, option);
+    }
+    /// Load vanilla structure from reader, recoverable errors are fixed by `H` and returned as
+    /// warnings.
+    pub fn from_vanilla_structure_reader_with_warnings<H: ErrorHandler>(
+        src: &mut dyn std::io::Read,
+        option: &VanillaStructureLoadOption,
+    ) -> Result<(Schematic, VanillaStructureMetaData, Vec<LoadWarning>), Error> {
+        let nbt = common::read_nbt_compound(src)?;
+        return Self::from_vanilla_structure_nbt_with_warnings::<H>(nbt, option);
+    }
+
+    /// Load vanilla structure from nbt, recoverable errors are fixed by `H` and returned as
+    /// warnings.
+    pub fn from_vanilla_structure_nbt_with_warnings<H: ErrorHandler>(
+        mut nbt: HashMap<String, Value>,
+        option: &VanillaStructureLoadOption,
+    ) -> Result<(Schematic, VanillaStructureMetaData, Vec<LoadWarning>), Error> {
+        let mut warnings = Vec::new();
         let mut schem = Schematic::new();
 
         let mut md = VanillaStructureMetaData::default();
         {
             md.data_version = *unwrap_opt_tag!(nbt.get("DataVersion"), Int, 0, "/DataVersion");
+            if let Some(author) = nbt.get("author") {
+                md.author = Some(unwrap_tag!(author, String, "".to_string(), "/author").clone());
+            }
+            if let Some(custom) = nbt.get("Metadata") {
+                let custom = unwrap_tag!(custom, Compound, HashMap::new(), "/Metadata");
+                if let Some(name) = custom.get("Name") {
+                    md.name =
+                        Some(unwrap_tag!(name, String, "".to_string(), "/Metadata/Name").clone());
+                }
+                if let Some(description) = custom.get("Description") {
+                    md.description = Some(
+                        unwrap_tag!(description, String, "".to_string(), "/Metadata/Description")
+                            .clone(),
+                    );
+                }
+            }
             schem.metadata = MetaDataIR::from_vanilla_structure(&md);
         }
 
@@ -265,23 +366,59 @@ impl Schematic {
             return Err(err);
         }
         let region_size = region_size.unwrap();
+        let volume = region_size
+            .iter()
+            .fold(1u64, |v, d| v.saturating_mul(*d as u64));
+        if volume > option.max_volume {
+            return Err(Error::InvalidValue {
+                tag_path: "/size".to_string(),
+                error: format!(
+                    "Volume of size {region_size:?} is {volume}, which exceeds the limit {}",
+                    option.max_volume
+                ),
+            });
+        }
         region.reshape(&region_size);
 
         //parse block palette
         {
-            let palette_list = unwrap_opt_tag!(nbt.get("palette"), List, vec![], "/palette");
-
-            region.palette.reserve(palette_list.len());
-
-            for (idx, blk_tag) in palette_list.iter().enumerate() {
-                let tag_path = format!("/palette[{}]", idx);
-
-                let blk_comp = unwrap_tag!(blk_tag, Compound, HashMap::new(), &tag_path);
-                let blk = common::parse_block(blk_comp, &tag_path);
-                match blk {
-                    Err(err) => return Err(err),
-                    Ok(blk) => region.palette.push(blk),
+            // Region::new() puts air in palette, which would shift all indices
+            region.palette.clear();
+            if let Some(palettes) = nbt.get("palettes") {
+                let palettes = unwrap_tag!(palettes, List, vec![], "/palettes");
+                let mut variants: Vec<Vec<Block>> = Vec::with_capacity(palettes.len());
+                for (idx, palette_tag) in palettes.iter().enumerate() {
+                    let tag_path = format!("/palettes[{}]", idx);
+                    let palette_list = unwrap_tag!(palette_tag, List, vec![], &tag_path);
+                    let variant = parse_palette(palette_list, &tag_path)?;
+                    if idx > 0 && variant.len() != variants[0].len() {
+                        return Err(Error::InvalidValue {
+                            tag_path,
+                            error: format!(
+                                "Palette variant has {} blocks, but the first one has {}",
+                                variant.len(),
+                                variants[0].len()
+                            ),
+                        });
+                    }
+                    variants.push(variant);
+                }
+                if option.palette_variant >= variants.len() {
+                    return Err(Error::InvalidValue {
+                        tag_path: "/palettes".to_string(),
+                        error: format!(
+                            "Palette variant {} is out of range, the structure has {} variants",
+                            option.palette_variant,
+                            variants.len()
+                        ),
+                    });
                 }
+                variants.swap(0, option.palette_variant);
+                region.palette = variants.remove(0);
+                region.alternate_palettes = variants;
+            } else {
+                let palette_list = unwrap_opt_tag!(nbt.get("palette"), List, vec![], "/palette");
+                region.palette = parse_palette(palette_list, "/palette")?;
             }
         }
 
@@ -311,34 +448,81 @@ impl Schematic {
         {
             let blocks_list = unwrap_opt_tag!(nbt.get("blocks"), List, vec![], "/blocks");
 
+            let palette_size = region.palette.len() as i32;
+            // invalid states are fixed once per distinct value
+            let mut fixes: HashMap<i32, u16> = HashMap::new();
             for (idx, blk_item) in blocks_list.iter().enumerate() {
+                let tag_path = format!("/blocks[{}]", idx);
                 let blk_item = parse_array_item(
                     blk_item,
-                    &*format!("/blocks[{}]", idx),
-                    region.palette.len() as i32,
+                    &tag_path,
                     [region_size[0], region_size[1], region_size[2]],
+                    option.skip_block_entities,
                 );
-                let state;
+                let mut state;
                 let pos;
                 let block_entity_opt;
                 match blk_item {
                     Ok(unwrapped_tmp) => (state, pos, block_entity_opt) = unwrapped_tmp,
                     Err(e) => return Err(e),
                 }
+                if state < 0 || state >= palette_size {
+                    state = match fixes.get(&state) {
+                        Some(fixed) => *fixed as i32,
+                        None => {
+                            let err = Error::BlockIndexOutOfRange {
+                                tag_path: format!("{}/state", tag_path),
+                                index: state,
+                                range: [0, palette_size],
+                            };
+                            let fixed = fix_block_index_out_of_range::<H>(
+                                option.error_handler.as_ref(),
+                                &mut region.palette,
+                                &err,
+                            )
+                            .resolve(
+                                err,
+                                |f| format!("replaced by block index {f}"),
+                                &mut warnings,
+                            )?;
+                            fixes.insert(state, fixed);
+                            fixed as i32
+                        }
+                    };
+                }
 
                 let pos_ndarr = [pos[0] as usize, pos[1] as usize, pos[2] as usize];
                 region.array_yzx[Region::pos_xyz_to_yzx(&pos_ndarr)] = state as u16;
 
                 if let Some(block_entity) = block_entity_opt {
-                    region
-                        .block_entities
-                        .insert([pos[0], pos[1], pos[2]], block_entity);
+                    if region.block_entities.contains_key(&pos) {
+                        let err = Error::MultipleBlockEntityInOnePos {
+                            pos,
+                            latter_tag_path: format!("{}/nbt", tag_path),
+                        };
+                        let method = fix_multiple_block_entity_in_one_pos::<H>(
+                            option.error_handler.as_ref(),
+                            &mut region.palette,
+                            &err,
+                        )
+                        .resolve(
+                            err,
+                            |m| format!("{m:?}"),
+                            &mut warnings,
+                        )?;
+                        if method == MultipleBlockEntityFixMethod::KeepFormer {
+                            continue;
+                        }
+                    }
+                    region.block_entities.insert(pos, block_entity);
                 }
             }
         }
 
         // fill in entities
-        {
+        if option.skip_entities {
+            nbt.remove("entities");
+        } else {
             // unwrap the list
             let mut entity_list =
                 unwrap_opt_tag!(nbt.remove("entities"), List, vec![], "/entities");
@@ -352,13 +536,26 @@ impl Schematic {
             }
         }
 
-        {
-            let shrink_err = region.shrink_palette();
-            assert!(shrink_err.is_ok());
+        // blocks appended for background and fixes are the same in all variants
+        for variant in &mut region.alternate_palettes {
+            let len = variant.len();
+            variant.extend_from_slice(&region.palette[len..]);
         }
+        region.shrink_palette()?;
 
+        report_single_region(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            region.volume(),
+        )?;
         schem.regions.push(region);
-        return Ok((schem, md));
+        common::validate_palette_if(
+            option.validate_blocks,
+            md.data_version,
+            &schem.regions[0].palette,
+            "/palette",
+        )?;
+        return Ok((schem, md, warnings));
     }
 }
 
@@ -387,88 +584,436 @@ fn pos_to_nbt(pos: &[i32; 3]) -> Value {
     return Value::List(pos_list);
 }
 
+/// One piece of a schematic split for structure blocks, see
+/// `Schematic::to_nbt_vanilla_structure_pieces`
+#[derive(Debug, Clone)]
+pub struct StructurePiece {
+    /// Index of this piece in the grid of pieces, xyz
+    pub index: [i32; 3],
+    /// Offset of this piece in the whole schematic
+    pub offset: [i32; 3],
+    /// Shape of this piece
+    pub shape: [i32; 3],
+    /// Vanilla structure nbt of this piece
+    pub nbt: HashMap<String, Value>,
+}
+
+/// Describes a piece in the manifest written when splitting
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StructurePieceInfo {
+    pub file: String,
+    pub index: [i32; 3],
+    pub offset: [i32; 3],
+    pub size: [i32; 3],
+}
+
+/// Manifest written beside split `.nbt` files
+#[cfg(feature = "fs")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StructureManifest {
+    pub size: [i32; 3],
+    pub data_version: i32,
+    pub pieces: Vec<StructurePieceInfo>,
+}
+
+fn exceeds_max_size(shape: &[i32; 3], option: &VanillaStructureSaveOption) -> bool {
+    if let Some(max_size) = &option.max_size {
+        for dim in 0..3 {
+            if shape[dim] > max_size[dim] {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+/// Compress and write `nbt` to `dest`. With `max_file_size`, the file is compressed in memory
+/// first, and nothing is written if it's too large.
+#[cfg(feature = "fs")]
+fn write_structure_nbt(
+    dest: &mut dyn std::io::Write,
+    nbt: &HashMap<String, Value>,
+    option: &VanillaStructureSaveOption,
+    filename: Option<&str>,
+) -> Result<(), Error> {
+    let Some(max_file_size) = option.max_file_size else {
+        return common::write_compressed_nbt(
+            dest,
+            nbt,
+            option.compress_method,
+            option.compress_level,
+            filename,
+        );
+    };
+    let mut content = Vec::new();
+    common::write_compressed_nbt(
+        &mut content,
+        nbt,
+        option.compress_method,
+        option.compress_level,
+        filename,
+    )?;
+    if content.len() as u64 > max_file_size {
+        return Err(Error::FileTooLarge {
+            size: content.len() as u64,
+            max_size: max_file_size,
+        });
+    }
+    return dest
+        .write_all(&content)
+        .map_err(|e| Error::NBTWriteError(e.into()));
+}
+
+/// Save `nbt` to file at `path`. The file is not created if it exceeds `max_file_size`.
+#[cfg(feature = "fs")]
+fn save_structure_nbt_file(
+    path: &std::path::Path,
+    nbt: &HashMap<String, Value>,
+    option: &VanillaStructureSaveOption,
+) -> Result<(), Error> {
+    let filename = path.to_string_lossy();
+    let mut content = Vec::new();
+    if option.max_file_size.is_some() {
+        write_structure_nbt(&mut content, nbt, option, Some(&filename))?;
+    }
+    let mut file = match File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(Error::FileCreateError(e)),
+    };
+    if option.max_file_size.is_some() {
+        return std::io::Write::write_all(&mut file, &content)
+            .map_err(|e| Error::NBTWriteError(e.into()));
+    }
+    return write_structure_nbt(&mut file, nbt, option, Some(&filename));
+}
+
 #[allow(dead_code)]
 impl Schematic {
-    /// Save schematic to nbt as vanilla structure
+    /// Save schematic to nbt as vanilla structure. Returns `SizeTooLarge` if the schematic is
+    /// larger than `option.max_size`.
     pub fn to_nbt_vanilla_structure(
         &self,
         option: &VanillaStructureSaveOption,
     ) -> Result<HashMap<String, Value>, Error> {
-        let mut nbt: HashMap<String, Value> = HashMap::new();
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.to_nbt_vanilla_structure(option);
+        }
+        let selected = self.with_selected_palette_variant(option.palette_variant)?;
+        let this = selected.as_ref().unwrap_or(self);
+        let shape = this.shape();
+        if exceeds_max_size(&shape, option) {
+            let max_size = option.max_size.unwrap();
+            return Err(Error::SizeTooLarge {
+                size: [shape[0] as u64, shape[1] as u64, shape[2] as u64],
+                max_size: [max_size[0] as u64, max_size[1] as u64, max_size[2] as u64],
+            });
+        }
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            1,
+            this.volume(),
+        );
+        let nbt = this.to_nbt_vanilla_structure_unchecked(option, &mut reporter)?;
+        reporter.finish_region(this.volume())?;
+        return Ok(nbt);
+    }
 
+    /// Split schematic into a grid of pieces no larger than `option.max_size`(or
+    /// `STRUCTURE_BLOCK_MAX_SIZE` if it's `None`), and save each piece to nbt as vanilla
+    /// structure. Pieces are ordered x-major, then y, then z.
+    pub fn to_nbt_vanilla_structure_pieces(
+        &self,
+        option: &VanillaStructureSaveOption,
+    ) -> Result<Vec<StructurePiece>, Error> {
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
         {
-            let mut size = Vec::with_capacity(3);
-            for dim in 0..3 {
-                size.push(Value::Int(self.regions[0].shape()[dim]));
+            return converted.to_nbt_vanilla_structure_pieces(option);
+        }
+        let selected = self.with_selected_palette_variant(option.palette_variant)?;
+        let this = selected.as_ref().unwrap_or(self);
+        let max_size = option
+            .max_size
+            .unwrap_or(VanillaStructureSaveOption::STRUCTURE_BLOCK_MAX_SIZE);
+        for dim in 0..3 {
+            if max_size[dim] <= 0 {
+                return Err(Error::InvalidValue {
+                    tag_path: "VanillaStructureSaveOption.max_size".to_string(),
+                    error: format!("max_size should be positive, but found {:?}", max_size),
+                });
             }
-            nbt.insert(String::from("size"), Value::List(size));
         }
 
-        let (full_palette, luts_of_block_idx) = self.full_palette();
-        {
-            let mut nbt_palette = Vec::with_capacity(full_palette.len());
-            for (blk, _) in full_palette {
-                nbt_palette.push(Value::Compound(blk.to_nbt()));
+        let shape = this.shape();
+        let mut counts = [0; 3];
+        for dim in 0..3 {
+            counts[dim] = max(1, (shape[dim] + max_size[dim] - 1) / max_size[dim]);
+        }
+
+        let piece_count = (counts[0] * counts[1] * counts[2]) as usize;
+        let mut pieces = Vec::with_capacity(piece_count);
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            piece_count,
+            this.volume(),
+        );
+        for ix in 0..counts[0] {
+            for iy in 0..counts[1] {
+                for iz in 0..counts[2] {
+                    let index = [ix, iy, iz];
+                    let mut offset = [0; 3];
+                    let mut piece_shape = [0; 3];
+                    for dim in 0..3 {
+                        offset[dim] = index[dim] * max_size[dim];
+                        piece_shape[dim] = min(max_size[dim], shape[dim] - offset[dim]);
+                    }
+                    let piece = this.vanilla_structure_piece(offset, piece_shape)?;
+                    pieces.push(StructurePiece {
+                        index,
+                        offset,
+                        shape: piece_shape,
+                        nbt: piece.to_nbt_vanilla_structure_unchecked(option, &mut reporter)?,
+                    });
+                    reporter.finish_region(piece.volume())?;
+                }
             }
-            nbt.insert(String::from("palette"), Value::List(nbt_palette));
         }
+        return Ok(pieces);
+    }
 
-        let shape = self.shape();
+    /// Copy the part of schematic in box(`offset`, `shape`) into a new single-region schematic
+    fn vanilla_structure_piece(
+        &self,
+        offset: [i32; 3],
+        shape: [i32; 3],
+    ) -> Result<Schematic, Error> {
+        let mut region = Region::with_shape(shape);
+        region.name = self.regions.first().map_or(region.name, |r| r.name.clone());
+        region.palette.clear();
+        let void_id = region.find_or_append_to_palette(&Block::structure_void());
+        region.array_yzx.fill(void_id);
+
+        // (region index, block index in region) -> block index in piece
+        let mut lut: HashMap<(usize, u16), u16> = HashMap::new();
+        for x in 0..shape[0] {
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    let g_pos = [x + offset[0], y + offset[1], z + offset[2]];
+                    let reg_idx = match self.first_region_index_at(g_pos) {
+                        Some(idx) => idx,
+                        None => continue,
+                    };
+                    let reg = &self.regions[reg_idx];
+                    let r_pos = reg.global_pos_to_relative_pos(g_pos);
+                    let (blk_id, blk, be, _) = match reg.block_info_at(r_pos) {
+                        Some(info) => info,
+                        None => continue,
+                    };
+                    let piece_id = *lut
+                        .entry((reg_idx, blk_id))
+                        .or_insert_with(|| region.find_or_append_to_palette(blk));
+                    region.array_yzx[[y as usize, z as usize, x as usize]] = piece_id;
+                    if let Some(be) = be {
+                        region.block_entities.insert([x, y, z], be.clone());
+                    }
+                }
+            }
+        }
 
+        let piece_box = BlockBox::from_pos_shape(offset, shape);
+        for reg in &self.regions {
+            for entity in &reg.entities {
+                let g_pos: [i32; 3] =
+                    std::array::from_fn(|dim| entity.block_pos[dim] + reg.offset[dim]);
+                if !piece_box.contains(g_pos) {
+                    continue;
+                }
+                let mut entity = entity.clone();
+                let mut adder = reg.offset;
+                for dim in 0..3 {
+                    adder[dim] -= offset[dim];
+                }
+                entity.pos_shift(adder);
+                region.entities.push(entity);
+            }
+        }
+        region.shrink_palette()?;
+
+        let mut result = Schematic::new();
+        result.metadata = self.metadata.clone();
+        result.regions.push(region);
+        return Ok(result);
+    }
+
+    /// Blocks are reported to `reporter` row by row, the caller finishes the region.
+    fn to_nbt_vanilla_structure_unchecked(
+        &self,
+        option: &VanillaStructureSaveOption,
+        reporter: &mut ProgressReporter,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let (full_palette, mut luts_of_block_idx) =
+            self.full_palette_ordered(option.palette_order, None);
+        let mut nbt = self.structure_nbt_without_blocks(option, &full_palette);
+        if let Some((palettes, luts)) = self.structure_palette_variants(option) {
+            nbt.remove("palette");
+            nbt.insert(String::from("palettes"), Value::List(palettes));
+            luts_of_block_idx = luts;
+        }
+
+        let shape = self.shape();
         {
             let mut blocks: Vec<Value> = Vec::with_capacity(self.volume() as usize);
             for x in 0..shape[0] {
                 for y in 0..shape[1] {
                     for z in 0..shape[2] {
-                        let g_pos = [x, y, z];
-
-                        let mut first_region_idx = None;
-                        let mut first_r_blk_info = None;
-
-                        for (reg_idx, reg) in self.regions.iter().enumerate() {
-                            let r_pos = reg.global_pos_to_relative_pos(g_pos);
-                            let r_blk_info = reg.block_info_at(r_pos);
-                            if let Some(r_blk_info) = r_blk_info {
-                                first_region_idx = Some(reg_idx);
-                                first_r_blk_info = Some(r_blk_info);
-                                break;
-                            } else {
-                                continue;
-                            }
+                        if let Some(block) =
+                            self.structure_block_nbt([x, y, z], &luts_of_block_idx, option)
+                        {
+                            blocks.push(Value::Compound(block));
                         }
+                    }
+                    reporter.advance(shape[2] as u64)?;
+                }
+            }
+            nbt.insert(String::from("blocks"), Value::List(blocks));
+        }
 
-                        if let None = first_region_idx {
-                            // there is no block through out all regions
-                            continue;
-                        }
+        return Ok(nbt);
+    }
 
-                        assert_eq!(first_r_blk_info.is_some(), first_region_idx.is_some());
-                        let first_region_idx: usize = first_region_idx.unwrap();
-                        let first_r_blk_info = first_r_blk_info.unwrap();
-                        let g_blk_id =
-                            luts_of_block_idx[first_region_idx][first_r_blk_info.0 as usize];
+    /// `palettes` list and LUT of block indices, if the schematic is saved with palette variants.
+    /// Blocks are not merged like in the full palette, because blocks that are the same in one
+    /// variant may differ in another.
+    fn structure_palette_variants(
+        &self,
+        option: &VanillaStructureSaveOption,
+    ) -> Option<(Vec<Value>, Vec<Vec<usize>>)> {
+        if option.palette_variant.is_some() || self.regions.len() != 1 {
+            return None;
+        }
+        let reg = &self.regions[0];
+        if reg.alternate_palettes.is_empty()
+            || reg
+                .alternate_palettes
+                .iter()
+                .any(|v| v.len() != reg.palette.len())
+        {
+            return None;
+        }
+        let blocks: Vec<&Block> = reg.palette.iter().collect();
+        let new_indices = option.palette_order.new_indices(&blocks);
+        let mut old_indices = vec![0; new_indices.len()];
+        for (old_idx, new_idx) in new_indices.iter().enumerate() {
+            old_indices[*new_idx] = old_idx;
+        }
+        let palettes = std::iter::once(&reg.palette)
+            .chain(reg.alternate_palettes.iter())
+            .map(|variant| {
+                let list = old_indices
+                    .iter()
+                    .map(|idx| Value::Compound(variant[*idx].to_nbt()))
+                    .collect();
+                Value::List(list)
+            })
+            .collect();
+        return Some((palettes, vec![new_indices]));
+    }
 
-                        if first_r_blk_info.1.is_structure_void() {
-                            continue;
-                        }
+    /// Copy of the schematic using palette variant `variant` in all regions, with other variants
+    /// removed. `None` if there is nothing to select.
+    fn with_selected_palette_variant(
+        &self,
+        variant: Option<usize>,
+    ) -> Result<Option<Schematic>, Error> {
+        let Some(variant) = variant else {
+            return Ok(None);
+        };
+        if variant == 0 && self.regions.iter().all(|r| r.alternate_palettes.is_empty()) {
+            return Ok(None);
+        }
+        let mut result = self.clone();
+        for reg in &mut result.regions {
+            reg.select_palette_variant(variant)?;
+            reg.alternate_palettes.clear();
+        }
+        return Ok(Some(result));
+    }
 
-                        if (!option.keep_air) && (first_r_blk_info.1.id == "air") {
-                            continue;
-                        }
+    /// The block saved at `g_pos`, as `(index in full palette, block, block entity)`. `None` if
+    /// nothing is saved there, because it's outside all regions, structure void or omitted air.
+    fn structure_block_at(
+        &self,
+        g_pos: [i32; 3],
+        luts_of_block_idx: &[Vec<usize>],
+        option: &VanillaStructureSaveOption,
+    ) -> Option<(usize, &Block, Option<&BlockEntity>)> {
+        for (reg_idx, reg) in self.regions.iter().enumerate() {
+            let r_pos = reg.global_pos_to_relative_pos(g_pos);
+            let Some((blk_idx, blk, be, _)) = reg.block_info_at(r_pos) else {
+                continue;
+            };
+            if blk.is_structure_void() {
+                return None;
+            }
+            if option.air_policy == AirPolicy::Omit && blk.id == "air" {
+                return None;
+            }
+            return Some((luts_of_block_idx[reg_idx][blk_idx as usize], blk, be));
+        }
+        // there is no block through out all regions
+        return None;
+    }
 
-                        let mut cur_nbt: HashMap<String, Value> = HashMap::new();
-                        cur_nbt.insert(String::from("state"), Value::Int(g_blk_id as i32));
-                        {
-                            cur_nbt.insert(String::from("pos"), pos_to_nbt(&g_pos));
-                        }
-                        if let Some(be) = first_r_blk_info.2 {
-                            cur_nbt.insert(String::from("nbt"), Value::Compound(be.tags.clone()));
-                        }
-                        blocks.push(Value::Compound(cur_nbt));
-                    }
+    /// Element of `blocks` list for the block at `g_pos`
+    fn structure_block_nbt(
+        &self,
+        g_pos: [i32; 3],
+        luts_of_block_idx: &[Vec<usize>],
+        option: &VanillaStructureSaveOption,
+    ) -> Option<HashMap<String, Value>> {
+        let (g_blk_id, blk, be) = self.structure_block_at(g_pos, luts_of_block_idx, option)?;
+        let mut cur_nbt: HashMap<String, Value> = HashMap::new();
+        cur_nbt.insert(String::from("state"), Value::Int(g_blk_id as i32));
+        cur_nbt.insert(String::from("pos"), pos_to_nbt(&g_pos));
+        if let Some(be) = be {
+            let mut tags = be.tags.clone();
+            if option.fill_block_entity_id && !tags.contains_key("id") {
+                if let Some(id) = block_entity_id_of(blk) {
+                    tags.insert("id".to_string(), Value::String(format!("minecraft:{id}")));
                 }
             }
-            nbt.insert(String::from("blocks"), Value::List(blocks));
+            cur_nbt.insert(String::from("nbt"), Value::Compound(tags));
+        }
+        return Some(cur_nbt);
+    }
+
+    /// Tags of vanilla structure except `blocks`
+    fn structure_nbt_without_blocks(
+        &self,
+        option: &VanillaStructureSaveOption,
+        full_palette: &[(&Block, u64)],
+    ) -> HashMap<String, Value> {
+        let mut nbt: HashMap<String, Value> = HashMap::new();
+
+        {
+            let mut size = Vec::with_capacity(3);
+            for sz in self.shape() {
+                size.push(Value::Int(sz));
+            }
+            nbt.insert(String::from("size"), Value::List(size));
+        }
+
+        {
+            let mut nbt_palette = Vec::with_capacity(full_palette.len());
+            for (blk, _) in full_palette {
+                nbt_palette.push(Value::Compound(blk.to_nbt()));
+            }
+            nbt.insert(String::from("palette"), Value::List(nbt_palette));
         }
 
         {
@@ -484,7 +1029,10 @@ impl Schematic {
                     }
                     nbt.insert(String::from("blockPos"), Value::List(block_pos));
                     nbt.insert(String::from("pos"), Value::List(pos));
-                    nbt.insert(String::from("nbt"), Value::Compound(entity.tags.clone()));
+                    nbt.insert(
+                        String::from("nbt"),
+                        Value::Compound(entity.tags_with_passengers([0.0; 3])),
+                    );
 
                     entities.push(Value::Compound(nbt));
                 }
@@ -496,8 +1044,139 @@ impl Schematic {
             String::from("DataVersion"),
             Value::Int(self.metadata.mc_data_version),
         );
+        nbt.insert(
+            String::from("author"),
+            Value::String(self.metadata.author.clone()),
+        );
+        if option.keep_unmapped_metadata {
+            let mut custom = HashMap::new();
+            custom.insert(
+                "Name".to_string(),
+                Value::String(self.metadata.name.clone()),
+            );
+            custom.insert(
+                "Description".to_string(),
+                Value::String(self.metadata.description.clone()),
+            );
+            nbt.insert(String::from("Metadata"), Value::Compound(custom));
+        }
+        return nbt;
+    }
+
+    /// Compress the structure and write it to `dest` block by block, without building the
+    /// `blocks` list in memory.
+    fn write_vanilla_structure_stream(
+        &self,
+        dest: &mut dyn std::io::Write,
+        option: &VanillaStructureSaveOption,
+        filename: Option<&str>,
+    ) -> Result<(), Error> {
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.write_vanilla_structure_stream(dest, option, filename);
+        }
+        let shape = self.shape();
+        if exceeds_max_size(&shape, option) {
+            let max_size = option.max_size.unwrap();
+            return Err(Error::SizeTooLarge {
+                size: [shape[0] as u64, shape[1] as u64, shape[2] as u64],
+                max_size: [max_size[0] as u64, max_size[1] as u64, max_size[2] as u64],
+            });
+        }
+        let (full_palette, luts_of_block_idx) =
+            self.full_palette_ordered(option.palette_order, None);
+        let nbt = self.structure_nbt_without_blocks(option, &full_palette);
+        // length of list is written before elements
+        let mut block_count: u64 = 0;
+        for x in 0..shape[0] {
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    if self
+                        .structure_block_at([x, y, z], &luts_of_block_idx, option)
+                        .is_some()
+                    {
+                        block_count += 1;
+                    }
+                }
+            }
+        }
+        if block_count > i32::MAX as u64 {
+            return Err(Error::MetadataOverflow {
+                tag_path: "/blocks".to_string(),
+                value: block_count,
+                max: i32::MAX as u64,
+            });
+        }
+        let block_count = block_count as i32;
+
+        let mut encoder = common::CompressedWriter::new(
+            dest,
+            option.compress_method,
+            option.compress_level,
+            filename,
+        )?;
+        common::write_tag_header(&mut encoder, 10, "")?;
+        for (key, value) in &nbt {
+            common::write_nbt_entry(&mut encoder, key, value)?;
+        }
+        common::write_tag_header(&mut encoder, 9, "blocks")?;
+        // empty lists have element type of end tag
+        let element_type: u8 = if block_count > 0 { 10 } else { 0 };
+        common::write_raw_bytes(&mut encoder, &[element_type])?;
+        common::write_raw_bytes(&mut encoder, &block_count.to_be_bytes())?;
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            1,
+            self.volume(),
+        );
+        // blocks are tiny, passing them to the encoder one by one is very slow
+        const BUFFER_SIZE: usize = 1 << 16;
+        let mut buffer: Vec<u8> = Vec::with_capacity(BUFFER_SIZE);
+        for x in 0..shape[0] {
+            for y in 0..shape[1] {
+                for z in 0..shape[2] {
+                    if let Some(block) =
+                        self.structure_block_nbt([x, y, z], &luts_of_block_idx, option)
+                    {
+                        common::write_compound_payload(&mut buffer, &block)?;
+                    }
+                }
+                if buffer.len() >= BUFFER_SIZE {
+                    common::write_raw_bytes(&mut encoder, &buffer)?;
+                    buffer.clear();
+                }
+                reporter.advance(shape[2] as u64)?;
+            }
+        }
+        common::write_raw_bytes(&mut encoder, &buffer)?;
+        common::write_raw_bytes(&mut encoder, &[0])?;
+        encoder.finish()?;
+        reporter.finish_region(self.volume())?;
+        return Ok(());
+    }
 
-        return Ok(nbt);
+    /// Stream the structure to `dest`. With `max_file_size`, the file is compressed in memory
+    /// first, and nothing is written if it's too large.
+    fn write_vanilla_structure_checked(
+        &self,
+        dest: &mut dyn std::io::Write,
+        option: &VanillaStructureSaveOption,
+        filename: Option<&str>,
+    ) -> Result<(), Error> {
+        let Some(max_file_size) = option.max_file_size else {
+            return self.write_vanilla_structure_stream(dest, option, filename);
+        };
+        let mut content = Vec::new();
+        self.write_vanilla_structure_stream(&mut content, option, filename)?;
+        if content.len() as u64 > max_file_size {
+            return Err(Error::FileTooLarge {
+                size: content.len() as u64,
+                max_size: max_file_size,
+            });
+        }
+        return common::write_raw_bytes(dest, &content);
     }
 
     // pub fn save_vanilla_structure(&self, dst: &mut dyn std::io::Write, option: &VanillaStructureSaveOption) -> Result<(), LoadError> {
@@ -516,49 +1195,104 @@ impl Schematic {
     // }
 
     /// Save schematic to file as vanilla structure
-
+    #[cfg(feature = "fs")]
     pub fn save_vanilla_structure_file(
         &self,
         filename: &str,
         option: &VanillaStructureSaveOption,
     ) -> Result<(), Error> {
-        let nbt = self.to_nbt_vanilla_structure(option)?;
+        if option.split_oversized && exceeds_max_size(&self.shape(), option) {
+            return self.save_vanilla_structure_pieces(filename, option);
+        }
 
+        // check size before creating the file
+        let mut content = Vec::new();
+        if option.max_file_size.is_some() {
+            self.write_vanilla_structure_checked(&mut content, option, Some(filename))?;
+        }
         let file = match File::create(filename) {
             Ok(f) => f,
             Err(e) => return Err(Error::FileCreateError(e)),
         };
+        let mut writer = std::io::BufWriter::new(file);
+        if option.max_file_size.is_some() {
+            common::write_raw_bytes(&mut writer, &content)?;
+        } else {
+            self.write_vanilla_structure_stream(&mut writer, option, Some(filename))?;
+        }
+        return std::io::Write::flush(&mut writer).map_err(|e| Error::NBTWriteError(e.into()));
+    }
 
-        let encoder = GzBuilder::new()
-            .filename(filename)
-            .comment("Generated by mc_schem")
-            .write(file, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
+    /// Save pieces as `{stem}_{x}_{y}_{z}.nbt` beside `filename`, and write a manifest to
+    /// `{stem}.json`
+    #[cfg(feature = "fs")]
+    fn save_vanilla_structure_pieces(
+        &self,
+        filename: &str,
+        option: &VanillaStructureSaveOption,
+    ) -> Result<(), Error> {
+        let path = std::path::Path::new(filename);
+        let stem = match path.file_stem() {
+            Some(s) => s.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::FileCreateError(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid filename \"{filename}\""),
+                )))
+            }
+        };
+        let dir = path.parent().unwrap_or(std::path::Path::new(""));
+
+        let pieces = self.to_nbt_vanilla_structure_pieces(option)?;
+        let mut manifest = StructureManifest {
+            size: self.shape(),
+            data_version: option
+                .target_data_version
+                .unwrap_or(self.metadata.mc_data_version),
+            pieces: Vec::with_capacity(pieces.len()),
+        };
+        for piece in &pieces {
+            let piece_name = format!(
+                "{stem}_{}_{}_{}.nbt",
+                piece.index[0], piece.index[1], piece.index[2]
+            );
+            let piece_path = dir.join(&piece_name);
+            save_structure_nbt_file(piece_path.as_path(), &piece.nbt, option)?;
+            manifest.pieces.push(StructurePieceInfo {
+                file: piece_name,
+                index: piece.index,
+                offset: piece.offset,
+                size: piece.shape,
+            });
         }
 
+        let file = match File::create(dir.join(format!("{stem}.json"))) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        if let Err(e) = serde_json::to_writer_pretty(file, &manifest) {
+            return Err(Error::FileCreateError(e.into()));
+        }
         return Ok(());
     }
 
-    /// Save schematic to writer as vanilla structure
+    /// Save schematic to writer as vanilla structure. Blocks are encoded and compressed one by one
+    /// instead of being collected into a list first.
     pub fn save_vanilla_structure_writer(
         &self,
         dest: &mut dyn std::io::Write,
         option: &VanillaStructureSaveOption,
     ) -> Result<(), Error> {
-        let nbt = self.to_nbt_vanilla_structure(option)?;
-
-        let encoder = GzBuilder::new()
-            .comment("Generated by mc_schem")
-            .write(dest, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
-        }
+        return self.write_vanilla_structure_checked(dest, option, None);
+    }
 
-        return Ok(());
+    /// Save schematic to bytes as vanilla structure
+    pub fn to_vanilla_structure_bytes(
+        &self,
+        option: &VanillaStructureSaveOption,
+    ) -> Result<Vec<u8>, Error> {
+        let mut dest = Vec::new();
+        self.save_vanilla_structure_writer(&mut dest, option)?;
+        return Ok(dest);
     }
 }
@@ -0,0 +1,180 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+
+use static_assertions as sa;
+
+use crate::block::Block;
+use crate::region::{BlockEntity, Entity, Region, WorldSlice};
+use crate::schem::{MetaDataIR, Schematic};
+
+/// Read-only view of a schematic with precomputed indices, built by [`Schematic::view`]. The
+/// schematic is borrowed immutably, so indices never go stale, and one view can answer queries
+/// from many threads at once. \
+/// Blocks of all regions are indexed in the full palette of the schematic. Positions are global,
+/// and where regions overlap, a position is listed once for each region.
+#[derive(Debug, Clone)]
+pub struct SchematicView<'a> {
+    schem: &'a Schematic,
+    /// Full palette of all regions, with hash of each block
+    palette: Vec<(&'a Block, u64)>,
+    /// Block -> index in `palette`
+    index_of_block: HashMap<&'a Block, usize>,
+    /// Region index -> LUT from block index in region to index in `palette`
+    luts: Vec<Vec<usize>>,
+    /// Index in `palette` -> global positions in y, z, x order of each region
+    positions: Vec<Vec<[i32; 3]>>,
+    /// Hash of the whole palette, independent from the order of blocks
+    palette_hash: u64,
+}
+
+sa::assert_impl_all!(SchematicView<'static>: Send, Sync);
+
+#[allow(dead_code)]
+impl<'a> SchematicView<'a> {
+    /// Build indices of `schem`, which takes a pass over all blocks
+    pub fn new(schem: &'a Schematic) -> SchematicView<'a> {
+        let (palette, luts) = schem.full_palette();
+        let mut index_of_block = HashMap::with_capacity(palette.len());
+        for (idx, (blk, _)) in palette.iter().enumerate() {
+            index_of_block.insert(*blk, idx);
+        }
+
+        let mut positions: Vec<Vec<[i32; 3]>> = vec![Vec::new(); palette.len()];
+        for (reg, lut) in schem.regions.iter().zip(&luts) {
+            for ((y, z, x), blk_id) in reg.array_yzx.indexed_iter() {
+                let Some(idx) = lut.get(*blk_id as usize) else {
+                    continue;
+                };
+                let r_pos = [x as i32, y as i32, z as i32];
+                positions[*idx].push(reg.relative_pos_to_global_pos(r_pos));
+            }
+        }
+
+        // xor of block hashes, so it doesn't depend on the order of regions and palettes
+        let palette_hash = palette.iter().fold(0u64, |h, (_, blk_hash)| h ^ blk_hash);
+        return SchematicView {
+            schem,
+            palette,
+            index_of_block,
+            luts,
+            positions,
+            palette_hash,
+        };
+    }
+
+    /// The viewed schematic
+    pub fn schematic(&self) -> &'a Schematic {
+        return self.schem;
+    }
+
+    /// Regions of the viewed schematic
+    pub fn regions(&self) -> &'a [Region] {
+        return &self.schem.regions;
+    }
+
+    /// Metadata of the viewed schematic
+    pub fn metadata(&self) -> &'a MetaDataIR {
+        return &self.schem.metadata;
+    }
+
+    /// Global shape of the schematic
+    pub fn shape(&self) -> [i32; 3] {
+        return self.schem.shape();
+    }
+
+    /// All kinds of blocks in all regions, without duplicates
+    pub fn palette(&self) -> impl ExactSizeIterator<Item = &'a Block> + '_ {
+        return self.palette.iter().map(|(blk, _)| *blk);
+    }
+
+    /// Hash of the set of blocks in all regions, which can be used as a key to cache results
+    /// that only depend on the palette
+    pub fn palette_hash(&self) -> u64 {
+        return self.palette_hash;
+    }
+
+    /// Index of `block` in [`SchematicView::palette`], without scanning the palette
+    pub fn index_of(&self, block: &Block) -> Option<usize> {
+        return self.index_of_block.get(block).copied();
+    }
+
+    /// If any region has `block`
+    pub fn contains(&self, block: &Block) -> bool {
+        return self.index_of_block.contains_key(block);
+    }
+
+    /// Global positions of `block`, empty if it's not in the schematic
+    pub fn positions_of(&self, block: &Block) -> &[[i32; 3]] {
+        return match self.index_of(block) {
+            Some(idx) => &self.positions[idx],
+            None => &[],
+        };
+    }
+
+    /// Count of `block` in all regions
+    pub fn count_of(&self, block: &Block) -> usize {
+        return self.positions_of(block).len();
+    }
+
+    /// Blocks and their counts in all regions, in the order of palette
+    pub fn block_counts(&self) -> impl Iterator<Item = (&'a Block, usize)> + '_ {
+        return self
+            .palette
+            .iter()
+            .zip(&self.positions)
+            .map(|((blk, _), pos)| (*blk, pos.len()));
+    }
+
+    /// Index in [`SchematicView::palette`] of the block at global position `pos`, from the first
+    /// region containing it
+    pub fn index_at(&self, pos: [i32; 3]) -> Option<usize> {
+        let reg_idx = self.schem.first_region_index_at(pos)?;
+        let reg = &self.schem.regions[reg_idx];
+        let blk_id = reg.block_index_at(reg.global_pos_to_relative_pos(pos))?;
+        return self.luts[reg_idx].get(blk_id as usize).copied();
+    }
+
+    /// The block at global position `pos`, from the first region containing it
+    pub fn block_at(&self, pos: [i32; 3]) -> Option<&'a Block> {
+        return self.index_at(pos).map(|idx| self.palette[idx].0);
+    }
+
+    /// The block entity at global position `pos`, from the first region containing it
+    pub fn block_entity_at(&self, pos: [i32; 3]) -> Option<&'a BlockEntity> {
+        return self.schem.first_block_entity_at(pos);
+    }
+
+    /// Entities of all regions, with their region index. Positions are relative to the region.
+    pub fn entities(&self) -> impl Iterator<Item = (usize, &'a Entity)> + '_ {
+        return self
+            .schem
+            .regions
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, reg)| reg.entities.iter().map(move |e| (idx, e)));
+    }
+}
+
+impl Schematic {
+    /// Build a read-only [`SchematicView`] with indices for fast queries
+    pub fn view(&self) -> SchematicView<'_> {
+        return SchematicView::new(self);
+    }
+}
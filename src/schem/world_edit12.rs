@@ -17,17 +17,19 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use crate::block::Block;
-use crate::error::Error;
+use crate::error::{Error, ErrorHandleResult, ErrorHandler};
 use crate::old_block::OldBlockParseError;
-use crate::region::{BlockEntity, Region};
+use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::schem::progress::{report_single_region, ProgressReporter};
 use crate::schem::{
-    common, id_of_nbt_tag, MetaDataIR, Schematic, WE12MetaData, WorldEdit12LoadOption,
+    common, id_of_nbt_tag, MetaDataIR, Schematic, WE12LoadResult, WE12MetaData,
+    WorldEdit12LoadOption, WorldEdit12SaveOption,
 };
 use crate::{unwrap_opt_tag, unwrap_tag};
 use fastnbt::Value;
-use flate2::read::GzDecoder;
 use ndarray::Array3;
 use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::fs::File;
 use std::mem;
 
@@ -43,6 +45,12 @@ fn parse_shape(nbt: &HashMap<String, Value>) -> Result<[i16; 3], Error> {
     let x_size = *unwrap_opt_tag!(nbt.get("Width"), Short, 0, "/Width".to_string());
     let y_size = *unwrap_opt_tag!(nbt.get("Height"), Short, 0, "/Height".to_string());
     let z_size = *unwrap_opt_tag!(nbt.get("Length"), Short, 0, "/Length".to_string());
+    if x_size < 0 || y_size < 0 || z_size < 0 {
+        return Err(Error::NegativeSize {
+            size: [x_size as i32, y_size as i32, z_size as i32],
+            region_name: String::new(),
+        });
+    }
     return Ok([x_size, y_size, z_size]);
 }
 
@@ -61,8 +69,6 @@ impl Schematic {
             z_size = shape[2] as usize;
         }
 
-        let mut array = Array3::default([y_size, z_size, x_size]);
-
         let blocks = unwrap_opt_tag!(
             nbt.get("Blocks"),
             ByteArray,
@@ -97,6 +103,8 @@ impl Schematic {
                 });
             }
         }
+        // allocate after the length is checked, so a forged shape can't exhaust memory
+        let mut array = Array3::default([y_size, z_size, x_size]);
         array.fill((0, 0));
         let mut counter = 0;
         for y in 0..y_size {
@@ -152,31 +160,35 @@ impl Schematic {
     }
 
     /// Load `.schematic` from file
+    #[cfg(feature = "fs")]
     pub fn from_world_edit_12_file(
         filename: &str,
         option: &WorldEdit12LoadOption,
-    ) -> Result<(Schematic, WE12MetaData, Array3<(u8, u8)>), Error> {
+    ) -> WE12LoadResult {
         let file = match File::open(filename) {
             Ok(f) => f,
             Err(e) => return Err(Error::FileOpenError(e)),
         };
-        let decoder = GzDecoder::new(file);
-        let nbt: HashMap<String, Value> = match fastnbt::from_reader(decoder) {
-            Ok(n) => n,
-            Err(e) => return Err(Error::NBTReadError(e)),
-        };
+        let mut decoder = common::decompress_reader(file)?;
+        let nbt = common::read_nbt_compound(&mut decoder)?;
         return Self::from_world_edit_12_nbt(nbt, option);
     }
 
+    /// Load `.schematic` from bytes, compression is detected
+    pub fn from_world_edit_12_bytes(
+        bytes: &[u8],
+        option: &WorldEdit12LoadOption,
+    ) -> WE12LoadResult {
+        let mut decoder = common::decompress_reader(bytes)?;
+        return Self::from_world_edit_12_reader(&mut decoder, option);
+    }
+
     /// Load `.schematic` from reader
     pub fn from_world_edit_12_reader(
         src: &mut dyn std::io::Read,
         option: &WorldEdit12LoadOption,
-    ) -> Result<(Schematic, WE12MetaData, Array3<(u8, u8)>), Error> {
-        let nbt: HashMap<String, Value> = match fastnbt::from_reader(src) {
-            Ok(n) => n,
-            Err(e) => return Err(Error::NBTReadError(e)),
-        };
+    ) -> WE12LoadResult {
+        let nbt = common::read_nbt_compound(src)?;
         return Self::from_world_edit_12_nbt(nbt, option);
     }
 
@@ -184,7 +196,7 @@ impl Schematic {
     pub fn from_world_edit_12_nbt(
         mut nbt: HashMap<String, Value>,
         option: &WorldEdit12LoadOption,
-    ) -> Result<(Schematic, WE12MetaData, Array3<(u8, u8)>), Error> {
+    ) -> WE12LoadResult {
         let mut schem = Schematic::new();
         // metadata
 
@@ -192,6 +204,11 @@ impl Schematic {
         schem.metadata = md;
 
         let (region, number_id) = Region::from_world_edit_12(&mut nbt, option)?;
+        report_single_region(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            region.volume(),
+        )?;
         schem.regions.push(region);
 
         return Ok((schem, raw, number_id));
@@ -284,6 +301,9 @@ impl Region {
         }
 
         //tile entities
+        if option.skip_block_entities {
+            nbt.insert("TileEntities".to_string(), Value::List(vec![]));
+        }
         let tile_entities =
             unwrap_opt_tag!(nbt.get_mut("TileEntities"), List, vec![], "/TileEntities");
         region.block_entities.reserve(tile_entities.len());
@@ -374,3 +394,232 @@ impl Region {
         return Ok((region, id_damage_array));
     }
 }
+
+/// Number id and damage of `block`, blocks without one are passed to
+/// `H::fix_no_old_block_equivalent`
+fn old_number_id<H: ErrorHandler>(block: &Block, pos: [i32; 3]) -> Result<(u8, u8), Error> {
+    return match block.to_old() {
+        Ok(n) => Ok(n),
+        Err(_) => {
+            let err = Error::NoOldBlockEquivalent {
+                block: block.clone(),
+                pos,
+            };
+            match H::fix_no_old_block_equivalent(&err) {
+                ErrorHandleResult::HandledWithWarning(n) => Ok(n),
+                ErrorHandleResult::HandledWithoutWarning(n) => Ok(n),
+                ErrorHandleResult::NotHandled => Err(err),
+            }
+        }
+    };
+}
+
+#[allow(dead_code)]
+impl Schematic {
+    /// Convert blocks to number id and damage of 1.12. Blocks without number id are passed to
+    /// `H::fix_no_old_block_equivalent`, and `NoOldBlockEquivalent` is returned if not handled.
+    pub fn to_number_id_we12<H: ErrorHandler>(
+        &self,
+        option: &WorldEdit12SaveOption,
+    ) -> Result<Array3<(u8, u8)>, Error> {
+        return self.number_id_we12::<H>(option, &mut ProgressReporter::new(None, None, 1, 0));
+    }
+
+    /// Blocks of every region are reported to `reporter` while converting
+    fn number_id_we12<H: ErrorHandler>(
+        &self,
+        option: &WorldEdit12SaveOption,
+        reporter: &mut ProgressReporter,
+    ) -> Result<Array3<(u8, u8)>, Error> {
+        let shape = self.shape();
+        let background = option.background_block.to_block();
+        let shape_yzx = [shape[1] as usize, shape[2] as usize, shape[0] as usize];
+        let mut array = Array3::default(shape_yzx);
+        // positions that are not covered by any region, or are structure void
+        let mut is_background = Array3::from_elem(shape_yzx, true);
+
+        // The first region wins where regions overlap, so regions are written in reverse order
+        for reg in self.regions.iter().rev() {
+            let mut lut: Vec<Option<(u8, u8)>> = vec![None; reg.palette.len()];
+            for ((y, z, x), blk_idx) in reg.array_yzx.indexed_iter() {
+                reporter.advance(1)?;
+                let pos = [
+                    x as i32 + reg.offset[0],
+                    y as i32 + reg.offset[1],
+                    z as i32 + reg.offset[2],
+                ];
+                if (0..3).any(|dim| pos[dim] < 0 || pos[dim] >= shape[dim]) {
+                    continue;
+                }
+                let idx = [pos[1] as usize, pos[2] as usize, pos[0] as usize];
+                let block = &reg.palette[*blk_idx as usize];
+                if block.is_structure_void() {
+                    is_background[idx] = true;
+                    continue;
+                }
+                let number_id = match lut[*blk_idx as usize] {
+                    Some(n) => n,
+                    None => {
+                        let n = old_number_id::<H>(block, pos)?;
+                        lut[*blk_idx as usize] = Some(n);
+                        n
+                    }
+                };
+                array[idx] = number_id;
+                is_background[idx] = false;
+            }
+        }
+
+        let mut background_id = None;
+        for ((y, z, x), is_bg) in is_background.indexed_iter() {
+            if !*is_bg {
+                continue;
+            }
+            let n = match background_id {
+                Some(n) => n,
+                None => {
+                    let n = old_number_id::<H>(&background, [x as i32, y as i32, z as i32])?;
+                    background_id = Some(n);
+                    n
+                }
+            };
+            array[[y, z, x]] = n;
+        }
+        return Ok(array);
+    }
+
+    /// Save schematic to nbt as `.schematic`
+    pub fn to_nbt_world_edit_12<H: ErrorHandler>(
+        &self,
+        option: &WorldEdit12SaveOption,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let shape = self.shape();
+        for sz in shape {
+            if sz > i16::MAX as i32 {
+                return Err(Error::SizeTooLarge {
+                    size: [shape[0] as u64, shape[1] as u64, shape[2] as u64],
+                    max_size: [i16::MAX as u64; 3],
+                });
+            }
+        }
+        let mut nbt = HashMap::new();
+        for (key, sz) in ["Width", "Height", "Length"].iter().zip(shape) {
+            nbt.insert(key.to_string(), Value::Short(sz as i16));
+        }
+        nbt.insert("Materials".to_string(), Value::String("Alpha".to_string()));
+        let origin = self.metadata.schem_origin.unwrap_or([0, 0, 0]);
+        for (dim, letter) in ['X', 'Y', 'Z'].iter().enumerate() {
+            nbt.insert(
+                format!("WEOffset{}", letter),
+                Value::Int(self.metadata.schem_offset[dim]),
+            );
+            nbt.insert(format!("WEOrigin{}", letter), Value::Int(origin[dim]));
+        }
+
+        // blocks, in yzx order
+        let blocks_total = self.regions.iter().map(|r| r.volume()).sum();
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            1,
+            blocks_total,
+        );
+        {
+            let number_id = self.number_id_we12::<H>(option, &mut reporter)?;
+            let mut blocks = Vec::with_capacity(number_id.len());
+            let mut data = Vec::with_capacity(number_id.len());
+            for (id, damage) in number_id.iter() {
+                blocks.push(*id as i8);
+                data.push(*damage as i8);
+            }
+            nbt.insert(
+                "Blocks".to_string(),
+                Value::ByteArray(fastnbt::ByteArray::new(blocks)),
+            );
+            nbt.insert(
+                "Data".to_string(),
+                Value::ByteArray(fastnbt::ByteArray::new(data)),
+            );
+        }
+
+        let mut tile_entities = Vec::new();
+        let mut entities = Vec::new();
+        for (reg_idx, reg) in self.regions.iter().enumerate() {
+            for (r_pos, be) in &reg.block_entities {
+                let g_pos = reg.relative_pos_to_global_pos(*r_pos);
+                if self.first_region_index_at(g_pos) != Some(reg_idx) {
+                    // covered by former regions
+                    continue;
+                }
+                let mut tags = be.tags.clone();
+                for (dim, key) in ["x", "y", "z"].iter().enumerate() {
+                    tags.insert(key.to_string(), Value::Int(g_pos[dim]));
+                }
+                tile_entities.push(Value::Compound(tags));
+            }
+            for entity in &reg.entities {
+                let mut tags = entity.tags_with_passengers(reg.offset.map(|v| v as f64));
+                let mut pos = Vec::with_capacity(3);
+                for dim in 0..3 {
+                    pos.push(Value::Double(entity.position[dim] + reg.offset[dim] as f64));
+                }
+                tags.insert("Pos".to_string(), Value::List(pos));
+                entities.push(Value::Compound(tags));
+            }
+        }
+        nbt.insert("TileEntities".to_string(), Value::List(tile_entities));
+        nbt.insert("Entities".to_string(), Value::List(entities));
+
+        reporter.finish_region(blocks_total)?;
+        return Ok(nbt);
+    }
+
+    /// Save `.schematic` to writer
+    pub fn save_world_edit_12_writer<H: ErrorHandler>(
+        &self,
+        dest: &mut dyn std::io::Write,
+        option: &WorldEdit12SaveOption,
+    ) -> Result<(), Error> {
+        let nbt = self.to_nbt_world_edit_12::<H>(option)?;
+        common::write_compressed_nbt(
+            dest,
+            &nbt,
+            option.compress_method,
+            option.compress_level,
+            None,
+        )?;
+        return Ok(());
+    }
+
+    /// Save `.schematic` to bytes
+    pub fn to_world_edit_12_bytes<H: ErrorHandler>(
+        &self,
+        option: &WorldEdit12SaveOption,
+    ) -> Result<Vec<u8>, Error> {
+        let mut dest = Vec::new();
+        self.save_world_edit_12_writer::<H>(&mut dest, option)?;
+        return Ok(dest);
+    }
+
+    /// Save `.schematic` to file, blocks are converted to number id of 1.12
+    #[cfg(feature = "fs")]
+    pub fn save_world_edit_12_file<H: ErrorHandler>(
+        &self,
+        filename: &str,
+        option: &WorldEdit12SaveOption,
+    ) -> Result<(), Error> {
+        let nbt = self.to_nbt_world_edit_12::<H>(option)?;
+        let mut file = match File::create(filename) {
+            Ok(f) => f,
+            Err(e) => return Err(Error::FileCreateError(e)),
+        };
+        common::write_compressed_nbt(
+            &mut file,
+            &nbt,
+            option.compress_method,
+            option.compress_level,
+            Some(filename),
+        )?;
+        return Ok(());
+    }
+}
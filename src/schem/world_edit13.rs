@@ -16,38 +16,41 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::biome::Biome;
 use crate::block::Block;
 use crate::error::Error;
-use crate::region::{BlockEntity, Region, WorldSlice};
+use crate::region::{BlockEntity, PendingTick, PendingTickInfo, Region, WorldSlice};
 use crate::schem::id_of_nbt_tag;
+use crate::schem::progress::ProgressReporter;
 use crate::schem::{
-    common, MetaDataIR, Schematic, WE13MetaData, WE13MetaDataV3Extra, WorldEdit13LoadOption,
-    WorldEdit13SaveOption,
+    common, DataVersion, MetaDataIR, Schematic, WE13MetaData, WE13MetaDataV3Extra,
+    WorldEdit13LoadOption, WorldEdit13SaveOption,
 };
+use crate::varint::{self, VarIntDecoder};
 use crate::{unwrap_opt_tag, unwrap_tag};
 use fastnbt::Value;
-use flate2::read::GzDecoder;
-use flate2::GzBuilder;
 use ndarray::Array3;
 use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::fs::File;
 
 #[allow(dead_code)]
 impl Schematic {
     /// Load `.schem` from file
+    #[cfg(feature = "fs")]
     pub fn from_world_edit_13_file(
         filename: &str,
         option: &WorldEdit13LoadOption,
     ) -> Result<(Schematic, WE13MetaData), Error> {
         let mut file = File::open(filename).map_err(Error::FileOpenError)?;
 
-        let decoder = GzDecoder::new(&mut file);
-        let nbt = fastnbt::from_reader(decoder).map_err(Error::NBTReadError)?;
+        let mut decoder = common::decompress_reader(&mut file)?;
+        let nbt = common::read_nbt_compound(&mut decoder)?;
 
         return Self::from_world_edit_13_nbt(nbt, option);
     }
 
-    fn parse_v2(
+    fn parse_v1_v2(
         root: HashMap<String, Value>,
         option: &WorldEdit13LoadOption,
     ) -> Result<(Schematic, WE13MetaData), Error> {
@@ -55,10 +58,12 @@ impl Schematic {
         // metadata
         let we13 = parse_metadata(&root, "", option)?;
         schem.metadata = MetaDataIR::from_world_edit13(&we13);
-        match Region::from_world_edit_13_v2(root, option) {
-            Ok(reg) => schem.regions.push(reg),
-            Err(e) => return Err(e),
-        }
+        let region = if we13.version == 1 {
+            Region::from_world_edit_13_v1(root, option)?
+        } else {
+            Region::from_world_edit_13_v2(root, option)?
+        };
+        schem.regions.push(region);
         return Ok((schem, we13));
     }
 
@@ -83,29 +88,59 @@ impl Schematic {
 
         return Ok((schem, we13));
     }
-    /// Load `.schem` from nbt
+    /// Load `.schem` from nbt, sponge schematic v1, v2 and v3 are supported
     pub fn from_world_edit_13_nbt(
         root: HashMap<String, Value>,
         option: &WorldEdit13LoadOption,
     ) -> Result<(Schematic, WE13MetaData), Error> {
-        if root.contains_key("Schematic") {
-            //v3
-            Self::parse_v3(root, option)
-        } else {
-            Self::parse_v2(root, option)
+        // v3 puts everything in /Schematic
+        let (tag_version, tag_path) = match root.get("Schematic") {
+            Some(Value::Compound(tag_schem)) => (tag_schem.get("Version"), "/Schematic/Version"),
+            _ => (root.get("Version"), "/Version"),
+        };
+        let version = *unwrap_opt_tag!(tag_version, Int, 0, tag_path);
+
+        let (schem, md) = match version {
+            1 | 2 => Self::parse_v1_v2(root, option)?,
+            3 => Self::parse_v3(root, option)?,
+            _ => {
+                return Err(Error::UnsupportedWorldEdit13Version {
+                    version,
+                    supported_versions: Self::loadable_world_edit_13_versions(),
+                })
+            }
+        };
+        {
+            let palette_path = if version == 3 {
+                "/Schematic/Blocks/Palette"
+            } else {
+                "/Palette"
+            };
+            for reg in &schem.regions {
+                common::validate_palette_if(
+                    option.validate_blocks,
+                    schem.metadata.mc_data_version,
+                    &reg.palette,
+                    palette_path,
+                )?;
+            }
         }
+        return Ok((schem, md));
+    }
+    /// Load `.schem` from bytes, compression is detected
+    pub fn from_world_edit_13_bytes(
+        bytes: &[u8],
+        option: &WorldEdit13LoadOption,
+    ) -> Result<(Schematic, WE13MetaData), Error> {
+        let mut decoder = common::decompress_reader(bytes)?;
+        return Self::from_world_edit_13_reader(&mut decoder, option);
     }
     /// Load `.schem` from reader
     pub fn from_world_edit_13_reader(
         src: &mut dyn std::io::Read,
         option: &WorldEdit13LoadOption,
     ) -> Result<(Schematic, WE13MetaData), Error> {
-        let root_opt: Result<HashMap<String, Value>, fastnbt::error::Error> =
-            fastnbt::from_reader(src);
-        let root = match root_opt {
-            Ok(nbt_) => nbt_,
-            Err(e) => return Err(Error::NBTReadError(e)),
-        };
+        let root = common::read_nbt_compound(src)?;
         return Self::from_world_edit_13_nbt(root, option);
     }
 }
@@ -121,6 +156,15 @@ impl MetaDataIR {
             result.time_created = date;
             result.time_modified = date;
         }
+        if let Some(name) = &src.name {
+            result.name = name.clone();
+        }
+        if let Some(author) = &src.author {
+            result.author = author.clone();
+        }
+        if let Some(description) = &src.description {
+            result.description = description.clone();
+        }
 
         if let Some(extra) = &src.v3_extra {
             result.schem_editing_platform = Some(extra.editing_platform.clone());
@@ -144,16 +188,21 @@ fn parse_metadata(
     we13.v3_extra = None;
 
     we13.version = *unwrap_opt_tag!(nbt.get("Version"), Int, 0, format!("{tag_path}/Version"));
-    we13.data_version = *unwrap_opt_tag!(
-        nbt.get("DataVersion"),
-        Int,
-        0,
-        format!("{tag_path}/DataVersion")
-    );
     let schem_version = we13.version;
+    if schem_version == 1 && !nbt.contains_key("DataVersion") {
+        // v1 has no data version, it was written by WorldEdit for 1.13
+        we13.data_version = DataVersion::Java_1_13_2 as i32;
+    } else {
+        we13.data_version = *unwrap_opt_tag!(
+            nbt.get("DataVersion"),
+            Int,
+            0,
+            format!("{tag_path}/DataVersion")
+        );
+    }
 
-    // offset
-    {
+    // offset, optional in v1
+    if schem_version != 1 || nbt.contains_key("Offset") {
         let offset_list = unwrap_opt_tag!(
             nbt.get("Offset"),
             IntArray,
@@ -166,17 +215,47 @@ fn parse_metadata(
         }
     }
 
-    let tag_md = unwrap_opt_tag!(
-        nbt.get("Metadata"),
-        Compound,
-        HashMap::new(),
-        format!("{tag_path}/Metadata")
-    );
-    if schem_version == 2 {
+    let empty_md = HashMap::new();
+    let tag_md = if schem_version == 1 && !nbt.contains_key("Metadata") {
+        &empty_md
+    } else {
+        unwrap_opt_tag!(
+            nbt.get("Metadata"),
+            Compound,
+            HashMap::new(),
+            format!("{tag_path}/Metadata")
+        )
+    };
+    // optional in all versions
+    {
+        let keys = ["Name", "Author", "Description"];
+        let mut values = [None, None, None];
+        for (key, value) in keys.iter().zip(values.iter_mut()) {
+            if let Some(tag) = tag_md.get(*key) {
+                *value = Some(
+                    unwrap_tag!(
+                        tag,
+                        String,
+                        "".to_string(),
+                        format!("{tag_path}/Metadata/{key}")
+                    )
+                    .clone(),
+                );
+            }
+        }
+        let [name, author, description] = values;
+        we13.name = name;
+        we13.author = author;
+        we13.description = description;
+    }
+    if schem_version == 1 || schem_version == 2 {
         // we offset
         {
             let keys = ["WEOffsetX", "WEOffsetY", "WEOffsetZ"];
             for (dim, key) in keys.iter().enumerate() {
+                if schem_version == 1 && !tag_md.contains_key(*key) {
+                    continue;
+                }
                 we13.we_offset[dim] = *unwrap_opt_tag!(
                     tag_md.get(*key),
                     Int,
@@ -239,50 +318,30 @@ fn parse_metadata(
     return Ok(we13);
 }
 
-fn parse_single_block(src: &[i8]) -> i32 {
-    debug_assert!(!src.is_empty());
-    if src.len() == 1 {
-        debug_assert!(src[0] >= 0);
-        return src[0] as i32;
-    }
-
-    let mut result = 0;
-    for (idx, value) in src.iter().enumerate() {
-        if idx == src.len() - 1 {
-            debug_assert!(*value >= 1);
-        } else {
-            debug_assert!(*value < 0);
-        }
-        let value_fixed = if idx != 0 {
-            *value as i32 + 1
-        } else {
-            *value as i32
-        };
-        result += value_fixed * (1 << (idx * 7));
-    }
-
-    return result;
-}
-
 #[allow(dead_code)]
 impl Region {
     fn parse_palette_v2(
         nbt: &HashMap<String, Value>,
         tag_path: &str,
+        version: i32,
         _option: &WorldEdit13LoadOption,
     ) -> Result<Vec<Block>, Error> {
-        let palette_max = *unwrap_opt_tag!(
-            nbt.get("PaletteMax"),
-            Int,
-            0,
-            format!("{tag_path}/PaletteMax")
-        );
         let palette_comp = unwrap_opt_tag!(
             nbt.get("Palette"),
             Compound,
             HashMap::new(),
             format!("{tag_path}/Palette")
         );
+        if version == 1 {
+            // PaletteMax of v1 is not reliable, and often missing
+            return parse_palette(palette_comp, tag_path);
+        }
+        let palette_max = *unwrap_opt_tag!(
+            nbt.get("PaletteMax"),
+            Int,
+            0,
+            format!("{tag_path}/PaletteMax")
+        );
         if palette_max != palette_comp.len() as i32 {
             return Err(Error::InvalidValue {
                 tag_path: format!("{tag_path}/Palette"),
@@ -321,69 +380,76 @@ impl Region {
     fn parse_3d_array_v2(
         block_data: &[i8],
         tag_path: &str,
-        _option: &WorldEdit13LoadOption,
+        mut reporter: Option<&mut ProgressReporter>,
         size: [i32; 3],
-        palette: &[Block],
+        palette_len: usize,
     ) -> Result<Array3<u16>, Error> {
+        let total_blocks = size[1] as usize * size[2] as usize * size[0] as usize;
+        // every block takes at least 1 byte, check it before allocating for a forged size
+        if total_blocks > block_data.len() {
+            return Err(Error::BlockDataIncomplete {
+                tag_path: tag_path.to_string(),
+                index: block_data.len(),
+                detail: format!(
+                    "{} bytes can hold at most {} blocks, but {} blocks are required.",
+                    block_data.len(),
+                    block_data.len(),
+                    total_blocks
+                ),
+            });
+        }
         let mut array: Array3<u16> =
             Array3::default([size[1] as usize, size[2] as usize, size[0] as usize]);
 
-        let total_blocks = size[1] as usize * size[2] as usize * size[0] as usize;
         let mut decoded_blocks = 0;
-        let mut idx = 0;
+        let mut decoder = VarIntDecoder::new(block_data);
         for y in 0..size[1] as usize {
             for z in 0..size[2] as usize {
                 for x in 0..size[0] as usize {
-                    if idx >= block_data.len() {
-                        return Err(Error::BlockDataIncomplete {
-                            tag_path: tag_path.to_string(),
-                            index: idx,
-                            detail: format!(
-                                "{} blocks decoded, {} blocks missing, {} blocks in total.",
-                                decoded_blocks,
-                                total_blocks - decoded_blocks,
-                                total_blocks
-                            ),
-                        });
-                    }
-
-                    let cur_block_first_byte_index = idx;
-
-                    let mut cur_block_bytes = usize::MAX;
-                    for iidx in cur_block_first_byte_index..block_data.len() {
-                        if block_data[iidx] >= 0 {
-                            cur_block_bytes = iidx - cur_block_first_byte_index + 1;
-                            break;
+                    let first_byte_index = decoder.position();
+                    let decoded_block_index = match decoder.next() {
+                        Some(Ok(value)) => value,
+                        Some(Err(e)) => {
+                            return Err(Error::BlockDataIncomplete {
+                                tag_path: tag_path.to_string(),
+                                index: e.index(),
+                                detail: format!(
+                                    "{e}; {} blocks decoded, {} blocks missing, {} blocks in total.",
+                                    decoded_blocks,
+                                    total_blocks - decoded_blocks,
+                                    total_blocks
+                                ),
+                            });
                         }
-                    }
-                    if cur_block_bytes >= block_data.len() {
-                        let first_byte = block_data[cur_block_first_byte_index];
-                        return Err(Error::BlockDataIncomplete {
-                            tag_path: tag_path.to_string(),
-                            index: idx,
-                            detail: format!("BlockData[{}] is {}, which expects one or more elements to represent a block, but the data ends; {} blocks decoded, {} blocks missing, {} blocks in total.", idx - 1, first_byte, decoded_blocks, total_blocks - decoded_blocks, total_blocks),
-                        });
-                    }
-                    idx += cur_block_bytes;
-                    let decoded_block_index = parse_single_block(
-                        &block_data[cur_block_first_byte_index
-                            ..(cur_block_first_byte_index + cur_block_bytes)],
-                    );
+                        None => {
+                            return Err(Error::BlockDataIncomplete {
+                                tag_path: tag_path.to_string(),
+                                index: first_byte_index,
+                                detail: format!(
+                                    "{} blocks decoded, {} blocks missing, {} blocks in total.",
+                                    decoded_blocks,
+                                    total_blocks - decoded_blocks,
+                                    total_blocks
+                                ),
+                            });
+                        }
+                    };
 
-                    assert!(decoded_block_index >= 0);
-                    if decoded_block_index as usize >= palette.len() {
+                    if decoded_block_index as usize >= palette_len {
                         return Err(Error::BlockIndexOutOfRange {
-                            tag_path: format!("{tag_path}[{}]", cur_block_first_byte_index),
-                            index: decoded_block_index,
-                            range: [0, palette.len() as i32],
+                            tag_path: format!("{tag_path}[{}]", first_byte_index),
+                            index: i32::try_from(decoded_block_index).unwrap_or(i32::MAX),
+                            range: [0, palette_len as i32],
                         });
                     }
                     decoded_blocks += 1;
                     array[[y, z, x]] = decoded_block_index as u16;
                 }
+                if let Some(reporter) = reporter.as_mut() {
+                    reporter.advance(size[0] as u64)?;
+                }
             }
         }
-        debug_assert!(idx == block_data.len());
         return Ok(array);
     }
 
@@ -412,20 +478,39 @@ impl Region {
         return Ok(result);
     }
 
+    /// Load region from nbt, for `.schem` v1
+    pub fn from_world_edit_13_v1(
+        root: HashMap<String, Value>,
+        option: &WorldEdit13LoadOption,
+    ) -> Result<Region, Error> {
+        return Self::from_world_edit_13_v1_v2(root, 1, option);
+    }
+
     /// Load region from nbt, for `.schem` v2
     pub fn from_world_edit_13_v2(
+        root: HashMap<String, Value>,
+        option: &WorldEdit13LoadOption,
+    ) -> Result<Region, Error> {
+        return Self::from_world_edit_13_v1_v2(root, 2, option);
+    }
+
+    fn from_world_edit_13_v1_v2(
         mut root: HashMap<String, Value>,
+        version: i32,
         option: &WorldEdit13LoadOption,
     ) -> Result<Region, Error> {
         let mut region = Region::new();
         let tag_path = "";
         // palette
-        region.palette = Self::parse_palette_v2(&root, tag_path, option)?;
+        region.palette = Self::parse_palette_v2(&root, tag_path, version, option)?;
 
         // offset
         region.offset = [0, 0, 0];
 
         let size: [i32; 3] = Self::parse_size_v2(&root, tag_path, option)?;
+        let volume = size.iter().map(|s| *s as u64).product();
+        let mut reporter =
+            ProgressReporter::new(option.progress.as_ref(), option.cancel.as_ref(), 1, volume);
 
         // parse 3d array
         {
@@ -439,20 +524,61 @@ impl Region {
             region.array_yzx = Self::parse_3d_array_v2(
                 block_data.as_ref(),
                 &block_data_tag_path,
-                option,
+                Some(&mut reporter),
                 size,
-                &region.palette,
+                region.palette.len(),
             )?;
         }
 
-        // parse block entities
+        // parse block entities, they are named TileEntities in v1
         {
-            let be_tag_path = format!("{tag_path}/BlockEntities");
-            let block_entities =
-                unwrap_opt_tag!(root.get_mut("BlockEntities"), List, vec![], be_tag_path);
+            let be_key = if version == 1 {
+                "TileEntities"
+            } else {
+                "BlockEntities"
+            };
+            let be_tag_path = format!("{tag_path}/{be_key}");
+            if (version == 1 && !root.contains_key(be_key)) || option.skip_block_entities {
+                root.insert(be_key.to_string(), Value::List(vec![]));
+            }
+            let block_entities = unwrap_opt_tag!(root.get_mut(be_key), List, vec![], be_tag_path);
             region.block_entities =
                 Self::parse_block_entities_v2(block_entities, &be_tag_path, option, size)?;
+            if version == 1 {
+                // ContentVersion only describes the sponge format of v1 block entities
+                for be in region.block_entities.values_mut() {
+                    be.tags.remove("ContentVersion");
+                }
+            }
         }
+
+        // parse biomes, v2 biomes are 2d
+        if let Some(Value::Compound(tag_palette)) = root.get("BiomePalette") {
+            let tag_path = format!("{tag_path}/BiomePalette");
+            region.biome_palette = parse_biome_palette(tag_palette, &tag_path)?;
+            let data_tag_path = format!("{tag_path}/BiomeData");
+            let biome_data = unwrap_opt_tag!(
+                root.get("BiomeData"),
+                ByteArray,
+                fastnbt::ByteArray::new(vec![]),
+                data_tag_path
+            );
+            let biomes_2d = Self::parse_3d_array_v2(
+                biome_data.as_ref(),
+                &data_tag_path,
+                None,
+                [size[0], 1, size[2]],
+                region.biome_palette.len(),
+            )?;
+            let shape_yzx = region.array_yzx.raw_dim();
+            region.biomes = Some(
+                biomes_2d
+                    .broadcast(shape_yzx)
+                    .expect("Biome array should be broadcast to block array")
+                    .to_owned(),
+            );
+        }
+        reporter.finish_region(volume)?;
         return Ok(region);
     }
 
@@ -465,6 +591,9 @@ impl Region {
         let mut region = Region::new();
         //size
         let size = Self::parse_size_v2(&tag_schem, tag_schem_path, option)?;
+        let volume = size.iter().map(|s| *s as u64).product();
+        let mut reporter =
+            ProgressReporter::new(option.progress.as_ref(), option.cancel.as_ref(), 1, volume);
 
         let tag_blocks_path = "/Schematic/Blocks";
         let tag_blocks = unwrap_opt_tag!(
@@ -493,12 +622,20 @@ impl Region {
                 fastnbt::ByteArray::new(vec![]),
                 tag_data_path
             );
-            region.array_yzx =
-                Self::parse_3d_array_v2(&tag_data, tag_data_path, option, size, &region.palette)?;
+            region.array_yzx = Self::parse_3d_array_v2(
+                tag_data,
+                tag_data_path,
+                Some(&mut reporter),
+                size,
+                region.palette.len(),
+            )?;
         }
         //block entities
         {
             let tag_be_path = "/Schematic/Blocks/BlockEntities";
+            if option.skip_block_entities {
+                tag_blocks.insert("BlockEntities".to_string(), Value::List(vec![]));
+            }
             let tag_be = unwrap_opt_tag!(
                 tag_blocks.get_mut("BlockEntities"),
                 List,
@@ -508,11 +645,92 @@ impl Region {
             region.block_entities =
                 Self::parse_block_entities_v2(tag_be, tag_be_path, option, size)?;
         }
+        //pending ticks
+        {
+            // sponge has no sub tick, the order in list is kept instead
+            let mut sub_tick: i64 = 0;
+            for (key, is_block) in [("BlockTicks", true), ("FluidTicks", false)] {
+                let Some(tag_ticks) = tag_blocks.get(key) else {
+                    continue;
+                };
+                let tag_ticks_path = format!("/Schematic/Blocks/{key}");
+                let tag_ticks = unwrap_tag!(tag_ticks, List, vec![], tag_ticks_path);
+                for (idx, tag_tick) in tag_ticks.iter().enumerate() {
+                    let path = format!("{tag_ticks_path}[{idx}]");
+                    let tag_tick = unwrap_tag!(tag_tick, Compound, HashMap::new(), path);
+                    let (pos, mut tick) = parse_pending_tick_v3(tag_tick, &path, &size, is_block)?;
+                    tick.sub_tick = sub_tick;
+                    sub_tick += 1;
+                    region.pending_ticks.entry(pos).or_default().push(tick);
+                }
+            }
+        }
+        //biomes
+        if let Some(tag_biomes) = tag_schem.get("Biomes") {
+            let tag_biomes_path = "/Schematic/Biomes";
+            let tag_biomes = unwrap_tag!(tag_biomes, Compound, HashMap::new(), tag_biomes_path);
+            let tag_palette_path = "/Schematic/Biomes/Palette";
+            let tag_palette = unwrap_opt_tag!(
+                tag_biomes.get("Palette"),
+                Compound,
+                HashMap::new(),
+                tag_palette_path
+            );
+            region.biome_palette = parse_biome_palette(tag_palette, tag_palette_path)?;
+
+            let tag_data_path = "/Schematic/Biomes/Data";
+            let tag_data = unwrap_opt_tag!(
+                tag_biomes.get("Data"),
+                ByteArray,
+                fastnbt::ByteArray::new(vec![]),
+                tag_data_path
+            );
+            region.biomes = Some(Self::parse_3d_array_v2(
+                tag_data,
+                tag_data_path,
+                None,
+                size,
+                region.biome_palette.len(),
+            )?);
+        }
 
+        reporter.finish_region(volume)?;
         return Ok(region);
     }
 }
 
+fn parse_biome_palette(pal: &HashMap<String, Value>, tag_path: &str) -> Result<Vec<String>, Error> {
+    let mut result: Vec<Option<String>> = vec![None; pal.len()];
+    for (key, val) in pal {
+        let cur_tag_path = format!("{tag_path}/{}", key);
+        let idx = *unwrap_tag!(val, Int, 0, cur_tag_path);
+        if idx < 0 || idx >= pal.len() as i32 {
+            return Err(Error::InvalidValue {
+                tag_path: cur_tag_path,
+                error: format!(
+                    "Biome index {} in palette is out of range [0,{})",
+                    idx,
+                    pal.len()
+                ),
+            });
+        }
+        if result[idx as usize].is_some() {
+            return Err(Error::InvalidValue {
+                tag_path: cur_tag_path,
+                error: format!("Biome index {} is used more than once", idx),
+            });
+        }
+        // biomes without namespace belong to minecraft
+        let id = if key.contains(':') {
+            key.clone()
+        } else {
+            format!("minecraft:{key}")
+        };
+        result[idx as usize] = Some(id);
+    }
+    return Ok(result.into_iter().map(|b| b.unwrap()).collect());
+}
+
 fn parse_palette(pal: &HashMap<String, Value>, tag_path: &str) -> Result<Vec<Block>, Error> {
     if pal.len() >= 65536 {
         return Err(Error::PaletteTooLong(pal.len()));
@@ -599,12 +817,72 @@ fn parse_block_entity(
     return Ok((be, pos));
 }
 
+/// Parse a scheduled tick of `.schem` v3, like `{Pos: [I; x, y, z], Id: "minecraft:water",
+/// Delay: 5, Priority: 0}`
+fn parse_pending_tick_v3(
+    nbt: &HashMap<String, Value>,
+    tag_path: &str,
+    region_size: &[i32; 3],
+    is_block: bool,
+) -> Result<([i32; 3], PendingTick), Error> {
+    let pos_tag_path = format!("{tag_path}/Pos");
+    let pos_tag = unwrap_opt_tag!(
+        nbt.get("Pos"),
+        IntArray,
+        fastnbt::IntArray::new(vec![]),
+        pos_tag_path
+    );
+    let pos = common::parse_size_list(pos_tag.as_ref(), &pos_tag_path, false)?;
+    for dim in 0..3 {
+        if pos[dim] < 0 || pos[dim] >= region_size[dim] {
+            return Err(Error::BlockPosOutOfRange {
+                tag_path: pos_tag_path,
+                pos,
+                lower_bound: [0, 0, 0],
+                upper_bound: *region_size,
+            });
+        }
+    }
+
+    let id = unwrap_opt_tag!(
+        nbt.get("Id"),
+        String,
+        "".to_string(),
+        format!("{tag_path}/Id")
+    )
+    .clone();
+    let tick = PendingTick {
+        priority: *unwrap_opt_tag!(nbt.get("Priority"), Int, 0, format!("{tag_path}/Priority")),
+        sub_tick: 0,
+        time: *unwrap_opt_tag!(nbt.get("Delay"), Int, 0, format!("{tag_path}/Delay")),
+        info: if is_block {
+            PendingTickInfo::Block { id }
+        } else {
+            PendingTickInfo::Fluid { id }
+        },
+    };
+    return Ok((pos, tick));
+}
+
+/// Palette tag of `.schem` with block indices mapped to it
+struct SavedPalette {
+    tag: HashMap<String, Value>,
+    /// Block index of every region to index in `tag`
+    luts_of_block_idx: Vec<Vec<usize>>,
+    background_blk_index: u16,
+}
+
 #[allow(dead_code)]
 impl Schematic {
     pub fn supported_world_edit_13_versions() -> Vec<i32> {
         return vec![2, 3];
     }
 
+    /// Sponge schematic versions that can be loaded, v1 can be loaded but not saved
+    pub fn loadable_world_edit_13_versions() -> Vec<i32> {
+        return vec![1, 2, 3];
+    }
+
     pub fn metadata_world_edit_13(&self) -> Result<WE13MetaData, Error> {
         let mut result = WE13MetaData::from_data_version_i32(self.metadata.mc_data_version)?;
         // if let Some(raw_md) = &self.raw_metadata {
@@ -619,7 +897,12 @@ impl Schematic {
             .metadata
             .schem_we_offset
             .unwrap_or(self.metadata.schem_offset);
-        result.date = Some(self.metadata.time_modified);
+        if self.metadata.time_modified > 0 {
+            result.date = Some(self.metadata.time_modified);
+        }
+        result.name = Some(self.metadata.name.clone());
+        result.author = Some(self.metadata.author.clone());
+        result.description = Some(self.metadata.description.clone());
         result.width = self.shape()[0] as i16; //x
         result.height = self.shape()[1] as i16; //y
         result.length = self.shape()[2] as i16; //z
@@ -636,6 +919,7 @@ impl Schematic {
                 Value::Int(md.we_offset[dim]),
             );
         }
+        Self::write_metadata_strings(&mut md_nbt, md);
         dest.insert("Metadata".to_string(), Value::Compound(md_nbt));
         dest.insert(
             "Offset".to_string(),
@@ -674,9 +958,23 @@ impl Schematic {
             );
             md_nbt.insert("WorldEdit".to_string(), Value::Compound(we));
         }
+        Self::write_metadata_strings(&mut md_nbt, md);
         dest.insert("Metadata".to_string(), Value::Compound(md_nbt));
     }
 
+    fn write_metadata_strings(md_nbt: &mut HashMap<String, Value>, md: &WE13MetaData) {
+        let fields = [
+            ("Name", &md.name),
+            ("Author", &md.author),
+            ("Description", &md.description),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                md_nbt.insert(key.to_string(), Value::String(value.clone()));
+            }
+        }
+    }
+
     fn write_shape_v2(dest: &mut HashMap<String, Value>, shape: [i32; 3]) -> Result<(), Error> {
         for sz in shape {
             if sz < 0 {
@@ -702,6 +1000,10 @@ impl Schematic {
         full_palette: &Vec<(&Block, u64)>,
         option: &WorldEdit13SaveOption,
     ) -> Result<(HashMap<String, Value>, u16), Error> {
+        // block indices are handled as u16 when encoding block data
+        if full_palette.len() > u16::MAX as usize + 1 {
+            return Err(Error::PaletteTooLong(full_palette.len()));
+        }
         let mut pal = HashMap::with_capacity(full_palette.len());
         for (index, (blk, _)) in full_palette.iter().enumerate() {
             let id = blk.full_id();
@@ -728,13 +1030,16 @@ impl Schematic {
         return Ok((pal, background_blk_index));
     }
 
-    fn save_block_data_v2(
+    /// Call `f` with index in full palette of every block, in y-z-x order. Blocks are reported to
+    /// `reporter` row by row.
+    fn for_each_block_index_v2(
         &self,
         shape: [i32; 3],
         luts_of_block_idx: &[Vec<usize>],
         background_blk_index: u16,
-    ) -> Result<Vec<i8>, Error> {
-        let mut block_data = Vec::with_capacity(self.volume() as usize * 2);
+        mut reporter: Option<&mut ProgressReporter>,
+        f: &mut dyn FnMut(u16),
+    ) -> Result<(), Error> {
         for y in 0..shape[1] {
             for z in 0..shape[2] {
                 for x in 0..shape[0] {
@@ -750,19 +1055,133 @@ impl Schematic {
                             None => {}
                         }
                     }
-                    let cur_block_gindex = cur_block_gindex.unwrap_or_else(|| background_blk_index);
+                    f(cur_block_gindex.unwrap_or(background_blk_index));
+                }
+                if let Some(reporter) = reporter.as_mut() {
+                    reporter.advance(shape[0] as u64)?;
+                }
+            }
+        }
+        return Ok(());
+    }
 
-                    let encoded_index = encode_single_block(cur_block_gindex);
-                    for value in &encoded_index {
-                        block_data.push(*value);
-                        if *value >= 0 {
-                            break;
+    fn save_block_data_v2(
+        &self,
+        shape: [i32; 3],
+        luts_of_block_idx: &[Vec<usize>],
+        background_blk_index: u16,
+        reporter: Option<&mut ProgressReporter>,
+    ) -> Result<Vec<i8>, Error> {
+        let mut block_data = Vec::with_capacity(self.volume() as usize * 2);
+        self.for_each_block_index_v2(
+            shape,
+            luts_of_block_idx,
+            background_blk_index,
+            reporter,
+            &mut |idx| {
+                varint::encode_into(idx as u32, &mut block_data);
+            },
+        )?;
+        return Ok(block_data);
+    }
+
+    /// Write block data as a named byte array tag, encoding blocks straight into `dest`. The
+    /// length is counted in a first pass, since it's written before the content.
+    fn write_block_data_v2(
+        &self,
+        dest: &mut dyn std::io::Write,
+        key: &str,
+        shape: [i32; 3],
+        luts_of_block_idx: &[Vec<usize>],
+        background_blk_index: u16,
+        reporter: &mut ProgressReporter,
+    ) -> Result<(), Error> {
+        let mut length: u64 = 0;
+        self.for_each_block_index_v2(
+            shape,
+            luts_of_block_idx,
+            background_blk_index,
+            None,
+            &mut |idx| {
+                length += varint::encoded_len(idx as u32) as u64;
+            },
+        )?;
+        if length > i32::MAX as u64 {
+            return Err(Error::MetadataOverflow {
+                tag_path: key.to_string(),
+                value: length,
+                max: i32::MAX as u64,
+            });
+        }
+        common::write_tag_header(dest, 7, key)?;
+        common::write_raw_bytes(dest, &(length as i32).to_be_bytes())?;
+
+        const BUFFER_SIZE: usize = 1 << 16;
+        let mut buffer: Vec<u8> = Vec::with_capacity(BUFFER_SIZE + 8);
+        let mut result = Ok(());
+        self.for_each_block_index_v2(
+            shape,
+            luts_of_block_idx,
+            background_blk_index,
+            Some(reporter),
+            &mut |idx| {
+                varint::encode_into_bytes(idx as u32, &mut buffer);
+                if buffer.len() >= BUFFER_SIZE && result.is_ok() {
+                    result = dest.write_all(&buffer);
+                    buffer.clear();
+                }
+            },
+        )?;
+        result.map_err(|e| Error::NBTWriteError(e.into()))?;
+        return common::write_raw_bytes(dest, &buffer);
+    }
+
+    /// Save biomes in `.schem` v3 format, returns `None` if no region records biomes. Positions
+    /// not covered by any region with biomes are filled with the default biome.
+    fn save_biomes_v3(&self, shape: [i32; 3]) -> Option<HashMap<String, Value>> {
+        if self.regions.iter().all(|reg| reg.biomes.is_none()) {
+            return None;
+        }
+        let default_biome = Biome::default().id();
+        let mut palette: Vec<&str> = Vec::new();
+        let mut data = Vec::with_capacity(self.volume() as usize);
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let mut biome = default_biome.as_str();
+                    for reg in &self.regions {
+                        let r_pos = reg.global_pos_to_relative_pos([x, y, z]);
+                        if !reg.contains_coord(r_pos) {
+                            continue;
+                        }
+                        if let Some(b) = reg.biome_id_at(r_pos) {
+                            biome = b;
                         }
+                        break;
                     }
+                    let index = match palette.iter().position(|b| *b == biome) {
+                        Some(idx) => idx,
+                        None => {
+                            palette.push(biome);
+                            palette.len() - 1
+                        }
+                    };
+                    varint::encode_into(index as u32, &mut data);
                 }
             }
         }
-        return Ok(block_data);
+
+        let mut tag_palette = HashMap::with_capacity(palette.len());
+        for (idx, biome) in palette.iter().enumerate() {
+            tag_palette.insert(biome.to_string(), Value::Int(idx as i32));
+        }
+        let mut result = HashMap::new();
+        result.insert("Palette".to_string(), Value::Compound(tag_palette));
+        result.insert(
+            "Data".to_string(),
+            Value::ByteArray(fastnbt::ByteArray::new(data)),
+        );
+        return Some(result);
     }
 
     fn save_block_entities_v2(&self, shape: [i32; 3]) -> Result<Vec<Value>, Error> {
@@ -804,34 +1223,93 @@ impl Schematic {
         return Ok(be_list);
     }
 
-    /// Save `.schem` v2 to nbt
+    /// Returns block ticks and fluid ticks of `.schem` v3, ordered by delay and sub tick so that
+    /// ticks due in the same game tick keep their order.
+    fn save_pending_ticks_v3(&self, shape: [i32; 3]) -> (Vec<Value>, Vec<Value>) {
+        let mut ticks: Vec<([i32; 3], &PendingTick)> = Vec::new();
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    for tick in self.first_pending_tick_at([x, y, z]) {
+                        ticks.push(([x, y, z], tick));
+                    }
+                }
+            }
+        }
+        // stable sort, so ticks with equal delay and sub tick stay in y-z-x order
+        ticks.sort_by_key(|(_, tick)| (tick.time, tick.sub_tick));
+
+        let mut block_ticks = Vec::new();
+        let mut fluid_ticks = Vec::new();
+        for (pos, tick) in ticks {
+            let (id, dest) = match &tick.info {
+                PendingTickInfo::Block { id } => (id, &mut block_ticks),
+                PendingTickInfo::Fluid { id } => (id, &mut fluid_ticks),
+            };
+            let mut nbt = HashMap::new();
+            nbt.insert(
+                "Pos".to_string(),
+                Value::IntArray(fastnbt::IntArray::new(pos.to_vec())),
+            );
+            nbt.insert("Id".to_string(), Value::String(id.clone()));
+            nbt.insert("Delay".to_string(), Value::Int(tick.time));
+            nbt.insert("Priority".to_string(), Value::Int(tick.priority));
+            dest.push(Value::Compound(nbt));
+        }
+        return (block_ticks, fluid_ticks);
+    }
+
+    fn palette_v2(&self, option: &WorldEdit13SaveOption) -> Result<SavedPalette, Error> {
+        // the background block is added before arranging, so it takes part in sorting
+        let background_block = option.background_block.to_block();
+        let (full_palette, luts_of_block_idx) =
+            self.full_palette_ordered(option.palette_order, Some(&background_block));
+        let (tag, background_blk_index) = Self::save_palette_v2(&full_palette, option)?;
+        return Ok(SavedPalette {
+            tag,
+            luts_of_block_idx,
+            background_blk_index,
+        });
+    }
+
+    /// Save `.schem` v2 to nbt. Pending ticks are not saved, since v2 has no tag for them.
     pub fn to_nbt_world_edit_13_v2(
         &self,
         md: WE13MetaData,
         option: &WorldEdit13SaveOption,
+    ) -> Result<HashMap<String, Value>, Error> {
+        return self.world_edit_13_v2_nbt(md, option, true, None);
+    }
+
+    /// `BlockData` is omitted if `with_block_data` is false, so that it can be streamed.
+    fn world_edit_13_v2_nbt(
+        &self,
+        md: WE13MetaData,
+        option: &WorldEdit13SaveOption,
+        with_block_data: bool,
+        reporter: Option<&mut ProgressReporter>,
     ) -> Result<HashMap<String, Value>, Error> {
         let mut root = HashMap::new();
         // metadata
         Self::write_metadata_v2(&mut root, &md);
 
-        let (full_palette, luts_of_block_idx) = self.full_palette();
-        let background_blk_index: u16;
+        let SavedPalette {
+            tag: pal,
+            luts_of_block_idx,
+            background_blk_index,
+        } = self.palette_v2(option)?;
         // palette
-        {
-            let (pal, bbi) = Self::save_palette_v2(&full_palette, option)?;
-            background_blk_index = bbi;
-            root.insert("PaletteMax".to_string(), Value::Int(pal.len() as i32));
-            root.insert("Palette".to_string(), Value::Compound(pal));
-        }
+        root.insert("PaletteMax".to_string(), Value::Int(pal.len() as i32));
+        root.insert("Palette".to_string(), Value::Compound(pal));
 
         // shape
         let shape = self.shape();
         Self::write_shape_v2(&mut root, shape)?;
 
         // block data
-        {
+        if with_block_data {
             let block_data =
-                self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index)?;
+                self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index, reporter)?;
             root.insert(
                 "BlockData".to_string(),
                 Value::ByteArray(fastnbt::ByteArray::new(block_data)),
@@ -852,29 +1330,39 @@ impl Schematic {
         &self,
         md: WE13MetaData,
         option: &WorldEdit13SaveOption,
+    ) -> Result<HashMap<String, Value>, Error> {
+        return self.world_edit_13_v3_nbt(md, option, true, None);
+    }
+
+    /// `Blocks.Data` is omitted if `with_block_data` is false, so that it can be streamed.
+    fn world_edit_13_v3_nbt(
+        &self,
+        md: WE13MetaData,
+        option: &WorldEdit13SaveOption,
+        with_block_data: bool,
+        reporter: Option<&mut ProgressReporter>,
     ) -> Result<HashMap<String, Value>, Error> {
         let mut tag_schem = HashMap::new();
         // metadata
         Self::write_metadata_v3(&mut tag_schem, &md);
 
         let mut tag_blocks = HashMap::new();
-        let (full_palette, luts_of_block_idx) = self.full_palette();
-        let background_blk_index: u16;
+        let SavedPalette {
+            tag: pal,
+            luts_of_block_idx,
+            background_blk_index,
+        } = self.palette_v2(option)?;
         // palette
-        {
-            let (pal, bbi) = Self::save_palette_v2(&full_palette, option)?;
-            background_blk_index = bbi;
-            tag_blocks.insert("Palette".to_string(), Value::Compound(pal));
-        }
+        tag_blocks.insert("Palette".to_string(), Value::Compound(pal));
 
         // shape
         let shape = self.shape();
         Self::write_shape_v2(&mut tag_schem, shape)?;
 
         // block data
-        {
+        if with_block_data {
             let block_data =
-                self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index)?;
+                self.save_block_data_v2(shape, &luts_of_block_idx, background_blk_index, reporter)?;
             tag_blocks.insert(
                 "Data".to_string(),
                 Value::ByteArray(fastnbt::ByteArray::new(block_data)),
@@ -886,7 +1374,21 @@ impl Schematic {
             let be_list = self.save_block_entities_v2(shape)?;
             tag_blocks.insert("BlockEntities".to_string(), Value::List(be_list));
         }
+        // pending ticks
+        {
+            let (block_ticks, fluid_ticks) = self.save_pending_ticks_v3(shape);
+            if !block_ticks.is_empty() {
+                tag_blocks.insert("BlockTicks".to_string(), Value::List(block_ticks));
+            }
+            if !fluid_ticks.is_empty() {
+                tag_blocks.insert("FluidTicks".to_string(), Value::List(fluid_ticks));
+            }
+        }
         tag_schem.insert("Blocks".to_string(), Value::Compound(tag_blocks));
+        // biomes
+        if let Some(tag_biomes) = self.save_biomes_v3(shape) {
+            tag_schem.insert("Biomes".to_string(), Value::Compound(tag_biomes));
+        }
         let mut root = HashMap::new();
         root.insert("Schematic".to_string(), Value::Compound(tag_schem));
 
@@ -898,19 +1400,121 @@ impl Schematic {
         &self,
         option: &WorldEdit13SaveOption,
     ) -> Result<HashMap<String, Value>, Error> {
-        let md = self.metadata_world_edit_13()?;
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.to_nbt_world_edit_13(option);
+        }
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            1,
+            self.volume(),
+        );
+        let nbt = self.world_edit_13_nbt(option, true, Some(&mut reporter))?;
+        reporter.finish_region(self.volume())?;
+        return Ok(nbt);
+
+        //Self::write_metadata_v3(&mut root, &md)
+    }
+
+    /// Nbt of the version selected by `option`, block data is omitted if `with_block_data` is
+    /// false. Encoded blocks are reported to `reporter`.
+    fn world_edit_13_nbt(
+        &self,
+        option: &WorldEdit13SaveOption,
+        with_block_data: bool,
+        reporter: Option<&mut ProgressReporter>,
+    ) -> Result<HashMap<String, Value>, Error> {
+        let mut md = self.metadata_world_edit_13()?;
+        if let Some(version) = option.schem_version {
+            md.version = version;
+        }
+        if !option.keep_unmapped_metadata {
+            md.description = None;
+        }
         let schem_version = md.version;
 
         return match schem_version {
-            2 => self.to_nbt_world_edit_13_v2(md, option),
-            3 => self.to_nbt_world_edit_13_v3(md, option),
+            2 => self.world_edit_13_v2_nbt(md, option, with_block_data, reporter),
+            3 => self.world_edit_13_v3_nbt(md, option, with_block_data, reporter),
             _ => Err(Error::UnsupportedWorldEdit13Version {
                 version: schem_version,
                 supported_versions: Self::supported_world_edit_13_versions(),
             }),
         };
+    }
 
-        //Self::write_metadata_v3(&mut root, &md)
+    /// Compress `.schem` and write it to `dest`. Block data, the largest tag, is encoded straight
+    /// into the compressor instead of being collected into a byte array.
+    fn save_world_edit_13_stream(
+        &self,
+        dest: &mut dyn std::io::Write,
+        option: &WorldEdit13SaveOption,
+        filename: Option<&str>,
+    ) -> Result<(), Error> {
+        if let Some(converted) =
+            self.converted_to(option.target_data_version, option.on_downgrade.as_ref())
+        {
+            return converted.save_world_edit_13_stream(dest, option, filename);
+        }
+        let nbt = self.world_edit_13_nbt(option, false, None)?;
+        let palette = self.palette_v2(option)?;
+        let shape = self.shape();
+        let mut reporter = ProgressReporter::new(
+            option.progress.as_ref(),
+            option.cancel.as_ref(),
+            1,
+            self.volume(),
+        );
+
+        let mut encoder = common::CompressedWriter::new(
+            dest,
+            option.compress_method,
+            option.compress_level,
+            filename,
+        )?;
+        common::write_tag_header(&mut encoder, 10, "")?;
+        if let Some(Value::Compound(tag_schem)) = nbt.get("Schematic") {
+            // v3, data is in `Schematic.Blocks`
+            common::write_tag_header(&mut encoder, 10, "Schematic")?;
+            for (key, value) in tag_schem {
+                if key != "Blocks" {
+                    common::write_nbt_entry(&mut encoder, key, value)?;
+                }
+            }
+            common::write_tag_header(&mut encoder, 10, "Blocks")?;
+            if let Some(Value::Compound(tag_blocks)) = tag_schem.get("Blocks") {
+                for (key, value) in tag_blocks {
+                    common::write_nbt_entry(&mut encoder, key, value)?;
+                }
+            }
+            self.write_block_data_v2(
+                &mut encoder,
+                "Data",
+                shape,
+                &palette.luts_of_block_idx,
+                palette.background_blk_index,
+                &mut reporter,
+            )?;
+            common::write_raw_bytes(&mut encoder, &[0, 0])?;
+        } else {
+            for (key, value) in &nbt {
+                common::write_nbt_entry(&mut encoder, key, value)?;
+            }
+            self.write_block_data_v2(
+                &mut encoder,
+                "BlockData",
+                shape,
+                &palette.luts_of_block_idx,
+                palette.background_blk_index,
+                &mut reporter,
+            )?;
+        }
+        common::write_raw_bytes(&mut encoder, &[0])?;
+        encoder.finish()?;
+        reporter.finish_region(self.volume())?;
+        return Ok(());
     }
 
     /// Save `.schem` to writer
@@ -919,109 +1523,42 @@ impl Schematic {
         dest: &mut dyn std::io::Write,
         option: &WorldEdit13SaveOption,
     ) -> Result<(), Error> {
-        let nbt = match self.to_nbt_world_edit_13(option) {
-            Ok(n) => n,
-            Err(e) => return Err(e),
-        };
-        let mut encoder = GzBuilder::new()
-            .comment("Generated by mc_schem")
-            .write(dest, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
-        }
-        if let Err(e) = encoder.finish() {
-            return Err(Error::NBTWriteError(e.into()));
-        }
+        return self.save_world_edit_13_stream(dest, option, None);
+    }
 
-        return Ok(());
+    /// Save `.schem` to bytes
+    pub fn to_world_edit_13_bytes(&self, option: &WorldEdit13SaveOption) -> Result<Vec<u8>, Error> {
+        let mut dest = Vec::new();
+        self.save_world_edit_13_writer(&mut dest, option)?;
+        return Ok(dest);
     }
 
     /// Save `.schem` to file
+    #[cfg(feature = "fs")]
     pub fn save_world_edit_13_file(
         &self,
         filename: &str,
         option: &WorldEdit13SaveOption,
     ) -> Result<(), Error> {
-        let nbt = match self.to_nbt_world_edit_13(option) {
-            Ok(n) => n,
-            Err(e) => return Err(e),
-        };
-
         let file = match File::create(filename) {
             Ok(f) => f,
             Err(e) => return Err(Error::FileCreateError(e)),
         };
-
-        let mut encoder = GzBuilder::new()
-            .filename(filename)
-            .comment("Generated by mc_schem")
-            .write(file, option.compress_level);
-
-        let res: Result<(), fastnbt::error::Error> = fastnbt::to_writer(&mut encoder, &nbt);
-        if let Err(e) = res {
-            return Err(Error::NBTWriteError(e));
-        }
-        if let Err(e) = encoder.finish() {
-            return Err(Error::NBTWriteError(e.into()));
-        }
-
-        return Ok(());
-    }
-}
-
-fn encode_single_block(value: u16) -> [i8; 8] {
-    // let index = index as i32;
-    //
-    // let first_byte = index % 128 - 128;
-    // let second_byte = (index - first_byte) / 128 - 1;
-    // debug_assert!(first_byte < 0);
-    // debug_assert!(second_byte >= 1);
-    //
-    // return [first_byte as i8, second_byte as i8];
-    let mut result = [0; 8];
-    if value <= 127 {
-        result[0] = value as i8;
-        return result;
+        let mut writer = std::io::BufWriter::new(file);
+        self.save_world_edit_13_stream(&mut writer, option, Some(filename))?;
+        return std::io::Write::flush(&mut writer).map_err(|e| Error::NBTWriteError(e.into()));
     }
-
-    let mut value = value as i32;
-    let mut byte_idx = 0;
-    loop {
-        let cur_byte_val = value % 128;
-        value = value / 128;
-        let encoded_byte: i8;
-        if value > 0 {
-            // not the last byte
-            encoded_byte = (cur_byte_val - 128) as i8;
-            debug_assert!(encoded_byte < 0);
-        } else {
-            // the last byte of this block
-            encoded_byte = cur_byte_val as i8;
-            debug_assert!(encoded_byte > 0);
-        }
-        result[byte_idx] = encoded_byte;
-        byte_idx += 1;
-        if value <= 0 {
-            break;
-        }
-    }
-    return result;
 }
 
 #[test]
 fn test_schem_encode_decoding() {
     for id in 0..65536 {
-        let code = encode_single_block(id as u16);
-        let mut code_length = 0;
-        for val in code {
-            code_length += 1;
-            if val >= 0 {
-                break;
-            }
+        let code = varint::encode_all([id]);
+        assert_eq!(code.len(), varint::encoded_len(id));
+        for (idx, val) in code.iter().enumerate() {
+            assert_eq!(*val >= 0, idx == code.len() - 1);
         }
-        let decoded_id = parse_single_block(&code[0..code_length]);
-        assert_eq!(id, decoded_id);
+        let decoded_id = varint::decode_exact(&code, 1).unwrap();
+        assert_eq!(id, decoded_id[0]);
     }
 }
@@ -0,0 +1,489 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::Error;
+use fastnbt::{ByteArray, IntArray, LongArray, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Max depth of nested lists and compounds when parsing
+const MAX_DEPTH: usize = 512;
+
+/// Convert a nbt tag to SNBT. If `pretty` is true, compounds and lists of compounds or lists are
+/// split into lines with 4-space indentation. Keys of compounds are sorted, so the output is stable.
+pub fn value_to_snbt(value: &Value, pretty: bool) -> String {
+    let mut dest = String::new();
+    write_value(&mut dest, value, pretty, 0);
+    return dest;
+}
+
+/// Convert a nbt compound to SNBT, see [`value_to_snbt`]
+pub fn compound_to_snbt(compound: &HashMap<String, Value>, pretty: bool) -> String {
+    let mut dest = String::new();
+    write_compound(&mut dest, compound, pretty, 0);
+    return dest;
+}
+
+/// Parse a SNBT tag
+pub fn parse_value(text: &str) -> Result<Value, Error> {
+    let mut parser = Parser { text, pos: 0 };
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    return Ok(value);
+}
+
+/// Parse a SNBT compound
+pub fn parse_compound(text: &str) -> Result<HashMap<String, Value>, Error> {
+    return match parse_value(text)? {
+        Value::Compound(c) => Ok(c),
+        _ => Err(Error::SNBTParseError {
+            position: 0,
+            detail: "the root tag is not a compound".to_string(),
+        }),
+    };
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '+';
+}
+
+fn write_string(dest: &mut String, s: &str) {
+    dest.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => dest.push_str("\\\""),
+            '\\' => dest.push_str("\\\\"),
+            '\n' => dest.push_str("\\n"),
+            '\t' => dest.push_str("\\t"),
+            _ => dest.push(c),
+        }
+    }
+    dest.push('"');
+}
+
+fn write_key(dest: &mut String, key: &str) {
+    if !key.is_empty() && key.chars().all(is_unquoted_char) {
+        dest.push_str(key);
+    } else {
+        write_string(dest, key);
+    }
+}
+
+fn write_indent(dest: &mut String, depth: usize) {
+    for _ in 0..depth {
+        dest.push_str("    ");
+    }
+}
+
+fn write_array<T: std::fmt::Display>(dest: &mut String, prefix: char, suffix: &str, data: &[T]) {
+    dest.push('[');
+    dest.push(prefix);
+    dest.push(';');
+    for (idx, v) in data.iter().enumerate() {
+        if idx > 0 {
+            dest.push(',');
+        }
+        let _ = write!(dest, " {v}{suffix}");
+    }
+    dest.push(']');
+}
+
+fn write_compound(
+    dest: &mut String,
+    compound: &HashMap<String, Value>,
+    pretty: bool,
+    depth: usize,
+) {
+    if compound.is_empty() {
+        dest.push_str("{}");
+        return;
+    }
+    let mut keys: Vec<&String> = compound.keys().collect();
+    keys.sort();
+    dest.push('{');
+    for (idx, key) in keys.iter().enumerate() {
+        if idx > 0 {
+            dest.push(',');
+        }
+        if pretty {
+            dest.push('\n');
+            write_indent(dest, depth + 1);
+        }
+        write_key(dest, key);
+        dest.push_str(if pretty { ": " } else { ":" });
+        write_value(dest, &compound[*key], pretty, depth + 1);
+    }
+    if pretty {
+        dest.push('\n');
+        write_indent(dest, depth);
+    }
+    dest.push('}');
+}
+
+fn write_value(dest: &mut String, value: &Value, pretty: bool, depth: usize) {
+    match value {
+        Value::Byte(v) => {
+            let _ = write!(dest, "{v}b");
+        }
+        Value::Short(v) => {
+            let _ = write!(dest, "{v}s");
+        }
+        Value::Int(v) => {
+            let _ = write!(dest, "{v}");
+        }
+        Value::Long(v) => {
+            let _ = write!(dest, "{v}L");
+        }
+        Value::Float(v) => {
+            let _ = write!(dest, "{v:?}f");
+        }
+        Value::Double(v) => {
+            let _ = write!(dest, "{v:?}d");
+        }
+        Value::String(s) => write_string(dest, s),
+        Value::ByteArray(arr) => write_array(dest, 'B', "b", arr),
+        Value::IntArray(arr) => write_array(dest, 'I', "", arr),
+        Value::LongArray(arr) => write_array(dest, 'L', "L", arr),
+        Value::List(list) => {
+            let multi_line = pretty
+                && list
+                    .iter()
+                    .any(|v| matches!(v, Value::Compound(..) | Value::List(..)));
+            dest.push('[');
+            for (idx, v) in list.iter().enumerate() {
+                if idx > 0 {
+                    dest.push(',');
+                    if pretty && !multi_line {
+                        dest.push(' ');
+                    }
+                }
+                if multi_line {
+                    dest.push('\n');
+                    write_indent(dest, depth + 1);
+                }
+                write_value(dest, v, pretty, depth + 1);
+            }
+            if multi_line {
+                dest.push('\n');
+                write_indent(dest, depth);
+            }
+            dest.push(']');
+        }
+        Value::Compound(c) => write_compound(dest, c, pretty, depth),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// Byte offset of next character
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, detail: &str) -> Error {
+        return Error::SNBTParseError {
+            position: self.pos,
+            detail: detail.to_string(),
+        };
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.text[self.pos..].chars().next();
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("expected '{expected}'")));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        self.skip_whitespace();
+        return match self.peek() {
+            Some('{') => Ok(Value::Compound(self.parse_compound(depth)?)),
+            Some('[') => self.parse_list_or_array(depth),
+            Some('"') | Some('\'') => Ok(Value::String(self.parse_quoted()?)),
+            Some(_) => {
+                let begin = self.pos;
+                let token = self.parse_unquoted();
+                if token.is_empty() {
+                    return Err(self.error("expected a value"));
+                }
+                Ok(parse_scalar(token)
+                    .unwrap_or_else(|| Value::String(self.text[begin..self.pos].to_string())))
+            }
+            None => Err(self.error("unexpected end of text")),
+        };
+    }
+
+    fn parse_unquoted(&mut self) -> &'a str {
+        let begin = self.pos;
+        while let Some(c) = self.peek() {
+            if !is_unquoted_char(c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        return &self.text[begin..self.pos];
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, Error> {
+        let quote = self.peek().unwrap();
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += c.len_utf8();
+            if c == quote {
+                return Ok(result);
+            }
+            if c != '\\' {
+                result.push(c);
+                continue;
+            }
+            let escaped = match self.peek() {
+                Some(e) => e,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += escaped.len_utf8();
+            match escaped {
+                'n' => result.push('\n'),
+                't' => result.push('\t'),
+                '\\' | '"' | '\'' => result.push(escaped),
+                _ => return Err(self.error(&format!("invalid escape '\\{escaped}'"))),
+            }
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        return match self.peek() {
+            Some('"') | Some('\'') => self.parse_quoted(),
+            _ => {
+                let key = self.parse_unquoted();
+                if key.is_empty() {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(key.to_string())
+            }
+        };
+    }
+
+    fn parse_compound(&mut self, depth: usize) -> Result<HashMap<String, Value>, Error> {
+        self.expect('{')?;
+        let mut result = HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(result);
+        }
+        loop {
+            let key_pos = self.pos;
+            let key = self.parse_key()?;
+            self.expect(':')?;
+            let value = self.parse_value(depth + 1)?;
+            if result.insert(key.clone(), value).is_some() {
+                return Err(Error::SNBTParseError {
+                    position: key_pos,
+                    detail: format!("duplicated key \"{key}\""),
+                });
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_list_or_array(&mut self, depth: usize) -> Result<Value, Error> {
+        self.expect('[')?;
+        // typed arrays like [I; 1, 2]
+        let rest = self.text[self.pos..].trim_start();
+        let mut chars = rest.chars();
+        if let (Some(t @ ('B' | 'I' | 'L')), Some(';')) = (chars.next(), chars.next()) {
+            self.pos = self.text.len() - rest.len() + 2;
+            return self.parse_array(t);
+        }
+
+        let mut list = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::List(list));
+        }
+        loop {
+            let elem_pos = self.pos;
+            let value = self.parse_value(depth + 1)?;
+            if let Some(first) = list.first() {
+                if std::mem::discriminant(first) != std::mem::discriminant(&value) {
+                    return Err(Error::SNBTParseError {
+                        position: elem_pos,
+                        detail: "elements of a list must have the same type".to_string(),
+                    });
+                }
+            }
+            list.push(value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Value::List(list));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, array_type: char) -> Result<Value, Error> {
+        let mut values: Vec<Value> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let elem_pos = self.pos;
+                let token = self.parse_unquoted();
+                let value = match parse_scalar(token) {
+                    Some(v) => v,
+                    None => {
+                        return Err(Error::SNBTParseError {
+                            position: elem_pos,
+                            detail: format!("\"{token}\" is not a number"),
+                        })
+                    }
+                };
+                values.push(value);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    Some(']') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("expected ',' or ']'")),
+                }
+            }
+        }
+
+        let err = |detail: &str| Error::SNBTParseError {
+            position: self.pos,
+            detail: detail.to_string(),
+        };
+        return match array_type {
+            'B' => {
+                let mut data = Vec::with_capacity(values.len());
+                for v in values {
+                    match v {
+                        Value::Byte(b) => data.push(b),
+                        _ => return Err(err("byte array contains non-byte element")),
+                    }
+                }
+                Ok(Value::ByteArray(ByteArray::new(data)))
+            }
+            'I' => {
+                let mut data = Vec::with_capacity(values.len());
+                for v in values {
+                    match v {
+                        Value::Int(i) => data.push(i),
+                        Value::Short(i) => data.push(i as i32),
+                        Value::Byte(i) => data.push(i as i32),
+                        _ => return Err(err("int array contains non-int element")),
+                    }
+                }
+                Ok(Value::IntArray(IntArray::new(data)))
+            }
+            _ => {
+                let mut data = Vec::with_capacity(values.len());
+                for v in values {
+                    match v {
+                        Value::Long(i) => data.push(i),
+                        Value::Int(i) => data.push(i as i64),
+                        Value::Short(i) => data.push(i as i64),
+                        Value::Byte(i) => data.push(i as i64),
+                        _ => return Err(err("long array contains non-long element")),
+                    }
+                }
+                Ok(Value::LongArray(LongArray::new(data)))
+            }
+        };
+    }
+}
+
+/// Parse unquoted token as number or boolean, returns `None` if it's a string.
+fn parse_scalar(token: &str) -> Option<Value> {
+    match token {
+        "true" => return Some(Value::Byte(1)),
+        "false" => return Some(Value::Byte(0)),
+        _ => {}
+    }
+    let (body, suffix) = match token.char_indices().last() {
+        Some((idx, c)) if c.is_ascii_alphabetic() => (&token[..idx], Some(c)),
+        _ => (token, None),
+    };
+    if body.is_empty() {
+        return None;
+    }
+    return match suffix.map(|c| c.to_ascii_lowercase()) {
+        Some('b') => body.parse().ok().map(Value::Byte),
+        Some('s') => body.parse().ok().map(Value::Short),
+        Some('l') => body.parse().ok().map(Value::Long),
+        Some('f') => body.parse().ok().map(Value::Float),
+        Some('d') => body.parse().ok().map(Value::Double),
+        Some(_) => {
+            // exponent like 1e5 ends with a digit, so a letter here means a string
+            None
+        }
+        None => {
+            if let Ok(i) = body.parse::<i32>() {
+                Some(Value::Int(i))
+            } else if body.contains(['.', 'e', 'E']) {
+                body.parse().ok().map(Value::Double)
+            } else {
+                None
+            }
+        }
+    };
+}
@@ -0,0 +1,273 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::region::{Entity, Region, WorldSlice};
+use fastnbt::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Rotation around y axis, viewed from above. Same as rotation of structure blocks.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    None = 0,
+    Clockwise90 = 1,
+    Clockwise180 = 2,
+    CounterClockwise90 = 3,
+}
+
+/// Mirroring of horizontal axes. Same as mirror of structure blocks.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mirror {
+    None = 0,
+    /// Flip z axis
+    LeftRight = 1,
+    /// Flip x axis
+    FrontBack = 2,
+}
+
+impl Rotation {
+    /// Number of clockwise quarter turns
+    pub fn quarter_turns(&self) -> u8 {
+        return *self as u8;
+    }
+}
+
+/// Rotates and mirrors entities inside a box, like placing a structure with structure blocks.
+/// Mirror is applied before rotation. Besides position, these vanilla tags are transformed:
+/// - `Rotation`: yaw of all entities, including armor stands
+/// - `Motion`
+/// - `Facing`/`facing`: 3d facing of item frames, 2d facing of paintings
+/// - `ItemRotation`: rotation of item in item frames facing up or down, or mirrored
+/// - `TileX`/`TileY`/`TileZ`: block position of hanging entities
+/// - `Passengers`, recursively, and `Entity::passengers`
+#[derive(Debug, Clone)]
+pub struct EntityTransformer {
+    pub rotation: Rotation,
+    pub mirror: Mirror,
+    /// Shape(xyz) of the box before transforming
+    pub shape: [i32; 3],
+}
+
+/// 3d data value of directions: down, up, north, south, west, east
+const DIRECTIONS_3D: [[i32; 3]; 6] = [
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+    [-1, 0, 0],
+    [1, 0, 0],
+];
+
+/// 2d data value of directions: south, west, north, east
+const DIRECTIONS_2D: [[i32; 3]; 4] = [[0, 0, 1], [-1, 0, 0], [0, 0, -1], [1, 0, 0]];
+
+impl EntityTransformer {
+    pub fn new(rotation: Rotation, mirror: Mirror, shape: [i32; 3]) -> EntityTransformer {
+        return EntityTransformer {
+            rotation,
+            mirror,
+            shape,
+        };
+    }
+
+    /// Shape(xyz) of the box after transforming
+    pub fn transformed_shape(&self) -> [i32; 3] {
+        return if self.rotation.quarter_turns() % 2 == 1 {
+            [self.shape[2], self.shape[1], self.shape[0]]
+        } else {
+            self.shape
+        };
+    }
+
+    /// Transform a direction vector, `y` is kept
+    pub fn transform_vector(&self, v: [f64; 3]) -> [f64; 3] {
+        let [mut x, y, mut z] = v;
+        match self.mirror {
+            Mirror::None => {}
+            Mirror::LeftRight => z = -z,
+            Mirror::FrontBack => x = -x,
+        }
+        for _ in 0..self.rotation.quarter_turns() {
+            // clockwise: south -> west -> north -> east
+            (x, z) = (-z, x);
+        }
+        return [x, y, z];
+    }
+
+    /// Transform a position inside the box. For a block position, use `transform_block_pos`.
+    pub fn transform_position(&self, pos: [f64; 3]) -> [f64; 3] {
+        let shape = [
+            self.shape[0] as f64,
+            self.shape[1] as f64,
+            self.shape[2] as f64,
+        ];
+        let [mut x, y, mut z] = pos;
+        match self.mirror {
+            Mirror::None => {}
+            Mirror::LeftRight => z = shape[2] - z,
+            Mirror::FrontBack => x = shape[0] - x,
+        }
+        let (mut size_x, mut size_z) = (shape[0], shape[2]);
+        for _ in 0..self.rotation.quarter_turns() {
+            (x, z) = (size_z - z, x);
+            (size_x, size_z) = (size_z, size_x);
+        }
+        return [x, y, z];
+    }
+
+    /// Transform position of a block inside the box
+    pub fn transform_block_pos(&self, pos: [i32; 3]) -> [i32; 3] {
+        let center = [
+            pos[0] as f64 + 0.5,
+            pos[1] as f64 + 0.5,
+            pos[2] as f64 + 0.5,
+        ];
+        return Entity::block_pos_of(self.transform_position(center));
+    }
+
+    /// Transform yaw in degrees, 0 means facing south
+    pub fn transform_yaw(&self, yaw: f32) -> f32 {
+        let mut yaw = yaw;
+        match self.mirror {
+            Mirror::None => {}
+            Mirror::LeftRight => yaw = 180.0 - yaw,
+            Mirror::FrontBack => yaw = -yaw,
+        }
+        yaw += 90.0 * self.rotation.quarter_turns() as f32;
+        return yaw.rem_euclid(360.0);
+    }
+
+    fn transform_direction_index(&self, index: i8, directions: &[[i32; 3]]) -> i8 {
+        let dir = match directions.get(index as usize) {
+            Some(d) => d,
+            None => return index,
+        };
+        let v = self.transform_vector([dir[0] as f64, dir[1] as f64, dir[2] as f64]);
+        let v = [
+            v[0].round() as i32,
+            v[1].round() as i32,
+            v[2].round() as i32,
+        ];
+        return match directions.iter().position(|d| *d == v) {
+            Some(idx) => idx as i8,
+            None => index,
+        };
+    }
+
+    /// Transform entity position and tags, including all passengers
+    pub fn transform_entity(&self, entity: &mut Entity) {
+        entity.set_position(self.transform_position(entity.position));
+        self.transform_tags(&mut entity.tags);
+        for passenger in &mut entity.passengers {
+            self.transform_entity(passenger);
+        }
+    }
+
+    /// Transform tags of an entity, including `Pos` if it exists
+    pub fn transform_tags(&self, tags: &mut HashMap<String, Value>) {
+        let id = match tags.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            _ => String::new(),
+        };
+        let is_painting = id == "minecraft:painting" || id == "painting";
+
+        if let Some(Value::List(pos)) = tags.get_mut("Pos") {
+            if let [Value::Double(x), Value::Double(y), Value::Double(z)] = pos.as_mut_slice() {
+                [*x, *y, *z] = self.transform_position([*x, *y, *z]);
+            }
+        }
+        if let Some(Value::List(motion)) = tags.get_mut("Motion") {
+            if let [Value::Double(x), Value::Double(y), Value::Double(z)] = motion.as_mut_slice() {
+                [*x, *y, *z] = self.transform_vector([*x, *y, *z]);
+            }
+        }
+        if let Some(Value::List(rotation)) = tags.get_mut("Rotation") {
+            if let Some(Value::Float(yaw)) = rotation.first_mut() {
+                *yaw = self.transform_yaw(*yaw);
+            }
+        }
+
+        let mut facing_3d = None;
+        for key in ["Facing", "facing"] {
+            if let Some(Value::Byte(facing)) = tags.get_mut(key) {
+                if is_painting {
+                    *facing = self.transform_direction_index(*facing, &DIRECTIONS_2D);
+                } else {
+                    *facing = self.transform_direction_index(*facing, &DIRECTIONS_3D);
+                    facing_3d = Some(*facing);
+                }
+            }
+        }
+        if let Some(Value::Byte(item_rotation)) = tags.get_mut("ItemRotation") {
+            // item rotates in 8 steps of 45 degrees, clockwise viewed from the front
+            let mut r = *item_rotation as i32;
+            let mirrored = self.mirror != Mirror::None;
+            match facing_3d {
+                // facing up, viewed from above
+                Some(1) => {
+                    if mirrored {
+                        r = 8 - r;
+                    }
+                    r += 2 * self.rotation.quarter_turns() as i32;
+                }
+                // facing down, viewed from below
+                Some(0) => {
+                    if mirrored {
+                        r = 8 - r;
+                    }
+                    r -= 2 * self.rotation.quarter_turns() as i32;
+                }
+                _ => {
+                    if mirrored {
+                        r = 8 - r;
+                    }
+                }
+            }
+            *item_rotation = r.rem_euclid(8) as i8;
+        }
+
+        if let (Some(Value::Int(x)), Some(Value::Int(y)), Some(Value::Int(z))) =
+            (tags.get("TileX"), tags.get("TileY"), tags.get("TileZ"))
+        {
+            let [x, y, z] = self.transform_block_pos([*x, *y, *z]);
+            tags.insert("TileX".to_string(), Value::Int(x));
+            tags.insert("TileY".to_string(), Value::Int(y));
+            tags.insert("TileZ".to_string(), Value::Int(z));
+        }
+
+        if let Some(Value::List(passengers)) = tags.get_mut("Passengers") {
+            for passenger in passengers {
+                if let Value::Compound(p) = passenger {
+                    self.transform_tags(p);
+                }
+            }
+        }
+    }
+}
+
+impl Region {
+    /// Rotate and mirror all entities of region in the box of region. Blocks are not changed.
+    pub fn transform_entities(&mut self, rotation: Rotation, mirror: Mirror) {
+        let transformer = EntityTransformer::new(rotation, mirror, self.shape());
+        for entity in &mut self.entities {
+            transformer.transform_entity(entity);
+        }
+    }
+}
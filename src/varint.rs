@@ -0,0 +1,211 @@
+/*
+mc_schem is a rust library to generate, load, manipulate and save minecraft schematic files.
+Copyright (C) 2024  joseph
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Varint codec used by `BlockData` and biome data of sponge schematics(`.schem`). Every value
+//! takes 1 to 5 bytes, 7 bits per byte from the lowest, and the highest bit of a byte is set if
+//! more bytes follow. Bytes are `i8` like nbt byte arrays, so a byte is the last one of its value
+//! iff it's non-negative.
+
+use std::fmt::{Display, Formatter};
+
+/// Max bytes of a varint, enough for any `u32`
+pub const MAX_VARINT_BYTES: usize = 5;
+
+/// Malformed varint data, `index` is the index of the first byte of the broken value
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VarIntError {
+    /// Data ends before the value starting at `index` is complete
+    Truncated { index: usize },
+    /// The value starting at `index` has more than `MAX_VARINT_BYTES` bytes or exceeds
+    /// `u32::MAX`
+    Overflow { index: usize },
+    /// Data ends at `index` after `decoded` values, but `expected` values are required
+    NotEnoughValues {
+        index: usize,
+        decoded: usize,
+        expected: usize,
+    },
+    /// All `expected` values are decoded, but bytes remain from `index`
+    TrailingBytes { index: usize, expected: usize },
+}
+
+impl VarIntError {
+    /// Index of the byte where the error happens
+    pub fn index(&self) -> usize {
+        return match self {
+            VarIntError::Truncated { index }
+            | VarIntError::Overflow { index }
+            | VarIntError::NotEnoughValues { index, .. }
+            | VarIntError::TrailingBytes { index, .. } => *index,
+        };
+    }
+}
+
+impl Display for VarIntError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            VarIntError::Truncated { index } => {
+                write!(f, "varint starting at byte {index} is cut off by the end of data")
+            }
+            VarIntError::Overflow { index } => {
+                write!(f, "varint starting at byte {index} doesn't fit in 32 bits")
+            }
+            VarIntError::NotEnoughValues {
+                index,
+                decoded,
+                expected,
+            } => write!(
+                f,
+                "data ends at byte {index} after {decoded} values, {} values are missing, {expected} values in total",
+                expected - decoded
+            ),
+            VarIntError::TrailingBytes { index, expected } => write!(
+                f,
+                "{expected} values are decoded, but extra bytes remain from byte {index}"
+            ),
+        };
+    }
+}
+
+impl std::error::Error for VarIntError {}
+
+/// Count of bytes to encode `value`
+pub fn encoded_len(value: u32) -> usize {
+    let bits = 32 - value.leading_zeros() as usize;
+    return bits.div_ceil(7).max(1);
+}
+
+/// Append varint bytes of `value` to `dest`
+pub fn encode_into(value: u32, dest: &mut Vec<i8>) {
+    let mut value = value;
+    while value >= 0x80 {
+        dest.push(((value & 0x7F) | 0x80) as u8 as i8);
+        value >>= 7;
+    }
+    dest.push(value as i8);
+}
+
+/// Same as `encode_into`, but appends unsigned bytes, which can be written to a stream directly
+pub fn encode_into_bytes(value: u32, dest: &mut Vec<u8>) {
+    let mut value = value;
+    while value >= 0x80 {
+        dest.push(((value & 0x7F) | 0x80) as u8);
+        value >>= 7;
+    }
+    dest.push(value as u8);
+}
+
+/// Encode all values
+pub fn encode_all<I>(values: I) -> Vec<i8>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut result = Vec::new();
+    for value in values {
+        encode_into(value, &mut result);
+    }
+    return result;
+}
+
+/// Decode the value starting at `data[start]`. Returns the value and the index of the byte after
+/// it.
+pub fn decode_one(data: &[i8], start: usize) -> Result<(u32, usize), VarIntError> {
+    let mut result: u64 = 0;
+    for offset in 0..MAX_VARINT_BYTES {
+        let Some(byte) = data.get(start + offset) else {
+            return Err(VarIntError::Truncated { index: start });
+        };
+        let byte = *byte as u8;
+        result |= ((byte & 0x7F) as u64) << (7 * offset);
+        if byte & 0x80 == 0 {
+            return match u32::try_from(result) {
+                Ok(value) => Ok((value, start + offset + 1)),
+                Err(_) => Err(VarIntError::Overflow { index: start }),
+            };
+        }
+    }
+    return Err(VarIntError::Overflow { index: start });
+}
+
+/// Iterates over values of varint data. After an error, the iterator stops.
+#[derive(Debug, Clone)]
+pub struct VarIntDecoder<'a> {
+    data: &'a [i8],
+    position: usize,
+    failed: bool,
+}
+
+impl<'a> VarIntDecoder<'a> {
+    pub fn new(data: &'a [i8]) -> VarIntDecoder<'a> {
+        return VarIntDecoder {
+            data,
+            position: 0,
+            failed: false,
+        };
+    }
+
+    /// Index of the first byte of the next value
+    pub fn position(&self) -> usize {
+        return self.position;
+    }
+}
+
+impl Iterator for VarIntDecoder<'_> {
+    type Item = Result<u32, VarIntError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position >= self.data.len() {
+            return None;
+        }
+        return match decode_one(self.data, self.position) {
+            Ok((value, next)) => {
+                self.position = next;
+                Some(Ok(value))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        };
+    }
+}
+
+/// Decode exactly `count` values, every byte of `data` must be used.
+pub fn decode_exact(data: &[i8], count: usize) -> Result<Vec<u32>, VarIntError> {
+    let mut result = Vec::with_capacity(count.min(data.len()));
+    let mut decoder = VarIntDecoder::new(data);
+    while result.len() < count {
+        match decoder.next() {
+            Some(value) => result.push(value?),
+            None => {
+                return Err(VarIntError::NotEnoughValues {
+                    index: decoder.position(),
+                    decoded: result.len(),
+                    expected: count,
+                })
+            }
+        }
+    }
+    if decoder.position() < data.len() {
+        return Err(VarIntError::TrailingBytes {
+            index: decoder.position(),
+            expected: count,
+        });
+    }
+    return Ok(result);
+}
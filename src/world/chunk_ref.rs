@@ -1,3 +1,4 @@
+use crate::biome::Biome;
 use crate::block::Block;
 use crate::region::{BlockEntity, HasOffset, PendingTick, WorldSlice};
 use crate::world::{AbsolutePosIndexed, ChunkRefAbsolutePos, ChunkRefRelativePos, SubChunk};
@@ -6,7 +7,7 @@ use std::ops::Range;
 impl ChunkRefRelativePos<'_> {
     fn y_pos_to_section_number(&self, y_r: i32) -> i8 {
         let y_a = y_r + self.chunk.y_offset();
-        return (y_a / 16) as i8;
+        return y_a.div_euclid(16) as i8;
     }
 
     fn to_sub_chunk_r_pos(&self, r_pos: [i32; 3]) -> (i8, &SubChunk, [i32; 3]) {
@@ -80,7 +81,7 @@ impl WorldSlice for ChunkRefRelativePos<'_> {
 
 impl<'s, 'chunk: 's> ChunkRefAbsolutePos<'chunk> {
     fn to_sub_chunk_r_pos(&'s self, a_pos: [i32; 3]) -> (i8, &'chunk SubChunk, [i32; 3]) {
-        let sect_number = (a_pos[1] / 16) as i8;
+        let sect_number = a_pos[1].div_euclid(16) as i8;
         debug_assert!(self.chunk.sub_chunks.contains_key(&sect_number));
         let sub_chunk: &'chunk SubChunk = self.chunk.sub_chunks.get(&sect_number).unwrap();
         let o = self.offset();
@@ -92,6 +93,15 @@ impl<'s, 'chunk: 's> ChunkRefAbsolutePos<'chunk> {
         debug_assert!((0..16).contains(&r_pos[1]));
         return (sect_number, sub_chunk, r_pos);
     }
+
+    /// Biome at absolute position `a_pos`, `None` if the position is outside this chunk
+    pub fn biome_at(&'s self, a_pos: [i32; 3]) -> Option<Biome> {
+        if self.contains_coord(a_pos) {
+            let (_, sect, r_pos) = self.to_sub_chunk_r_pos(a_pos);
+            return Some(sect.biome_at(r_pos));
+        }
+        return None;
+    }
 }
 
 impl HasOffset for ChunkRefAbsolutePos<'_> {
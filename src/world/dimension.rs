@@ -1,7 +1,8 @@
+use crate::biome::Biome;
 use crate::block::Block;
 use crate::error::unwrap_opt_i32;
 use crate::raid::{Raid, RaidList};
-use crate::region::{BlockEntity, HasOffset, PendingTick};
+use crate::region::{BlockBox, BlockEntity, HasOffset, PendingTick, Region};
 use crate::schem::id_of_nbt_tag;
 #[allow(unused_imports)]
 use crate::world::{
@@ -154,10 +155,10 @@ impl Dimension {
 
     pub fn block_pos_to_chunk_pos(block_pos: [i32; 3]) -> (ChunkPos, i8) {
         let cpos = ChunkPos::from_global_pos(&XZCoordinate {
-            x: block_pos[0] / 16,
-            z: block_pos[2] / 16,
+            x: block_pos[0].div_euclid(16),
+            z: block_pos[2].div_euclid(16),
         });
-        let y = block_pos[1] / 16;
+        let y = block_pos[1].div_euclid(16);
         return (cpos, y as i8);
     }
 
@@ -247,6 +248,79 @@ impl Dimension {
 
         return Ok(());
     }
+
+    /// Copy blocks, biomes, block entities and entities inside `bbox` into a new region, with
+    /// offset `bbox.lower`. Positions in missing or unparsed chunks are left as air, with the
+    /// default biome.
+    pub fn to_region(&self, bbox: &BlockBox) -> Result<Region, Error> {
+        let size: [u64; 3] =
+            std::array::from_fn(|dim| (bbox.upper[dim] as i64 - bbox.lower[dim] as i64 + 1) as u64);
+        if size.iter().any(|len| *len > i32::MAX as u64) {
+            return Err(Error::SizeTooLarge {
+                size,
+                max_size: [i32::MAX as u64; 3],
+            });
+        }
+        let shape = size.map(|len| len as i32);
+        let mut region = Region::with_shape(shape);
+        region.offset = bbox.lower;
+        region.fill_biome(Biome::default());
+        // Vanilla biomes are few, so a small table avoids repeated palette searches
+        let mut biome_lut: HashMap<Biome, u16> = HashMap::new();
+
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                for x in 0..shape[0] {
+                    let a_pos = [bbox.lower[0] + x, bbox.lower[1] + y, bbox.lower[2] + z];
+                    if !self.contains_coord(a_pos) {
+                        continue;
+                    }
+                    let (chunk_pos, _) = Self::block_pos_to_chunk_pos(a_pos);
+                    let Some(chunk) = self.get_chunk(&chunk_pos) else {
+                        continue;
+                    };
+                    let chunk = chunk.as_absolute_pos(&chunk_pos);
+                    let r_pos = [x, y, z];
+                    if let Some(block) = chunk.block_at(a_pos) {
+                        if region.set_block(r_pos, block).is_err() {
+                            return Err(Error::PaletteTooLong(region.palette.len()));
+                        }
+                    }
+                    if let Some(biome) = chunk.biome_at(a_pos) {
+                        let idx = *biome_lut
+                            .entry(biome)
+                            .or_insert_with(|| region.find_or_append_biome(&biome.id()));
+                        if let Some(biomes) = &mut region.biomes {
+                            biomes[[y as usize, z as usize, x as usize]] = idx;
+                        }
+                    }
+                }
+            }
+        }
+
+        for variant in self.chunks.values() {
+            let ChunkVariant::Parsed(chunk) = variant else {
+                continue;
+            };
+            for (a_pos, be) in &chunk.block_entities {
+                if bbox.contains(*a_pos) {
+                    let r_pos = std::array::from_fn(|dim| a_pos[dim] - bbox.lower[dim]);
+                    region.block_entities.insert(r_pos, be.clone());
+                }
+            }
+            for entity in &chunk.entities {
+                if bbox.contains(entity.block_pos) {
+                    let mut entity = entity.clone();
+                    let lower = bbox.lower;
+                    let p = entity.position;
+                    entity.set_position(std::array::from_fn(|dim| p[dim] - lower[dim] as f64));
+                    region.entities.push(entity);
+                }
+            }
+        }
+
+        return Ok(region);
+    }
 }
 
 impl HasOffset for Dimension {
@@ -424,3 +498,24 @@ fn test_raids() {
     Dimension::from_files(&files.sub_directory("DIM-1"), false, -64..320, -1).unwrap();
     Dimension::from_files(&files.sub_directory("DIM1"), false, -64..320, 1).unwrap();
 }
+
+#[test]
+fn test_dimension_to_region() {
+    use crate::region::WorldSlice;
+    let files = FilesInMemory::from_7z_file("test_files/world/00_1.20.2.7z", "").unwrap();
+    let mut dim = Dimension::from_files(&files, false, -64..320, 0).unwrap();
+    dim.parse_all(0).unwrap();
+
+    let bbox = BlockBox::new([0, -64, 0], [15, 15, 15]);
+    let region = dim.to_region(&bbox).unwrap();
+    assert_eq!(region.offset, [0, -64, 0]);
+    assert_eq!(region.shape(), [16, 80, 16]);
+    for pos in [[0, 0, 0], [7, 50, 3], [15, 79, 15]] {
+        let a_pos = [pos[0], pos[1] - 64, pos[2]];
+        assert_eq!(region.block_at(pos), dim.block_at(a_pos));
+        let (chunk_pos, _) = Dimension::block_pos_to_chunk_pos(a_pos);
+        let chunk = dim.get_chunk(&chunk_pos).unwrap();
+        let biome = chunk.as_absolute_pos(&chunk_pos).biome_at(a_pos).unwrap();
+        assert_eq!(region.biome_at(pos), Some(biome));
+    }
+}
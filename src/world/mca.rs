@@ -152,7 +152,7 @@ impl MCARawData {
     pub fn to_nbt(&self) -> Result<NBTWithSource, Error> {
         let parse_opt: Result<HashMap<String, Value>, fastnbt::error::Error>;
 
-        if self.data[0..2] == [0x78, 0x9c] {
+        if self.data.as_slice().starts_with(&[0x78, 0x9c]) {
             //zlib
             // Some mcc files are stored as zlib, but the compress method is 130.
             // This is to fix minecraft's error(at least in 1.20.2
@@ -373,16 +373,20 @@ fn parse_mca_single_chunk(
 
     let offset_by_segment = u32::from_be_bytes([0, header[0], header[1], header[2]]);
     let num_segments = header[3] as u32;
-    if offset_by_segment < 2
-        || (num_segments + offset_by_segment) as usize > (mca_bytes.len() / SEGMENT_BYTES)
-    {
-        return Err(Error::InvalidSegmentRangeInMCA {
+    let invalid_range_err = || {
+        return Error::InvalidSegmentRangeInMCA {
             chunk_local_x: local_coord.x as i32,
             chunk_local_z: local_coord.z as i32,
             offset_by_segment,
             num_segments,
             total_segments: mca_bytes.len() / SEGMENT_BYTES,
-        });
+        };
+    };
+    if offset_by_segment < 2
+        || num_segments < 1
+        || (num_segments + offset_by_segment) as usize > (mca_bytes.len() / SEGMENT_BYTES)
+    {
+        return Err(invalid_range_err());
     }
 
     let data_beg_idx = offset_by_segment as usize * SEGMENT_BYTES;
@@ -394,6 +398,10 @@ fn parse_mca_single_chunk(
     if ![1, 128, 2, 129, 3, 130].contains(&compress_label) {
         return Err(Error::InvalidMCACompressType { compress_label });
     }
+    // the length recorded in chunk header comes from the file, it may point out of the file
+    if compress_label <= 127 && data_beg_idx + 5 + compressed_len > mca_bytes.len() {
+        return Err(invalid_range_err());
+    }
 
     if compress_label > 127 {
         let mcc_filename = chunk_pos.filename_mcc();
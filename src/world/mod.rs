@@ -28,7 +28,7 @@ use crate::block::Block;
 use crate::error::Error;
 use crate::raid::RaidList;
 use crate::region::{Light, PendingTick};
-use crate::{BlockEntity, Entity};
+use crate::{BlockEntity, Entity, WorldHeight};
 
 mod chunk;
 mod chunk_ref;
@@ -150,6 +150,12 @@ pub struct World {
 #[derive(Debug, Clone)]
 pub struct WorldLoadOption {
     parse_directly: bool,
+    /// Build height of the overworld, -64 to 319 by default
+    pub overworld_height: WorldHeight,
+    /// Build height of the nether, 0 to 255 by default
+    pub nether_height: WorldHeight,
+    /// Build height of the end, 0 to 255 by default
+    pub end_height: WorldHeight,
 }
 
 #[derive(Debug, Clone)]
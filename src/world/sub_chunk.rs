@@ -32,8 +32,10 @@ impl SubChunk {
         return ArrayView2::from_shape([8, 8], &self.biome_array).unwrap();
     }
 
+    /// Biome at `r_pos`. Biomes are stored in 4x4x4 cells, indexed in y,z,x like vanilla.
     pub fn biome_at(&self, r_pos: [i32; 3]) -> Biome {
-        return self.biome()[[(r_pos[2] / 2) as usize, (r_pos[0] / 2) as usize]];
+        let idx = (r_pos[1] / 4) * 16 + (r_pos[2] / 4) * 4 + r_pos[0] / 4;
+        return self.biome_array[idx as usize];
     }
 }
 
@@ -1,6 +1,6 @@
 #[allow(unused_imports)]
 use crate::world::{Dimension, FilesInMemory, FilesRead, World, WorldLoadOption};
-use crate::Error;
+use crate::{Error, WorldHeight};
 use std::collections::BTreeMap;
 #[allow(unused_imports)]
 use std::time;
@@ -9,6 +9,9 @@ impl Default for WorldLoadOption {
     fn default() -> Self {
         return Self {
             parse_directly: false,
+            overworld_height: WorldHeight::OVERWORLD,
+            nether_height: WorldHeight::LEGACY,
+            end_height: WorldHeight::LEGACY,
         };
     }
 }
@@ -19,12 +22,16 @@ impl World {
             dimensions: BTreeMap::new(),
         };
         for dim in [0, -1, 1] {
+            let height = match dim {
+                0 => &option.overworld_height,
+                -1 => &option.nether_height,
+                _ => &option.end_height,
+            };
+            let y_range = height.min_y..(height.max_y() + 1);
             let dimension = if dim == 0 {
-                Dimension::from_files(files, option.parse_directly, -64..320, dim)?
+                Dimension::from_files(files, option.parse_directly, y_range, dim)?
             } else {
                 let dir = format!("DIM{dim}");
-                // let y_range = if dim == -1 { 0..256 } else { -64..320 };
-                let y_range = 0..256;
                 Dimension::from_files(
                     &files.sub_directory(&dir),
                     option.parse_directly,
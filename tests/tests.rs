@@ -17,17 +17,26 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::env;
+#[cfg(feature = "fs")]
 use std::fs::{create_dir_all, File};
 // use std::io::Read;
 use fastnbt::Value;
+#[cfg(feature = "fs")]
 use flate2::read::GzDecoder;
-use flate2::{Compression, GzBuilder};
+use flate2::Compression;
+#[cfg(feature = "fs")]
+use flate2::GzBuilder;
+#[cfg(feature = "fs")]
 use mc_schem::block::CommonBlock;
+use mc_schem::region::{BlockBox, WorldSlice};
+#[cfg(feature = "fs")]
+use mc_schem::MetaDataIR;
 use mc_schem::{
     old_block, schem, Block, BlockEntity, DataVersion, LitematicaLoadOption, LitematicaSaveOption,
-    MetaDataIR, Region, Schematic, WorldEdit12LoadOption, WorldEdit13LoadOption,
-    WorldEdit13SaveOption,
+    MaterialListOption, Region, Schematic, VanillaStructureSaveOption, WorldEdit12LoadOption,
+    WorldEdit13LoadOption, WorldEdit13SaveOption,
 };
 use ndarray::Array3;
 use rand::Rng;
@@ -182,6 +191,7 @@ fn litematica_multi_bit_set_rw() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn litematica_3d_array_decode() {
     use crate::schem::LitematicaLoadOption;
     println!(
@@ -550,6 +560,7 @@ fn test_old_block_number_id_damage() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn parse_full_blocks_mc12() {
     let num_id_array;
     {
@@ -652,6 +663,7 @@ fn parse_full_blocks_mc12() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn make_mc12_numeric_lut() {
     let schem_file = "./test_files/schematic/full-blocks-1.12.2.schematic";
 
@@ -703,6 +715,7 @@ fn make_mc12_numeric_lut() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn load_save_vanilla_structure() {
     use schem::{VanillaStructureLoadOption, VanillaStructureSaveOption};
     let schem = Schematic::from_vanilla_structure_file(
@@ -729,6 +742,7 @@ fn load_save_vanilla_structure() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn load_save_litematica() {
     use schem::LitematicaLoadOption;
     //println!("Current dir: {}", env::current_dir().unwrap().to_string_lossy());
@@ -758,6 +772,7 @@ fn load_save_litematica() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn load_litematica_with_negative_size() {
     let src_dir = "./test_files/litematica";
     let src_filename = format!(
@@ -770,6 +785,7 @@ fn load_litematica_with_negative_size() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn load_save_world_edit13() {
     use schem::WorldEdit13LoadOption;
 
@@ -798,6 +814,7 @@ fn load_save_world_edit13() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn load_save_world_edit12() {
     use schem::WorldEdit12LoadOption;
     //let src_dir = "./test_files/schematic";
@@ -811,6 +828,7 @@ fn load_save_world_edit12() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn make_test_litematic() {
     let mut commands = Vec::with_capacity(16 * 16 * 16);
     for id in 0..256 {
@@ -901,6 +919,7 @@ fn make_test_litematic() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn correct_test_litematica() {
     let pos_block = [
         ([0, 0, 0], "white_concrete"),
@@ -940,6 +959,7 @@ fn correct_test_litematica() {
 
 #[test]
 #[allow(unused_assignments)]
+#[cfg(feature = "fs")]
 fn correct_test_mc13_plus() {
     let test_versions = ["1.14.4", "1.18.2", "1.19.4", "1.20.2"]; //,
     let mut err_counter = 0;
@@ -979,6 +999,7 @@ fn correct_test_mc13_plus() {
 
 #[test]
 #[allow(unused_assignments)]
+#[cfg(feature = "fs")]
 fn correct_test_mc12() {
     let test_versions = ["1.12.2"]; //,
     let mut err_counter = 0;
@@ -1053,6 +1074,7 @@ fn correct_test_mc12() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn test_merge_regions() {
     let mut schem = Schematic::from_litematica_file(
         "./test_files/litematica/multi-region01.litematic",
@@ -1090,6 +1112,5592 @@ fn test_3d_array_order() {
     }
 }
 
+#[test]
+fn test_spatial_index() {
+    let mut region = Region::with_shape([64, 16, 64]);
+    for x in 0..64 {
+        let mut entity = mc_schem::Entity::new();
+        entity.block_pos = [x, 3, x];
+        entity.position = [x as f64 + 0.5, 3.0, x as f64 + 0.5];
+        region.entities.push(entity);
+        region.set_block_entity_at([x, 1, 63 - x], BlockEntity::new());
+    }
+    let index = region.spatial_index(8);
+    for aabb in [
+        BlockBox::new([0, 0, 0], [63, 15, 63]),
+        BlockBox::new([10, 0, 5], [20, 15, 12]),
+        BlockBox::new([-5, 3, -5], [3, 3, 3]),
+        BlockBox::new([30, 4, 30], [40, 15, 40]),
+        BlockBox::new([i32::MIN; 3], [i32::MAX; 3]),
+        BlockBox::from_pos_shape([i32::MAX - 1; 3], [i32::MAX; 3]),
+    ] {
+        let indexed: Vec<[i32; 3]> = index
+            .entities_in(&aabb)
+            .iter()
+            .map(|e| e.block_pos)
+            .collect();
+        let scanned: Vec<[i32; 3]> = region
+            .entities_in(&aabb)
+            .iter()
+            .map(|e| e.block_pos)
+            .collect();
+        assert_eq!(indexed, scanned);
+        let indexed: Vec<[i32; 3]> = index
+            .block_entities_in(&aabb)
+            .iter()
+            .map(|(p, _)| *p)
+            .collect();
+        let scanned: Vec<[i32; 3]> = region
+            .block_entities_in(&aabb)
+            .iter()
+            .map(|(p, _)| *p)
+            .collect();
+        assert_eq!(indexed, scanned);
+    }
+    assert_eq!(
+        index
+            .entities_in(&BlockBox::new([10, 0, 5], [20, 15, 12]))
+            .len(),
+        3
+    );
+    assert_eq!(
+        BlockBox::from_pos_shape([i32::MAX - 1, 0, 0], [4, 4, 4]).upper,
+        [i32::MAX, 3, 3]
+    );
+}
+
+#[test]
+fn test_merge_adjacent_regions() {
+    let stone = Block::from_id("stone").unwrap();
+    let glass = Block::from_id("glass").unwrap();
+    let mut schem = Schematic::new();
+    for chunk_x in 0..4 {
+        let mut reg = Region::with_shape([16, 4, 16]);
+        reg.name = format!("chunk{chunk_x}");
+        reg.offset = [chunk_x * 16, 0, 0];
+        if chunk_x % 2 == 0 {
+            reg.find_or_append_to_palette(&glass);
+            reg.find_or_append_to_palette(&stone);
+        } else {
+            reg.find_or_append_to_palette(&stone);
+            reg.find_or_append_to_palette(&glass);
+        }
+        reg.set_block([chunk_x, 1, 2], &stone).unwrap();
+        reg.set_block([3, 2, chunk_x], &glass).unwrap();
+        reg.set_block_entity_at([5, 0, 5], BlockEntity::new());
+        schem.regions.push(reg);
+    }
+    // not aligned, should be kept
+    let mut reg = Region::with_shape([16, 4, 8]);
+    reg.offset = [0, 0, 16];
+    schem.regions.push(reg);
+
+    let before = schem.to_single_region(&Block::air());
+    assert_eq!(schem.merge_adjacent_regions(), 3);
+    assert_eq!(schem.regions.len(), 2);
+    assert_eq!(schem.regions[0].shape(), [64, 4, 16]);
+    assert_eq!(schem.regions[0].block_entities.len(), 4);
+    let after = schem.to_single_region(&Block::air());
+    for x in 0..64 {
+        for y in 0..4 {
+            for z in 0..24 {
+                assert_eq!(before.block_at([x, y, z]), after.block_at([x, y, z]));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_merge_alternate_palettes() {
+    let stone = Block::from_id("stone").unwrap();
+    let glass = Block::from_id("glass").unwrap();
+    let air = Block::air();
+    let mut lower = Region::with_shape([2, 1, 1]);
+    lower.palette = vec![air.clone(), stone.clone()];
+    lower.alternate_palettes = vec![vec![air.clone(), glass.clone()]];
+    lower.array_yzx[[0, 0, 1]] = 1;
+    // same variants with palette in another order
+    let mut upper = Region::with_shape([2, 1, 1]);
+    upper.offset = [2, 0, 0];
+    upper.palette = vec![stone.clone(), air.clone()];
+    upper.alternate_palettes = vec![vec![glass.clone(), air.clone()]];
+
+    let merged = lower.merge_adjacent(&upper).unwrap();
+    assert_eq!(merged.shape(), [4, 1, 1]);
+    assert_eq!(merged.alternate_palettes, lower.alternate_palettes);
+    assert_eq!(merged.block_at([2, 0, 0]), Some(&stone));
+
+    // variants differ, or only one region has them
+    let mut other_variant = upper.clone();
+    other_variant.alternate_palettes = vec![vec![stone.clone(), glass.clone()]];
+    assert!(lower.merge_adjacent(&other_variant).is_none());
+    let mut no_variant = upper.clone();
+    no_variant.alternate_palettes.clear();
+    assert!(lower.merge_adjacent(&no_variant).is_none());
+}
+
+#[test]
+fn test_merge_many_chunk_regions() {
+    let stone = Block::from_id("stone").unwrap();
+    let mut schem = Schematic::new();
+    // one region per chunk, like some exporters
+    for chunk_x in 0..40 {
+        for chunk_z in 0..40 {
+            let mut reg = Region::with_shape([16, 1, 16]);
+            reg.name = format!("chunk_{chunk_x}_{chunk_z}");
+            reg.offset = [chunk_x * 16, 0, chunk_z * 16];
+            reg.set_block([chunk_x % 16, 0, chunk_z % 16], &stone)
+                .unwrap();
+            schem.regions.push(reg);
+        }
+    }
+
+    assert_eq!(schem.merge_adjacent_regions(), 40 * 40 - 1);
+    assert_eq!(schem.regions.len(), 1);
+    let reg = &schem.regions[0];
+    assert_eq!(reg.offset, [0, 0, 0]);
+    assert_eq!(reg.shape(), [640, 1, 640]);
+    assert_eq!(reg.total_blocks(false), 40 * 40);
+    for chunk_x in 0..40 {
+        for chunk_z in 0..40 {
+            let pos = [chunk_x * 16 + chunk_x % 16, 0, chunk_z * 16 + chunk_z % 16];
+            assert_eq!(reg.block_at(pos), Some(&stone));
+        }
+    }
+}
+
+#[test]
+fn test_entity_block_pos() {
+    let mut entity = mc_schem::Entity::with_position([-0.5, 64.0, 0.999]);
+    assert_eq!(entity.block_pos, [-1, 64, 0]);
+    entity.set_position([-16.0, -0.0001, 15.5]);
+    assert_eq!(entity.block_pos, [-16, -1, 15]);
+    entity.position[0] = 3.2;
+    entity.update_block_pos();
+    assert_eq!(entity.block_pos, [3, -1, 15]);
+    assert_eq!(
+        mc_schem::Entity::block_pos_of([1e20, -1e20, f64::NAN]),
+        [i32::MAX, i32::MIN, 0]
+    );
+
+    // the field is public, so an entity can be built with a struct literal and fixed afterwards
+    let mut literal = mc_schem::Entity {
+        tags: HashMap::new(),
+        position: [-2.5, 0.0, 7.0],
+        block_pos: [0, 0, 0],
+        passengers: Vec::new(),
+    };
+    literal.update_block_pos();
+    assert_eq!(literal.block_pos, [-3, 0, 7]);
+}
+
+#[test]
+fn test_material_list() {
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([10, 10, 10]);
+    reg.fill_with(&Block::from_id("minecraft:stone").unwrap());
+    reg.set_block(
+        [0, 0, 0],
+        &Block::from_id("minecraft:oak_log[axis=x]").unwrap(),
+    )
+    .unwrap();
+    reg.set_block(
+        [0, 0, 1],
+        &Block::from_id("minecraft:oak_log[axis=y]").unwrap(),
+    )
+    .unwrap();
+    reg.set_block([0, 0, 2], &Block::structure_void()).unwrap();
+    reg.set_block([0, 0, 3], &Block::air()).unwrap();
+    schem.regions.push(reg);
+
+    let list = schem.material_list(&MaterialListOption::default());
+    assert_eq!(list.len(), 2);
+    assert_eq!(list["minecraft:stone"], 996);
+    assert_eq!(list["minecraft:oak_log"], 2);
+
+    let option = MaterialListOption {
+        full_block_state: true,
+        include_air: true,
+        ..MaterialListOption::default()
+    };
+    let list = schem.material_list(&option);
+    assert_eq!(list.len(), 4);
+    assert_eq!(list["minecraft:oak_log[axis=y]"], 1);
+    assert_eq!(list["minecraft:air"], 1);
+
+    let stacks = schem.material_list_in_stacks(&MaterialListOption::default());
+    let stone = stacks["minecraft:stone"];
+    assert_eq!(
+        (stone.shulker_boxes, stone.stacks, stone.items),
+        (0, 15, 36)
+    );
+    let big = schem::material_list::ItemStacks::from_count(27 * 64 * 2 + 65, 64);
+    assert_eq!(big.to_string(), "2 SB + 1 stacks + 1");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_litematica_bits_per_block() {
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([7, 5, 3]);
+    reg.name = "bits".to_string();
+    let blocks = ["minecraft:stone", "minecraft:glass", "minecraft:dirt"];
+    let mut rng = rand::thread_rng();
+    for x in 0..7 {
+        for y in 0..5 {
+            for z in 0..3 {
+                let id = blocks[rng.gen_range(0..blocks.len())];
+                reg.set_block([x, y, z], &Block::from_id(id).unwrap())
+                    .unwrap();
+            }
+        }
+    }
+    schem.regions.push(reg);
+
+    create_dir_all("./target/test/test_litematica_bits_per_block").unwrap();
+    for bits in [
+        schem::BitsPerBlock::Auto,
+        schem::BitsPerBlock::Forced(5),
+        schem::BitsPerBlock::Forced(16),
+    ] {
+        let option = LitematicaSaveOption {
+            bits_per_block: bits,
+            verify_block_states: true,
+            ..LitematicaSaveOption::default()
+        };
+        let filename = "./target/test/test_litematica_bits_per_block/out.litematic";
+        schem.save_litematica_file(filename, &option).unwrap();
+        let loaded = Schematic::from_litematica_file(filename, &LitematicaLoadOption::default())
+            .unwrap()
+            .0;
+        assert_eq!(loaded.regions[0].array_yzx, schem.regions[0].array_yzx);
+        assert_eq!(loaded.regions[0].palette, schem.regions[0].palette);
+    }
+
+    for bits in [0, 1, 17] {
+        let option = LitematicaSaveOption {
+            bits_per_block: schem::BitsPerBlock::Forced(bits),
+            ..LitematicaSaveOption::default()
+        };
+        assert!(schem.to_nbt_litematica(&option).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_schem_diff() {
+    let schem = Schematic::from_litematica_file(
+        "./test_files/litematica/multi-region01.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap()
+    .0;
+    let mut other = Schematic::from_litematica_file(
+        "./test_files/litematica/multi-region01.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap()
+    .0;
+    assert!(schem.diff(&other).is_empty());
+
+    let gold = Block::from_id("minecraft:gold_block").unwrap();
+    other.regions[0].set_block([0, 0, 0], &gold).unwrap();
+    other.regions[0].set_block_entity_at([1, 0, 0], BlockEntity::new());
+    let diff = schem.diff(&other);
+    let pos = other.regions[0].relative_pos_to_global_pos([0, 0, 0]);
+    assert_eq!(diff.blocks.len(), 1);
+    assert_eq!(diff.blocks[0].pos, pos);
+    assert_eq!(diff.blocks[0].after, Some(gold.clone()));
+    assert_eq!(diff.palette_added, vec![gold]);
+    assert_eq!(diff.block_entities.len(), 1);
+    assert!(diff.block_entities[0].before.is_none());
+    println!("{diff}");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_convert_file() {
+    create_dir_all("./target/test/test_convert_file").unwrap();
+    let inputs = [
+        "./test_files/litematica/test03.litematic",
+        "./test_files/schem/test02.schem",
+        "./test_files/vanilla_structure/test01.nbt",
+    ];
+    for input in inputs {
+        for ext in ["litematic", "schem", "nbt"] {
+            let output = format!("./target/test/test_convert_file/out.{ext}");
+            let report = mc_schem::convert_file(input, &output).unwrap();
+            println!("{input} -> {output}: {report:?}");
+            assert_eq!(report.output_format.extension(), format!(".{ext}"));
+            let reloaded = Schematic::from_file(&output).unwrap().0;
+            assert_eq!(reloaded.regions.len(), report.output_regions);
+        }
+    }
+    let report = mc_schem::convert_file(
+        "./test_files/schematic/full-blocks-1.12.2.schematic",
+        "./target/test/test_convert_file/out.litematic",
+    )
+    .unwrap();
+    assert_eq!(report.input_format, mc_schem::SchemFormat::WorldEdit12);
+    assert!(!report.warnings.is_empty());
+    let report = mc_schem::convert_file(
+        "./test_files/schem/test02.schem",
+        "./target/test/test_convert_file/out.schematic",
+    )
+    .unwrap();
+    assert_eq!(report.output_format, mc_schem::SchemFormat::WorldEdit12);
+    assert!(mc_schem::convert_file(
+        "./test_files/schem/test02.schem",
+        "./target/test/test_convert_file/out.unknown"
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_world_edit_13_v2_offset() {
+    let (mut schem, _) = Schematic::from_litematica_file(
+        "./test_files/litematica/test03.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap();
+    schem.metadata.mc_data_version = DataVersion::Java_1_20_4 as i32;
+    schem.metadata.schem_offset = [100, 64, -20];
+    schem.metadata.schem_we_offset = Some([-1, 0, -2]);
+
+    let mut option = WorldEdit13SaveOption::default();
+    option.schem_version = Some(2);
+    let nbt = schem.to_nbt_world_edit_13(&option).unwrap();
+    assert_eq!(nbt.get("Version"), Some(&Value::Int(2)));
+    assert!(!nbt.contains_key("Schematic"));
+    let palette_max = match nbt.get("PaletteMax") {
+        Some(Value::Int(v)) => *v,
+        _ => panic!("PaletteMax is missing"),
+    };
+    match nbt.get("Palette") {
+        Some(Value::Compound(pal)) => assert_eq!(pal.len() as i32, palette_max),
+        _ => panic!("Palette is missing"),
+    }
+    match nbt.get("Metadata") {
+        Some(Value::Compound(md)) => {
+            assert_eq!(md.get("WEOffsetX"), Some(&Value::Int(-1)));
+            assert_eq!(md.get("WEOffsetY"), Some(&Value::Int(0)));
+            assert_eq!(md.get("WEOffsetZ"), Some(&Value::Int(-2)));
+        }
+        _ => panic!("Metadata is missing"),
+    }
+
+    let (loaded, raw) =
+        Schematic::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption::default()).unwrap();
+    assert_eq!(raw.version, 2);
+    assert_eq!(raw.offset, [100, 64, -20]);
+    assert_eq!(raw.we_offset, [-1, 0, -2]);
+    assert_eq!(loaded.shape(), schem.shape());
+
+    // selected by data version if not specified
+    let nbt = schem
+        .to_nbt_world_edit_13(&WorldEdit13SaveOption::default())
+        .unwrap();
+    assert!(nbt.contains_key("Schematic"));
+}
+
+#[test]
+fn test_litematica_pending_ticks() {
+    use mc_schem::region::{PendingTick, PendingTickInfo};
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[3, 3, 3]);
+    let tick_sand = PendingTick {
+        priority: 0,
+        sub_tick: 5,
+        time: 2,
+        info: PendingTickInfo::Block {
+            id: "minecraft:sand".to_string(),
+        },
+    };
+    let tick_water = PendingTick {
+        priority: -1,
+        sub_tick: 6,
+        time: 5,
+        info: PendingTickInfo::Fluid {
+            id: "minecraft:flowing_water".to_string(),
+        },
+    };
+    region
+        .pending_ticks
+        .insert([1, 2, 1], vec![tick_sand.clone(), tick_water.clone()]);
+    region
+        .pending_ticks
+        .insert([0, 0, 2], vec![tick_water.clone()]);
+    schem.regions.push(region);
+
+    let nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let (loaded, _) =
+        Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    let region = &loaded.regions[0];
+    assert_eq!(region.pending_ticks.len(), 2);
+    let ticks = &region.pending_ticks[&[1, 2, 1]];
+    assert_eq!(ticks.len(), 2);
+    assert!(ticks.contains(&tick_sand));
+    assert!(ticks.contains(&tick_water));
+    assert_eq!(region.pending_ticks[&[0, 0, 2]], vec![tick_water]);
+}
+
+#[test]
+fn test_block_typed_properties() {
+    use mc_schem::block::{Axis, DoubleBlockHalf, Facing, Half};
+    let mut blk =
+        Block::from_id("minecraft:oak_stairs[facing=north,half=top,waterlogged=true]").unwrap();
+    assert_eq!(blk.get_property_as::<Facing>("facing"), Some(Facing::North));
+    assert_eq!(blk.get_property_as::<Half>("half"), Some(Half::Top));
+    assert_eq!(blk.get_property_as::<DoubleBlockHalf>("half"), None);
+    assert_eq!(blk.get_property_as::<Axis>("axis"), None);
+    assert!(blk.is_waterlogged());
+
+    blk.set_property("facing", &Facing::North.opposite());
+    blk.set_property("half", &Half::Bottom);
+    blk.set_waterlogged(false);
+    assert_eq!(
+        blk.full_id(),
+        "minecraft:oak_stairs[facing=south,half=bottom,waterlogged=false]"
+    );
+
+    let mut log = Block::from_id("minecraft:oak_log").unwrap();
+    log.set_property("axis", &Facing::East.axis());
+    assert_eq!(log.full_id(), "minecraft:oak_log[axis=x]");
+    assert_eq!(log.get_property_as::<Axis>("axis"), Some(Axis::X));
+    assert!(!log.is_waterlogged());
+}
+
+#[test]
+#[cfg(feature = "block_registry")]
+fn test_block_registry() {
+    use mc_schem::block_registry::BlockRegistry;
+    let dv = DataVersion::Java_1_20_4 as i32;
+    let registry = BlockRegistry::embedded(dv);
+    assert!(!registry.is_empty());
+
+    let stairs = Block::from_id("minecraft:oak_stairs[facing=east]").unwrap();
+    stairs.validate(dv).unwrap();
+    let mut canonical = stairs.clone();
+    canonical.canonicalize().unwrap();
+    assert_eq!(
+        canonical.full_id(),
+        "minecraft:oak_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]"
+    );
+
+    assert!(Block::from_id("minecraft:oak_stairs[facing=up]")
+        .unwrap()
+        .validate(dv)
+        .is_err());
+    assert!(Block::from_id("minecraft:stone[lit=true]")
+        .unwrap()
+        .validate(dv)
+        .is_err());
+    // unknown blocks are accepted
+    Block::from_id("mymod:machine[tier=9]")
+        .unwrap()
+        .validate(dv)
+        .unwrap();
+
+    let (schem, _) = Schematic::from_litematica_file(
+        "./test_files/litematica/test03.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap();
+    schem.validate_palettes().unwrap();
+
+    // registries differ between versions
+    assert_eq!(
+        BlockRegistry::embedded_data_versions(),
+        vec![DataVersion::Java_1_16_5 as i32, dv]
+    );
+    let leaves = Block::from_id("minecraft:oak_leaves[waterlogged=true]").unwrap();
+    leaves.validate(dv).unwrap();
+    assert!(leaves.validate(DataVersion::Java_1_16_5 as i32).is_err());
+    let def = BlockRegistry::embedded(dv)
+        .get(&Block::from_id("minecraft:lantern").unwrap())
+        .unwrap();
+    assert_eq!(
+        def.default_block().unwrap().full_id(),
+        "minecraft:lantern[hanging=false,waterlogged=false]"
+    );
+
+    // validation at load time
+    let mut broken = Schematic::new();
+    let mut reg = Region::with_shape([1, 1, 1]);
+    reg.name = "broken".to_string();
+    reg.set_block([0, 0, 0], &Block::from_id("oak_stairs[facing=up]").unwrap())
+        .unwrap();
+    broken.regions.push(reg);
+    broken.metadata.mc_data_version = dv;
+    let bytes = broken
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+    Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+    let option = LitematicaLoadOption {
+        validate_blocks: true,
+        ..LitematicaLoadOption::default()
+    };
+    let err = Schematic::from_litematica_bytes(&bytes, &option).unwrap_err();
+    assert!(err
+        .tag_path()
+        .is_some_and(|p| p.starts_with("/Regions/broken/BlockStatePalette")));
+
+    // generated registries list every block, so unknown vanilla blocks are rejected
+    let text =
+        "# Generated by generate_registry.py from the blocks.json data report, do not edit.\n\
+                minecraft:stone\n\
+                minecraft:lantern hanging=false|true\n";
+    let complete = BlockRegistry::parse(dv, text).unwrap();
+    assert!(complete.is_complete());
+    complete
+        .validate(&Block::from_id("minecraft:lantern[hanging=true]").unwrap())
+        .unwrap();
+    assert!(matches!(
+        complete.validate(&Block::from_id("minecraft:no_such_block").unwrap()),
+        Err(mc_schem::Error::UnknownBlock { .. })
+    ));
+    complete
+        .validate(&Block::from_id("mymod:machine").unwrap())
+        .unwrap();
+    let partial = BlockRegistry::parse(dv, "minecraft:stone\n").unwrap();
+    assert!(!partial.is_complete());
+    partial
+        .validate(&Block::from_id("minecraft:no_such_block").unwrap())
+        .unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "block_registry"))]
+fn test_validate_blocks_without_registry() {
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([1, 1, 1])
+        .set([0, 0, 0], &Block::from_id("stone").unwrap())
+        .build();
+    let bytes = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+    let option = LitematicaLoadOption {
+        validate_blocks: true,
+        ..LitematicaLoadOption::default()
+    };
+    assert!(matches!(
+        Schematic::from_litematica_bytes(&bytes, &option),
+        Err(mc_schem::Error::FeatureDisabled {
+            feature: "block_registry"
+        })
+    ));
+    Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+}
+
+#[test]
+fn test_region_biomes() {
+    use mc_schem::Biome;
+    let mut region = Region::with_shape([4, 3, 2]);
+    assert_eq!(region.biome_at([0, 0, 0]), None);
+    region.set_biome([1, 2, 1], Biome::cherry_grove).unwrap();
+    assert_eq!(region.biome_at([1, 2, 1]), Some(Biome::cherry_grove));
+    assert_eq!(region.biome_at([0, 0, 0]), Some(Biome::the_void));
+    assert!(region.set_biome([4, 0, 0], Biome::plains).is_err());
+    region
+        .set_biome_id([3, 0, 0], "terralith:yellowstone")
+        .unwrap();
+    assert_eq!(region.biome_at([3, 0, 0]), None);
+
+    let mut schem = Schematic::new();
+    schem.metadata.mc_data_version = DataVersion::Java_1_20_4 as i32;
+    schem.regions.push(region);
+    let nbt = schem
+        .to_nbt_world_edit_13(&WorldEdit13SaveOption::default())
+        .unwrap();
+    let (loaded, _) =
+        Schematic::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption::default()).unwrap();
+    let region = &loaded.regions[0];
+    assert!(region.biomes.is_some());
+    assert_eq!(region.biome_at([1, 2, 1]), Some(Biome::cherry_grove));
+    assert_eq!(region.biome_at([3, 0, 1]), Some(Biome::the_void));
+    assert_eq!(region.biome_id_at([3, 0, 0]), Some("terralith:yellowstone"));
+
+    let mut region = region.clone();
+    region.fill_biome(Biome::desert);
+    assert_eq!(region.biome_at([1, 2, 1]), Some(Biome::desert));
+    region.clear_biomes();
+    assert_eq!(region.biome_at([1, 2, 1]), None);
+}
+
+#[test]
+fn test_required_mods() {
+    let mut schem = Schematic::new();
+    let mut region = Region::with_shape([2, 1, 1]);
+    region
+        .set_block(
+            [0, 0, 0],
+            &Block::from_id("create:cogwheel[axis=y]").unwrap(),
+        )
+        .unwrap();
+    region
+        .set_block([1, 0, 0], &Block::from_id("minecraft:stone").unwrap())
+        .unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("mekanism:digital_miner".to_string()),
+    );
+    region.block_entities.insert([1, 0, 0], be);
+    region
+        .set_biome_id([0, 0, 0], "terralith:yellowstone")
+        .unwrap();
+    schem.regions.push(region);
+
+    let mods: Vec<String> = schem.required_mods().into_iter().collect();
+    assert_eq!(mods, vec!["create", "mekanism", "terralith"]);
+
+    let nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    match nbt.get("Metadata") {
+        Some(Value::Compound(md)) => assert_eq!(
+            md.get("RequiredMods"),
+            Some(&Value::List(vec![
+                Value::String("create".to_string()),
+                Value::String("mekanism".to_string()),
+                Value::String("terralith".to_string()),
+            ]))
+        ),
+        _ => panic!("Metadata is missing"),
+    }
+    let (loaded, _) =
+        Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(
+        loaded.regions[0].palette[loaded.regions[0].array_yzx[[0, 0, 0]] as usize].full_id(),
+        "create:cogwheel[axis=y]"
+    );
+    assert_eq!(loaded.required_mods().len(), 2);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_error_code() {
+    let err = Schematic::from_litematica_file(
+        "./test_files/litematica/no_such_file.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, mc_schem::error::Error::FileOpenError(_)));
+    assert_eq!(err.code(), 11);
+    assert_eq!(
+        mc_schem::error::Error::CustomError("".to_string()).code(),
+        44
+    );
+}
+
+#[test]
+fn test_bytes_round_trip() {
+    use mc_schem::SchemFormat;
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[4, 3, 2]);
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let stairs = Block::from_id("minecraft:oak_stairs[facing=east,half=top]").unwrap();
+    region.set_block([0, 0, 0], &stone).unwrap();
+    region.set_block([3, 2, 1], &stairs).unwrap();
+    schem.regions.push(region);
+
+    for format in [
+        SchemFormat::Litematica,
+        SchemFormat::VanillaStructure,
+        SchemFormat::WorldEdit13,
+    ] {
+        let bytes = schem.to_bytes(&format).unwrap();
+        let (loaded, _, detected) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+        assert_eq!(detected, format);
+        assert_eq!(loaded.shape(), [4, 3, 2]);
+        assert_eq!(loaded.first_block_at([0, 0, 0]), Some(&stone));
+        assert_eq!(loaded.first_block_at([3, 2, 1]), Some(&stairs));
+    }
+
+    let bytes = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+    let (loaded, _) =
+        Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(loaded.regions.len(), 1);
+    let bytes = schem.to_bytes(&SchemFormat::WorldEdit12).unwrap();
+    let (_, _, detected) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+    assert_eq!(detected, SchemFormat::WorldEdit12);
+}
+
+#[test]
+fn test_serde_json() {
+    use mc_schem::region::{BlockEntity, PendingTickInfo};
+    use mc_schem::{Entity, PendingTick};
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[2, 2, 2]);
+    let chest = Block::from_id("minecraft:chest[facing=north]").unwrap();
+    region.set_block([1, 0, 1], &chest).unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    let mut item = HashMap::new();
+    item.insert("Count".to_string(), Value::Byte(3));
+    item.insert("Slot".to_string(), Value::Byte(0));
+    be.tags.insert(
+        "Items".to_string(),
+        Value::List(vec![Value::Compound(item)]),
+    );
+    region.block_entities.insert([1, 0, 1], be);
+    region.pending_ticks.insert(
+        [0, 1, 0],
+        vec![PendingTick {
+            priority: 0,
+            sub_tick: 1,
+            time: 2,
+            info: PendingTickInfo::Block {
+                id: "minecraft:sand".to_string(),
+            },
+        }],
+    );
+    let mut entity = Entity::new();
+    entity.set_position([0.5, 1.0, 0.5]);
+    entity.tags.insert(
+        "UUID".to_string(),
+        Value::IntArray(fastnbt::IntArray::new(vec![1, -2, 3, -4])),
+    );
+    region.entities.push(entity);
+    region.fill_biome_id("minecraft:plains");
+    schem.regions.push(region);
+
+    let json = serde_json::to_string(&schem).unwrap();
+    let loaded: Schematic = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        loaded.metadata.mc_data_version,
+        schem.metadata.mc_data_version
+    );
+    let region = &loaded.regions[0];
+    assert_eq!(region.array_yzx, schem.regions[0].array_yzx);
+    assert_eq!(region.palette, schem.regions[0].palette);
+    assert_eq!(
+        region.block_entities[&[1, 0, 1]].tags,
+        schem.regions[0].block_entities[&[1, 0, 1]].tags
+    );
+    assert_eq!(
+        region.pending_ticks[&[0, 1, 0]],
+        schem.regions[0].pending_ticks[&[0, 1, 0]]
+    );
+    assert_eq!(region.entities[0].block_pos, [0, 1, 0]);
+    assert_eq!(region.entities[0].tags, schem.regions[0].entities[0].tags);
+    assert_eq!(region.biome_id_at([1, 1, 1]), Some("minecraft:plains"));
+
+    // corrupted regions are rejected instead of panicking later
+    let mut bad = schem.regions[0].clone();
+    bad.array_yzx[[0, 0, 0]] = bad.palette.len() as u16;
+    let json = serde_json::to_string(&bad).unwrap();
+    assert!(serde_json::from_str::<Region>(&json).is_err());
+
+    let mut bad = schem.regions[0].clone();
+    bad.biomes = Some(Array3::zeros([1, 2, 2]));
+    let json = serde_json::to_string(&bad).unwrap();
+    assert!(serde_json::from_str::<Region>(&json).is_err());
+
+    let mut bad = schem.regions[0].clone();
+    let be = bad.block_entities[&[1, 0, 1]].clone();
+    bad.block_entities.insert([2, 0, 0], be);
+    let json = serde_json::to_string(&bad).unwrap();
+    assert!(serde_json::from_str::<Region>(&json).is_err());
+}
+
+#[test]
+fn test_snbt() {
+    use mc_schem::snbt;
+    use mc_schem::SchemFormat;
+    let text = r#"{a: 1b, "b c": [I; 1, -2], d: [{e: 'it\'s'}, {e: "x"}], f: 1.5f, g: -3L, h: 2.0, i: true}"#;
+    let parsed = snbt::parse_compound(text).unwrap();
+    assert_eq!(parsed["a"], Value::Byte(1));
+    assert_eq!(
+        parsed["b c"],
+        Value::IntArray(fastnbt::IntArray::new(vec![1, -2]))
+    );
+    assert_eq!(parsed["f"], Value::Float(1.5));
+    assert_eq!(parsed["g"], Value::Long(-3));
+    assert_eq!(parsed["h"], Value::Double(2.0));
+    assert_eq!(parsed["i"], Value::Byte(1));
+    match &parsed["d"] {
+        Value::List(l) => match &l[0] {
+            Value::Compound(c) => assert_eq!(c["e"], Value::String("it's".to_string())),
+            _ => panic!("expected compound"),
+        },
+        _ => panic!("expected list"),
+    }
+    for pretty in [true, false] {
+        let written = snbt::compound_to_snbt(&parsed, pretty);
+        assert_eq!(snbt::parse_compound(&written).unwrap(), parsed);
+    }
+    assert!(snbt::parse_compound("{a: [1, 2b]}").is_err());
+    let err = snbt::parse_compound("{a: 1,, }").unwrap_err();
+    assert_eq!(err.code(), 45);
+
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[3, 2, 2]);
+    let stairs = Block::from_id("minecraft:oak_stairs[facing=east,half=top]").unwrap();
+    region.set_block([2, 1, 1], &stairs).unwrap();
+    schem.regions.push(region);
+    for format in [
+        SchemFormat::Litematica,
+        SchemFormat::VanillaStructure,
+        SchemFormat::WorldEdit13,
+    ] {
+        let text = schem.to_snbt(&format).unwrap();
+        let (loaded, _, detected) = Schematic::from_snbt(&text).unwrap();
+        assert_eq!(detected, format);
+        assert_eq!(loaded.first_block_at([2, 1, 1]), Some(&stairs));
+    }
+}
+
+#[test]
+fn test_compress_method() {
+    use mc_schem::schem::CompressMethod;
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[2, 2, 2]);
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    region.set_block([1, 1, 1], &stone).unwrap();
+    schem.regions.push(region);
+
+    for (method, head) in [
+        (CompressMethod::Gzip, 0x1f),
+        (CompressMethod::Zlib, 0x78),
+        (CompressMethod::Uncompressed, 0x0a),
+    ] {
+        let option = LitematicaSaveOption {
+            compress_method: method,
+            compress_level: Compression::fast(),
+            ..LitematicaSaveOption::default()
+        };
+        let bytes = schem.to_litematica_bytes(&option).unwrap();
+        assert_eq!(bytes[0], head);
+        let (loaded, _) =
+            Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+        assert_eq!(loaded.first_block_at([1, 1, 1]), Some(&stone));
+
+        let option = VanillaStructureSaveOption {
+            compress_method: method,
+            ..VanillaStructureSaveOption::default()
+        };
+        let bytes = schem.to_vanilla_structure_bytes(&option).unwrap();
+        assert_eq!(bytes[0], head);
+        let (loaded, ..) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+        assert_eq!(loaded.first_block_at([1, 1, 1]), Some(&stone));
+
+        let option = WorldEdit13SaveOption {
+            compress_method: method,
+            ..WorldEdit13SaveOption::default()
+        };
+        let bytes = schem.to_world_edit_13_bytes(&option).unwrap();
+        assert_eq!(bytes[0], head);
+        let (loaded, ..) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+        assert_eq!(loaded.first_block_at([1, 1, 1]), Some(&stone));
+    }
+}
+
+#[test]
+fn test_multi_bit_set_resize() {
+    use mc_schem::MultiBitSet;
+    let values: Vec<u64> = (0..100).map(|i| (i * 7) % 13).collect();
+    let mut mbs = MultiBitSet::new();
+    mbs.reset(4, values.len());
+    for (idx, v) in values.iter().enumerate() {
+        mbs.set(idx, *v).unwrap();
+    }
+    assert_eq!(mbs.iter().len(), values.len());
+    assert_eq!(mbs.iter().collect::<Vec<u64>>(), values);
+
+    for bits in [5, 13, 64, 4] {
+        mbs.resize_element_bits(bits).unwrap();
+        assert_eq!(mbs.element_bits(), bits);
+        assert_eq!(
+            mbs.as_u64_slice().len(),
+            (values.len() * bits as usize + 63) / 64
+        );
+        assert_eq!(mbs.iter().collect::<Vec<u64>>(), values);
+    }
+
+    let err = mbs.resize_element_bits(3).unwrap_err();
+    assert_eq!(err.code(), 46);
+    assert_eq!(mbs.element_bits(), 4);
+    assert_eq!(mbs.iter().collect::<Vec<u64>>(), values);
+
+    // out-of-range bits are errors rather than panics
+    for bits in [0, 65] {
+        assert!(mbs.resize_element_bits(bits).is_err());
+        assert_eq!(mbs.element_bits(), 4);
+        assert_eq!(mbs.iter().collect::<Vec<u64>>(), values);
+    }
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_vanilla_structure_split() {
+    use mc_schem::{Entity, VanillaStructureLoadOption};
+    let mut schem = Schematic::new();
+    let mut region = Region::with_shape([5, 3, 4]);
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    region.set_block([0, 0, 0], &stone).unwrap();
+    region.set_block([4, 2, 3], &glass).unwrap();
+    region.set_block([3, 1, 2], &stone).unwrap();
+    region.entities.push(Entity::with_position([3.5, 1.0, 2.5]));
+    schem.regions.push(region);
+
+    let option = VanillaStructureSaveOption {
+        max_size: Some([2, 2, 2]),
+        ..Default::default()
+    };
+    match schem.to_nbt_vanilla_structure(&option) {
+        Err(mc_schem::Error::SizeTooLarge { size, max_size }) => {
+            assert_eq!(size, [5, 3, 4]);
+            assert_eq!(max_size, [2, 2, 2]);
+        }
+        _ => panic!("Expected SizeTooLarge"),
+    }
+
+    let pieces = schem.to_nbt_vanilla_structure_pieces(&option).unwrap();
+    assert_eq!(pieces.len(), 3 * 2 * 2);
+    let last = pieces.last().unwrap();
+    assert_eq!(last.index, [2, 1, 1]);
+    assert_eq!(last.offset, [4, 2, 2]);
+    assert_eq!(last.shape, [1, 1, 2]);
+
+    for piece in pieces {
+        let (loaded, _) = Schematic::from_vanilla_structure_nbt(
+            piece.nbt,
+            &VanillaStructureLoadOption::default(),
+        )
+        .unwrap();
+        assert_eq!(loaded.shape(), piece.shape);
+        for x in 0..piece.shape[0] {
+            for y in 0..piece.shape[1] {
+                for z in 0..piece.shape[2] {
+                    let g_pos = [
+                        x + piece.offset[0],
+                        y + piece.offset[1],
+                        z + piece.offset[2],
+                    ];
+                    assert_eq!(
+                        loaded.first_block_at([x, y, z]),
+                        schem.first_block_at(g_pos)
+                    );
+                }
+            }
+        }
+        let entity_count = loaded.regions[0].entities.len();
+        if piece.index == [1, 0, 1] {
+            assert_eq!(entity_count, 1);
+            assert_eq!(loaded.regions[0].entities[0].block_pos, [1, 1, 0]);
+        } else {
+            assert_eq!(entity_count, 0);
+        }
+    }
+
+    let out_dir = "./target/test/vanilla_structure_split";
+    create_dir_all(out_dir).unwrap();
+    let option = VanillaStructureSaveOption {
+        split_oversized: true,
+        ..option
+    };
+    schem
+        .save_vanilla_structure_file(&format!("{out_dir}/split.nbt"), &option)
+        .unwrap();
+    let manifest: serde_json::Value =
+        serde_json::from_reader(File::open(format!("{out_dir}/split.json")).unwrap()).unwrap();
+    assert_eq!(manifest["pieces"].as_array().unwrap().len(), 12);
+    Schematic::from_vanilla_structure_file(
+        &format!("{out_dir}/split_2_1_1.nbt"),
+        &VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_load_sponge_v1() {
+    let mut palette = HashMap::new();
+    palette.insert("minecraft:air".to_string(), Value::Int(0));
+    palette.insert("minecraft:chest[facing=north]".to_string(), Value::Int(1));
+    palette.insert("minecraft:stone".to_string(), Value::Int(2));
+    let mut chest = HashMap::new();
+    chest.insert("ContentVersion".to_string(), Value::Int(1));
+    chest.insert(
+        "Id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    chest.insert(
+        "Pos".to_string(),
+        Value::IntArray(fastnbt::IntArray::new(vec![1, 0, 0])),
+    );
+
+    let mut root = HashMap::new();
+    root.insert("Version".to_string(), Value::Int(1));
+    root.insert("Width".to_string(), Value::Short(2));
+    root.insert("Height".to_string(), Value::Short(1));
+    root.insert("Length".to_string(), Value::Short(2));
+    root.insert("Palette".to_string(), Value::Compound(palette));
+    root.insert(
+        "BlockData".to_string(),
+        Value::ByteArray(fastnbt::ByteArray::new(vec![2, 1, 0, 2])),
+    );
+    root.insert(
+        "TileEntities".to_string(),
+        Value::List(vec![Value::Compound(chest)]),
+    );
+
+    let (schem, md) =
+        Schematic::from_world_edit_13_nbt(root.clone(), &WorldEdit13LoadOption::default()).unwrap();
+    assert_eq!(md.version, 1);
+    assert_eq!(md.data_version, DataVersion::Java_1_13_2 as i32);
+    assert_eq!(schem.shape(), [2, 1, 2]);
+    assert_eq!(schem.first_block_at([0, 0, 0]).unwrap().id, "stone");
+    assert_eq!(schem.first_block_at([1, 0, 0]).unwrap().id, "chest");
+    assert_eq!(schem.first_block_at([0, 0, 1]).unwrap().id, "air");
+    let be = schem.first_block_entity_at([1, 0, 0]).unwrap();
+    assert!(!be.tags.contains_key("ContentVersion"));
+
+    root.insert("Version".to_string(), Value::Int(4));
+    assert!(Schematic::from_world_edit_13_nbt(root, &WorldEdit13LoadOption::default()).is_err());
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_save_world_edit_12() {
+    use mc_schem::error::{DefaultErrorHandler, StrictErrorHandler};
+    use mc_schem::WorldEdit12SaveOption;
+
+    // every number id should survive new -> old -> new
+    let mut damages = Vec::new();
+    for id in 0..=255u8 {
+        old_block::get_valid_damage_values(id, &mut damages);
+        for damage in &damages {
+            let Ok(blk) = Block::from_old(id, *damage, DataVersion::Java_1_12_2) else {
+                continue;
+            };
+            let (new_id, new_damage) = blk.to_old().unwrap();
+            let reloaded = Block::from_old(new_id, new_damage, DataVersion::Java_1_12_2).unwrap();
+            assert_eq!(blk, reloaded, "{id}:{damage} changed after conversion");
+        }
+    }
+
+    let mut schem = Schematic::new();
+    let mut region = Region::with_shape([3, 2, 2]);
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_old(54, 2, DataVersion::Java_1_12_2).unwrap();
+    let copper = Block::from_id("minecraft:copper_block").unwrap();
+    region.set_block([0, 0, 0], &stone).unwrap();
+    region.set_block([1, 0, 0], &chest).unwrap();
+    region.set_block([2, 1, 1], &copper).unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    region.block_entities.insert([1, 0, 0], be);
+    schem.regions.push(region);
+
+    let option = WorldEdit12SaveOption::default();
+    match schem.to_nbt_world_edit_12::<StrictErrorHandler>(&option) {
+        Err(mc_schem::Error::NoOldBlockEquivalent { block, pos }) => {
+            assert_eq!(block, copper);
+            assert_eq!(pos, [2, 1, 1]);
+        }
+        _ => panic!("Expected NoOldBlockEquivalent"),
+    }
+
+    let out_dir = "./target/test/save_world_edit_12";
+    create_dir_all(out_dir).unwrap();
+    let filename = format!("{out_dir}/test.schematic");
+    schem
+        .save_world_edit_12_file::<DefaultErrorHandler>(&filename, &option)
+        .unwrap();
+    let (loaded, _, number_id) =
+        Schematic::from_world_edit_12_file(&filename, &WorldEdit12LoadOption::default()).unwrap();
+    assert_eq!(loaded.shape(), [3, 2, 2]);
+    assert_eq!(number_id[[0, 0, 0]], (1, 0));
+    assert_eq!(loaded.first_block_at([1, 0, 0]), Some(&chest));
+    assert!(loaded.first_block_entity_at([1, 0, 0]).is_some());
+    assert!(loaded.first_block_at([2, 1, 1]).unwrap().is_air());
+}
+
+#[test]
+fn test_datafix_upgrade() {
+    let mut region = Region::with_shape([8, 1, 1]);
+    let ids = [
+        "minecraft:sign[rotation=4]",
+        "minecraft:grass_path",
+        "minecraft:cauldron[level=0]",
+        "minecraft:cauldron[level=2]",
+        "minecraft:cobblestone_wall[east=false,north=true,south=false,up=true,waterlogged=false,west=false]",
+        "minecraft:grass",
+        "minecraft:dirt_path",
+        "minecraft:stone",
+    ];
+    for (x, id) in ids.iter().enumerate() {
+        region
+            .set_block([x as i32, 0, 0], &Block::from_id(id).unwrap())
+            .unwrap();
+    }
+    let make_schem = || {
+        let mut schem = Schematic::new();
+        schem.metadata.mc_data_version = DataVersion::Java_1_13_2 as i32;
+        schem.regions.push(region.clone());
+        return schem;
+    };
+    let mut schem = make_schem();
+
+    let mut schem_1_16 = make_schem();
+    assert_eq!(schem_1_16.upgrade_to(DataVersion::Java_1_16 as i32), 2);
+    assert_eq!(schem_1_16.first_block_at([0, 0, 0]).unwrap().id, "oak_sign");
+    assert_eq!(
+        schem_1_16.first_block_at([1, 0, 0]).unwrap().id,
+        "grass_path"
+    );
+
+    let changed = schem.upgrade_to(DataVersion::Java_1_20_3 as i32);
+    assert_eq!(changed, 6);
+    assert_eq!(
+        schem.metadata.mc_data_version,
+        DataVersion::Java_1_20_3 as i32
+    );
+    let expected = [
+        "minecraft:oak_sign[rotation=4]",
+        "minecraft:dirt_path",
+        "minecraft:cauldron",
+        "minecraft:water_cauldron[level=2]",
+        "minecraft:cobblestone_wall[east=none,north=low,south=none,up=true,waterlogged=false,west=none]",
+        "minecraft:short_grass",
+        "minecraft:dirt_path",
+        "minecraft:stone",
+    ];
+    for (x, id) in expected.iter().enumerate() {
+        assert_eq!(
+            schem.first_block_at([x as i32, 0, 0]).unwrap(),
+            &Block::from_id(id).unwrap()
+        );
+    }
+    // grass_path and dirt_path are merged, air is kept
+    assert_eq!(schem.regions[0].palette.len(), region.palette.len() - 1);
+    assert_eq!(schem.upgrade_to(DataVersion::Java_1_13 as i32), 0);
+}
+
+#[test]
+fn test_datafix_downgrade() {
+    let mut region = Region::with_shape([6, 1, 1]);
+    let ids = [
+        "minecraft:cherry_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]",
+        "minecraft:crimson_sign[rotation=0,waterlogged=false]",
+        "minecraft:water_cauldron[level=3]",
+        "minecraft:dirt_path",
+        "minecraft:short_grass",
+        "minecraft:deepslate_diamond_ore",
+    ];
+    for (x, id) in ids.iter().enumerate() {
+        region
+            .set_block([x as i32, 0, 0], &Block::from_id(id).unwrap())
+            .unwrap();
+    }
+    let mut schem = Schematic::new();
+    schem.metadata.mc_data_version = DataVersion::Java_1_20_4 as i32;
+    schem.regions.push(region);
+
+    let mut old = schem.clone();
+    let report = old.downgrade_to(DataVersion::Java_1_13_2 as i32);
+    assert_eq!(
+        old.metadata.mc_data_version,
+        DataVersion::Java_1_13_2 as i32
+    );
+    assert_eq!(report.changed, 3);
+    assert_eq!(report.substituted.len(), 3);
+    let expected = [
+        "minecraft:oak_stairs[facing=east,half=bottom,shape=straight,waterlogged=false]",
+        "minecraft:sign[rotation=0,waterlogged=false]",
+        "minecraft:cauldron[level=3]",
+        "minecraft:grass_path",
+        "minecraft:grass",
+        "minecraft:diamond_ore",
+    ];
+    for (x, id) in expected.iter().enumerate() {
+        assert_eq!(
+            old.first_block_at([x as i32, 0, 0]).unwrap(),
+            &Block::from_id(id).unwrap()
+        );
+    }
+
+    // upgrade again gets renamed blocks back
+    old.upgrade_to(DataVersion::Java_1_20_4 as i32);
+    assert_eq!(
+        old.first_block_at([2, 0, 0]),
+        schem.first_block_at([2, 0, 0])
+    );
+    assert_eq!(
+        old.first_block_at([3, 0, 0]),
+        schem.first_block_at([3, 0, 0])
+    );
+
+    let substituted = std::sync::Arc::new(std::sync::Mutex::new(0));
+    let counter = substituted.clone();
+    let option = VanillaStructureSaveOption {
+        target_data_version: Some(DataVersion::Java_1_16_5 as i32),
+        on_downgrade: Some(mc_schem::datafix::DowngradeCallback::new(move |report| {
+            *counter.lock().unwrap() += report.substituted.len();
+        })),
+        ..Default::default()
+    };
+    let nbt = schem.to_nbt_vanilla_structure(&option).unwrap();
+    assert!(*substituted.lock().unwrap() > 0);
+    let (loaded, _) = Schematic::from_vanilla_structure_nbt(
+        nbt,
+        &mc_schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        loaded.metadata.mc_data_version,
+        DataVersion::Java_1_16_5 as i32
+    );
+    assert_eq!(loaded.first_block_at([3, 0, 0]).unwrap().id, "grass_path");
+    // 1.16 has crimson sign
+    assert_eq!(loaded.first_block_at([1, 0, 0]).unwrap().id, "crimson_sign");
+}
+
+#[test]
+fn test_datafix_downgrade_sign() {
+    let mut region = Region::with_shape([3, 1, 1]);
+    let ids = [
+        "minecraft:spruce_planks",
+        "minecraft:spruce_sign[rotation=0,waterlogged=false]",
+        "minecraft:birch_wall_sign[facing=north,waterlogged=false]",
+    ];
+    for (x, id) in ids.iter().enumerate() {
+        region
+            .set_block([x as i32, 0, 0], &Block::from_id(id).unwrap())
+            .unwrap();
+    }
+    let mut schem = Schematic::new();
+    schem.metadata.mc_data_version = DataVersion::Java_1_20_4 as i32;
+    schem.regions.push(region);
+
+    let report = schem.downgrade_to(DataVersion::Java_1_13_2 as i32);
+    assert_eq!(report.substituted.len(), 2);
+    let expected = [
+        "minecraft:spruce_planks",
+        "minecraft:sign[rotation=0,waterlogged=false]",
+        "minecraft:wall_sign[facing=north,waterlogged=false]",
+    ];
+    for (x, id) in expected.iter().enumerate() {
+        assert_eq!(
+            schem.first_block_at([x as i32, 0, 0]).unwrap(),
+            &Block::from_id(id).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_entity_transformer() {
+    use mc_schem::region::Entity;
+    use mc_schem::transform::{EntityTransformer, Mirror, Rotation};
+
+    let mut armor_stand = Entity::with_position([0.5, 0.0, 0.5]);
+    armor_stand.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:armor_stand".to_string()),
+    );
+    armor_stand.tags.insert(
+        "Rotation".to_string(),
+        Value::List(vec![Value::Float(0.0), Value::Float(10.0)]),
+    );
+    armor_stand.tags.insert(
+        "Motion".to_string(),
+        Value::List(vec![
+            Value::Double(0.0),
+            Value::Double(0.5),
+            Value::Double(1.0),
+        ]),
+    );
+    let mut passenger = HashMap::new();
+    passenger.insert(
+        "Rotation".to_string(),
+        Value::List(vec![Value::Float(90.0), Value::Float(0.0)]),
+    );
+    armor_stand.tags.insert(
+        "Passengers".to_string(),
+        Value::List(vec![Value::Compound(passenger)]),
+    );
+
+    let mut item_frame = Entity::with_position([1.5, 0.5, 2.97]);
+    item_frame.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:item_frame".to_string()),
+    );
+    // facing south
+    item_frame.tags.insert("Facing".to_string(), Value::Byte(3));
+    item_frame
+        .tags
+        .insert("ItemRotation".to_string(), Value::Byte(1));
+    item_frame.tags.insert("TileX".to_string(), Value::Int(1));
+    item_frame.tags.insert("TileY".to_string(), Value::Int(0));
+    item_frame.tags.insert("TileZ".to_string(), Value::Int(2));
+
+    let mut floor_frame = Entity::with_position([2.5, 0.03, 0.5]);
+    floor_frame
+        .tags
+        .insert("Facing".to_string(), Value::Byte(1));
+    floor_frame
+        .tags
+        .insert("ItemRotation".to_string(), Value::Byte(1));
+
+    let mut painting = Entity::with_position([3.5, 1.5, 0.03]);
+    painting.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:painting".to_string()),
+    );
+    // facing south
+    painting.tags.insert("facing".to_string(), Value::Byte(0));
+
+    let mut region = Region::with_shape([4, 2, 3]);
+    region.entities = vec![armor_stand, item_frame, floor_frame, painting];
+    region.transform_entities(Rotation::Clockwise90, Mirror::None);
+    let [stand, frame, floor, painting] = region.entities.as_slice() else {
+        panic!("Entity count changed");
+    };
+
+    assert_eq!(stand.position, [2.5, 0.0, 0.5]);
+    assert_eq!(stand.block_pos, [2, 0, 0]);
+    let Some(Value::List(rot)) = stand.tags.get("Rotation") else {
+        panic!()
+    };
+    assert_eq!(rot[0], Value::Float(90.0));
+    assert_eq!(rot[1], Value::Float(10.0));
+    let Some(Value::List(motion)) = stand.tags.get("Motion") else {
+        panic!()
+    };
+    assert_eq!(motion[0], Value::Double(-1.0));
+    assert_eq!(motion[1], Value::Double(0.5));
+    let Some(Value::List(passengers)) = stand.tags.get("Passengers") else {
+        panic!()
+    };
+    let Value::Compound(passenger) = &passengers[0] else {
+        panic!()
+    };
+    assert_eq!(
+        passenger.get("Rotation"),
+        Some(&Value::List(vec![Value::Float(180.0), Value::Float(0.0)]))
+    );
+
+    // south -> west, wall-mounted item keeps its rotation
+    assert_eq!(frame.tags.get("Facing"), Some(&Value::Byte(4)));
+    assert_eq!(frame.tags.get("ItemRotation"), Some(&Value::Byte(1)));
+    assert_eq!(frame.block_pos, [0, 0, 1]);
+    assert_eq!(frame.tags.get("TileX"), Some(&Value::Int(0)));
+    assert_eq!(frame.tags.get("TileZ"), Some(&Value::Int(1)));
+
+    assert_eq!(floor.tags.get("Facing"), Some(&Value::Byte(1)));
+    assert_eq!(floor.tags.get("ItemRotation"), Some(&Value::Byte(3)));
+
+    assert_eq!(painting.tags.get("facing"), Some(&Value::Byte(1)));
+
+    let transformer = EntityTransformer::new(Rotation::None, Mirror::LeftRight, [4, 2, 3]);
+    assert_eq!(transformer.transform_yaw(30.0), 150.0);
+    assert_eq!(
+        transformer.transform_position([1.0, 2.0, 0.5]),
+        [1.0, 2.0, 2.5]
+    );
+    assert_eq!(transformer.transform_block_pos([1, 0, 0]), [1, 0, 2]);
+}
+
+#[test]
+fn test_block_entity_helpers() {
+    use mc_schem::block_entity::{ChestData, FurnaceData, SignText};
+    use mc_schem::item::Item;
+
+    let mut chest = BlockEntity::new();
+    chest.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    let mut data = ChestData::from_block_entity(&chest).unwrap();
+    assert!(data.items.0.is_empty());
+    data.items.0.insert(3, Item::new("minecraft:diamond", 16));
+    data.custom_name = Some("\"Loot\"".to_string());
+    data.write_to(&mut chest);
+    assert_eq!(
+        chest.tags["id"],
+        Value::String("minecraft:chest".to_string())
+    );
+    let parsed = ChestData::from_block_entity(&chest).unwrap();
+    assert_eq!(parsed.items.0[&3].count, 16);
+    assert_eq!(parsed.items.0[&3].id, "minecraft:diamond");
+    assert_eq!(parsed.custom_name.as_deref(), Some("\"Loot\""));
+
+    let mut furnace = BlockEntity::new();
+    let mut data = FurnaceData::from_block_entity(&furnace).unwrap();
+    data.fuel = Some(Item::new("minecraft:coal", 1));
+    data.burn_time = 200;
+    data.write_to(&mut furnace);
+    assert_eq!(furnace.tags["BurnTime"], Value::Short(200));
+    let parsed = FurnaceData::from_block_entity(&furnace).unwrap();
+    assert!(parsed.input.is_none());
+    assert_eq!(parsed.fuel.unwrap().id, "minecraft:coal");
+
+    let mut sign = BlockEntity::new();
+    sign.tags
+        .insert("Text1".to_string(), Value::String("\"hello\"".to_string()));
+    sign.tags
+        .insert("Color".to_string(), Value::String("red".to_string()));
+    let text = SignText::from_block_entity(&sign).unwrap();
+    assert_eq!(text.front.messages[0], "\"hello\"");
+    assert_eq!(text.front.color, "red");
+
+    text.write_to(&mut sign, DataVersion::Java_1_20 as i32);
+    assert!(!sign.tags.contains_key("Text1"));
+    assert!(sign.tags.contains_key("front_text"));
+    assert_eq!(SignText::from_block_entity(&sign).unwrap(), text);
+
+    text.write_to(&mut sign, DataVersion::Java_1_19_4 as i32);
+    assert!(!sign.tags.contains_key("front_text"));
+    assert_eq!(SignText::from_block_entity(&sign).unwrap(), text);
+
+    sign.tags.insert("Text2".to_string(), Value::Int(1));
+    assert!(SignText::from_block_entity(&sign).is_err());
+}
+
+#[test]
+fn test_item_stack_replace() {
+    use mc_schem::item::{Item, ItemHolder};
+
+    let mut region = Region::new();
+    region.reshape(&[2, 1, 1]);
+    let mut chest = BlockEntity::new();
+    let diamonds = Item::new("minecraft:diamond_block", 5);
+    let mut slot = diamonds.to_nbt();
+    slot.insert("Slot".to_string(), Value::Byte(0));
+    let mut shulker = Item::new("minecraft:shulker_box", 1);
+    let mut inner = diamonds.to_nbt();
+    inner.insert("Slot".to_string(), Value::Byte(4));
+    let mut be_tag = HashMap::new();
+    be_tag.insert(
+        "Items".to_string(),
+        Value::List(vec![Value::Compound(inner)]),
+    );
+    let mut tag = HashMap::new();
+    tag.insert("BlockEntityTag".to_string(), Value::Compound(be_tag));
+    shulker.tags = tag;
+    let mut shulker_slot = shulker.to_nbt();
+    shulker_slot.insert("Slot".to_string(), Value::Byte(1));
+    chest.tags.insert(
+        "Items".to_string(),
+        Value::List(vec![Value::Compound(slot), Value::Compound(shulker_slot)]),
+    );
+    region.block_entities.insert([0, 0, 0], chest);
+
+    let mut frame = mc_schem::Entity::new();
+    let mut modern = Item::new("minecraft:diamond_block", 2);
+    modern.components = Some(HashMap::new());
+    frame
+        .tags
+        .insert("Item".to_string(), Value::Compound(modern.to_nbt()));
+    region.entities.push(frame);
+
+    let mut schem = Schematic::new();
+    schem.regions.push(region);
+
+    let found = schem
+        .find_items(|item| item.id == "minecraft:diamond_block")
+        .unwrap();
+    assert_eq!(found.len(), 3);
+    assert!(found
+        .iter()
+        .any(|(_, f)| f.holder == ItemHolder::Entity(0) && f.item.count == 2));
+
+    let replaced = schem
+        .replace_items("minecraft:diamond_block", "minecraft:netherite_block")
+        .unwrap();
+    assert_eq!(replaced, 3);
+    assert!(schem
+        .find_items(|item| item.id == "minecraft:diamond_block")
+        .unwrap()
+        .is_empty());
+
+    let chest = &schem.regions[0].block_entities[&[0, 0, 0]];
+    let Value::List(items) = &chest.tags["Items"] else {
+        panic!()
+    };
+    let Value::Compound(first) = &items[0] else {
+        panic!()
+    };
+    assert_eq!(first["Slot"], Value::Byte(0));
+    assert_eq!(
+        Item::from_nbt(first, "").unwrap(),
+        Item::new("minecraft:netherite_block", 5)
+    );
+    let Value::Compound(frame_item) = &schem.regions[0].entities[0].tags["Item"] else {
+        panic!()
+    };
+    assert_eq!(frame_item["count"], Value::Int(2));
+
+    // a broken item makes nothing change
+    let region = &mut schem.regions[0];
+    let mut broken = Item::new("minecraft:stone", 1).to_nbt();
+    broken.insert("Count".to_string(), Value::String("one".to_string()));
+    let mut stand = mc_schem::Entity::new();
+    stand.tags.insert(
+        "HandItems".to_string(),
+        Value::List(vec![Value::Compound(broken)]),
+    );
+    region.entities.push(stand);
+    let before = region.block_entities[&[0, 0, 0]].tags.clone();
+    assert!(region
+        .replace_items("minecraft:netherite_block", "minecraft:gold_block")
+        .is_err());
+    assert_eq!(region.block_entities[&[0, 0, 0]].tags, before);
+}
+
+#[test]
+fn test_sign_text_conversion() {
+    use mc_schem::block_entity::{json_text_to_plain, SignText};
+
+    assert_eq!(json_text_to_plain("\"hello\""), "hello");
+    assert_eq!(
+        json_text_to_plain(r#"{"text":"a","bold":true,"extra":["b",{"text":"c"}]}"#),
+        "abc"
+    );
+    assert_eq!(json_text_to_plain("not json"), "not json");
+    assert_eq!(json_text_to_plain(r#""\ud83d\ude00""#), "\u{1f600}");
+    // a lone surrogate is not valid json
+    assert_eq!(json_text_to_plain(r#""\ud83dx""#), r#""\ud83dx""#);
+
+    let mut text = SignText::default();
+    text.front
+        .set_plain_lines(["say \"hi\"", "back\\slash", "", "end"]);
+    assert_eq!(text.front.messages[0], r#""say \"hi\"""#);
+    assert_eq!(
+        text.front.plain_lines(),
+        ["say \"hi\"", "back\\slash", "", "end"].map(String::from)
+    );
+
+    let mut region = Region::new();
+    region.reshape(&[1, 1, 1]);
+    region
+        .set_block([0, 0, 0], &Block::from_id("minecraft:sign").unwrap())
+        .unwrap();
+    let mut sign = BlockEntity::new();
+    text.write_to(&mut sign, DataVersion::Java_1_19_4 as i32);
+    region.block_entities.insert([0, 0, 0], sign);
+    let mut schem = Schematic::new();
+    schem.metadata.mc_data_version = DataVersion::Java_1_12_2 as i32;
+    schem.regions.push(region);
+
+    schem.upgrade_to(DataVersion::Java_1_20_4 as i32);
+    let be = &schem.regions[0].block_entities[&[0, 0, 0]];
+    assert!(be.tags.contains_key("front_text"));
+    assert!(!be.tags.contains_key("Text1"));
+    let upgraded = SignText::from_block_entity(be).unwrap();
+    assert_eq!(upgraded.front.plain_lines()[3], "end");
+
+    schem.downgrade_to(DataVersion::Java_1_19_4 as i32);
+    let be = &schem.regions[0].block_entities[&[0, 0, 0]];
+    assert!(be.tags.contains_key("Text1"));
+    assert!(!be.tags.contains_key("front_text"));
+    assert_eq!(SignText::from_block_entity(be).unwrap(), text);
+}
+
+#[test]
+fn test_schematic_builder() {
+    use mc_schem::SchematicBuilder;
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let gold = Block::from_id("minecraft:gold_block").unwrap();
+    let schem = SchematicBuilder::new()
+        .with_size([11, 11, 11])
+        .with_name("builder")
+        .with_data_version(DataVersion::Java_1_20_4 as i32)
+        .box_outline([0, 0, 0], [10, 10, 10], &glass)
+        .sphere([5, 5, 5], 3.0, &stone)
+        .line([-5, 5, 5], [20, 5, 5], &gold)
+        .cylinder([5, 1, 5], 1.0, 2, &stone)
+        .set([5, 9, 5], &gold)
+        .build();
+    assert_eq!(schem.metadata.name, "builder");
+    let reg = &schem.regions[0];
+    assert_eq!(reg.shape(), [11, 11, 11]);
+
+    let count = |blk: &Block| -> usize {
+        let idx = reg.find_in_palette(blk).unwrap();
+        reg.array_yzx.iter().filter(|b| **b == idx).count()
+    };
+    // shell minus the two line ends on x faces
+    assert_eq!(count(&glass), 11 * 11 * 11 - 9 * 9 * 9 - 2);
+    // line clipped to the region, plus one block set
+    assert_eq!(count(&gold), 11 + 1);
+    assert_eq!(reg.block_at([5, 8, 5]), Some(&stone));
+    assert_eq!(reg.block_at([5, 9, 6]), Some(&Block::air()));
+    // cylinder of radius 1 is a plus shape, 2 layers
+    assert_eq!(reg.block_at([6, 1, 5]), Some(&stone));
+    assert_eq!(reg.block_at([6, 2, 5]), Some(&stone));
+    assert_eq!(reg.block_at([6, 1, 6]), Some(&Block::air()));
+    assert_eq!(reg.block_at([5, 3, 5]), Some(&stone));
+
+    // resizing clears blocks
+    let schem = SchematicBuilder::new()
+        .fill([0, 0, 0], [0, 0, 0], &stone)
+        .with_size([2, 2, 2])
+        .build();
+    assert!(schem.regions[0].array_yzx.iter().all(|b| *b == 0));
+}
+
+#[test]
+fn test_region_draw() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let mut region = Region::with_shape([9, 9, 9]);
+
+    // clipped to the region
+    assert_eq!(region.draw_line([-3, 0, 0], [12, 0, 0], &stone), 9);
+    assert_eq!(region.draw_line([0, 0, 0], [8, 8, 8], &stone), 9);
+
+    let mut region = Region::with_shape([9, 9, 9]);
+    assert_eq!(
+        region.draw_cuboid([0, 0, 0], [8, 8, 8], &stone, true),
+        729 - 343
+    );
+    assert_eq!(region.block_at([4, 4, 4]), Some(&Block::air()));
+    assert_eq!(region.draw_cuboid([2, 2, 2], [6, 6, 6], &stone, false), 125);
+
+    let mut solid = Region::with_shape([9, 9, 9]);
+    let mut hollow = Region::with_shape([9, 9, 9]);
+    let solid_count = solid.draw_sphere([4, 4, 4], 4.0, &stone, false);
+    let hollow_count = hollow.draw_sphere([4, 4, 4], 4.0, &stone, true);
+    assert!(hollow_count < solid_count);
+    assert_eq!(hollow.block_at([4, 4, 4]), Some(&Block::air()));
+    assert_eq!(hollow.block_at([4, 4, 0]), Some(&stone));
+    assert_eq!(solid.block_at([4, 4, 4]), Some(&stone));
+
+    // half of the sphere is outside
+    let mut clipped = Region::with_shape([9, 9, 9]);
+    let clipped_count = clipped.draw_sphere([4, 0, 4], 4.0, &stone, false);
+    let mut disc = Region::with_shape([9, 9, 9]);
+    let disc_count = disc.draw_cylinder([4, 4, 4], 4.0, 1, &stone, false);
+    assert_eq!(clipped_count, (solid_count + disc_count) / 2);
+
+    let mut tube = Region::with_shape([9, 9, 9]);
+    let tube_count = tube.draw_cylinder([4, 8, 4], 4.0, -3, &stone, true);
+    assert!(tube_count < disc_count * 3);
+    assert_eq!(tube.block_at([4, 6, 0]), Some(&stone));
+    assert_eq!(tube.block_at([4, 6, 4]), Some(&Block::air()));
+    assert_eq!(tube.block_at([4, 5, 0]), Some(&Block::air()));
+
+    // huge shapes only loop over the region
+    let mut huge = Region::with_shape([9, 9, 9]);
+    assert_eq!(huge.draw_sphere([4, 4, 4], 1e9, &stone, false), 729);
+    assert_eq!(
+        huge.draw_cuboid([i32::MIN; 3], [i32::MAX; 3], &stone, false),
+        729
+    );
+    assert_eq!(
+        huge.draw_cylinder([4, 0, 4], 1e9, i32::MAX, &stone, false),
+        729
+    );
+    let mut line = Region::with_shape([9, 9, 9]);
+    assert_eq!(
+        line.draw_line([i32::MIN, 4, 4], [i32::MAX, 4, 4], &stone),
+        9
+    );
+    assert_eq!(line.draw_line([i32::MIN; 3], [i32::MAX; 3], &stone), 9);
+    assert_eq!(line.block_at([8, 8, 8]), Some(&stone));
+
+    // nothing is drawn, so palette is unchanged
+    let mut outside = Region::with_shape([9, 9, 9]);
+    assert_eq!(outside.draw_line([20, 0, 0], [30, 0, 0], &stone), 0);
+    assert_eq!(
+        outside.draw_cuboid([-5, -5, -5], [-1, -1, -1], &stone, false),
+        0
+    );
+    assert_eq!(outside.palette.len(), 1);
+
+    // overwritten blocks lose their block entities
+    let mut region = Region::with_shape([9, 9, 9]);
+    region.block_entities.insert([1, 1, 1], BlockEntity::new());
+    region.block_entities.insert([8, 8, 8], BlockEntity::new());
+    region.draw_cuboid([0, 0, 0], [2, 2, 2], &stone, false);
+    assert!(!region.block_entities.contains_key(&[1, 1, 1]));
+    assert!(region.block_entities.contains_key(&[8, 8, 8]));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_packed_litematica() {
+    use mc_schem::PackedSchematic;
+
+    let filename = "./test_files/litematica/full-blocks-1.12.2.litematic";
+    let option = LitematicaLoadOption::default();
+    let (packed, _) = PackedSchematic::from_litematica_file(filename, &option).unwrap();
+    let (schem, _) = Schematic::from_litematica_file(filename, &option).unwrap();
+    assert_eq!(packed.regions.len(), schem.regions.len());
+
+    for (p, r) in packed.regions.iter().zip(&schem.regions) {
+        assert_eq!(p.name, r.name);
+        assert_eq!(p.shape(), r.shape());
+        let structure_void = r
+            .array_yzx
+            .iter()
+            .filter(|b| r.palette[**b as usize].is_structure_void())
+            .count() as u64;
+        assert_eq!(p.total_blocks(true), r.volume() - structure_void);
+        let total_bytes = r.volume() as usize * std::mem::size_of::<u16>();
+        assert!(p.packed_blocks().as_u64_slice().len() * 8 < total_bytes);
+        for x in 0..r.shape()[0] {
+            for y in 0..r.shape()[1] {
+                for z in 0..r.shape()[2] {
+                    assert_eq!(p.block_at([x, y, z]), r.block_at([x, y, z]));
+                }
+            }
+        }
+        assert_eq!(p.block_at(r.shape()), None);
+    }
+
+    let unpacked = packed.into_schematic();
+    for (u, r) in unpacked.regions.iter().zip(&schem.regions) {
+        assert_eq!(u.array_yzx, r.array_yzx);
+        assert_eq!(u.palette, r.palette);
+    }
+}
+
+#[test]
+fn test_chunked_region() {
+    use mc_schem::schem::chunked::Section;
+    use mc_schem::ChunkedRegion;
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let mut region = Region::with_shape([40, 20, 33]);
+    region.draw_sphere([8, 8, 8], 5.0, &stone, true);
+    region.set_block([39, 19, 32], &glass).unwrap();
+
+    let chunked = ChunkedRegion::from_region(&region).unwrap();
+    assert_eq!(chunked.section_shape(), [3, 2, 3]);
+    assert_eq!(chunked.shape(), region.shape());
+    assert!(!chunked.section_at([0, 0, 0]).unwrap().is_uniform());
+    assert!(!chunked.section_at([2, 1, 2]).unwrap().is_uniform());
+    let uniform = chunked.sections().iter().filter(|s| s.is_uniform()).count();
+    assert_eq!(uniform, 3 * 2 * 3 - 2);
+    assert!(chunked.section_at([3, 0, 0]).is_none());
+    assert_eq!(chunked.total_blocks(false), region.total_blocks(false));
+
+    let back = chunked.to_region();
+    assert_eq!(back.array_yzx, region.array_yzx);
+    assert_eq!(back.palette, region.palette);
+
+    // fields other than blocks survive the round trip
+    let mut with_extras = region.clone();
+    let mut biomes: Array3<u16> = Array3::zeros(region.array_yzx.dim());
+    biomes[[0, 0, 1]] = 1;
+    with_extras.biomes = Some(biomes);
+    with_extras.biome_palette = vec![
+        "minecraft:plains".to_string(),
+        "minecraft:desert".to_string(),
+    ];
+    with_extras.alternate_palettes = vec![region
+        .palette
+        .iter()
+        .map(|blk| {
+            if blk == &stone {
+                glass.clone()
+            } else {
+                blk.clone()
+            }
+        })
+        .collect()];
+    with_extras.negative_size = [true, false, true];
+    let back = ChunkedRegion::from_region(&with_extras)
+        .unwrap()
+        .to_region();
+    assert_eq!(back.biomes, with_extras.biomes);
+    assert_eq!(back.biome_palette, with_extras.biome_palette);
+    assert_eq!(back.alternate_palettes, with_extras.alternate_palettes);
+    assert_eq!(back.negative_size, with_extras.negative_size);
+
+    let mut chunked = chunked;
+    chunked.set_block([39, 19, 32], &Block::air()).unwrap();
+    chunked.set_block([20, 0, 0], &glass).unwrap();
+    assert!(chunked.set_block([40, 0, 0], &glass).is_err());
+    chunked.compact().unwrap();
+    assert!(chunked.section_at([2, 1, 2]).unwrap().is_uniform());
+    assert_eq!(chunked.block_at([20, 0, 0]), Some(&glass));
+    if let Some(Section::Paletted { palette, .. }) = chunked.section_at([1, 0, 0]) {
+        assert_eq!(palette.len(), 2);
+    } else {
+        panic!("section [1,0,0] should be paletted");
+    }
+
+    // section palette grows past several bit widths
+    for i in 0..300 {
+        let blk = Block::from_id(&format!("mod:block_{i}")).unwrap();
+        chunked
+            .set_block([i % 16, 16 + i / 256, (i / 16) % 16], &blk)
+            .unwrap();
+    }
+    for i in 0..300 {
+        let pos = [i % 16, 16 + i / 256, (i / 16) % 16];
+        assert_eq!(chunked.block_at(pos).unwrap().id, format!("block_{i}"));
+    }
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_memory_usage() {
+    use mc_schem::{ChunkedRegion, PackedSchematic};
+
+    let filename = "./test_files/litematica/full-blocks-1.12.2.litematic";
+    let option = LitematicaLoadOption::default();
+    let (schem, _) = Schematic::from_litematica_file(filename, &option).unwrap();
+    let report = schem.memory_usage();
+    assert_eq!(report.regions.len(), schem.regions.len());
+    let reg = &schem.regions[0];
+    let reg_report = &report.regions[0];
+    assert_eq!(reg_report.name, reg.name);
+    assert_eq!(reg_report.block_array, reg.volume() as usize * 2);
+    assert!(reg_report.palette > reg.palette.len() * std::mem::size_of::<Block>());
+    assert!(report.total() >= reg_report.total());
+
+    let (packed, _) = PackedSchematic::from_litematica_file(filename, &option).unwrap();
+    assert!(packed.regions[0].memory_usage().block_array < reg_report.block_array);
+    assert_eq!(packed.regions[0].memory_usage().palette, reg_report.palette);
+
+    let mut empty = Region::with_shape([64, 64, 64]);
+    let dense = empty.memory_usage().block_array;
+    empty
+        .set_block([0, 0, 0], &Block::from_id("stone").unwrap())
+        .unwrap();
+    let chunked = ChunkedRegion::from_region(&empty).unwrap().memory_usage();
+    assert!(chunked.block_array * 10 < dense);
+}
+
+#[test]
+fn test_progress_callback() {
+    use mc_schem::{Progress, ProgressCallback};
+    use std::sync::{Arc, Mutex};
+
+    let record = |events: &Arc<Mutex<Vec<Progress>>>| {
+        let events = events.clone();
+        Some(ProgressCallback::new(move |p| {
+            events.lock().unwrap().push(p)
+        }))
+    };
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([64, 32, 64])
+        .sphere([32, 16, 32], 12.0, &Block::from_id("stone").unwrap())
+        .build();
+    let litematica = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut option = LitematicaLoadOption::default();
+    option.progress = record(&events);
+    let (schem, _) = Schematic::from_litematica_bytes(&litematica, &option).unwrap();
+    {
+        let events = events.lock().unwrap();
+        let last = events.last().unwrap();
+        assert_eq!(last.region_index, schem.regions.len());
+        assert_eq!(last.region_count, schem.regions.len());
+        assert_eq!(last.blocks_done, last.blocks_total);
+        assert_eq!(last.fraction(), 1.0);
+        // blocks are reported inside the region
+        assert!(events.len() > schem.regions.len());
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].blocks_done <= w[1].blocks_done));
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut option = WorldEdit13SaveOption::default();
+    option.progress = record(&events);
+    let bytes = schem.to_world_edit_13_bytes(&option).unwrap();
+    {
+        // blocks are reported while writing
+        let events = events.lock().unwrap();
+        assert!(events.len() > 1);
+        assert_eq!(events.last().unwrap().blocks_done, schem.volume());
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut option = VanillaStructureSaveOption::default();
+    option.progress = record(&events);
+    schem.to_vanilla_structure_bytes(&option).unwrap();
+    assert!(events.lock().unwrap().len() > 1);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut option = mc_schem::WorldEdit12SaveOption::default();
+    option.progress = record(&events);
+    schem
+        .to_world_edit_12_bytes::<mc_schem::error::DefaultErrorHandler>(&option)
+        .unwrap();
+    {
+        let events = events.lock().unwrap();
+        assert!(events.len() > 1);
+        assert_eq!(events.last().unwrap().fraction(), 1.0);
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut option = WorldEdit13LoadOption::default();
+    option.progress = record(&events);
+    Schematic::from_world_edit_13_bytes(&bytes, &option).unwrap();
+    let events = events.lock().unwrap();
+    assert!(events.len() > 1);
+    assert_eq!(events.last().unwrap().blocks_done, schem.volume());
+}
+
+#[test]
+fn test_cancellation() {
+    use mc_schem::ProgressCallback;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([64, 32, 64])
+        .fill([0, 0, 0], [63, 0, 63], &Block::from_id("stone").unwrap())
+        .build();
+    let litematica = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+
+    // trip the flag from the first progress report, loading stops before the region ends
+    let cancel = Arc::new(AtomicBool::new(false));
+    let reports = Arc::new(AtomicUsize::new(0));
+    let mut option = LitematicaLoadOption::default();
+    option.cancel = Some(cancel.clone());
+    option.progress = {
+        let cancel = cancel.clone();
+        let reports = reports.clone();
+        Some(ProgressCallback::new(move |_| {
+            reports.fetch_add(1, Ordering::Relaxed);
+            cancel.store(true, Ordering::Relaxed);
+        }))
+    };
+    let err = Schematic::from_litematica_bytes(&litematica, &option).unwrap_err();
+    assert!(matches!(err, mc_schem::Error::Cancelled));
+    assert_eq!(err.code(), 48);
+    assert_eq!(reports.load(Ordering::Relaxed), 1);
+
+    let mut option = WorldEdit13SaveOption::default();
+    option.cancel = Some(Arc::new(AtomicBool::new(true)));
+    assert!(matches!(
+        schem.to_world_edit_13_bytes(&option),
+        Err(mc_schem::Error::Cancelled)
+    ));
+
+    // an untripped flag changes nothing
+    let mut option = LitematicaLoadOption::default();
+    option.cancel = Some(Arc::new(AtomicBool::new(false)));
+    Schematic::from_litematica_bytes(&litematica, &option).unwrap();
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_litematica_async() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([16, 8, 16])
+        .sphere([8, 4, 8], 4.0, &Block::from_id("stone").unwrap())
+        .build();
+
+    let bytes = runtime.block_on(async {
+        let mut dest: Vec<u8> = Vec::new();
+        schem
+            .save_litematica_async(&mut dest, LitematicaSaveOption::default())
+            .await
+            .unwrap();
+        dest
+    });
+    assert!(!bytes.is_empty());
+
+    let (loaded, _) = runtime
+        .block_on(async {
+            let mut src: &[u8] = &bytes;
+            Schematic::from_litematica_async(&mut src, LitematicaLoadOption::default()).await
+        })
+        .unwrap();
+    assert_eq!(loaded.shape(), schem.shape());
+    let shape = schem.shape();
+    for x in 0..shape[0] {
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                assert_eq!(
+                    loaded.first_block_at([x, y, z]),
+                    schem.first_block_at([x, y, z])
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_load_warnings() {
+    use mc_schem::error::{DefaultErrorHandler, StrictErrorHandler};
+
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 4, 4])
+        .set([0, 0, 0], &Block::from_id("stone").unwrap())
+        .set([1, 0, 0], &Block::from_id("dirt").unwrap())
+        .set([2, 0, 0], &Block::from_id("chest").unwrap())
+        .set([3, 0, 0], &Block::from_id("glass").unwrap())
+        .build();
+    let mut be = mc_schem::BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    schem.regions[0].block_entities.insert([2, 0, 0], be);
+
+    // litematica: out-of-range indices and a duplicated tile entity
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    {
+        let Some(Value::Compound(regions)) = nbt.get_mut("Regions") else {
+            panic!()
+        };
+        let Some(Value::Compound(region)) = regions.values_mut().next() else {
+            panic!()
+        };
+        let Some(Value::List(palette)) = region.get("BlockStatePalette") else {
+            panic!()
+        };
+        // 3 bits per block, so 7 is invalid
+        assert_eq!(palette.len(), 5);
+        let Some(Value::LongArray(states)) = region.get_mut("BlockStates") else {
+            panic!()
+        };
+        let mut longs = states.to_vec();
+        longs[0] = -1;
+        *states = fastnbt::LongArray::new(longs);
+        let Some(Value::List(tes)) = region.get_mut("TileEntities") else {
+            panic!()
+        };
+        let dup = tes[0].clone();
+        tes.push(dup);
+    }
+    let err = Schematic::from_litematica_nbt(nbt.clone(), &LitematicaLoadOption::default());
+    assert!(matches!(
+        err,
+        Err(mc_schem::Error::BlockIndexOutOfRange { .. })
+    ));
+    assert!(
+        Schematic::from_litematica_nbt_with_warnings::<StrictErrorHandler>(
+            nbt.clone(),
+            &LitematicaLoadOption::default()
+        )
+        .is_err()
+    );
+    let (loaded, _, warnings) =
+        Schematic::from_litematica_nbt_with_warnings::<DefaultErrorHandler>(
+            nbt,
+            &LitematicaLoadOption::default(),
+        )
+        .unwrap();
+    // one warning per distinct invalid index, and one for the tile entity
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(
+        warnings[0].error,
+        mc_schem::Error::BlockIndexOutOfRange { .. }
+    ));
+    assert!(matches!(
+        warnings[1].error,
+        mc_schem::Error::MultipleBlockEntityInOnePos { .. }
+    ));
+    assert!(loaded.first_block_at([0, 0, 0]).unwrap().is_air());
+    assert_eq!(loaded.regions[0].block_entities.len(), 1);
+
+    // vanilla structure: invalid state and duplicated block entity
+    let mut nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    {
+        let Some(Value::List(blocks)) = nbt.get_mut("blocks") else {
+            panic!()
+        };
+        let with_nbt = blocks
+            .iter()
+            .find(|b| matches!(b, Value::Compound(c) if c.contains_key("nbt")))
+            .unwrap()
+            .clone();
+        blocks.push(with_nbt);
+        let Value::Compound(first) = &mut blocks[0] else {
+            panic!()
+        };
+        first.insert("state".to_string(), Value::Int(999));
+    }
+    assert!(Schematic::from_vanilla_structure_nbt(
+        nbt.clone(),
+        &mc_schem::VanillaStructureLoadOption::default()
+    )
+    .is_err());
+    let (_, _, warnings) =
+        Schematic::from_vanilla_structure_nbt_with_warnings::<DefaultErrorHandler>(
+            nbt,
+            &mc_schem::VanillaStructureLoadOption::default(),
+        )
+        .unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].to_string().contains("999"));
+
+    // without a chosen handler, the latter block entity is kept silently
+    let mut nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    {
+        let Some(Value::List(blocks)) = nbt.get_mut("blocks") else {
+            panic!()
+        };
+        let mut with_nbt = blocks
+            .iter()
+            .find(|b| matches!(b, Value::Compound(c) if c.contains_key("nbt")))
+            .unwrap()
+            .clone();
+        let Value::Compound(block) = &mut with_nbt else {
+            panic!()
+        };
+        let Some(Value::Compound(be)) = block.get_mut("nbt") else {
+            panic!()
+        };
+        be.insert("Latter".to_string(), Value::Byte(1));
+        blocks.push(with_nbt);
+    }
+    let (loaded, _) = Schematic::from_vanilla_structure_nbt(
+        nbt,
+        &mc_schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(loaded.regions[0].block_entities.len(), 1);
+    assert!(loaded.regions[0]
+        .block_entities
+        .values()
+        .all(|be| be.tags.contains_key("Latter")));
+}
+
+#[test]
+fn test_error_handler_closure() {
+    use mc_schem::error::{ErrorHandleResult, ErrorHandlerFn, Fix, MultipleBlockEntityFixMethod};
+    use std::sync::{Arc, Mutex};
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 2])
+        .set([0, 0, 0], &Block::from_id("stone").unwrap())
+        .set([1, 0, 0], &Block::from_id("chest").unwrap())
+        .build();
+    let mut nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    {
+        let Some(Value::List(blocks)) = nbt.get_mut("blocks") else {
+            panic!()
+        };
+        let Value::Compound(first) = &mut blocks[0] else {
+            panic!()
+        };
+        first.insert("state".to_string(), Value::Int(-1));
+    }
+
+    // the closure fixes errors without defining an ErrorHandler type
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut option = mc_schem::VanillaStructureLoadOption::default();
+    option.error_handler = {
+        let log = log.clone();
+        Some(ErrorHandlerFn::new(move |err, palette| {
+            log.lock().unwrap().push(err.code());
+            let stone = Block::from_id("stone").unwrap();
+            let idx = palette.iter().position(|b| b == &stone).unwrap();
+            return ErrorHandleResult::HandledWithoutWarning(Fix::BlockIndex(idx as u16));
+        }))
+    };
+    let (loaded, _) = Schematic::from_vanilla_structure_nbt(nbt.clone(), &option).unwrap();
+    assert_eq!(log.lock().unwrap().len(), 1);
+    assert_eq!(
+        loaded.regions[0].palette.len(),
+        schem.regions[0].palette.len()
+    );
+
+    // a fix not matching the error falls back to the handler type
+    let mut option = mc_schem::VanillaStructureLoadOption::default();
+    option.error_handler = Some(ErrorHandlerFn::new(|_, _| {
+        return ErrorHandleResult::HandledWithWarning(Fix::MultipleBlockEntity(
+            MultipleBlockEntityFixMethod::KeepFormer,
+        ));
+    }));
+    assert!(Schematic::from_vanilla_structure_nbt(nbt.clone(), &option).is_err());
+
+    // an index out of the palette is not accepted as a fix
+    let mut option = mc_schem::VanillaStructureLoadOption::default();
+    option.error_handler = Some(ErrorHandlerFn::new(|_, palette| {
+        return ErrorHandleResult::HandledWithoutWarning(Fix::BlockIndex(palette.len() as u16));
+    }));
+    assert!(matches!(
+        Schematic::from_vanilla_structure_nbt(nbt, &option),
+        Err(mc_schem::Error::BlockIndexOutOfRange { .. })
+    ));
+}
+
+#[test]
+fn test_error_source_and_location() {
+    use std::error::Error as _;
+
+    let err =
+        Schematic::from_litematica_bytes(b"not a litematic", &LitematicaLoadOption::default())
+            .unwrap_err();
+    assert!(err.source().is_some());
+    assert_eq!(err.tag_path(), None);
+
+    let err = mc_schem::snbt::parse_compound("{a: 1, b: }").unwrap_err();
+    assert_eq!(err.code(), 45);
+    assert!(err.byte_offset().is_some());
+
+    // tag path points to the tile entity in its region
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 2])
+        .set([0, 0, 0], &Block::from_id("chest").unwrap())
+        .build();
+    let mut be = mc_schem::BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    schem.regions[0].block_entities.insert([0, 0, 0], be);
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let region_name;
+    {
+        let Some(Value::Compound(regions)) = nbt.get_mut("Regions") else {
+            panic!()
+        };
+        let (name, Value::Compound(region)) = regions.iter_mut().next().unwrap() else {
+            panic!()
+        };
+        region_name = name.clone();
+        let Some(Value::List(tes)) = region.get_mut("TileEntities") else {
+            panic!()
+        };
+        let Value::Compound(te) = &mut tes[0] else {
+            panic!()
+        };
+        te.insert("x".to_string(), Value::Int(100));
+    }
+    let err = Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap_err();
+    assert_eq!(
+        err.tag_path(),
+        Some(format!("/Regions/{region_name}/TileEntities[0]/x").as_str())
+    );
+    assert!(err.source().is_none());
+}
+
+#[test]
+fn test_validate() {
+    use mc_schem::schem::validate::ValidationIssue;
+
+    let dv = DataVersion::Java_1_20_4 as i32;
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 4, 4])
+        .set([0, 0, 0], &Block::from_id("chest").unwrap())
+        .set([1, 0, 0], &Block::from_id("stone").unwrap())
+        .set([2, 0, 0], &Block::from_id("oak_wall_sign").unwrap())
+        .build();
+    let with_id = |id: &str| {
+        let mut be = mc_schem::BlockEntity::new();
+        be.tags
+            .insert("id".to_string(), Value::String(id.to_string()));
+        be
+    };
+    {
+        let region = &mut schem.regions[0];
+        region
+            .block_entities
+            .insert([0, 0, 0], with_id("minecraft:chest"));
+        region
+            .block_entities
+            .insert([2, 0, 0], with_id("minecraft:sign"));
+    }
+    let report = schem.validate(dv);
+    assert!(report.is_valid(), "{:?}", report.issues);
+
+    {
+        let region = &mut schem.regions[0];
+        region
+            .block_entities
+            .insert([1, 0, 0], with_id("minecraft:chest"));
+        region
+            .block_entities
+            .insert([2, 0, 0], with_id("minecraft:furnace"));
+        region
+            .block_entities
+            .insert([9, 0, 0], with_id("minecraft:chest"));
+        region.array_yzx[[3, 3, 3]] = region.palette.len() as u16;
+        let mut entity = mc_schem::Entity::new();
+        entity.set_position([1.5, -2.0, 0.5]);
+        region.entities.push(entity);
+    }
+    let issues = schem.validate(dv).issues;
+    assert_eq!(issues.len(), 5, "{issues:?}");
+    assert!(issues.contains(&ValidationIssue::BlockIndexOutOfRange {
+        region: 0,
+        pos: [3, 3, 3],
+        index: schem.regions[0].palette.len() as u16,
+        palette_len: schem.regions[0].palette.len(),
+    }));
+    assert!(issues.contains(&ValidationIssue::BlockEntityOutOfRegion {
+        region: 0,
+        pos: [9, 0, 0]
+    }));
+    let wrong: Vec<_> = issues
+        .iter()
+        .filter_map(|i| match i {
+            ValidationIssue::BlockEntityOnWrongBlock {
+                pos, expected_id, ..
+            } => Some((*pos, *expected_id)),
+            _ => None,
+        })
+        .collect();
+    assert!(wrong.contains(&([1, 0, 0], None)));
+    assert!(wrong.contains(&([2, 0, 0], Some("sign"))));
+    assert!(matches!(
+        issues.last().unwrap(),
+        ValidationIssue::EntityOutOfRegion {
+            entity_index: 0,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_region_overlaps() {
+    use mc_schem::schem::overlap::OverlapStrategy;
+
+    let stone = Block::from_id("stone").unwrap();
+    let glass = Block::from_id("glass").unwrap();
+    let make_region = |name: &str, offset: [i32; 3], block: &Block| {
+        let mut reg = mc_schem::SchematicBuilder::new()
+            .with_size([3, 3, 3])
+            .fill([0, 0, 0], [2, 2, 2], block)
+            .build()
+            .regions
+            .remove(0);
+        reg.name = name.to_string();
+        reg.offset = offset;
+        reg
+    };
+    let mut schem = Schematic::new();
+    schem.regions.push(make_region("a", [0, 0, 0], &stone));
+    schem.regions.push(make_region("b", [2, 0, 0], &glass));
+    schem.regions.push(make_region("c", [10, 0, 0], &glass));
+
+    let overlaps = schem.detect_overlaps();
+    assert_eq!(overlaps.len(), 1);
+    assert_eq!((overlaps[0].first, overlaps[0].second), (0, 1));
+    assert_eq!(overlaps[0].bounds.lower, [2, 0, 0]);
+    assert_eq!(overlaps[0].bounds.upper, [2, 2, 2]);
+
+    let err = schem
+        .clone()
+        .resolve_overlaps(OverlapStrategy::Error)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        mc_schem::Error::RegionsOverlap {
+            first: 0,
+            second: 1,
+            ..
+        }
+    ));
+
+    let mut prioritized = schem.clone();
+    assert_eq!(
+        prioritized
+            .resolve_overlaps(OverlapStrategy::PriorityOrder)
+            .unwrap(),
+        1
+    );
+    assert!(prioritized.detect_overlaps().len() == 1);
+    for y in 0..3 {
+        let blocks: Vec<_> = prioritized
+            .blocks_at([2, y, 0])
+            .into_iter()
+            .filter(|b| !b.is_structure_void())
+            .collect();
+        assert_eq!(blocks, vec![&stone]);
+    }
+    assert_eq!(prioritized.blocks_at([3, 0, 0]), vec![&glass]);
+
+    let mut merged = schem.clone();
+    assert_eq!(merged.resolve_overlaps(OverlapStrategy::Merge).unwrap(), 1);
+    assert!(merged.detect_overlaps().is_empty());
+    assert_eq!(merged.regions.len(), 2);
+    assert_eq!(merged.regions[0].name, "a");
+    assert_eq!(merged.regions[0].shape(), [5, 3, 3]);
+    assert_eq!(merged.first_block_at([2, 1, 1]), Some(&stone));
+    assert_eq!(merged.first_block_at([4, 1, 1]), Some(&glass));
+    assert_eq!(merged.regions[1].offset, [10, 0, 0]);
+}
+
+#[test]
+fn test_litematica_negative_size() {
+    let stone = Block::from_id("stone").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 2, 3])
+        .set([3, 0, 2], &stone)
+        .build();
+    {
+        let region = &mut schem.regions[0];
+        region.name = "main".to_string();
+        region.offset = [-4, 0, 1];
+        region.negative_size = [true, false, true];
+        let mut entity = mc_schem::Entity::new();
+        entity.set_position([0.5, 0.0, 0.5]);
+        region.entities.push(entity);
+    }
+
+    let nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let Some(Value::Compound(regions)) = nbt.get("Regions") else {
+        panic!("missing regions")
+    };
+    let Some(Value::Compound(main)) = regions.get("main") else {
+        panic!("missing region main")
+    };
+    let xyz = |name: &str| {
+        let Some(Value::Compound(c)) = main.get(name) else {
+            panic!("missing {name}")
+        };
+        ["x", "y", "z"].map(|k| match c.get(k) {
+            Some(Value::Int(v)) => *v,
+            _ => panic!("missing {name}.{k}"),
+        })
+    };
+    assert_eq!(xyz("Size"), [-4, 2, -3]);
+    assert_eq!(xyz("Position"), [-1, 0, 3]);
+
+    let (loaded, _) =
+        Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    let region = &loaded.regions[0];
+    assert_eq!(region.offset, [-4, 0, 1]);
+    assert_eq!(region.shape(), [4, 2, 3]);
+    assert_eq!(region.negative_size, [true, false, true]);
+    assert_eq!(region.block_at([3, 0, 2]), Some(&stone));
+    assert_eq!(region.entities[0].position, [0.5, 0.0, 0.5]);
+}
+
+#[test]
+fn test_litematica_preview() {
+    use mc_schem::error::StrictErrorHandler;
+    use mc_schem::schem::PreviewImage;
+
+    let rgba: Vec<u8> = (0..2 * 2 * 4).map(|v| v as u8 * 10).collect();
+    let preview = PreviewImage::from_rgba(2, 2, &rgba).unwrap();
+    assert_eq!(preview.argb[0], 0x1E000A14);
+    assert_eq!(preview.to_rgba(), rgba);
+    assert!(PreviewImage::from_rgba(2, 2, &rgba[1..]).is_none());
+    assert!(PreviewImage::from_argb_ints(&[0; 3]).is_none());
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 2])
+        .set([0, 0, 0], &Block::from_id("stone").unwrap())
+        .build();
+    let option = LitematicaSaveOption {
+        preview: Some(preview.clone()),
+        ..LitematicaSaveOption::default()
+    };
+    let nbt = schem.to_nbt_litematica(&option).unwrap();
+    let (loaded, md) =
+        Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(md.preview.as_ref(), Some(&preview));
+    // the preview is kept in metadata and saved again
+    let nbt = loaded
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let (_, md) = Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(md.preview, Some(preview));
+
+    let option = LitematicaSaveOption {
+        preview: PreviewImage::from_rgba(2, 1, &rgba[0..8]),
+        ..LitematicaSaveOption::default()
+    };
+    assert!(schem.to_nbt_litematica(&option).is_err());
+
+    // a preview that is not square is dropped with a warning
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let Some(Value::Compound(md)) = nbt.get_mut("Metadata") else {
+        panic!()
+    };
+    md.insert(
+        "PreviewImageData".to_string(),
+        Value::IntArray(fastnbt::IntArray::new(vec![0; 3])),
+    );
+    let (loaded, md, warnings) =
+        Schematic::from_litematica_nbt_with_warnings::<StrictErrorHandler>(
+            nbt,
+            &LitematicaLoadOption::default(),
+        )
+        .unwrap();
+    assert!(md.preview.is_none());
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].error.tag_path(),
+        Some("/Metadata/PreviewImageData")
+    );
+    assert_eq!(loaded.first_block_at([0, 0, 0]).unwrap().id, "stone");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_metadata_setters() {
+    use mc_schem::schem::RawMetaData;
+
+    let (mut schem, md) = Schematic::from_litematica_file(
+        "./test_files/litematica/correct_test.litematic",
+        &LitematicaLoadOption::default(),
+    )
+    .unwrap();
+    let created = md.time_created;
+    let mut raw = RawMetaData::Litematica(md);
+
+    schem.set_author("someone");
+    schem.set_name("a house");
+    schem.set_description("with a garden");
+    schem.touch_modified_time();
+    schem.sync_raw_metadata(&mut raw);
+    let RawMetaData::Litematica(md) = &raw else {
+        panic!("raw metadata changed its format")
+    };
+    assert_eq!(md.author, "someone");
+    assert_eq!(md.name, "a house");
+    assert_eq!(md.description, "with a garden");
+    assert_eq!(md.time_created, created);
+    assert_eq!(md.time_modified, schem.metadata.time_modified);
+
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let (loaded, md) =
+        Schematic::from_litematica_nbt(nbt.clone(), &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(loaded.metadata.author, "someone");
+    assert_eq!(md.time_created, created);
+    assert_eq!(md.time_modified, schem.metadata.time_modified);
+
+    // missing time stamps are filled instead of rejected
+    if let Some(Value::Compound(md_nbt)) = nbt.get_mut("Metadata") {
+        md_nbt.remove("TimeCreated");
+    }
+    let (_, md) = Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(md.time_created, md.time_modified);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_metadata_across_formats() {
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 2])
+        .set([0, 0, 0], &Block::from_id("stone").unwrap())
+        .build();
+    schem.set_author("someone");
+    schem.set_name("a house");
+    schem.set_description("with a garden");
+
+    // structure has only author
+    let nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    let (loaded, md) = Schematic::from_vanilla_structure_nbt(
+        nbt,
+        &mc_schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(md.author.as_deref(), Some("someone"));
+    assert_eq!(md.description, None);
+    assert_eq!(loaded.metadata.author, "someone");
+
+    let option = VanillaStructureSaveOption {
+        keep_unmapped_metadata: true,
+        ..VanillaStructureSaveOption::default()
+    };
+    let nbt = schem.to_nbt_vanilla_structure(&option).unwrap();
+    let (loaded, _) = Schematic::from_vanilla_structure_nbt(
+        nbt,
+        &mc_schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(loaded.metadata.name, "a house");
+    assert_eq!(loaded.metadata.description, "with a garden");
+
+    // sponge schematic has name and author
+    let nbt = schem
+        .to_nbt_world_edit_13(&WorldEdit13SaveOption::default())
+        .unwrap();
+    let (loaded, md) =
+        Schematic::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption::default()).unwrap();
+    assert_eq!(md.name.as_deref(), Some("a house"));
+    assert_eq!(md.description, None);
+    assert_eq!(loaded.metadata.author, "someone");
+
+    let option = WorldEdit13SaveOption {
+        keep_unmapped_metadata: true,
+        ..WorldEdit13SaveOption::default()
+    };
+    let nbt = schem.to_nbt_world_edit_13(&option).unwrap();
+    let (loaded, _) =
+        Schematic::from_world_edit_13_nbt(nbt, &WorldEdit13LoadOption::default()).unwrap();
+    assert_eq!(loaded.metadata.description, "with a garden");
+
+    // formats without name fall back to the file name
+    create_dir_all("./target/test/test_metadata_across_formats").unwrap();
+    let output = "./target/test/test_metadata_across_formats/out.litematic";
+    mc_schem::convert_file("./test_files/vanilla_structure/test01.nbt", output).unwrap();
+    let (loaded, _) = Schematic::from_file(output).unwrap();
+    assert_eq!(loaded.metadata.name, "test01");
+}
+
+#[test]
+fn test_region_stats() {
+    let stone = Block::from_id("stone").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([5, 5, 5])
+        .set([1, 2, 3], &stone)
+        .set([3, 1, 2], &stone)
+        .set([2, 2, 2], &Block::from_id("glass").unwrap())
+        .build();
+    schem.regions[0].offset = [10, 0, 0];
+    let region = &schem.regions[0];
+
+    let counts = region.block_counts();
+    assert!(counts.contains(&(&stone, 2)));
+    assert_eq!(counts.iter().map(|(_, c)| c).sum::<u64>(), 125);
+    assert_eq!(region.palette_counts().len(), region.palette.len());
+
+    let bbox = region.non_air_bounding_box().unwrap();
+    assert_eq!(bbox, BlockBox::new([1, 1, 2], [3, 2, 3]));
+    let global = schem.non_air_bounding_box().unwrap();
+    assert_eq!(global, BlockBox::new([11, 1, 2], [13, 2, 3]));
+
+    let stats = schem.region_stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].total_blocks, 3);
+    assert_eq!(stats[0].volume, 125);
+    assert_eq!(stats[0].block_counts[0].1, 122);
+    assert_eq!(stats[0].block_counts[1], (stone.full_id(), 2));
+    assert!(mc_schem::Region::with_shape([2, 2, 2])
+        .non_air_bounding_box()
+        .is_none());
+}
+
+#[test]
+fn test_find_blocks() {
+    use mc_schem::BlockMatcher;
+
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 4, 4])
+        .set([0, 0, 0], &Block::from_id("chest[facing=north]").unwrap())
+        .set([1, 2, 3], &Block::from_id("chest[facing=south]").unwrap())
+        .set([3, 3, 3], &Block::from_id("spawner").unwrap())
+        .build();
+    let mut second = schem.regions[0].clone();
+    second.offset = [8, 0, 0];
+    schem.regions.push(second);
+
+    let chests = BlockMatcher::from_id("minecraft:chest").unwrap();
+    let found: Vec<_> = schem.find_blocks(&chests).collect();
+    assert_eq!(
+        found,
+        vec![
+            (0, [0, 0, 0]),
+            (0, [1, 2, 3]),
+            (1, [0, 0, 0]),
+            (1, [1, 2, 3])
+        ]
+    );
+
+    let north = BlockMatcher::from_id("chest[facing=north]").unwrap();
+    assert_eq!(schem.find_blocks(&north).count(), 2);
+    let exact = BlockMatcher::Exact(Block::from_id("chest").unwrap());
+    assert_eq!(schem.find_blocks(&exact).count(), 0);
+
+    let any = BlockMatcher::AnyOf(vec![
+        BlockMatcher::from_id("spawner").unwrap(),
+        BlockMatcher::Predicate(|blk| blk.attributes.get("facing") == Some(&"south".to_string())),
+    ]);
+    let found: Vec<_> = schem.regions[0].find_blocks(&any).collect();
+    assert_eq!(found, vec![[1, 2, 3], [3, 3, 3]]);
+}
+
+#[test]
+fn test_command_blocks() {
+    use mc_schem::command_block::{transform_command, translate_command};
+    use mc_schem::transform::{Mirror, Rotation};
+
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([3, 3, 3])
+        .set(
+            [0, 0, 0],
+            &Block::from_id("command_block[facing=up]").unwrap(),
+        )
+        .set([0, 1, 0], &Block::from_id("chain_command_block").unwrap())
+        .build();
+    let mut be = mc_schem::BlockEntity::new();
+    be.tags.insert(
+        "Command".to_string(),
+        Value::String("setblock ~1 ~2 ~3 stone".to_string()),
+    );
+    schem.regions[0].block_entities.insert([0, 0, 0], be);
+
+    let blocks = schem.command_blocks();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].command, "setblock ~1 ~2 ~3 stone");
+    assert_eq!(blocks[1].pos, [0, 1, 0]);
+    assert_eq!(blocks[1].command, "");
+
+    assert_eq!(
+        transform_command(
+            "setblock ~1 ~2 ~3 stone",
+            Rotation::Clockwise90,
+            Mirror::None
+        ),
+        "setblock ~-3 ~2 ~1 stone"
+    );
+    assert_eq!(
+        transform_command(
+            "tp @e[x=1,dx=2] ~1 ~ ~ 90 0",
+            Rotation::Clockwise90,
+            Mirror::None
+        ),
+        "tp @e[x=1,dx=2] ~ ~ ~1 90 0"
+    );
+    assert_eq!(
+        transform_command("fill ~ ~ ~-2 ~ ~ ~2 air", Rotation::None, Mirror::LeftRight),
+        "fill ~ ~ ~2 ~ ~ ~-2 air"
+    );
+    assert_eq!(
+        translate_command("fill ~ ~ ~ 3 4 -5.5 air", [1, 2, 3]),
+        "fill ~ ~ ~ 4 6 -2.5 air"
+    );
+    assert_eq!(
+        translate_command(r#"tellraw @a {"text":"1 2 3"}"#, [1, 2, 3]),
+        r#"tellraw @a {"text":"1 2 3"}"#
+    );
+
+    let changed = schem.rewrite_commands(|info| {
+        if info.command.is_empty() {
+            Some("say hi".to_string())
+        } else {
+            None
+        }
+    });
+    assert_eq!(changed, 1);
+    assert_eq!(schem.command_blocks()[1].command, "say hi");
+    assert_eq!(
+        schem.transform_commands(Rotation::Clockwise180, Mirror::None),
+        1
+    );
+    assert_eq!(
+        schem.command_blocks()[0].command,
+        "setblock ~-1 ~2 ~-3 stone"
+    );
+}
+
+#[test]
+fn test_export_mcfunction() {
+    use mc_schem::McFunctionExportOption;
+
+    let stone = Block::from_id("stone").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 2, 4])
+        .fill([0, 0, 0], [2, 0, 1], &stone)
+        .set([3, 1, 3], &Block::from_id("chest[facing=north]").unwrap())
+        .build();
+    let mut be = mc_schem::region::BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    be.tags
+        .insert("CustomName".to_string(), Value::String("box".to_string()));
+    schem.regions[0].set_block_entity_at([3, 1, 3], be);
+
+    let mut out = Vec::new();
+    let num = schem
+        .export_mcfunction(&mut out, &McFunctionExportOption::default())
+        .unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(num, lines.len());
+    assert_eq!(lines[0], "fill ~ ~ ~ ~2 ~ ~1 stone");
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("setblock ~3 ~1 ~3 chest[facing=north]{"));
+    assert!(lines[1].contains("CustomName"));
+    assert!(!lines[1].contains("\"id\"") && !lines[1].contains("id:"));
+
+    let option = McFunctionExportOption {
+        origin: Some([100, 64, -20]),
+        use_fill: false,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    let num = schem.export_mcfunction(&mut out, &option).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert_eq!(num, 7);
+    assert!(text.starts_with("setblock 100 64 -20 stone\n"));
+    assert!(text.contains("setblock 103 65 -17 chest[facing=north]{"));
+
+    let option = McFunctionExportOption {
+        air_policy: mc_schem::AirPolicy::Include,
+        max_fill_volume: 16,
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    schem.export_mcfunction(&mut out, &option).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text
+        .lines()
+        .any(|l| l.starts_with("fill") && l.ends_with("air")));
+}
+
+#[test]
+fn test_litematica_target_version() {
+    use mc_schem::region::{PendingTick, PendingTickInfo};
+
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 2])
+        .set([0, 0, 0], &Block::from_id("water").unwrap())
+        .set([1, 0, 0], &Block::from_id("chest").unwrap())
+        .build();
+    {
+        let region = &mut schem.regions[0];
+        region.name = "main".to_string();
+        let tick = PendingTick {
+            priority: 0,
+            sub_tick: 0,
+            time: 5,
+            info: PendingTickInfo::Fluid {
+                id: "minecraft:water".to_string(),
+            },
+        };
+        region.pending_ticks.insert([0, 0, 0], vec![tick]);
+        let mut be = mc_schem::BlockEntity::new();
+        be.tags.insert(
+            "id".to_string(),
+            Value::String("minecraft:chest".to_string()),
+        );
+        region.set_block_entity_at([1, 0, 0], be);
+    }
+
+    let region_of = |nbt: &std::collections::HashMap<String, Value>| {
+        let Some(Value::Compound(regions)) = nbt.get("Regions") else {
+            panic!("missing regions")
+        };
+        let Some(Value::Compound(main)) = regions.get("main") else {
+            panic!("missing region main")
+        };
+        main.clone()
+    };
+
+    for version in [4, 5, 6, 7] {
+        let option = LitematicaSaveOption {
+            target_version: Some(version),
+            ..Default::default()
+        };
+        let nbt = schem.to_nbt_litematica(&option).unwrap();
+        assert_eq!(nbt.get("Version"), Some(&Value::Int(version)));
+        assert_eq!(nbt.contains_key("SubVersion"), version >= 6);
+        let main = region_of(&nbt);
+        assert_eq!(main.contains_key("PendingFluidTicks"), version >= 5);
+        assert!(main.contains_key("PendingBlockTicks"));
+
+        let (loaded, raw) =
+            Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+        assert_eq!(raw.version, version);
+        assert_eq!(
+            loaded.regions[0].pending_ticks.len(),
+            (version >= 5) as usize
+        );
+    }
+
+    let option = LitematicaSaveOption {
+        target_version: Some(3),
+        ..Default::default()
+    };
+    assert!(matches!(
+        schem.to_nbt_litematica(&option),
+        Err(mc_schem::Error::UnsupportedLitematicaVersion { version: 3, .. })
+    ));
+
+    // version 1 wraps tile entities and entities
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    nbt.insert("Version".to_string(), Value::Int(1));
+    {
+        let Some(Value::Compound(regions)) = nbt.get_mut("Regions") else {
+            panic!("missing regions")
+        };
+        let Some(Value::Compound(main)) = regions.get_mut("main") else {
+            panic!("missing region main")
+        };
+        let mut pos = std::collections::HashMap::new();
+        for (key, val) in [("x", 1), ("y", 0), ("z", 0)] {
+            pos.insert(key.to_string(), Value::Int(val));
+        }
+        let mut tile_nbt = std::collections::HashMap::new();
+        tile_nbt.insert(
+            "id".to_string(),
+            Value::String("minecraft:chest".to_string()),
+        );
+        let mut te = std::collections::HashMap::new();
+        te.insert("Pos".to_string(), Value::Compound(pos));
+        te.insert("TileNBT".to_string(), Value::Compound(tile_nbt));
+        main.insert(
+            "TileEntities".to_string(),
+            Value::List(vec![Value::Compound(te)]),
+        );
+
+        let mut pos = std::collections::HashMap::new();
+        for (key, val) in [("dx", 0.5), ("dy", 1.0), ("dz", 1.5)] {
+            pos.insert(key.to_string(), Value::Double(val));
+        }
+        let mut entity_data = std::collections::HashMap::new();
+        entity_data.insert("id".to_string(), Value::String("minecraft:pig".to_string()));
+        let mut entity = std::collections::HashMap::new();
+        entity.insert("Pos".to_string(), Value::Compound(pos));
+        entity.insert("EntityData".to_string(), Value::Compound(entity_data));
+        main.insert(
+            "Entities".to_string(),
+            Value::List(vec![Value::Compound(entity)]),
+        );
+    }
+    let (loaded, _) =
+        Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default()).unwrap();
+    let region = &loaded.regions[0];
+    assert!(region.block_entities.contains_key(&[1, 0, 0]));
+    assert_eq!(region.entities.len(), 1);
+    assert_eq!(region.entities[0].position, [0.5, 1.0, 1.5]);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn test_void_policy() {
+    use mc_schem::{ConvertOption, VoidPolicy};
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let build = || {
+        mc_schem::SchematicBuilder::new()
+            .with_size([3, 1, 1])
+            .set([0, 0, 0], &stone)
+            .set([1, 0, 0], &Block::air())
+            .set([2, 0, 0], &Block::structure_void())
+            .build()
+    };
+
+    let mut schem = build();
+    assert_eq!(schem.apply_void_policy(VoidPolicy::Keep), 0);
+    assert_eq!(schem.apply_void_policy(VoidPolicy::VoidToAir), 1);
+    let region = &schem.regions[0];
+    assert!(region.palette.iter().all(|b| !b.is_structure_void()));
+    assert_eq!(region.block_at([1, 0, 0]), Some(&Block::air()));
+    assert_eq!(region.block_at([2, 0, 0]), Some(&Block::air()));
+    assert_eq!(region.block_at([0, 0, 0]), Some(&stone));
+
+    let mut schem = build();
+    assert_eq!(schem.apply_void_policy(VoidPolicy::AirToVoid), 1);
+    let region = &schem.regions[0];
+    assert!(region.palette.iter().all(|b| !b.is_air()));
+    assert_eq!(region.block_at([1, 0, 0]), Some(&Block::structure_void()));
+    assert_eq!(region.block_at([0, 0, 0]), Some(&stone));
+
+    // litematica -> structure, air is not stored so it doesn't overwrite existing blocks
+    create_dir_all("./target/test/test_void_policy").unwrap();
+    let input = "./target/test/test_void_policy/in.litematic";
+    build()
+        .save_litematica_file(input, &LitematicaSaveOption::default())
+        .unwrap();
+    let output = "./target/test/test_void_policy/out.nbt";
+    let option = ConvertOption {
+        void_policy: VoidPolicy::AirToVoid,
+    };
+    let report = mc_schem::convert_file_with_option(input, output, &option).unwrap();
+    assert!(report
+        .warnings
+        .iter()
+        .any(|w| w.contains("replaced with structure void")));
+    let (reloaded, _) = Schematic::from_vanilla_structure_file(
+        output,
+        &mc_schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        reloaded.regions[0].block_at([1, 0, 0]),
+        Some(&Block::structure_void())
+    );
+
+    // structure -> litematica, voids are stripped
+    let output = "./target/test/test_void_policy/out.litematic";
+    let option = ConvertOption {
+        void_policy: VoidPolicy::VoidToAir,
+    };
+    mc_schem::convert_file_with_option("./target/test/test_void_policy/out.nbt", output, &option)
+        .unwrap();
+    let (reloaded, _) =
+        Schematic::from_litematica_file(output, &LitematicaLoadOption::default()).unwrap();
+    assert!(reloaded.regions[0]
+        .palette
+        .iter()
+        .all(|b| !b.is_structure_void()));
+}
+
+#[test]
+fn test_air_policy() {
+    use mc_schem::AirPolicy;
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 2, 1])
+        .set([0, 0, 0], &Block::from_id("minecraft:stone").unwrap())
+        .set([1, 0, 0], &Block::structure_void())
+        .build();
+    assert_eq!(schem.total_blocks(false), 2);
+    assert_eq!(schem.total_blocks(true), 4);
+
+    let count_blocks = |option: &VanillaStructureSaveOption| {
+        let nbt = schem.to_nbt_vanilla_structure(option).unwrap();
+        match nbt.get("blocks") {
+            Some(Value::List(blocks)) => blocks.len(),
+            _ => panic!("missing blocks"),
+        }
+    };
+    let mut option = VanillaStructureSaveOption::default();
+    assert_eq!(option.air_policy, AirPolicy::Include);
+    assert_eq!(count_blocks(&option), 3);
+    option.air_policy = AirPolicy::Omit;
+    assert_eq!(count_blocks(&option), 1);
+
+    let total_blocks = |option: &LitematicaSaveOption| {
+        let nbt = schem.to_nbt_litematica(option).unwrap();
+        let Some(Value::Compound(md)) = nbt.get("Metadata") else {
+            panic!("missing metadata")
+        };
+        md.get("TotalBlocks").cloned()
+    };
+    let mut option = LitematicaSaveOption::default();
+    assert_eq!(total_blocks(&option), Some(Value::Int(2)));
+    option.air_policy = AirPolicy::Include;
+    assert_eq!(total_blocks(&option), Some(Value::Int(4)));
+}
+
+#[test]
+fn test_check_block_entity_bindings() {
+    use mc_schem::schem::validate::BlockEntityBindingFix;
+
+    let dv = DataVersion::Java_1_20_4 as i32;
+    let build = || {
+        let mut schem = mc_schem::SchematicBuilder::new()
+            .with_size([3, 1, 1])
+            .set([0, 0, 0], &Block::from_id("chest").unwrap())
+            .set([1, 0, 0], &Block::from_id("stone").unwrap())
+            .set([2, 0, 0], &Block::from_id("furnace").unwrap())
+            .build();
+        schem.metadata.mc_data_version = dv;
+        for pos in [[0, 0, 0], [1, 0, 0], [2, 0, 0]] {
+            let mut be = mc_schem::BlockEntity::new();
+            be.tags.insert(
+                "id".to_string(),
+                Value::String("minecraft:chest".to_string()),
+            );
+            be.tags.insert("Items".to_string(), Value::List(vec![]));
+            schem.regions[0].set_block_entity_at(pos, be);
+        }
+        schem
+    };
+
+    let mut schem = build();
+    let found = schem.check_block_entity_bindings(BlockEntityBindingFix::Keep);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].0, 0);
+    assert_eq!(found[0].1.pos, [1, 0, 0]);
+    assert_eq!(found[0].1.expected_id, None);
+    assert_eq!(found[1].1.pos, [2, 0, 0]);
+    assert_eq!(found[1].1.expected_id, Some("furnace"));
+    assert_eq!(schem.regions[0].block_entities.len(), 3);
+    assert_eq!(schem.validate(dv).issues.len(), 2);
+
+    let mut schem = build();
+    schem.check_block_entity_bindings(BlockEntityBindingFix::Prune);
+    assert_eq!(schem.regions[0].block_entities.len(), 1);
+    assert!(schem.validate(dv).is_valid());
+
+    let mut schem = build();
+    schem.check_block_entity_bindings(BlockEntityBindingFix::Retype);
+    let region = &schem.regions[0];
+    assert_eq!(region.block_entities.len(), 2);
+    let furnace = &region.block_entities[&[2, 0, 0]];
+    assert_eq!(
+        furnace.tags.get("id"),
+        Some(&Value::String("minecraft:furnace".to_string()))
+    );
+    assert!(furnace.tags.contains_key("Items"));
+    assert!(schem.validate(dv).is_valid());
+    assert!(schem
+        .check_block_entity_bindings(BlockEntityBindingFix::Keep)
+        .is_empty());
+}
+
+#[test]
+fn test_scale() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_id("minecraft:chest").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 1, 1])
+        .set([0, 0, 0], &stone)
+        .set([1, 0, 0], &chest)
+        .build();
+    {
+        let region = &mut schem.regions[0];
+        region.offset = [1, 2, 3];
+        region.set_block_entity_at([1, 0, 0], mc_schem::BlockEntity::new());
+        let mut entity = mc_schem::Entity::new();
+        entity.set_position([0.5, 0.0, 0.5]);
+        region.entities.push(entity);
+    }
+
+    schem.regions[0]
+        .set_biome_id([1, 0, 0], "minecraft:desert")
+        .unwrap();
+    schem.scale(3).unwrap();
+    let region = &schem.regions[0];
+    assert_eq!(region.shape(), [6, 3, 3]);
+    assert_eq!(region.biome_id_at([5, 2, 2]), Some("minecraft:desert"));
+    assert_ne!(region.biome_id_at([2, 2, 2]), Some("minecraft:desert"));
+    assert_eq!(region.offset, [3, 6, 9]);
+    assert_eq!(region.block_at([2, 2, 2]), Some(&stone));
+    assert_eq!(region.block_at([3, 0, 0]), Some(&chest));
+    assert_eq!(region.block_at([5, 2, 2]), Some(&chest));
+    assert_eq!(region.block_entities.len(), 27);
+    assert!(region.block_entities.contains_key(&[4, 1, 1]));
+    assert_eq!(region.entities[0].position, [1.5, 0.0, 1.5]);
+    assert_eq!(region.entities[0].block_pos, [1, 0, 1]);
+
+    schem.scale(1).unwrap();
+    assert_eq!(schem.regions[0].shape(), [6, 3, 3]);
+    assert!(matches!(
+        schem.scale(1 << 30),
+        Err(mc_schem::Error::SizeTooLarge { .. })
+    ));
+    assert_eq!(schem.regions[0].shape(), [6, 3, 3]);
+}
+
+#[test]
+fn test_downscale() {
+    use mc_schem::schem::scale::DownscaleStrategy;
+
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let dirt = Block::from_id("minecraft:dirt").unwrap();
+    let gold = Block::from_id("minecraft:gold_block").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([5, 2, 2])
+        .fill([0, 0, 0], [0, 0, 1], &stone)
+        .fill([1, 0, 0], [1, 1, 0], &dirt)
+        .set([1, 1, 1], &gold)
+        .set([4, 1, 1], &stone)
+        .build();
+    schem.regions[0].offset = [-3, 0, 4];
+    schem.regions[0].set_block_entity_at([1, 1, 1], mc_schem::BlockEntity::new());
+    schem.regions[0]
+        .set_biome_id([4, 0, 0], "minecraft:desert")
+        .unwrap();
+
+    let mut majority = schem.clone();
+    majority.downscale(2, &DownscaleStrategy::Majority);
+    let region = &majority.regions[0];
+    assert_eq!(region.shape(), [3, 1, 1]);
+    assert_eq!(region.offset, [-2, 0, 2]);
+    // 2 stone, 2 dirt and 1 gold in the first cell, stone comes first in palette
+    assert_eq!(region.block_at([0, 0, 0]), Some(&stone));
+    assert_eq!(region.block_at([1, 0, 0]), Some(&Block::air()));
+    // the last cell is 1x2x2, air is ignored
+    assert_eq!(region.block_at([2, 0, 0]), Some(&stone));
+    assert!(region.block_entities.is_empty());
+    assert_eq!(region.biome_id_at([2, 0, 0]), Some("minecraft:desert"));
+
+    let mut with_air = schem.clone();
+    with_air.downscale(2, &DownscaleStrategy::MajorityWithAir);
+    assert_eq!(with_air.regions[0].block_at([0, 0, 0]), Some(&Block::air()));
+    assert_eq!(with_air.regions[0].block_at([2, 0, 0]), Some(&Block::air()));
+
+    let mut priority = schem.clone();
+    priority.downscale(2, &DownscaleStrategy::Priority(vec![gold.clone()]));
+    assert_eq!(priority.regions[0].block_at([0, 0, 0]), Some(&gold));
+    assert_eq!(priority.regions[0].block_at([2, 0, 0]), Some(&stone));
+}
+
+#[test]
+fn test_split() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_id("minecraft:chest").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([5, 2, 3])
+        .fill([0, 0, 0], [4, 0, 2], &stone)
+        .set([4, 1, 2], &chest)
+        .build();
+    schem.metadata.name = "castle".to_string();
+    {
+        let region = &mut schem.regions[0];
+        region.offset = [-1, 0, 0];
+        region.set_block_entity_at([4, 1, 2], mc_schem::BlockEntity::new());
+        let mut entity = mc_schem::Entity::new();
+        entity.set_position([3.5, 1.0, 0.5]);
+        region.entities.push(entity);
+    }
+    let mut second = mc_schem::Region::with_shape([1, 1, 1]);
+    second.offset = [10, 0, 0];
+    second.set_block([0, 0, 0], &stone).unwrap();
+    schem.regions.push(second);
+
+    assert!(schem.split([0, 2, 2]).is_err());
+    let pieces = schem.split([3, 2, 2]).unwrap();
+    // x cells from -1: [-1, 1], [2, 4], [5, 7] is empty, [8, 10]
+    let indices: Vec<[i32; 3]> = pieces.iter().map(|p| p.index).collect();
+    assert_eq!(
+        indices,
+        vec![[0, 0, 0], [0, 0, 1], [1, 0, 0], [1, 0, 1], [3, 0, 0]]
+    );
+    let total: u64 = pieces.iter().map(|p| p.schematic.total_blocks(false)).sum();
+    assert_eq!(total, schem.total_blocks(false));
+
+    let piece = &pieces[3];
+    assert_eq!(piece.offset, [2, 0, 2]);
+    assert_eq!(piece.schematic.metadata.name, "castle_1_0_1");
+    let region = &piece.schematic.regions[0];
+    assert_eq!(region.offset, [0, 0, 0]);
+    assert_eq!(region.shape(), [2, 2, 1]);
+    assert_eq!(region.block_at([1, 1, 0]), Some(&chest));
+    assert!(region.block_entities.contains_key(&[1, 1, 0]));
+
+    let piece = &pieces[2];
+    assert_eq!(piece.schematic.regions[0].entities.len(), 1);
+    assert_eq!(
+        piece.schematic.regions[0].entities[0].position,
+        [0.5, 1.0, 0.5]
+    );
+
+    let piece = &pieces[4];
+    assert_eq!(piece.offset, [8, 0, 0]);
+    assert_eq!(piece.schematic.regions[0].offset, [2, 0, 0]);
+}
+
+#[test]
+fn test_layers() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let chest = Block::from_id("minecraft:chest").unwrap();
+    let mut region = mc_schem::SchematicBuilder::new()
+        .with_size([2, 3, 2])
+        .fill([0, 0, 0], [1, 0, 1], &stone)
+        .set([1, 2, 1], &chest)
+        .build()
+        .regions
+        .remove(0);
+    region.offset = [5, 10, 5];
+    region.set_block_entity_at([1, 2, 1], mc_schem::BlockEntity::new());
+
+    assert!(region.extract_layer(3).is_none());
+    let top = region.extract_layer(2).unwrap();
+    assert_eq!(top.shape(), [2, 1, 2]);
+    assert_eq!(top.offset, [5, 12, 5]);
+    assert_eq!(top.block_at([1, 0, 1]), Some(&chest));
+    assert!(top.block_entities.contains_key(&[1, 0, 1]));
+    assert_eq!(region.extract_slab(-5..2).unwrap().shape(), [2, 2, 2]);
+
+    let mut slab = mc_schem::Region::with_shape([2, 2, 2]);
+    slab.fill_with(&glass);
+    assert!(region.insert_layer(4, &slab).is_err());
+    assert!(region
+        .insert_layer(0, &mc_schem::Region::with_shape([1, 1, 2]))
+        .is_err());
+
+    region.insert_layer(1, &slab).unwrap();
+    assert_eq!(region.shape(), [2, 5, 2]);
+    assert_eq!(region.offset, [5, 10, 5]);
+    assert_eq!(region.block_at([0, 0, 0]), Some(&stone));
+    assert_eq!(region.block_at([0, 2, 0]), Some(&glass));
+    assert_eq!(region.block_at([1, 4, 1]), Some(&chest));
+    assert!(region.block_entities.contains_key(&[1, 4, 1]));
+    assert_eq!(region.extract_slab(1..3).unwrap().total_blocks(false), 8);
+}
+
+#[test]
+fn test_render_top_down() {
+    use mc_schem::MapColor;
+    let grass = Block::from_id("minecraft:grass_block").unwrap();
+    let wool = Block::from_id("minecraft:light_blue_wool").unwrap();
+    let torch = Block::from_id("minecraft:torch").unwrap();
+    assert_eq!(MapColor::of_block(&grass), MapColor::Grass);
+    assert_eq!(MapColor::of_block(&wool), MapColor::ColorLightBlue);
+    assert_eq!(
+        MapColor::of_block(&Block::from_id("white_terracotta").unwrap()),
+        MapColor::TerracottaWhite
+    );
+    assert_eq!(MapColor::of_block(&torch), MapColor::Transparent);
+    assert_eq!(MapColor::Grass.rgb(), [0x7F, 0xB2, 0x38]);
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([3, 3, 2])
+        .fill([0, 0, 0], [2, 0, 1], &grass)
+        .set([1, 1, 1], &wool)
+        .set([2, 2, 1], &torch)
+        .build();
+    let option = mc_schem::TopDownRenderOption {
+        pixels_per_block: 2,
+        ..Default::default()
+    };
+    let image = schem.render_top_down(&option);
+    assert_eq!((image.width, image.height), (6, 4));
+    assert_eq!(image.pixels.len(), 6 * 4 * 4);
+    let flat = MapColor::Grass.rgb().map(|c| (c as u32 * 220 / 255) as u8);
+    assert_eq!(image.pixel(0, 0), Some([flat[0], flat[1], flat[2], 255]));
+    // the torch is invisible, so the column shows grass
+    assert_eq!(image.pixel(5, 3), Some([flat[0], flat[1], flat[2], 255]));
+    // the wool is higher than its north neighbour
+    let wool_rgb = MapColor::ColorLightBlue.rgb();
+    assert_eq!(
+        image.pixel(2, 2),
+        Some([wool_rgb[0], wool_rgb[1], wool_rgb[2], 255])
+    );
+    assert_eq!(image.pixel(6, 0), None);
+
+    let mut png = Vec::new();
+    image.write_png(&mut png).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+}
+
+#[test]
+fn test_render_isometric() {
+    use mc_schem::MapColor;
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let gold = Block::from_id("minecraft:gold_block").unwrap();
+    let single = mc_schem::SchematicBuilder::new()
+        .with_size([1, 1, 1])
+        .set([0, 0, 0], &stone)
+        .build();
+    let option = mc_schem::IsometricRenderOption {
+        tile_width: 4,
+        ..Default::default()
+    };
+    let image = single.render_isometric(&option);
+    assert_eq!((image.width, image.height), (4, 4));
+    let shaded = |color: MapColor, b: u32| {
+        let c = color.rgb().map(|c| (c as u32 * b / 255) as u8);
+        Some([c[0], c[1], c[2], 255])
+    };
+    assert_eq!(image.pixel(0, 0), Some([0, 0, 0, 0]));
+    assert_eq!(image.pixel(1, 0), shaded(MapColor::Stone, 255));
+    assert_eq!(image.pixel(0, 2), shaded(MapColor::Stone, 204));
+    assert_eq!(image.pixel(3, 2), shaded(MapColor::Stone, 153));
+
+    // gold is in front of stone along x, so it covers the east face of stone
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 1, 1])
+        .set([0, 0, 0], &stone)
+        .set([1, 0, 0], &gold)
+        .build();
+    let image = schem.render_isometric(&option);
+    assert_eq!((image.width, image.height), (6, 5));
+    assert_eq!(image.pixel(3, 2), shaded(MapColor::Gold, 255));
+    // viewed from the other side, stone is in front
+    let option = mc_schem::IsometricRenderOption {
+        rotation: mc_schem::transform::Rotation::Clockwise180,
+        ..option
+    };
+    let image = schem.render_isometric(&option);
+    assert_eq!(image.pixel(3, 2), shaded(MapColor::Stone, 255));
+}
+
+#[test]
+fn test_export_mesh() {
+    use mc_schem::{MeshColoring, MeshExportOption, MeshFormat};
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 1, 1])
+        .set([0, 0, 0], &stone)
+        .set([1, 0, 0], &stone)
+        .set([2, 0, 0], &glass)
+        .build();
+    assert_eq!(schem.mesh_materials(), vec![stone.clone(), glass.clone()]);
+
+    let mut obj = Vec::new();
+    let quads = schem
+        .export_mesh(&mut obj, MeshFormat::Obj, &MeshExportOption::default())
+        .unwrap();
+    // stone: 4 merged sides and the -x end, glass: 4 sides and the +x end
+    assert_eq!(quads, 10);
+    let obj = String::from_utf8(obj).unwrap();
+    assert_eq!(obj.lines().filter(|l| l.starts_with("f ")).count(), 10);
+    assert_eq!(obj.lines().filter(|l| l.starts_with("usemtl ")).count(), 2);
+    assert!(obj.contains("v 2 1 0\n"));
+
+    let option = MeshExportOption {
+        greedy: false,
+        coloring: MeshColoring::MapColor,
+    };
+    let mut obj = Vec::new();
+    assert_eq!(
+        schem
+            .export_mesh(&mut obj, MeshFormat::Obj, &option)
+            .unwrap(),
+        14
+    );
+    let option = MeshExportOption {
+        greedy: true,
+        coloring: MeshColoring::AtlasUv { columns: 2 },
+    };
+    let mut gltf = Vec::new();
+    assert_eq!(
+        schem
+            .export_mesh(&mut gltf, MeshFormat::Gltf, &option)
+            .unwrap(),
+        14
+    );
+    let json: serde_json::Value = serde_json::from_slice(&gltf).unwrap();
+    let primitives = json["meshes"][0]["primitives"].as_array().unwrap();
+    assert_eq!(primitives.len(), 2);
+    assert!(primitives[0]["attributes"]["TEXCOORD_0"].is_number());
+    assert_eq!(json["materials"][1]["name"], "minecraft:glass");
+    let position = &json["accessors"][0];
+    assert_eq!(position["count"], 9 * 4);
+    assert_eq!(position["max"][0].as_f64(), Some(2.0));
+    let buffer = &json["buffers"][0];
+    let len = buffer["byteLength"].as_u64().unwrap() as usize;
+    let uri = buffer["uri"].as_str().unwrap();
+    let encoded = uri.split_once("base64,").unwrap().1;
+    assert_eq!(encoded.len(), len.div_ceil(3) * 4);
+}
+
+#[test]
+fn test_export_bo3() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_id("minecraft:chest[facing=north]").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([3, 2, 3])
+        .fill([0, 0, 0], [2, 0, 2], &stone)
+        .set([2, 1, 2], &chest)
+        .build();
+    schem.metadata.author = "someone".to_string();
+    let mut be = mc_schem::BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        fastnbt::Value::String("minecraft:chest".to_string()),
+    );
+    schem.regions[0].set_block_entity_at([2, 1, 2], be);
+
+    let mut text = Vec::new();
+    let files = schem
+        .export_bo3(&mut text, "tower", &mc_schem::Bo3ExportOption::default())
+        .unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("Author: someone\n"));
+    let blocks: Vec<&str> = text.lines().filter(|l| l.starts_with("Block(")).collect();
+    assert_eq!(blocks.len(), 10);
+    assert!(blocks.contains(&"Block(-1,0,-1,minecraft:stone)"));
+    assert!(blocks.contains(&"Block(1,1,1,minecraft:chest[facing=north],tower/0_chest.nbt)"));
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].filename, "tower/0_chest.nbt");
+    assert!(files[0].content.starts_with(&[0x1f, 0x8b]));
+
+    let option = mc_schem::Bo3ExportOption {
+        center: Some([0, 0, 0]),
+        air_policy: mc_schem::AirPolicy::Include,
+        include_block_entities: false,
+        ..Default::default()
+    };
+    let mut text = Vec::new();
+    let files = schem.export_bo3(&mut text, "tower", &option).unwrap();
+    assert!(files.is_empty());
+    let text = String::from_utf8(text).unwrap();
+    assert_eq!(text.lines().filter(|l| l.starts_with("Block(")).count(), 18);
+    assert!(text.contains("Block(2,1,2,minecraft:chest[facing=north])\n"));
+}
+
+#[test]
+fn test_create_mod_preset() {
+    use mc_schem::VanillaStructureSaveOption;
+    let chest = Block::from_id("minecraft:chest[facing=east]").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 1, 1])
+        .set([0, 0, 0], &chest)
+        .build();
+    schem.regions[0].set_block_entity_at([0, 0, 0], mc_schem::BlockEntity::new());
+
+    let option = VanillaStructureSaveOption::create_mod();
+    assert_eq!(
+        option.max_file_size,
+        Some(VanillaStructureSaveOption::CREATE_MAX_FILE_SIZE)
+    );
+    let nbt = schem.to_nbt_vanilla_structure(&option).unwrap();
+    let Some(fastnbt::Value::List(blocks)) = nbt.get("blocks") else {
+        panic!("blocks should be a list");
+    };
+    let be_ids: Vec<&fastnbt::Value> = blocks
+        .iter()
+        .filter_map(|blk| match blk {
+            fastnbt::Value::Compound(blk) => match blk.get("nbt") {
+                Some(fastnbt::Value::Compound(tags)) => tags.get("id"),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        be_ids,
+        vec![&fastnbt::Value::String("minecraft:chest".to_string())]
+    );
+    let bytes = schem.to_vanilla_structure_bytes(&option).unwrap();
+    assert!(bytes.starts_with(&[0x1f, 0x8b]));
+
+    let option = VanillaStructureSaveOption {
+        max_file_size: Some(16),
+        ..VanillaStructureSaveOption::create_mod()
+    };
+    let mut dest = Vec::new();
+    let err = schem
+        .save_vanilla_structure_writer(&mut dest, &option)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        mc_schem::Error::FileTooLarge { max_size: 16, .. }
+    ));
+    assert_eq!(err.code(), 51);
+    assert!(dest.is_empty());
+}
+
+#[test]
+fn test_axiom_blueprint() {
+    use mc_schem::schem::RawMetaData;
+    use mc_schem::{AxiomSaveOption, SchemFormat};
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_id("minecraft:chest[facing=east]").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([20, 3, 18])
+        .fill([0, 0, 0], [19, 0, 17], &stone)
+        .set([17, 1, 16], &chest)
+        .set([3, 2, 4], &Block::structure_void())
+        .build();
+    let mut be = mc_schem::BlockEntity::new();
+    be.tags
+        .insert("CustomName".to_string(), Value::String("loot".to_string()));
+    schem.regions[0].set_block_entity_at([17, 1, 16], be);
+    schem.metadata.name = "floor".to_string();
+    schem.metadata.axiom_tags = vec!["build".to_string()];
+
+    let bytes = schem.to_axiom_bytes(&AxiomSaveOption::default()).unwrap();
+    assert!(bytes.starts_with(&[0x0a, 0xe5, 0xbb, 0x36]));
+    let (loaded, raw, format) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+    assert_eq!(format, SchemFormat::Axiom);
+    let RawMetaData::Axiom(raw) = raw else {
+        panic!("Expected axiom metadata");
+    };
+    assert_eq!(raw.name, "floor");
+    assert_eq!(raw.tags, vec!["build".to_string()]);
+    assert_eq!(raw.block_count, 20 * 18 + 1);
+    assert!(raw.contains_air);
+    assert!(raw.thumbnail.starts_with(&[0x89, b'P', b'N', b'G']));
+    assert_eq!(loaded.metadata.axiom_tags, schem.metadata.axiom_tags);
+
+    assert_eq!(loaded.shape(), schem.shape());
+    let reg = &loaded.regions[0];
+    for (pos, blk) in [
+        ([0, 0, 0], &stone),
+        ([19, 0, 17], &stone),
+        ([17, 1, 16], &chest),
+        ([0, 2, 0], &Block::air()),
+        ([3, 2, 4], &Block::structure_void()),
+    ] {
+        assert_eq!(reg.block_at(pos), Some(blk));
+    }
+    assert_eq!(
+        reg.block_entity_at([17, 1, 16])
+            .unwrap()
+            .tags
+            .get("CustomName"),
+        Some(&Value::String("loot".to_string()))
+    );
+
+    let (without_thumbnail, _) = Schematic::from_axiom_bytes(
+        &schem
+            .to_axiom_bytes(&AxiomSaveOption {
+                render_thumbnail: false,
+                ..AxiomSaveOption::default()
+            })
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(without_thumbnail.metadata.axiom_thumbnail.is_none());
+    assert!(Schematic::from_axiom_bytes(&[0, 1, 2, 3, 4]).is_err());
+
+    // sections far away from each other don't fit in one region
+    let with_sections_at = |positions: &[[i32; 3]]| {
+        let mut nbt = schem.to_nbt_axiom(&AxiomSaveOption::default()).unwrap();
+        let Some(Value::List(sections)) = nbt.get_mut("BlockRegion") else {
+            panic!()
+        };
+        let Value::Compound(section) = sections[0].clone() else {
+            panic!()
+        };
+        sections.clear();
+        for pos in positions {
+            let mut section = section.clone();
+            for (key, p) in ["X", "Y", "Z"].iter().zip(pos) {
+                section.insert(key.to_string(), Value::Int(*p));
+            }
+            sections.push(Value::Compound(section));
+        }
+        nbt.remove("BlockEntities");
+        return Schematic::from_nbt_axiom(nbt);
+    };
+    assert!(with_sections_at(&[[0, 0, 0], [1, 0, 1]]).is_ok());
+    assert!(matches!(
+        with_sections_at(&[[i32::MIN, 0, 0], [i32::MAX, 0, 0]]),
+        Err(mc_schem::Error::SizeTooLarge { .. })
+    ));
+    assert!(matches!(
+        with_sections_at(&[[0, 0, 0], [2000, 0, 2000]]),
+        Err(mc_schem::Error::VolumeTooLarge { .. })
+    ));
+}
+
+#[test]
+fn test_compress_methods() {
+    use mc_schem::schem::CompressMethod;
+    use mc_schem::SchemFormat;
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([3, 2, 3])
+        .fill(
+            [0, 0, 0],
+            [2, 0, 2],
+            &Block::from_id("minecraft:stone").unwrap(),
+        )
+        .build();
+    let mut methods = vec![
+        CompressMethod::Gzip,
+        CompressMethod::Zlib,
+        CompressMethod::Uncompressed,
+    ];
+    if cfg!(feature = "zstd") {
+        methods.push(CompressMethod::Zstd);
+    }
+    for method in methods {
+        let lite = schem
+            .to_litematica_bytes(&LitematicaSaveOption {
+                compress_method: method,
+                ..LitematicaSaveOption::default()
+            })
+            .unwrap();
+        let structure = schem
+            .to_vanilla_structure_bytes(&VanillaStructureSaveOption {
+                compress_method: method,
+                ..VanillaStructureSaveOption::default()
+            })
+            .unwrap();
+        let we13 = schem
+            .to_world_edit_13_bytes(&WorldEdit13SaveOption {
+                compress_method: method,
+                ..WorldEdit13SaveOption::default()
+            })
+            .unwrap();
+        for (bytes, format) in [
+            (lite, SchemFormat::Litematica),
+            (structure, SchemFormat::VanillaStructure),
+            (we13, SchemFormat::WorldEdit13),
+        ] {
+            assert_eq!(
+                mc_schem::schem::common::detect_compress_method(&bytes),
+                method
+            );
+            let (loaded, _, detected) = Schematic::from_bytes_detect_format(&bytes, None).unwrap();
+            assert_eq!(detected, format, "{method:?}");
+            assert_eq!(loaded.total_blocks(false), 9, "{method:?} {format:?}");
+        }
+    }
+
+    if !cfg!(feature = "zstd") {
+        let err = schem
+            .to_vanilla_structure_bytes(&VanillaStructureSaveOption {
+                compress_method: CompressMethod::Zstd,
+                ..VanillaStructureSaveOption::default()
+            })
+            .unwrap_err();
+        assert_eq!(err.code(), 52);
+        assert!(
+            Schematic::from_bytes_detect_format(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0], None).is_err()
+        );
+    }
+}
+
+#[test]
+fn test_nbt_read_error_position() {
+    use mc_schem::schem::CompressMethod;
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([4, 4, 4])
+        .fill(
+            [0, 0, 0],
+            [3, 3, 3],
+            &Block::from_id("minecraft:stone").unwrap(),
+        )
+        .build();
+    let bytes = schem
+        .to_litematica_bytes(&LitematicaSaveOption {
+            compress_method: CompressMethod::Uncompressed,
+            ..LitematicaSaveOption::default()
+        })
+        .unwrap();
+    let block_states = bytes
+        .windows(b"BlockStates".len())
+        .position(|w| w == b"BlockStates")
+        .unwrap();
+    let truncated = &bytes[..block_states + 20];
+
+    let err =
+        Schematic::from_litematica_bytes(truncated, &LitematicaLoadOption::default()).unwrap_err();
+    assert_eq!(err.code(), 53);
+    assert_eq!(err.byte_offset(), Some(truncated.len()));
+    let tag_path = err.tag_path().unwrap();
+    assert!(tag_path.starts_with("/Regions/"), "{tag_path}");
+    assert!(tag_path.ends_with("/BlockStates"), "{tag_path}");
+    assert!(err.to_string().contains("BlockStates"));
+
+    let err =
+        Schematic::from_litematica_bytes(&[10, 0], &LitematicaLoadOption::default()).unwrap_err();
+    assert_eq!(err.tag_path(), None);
+}
+
+#[test]
+fn test_litematica_region_filter() {
+    use mc_schem::RegionFilter;
+    let mut schem = Schematic::new();
+    for (idx, name) in ["house", "garden", "house_roof"].iter().enumerate() {
+        let mut reg = Region::with_shape([2, 2, 2]);
+        reg.name = name.to_string();
+        reg.offset = [idx as i32 * 2, 0, 0];
+        reg.fill_with(&Block::from_id("minecraft:stone").unwrap());
+        schem.regions.push(reg);
+    }
+    let bytes = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+
+    let load = |filter: RegionFilter| -> Vec<String> {
+        let option = LitematicaLoadOption {
+            region_filter: Some(filter),
+            ..LitematicaLoadOption::default()
+        };
+        let (loaded, md) = Schematic::from_litematica_bytes(&bytes, &option).unwrap();
+        assert_eq!(md.region_count, 3);
+        let mut names: Vec<String> = loaded.regions.iter().map(|r| r.name.clone()).collect();
+        names.sort();
+        return names;
+    };
+    assert_eq!(load(RegionFilter::names(&["garden"])), vec!["garden"]);
+    assert_eq!(
+        load(RegionFilter::predicate(|name| name.starts_with("house"))),
+        vec!["house", "house_roof"]
+    );
+    assert!(load(RegionFilter::names(&["missing"])).is_empty());
+}
+
+#[test]
+fn test_skip_entities_on_load() {
+    use mc_schem::{Entity, VanillaStructureLoadOption, WorldEdit12SaveOption};
+    let chest = Block::from_id("minecraft:chest[facing=east]").unwrap();
+    let mut schem = mc_schem::SchematicBuilder::new()
+        .with_size([3, 2, 3])
+        .set([1, 0, 1], &chest)
+        .build();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    schem.regions[0].set_block_entity_at([1, 0, 1], be);
+    let mut entity = Entity::new();
+    entity.set_position([1.5, 1.0, 1.5]);
+    entity
+        .tags
+        .insert("id".to_string(), Value::String("minecraft:pig".to_string()));
+    schem.regions[0].entities.push(entity);
+
+    let lite = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+    let (loaded, _) = Schematic::from_litematica_bytes(
+        &lite,
+        &LitematicaLoadOption {
+            skip_entities: true,
+            ..LitematicaLoadOption::default()
+        },
+    )
+    .unwrap();
+    assert!(loaded.regions[0].entities.is_empty());
+    assert_eq!(loaded.regions[0].block_entities.len(), 1);
+    let (loaded, _) = Schematic::from_litematica_bytes(
+        &lite,
+        &LitematicaLoadOption {
+            skip_block_entities: true,
+            ..LitematicaLoadOption::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(loaded.regions[0].entities.len(), 1);
+    assert!(loaded.regions[0].block_entities.is_empty());
+    assert_eq!(loaded.first_block_at([1, 0, 1]), Some(&chest));
+
+    let structure = schem
+        .to_vanilla_structure_bytes(&VanillaStructureSaveOption::default())
+        .unwrap();
+    let (loaded, _) = Schematic::from_vanilla_structure_bytes(
+        &structure,
+        &VanillaStructureLoadOption {
+            skip_entities: true,
+            skip_block_entities: true,
+            ..VanillaStructureLoadOption::default()
+        },
+    )
+    .unwrap();
+    assert!(loaded.regions[0].entities.is_empty());
+    assert!(loaded.regions[0].block_entities.is_empty());
+    assert_eq!(loaded.first_block_at([1, 0, 1]), Some(&chest));
+
+    let we13 = schem
+        .to_world_edit_13_bytes(&WorldEdit13SaveOption::default())
+        .unwrap();
+    let (loaded, _) = Schematic::from_world_edit_13_bytes(
+        &we13,
+        &WorldEdit13LoadOption {
+            skip_block_entities: true,
+            ..WorldEdit13LoadOption::default()
+        },
+    )
+    .unwrap();
+    assert!(loaded.regions[0].block_entities.is_empty());
+
+    let we12 = schem
+        .to_world_edit_12_bytes::<mc_schem::error::DefaultErrorHandler>(
+            &WorldEdit12SaveOption::default(),
+        )
+        .unwrap();
+    let (loaded, ..) = Schematic::from_world_edit_12_bytes(
+        &we12,
+        &WorldEdit12LoadOption {
+            skip_block_entities: true,
+            ..WorldEdit12LoadOption::default()
+        },
+    )
+    .unwrap();
+    assert!(loaded.regions[0].block_entities.is_empty());
+}
+
+#[test]
+fn test_iteration_order() {
+    use mc_schem::IterationOrder;
+    let shape = [2, 3, 4];
+    let mut region = Region::with_shape(shape);
+    for x in 0..shape[0] {
+        for y in 0..shape[1] {
+            for z in 0..shape[2] {
+                let blk = Block::from_id(&format!("minecraft:wool_{x}_{y}_{z}")).unwrap();
+                region.set_block([x, y, z], &blk).unwrap();
+            }
+        }
+    }
+    for order in IterationOrder::all() {
+        let view = region.as_order(*order);
+        let flat = region.reorder(*order).into_raw_vec();
+        assert_eq!(flat.len() as u64, region.volume());
+        for (idx, value) in flat.iter().enumerate() {
+            let pos = order.pos_of_index(idx, shape);
+            assert_eq!(order.linear_index(pos, shape), idx);
+            assert_eq!(
+                region.block_index_at(pos),
+                Some(*value),
+                "{order:?} {pos:?}"
+            );
+            assert_eq!(view[order.xyz_to_order(pos).map(|v| v as usize)], *value);
+        }
+        assert_eq!(view.iter().copied().collect::<Vec<u16>>(), flat);
+
+        let mut copy = Region::with_shape(shape);
+        copy.palette = region.palette.clone();
+        copy.assign_from_order(*order, &flat).unwrap();
+        assert_eq!(copy.array_yzx, region.array_yzx);
+    }
+    // array_yzx is already in YZX
+    assert_eq!(
+        region.reorder(IterationOrder::YZX).into_raw_vec(),
+        region.array_yzx.iter().copied().collect::<Vec<u16>>()
+    );
+
+    let mut copy = Region::with_shape(shape);
+    assert!(copy
+        .assign_from_order(IterationOrder::XYZ, &[0; 3])
+        .is_err());
+    assert!(copy
+        .assign_from_order(IterationOrder::XYZ, &vec![1; region.volume() as usize])
+        .is_err());
+}
+
+#[test]
+fn test_region_indices_access() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let mut region = Region::with_shape([3, 2, 4]);
+    let stone_index = region.find_or_append_to_palette(&stone);
+    region.set_block([2, 1, 3], &stone).unwrap();
+    region.set_block([0, 0, 1], &stone).unwrap();
+
+    let histogram =
+        region
+            .indices()
+            .iter()
+            .fold(vec![0u64; region.palette.len()], |mut hist, idx| {
+                hist[*idx as usize] += 1;
+                hist
+            });
+    assert_eq!(histogram, region.palette_counts());
+    assert_eq!(region.indices()[[1, 3, 2]], stone_index);
+
+    let (slice, shape, strides) = region.indices_slice().unwrap();
+    assert_eq!(shape, [2, 4, 3]);
+    for [x, y, z] in [[2, 1, 3], [0, 0, 1], [1, 1, 1]] {
+        let idx = y * strides[0] + z * strides[1] + x * strides[2];
+        assert_eq!(
+            Some(slice[idx]),
+            region.block_index_at([x as i32, y as i32, z as i32])
+        );
+    }
+
+    // replace stone with air in bulk
+    region
+        .indices_mut()
+        .mapv_inplace(|idx| if idx == stone_index { 0 } else { idx });
+    assert_eq!(region.total_blocks(false), 0);
+}
+
+#[test]
+fn test_region_blit() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let chest = Block::from_id("minecraft:chest").unwrap();
+    let mut src = Region::with_shape([4, 2, 4]);
+    src.fill_with(&stone);
+    src.set_block([1, 1, 1], &chest).unwrap();
+    src.set_block([3, 0, 3], &glass).unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    src.set_block_entity_at([1, 1, 1], be);
+    // never copied, so it shouldn't enter the palette of destination
+    src.find_or_append_to_palette(&Block::from_id("minecraft:dirt").unwrap());
+
+    let mut dst = Region::with_shape([5, 3, 5]);
+    let mut old_be = BlockEntity::new();
+    old_be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:barrel".to_string()),
+    );
+    dst.set_block_entity_at([3, 2, 3], old_be.clone());
+    dst.set_block_entity_at([0, 0, 0], old_be);
+
+    // copy x 1..=3, y 1, z 1..=3 of src to [2, 2, 2]
+    let copied = dst
+        .blit_from(&src, &BlockBox::new([1, 1, 1], [3, 1, 3]), [2, 2, 2])
+        .unwrap();
+    assert_eq!(copied, 3 * 3);
+    assert_eq!(dst.block_at([2, 2, 2]), Some(&chest));
+    assert_eq!(dst.block_at([4, 2, 4]), Some(&stone));
+    assert_eq!(dst.block_at([1, 2, 2]), Some(&Block::air()));
+    assert_eq!(dst.block_at([2, 1, 2]), Some(&Block::air()));
+    assert_eq!(
+        dst.block_entity_at([2, 2, 2]).unwrap().tags.get("id"),
+        Some(&Value::String("minecraft:chest".to_string()))
+    );
+    // overwritten by stone
+    assert!(dst.block_entity_at([3, 2, 3]).is_none());
+    assert!(dst.block_entity_at([0, 0, 0]).is_some());
+    assert_eq!(dst.palette.len(), 3);
+
+    // a box entirely outside copies nothing
+    assert_eq!(
+        dst.blit_from(&src, &BlockBox::new([0, 0, 0], [3, 1, 3]), [5, 0, 0])
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        dst.blit_from(&src, &BlockBox::new([0, 0, 0], [3, 1, 3]), [-2, 0, 0])
+            .unwrap(),
+        2 * 2 * 4
+    );
+    assert_eq!(dst.block_at([1, 0, 3]), Some(&glass));
+}
+
+#[test]
+fn test_region_to_cuboids() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let glass = Block::from_id("minecraft:glass").unwrap();
+    let mut reg = Region::with_shape([6, 3, 4]);
+    reg.fill_with(&stone);
+    reg.set_block([2, 1, 1], &glass).unwrap();
+
+    let cuboids = reg.to_cuboids();
+    // every block is covered exactly once, by a cuboid of its own block
+    let mut covered = Array3::from_elem([3, 4, 6], 0u8);
+    for cuboid in &cuboids {
+        let b = &cuboid.r_box;
+        for x in b.lower[0]..=b.upper[0] {
+            for y in b.lower[1]..=b.upper[1] {
+                for z in b.lower[2]..=b.upper[2] {
+                    assert_eq!(reg.block_index_at([x, y, z]), Some(cuboid.block_index));
+                    covered[[y as usize, z as usize, x as usize]] += 1;
+                }
+            }
+        }
+    }
+    assert!(covered.iter().all(|c| *c == 1));
+    assert!(cuboids.len() < 10, "too many cuboids: {}", cuboids.len());
+    assert_eq!(cuboids[0].r_box, BlockBox::new([0, 0, 0], [5, 0, 3]));
+
+    let air = Region::with_shape([3, 3, 3]).to_cuboids();
+    assert_eq!(air.len(), 1);
+
+    // excluded blocks are not covered, and volume is limited
+    let glass_idx = reg.block_index_at([2, 1, 1]).unwrap();
+    let limited = reg.to_cuboids_filtered(4, |_, idx| idx != glass_idx);
+    assert!(limited.iter().all(|c| c.block_index != glass_idx));
+    let volume: i64 = limited
+        .iter()
+        .map(|c| {
+            let b = &c.r_box;
+            (0..3)
+                .map(|d| (b.upper[d] - b.lower[d] + 1) as i64)
+                .product::<i64>()
+        })
+        .inspect(|v| assert!(*v <= 4))
+        .sum();
+    assert_eq!(volume, 6 * 3 * 4 - 1);
+}
+
+#[test]
+fn test_pending_ticks_world_edit_13_v3() {
+    use mc_schem::region::PendingTickInfo;
+    use mc_schem::PendingTick;
+    let mut reg = Region::with_shape([3, 2, 3]);
+    reg.fill_with(&Block::from_id("minecraft:water").unwrap());
+    let water = PendingTick {
+        priority: 0,
+        sub_tick: 7,
+        time: 5,
+        info: PendingTickInfo::Fluid {
+            id: "minecraft:water".to_string(),
+        },
+    };
+    let repeater = PendingTick {
+        priority: -1,
+        sub_tick: 3,
+        time: 2,
+        info: PendingTickInfo::Block {
+            id: "minecraft:repeater".to_string(),
+        },
+    };
+    reg.pending_ticks.insert([1, 0, 2], vec![water.clone()]);
+    reg.pending_ticks.insert([2, 1, 0], vec![repeater.clone()]);
+    let mut schem = Schematic::new();
+    schem.regions.push(reg);
+
+    let option = WorldEdit13SaveOption {
+        schem_version: Some(3),
+        ..WorldEdit13SaveOption::default()
+    };
+    let bytes = schem.to_world_edit_13_bytes(&option).unwrap();
+    let (loaded, _) =
+        Schematic::from_world_edit_13_bytes(&bytes, &WorldEdit13LoadOption::default()).unwrap();
+    let ticks = &loaded.regions[0].pending_ticks;
+    assert_eq!(ticks.len(), 2);
+    // delay, priority and kind are kept, sub ticks follow the order of delay
+    let loaded_water = &ticks[&[1, 0, 2]][0];
+    let loaded_repeater = &ticks[&[2, 1, 0]][0];
+    assert_eq!(loaded_water.time, 5);
+    assert_eq!(loaded_water.info, water.info);
+    assert_eq!(loaded_repeater.priority, -1);
+    assert_eq!(loaded_repeater.info, repeater.info);
+    assert!(loaded_repeater.sub_tick < loaded_water.sub_tick);
+
+    // v2 has no tag for pending ticks
+    let option = WorldEdit13SaveOption {
+        schem_version: Some(2),
+        ..WorldEdit13SaveOption::default()
+    };
+    let bytes = schem.to_world_edit_13_bytes(&option).unwrap();
+    let (loaded, _) =
+        Schematic::from_world_edit_13_bytes(&bytes, &WorldEdit13LoadOption::default()).unwrap();
+    assert!(loaded.regions[0].pending_ticks.is_empty());
+}
+
+#[test]
+fn test_palette_order_on_save() {
+    use mc_schem::PaletteOrder;
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let dirt = Block::from_id("minecraft:dirt").unwrap();
+    let mut reg = Region::with_shape([3, 1, 1]);
+    reg.palette = vec![stone.clone(), Block::air(), dirt.clone()];
+    reg.array_yzx = Array3::from_shape_vec((1, 1, 3), vec![0, 1, 2]).unwrap();
+    let mut schem = Schematic::new();
+    schem.regions.push(reg);
+    let names = |palette: &Value| -> Vec<String> {
+        let Value::List(list) = palette else {
+            panic!("palette is not a list")
+        };
+        return list
+            .iter()
+            .map(|blk| match blk {
+                Value::Compound(c) => match c.get("Name") {
+                    Some(Value::String(s)) => s.clone(),
+                    _ => panic!("no name"),
+                },
+                _ => panic!("not a compound"),
+            })
+            .collect();
+    };
+
+    // insertion order is kept by default
+    let nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    assert_eq!(
+        names(&nbt["palette"]),
+        ["minecraft:stone", "minecraft:air", "minecraft:dirt"]
+    );
+
+    let sorted = ["minecraft:air", "minecraft:dirt", "minecraft:stone"];
+    let nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption {
+            palette_order: PaletteOrder::Sorted,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(names(&nbt["palette"]), sorted);
+
+    let option = LitematicaSaveOption {
+        palette_order: PaletteOrder::Sorted,
+        verify_block_states: true,
+        ..Default::default()
+    };
+    let nbt = schem.regions[0]
+        .to_nbt_litematica_with_option(&option)
+        .unwrap();
+    assert_eq!(names(&nbt["BlockStatePalette"]), sorted);
+
+    // blocks are the same after remapping
+    let bytes = schem.to_litematica_bytes(&option).unwrap();
+    let (loaded, _) =
+        Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+    let bytes = schem
+        .to_world_edit_13_bytes(&WorldEdit13SaveOption {
+            palette_order: PaletteOrder::Sorted,
+            ..Default::default()
+        })
+        .unwrap();
+    let (loaded_we, _) =
+        Schematic::from_world_edit_13_bytes(&bytes, &WorldEdit13LoadOption::default()).unwrap();
+    for (x, blk) in [&stone, &Block::air(), &dirt].into_iter().enumerate() {
+        assert_eq!(loaded.first_block_at([x as i32, 0, 0]), Some(blk));
+        assert_eq!(loaded_we.first_block_at([x as i32, 0, 0]), Some(blk));
+    }
+}
+
+#[test]
+fn test_metadata_overflow() {
+    use mc_schem::schem::common::checked_int_tag;
+    assert_eq!(
+        checked_int_tag(i32::MAX as u64, "/Metadata/TotalVolume").unwrap(),
+        Value::Int(i32::MAX)
+    );
+    // a volume of 2048^3 doesn't fit in the int tag of litematica
+    let volume = 2048u64 * 2048 * 2048;
+    let err = checked_int_tag(volume, "/Metadata/TotalVolume").unwrap_err();
+    assert_eq!(err.code(), 54);
+    assert_eq!(err.tag_path(), Some("/Metadata/TotalVolume"));
+    assert!(err.to_string().contains(&volume.to_string()));
+}
+
+#[test]
+fn test_litematica_streaming_writer() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let mut schem = Schematic::new();
+    schem.metadata.time_created = 1700000000000;
+    schem.metadata.time_modified = 1700000000000;
+    for idx in 0..3 {
+        let mut reg = Region::with_shape([4, 3, 2]);
+        reg.name = "main".to_string();
+        reg.offset = [idx * 4, 0, 0];
+        reg.fill_with(&stone);
+        schem.regions.push(reg);
+    }
+    let option = LitematicaSaveOption {
+        compress_method: schem::CompressMethod::Uncompressed,
+        ..Default::default()
+    };
+    let expected = schem.to_nbt_litematica(&option).unwrap();
+    let bytes = schem.to_litematica_bytes(&option).unwrap();
+    let streamed: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+    assert_eq!(streamed, expected);
+    let Some(Value::Compound(regions)) = streamed.get("Regions") else {
+        panic!("Regions missing")
+    };
+    let mut names: Vec<&String> = regions.keys().collect();
+    names.sort();
+    assert_eq!(names, ["main", "main(1)", "main(2)"]);
+
+    let bytes = schem
+        .to_litematica_bytes(&LitematicaSaveOption::default())
+        .unwrap();
+    let (loaded, _) =
+        Schematic::from_litematica_bytes(&bytes, &LitematicaLoadOption::default()).unwrap();
+    assert_eq!(loaded.regions.len(), 3);
+    assert_eq!(loaded.total_blocks(false), 3 * 4 * 3 * 2);
+}
+
+#[test]
+fn test_structure_and_schem_streaming_writers() {
+    let stone = Block::from_id("minecraft:stone").unwrap();
+    let chest = Block::from_id("minecraft:chest").unwrap();
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([20, 4, 10]);
+    reg.fill_with(&stone);
+    reg.set_block([3, 2, 1], &chest).unwrap();
+    let mut be = BlockEntity::new();
+    be.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:chest".to_string()),
+    );
+    reg.set_block_entity_at([3, 2, 1], be);
+    schem.regions.push(reg);
+    // leaves positions covered by no region, which get the background block
+    let mut reg = Region::with_shape([3, 3, 3]);
+    reg.offset = [20, 4, 10];
+    reg.fill_with(&Block::from_id("minecraft:glass").unwrap());
+    schem.regions.push(reg);
+
+    let uncompressed = schem::CompressMethod::Uncompressed;
+    let option = VanillaStructureSaveOption {
+        compress_method: uncompressed,
+        ..Default::default()
+    };
+    let bytes = schem.to_vanilla_structure_bytes(&option).unwrap();
+    let streamed: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+    assert_eq!(streamed, schem.to_nbt_vanilla_structure(&option).unwrap());
+
+    for version in [2, 3] {
+        let option = WorldEdit13SaveOption {
+            compress_method: uncompressed,
+            schem_version: Some(version),
+            ..Default::default()
+        };
+        let bytes = schem.to_world_edit_13_bytes(&option).unwrap();
+        let streamed: HashMap<String, Value> = fastnbt::from_bytes(&bytes).unwrap();
+        assert_eq!(streamed, schem.to_nbt_world_edit_13(&option).unwrap());
+    }
+
+    // nothing to list in blocks
+    let mut void = Region::with_shape([2, 2, 2]);
+    void.fill_with(&Block::structure_void());
+    let mut schem = Schematic::new();
+    schem.regions.push(void);
+    let option = VanillaStructureSaveOption::default();
+    let bytes = schem.to_vanilla_structure_bytes(&option).unwrap();
+    let (loaded, _) = Schematic::from_vanilla_structure_bytes(
+        &bytes,
+        &schem::VanillaStructureLoadOption::default(),
+    )
+    .unwrap();
+    assert_eq!(loaded.regions[0].palette, [Block::structure_void()]);
+}
+
+#[test]
+fn test_varint_codec() {
+    use mc_schem::varint::{self, VarIntError};
+    use rand::Rng;
+
+    let values: Vec<u32> = vec![0, 1, 127, 128, 255, 16383, 16384, 65535, 1 << 28, u32::MAX];
+    let data = varint::encode_all(values.iter().copied());
+    assert_eq!(
+        data.len(),
+        values
+            .iter()
+            .map(|v| varint::encoded_len(*v))
+            .sum::<usize>()
+    );
+    assert_eq!(varint::decode_exact(&data, values.len()).unwrap(), values);
+
+    // truncated: the last value lacks its final byte
+    let truncated = &data[..data.len() - 1];
+    let start_of_last = data.len() - varint::encoded_len(u32::MAX);
+    assert_eq!(
+        varint::decode_exact(truncated, values.len()),
+        Err(VarIntError::Truncated {
+            index: start_of_last
+        })
+    );
+    // too many bytes, or too large for u32
+    assert_eq!(
+        varint::decode_one(&[0, -1, -1, -1, -1, -1, 0], 1),
+        Err(VarIntError::Overflow { index: 1 })
+    );
+    assert_eq!(
+        varint::decode_one(&[-1, -1, -1, -1, 0x10], 0),
+        Err(VarIntError::Overflow { index: 0 })
+    );
+    assert_eq!(
+        varint::decode_exact(&data, values.len() + 1),
+        Err(VarIntError::NotEnoughValues {
+            index: data.len(),
+            decoded: values.len(),
+            expected: values.len() + 1
+        })
+    );
+    assert_eq!(
+        varint::decode_exact(&data, 2),
+        Err(VarIntError::TrailingBytes {
+            index: 2,
+            expected: 2
+        })
+    );
+
+    // random bytes never panic, and errors point into the data
+    let mut rng = rand::thread_rng();
+    for _ in 0..2000 {
+        let len = rng.gen_range(0..16);
+        let bytes: Vec<i8> = (0..len).map(|_| rng.gen()).collect();
+        for value in varint::VarIntDecoder::new(&bytes) {
+            if let Err(e) = value {
+                assert!(e.index() < bytes.len());
+            }
+        }
+        if let Err(e) = varint::decode_exact(&bytes, 3) {
+            assert!(e.index() <= bytes.len());
+        }
+    }
+
+    // a schem with a single multi-byte block must load
+    let mut schem = Schematic::new();
+    let mut region = Region::new();
+    region.reshape(&[1, 1, 1]);
+    for i in 0..200 {
+        region
+            .palette
+            .push(Block::from_id(&format!("minecraft:wool_{i}")).unwrap());
+    }
+    region.array_yzx[[0, 0, 0]] = (region.palette.len() - 1) as u16;
+    schem.regions.push(region);
+    let mut bytes = Vec::new();
+    schem
+        .save_world_edit_13_writer(&mut bytes, &WorldEdit13SaveOption::default())
+        .unwrap();
+    let (loaded, _) =
+        Schematic::from_world_edit_13_bytes(&bytes, &WorldEdit13LoadOption::default()).unwrap();
+    assert_eq!(loaded.first_block_at([0, 0, 0]).unwrap().id, "wool_199");
+}
+
+#[test]
+fn test_block_interning() {
+    use mc_schem::BlockInterner;
+    use std::sync::Arc;
+
+    let a = Block::from_id("minecraft:oak_log[axis=y]").unwrap();
+    let b = Block::from_id("minecraft:oak_log[axis=y]").unwrap();
+    let c = Block::from_id("minecraft:oak_log[axis=x]").unwrap();
+    let mut interner = BlockInterner::new();
+    let ia = interner.intern(&a).unwrap();
+    let ib = interner.intern(&b).unwrap();
+    let ic = interner.intern(&c).unwrap();
+    assert_eq!(ia, ib);
+    assert_ne!(ia, ic);
+    assert!(Arc::ptr_eq(
+        interner.block(ia).unwrap(),
+        interner.block(ib).unwrap()
+    ));
+    assert_eq!(**interner.block(ic).unwrap(), c);
+    assert_eq!(
+        interner.intern_all(&[c.clone(), a.clone()]).unwrap(),
+        vec![ic, ia]
+    );
+    assert_eq!(interner.find(&b), Some(ia));
+    assert_eq!(interner.len(), 2);
+    // interners are independent
+    let other = BlockInterner::new();
+    assert_eq!(other.find(&a), None);
+    assert!(other.block(ia).is_none());
+
+    // full palette of regions with 1000+ shared modded blocks
+    let mut schem = Schematic::new();
+    for offset in 0..3 {
+        let mut region = Region::new();
+        region.reshape(&[1, 1, 1]);
+        for i in 0..1200 {
+            region
+                .palette
+                .push(Block::from_id(&format!("mod:block_{}", (i + offset * 100) % 1300)).unwrap());
+        }
+        schem.regions.push(region);
+    }
+    let (palette, lut_lut) = schem.full_palette();
+    assert_eq!(palette.len(), 1301);
+    for (reg, lut) in schem.regions.iter().zip(&lut_lut) {
+        for (blk, idx) in reg.palette.iter().zip(lut) {
+            assert_eq!(palette[*idx].0, blk);
+        }
+    }
+}
+
+#[test]
+fn test_palette_lookup_consistency() {
+    let mut region = Region::with_shape([16, 1, 16]);
+    for x in 0..16 {
+        for z in 0..16 {
+            let blk = Block::from_id(&format!("mod:block_{}", (x * 16 + z) % 100)).unwrap();
+            region.set_block([x, 0, z], &blk).unwrap();
+        }
+    }
+    // air + 100 distinct blocks
+    assert_eq!(region.palette.len(), 101);
+    let stone = Block::from_id("stone").unwrap();
+    let idx = region.find_or_append_to_palette(&stone);
+    assert_eq!(region.find_or_append_to_palette(&stone), idx);
+    assert_eq!(region.find_in_palette(&stone), Some(idx));
+
+    // changing the public palette directly must not return stale indices
+    region.palette.swap(0, idx as usize);
+    assert_eq!(region.find_or_append_to_palette(&stone), 0);
+    assert_eq!(region.find_or_append_to_palette(&Block::air()), idx);
+    region.palette.truncate(50);
+    let idx = region.find_or_append_to_palette(&stone);
+    assert_eq!(idx, 0);
+    let new_blk = Block::from_id("mod:block_99").unwrap();
+    assert_eq!(region.find_or_append_to_palette(&new_blk), 50);
+    assert_eq!(region.palette.len(), 51);
+
+    // clones carry the lookup
+    let mut cloned = region.clone();
+    assert_eq!(cloned.find_or_append_to_palette(&new_blk), 50);
+}
+
+#[test]
+fn test_multi_bit_set_word_wise_iter() {
+    use mc_schem::MultiBitSet;
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    for bits in 1..=64u8 {
+        let length = rng.gen_range(0..300);
+        let words = (length * bits as usize).div_ceil(64);
+        let data: Vec<u64> = (0..words).map(|_| rng.gen()).collect();
+        let mbs = MultiBitSet::from_data(&data, length, bits).unwrap();
+        let iter = mbs.iter();
+        assert_eq!(iter.len(), length);
+        let by_iter: Vec<u64> = iter.collect();
+        let by_get: Vec<u64> = (0..length).map(|idx| mbs.get(idx)).collect();
+        assert_eq!(by_iter, by_get, "bits = {bits}");
+
+        let mut unpacked = vec![0u16; length + 1];
+        assert_eq!(mbs.unpack_into_u16(&mut unpacked), length);
+        for (u, v) in unpacked.iter().zip(&by_get) {
+            assert_eq!(*u, *v as u16);
+        }
+    }
+}
+
+#[test]
+fn test_bench_fixtures() {
+    use mc_schem::bench_fixtures::{self, FixtureOption};
+
+    let option = FixtureOption {
+        shape: [8, 4, 6],
+        palette_size: 300,
+        region_count: 3,
+        air_ratio: 0.5,
+        seed: 42,
+    };
+    let a = bench_fixtures::synthetic_schematic(&option);
+    let b = bench_fixtures::synthetic_schematic(&option);
+    assert_eq!(a.regions.len(), 3);
+    assert_eq!(a.shape(), [24, 4, 6]);
+    for (ra, rb) in a.regions.iter().zip(&b.regions) {
+        assert_eq!(ra.palette.len(), 300);
+        assert_eq!(ra.array_yzx, rb.array_yzx);
+        assert!(ra.array_yzx.iter().all(|idx| (*idx as usize) < 300));
+    }
+    assert_ne!(a.regions[0].array_yzx, a.regions[1].array_yzx);
+    let air = a.regions[0]
+        .array_yzx
+        .iter()
+        .filter(|idx| **idx == 0)
+        .count();
+    assert!(air > 8 * 4 * 6 / 4 && air < 8 * 4 * 6 * 3 / 4);
+
+    let cube = bench_fixtures::cube(16, 1);
+    assert_eq!(cube.shape(), [16, 16, 16]);
+    assert_eq!(cube.total_blocks(false), 0);
+}
+
+#[test]
+fn test_malformed_input_fails_without_panic() {
+    use mc_schem::error::Error;
+    use mc_schem::schem::common;
+
+    // a byte array claiming 2^31-1 bytes
+    let forged_len: Vec<u8> = vec![10, 0, 0, 7, 0, 1, b'a', 0x7F, 0xFF, 0xFF, 0xFF, 0];
+    match common::parse_nbt_compound(&forged_len) {
+        Err(Error::NBTReadErrorAt { position, .. }) => assert!(position <= forged_len.len()),
+        other => panic!("expected NBTReadErrorAt, got {other:?}"),
+    }
+    // compounds nested deeper than 512
+    let mut deep: Vec<u8> = vec![10, 0, 0];
+    for _ in 0..1000 {
+        deep.extend([10, 0, 1, b'a']);
+    }
+    deep.extend(std::iter::repeat(0).take(1001));
+    assert!(common::parse_nbt_compound(&deep).is_err());
+
+    // forged sizes must fail before allocating
+    let mut we12 = HashMap::new();
+    we12.insert("Width".to_string(), Value::Short(-1));
+    we12.insert("Height".to_string(), Value::Short(-1));
+    we12.insert("Length".to_string(), Value::Short(-1));
+    we12.insert("Materials".to_string(), Value::String("Alpha".to_string()));
+    we12.insert(
+        "Blocks".to_string(),
+        Value::ByteArray(fastnbt::ByteArray::new(vec![])),
+    );
+    we12.insert(
+        "Data".to_string(),
+        Value::ByteArray(fastnbt::ByteArray::new(vec![])),
+    );
+    assert!(
+        Schematic::from_world_edit_12_nbt(we12, &mc_schem::WorldEdit12LoadOption::default())
+            .is_err()
+    );
+
+    let mut schem = Schematic::new();
+    schem.regions.push(Region::with_shape([2, 2, 2]));
+    let mut we13 = schem
+        .to_nbt_world_edit_13(&WorldEdit13SaveOption::default())
+        .unwrap();
+    let Some(Value::Compound(we13_body)) = we13.get_mut("Schematic") else {
+        panic!("expected a v3 schem");
+    };
+    for key in ["Width", "Height", "Length"] {
+        we13_body.insert(key.to_string(), Value::Short(i16::MAX));
+    }
+    match Schematic::from_world_edit_13_nbt(we13, &WorldEdit13LoadOption::default()) {
+        Err(Error::BlockDataIncomplete { .. }) => {}
+        other => panic!("expected BlockDataIncomplete, got {:?}", other.err()),
+    }
+
+    let mut structure = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    structure.insert(
+        "size".to_string(),
+        Value::List(vec![Value::Int(i32::MAX); 3]),
+    );
+    let mut option = schem::VanillaStructureLoadOption::default();
+    option.max_volume = 1 << 20;
+    assert!(Schematic::from_vanilla_structure_nbt(structure, &option).is_err());
+
+    let mut lite = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let Some(Value::Compound(regions)) = lite.get_mut("Regions") else {
+        panic!("no regions");
+    };
+    let Some(Value::Compound(region)) = regions.values_mut().next() else {
+        panic!("no region");
+    };
+    let mut size = HashMap::new();
+    for key in ["x", "y", "z"] {
+        size.insert(key.to_string(), Value::Int(i32::MIN));
+    }
+    region.insert("Size".to_string(), Value::Compound(size));
+    assert!(Schematic::from_litematica_nbt(lite, &LitematicaLoadOption::default()).is_err());
+}
+
+#[test]
+fn test_litematica_fixed_index_exceeding_bits() {
+    use mc_schem::error::{ErrorHandleResult, ErrorHandlerFn, Fix};
+
+    let mut schem = Schematic::new();
+    let mut region = Region::with_shape([2, 1, 1]);
+    region
+        .set_block([1, 0, 0], &Block::from_id("stone").unwrap())
+        .unwrap();
+    region
+        .set_block([0, 0, 0], &Block::from_id("glass").unwrap())
+        .unwrap();
+    schem.regions.push(region);
+    let mut nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    // drop glass from the palette, so its index is out of range but still fits in 2 bits
+    {
+        let Some(Value::Compound(regions)) = nbt.get_mut("Regions") else {
+            panic!()
+        };
+        let Some(Value::Compound(reg)) = regions.values_mut().next() else {
+            panic!()
+        };
+        let Some(Value::List(palette)) = reg.get_mut("BlockStatePalette") else {
+            panic!()
+        };
+        assert_eq!(palette.len(), 3);
+        palette.pop();
+    }
+
+    // the fix appends to the palette until the index needs 3 bits
+    let mut option = LitematicaLoadOption::default();
+    option.error_handler = Some(ErrorHandlerFn::new(|_, palette| {
+        while palette.len() < 5 {
+            palette.push(Block::from_id(&format!("wool_{}", palette.len())).unwrap());
+        }
+        return ErrorHandleResult::HandledWithoutWarning(Fix::BlockIndex(4));
+    }));
+    let (loaded, _) = Schematic::from_litematica_nbt(nbt, &option).unwrap();
+    let reg = &loaded.regions[0];
+    assert_eq!(reg.block_at([0, 0, 0]).unwrap().id, "wool_4");
+    assert_eq!(reg.block_at([1, 0, 0]).unwrap().id, "stone");
+}
+
+#[test]
+fn test_par_iter_blocks() {
+    use mc_schem::schem::search::BlockMatcher;
+    use mc_schem::SharedSchematic;
+    use rayon::prelude::*;
+
+    let mut schem = mc_schem::bench_fixtures::cube(13, 7);
+    let mut second = Region::with_shape([3, 2, 5]);
+    second.offset = [20, -4, 1];
+    second.fill_with(&Block::from_id("stone").unwrap());
+    schem.regions.push(second);
+
+    let reg = &schem.regions[0];
+    let sequential: Vec<([i32; 3], &Block)> = reg
+        .array_yzx
+        .indexed_iter()
+        .map(|((y, z, x), idx)| ([x as i32, y as i32, z as i32], &reg.palette[*idx as usize]))
+        .collect();
+    let parallel: Vec<([i32; 3], &Block)> = reg.par_iter_blocks().collect();
+    assert_eq!(parallel, sequential);
+    assert_eq!(reg.par_palette_counts(), reg.palette_counts());
+
+    // multiple threads analyze the same schematic
+    let shared: SharedSchematic = schem.into_shared();
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                return shared
+                    .par_iter_blocks()
+                    .filter(|(_, _, blk)| blk.id == "stone")
+                    .count();
+            })
+        })
+        .collect();
+    let expected = shared
+        .regions
+        .iter()
+        .map(|r| {
+            r.find_blocks(&BlockMatcher::from_id("stone").unwrap())
+                .count()
+        })
+        .sum::<usize>();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), expected);
+    }
+    assert!(shared
+        .par_iter_blocks()
+        .any(|(reg_idx, pos, _)| reg_idx == 1 && pos == [22, -3, 5]));
+}
+
+#[test]
+fn test_selection_clipboard() {
+    use mc_schem::region::BlockBox;
+    use mc_schem::{PasteOption, Selection};
+
+    let sphere = Selection::Sphere {
+        center: [0, 0, 0],
+        radius: 1.0,
+    };
+    assert_eq!(sphere.volume(), 19);
+    let triangle = Selection::Polygon {
+        points: vec![[0, 0], [4, 0], [0, 4]],
+        min_y: 0,
+        max_y: 1,
+    };
+    // 15 blocks on or below the diagonal in each layer
+    assert_eq!(triangle.volume(), 30);
+    assert!(triangle.contains([2, 1, 2]));
+    assert!(!triangle.contains([3, 0, 2]));
+
+    let stone = Block::from_id("stone").unwrap();
+    let dirt = Block::from_id("dirt").unwrap();
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([10, 4, 10]);
+    reg.offset = [-5, 0, -5];
+    reg.fill_with(&stone);
+    reg.set_block([5, 1, 5], &dirt).unwrap();
+    reg.set_block_entity_at([5, 1, 5], BlockEntity::new());
+    schem.regions.push(reg);
+
+    let cube = Selection::Cuboid(BlockBox::new([-1, 0, -1], [1, 2, 1]));
+    let clip = schem.cut(&cube, [0, 1, 0]).unwrap();
+    assert_eq!(clip.region.offset, [-1, -1, -1]);
+    assert_eq!(clip.region.block_at([1, 1, 1]), Some(&dirt));
+    assert!(clip.region.block_entity_at([1, 1, 1]).is_some());
+    assert!(schem.first_block_at([0, 1, 0]).unwrap().is_air());
+    assert!(schem.first_block_entity_at([0, 1, 0]).is_none());
+    assert_eq!(schem.first_block_at([2, 1, 0]), Some(&stone));
+
+    // origin lands at the target, the part out of the region is clipped
+    let pasted = schem
+        .paste(&clip, [3, 3, 3], &PasteOption::default())
+        .unwrap();
+    assert_eq!(pasted, 3 * 2 * 3);
+    assert_eq!(schem.first_block_at([3, 3, 3]), Some(&dirt));
+    assert!(schem.first_block_entity_at([3, 3, 3]).is_some());
+
+    // air is skipped with ignore_air, and blocks out of the sphere are not pasted
+    let holes = schem
+        .copy(
+            &Selection::Cuboid(BlockBox::new([-1, 0, -1], [1, 2, 1])),
+            [0; 3],
+        )
+        .unwrap();
+    let option = PasteOption {
+        ignore_air: true,
+        ..PasteOption::default()
+    };
+    assert_eq!(schem.paste(&holes, [-3, 0, -3], &option).unwrap(), 0);
+    let ball = schem
+        .copy(
+            &Selection::Sphere {
+                center: [3, 2, 3],
+                radius: 1.0,
+            },
+            [3, 2, 3],
+        )
+        .unwrap();
+    assert_eq!(ball.region.shape(), [3, 3, 3]);
+    assert!(ball.region.block_at([0, 0, 0]).unwrap().is_structure_void());
+    assert_eq!(
+        schem
+            .paste(&ball, [-3, 1, -3], &PasteOption::default())
+            .unwrap(),
+        19
+    );
+    assert_eq!(schem.first_block_at([-4, 1, -4]), Some(&stone));
+    assert_eq!(schem.first_block_at([-3, 2, -3]), Some(&dirt));
+}
+
+#[test]
+fn test_block_mask_parse() {
+    use mc_schem::{BlockMatcher, MaskParseError};
+
+    let stone_y = Block::from_id("stone[axis=y]").unwrap();
+    let stone_x = Block::from_id("stone[axis=x]").unwrap();
+    let dirt = Block::from_id("dirt").unwrap();
+    let air = Block::air();
+    let cave_air = Block::from_id("cave_air").unwrap();
+
+    let any: BlockMatcher = "#existing,!minecraft:air,minecraft:stone[axis=y]"
+        .parse()
+        .unwrap();
+    assert!(any.matches(&dirt));
+    assert!(any.matches(&cave_air));
+    assert!(!any.matches(&air));
+
+    // spaces join masks that must all match, commas inside brackets don't split
+    let both = BlockMatcher::parse_mask("#existing  !dirt,stone[axis=y,foo=bar]").unwrap();
+    assert!(both.matches(&stone_y));
+    assert!(!both.matches(&dirt));
+    assert!(!both.matches(&cave_air));
+    let axis = BlockMatcher::parse_mask("stone[axis=y]").unwrap();
+    assert!(axis.matches(&stone_y));
+    assert!(!axis.matches(&stone_x));
+    assert!(BlockMatcher::parse_mask("!!dirt").unwrap().matches(&dirt));
+
+    assert_eq!(
+        BlockMatcher::parse_mask("  ").err(),
+        Some(MaskParseError::EmptyMask)
+    );
+    assert!(matches!(
+        BlockMatcher::parse_mask("stone,,dirt"),
+        Err(MaskParseError::EmptyItem { .. })
+    ));
+    assert!(matches!(
+        BlockMatcher::parse_mask("!"),
+        Err(MaskParseError::EmptyItem { .. })
+    ));
+    assert!(matches!(
+        BlockMatcher::parse_mask("#solid"),
+        Err(MaskParseError::UnknownTag { .. })
+    ));
+    assert!(matches!(
+        BlockMatcher::parse_mask("stone[axis"),
+        Err(MaskParseError::InvalidBlock { .. })
+    ));
+}
+
+#[test]
+fn test_pattern_fill_replace() {
+    use mc_schem::schem::pattern::PatternItem;
+    use mc_schem::{BlockMatcher, Pattern, PatternParseError};
+
+    let pattern: Pattern = "50%stone, 50%cobblestone".parse().unwrap();
+    assert_eq!(pattern.items.len(), 2);
+    assert_eq!(pattern.items[0].0, 50.0);
+    let keep = Pattern::parse("*oak_planks,2%^birch_stairs[half=top]").unwrap();
+    assert!(matches!(keep.items[0], (w, PatternItem::KeepProperties(_)) if w == 1.0));
+    assert!(matches!(keep.items[1], (w, PatternItem::KeepProperties(_)) if w == 2.0));
+
+    assert_eq!(
+        Pattern::parse(" ").err(),
+        Some(PatternParseError::EmptyPattern)
+    );
+    assert!(matches!(
+        Pattern::parse("stone,"),
+        Err(PatternParseError::EmptyItem { .. })
+    ));
+    assert!(matches!(
+        Pattern::parse("-5%stone"),
+        Err(PatternParseError::InvalidWeight { .. })
+    ));
+    assert!(matches!(
+        Pattern::parse("50%Stone"),
+        Err(PatternParseError::InvalidBlock { .. })
+    ));
+
+    // the same seed gives the same blocks
+    let mut a = Region::with_shape([16, 4, 16]);
+    let mut b = a.clone();
+    assert_eq!(a.fill_with_pattern(&pattern, 42).unwrap(), 1024);
+    b.fill_with_pattern(&pattern, 42).unwrap();
+    assert_eq!(a.array_yzx, b.array_yzx);
+    assert_eq!(a.palette, b.palette);
+    let stone = Block::from_id("stone").unwrap();
+    let stone_count = a
+        .array_yzx
+        .iter()
+        .filter(|idx| a.palette[**idx as usize] == stone)
+        .count();
+    assert!(stone_count > 400 && stone_count < 624, "{stone_count}");
+
+    // properties of replaced blocks are kept
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([2, 1, 1]);
+    reg.set_block(
+        [0, 0, 0],
+        &Block::from_id("oak_stairs[facing=east,half=bottom]").unwrap(),
+    )
+    .unwrap();
+    reg.set_block([1, 0, 0], &Block::from_id("dirt").unwrap())
+        .unwrap();
+    schem.regions.push(reg);
+    let matcher = BlockMatcher::parse_mask("oak_stairs").unwrap();
+    let replaced = schem
+        .replace_with_pattern(
+            Some(&matcher),
+            &Pattern::parse("^birch_stairs[half=top]").unwrap(),
+            0,
+        )
+        .unwrap();
+    assert_eq!(replaced, 1);
+    assert_eq!(
+        schem.first_block_at([0, 0, 0]),
+        Some(&Block::from_id("birch_stairs[facing=east,half=top]").unwrap())
+    );
+    assert_eq!(schem.first_block_at([1, 0, 0]).unwrap().id, "dirt");
+}
+
+#[test]
+fn test_scatter() {
+    use mc_schem::{BlockMatcher, Pattern};
+
+    let grass = Block::from_id("grass_block").unwrap();
+    let mut reg = Region::with_shape([32, 3, 32]);
+    reg.draw_cuboid([0, 0, 0], [31, 0, 31], &grass, false);
+    // a stone in the grass layer, and a block on top of the grass
+    reg.set_block([0, 0, 0], &Block::from_id("stone").unwrap())
+        .unwrap();
+    reg.set_block([1, 1, 0], &Block::from_id("dirt").unwrap())
+        .unwrap();
+    let surface = BlockMatcher::parse_mask("grass_block").unwrap();
+    let flowers = Pattern::parse("poppy,dandelion").unwrap();
+
+    let mut again = reg.clone();
+    let placed = reg.scatter(&surface, &flowers, 0.25, 7).unwrap();
+    assert_eq!(again.scatter(&surface, &flowers, 0.25, 7).unwrap(), placed);
+    assert_eq!(reg.array_yzx, again.array_yzx);
+    assert!(placed > 150 && placed < 360, "{placed}");
+    for x in 0..32 {
+        for z in 0..32 {
+            let above = reg.block_at([x, 1, z]).unwrap();
+            assert!(reg.block_at([x, 2, z]).unwrap().is_air());
+            if x == 0 && z == 0 {
+                assert!(above.is_air());
+            }
+        }
+    }
+    assert_eq!(reg.block_at([1, 1, 0]).unwrap().id, "dirt");
+
+    let mut full = Region::with_shape([4, 2, 4]);
+    full.draw_cuboid([0, 0, 0], [3, 0, 3], &grass, false);
+    assert_eq!(full.scatter(&surface, &flowers, 1.0, 0).unwrap(), 16);
+    assert_eq!(full.scatter(&surface, &flowers, 1.0, 0).unwrap(), 0);
+    assert!(full.scatter(&surface, &flowers, 1.5, 0).is_err());
+    assert!(full.scatter(&surface, &flowers, f64::NAN, 0).is_err());
+}
+
+#[test]
+fn test_heightmap() {
+    use mc_schem::HeightmapKind;
+
+    let mut reg = Region::with_shape([3, 6, 2]);
+    let stone = Block::from_id("stone").unwrap();
+    reg.draw_cuboid([0, 0, 0], [2, 0, 1], &stone, false);
+    reg.set_block([0, 1, 0], &Block::from_id("poppy").unwrap())
+        .unwrap();
+    reg.set_block([1, 1, 0], &Block::from_id("water").unwrap())
+        .unwrap();
+    reg.set_block([2, 3, 0], &Block::from_id("oak_leaves").unwrap())
+        .unwrap();
+    reg.set_block([0, 4, 1], &Block::structure_void()).unwrap();
+
+    let surface = reg.heightmap(HeightmapKind::WorldSurface);
+    assert_eq!(surface.dim(), (2, 3));
+    assert_eq!(surface[[0, 0]], 2);
+    assert_eq!(surface[[0, 1]], 2);
+    assert_eq!(surface[[0, 2]], 4);
+    assert_eq!(surface[[1, 0]], 1);
+    let motion = reg.heightmap(HeightmapKind::MotionBlocking);
+    assert_eq!(motion[[0, 0]], 1);
+    assert_eq!(motion[[0, 1]], 2);
+    assert_eq!(motion[[0, 2]], 4);
+    assert_eq!(
+        reg.heightmap(HeightmapKind::MotionBlockingNoLeaves)[[0, 2]],
+        1
+    );
+    assert_eq!(reg.heightmap(HeightmapKind::OceanFloor)[[0, 1]], 1);
+    assert_eq!(
+        "MOTION_BLOCKING_NO_LEAVES"
+            .parse::<HeightmapKind>()
+            .unwrap(),
+        HeightmapKind::MotionBlockingNoLeaves
+    );
+
+    let image = reg.heightmap_image(HeightmapKind::WorldSurface);
+    assert_eq!((image.width, image.height), (3, 2));
+    assert_eq!(image.pixel(2, 0), Some([170, 170, 170, 255]));
+    assert_eq!(image.pixel(0, 1), Some([42, 42, 42, 255]));
+    let mut png = Vec::new();
+    image.write_png(&mut png).unwrap();
+    assert!(png.starts_with(b"\x89PNG"));
+}
+
+#[test]
+fn test_terrain_from_heightmap() {
+    use mc_schem::heightmap::{heightmap_to_image, terrain_from_heights, terrain_from_image};
+    use mc_schem::{HeightmapKind, RgbaImage, TerrainOption};
+    use ndarray::Array2;
+
+    let heights = Array2::from_shape_fn((5, 7), |(z, x)| (x * 3 + z) as f64);
+    let option = TerrainOption {
+        base_height: 2,
+        ..TerrainOption::default()
+    };
+    let terrain = terrain_from_heights(&heights, &option).unwrap();
+    assert_eq!(terrain.shape(), [7, 24, 5]);
+    assert_eq!(terrain.block_at([0, 1, 0]).unwrap().id, "grass_block");
+    assert!(terrain.block_at([0, 2, 0]).unwrap().is_air());
+    assert_eq!(terrain.block_at([6, 23, 4]).unwrap().id, "grass_block");
+    assert_eq!(terrain.block_at([6, 20, 4]).unwrap().id, "dirt");
+    assert_eq!(terrain.block_at([6, 19, 4]).unwrap().id, "stone");
+    let map = terrain.heightmap(HeightmapKind::WorldSurface);
+    assert_eq!(map[[4, 6]], 24);
+
+    // export as png, decode it and build the same terrain again
+    let image = heightmap_to_image(&map, 255);
+    let mut png = Vec::new();
+    image.write_png(&mut png).unwrap();
+    let decoded = RgbaImage::from_png(&png).unwrap();
+    assert_eq!(decoded, image);
+    let option = TerrainOption {
+        base_height: 0,
+        ..TerrainOption::default()
+    };
+    let rebuilt = terrain_from_image(&decoded, &option).unwrap();
+    assert_eq!(rebuilt.array_yzx, terrain.array_yzx);
+
+    assert!(RgbaImage::from_png(&png[..png.len() / 2]).is_err());
+    let bad_scale = TerrainOption {
+        vertical_scale: f64::NAN,
+        ..TerrainOption::default()
+    };
+    assert!(terrain_from_heights(&heights, &bad_scale).is_err());
+    let too_high = TerrainOption {
+        vertical_scale: 1000.0,
+        ..TerrainOption::default()
+    };
+    assert!(terrain_from_heights(&heights, &too_high).is_err());
+}
+
+#[test]
+fn test_block_color_table() {
+    use mc_schem::{BlockColorTable, MapColor, TopDownRenderOption};
+    use std::sync::Arc;
+
+    let old = BlockColorTable::vanilla(DataVersion::Java_1_16 as i32);
+    let new = BlockColorTable::vanilla(DataVersion::Java_1_20 as i32);
+    let deepslate = Block::from_id("deepslate").unwrap();
+    let stone = Block::from_id("stone").unwrap();
+    assert!(old.color_of(&deepslate).is_none());
+    assert!(new.color_of(&deepslate).is_some());
+    assert!(new.len() > old.len());
+    assert_eq!(old.color_of(&stone), new.color_of(&stone));
+
+    let mut table = BlockColorTable::new();
+    table.set("stone", [1, 2, 3]);
+    table.set("mymod:ore", [4, 5, 6]);
+    let mut ore = Block::from_id("mymod:ore").unwrap();
+    assert_eq!(table.color_of(&ore), Some([4, 5, 6]));
+    ore.namespace = String::new();
+    assert_eq!(table.color_of(&ore), None);
+
+    let schem = mc_schem::SchematicBuilder::new()
+        .with_size([2, 1, 1])
+        .set([0, 0, 0], &stone)
+        .set([1, 0, 0], &Block::from_id("dirt").unwrap())
+        .build();
+    let option = TopDownRenderOption {
+        shade_by_height: false,
+        block_colors: Some(Arc::new(table)),
+        ..Default::default()
+    };
+    let image = schem.render_top_down(&option);
+    let flat = |rgb: [u8; 3]| {
+        let c = rgb.map(|c| (c as u32 * 220 / 255) as u8);
+        Some([c[0], c[1], c[2], 255])
+    };
+    assert_eq!(image.pixel(0, 0), flat([1, 2, 3]));
+    // blocks not in the table keep map colors
+    assert_eq!(image.pixel(1, 0), flat(MapColor::Dirt.rgb()));
+}
+
+#[test]
+fn test_structure_palette_variants() {
+    use mc_schem::schem::VanillaStructureLoadOption;
+    let stone = Block::from_id("stone").unwrap();
+    let dirt = Block::from_id("dirt").unwrap();
+    let oak = Block::from_id("oak_planks").unwrap();
+    let birch = Block::from_id("birch_planks").unwrap();
+    let spruce = Block::from_id("spruce_planks").unwrap();
+
+    let mut region = Region::with_shape([3, 1, 1]);
+    region.set_block([0, 0, 0], &stone).unwrap();
+    region.set_block([1, 0, 0], &oak).unwrap();
+    region.set_block([2, 0, 0], &dirt).unwrap();
+    // dirt is removed from the palette, stone becomes the same in all variants
+    region.set_block([2, 0, 0], &stone).unwrap();
+    let mut birch_variant = region.palette.clone();
+    let mut spruce_variant = region.palette.clone();
+    let oak_idx = region.find_in_palette(&oak).unwrap() as usize;
+    birch_variant[oak_idx] = birch.clone();
+    spruce_variant[oak_idx] = spruce.clone();
+    region.alternate_palettes = vec![birch_variant, spruce_variant];
+    region.shrink_palette().unwrap();
+    assert_eq!(region.palette_variant_count(), 3);
+    assert!(region
+        .alternate_palettes
+        .iter()
+        .all(|v| v.len() == region.palette.len()));
+
+    region.select_palette_variant(2).unwrap();
+    assert_eq!(region.block_at([1, 0, 0]), Some(&spruce));
+    region.select_palette_variant(2).unwrap();
+    assert_eq!(region.block_at([1, 0, 0]), Some(&oak));
+    assert!(region.select_palette_variant(3).is_err());
+
+    let mut schem = Schematic::new();
+    schem.regions.push(region);
+
+    let nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    assert!(!nbt.contains_key("palette"));
+    let Some(Value::List(palettes)) = nbt.get("palettes") else {
+        panic!("palettes is not saved");
+    };
+    assert_eq!(palettes.len(), 3);
+
+    let (loaded, _) = Schematic::from_vanilla_structure_nbt(
+        nbt.clone(),
+        &VanillaStructureLoadOption {
+            palette_variant: 1,
+            ..VanillaStructureLoadOption::default()
+        },
+    )
+    .unwrap();
+    let reg = &loaded.regions[0];
+    assert_eq!(reg.palette_variant_count(), 3);
+    assert_eq!(reg.block_at([0, 0, 0]), Some(&stone));
+    assert_eq!(reg.block_at([1, 0, 0]), Some(&birch));
+    assert_eq!(reg.block_at([2, 0, 0]), Some(&stone));
+
+    let out_of_range = Schematic::from_vanilla_structure_nbt(
+        nbt,
+        &VanillaStructureLoadOption {
+            palette_variant: 3,
+            ..VanillaStructureLoadOption::default()
+        },
+    );
+    assert!(out_of_range.is_err());
+
+    let single = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption {
+            palette_variant: Some(2),
+            ..VanillaStructureSaveOption::default()
+        })
+        .unwrap();
+    assert!(!single.contains_key("palettes"));
+    let (loaded, _) =
+        Schematic::from_vanilla_structure_nbt(single, &VanillaStructureLoadOption::default())
+            .unwrap();
+    assert_eq!(loaded.regions[0].palette_variant_count(), 1);
+    assert_eq!(loaded.first_block_at([1, 0, 0]), Some(&spruce));
+}
+
+#[test]
+fn test_jigsaw_and_structure_block() {
+    use mc_schem::block_entity::{JigsawData, JigsawJoint, StructureBlockData, StructureBlockMode};
+    use mc_schem::transform::Rotation;
+
+    let mut region = Region::with_shape([2, 2, 1]);
+    let data = JigsawData {
+        name: "minecraft:bottom".to_string(),
+        target: "minecraft:top".to_string(),
+        pool: "minecraft:village/plains/houses".to_string(),
+        joint: JigsawJoint::Aligned,
+        placement_priority: 2,
+        ..JigsawData::default()
+    };
+    region.set_jigsaw([1, 0, 0], "down_north", &data).unwrap();
+    region
+        .set_jigsaw([0, 1, 0], "up_south", &JigsawData::default())
+        .unwrap();
+    assert!(region
+        .set_jigsaw([0, 0, 0], "north_down", &JigsawData::default())
+        .is_err());
+    assert!(region
+        .set_jigsaw([5, 0, 0], "north_up", &JigsawData::default())
+        .is_err());
+
+    let jigsaws = region.jigsaws().unwrap();
+    assert_eq!(jigsaws.len(), 2);
+    assert_eq!(jigsaws[0], ([1, 0, 0], data));
+    assert_eq!(jigsaws[1].1, JigsawData::default());
+    assert_eq!(
+        region.block_at([1, 0, 0]).unwrap().attributes["orientation"],
+        "down_north"
+    );
+    let be = &region.block_entities[&[1, 0, 0]];
+    assert_eq!(be.tags["joint"], Value::String("aligned".to_string()));
+
+    let mut old = BlockEntity::new();
+    old.tags.insert(
+        "attachement_type".to_string(),
+        Value::String("minecraft:street".to_string()),
+    );
+    old.tags.insert(
+        "target_pool".to_string(),
+        Value::String("minecraft:village/plains/streets".to_string()),
+    );
+    let parsed = JigsawData::from_block_entity(&old).unwrap();
+    assert_eq!(parsed.target, "minecraft:street");
+    assert_eq!(parsed.pool, "minecraft:village/plains/streets");
+
+    let structure = StructureBlockData {
+        name: "minecraft:house".to_string(),
+        metadata: "chest".to_string(),
+        size: [5, 4, 6],
+        rotation: Rotation::Clockwise90,
+        mode: StructureBlockMode::Load,
+        integrity: 0.5,
+        ..StructureBlockData::default()
+    };
+    let mut be = BlockEntity::new();
+    structure.write_to(&mut be);
+    assert_eq!(be.tags["mode"], Value::String("LOAD".to_string()));
+    assert_eq!(
+        be.tags["rotation"],
+        Value::String("CLOCKWISE_90".to_string())
+    );
+    assert_eq!(
+        StructureBlockData::from_block_entity(&be).unwrap(),
+        structure
+    );
+    assert_eq!(structure.block().attributes["mode"], "load");
+
+    be.tags
+        .insert("mode".to_string(), Value::String("BUILD".to_string()));
+    assert!(StructureBlockData::from_block_entity(&be).is_err());
+}
+
+#[test]
+fn test_regenerate_entity_uuids_on_paste() {
+    use mc_schem::region::{BlockBox, Entity};
+    use mc_schem::schem::entity_uuid::entity_uuid;
+    use mc_schem::{PasteOption, Selection};
+
+    let uuid_tag = |ints: [i32; 4]| Value::IntArray(fastnbt::IntArray::new(ints.to_vec()));
+    let mut region = Region::with_shape([8, 2, 8]);
+
+    let mut horse = Entity::with_position([1.5, 0.0, 1.5]);
+    horse
+        .tags
+        .insert("UUID".to_string(), uuid_tag([1, 2, 3, 4]));
+    let mut rider = HashMap::new();
+    rider.insert("UUIDMost".to_string(), Value::Long(11));
+    rider.insert("UUIDLeast".to_string(), Value::Long(12));
+    horse.tags.insert(
+        "Passengers".to_string(),
+        Value::List(vec![Value::Compound(rider)]),
+    );
+    let mut wolf = Entity::with_position([2.5, 0.0, 2.5]);
+    wolf.tags.insert("UUID".to_string(), uuid_tag([5, 6, 7, 8]));
+    let mut leash = HashMap::new();
+    leash.insert("UUID".to_string(), uuid_tag([1, 2, 3, 4]));
+    wolf.tags
+        .insert("Leash".to_string(), Value::Compound(leash));
+    // not copied, so the reference is kept
+    wolf.tags
+        .insert("Owner".to_string(), uuid_tag([9, 9, 9, 9]));
+    region.entities.push(horse);
+    region.entities.push(wolf);
+    let mut schem = Schematic::new();
+    schem.regions.push(region);
+
+    let clipboard = schem
+        .copy(
+            &Selection::Cuboid(BlockBox::new([0, 0, 0], [3, 1, 3])),
+            [0; 3],
+        )
+        .unwrap();
+    let option = PasteOption {
+        regenerate_uuids: true,
+        ..PasteOption::default()
+    };
+    schem.paste(&clipboard, [4, 0, 4], &option).unwrap();
+    schem.paste(&clipboard, [4, 0, 0], &option).unwrap();
+    let entities = &schem.regions[0].entities;
+    assert_eq!(entities.len(), 6);
+
+    let mut uuids: Vec<u128> = entities
+        .iter()
+        .map(|e| entity_uuid(&e.tags).unwrap())
+        .collect();
+    uuids.sort();
+    uuids.dedup();
+    assert_eq!(uuids.len(), 6);
+
+    for pair in entities[2..].chunks(2) {
+        let (horse, wolf) = (&pair[0], &pair[1]);
+        let Some(Value::Compound(leash)) = wolf.tags.get("Leash") else {
+            panic!("leash is removed");
+        };
+        assert_eq!(entity_uuid(leash), entity_uuid(&horse.tags));
+        assert_ne!(entity_uuid(leash), entity_uuid(&entities[0].tags));
+        assert_eq!(wolf.tags["Owner"], uuid_tag([9, 9, 9, 9]));
+        let Some(Value::List(passengers)) = horse.tags.get("Passengers") else {
+            panic!("passengers are removed");
+        };
+        let Value::Compound(rider) = &passengers[0] else {
+            panic!("passenger is not a compound");
+        };
+        assert_ne!(entity_uuid(rider), Some((11 << 64) | 12));
+    }
+
+    let mut copy = schem.clone();
+    assert_eq!(copy.regenerate_entity_uuids(7), 9);
+    let mut again = schem.clone();
+    again.regenerate_entity_uuids(7);
+    assert_eq!(
+        entity_uuid(&copy.regions[0].entities[3].tags),
+        entity_uuid(&again.regions[0].entities[3].tags)
+    );
+}
+
+#[test]
+fn test_entity_passengers() {
+    use mc_schem::region::Entity;
+    use mc_schem::schem::VanillaStructureLoadOption;
+    use mc_schem::transform::{Mirror, Rotation};
+
+    let pos_tag = |pos: [f64; 3]| Value::List(pos.iter().map(|v| Value::Double(*v)).collect());
+    let mut skeleton = HashMap::new();
+    skeleton.insert(
+        "id".to_string(),
+        Value::String("minecraft:skeleton".to_string()),
+    );
+    skeleton.insert("Pos".to_string(), pos_tag([100.5, 65.0, 200.5]));
+    let mut spider = HashMap::new();
+    spider.insert(
+        "id".to_string(),
+        Value::String("minecraft:spider".to_string()),
+    );
+    spider.insert("Pos".to_string(), pos_tag([100.5, 64.0, 200.5]));
+    spider.insert(
+        "Passengers".to_string(),
+        Value::List(vec![Value::Compound(skeleton)]),
+    );
+    let mut boat = Entity::with_position([0.5, 0.0, 0.5]);
+    boat.tags.insert(
+        "id".to_string(),
+        Value::String("minecraft:boat".to_string()),
+    );
+    boat.tags.insert(
+        "Passengers".to_string(),
+        Value::List(vec![Value::Compound(spider)]),
+    );
+
+    let mut region = Region::with_shape([4, 2, 2]);
+    region.entities.push(boat);
+    let mut schem = Schematic::new();
+    schem.regions.push(region);
+    let nbt = schem
+        .to_nbt_vanilla_structure(&VanillaStructureSaveOption::default())
+        .unwrap();
+    let (mut loaded, _) =
+        Schematic::from_vanilla_structure_nbt(nbt, &VanillaStructureLoadOption::default()).unwrap();
+
+    let boat = &loaded.regions[0].entities[0];
+    assert!(!boat.tags.contains_key("Passengers"));
+    assert_eq!(boat.passenger_count(), 2);
+    let spider = &boat.passengers[0];
+    assert_eq!(spider.position, [100.5, 64.0, 200.5]);
+    assert_eq!(spider.passengers[0].block_pos, [100, 65, 200]);
+
+    // put riders on the boat, then everything moves together
+    let region = &mut loaded.regions[0];
+    region.entities[0].passengers[0].set_position([0.5, 0.5, 0.5]);
+    region.entities[0].passengers[0].passengers[0].set_position([0.5, 1.5, 0.5]);
+    region.transform_entities(Rotation::Clockwise90, Mirror::None);
+    let boat = &region.entities[0];
+    let spider = &boat.passengers[0];
+    let skeleton = &spider.passengers[0];
+    assert_eq!(spider.position[0], boat.position[0]);
+    assert_eq!(spider.position[2], boat.position[2]);
+    assert_eq!(skeleton.position[0], boat.position[0]);
+    assert_eq!(skeleton.position[1], 1.5);
+
+    region.entities[0].pos_shift([1, 0, 0]);
+    let expected = region.entities[0].passengers[0].passengers[0].position;
+    let tags = region.entities[0].tags_with_passengers([10.0, 0.0, 0.0]);
+    let Some(Value::List(spiders)) = tags.get("Passengers") else {
+        panic!("passengers are not written");
+    };
+    let Value::Compound(spider) = &spiders[0] else {
+        panic!("passenger is not a compound");
+    };
+    let Some(Value::List(skeletons)) = spider.get("Passengers") else {
+        panic!("nested passengers are not written");
+    };
+    let Value::Compound(skeleton) = &skeletons[0] else {
+        panic!("passenger is not a compound");
+    };
+    assert_eq!(
+        skeleton["Pos"],
+        pos_tag([expected[0] + 10.0, expected[1], expected[2]])
+    );
+}
+
+#[test]
+fn test_schematic_view() {
+    let stone = Block::from_id("stone").unwrap();
+    let glass = Block::from_id("glass").unwrap();
+    let mut first = Region::with_shape([2, 1, 1]);
+    first.set_block([1, 0, 0], &stone).unwrap();
+    let mut second = Region::with_shape([2, 1, 1]);
+    second.offset = [2, 0, 0];
+    second.set_block([0, 0, 0], &glass).unwrap();
+    second.set_block([1, 0, 0], &stone).unwrap();
+    let mut schem = Schematic::new();
+    schem.regions.push(first);
+    schem.regions.push(second);
+
+    let shared = schem.clone().into_shared();
+    let view = shared.view();
+    assert_eq!(view.palette().len(), 3);
+    assert_eq!(view.positions_of(&stone), &[[1, 0, 0], [3, 0, 0]]);
+    assert_eq!(view.count_of(&Block::air()), 1);
+    assert_eq!(view.count_of(&Block::from_id("dirt").unwrap()), 0);
+    assert_eq!(view.block_at([2, 0, 0]), Some(&glass));
+    assert_eq!(view.block_at([4, 0, 0]), None);
+    assert_eq!(view.index_at([3, 0, 0]), view.index_of(&stone),);
+    let total: usize = view.block_counts().map(|(_, c)| c).sum();
+    assert_eq!(total, 4);
+
+    // the hash only depends on the set of blocks
+    let mut reordered = schem.clone();
+    reordered.regions.reverse();
+    assert_eq!(reordered.view().palette_hash(), view.palette_hash());
+    reordered.regions[0].set_block([0, 0, 0], &stone).unwrap();
+    reordered.regions[0].shrink_palette().unwrap();
+    assert_ne!(reordered.view().palette_hash(), view.palette_hash());
+
+    let counts: Vec<usize> = std::thread::scope(|s| {
+        let handles: Vec<_> = [&stone, &glass]
+            .iter()
+            .map(|blk| s.spawn(|| view.count_of(blk)))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(counts, vec![2, 1]);
+}
+
+#[test]
+fn test_world_height() {
+    use mc_schem::schem::mcfunction::McFunctionExportOption;
+    use mc_schem::{Error, WorldHeight};
+
+    assert_eq!(WorldHeight::OVERWORLD.max_y(), 319);
+    assert_eq!(WorldHeight::LEGACY.max_y(), 255);
+    assert_eq!(
+        WorldHeight::overworld_of(DataVersion::Java_1_20 as i32),
+        WorldHeight::OVERWORLD
+    );
+    assert_eq!(
+        WorldHeight::overworld_of(DataVersion::Java_1_12_2 as i32),
+        WorldHeight::LEGACY
+    );
+
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([2, 4, 2]);
+    reg.set_block([0, 0, 0], &Block::from_id("stone").unwrap())
+        .unwrap();
+    reg.offset = [0, -3, 0];
+    schem.regions.push(reg);
+    let b = schem.bounding_box().unwrap();
+    assert_eq!(b.lower, [0, -3, 0]);
+    assert_eq!(b.upper, [1, 0, 1]);
+
+    // a deep build at the bottom of a 1.18+ world
+    schem
+        .check_world_height(-61, &WorldHeight::OVERWORLD)
+        .unwrap();
+    assert!(schem
+        .check_world_height(-62, &WorldHeight::OVERWORLD)
+        .is_err());
+    match schem.check_world_height(-10, &WorldHeight::LEGACY) {
+        Err(Error::OutOfWorldHeight {
+            y_range,
+            world_y_range,
+        }) => {
+            assert_eq!(y_range, [-13, -10]);
+            assert_eq!(world_y_range, [0, 255]);
+        }
+        other => panic!("Expected OutOfWorldHeight, got {other:?}"),
+    }
+
+    let mut option = McFunctionExportOption {
+        origin: Some([0, -40, 0]),
+        world_height: Some(WorldHeight::OVERWORLD),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    schem.export_mcfunction(&mut out, &option).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .starts_with("setblock 0 -43 0 stone"));
+    option.world_height = Some(WorldHeight::LEGACY);
+    let mut out = Vec::new();
+    assert!(schem.export_mcfunction(&mut out, &option).is_err());
+    assert!(out.is_empty());
+}
+
+#[test]
+fn test_litematica_bits_per_block_small_region() {
+    let stone = Block::from_id("stone").unwrap();
+    let mut schem = Schematic::new();
+    let mut reg = Region::with_shape([10, 1, 1]);
+    reg.name = "small".to_string();
+    for x in [1, 2, 5, 9] {
+        reg.set_block([x, 0, 0], &stone).unwrap();
+    }
+    schem.regions.push(reg);
+
+    // 5 bits of 10 blocks take one long, just like 2 bits, so the loader couldn't tell them apart
+    let option = LitematicaSaveOption {
+        bits_per_block: schem::BitsPerBlock::Forced(5),
+        ..LitematicaSaveOption::default()
+    };
+    assert!(schem.to_nbt_litematica(&option).is_err());
+
+    let nbt = schem
+        .to_nbt_litematica(&LitematicaSaveOption::default())
+        .unwrap();
+    let loaded = Schematic::from_litematica_nbt(nbt.clone(), &LitematicaLoadOption::default())
+        .unwrap()
+        .0;
+    assert_eq!(loaded.regions[0].array_yzx, schem.regions[0].array_yzx);
+
+    // older mc_schem saved 2-block palettes with 1 bit per block
+    let mut nbt = nbt;
+    let Some(Value::Compound(regions)) = nbt.get_mut("Regions") else {
+        panic!("no Regions");
+    };
+    let Some(Value::Compound(region)) = regions.get_mut("small") else {
+        panic!("no region");
+    };
+    let mut packed = 0i64;
+    for x in 0..10 {
+        if schem.regions[0].block_at([x, 0, 0]) == Some(&stone) {
+            packed |= 1 << x;
+        }
+    }
+    region.insert(
+        "BlockStates".to_string(),
+        Value::LongArray(fastnbt::LongArray::new(vec![packed])),
+    );
+    let loaded = Schematic::from_litematica_nbt(nbt, &LitematicaLoadOption::default())
+        .unwrap()
+        .0;
+    assert_eq!(loaded.regions[0].array_yzx, schem.regions[0].array_yzx);
+}
+
 // #[test]
 // fn check_mca() {
 //     let filename = "F:\\Users\\Joseph\\Documents\\Games\\Minecraft\\PCL2\\.minecraft\\versions\\1.20.2-Fabric 0.15.6\\saves\\New World\\region\\r.0.0.mca";
@@ -1146,3 +6754,68 @@ fn test_3d_array_order() {
 //         println!("\t{key}");
 //     }
 // }
+
+#[cfg(feature = "resource_pack")]
+#[test]
+fn test_block_color_from_resource_pack() {
+    use mc_schem::{BlockColorTable, RgbaImage};
+    use std::io::Write;
+
+    let texture = |pixels: &[[u8; 4]], width: u32| {
+        let mut image = RgbaImage::new(width, pixels.len() as u32 / width, [0; 4]);
+        for (idx, p) in pixels.iter().enumerate() {
+            image.set_pixel(idx as u32 % width, idx as u32 / width, *p);
+        }
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        return png;
+    };
+    let mut zip_bytes = Vec::new();
+    {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(&mut zip_bytes));
+        let options = zip::write::FileOptions::default();
+        let mut add = |name: &str, data: &[u8]| {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        };
+        add("pack.mcmeta", b"{}");
+        // half transparent pixels don't count
+        add(
+            "assets/minecraft/textures/block/stone.png",
+            &texture(&[[100, 0, 0, 255], [200, 0, 0, 255], [0, 255, 0, 0]], 3),
+        );
+        // the top texture wins, and the second frame of animations is skipped
+        add(
+            "assets/minecraft/textures/block/oak_log.png",
+            &texture(&[[9, 9, 9, 255]], 1),
+        );
+        add(
+            "assets/minecraft/textures/block/oak_log_top.png",
+            &texture(&[[10, 20, 30, 255], [0, 0, 0, 255]], 1),
+        );
+        add(
+            "assets/mymod/textures/block/ore.png",
+            &texture(&[[7, 8, 9, 255]], 1),
+        );
+        add("assets/minecraft/textures/block/broken.png", b"not a png");
+        writer.finish().unwrap();
+    }
+
+    let mut table = BlockColorTable::vanilla(DataVersion::Java_1_20 as i32);
+    let before = table.len();
+    assert_eq!(table.apply_resource_pack(&zip_bytes).unwrap(), 3);
+    assert_eq!(table.len(), before + 1);
+    assert_eq!(
+        table.color_of(&Block::from_id("stone").unwrap()),
+        Some([150, 0, 0])
+    );
+    assert_eq!(
+        table.color_of(&Block::from_id("oak_log").unwrap()),
+        Some([10, 20, 30])
+    );
+    assert_eq!(
+        table.color_of(&Block::from_id("mymod:ore").unwrap()),
+        Some([7, 8, 9])
+    );
+    assert!(table.apply_resource_pack(b"not a zip").is_err());
+}